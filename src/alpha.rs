@@ -0,0 +1,215 @@
+use gltf::json::{Index, Root, Texture, material::AlphaMode, validation::Checked};
+use image::{DynamicImage, RgbaImage};
+
+/// Image `extras` key recording the alpha convention of a re-encoded base color texture
+pub(crate) const ALPHA_PREMULTIPLIED_KEY: &str = "alphaPremultiplied";
+
+/// Alpha convention to convert base color textures to while re-encoding
+///
+/// glTF specifies straight alpha, but some engines sample premultiplied textures directly.
+/// Converted images carry `"alphaPremultiplied": true|false` in their `extras`, and
+/// [`gpu_stats`](crate::gpu_stats) reports it per texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AlphaConversion {
+    /// Multiply color by alpha
+    Premultiply,
+    /// Divide color by alpha, restoring straight alpha from premultiplied sources
+    Unpremultiply,
+}
+
+impl AlphaConversion {
+    /// Whether output textures end up premultiplied
+    pub fn premultiplied(&self) -> bool {
+        matches!(self, AlphaConversion::Premultiply)
+    }
+}
+
+/// Convert the color channels of an RGBA image in place
+pub(crate) fn convert_alpha(img: &mut RgbaImage, conversion: AlphaConversion) {
+    for p in img.pixels_mut() {
+        let alpha = p.0[3] as u32;
+        for c in &mut p.0[..3] {
+            *c = match conversion {
+                AlphaConversion::Premultiply => ((*c as u32 * alpha + 127) / 255) as u8,
+                AlphaConversion::Unpremultiply if alpha == 0 => 0,
+                AlphaConversion::Unpremultiply => {
+                    ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8
+                }
+            };
+        }
+    }
+}
+
+/// Image `extras` key set when an unused alpha channel was dropped
+pub(crate) const ALPHA_PRUNED_KEY: &str = "alphaPruned";
+
+/// Whether every material sampling `texture` as base color is `OPAQUE`
+pub(crate) fn opaque_base_color(o_json: &Root, texture: Index<Texture>) -> bool {
+    o_json
+        .materials
+        .iter()
+        .filter(|mat| {
+            mat.pbr_metallic_roughness
+                .base_color_texture
+                .as_ref()
+                .is_some_and(|info| info.index == texture)
+        })
+        .all(|mat| mat.alpha_mode == Checked::Valid(AlphaMode::Opaque))
+}
+
+/// Copy of the image without its alpha channel, when that channel is uniformly 255
+pub(crate) fn prune_opaque_alpha(img: &DynamicImage) -> Option<DynamicImage> {
+    if !img.color().has_alpha() {
+        return None;
+    }
+    let rgba = img.to_rgba8();
+    if rgba.pixels().any(|p| p.0[3] != u8::MAX) {
+        return None;
+    }
+    Some(DynamicImage::ImageRgb8(
+        DynamicImage::ImageRgba8(rgba).to_rgb8(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_conversions_round_trip() {
+        let source = RgbaImage::from_raw(
+            3,
+            1,
+            vec![200, 100, 50, 128, 255, 255, 255, 255, 90, 90, 90, 0],
+        )
+        .unwrap();
+        let mut img = source.clone();
+        convert_alpha(&mut img, AlphaConversion::Premultiply);
+        assert_eq!(
+            img.as_raw(),
+            &[100, 50, 25, 128, 255, 255, 255, 255, 0, 0, 0, 0]
+        );
+        convert_alpha(&mut img, AlphaConversion::Unpremultiply);
+        assert_eq!(img.get_pixel(0, 0).0, [199, 100, 50, 128]);
+        assert_eq!(img.get_pixel(1, 0), source.get_pixel(1, 0));
+        assert_eq!(img.get_pixel(2, 0).0, [0, 0, 0, 0]);
+        assert!(AlphaConversion::Premultiply.premultiplied());
+        assert!(!AlphaConversion::Unpremultiply.premultiplied());
+    }
+
+    /// One quad whose base color texture is a flat color at the given alpha
+    #[cfg(feature = "png")]
+    fn flat_texture_glb(alpha: u8, alpha_mode: AlphaMode) -> Vec<u8> {
+        use std::io::Cursor;
+
+        use crate::{
+            blob::BlobWriter,
+            glb::{read_glb, write_glb},
+            opt::encode_png,
+            testing::SyntheticGlb,
+        };
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let pixels = RgbaImage::from_pixel(8, 8, image::Rgba([200, 100, 50, alpha]));
+        let mut png = Vec::new();
+        encode_png(pixels.as_raw(), 8, 8, image::ColorType::Rgba8, &mut png).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        json.images[0].buffer_view = Some(blob.push_view(&mut json, &png, None));
+        json.materials[0].alpha_mode = Checked::Valid(alpha_mode);
+        write_glb(&json, blob.into_vec()).unwrap()
+    }
+
+    /// MIME type, extras and first pixel of the optimized base color image
+    #[cfg(feature = "png")]
+    fn optimized_base_color(
+        glb: Vec<u8>,
+        options: &crate::options::OptimizeOptions,
+    ) -> (String, Option<serde_json::Value>, [u8; 4]) {
+        use std::io::Cursor;
+
+        use crate::{
+            glb::{from_extras, read_glb},
+            opt::{get_image_data, optimize_with},
+        };
+
+        let out = optimize_with(&mut Cursor::new(glb), options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let data = get_image_data(&blob, &json, Index::new(0)).unwrap();
+        let pixel = image::load_from_memory(data)
+            .unwrap()
+            .to_rgba8()
+            .get_pixel(0, 0)
+            .0;
+        let image = &json.images[0];
+        (
+            image.mime_type.as_ref().unwrap().0.clone(),
+            from_extras(&image.extras),
+            pixel,
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn converted_textures_are_tagged() {
+        use crate::{opt::ImageFormat, options::OptimizeOptions};
+
+        let options = OptimizeOptions {
+            alpha_conversion: Some(AlphaConversion::Premultiply),
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        let glb = flat_texture_glb(128, AlphaMode::Blend);
+        let (mime_type, extras, pixel) = optimized_base_color(glb, &options);
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(
+            extras,
+            Some(serde_json::json!({ALPHA_PREMULTIPLIED_KEY: true}))
+        );
+        assert_eq!(pixel, [100, 50, 25, 128]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn opaque_alpha_is_dropped_for_opaque_materials() {
+        use crate::options::OptimizeOptions;
+
+        let options = OptimizeOptions::default();
+        let glb = flat_texture_glb(255, AlphaMode::Opaque);
+        let (mime_type, extras, pixel) = optimized_base_color(glb, &options);
+        assert_eq!(mime_type, "image/jpeg");
+        assert_eq!(extras, Some(serde_json::json!({ALPHA_PRUNED_KEY: true})));
+        assert_eq!(pixel[3], 255);
+
+        // Blended materials may still read the channel
+        let options = OptimizeOptions {
+            image_format: Some(crate::opt::ImageFormat::Png),
+            ..options
+        };
+        let glb = flat_texture_glb(255, AlphaMode::Blend);
+        let (mime_type, extras, _) = optimized_base_color(glb, &options);
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(extras, None);
+    }
+
+    #[test]
+    fn only_uniform_alpha_is_pruned() {
+        let opaque =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, image::Rgba([1, 2, 3, 255])));
+        assert_eq!(
+            prune_opaque_alpha(&opaque).map(|img| img.color()),
+            Some(image::ColorType::Rgb8)
+        );
+        let mut translucent = opaque.to_rgba8();
+        translucent.get_pixel_mut(1, 1).0[3] = 254;
+        assert!(prune_opaque_alpha(&DynamicImage::ImageRgba8(translucent)).is_none());
+        let rgb = DynamicImage::ImageRgb8(image::RgbImage::new(2, 2));
+        assert!(prune_opaque_alpha(&rgb).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Node, Root, Scene};
+use serde_json::{Map, Value};
+
+use crate::{
+    bounds::scene_roots,
+    error::OptimizeError,
+    glb::{merge_extras, read_glb, to_extras, write_glb},
+};
+
+/// Empty node marking a point of interest, e.g. a hotspot or annotation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Anchor {
+    pub name: String,
+    /// Position in the coordinates of the source model
+    pub position: [f32; 3],
+    /// Extra data for the viewer, written to the node's `extras` next to `"anchor": true`
+    pub extras: Map<String, Value>,
+}
+
+/// Add `anchors` as root nodes of the default scene (or scene 0), moved by `offset` and then
+/// scaled by `scale`
+pub(crate) fn insert_anchors(n_json: &mut Root, anchors: &[Anchor], offset: [f32; 3], scale: f32) {
+    if n_json.scenes.is_empty() {
+        let roots = scene_roots(n_json);
+        let idx = n_json.push(Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes: roots,
+        });
+        n_json.scene = Some(idx);
+    }
+    let scene_idx = n_json.scene.map(|s| s.value()).unwrap_or(0);
+
+    for anchor in anchors {
+        let extras = to_extras(Value::Object(anchor.extras.clone()));
+        let node = n_json.push(Node {
+            name: Some(anchor.name.clone()),
+            translation: Some(std::array::from_fn(|i| {
+                (anchor.position[i] + offset[i]) * scale
+            })),
+            extras: merge_extras(&extras, "anchor", Value::Bool(true)),
+            ..Default::default()
+        });
+        if let Some(scene) = n_json.scenes.get_mut(scene_idx) {
+            scene.nodes.push(node);
+        }
+    }
+}
+
+/// Add empty nodes at the given positions to a GLB, leaving everything else as is
+pub fn add_anchors<R: Read + Seek>(
+    reader: &mut R,
+    anchors: &[Anchor],
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut n_json, n_blob) = read_glb(reader)?;
+    insert_anchors(&mut n_json, anchors, [0.0; 3], 1.0);
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::from_extras, optimizer::Optimizer, options::OptimizeOptions, rescale::Rescale,
+        testing::SyntheticGlb,
+    };
+
+    fn corner_anchor() -> Anchor {
+        let mut extras = Map::new();
+        extras.insert("label".into(), "corner".into());
+        Anchor {
+            name: "hotspot".into(),
+            position: [1.0, 1.0, 0.0],
+            extras,
+        }
+    }
+
+    /// The anchor node of a GLB and whether the default scene lists it
+    fn anchor_node(glb: &[u8]) -> (Node, bool) {
+        let (json, _) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let idx = json
+            .nodes
+            .iter()
+            .position(|n| n.name.as_deref() == Some("hotspot"))
+            .unwrap();
+        let scene = &json.scenes[json.scene.map_or(0, |s| s.value())];
+        let listed = scene.nodes.iter().any(|n| n.value() == idx);
+        (json.nodes[idx].clone(), listed)
+    }
+
+    #[test]
+    fn anchors_are_added_as_scene_roots() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let out = add_anchors(&mut Cursor::new(glb), &[corner_anchor()]).unwrap();
+        let (node, listed) = anchor_node(&out);
+        assert!(listed);
+        assert!(node.mesh.is_none());
+        assert_eq!(node.translation, Some([1.0, 1.0, 0.0]));
+        let extras = from_extras(&node.extras).unwrap();
+        assert_eq!(extras["anchor"], true);
+        assert_eq!(extras["label"], "corner");
+    }
+
+    #[test]
+    fn anchors_follow_the_pivot_and_scale() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let centered = OptimizeOptions {
+            center_pivot: true,
+            ..Default::default()
+        };
+        let anchors = [corner_anchor()];
+        let out = Optimizer::new(&centered)
+            .anchors(&anchors)
+            .run(&mut Cursor::new(&glb))
+            .map(|o| o.glb);
+        // The quad spans 0..1 on X and Y, so its bottom center is (0.5, 0, 0)
+        assert_eq!(
+            anchor_node(&out.unwrap()).0.translation,
+            Some([0.5, 1.0, 0.0])
+        );
+
+        let scaled = OptimizeOptions {
+            rescale: Some(Rescale::Uniform(2.0)),
+            ..centered
+        };
+        let out = Optimizer::new(&scaled)
+            .anchors(&anchors)
+            .run(&mut Cursor::new(&glb))
+            .map(|o| o.glb);
+        assert_eq!(
+            anchor_node(&out.unwrap()).0.translation,
+            Some([1.0, 2.0, 0.0])
+        );
+    }
+}
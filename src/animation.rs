@@ -0,0 +1,984 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{
+    Accessor, Animation, Index, Root,
+    accessor::{ComponentType, GenericComponentType, Type},
+    animation::{Interpolation, Property, Sampler},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    convert::{decode_component, encode_component, read_accessor},
+    crop::RewrittenDocument,
+    error::{AccessorProblem, OptimizeError},
+    glb::{read_glb, write_glb},
+};
+
+/// Keyframes closer than this (in seconds) count as the same time
+const TIME_EPSILON: f32 = 1e-5;
+
+/// Named start time splitting an animation timeline into clips
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationMarker {
+    pub name: String,
+    /// Start of the clip in seconds; it runs until the next marker or the end of the animation
+    pub time: f32,
+}
+
+/// Sampler input key that is not later than the key before it
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct KeyframeTimingIssue {
+    pub animation: usize,
+    pub sampler: usize,
+    /// Index of the key in the sampler input
+    pub key: usize,
+    pub time: f32,
+    /// Time of the previous key, after its own repair if it had one
+    pub previous: f32,
+    /// New time when the glitch is small enough to fix, `None` when it needs a look by hand
+    pub repaired: Option<f32>,
+}
+
+/// Resampling of `CUBICSPLINE` samplers to `LINEAR`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct CubicResampling {
+    /// Keys per second of the linear curve
+    pub frame_rate: f32,
+    /// Largest deviation from the spline allowed per component; samplers that would exceed it
+    /// stay cubic
+    pub max_error: f32,
+}
+
+impl Default for CubicResampling {
+    fn default() -> Self {
+        CubicResampling {
+            frame_rate: 30.0,
+            max_error: 1e-3,
+        }
+    }
+}
+
+/// Which animations to keep and how to cut them, see [`filter_animations`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct AnimationFilter {
+    /// Keep only animations with these names; empty keeps every animation
+    pub names: Vec<String>,
+    /// Trim kept animations to `[start, end]` seconds, shifted to start at 0
+    pub time_range: Option<(f32, f32)>,
+    /// Split each kept animation into one clip per marker
+    ///
+    /// Clips take the marker's name, prefixed by the animation name (`walk_start`) when more
+    /// than one animation is split.
+    pub markers: Vec<AnimationMarker>,
+}
+
+/// Integer types tried for a sampler output, smallest first, or `None` if it must stay float
+///
+/// Core glTF allows normalized integers only for rotation and morph weight outputs.
+fn candidate_types(path: Property, values: &[f32]) -> Option<[ComponentType; 2]> {
+    let in_range = |lo: f32| values.iter().all(|v| (lo..=1.0).contains(v));
+    match path {
+        Property::Rotation => Some([ComponentType::I8, ComponentType::I16]),
+        Property::MorphTargetWeights if in_range(0.0) => {
+            Some([ComponentType::U8, ComponentType::U16])
+        }
+        Property::MorphTargetWeights if in_range(-1.0) => {
+            Some([ComponentType::I8, ComponentType::I16])
+        }
+        _ => None,
+    }
+}
+
+/// Store a float rotation or weight sampler output as normalized integers
+///
+/// Picks the smallest type whose round trip stays within `max_error` per component. Returns
+/// `None` (the caller copies the output as is) for translation/scale outputs, non-float
+/// input, samplers shared by channels animating different paths, or when no type is precise
+/// enough.
+pub(crate) fn add_quantized_output(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    animation: &Animation,
+    sampler: usize,
+    max_error: f32,
+) -> Option<Index<Accessor>> {
+    let mut paths = animation
+        .channels
+        .iter()
+        .filter(|c| c.sampler.value() == sampler)
+        .map(|c| c.target.path);
+    let path = match paths.next()? {
+        Checked::Valid(path) if paths.all(|p| p == Checked::Valid(path)) => path,
+        _ => return None,
+    };
+
+    let output = animation.samplers.get(sampler)?.output;
+    let acc = o_json.accessors.get(output.value())?;
+    if !matches!(
+        acc.component_type,
+        Checked::Valid(GenericComponentType(ComponentType::F32))
+    ) {
+        return None;
+    }
+    let values: Vec<f32> = match path {
+        Property::Rotation => read_accessor::<4>(o_blob, o_json, output)?.concat(),
+        _ => read_accessor::<1>(o_blob, o_json, output)?.concat(),
+    };
+
+    let component = candidate_types(path, &values)?
+        .into_iter()
+        .find(|&component| {
+            let mut bytes = Vec::with_capacity(component.size());
+            values.iter().all(|&v| {
+                bytes.clear();
+                encode_component(component, true, v, &mut bytes);
+                (decode_component(component, true, &bytes) - v).abs() <= max_error
+            })
+        })?;
+
+    let mut bytes = Vec::with_capacity(values.len() * component.size());
+    for &v in &values {
+        encode_component(component, true, v, &mut bytes);
+    }
+    let view = n_blob.push_view(n_json, &bytes, None);
+
+    Some(n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        component_type: Checked::Valid(GenericComponentType(component)),
+        normalized: true,
+        min: None,
+        max: None,
+        sparse: None,
+        ..acc.clone()
+    }))
+}
+
+/// Keyframes of one sampler with outputs flattened to `width` floats per key
+///
+/// Cubic spline keys hold an in-tangent, the value and an out-tangent, each `width` long.
+struct Track {
+    times: Vec<f32>,
+    values: Vec<f32>,
+    width: usize,
+    interpolation: Interpolation,
+}
+
+impl Track {
+    fn read(blob: &[u8], json: &Root, sampler: &Sampler) -> Option<Track> {
+        let times = read_accessor::<1>(blob, json, sampler.input)?.concat();
+        let output = json.accessors.get(sampler.output.value())?;
+        let values = match output.type_ {
+            Checked::Valid(Type::Scalar) => {
+                read_accessor::<1>(blob, json, sampler.output)?.concat()
+            }
+            Checked::Valid(Type::Vec3) => read_accessor::<3>(blob, json, sampler.output)?.concat(),
+            Checked::Valid(Type::Vec4) => read_accessor::<4>(blob, json, sampler.output)?.concat(),
+            _ => return None,
+        };
+        let interpolation = match sampler.interpolation {
+            Checked::Valid(interpolation) => interpolation,
+            Checked::Invalid => return None,
+        };
+        let blocks = if interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        let width = values.len() / times.len().max(1) / blocks;
+        (width > 0 && values.len() == times.len() * width * blocks).then_some(Track {
+            times,
+            values,
+            width,
+            interpolation,
+        })
+    }
+
+    fn cubic(&self) -> bool {
+        self.interpolation == Interpolation::CubicSpline
+    }
+
+    /// In-tangent, value and out-tangent of key `i` (tangents empty unless cubic)
+    fn key(&self, i: usize) -> [&[f32]; 3] {
+        let w = self.width;
+        if self.cubic() {
+            let k = &self.values[i * w * 3..(i + 1) * w * 3];
+            [&k[..w], &k[w..2 * w], &k[2 * w..]]
+        } else {
+            [&[], &self.values[i * w..(i + 1) * w], &[]]
+        }
+    }
+
+    /// Value at time `t`, clamped to the first and last key like glTF runtimes do
+    fn sample(&self, t: f32, normalize: bool) -> Vec<f32> {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&k| k <= t);
+        let mut value = if next == 0 {
+            self.key(0)[1].to_vec()
+        } else if next > last {
+            self.key(last)[1].to_vec()
+        } else {
+            let (i, j) = (next - 1, next);
+            let dt = self.times[j] - self.times[i];
+            let u = if dt > 0.0 {
+                (t - self.times[i]) / dt
+            } else {
+                0.0
+            };
+            let ([_, v0, b0], [a1, v1, _]) = (self.key(i), self.key(j));
+            match self.interpolation {
+                Interpolation::Step => v0.to_vec(),
+                Interpolation::Linear => v0.iter().zip(v1).map(|(a, b)| a + (b - a) * u).collect(),
+                Interpolation::CubicSpline => {
+                    let (u2, u3) = (u * u, u * u * u);
+                    (0..self.width)
+                        .map(|c| {
+                            (2.0 * u3 - 3.0 * u2 + 1.0) * v0[c]
+                                + (u3 - 2.0 * u2 + u) * dt * b0[c]
+                                + (-2.0 * u3 + 3.0 * u2) * v1[c]
+                                + (u3 - u2) * dt * a1[c]
+                        })
+                        .collect()
+                }
+            }
+        };
+        if normalize {
+            let len = value.iter().map(|c| c * c).sum::<f32>().sqrt();
+            if len > 0.0 {
+                value.iter_mut().for_each(|c| *c /= len);
+            }
+        }
+        value
+    }
+
+    /// Keys within `[start, end]`, with sampled keys added at cut points, shifted to start at 0
+    ///
+    /// Keys added to a cubic spline get flat tangents.
+    fn trim(&self, start: f32, end: f32, normalize: bool) -> Track {
+        let has_key = |t: f32| self.times.iter().any(|&k| (k - t).abs() <= TIME_EPSILON);
+        let first = self.times[0];
+        let last = self.times[self.times.len() - 1];
+
+        // (time, key index or sampled value)
+        let mut keys: Vec<(f32, Result<usize, Vec<f32>>)> = Vec::new();
+        if start > first + TIME_EPSILON && !has_key(start) {
+            keys.push((start, Err(self.sample(start, normalize))));
+        }
+        for (i, &t) in self.times.iter().enumerate() {
+            if t >= start - TIME_EPSILON && t <= end + TIME_EPSILON {
+                keys.push((t.max(start), Ok(i)));
+            }
+        }
+        if end < last - TIME_EPSILON && !has_key(end) {
+            keys.push((end, Err(self.sample(end, normalize))));
+        }
+        if keys.is_empty() {
+            keys.push((start, Err(self.sample(start, normalize))));
+        }
+
+        let flat = vec![0.0; self.width];
+        let mut values = Vec::with_capacity(keys.len() * self.values.len() / self.times.len());
+        for (_, key) in &keys {
+            let [a, v, b] = match key {
+                Ok(i) => self.key(*i),
+                Err(value) => [&flat[..], &value[..], &flat[..]],
+            };
+            if self.cubic() {
+                values.extend_from_slice(a);
+            }
+            values.extend_from_slice(v);
+            if self.cubic() {
+                values.extend_from_slice(b);
+            }
+        }
+
+        Track {
+            times: keys.iter().map(|(t, _)| t - start).collect(),
+            values,
+            width: self.width,
+            interpolation: self.interpolation,
+        }
+    }
+
+    /// Append input and output accessors, the output typed like `output`
+    fn write(
+        &self,
+        blob: &mut BlobWriter,
+        json: &mut Root,
+        output: &Accessor,
+    ) -> (Index<Accessor>, Index<Accessor>) {
+        let float = |blob: &mut BlobWriter, json: &mut Root, values: &[f32], source: &Accessor| {
+            let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            let view = blob.push_view(json, &bytes, None);
+            let multiplicity = match source.type_ {
+                Checked::Valid(ty) => ty.multiplicity(),
+                Checked::Invalid => 1,
+            };
+            json.push(Accessor {
+                buffer_view: Some(view),
+                byte_offset: None,
+                count: (values.len() / multiplicity).into(),
+                component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+                normalized: false,
+                min: None,
+                max: None,
+                sparse: None,
+                ..source.clone()
+            })
+        };
+
+        let input_source = Accessor {
+            type_: Checked::Valid(Type::Scalar),
+            ..output.clone()
+        };
+        let input = float(blob, json, &self.times, &input_source);
+        // Sampler inputs must carry min/max
+        let acc = &mut json.accessors[input.value()];
+        acc.name = None;
+        acc.extras = Default::default();
+        acc.min = self.times.first().map(|&t| vec![t].into());
+        acc.max = self.times.last().map(|&t| vec![t].into());
+
+        (input, float(blob, json, &self.values, output))
+    }
+}
+
+/// Linear keys at `frame_rate` following a cubic spline, or `None` if they stray too far or
+/// would not be smaller
+fn linearize(track: &Track, resampling: &CubicResampling, normalize: bool) -> Option<Track> {
+    let first = track.times[0];
+    let last = track.times[track.times.len() - 1];
+    let steps = ((last - first) * resampling.frame_rate).ceil().max(1.0) as usize;
+    // Cubic keys store an in-tangent, value and out-tangent; give up if linear keys take more
+    if (steps + 1) * (1 + track.width) >= track.times.len() * (1 + 3 * track.width) {
+        return None;
+    }
+    let times: Vec<f32> = (0..=steps)
+        .map(|i| (first + i as f32 / resampling.frame_rate).min(last))
+        .collect();
+    let linear = Track {
+        values: times
+            .iter()
+            .flat_map(|&t| track.sample(t, normalize))
+            .collect(),
+        times,
+        width: track.width,
+        interpolation: Interpolation::Linear,
+    };
+
+    // Check halfway between the new keys and at every original key
+    let midpoints = linear.times.windows(2).map(|w| (w[0] + w[1]) / 2.0);
+    let fits = midpoints.chain(track.times.iter().copied()).all(|t| {
+        let expected = track.sample(t, normalize);
+        let actual = linear.sample(t, normalize);
+        expected
+            .iter()
+            .zip(&actual)
+            .all(|(e, a)| (e - a).abs() <= resampling.max_error)
+    });
+    fits.then_some(linear)
+}
+
+/// Replace cubic spline samplers with linear ones where the resampled curve is close and smaller
+///
+/// Returns `None` when no sampler was converted.
+pub(crate) fn linearize_cubic_samplers(
+    o_blob: &[u8],
+    o_json: &Root,
+    resampling: &CubicResampling,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed = false;
+
+    for (a, animation) in o_json.animations.iter().enumerate() {
+        for (i, sampler) in animation.samplers.iter().enumerate() {
+            if sampler.interpolation != Checked::Valid(Interpolation::CubicSpline) {
+                continue;
+            }
+            let Some(track) = Track::read(o_blob, o_json, sampler) else {
+                continue;
+            };
+            let rotation = animation.channels.iter().any(|c| {
+                c.sampler.value() == i && c.target.path == Checked::Valid(Property::Rotation)
+            });
+            let Some(linear) = linearize(&track, resampling, rotation) else {
+                continue;
+            };
+
+            let output = o_json.accessors[sampler.output.value()].clone();
+            let (input, output) = linear.write(&mut n_blob, &mut n_json, &output);
+            let n_sampler = &mut n_json.animations[a].samplers[i];
+            n_sampler.input = input;
+            n_sampler.output = output;
+            n_sampler.interpolation = Checked::Valid(Interpolation::Linear);
+            changed = true;
+        }
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+/// Length of an animation in seconds, from the last key of its longest sampler
+fn duration(blob: &[u8], json: &Root, animation: &Animation) -> f32 {
+    animation
+        .samplers
+        .iter()
+        .filter_map(|s| read_accessor::<1>(blob, json, s.input))
+        .filter_map(|times| times.last().map(|t| t[0]))
+        .fold(0.0, f32::max)
+}
+
+/// Copy of `animation` holding only the keys within `[start, end]`, shifted to start at 0
+fn trim_animation(
+    blob: &mut BlobWriter,
+    json: &mut Root,
+    animation: &Animation,
+    start: f32,
+    end: f32,
+) -> Result<Animation, OptimizeError> {
+    let mut trimmed = animation.clone();
+    for (i, sampler) in animation.samplers.iter().enumerate() {
+        let track = Track::read(blob, json, sampler).ok_or(OptimizeError::Accessor {
+            accessor: sampler.output.value(),
+            problem: AccessorProblem::Unusable {
+                usage: "keyframe output of its sampler",
+            },
+        })?;
+        let rotation = animation
+            .channels
+            .iter()
+            .any(|c| c.sampler.value() == i && c.target.path == Checked::Valid(Property::Rotation));
+        let output = json.accessors[sampler.output.value()].clone();
+        let (input, output) = track.trim(start, end, rotation).write(blob, json, &output);
+        trimmed.samplers[i].input = input;
+        trimmed.samplers[i].output = output;
+    }
+    Ok(trimmed)
+}
+
+/// Keep, trim and split animations, e.g. to clean up mocap exports
+///
+/// Names are matched first, then the time range is applied, then markers split what is left.
+/// Keys are cut at clip boundaries, with interpolated keys added where a boundary falls
+/// between two. Replaced keyframe data stays in the buffer until the file goes through
+/// [`optimize_with`](crate::optimize_with), so run this first.
+pub fn filter_animations<R: Read + Seek>(
+    reader: &mut R,
+    filter: &AnimationFilter,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut n_json, n_blob) = read_glb(reader)?;
+    let mut n_blob = BlobWriter::from(n_blob);
+
+    let missing: Vec<&str> = filter
+        .names
+        .iter()
+        .filter(|name| {
+            !n_json
+                .animations
+                .iter()
+                .any(|a| a.name.as_ref() == Some(*name))
+        })
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(OptimizeError::MissingName {
+            kind: "animation",
+            names: missing.into_iter().map(String::from).collect(),
+        });
+    }
+
+    let source: Vec<Animation> = std::mem::take(&mut n_json.animations)
+        .into_iter()
+        .filter(|a| {
+            filter.names.is_empty() || a.name.as_ref().is_some_and(|n| filter.names.contains(n))
+        })
+        .collect();
+
+    let mut markers = filter.markers.clone();
+    markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    for animation in &source {
+        if filter.time_range.is_none() && markers.is_empty() {
+            n_json.animations.push(animation.clone());
+            continue;
+        }
+
+        let (start, end) = filter
+            .time_range
+            .unwrap_or((0.0, duration(&n_blob, &n_json, animation)));
+        if markers.is_empty() {
+            let trimmed = trim_animation(&mut n_blob, &mut n_json, animation, start, end)?;
+            n_json.animations.push(trimmed);
+            continue;
+        }
+
+        for (k, marker) in markers.iter().enumerate() {
+            let clip_start = marker.time.max(start);
+            let clip_end = markers.get(k + 1).map_or(end, |next| next.time.min(end));
+            if clip_end <= clip_start {
+                continue;
+            }
+            let mut clip =
+                trim_animation(&mut n_blob, &mut n_json, animation, clip_start, clip_end)?;
+            clip.name = Some(match (&animation.name, source.len()) {
+                (Some(name), 2..) => format!("{name}_{}", marker.name),
+                _ => marker.name.clone(),
+            });
+            n_json.animations.push(clip);
+        }
+    }
+
+    write_glb(&n_json, n_blob)
+}
+
+/// Fix keys that step back (or repeat) by at most half the median key interval
+///
+/// The tolerance follows the sampler's own key spacing, so it works at any frame rate.
+/// Repaired keys move just past the previous key. Returns `(key, time, previous, repaired)`
+/// for every key that is not strictly increasing.
+fn repair_times(times: &mut [f32]) -> Vec<(usize, f32, f32, Option<f32>)> {
+    let mut intervals: Vec<f32> = times
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .collect();
+    intervals.sort_by(f32::total_cmp);
+    let median = intervals.get(intervals.len() / 2).copied().unwrap_or(0.0);
+    let nudge = median * 1e-3;
+
+    let mut issues = Vec::new();
+    for i in 1..times.len() {
+        let (previous, time) = (times[i - 1], times[i]);
+        if time > previous {
+            continue;
+        }
+        let repairable = time.is_finite()
+            && previous.is_finite()
+            && median > 0.0
+            && previous - time <= median / 2.0;
+        let repaired = repairable.then_some((previous + nudge).max(previous.next_up()));
+        if let Some(t) = repaired {
+            times[i] = t;
+        }
+        issues.push((i, time, previous, repaired));
+    }
+    issues
+}
+
+/// Check every sampler input for keys that do not increase strictly
+pub(crate) fn timing_issues(blob: &[u8], json: &Root) -> Vec<KeyframeTimingIssue> {
+    let mut issues = Vec::new();
+    for (a, animation) in json.animations.iter().enumerate() {
+        for (s, sampler) in animation.samplers.iter().enumerate() {
+            let Some(times) = read_accessor::<1>(blob, json, sampler.input) else {
+                continue;
+            };
+            let mut times = times.concat();
+            for (key, time, previous, repaired) in repair_times(&mut times) {
+                issues.push(KeyframeTimingIssue {
+                    animation: a,
+                    sampler: s,
+                    key,
+                    time,
+                    previous,
+                    repaired,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Copy a sampler input with small timing glitches repaired
+///
+/// Returns `None` (the caller copies the input as is) when no key needed a repair.
+pub(crate) fn add_repaired_input(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    input: Index<Accessor>,
+) -> Option<Index<Accessor>> {
+    let mut times = read_accessor::<1>(o_blob, o_json, input)?.concat();
+    if !repair_times(&mut times)
+        .iter()
+        .any(|(.., repaired)| repaired.is_some())
+    {
+        return None;
+    }
+
+    let bytes: Vec<u8> = times.iter().flat_map(|t| t.to_le_bytes()).collect();
+    let view = n_blob.push_view(n_json, &bytes, None);
+    let finite = times.iter().copied().filter(|t| t.is_finite());
+    let min = finite.clone().fold(f32::MAX, f32::min);
+    let max = finite.fold(f32::MIN, f32::max);
+    Some(n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+        normalized: false,
+        min: Some(vec![min].into()),
+        max: Some(vec![max].into()),
+        sparse: None,
+        ..o_json.accessors[input.value()].clone()
+    }))
+}
+
+/// Report sampler inputs whose keys do not increase strictly, as strict runtimes reject them
+///
+/// Issues with a `repaired` time are fixed by `repair_animation_timing`; the rest are left
+/// as they are.
+pub fn validate_animation_timing<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<Vec<KeyframeTimingIssue>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    Ok(timing_issues(&o_blob, &o_json))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    /// One sampler of a test animation, targeting node 0
+    #[derive(Clone, Copy)]
+    struct Key<'a> {
+        path: &'a str,
+        interpolation: &'a str,
+        times: &'a [f32],
+        values: &'a [f32],
+    }
+
+    /// The synthetic quad with one animation per `(name, samplers)` entry
+    fn animated_glb(animations: &[(&str, &[Key])]) -> Vec<u8> {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let mut views = Vec::new();
+        for (_, keys) in animations {
+            for key in *keys {
+                let bytes =
+                    |v: &[f32]| -> Vec<u8> { v.iter().flat_map(|f| f.to_le_bytes()).collect() };
+                let input = blob.push_view(&mut json, &bytes(key.times), None).value();
+                let output = blob.push_view(&mut json, &bytes(key.values), None).value();
+                views.push((input, output));
+            }
+        }
+
+        let mut doc = serde_json::to_value(&json).unwrap();
+        let mut views = views.into_iter();
+        let mut n_animations = Vec::new();
+        for (name, keys) in animations {
+            let (mut samplers, mut channels) = (Vec::new(), Vec::new());
+            for key in *keys {
+                let (input, output) = views.next().unwrap();
+                let accessors = doc["accessors"].as_array_mut().unwrap();
+                let (ty, width) = match key.path {
+                    "rotation" => ("VEC4", 4),
+                    "weights" => ("SCALAR", 1),
+                    _ => ("VEC3", 3),
+                };
+                accessors.push(json!({
+                    "bufferView": input, "componentType": 5126, "count": key.times.len(),
+                    "type": "SCALAR",
+                    "min": [key.times.iter().copied().fold(f32::MAX, f32::min)],
+                    "max": [key.times.iter().copied().fold(f32::MIN, f32::max)],
+                }));
+                accessors.push(json!({
+                    "bufferView": output, "componentType": 5126,
+                    "count": key.values.len() / width, "type": ty,
+                }));
+                let count = accessors.len();
+                channels.push(json!({
+                    "sampler": samplers.len(), "target": { "node": 0, "path": key.path },
+                }));
+                samplers.push(json!({
+                    "input": count - 2, "output": count - 1, "interpolation": key.interpolation,
+                }));
+            }
+            n_animations.push(json!({ "name": name, "samplers": samplers, "channels": channels }));
+        }
+        doc["animations"] = Value::Array(n_animations);
+        let json: Root = serde_json::from_value(doc).unwrap();
+        write_glb(&json, blob.into_vec()).unwrap()
+    }
+
+    /// Interpolation, times and output values of one sampler
+    type Sampled = (Interpolation, Vec<f32>, Vec<f32>);
+
+    /// Samplers of every animation, with the animation's name
+    fn samplers(glb: &[u8]) -> Vec<(Option<String>, Vec<Sampled>)> {
+        let (json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.animations
+            .iter()
+            .map(|animation| {
+                let samplers = animation
+                    .samplers
+                    .iter()
+                    .map(|sampler| {
+                        let track = Track::read(&blob, &json, sampler).unwrap();
+                        (track.interpolation, track.times, track.values)
+                    })
+                    .collect();
+                (animation.name.clone(), samplers)
+            })
+            .collect()
+    }
+
+    fn close(a: &[f32], b: &[f32], tolerance: f32) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance)
+    }
+
+    #[test]
+    fn animations_survive_optimization() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            skin: true,
+            animated: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = optimize_with(&mut Cursor::new(&glb), &OptimizeOptions::default()).unwrap();
+
+        let channels = |glb: &[u8]| {
+            let (json, _) = read_glb(&mut Cursor::new(glb)).unwrap();
+            json.animations[0]
+                .channels
+                .iter()
+                .map(|c| (c.sampler.value(), c.target.node.value(), c.target.path))
+                .collect::<Vec<_>>()
+        };
+        let before = channels(&glb);
+        assert_eq!(before.len(), 4);
+        assert_eq!(channels(&out), before);
+
+        let sampled = samplers(&glb);
+        assert_eq!(sampled[0].0.as_deref(), Some("move"));
+        assert_eq!(sampled[0].1[0].1, [0.0, 0.5, 1.0]);
+        assert_eq!(samplers(&out), sampled);
+    }
+
+    #[test]
+    fn rotations_are_quantized_within_the_bound() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let rotations = [0.0, 0.0, 0.0, 1.0, 0.0, half, 0.0, half, 0.0, 1.0, 0.0, 0.0];
+        let translations = [0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 2.0, 4.0, 6.0];
+        let glb = animated_glb(&[(
+            "spin",
+            &[
+                Key {
+                    path: "rotation",
+                    interpolation: "LINEAR",
+                    times: &[0.0, 0.5, 1.0],
+                    values: &rotations,
+                },
+                Key {
+                    path: "translation",
+                    interpolation: "LINEAR",
+                    times: &[0.0, 0.5, 1.0],
+                    values: &translations,
+                },
+            ],
+        )]);
+
+        for (max_error, component) in [(0.005, ComponentType::I8), (0.001, ComponentType::I16)] {
+            let options = OptimizeOptions {
+                quantize_animations: Some(max_error),
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (json, _) = read_glb(&mut Cursor::new(&out)).unwrap();
+            let output_type = |s: usize| {
+                let acc = &json.accessors[json.animations[0].samplers[s].output.value()];
+                (acc.component_type, acc.normalized)
+            };
+            assert!(matches!(
+                output_type(0),
+                (Checked::Valid(GenericComponentType(c)), true) if c == component
+            ));
+            assert!(matches!(
+                output_type(1),
+                (
+                    Checked::Valid(GenericComponentType(ComponentType::F32)),
+                    false
+                )
+            ));
+
+            let samplers = &samplers(&out)[0].1;
+            assert!(close(&samplers[0].2, &rotations, max_error));
+            assert_eq!(samplers[1].2, translations);
+        }
+    }
+
+    fn walk_and_run() -> Vec<u8> {
+        let key = Key {
+            path: "translation",
+            interpolation: "LINEAR",
+            times: &[0.0, 1.0, 2.0],
+            values: &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 2.0, 0.0, 0.0],
+        };
+        animated_glb(&[("walk", &[key]), ("run", &[key])])
+    }
+
+    fn filtered(glb: &[u8], filter: &AnimationFilter) -> Vec<u8> {
+        filter_animations(&mut Cursor::new(glb), filter).unwrap()
+    }
+
+    #[test]
+    fn animations_are_kept_by_name() {
+        let glb = walk_and_run();
+        let filter = AnimationFilter {
+            names: vec!["run".into()],
+            ..Default::default()
+        };
+        let names: Vec<_> = samplers(&filtered(&glb, &filter))
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, [Some("run".to_string())]);
+
+        let filter = AnimationFilter {
+            names: vec!["run".into(), "jump".into()],
+            ..Default::default()
+        };
+        let err = filter_animations(&mut Cursor::new(&glb), &filter).unwrap_err();
+        assert!(
+            matches!(err, OptimizeError::MissingName { kind: "animation", ref names } if names == &["jump"])
+        );
+    }
+
+    #[test]
+    fn trimmed_animations_start_at_zero_with_interpolated_ends() {
+        let filter = AnimationFilter {
+            names: vec!["walk".into()],
+            time_range: Some((0.5, 1.5)),
+            ..Default::default()
+        };
+        let out = samplers(&filtered(&walk_and_run(), &filter));
+        let (_, times, values) = &out[0].1[0];
+        assert!(close(times, &[0.0, 0.5, 1.0], 1e-6));
+        assert!(close(
+            values,
+            &[0.5, 0.0, 0.0, 1.0, 0.0, 0.0, 1.5, 0.0, 0.0],
+            1e-6
+        ));
+    }
+
+    #[test]
+    fn markers_split_animations_into_named_clips() {
+        let markers = vec![
+            AnimationMarker {
+                name: "start".into(),
+                time: 0.0,
+            },
+            AnimationMarker {
+                name: "loop".into(),
+                time: 1.0,
+            },
+        ];
+        let single = AnimationFilter {
+            names: vec!["walk".into()],
+            markers: markers.clone(),
+            ..Default::default()
+        };
+        let out = samplers(&filtered(&walk_and_run(), &single));
+        let names: Vec<_> = out.iter().map(|(name, _)| name.clone().unwrap()).collect();
+        assert_eq!(names, ["start", "loop"]);
+        assert!(close(&out[1].1[0].1, &[0.0, 1.0], 1e-6));
+        assert!(close(&out[1].1[0].2, &[1.0, 0.0, 0.0, 2.0, 0.0, 0.0], 1e-6));
+
+        let both = AnimationFilter {
+            markers,
+            ..Default::default()
+        };
+        let out = samplers(&filtered(&walk_and_run(), &both));
+        let names: Vec<_> = out.iter().map(|(name, _)| name.clone().unwrap()).collect();
+        assert_eq!(names, ["walk_start", "walk_loop", "run_start", "run_loop"]);
+    }
+
+    #[test]
+    fn small_timing_glitches_are_reported_and_repaired() {
+        let glb = animated_glb(&[(
+            "glitch",
+            &[Key {
+                path: "translation",
+                interpolation: "LINEAR",
+                times: &[0.0, 1.0, 0.99, 2.0, 1.0],
+                values: &[0.0; 15],
+            }],
+        )]);
+
+        let issues = validate_animation_timing(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!((issues[0].key, issues[0].time), (2, 0.99));
+        assert!(issues[0].repaired.is_some_and(|t| t > 1.0 && t < 1.01));
+        assert_eq!((issues[1].key, issues[1].repaired), (4, None));
+
+        let options = OptimizeOptions {
+            repair_animation_timing: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let times = &samplers(&out)[0].1[0].1;
+        assert_eq!(times[2], issues[0].repaired.unwrap());
+        assert_eq!(times[4], 1.0);
+        let issues = validate_animation_timing(&mut Cursor::new(&out)).unwrap();
+        assert_eq!(issues.len(), 1);
+    }
+
+    #[test]
+    fn cubic_samplers_are_linearized_only_within_the_error() {
+        // Eleven keys along x with flat tangents: the spline matches a line at every key and
+        // halfway between keys, but bends away from it elsewhere
+        let times: Vec<f32> = (0..=10).map(|i| i as f32 / 10.0).collect();
+        let values: Vec<f32> = times
+            .iter()
+            .flat_map(|&t| [0.0, 0.0, 0.0, t, 0.0, 0.0, 0.0, 0.0, 0.0])
+            .collect();
+        let glb = animated_glb(&[(
+            "ease",
+            &[Key {
+                path: "translation",
+                interpolation: "CUBICSPLINE",
+                times: &times,
+                values: &values,
+            }],
+        )]);
+        let resampled = |frame_rate| {
+            let options = OptimizeOptions {
+                resample_cubic_animations: Some(CubicResampling {
+                    frame_rate,
+                    max_error: 1e-3,
+                }),
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            samplers(&out).remove(0).1.remove(0)
+        };
+
+        let (interpolation, times, values) = resampled(10.0);
+        assert_eq!(interpolation, Interpolation::Linear);
+        assert_eq!(times.len(), 11);
+        assert!(
+            values
+                .chunks(3)
+                .zip(&times)
+                .all(|(v, t)| (v[0] - t).abs() < 1e-3)
+        );
+
+        let (interpolation, ..) = resampled(4.0);
+        assert_eq!(interpolation, Interpolation::CubicSpline);
+    }
+}
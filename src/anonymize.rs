@@ -0,0 +1,111 @@
+use image::{RgbaImage, imageops};
+
+use crate::error::OptimizeError;
+
+/// Checker colors of [`TextureAnonymization::Checkerboard`], light and dark grey
+const CHECKER_COLORS: [[u8; 3]; 2] = [[192, 192, 192], [96, 96, 96]];
+
+/// How base color art is hidden, see
+/// [`OptimizeOptions::anonymize_textures`](crate::OptimizeOptions::anonymize_textures)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum TextureAnonymization {
+    /// Replace the colors with a grey checkerboard of `cells` squares along the longer side
+    Checkerboard { cells: u32 },
+    /// Gaussian-blur the colors with a sigma of `radius` output pixels
+    Blur { radius: f32 },
+}
+
+impl Default for TextureAnonymization {
+    fn default() -> Self {
+        TextureAnonymization::Checkerboard { cells: 8 }
+    }
+}
+
+/// Hide the color channels of `rgba`, keeping its alpha so cutouts keep their shapes
+pub(crate) fn anonymize_rgba(
+    rgba: &mut RgbaImage,
+    anonymization: TextureAnonymization,
+) -> Result<(), OptimizeError> {
+    match anonymization {
+        TextureAnonymization::Checkerboard { cells } => {
+            if cells == 0 {
+                return Err(OptimizeError::InvalidOption(
+                    "checkerboard needs at least one cell".to_string(),
+                ));
+            }
+            let longer = rgba.width().max(rgba.height());
+            let cell = longer.div_ceil(cells).max(1);
+            for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+                let color = CHECKER_COLORS[((x / cell + y / cell) % 2) as usize];
+                pixel.0[..3].copy_from_slice(&color);
+            }
+        }
+        TextureAnonymization::Blur { radius } => {
+            if !radius.is_finite() || radius < 0.0 {
+                return Err(OptimizeError::InvalidOption(format!(
+                    "blur radius {radius} is not a non-negative number"
+                )));
+            }
+            if radius == 0.0 {
+                return Ok(());
+            }
+            let blurred = imageops::blur(rgba, radius);
+            for (pixel, blur) in rgba.pixels_mut().zip(blurred.pixels()) {
+                pixel.0[..3].copy_from_slice(&blur.0[..3]);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{ImageFormat, get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn anonymized_textures_hide_base_color_art() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run = |anonymize_textures| {
+            let options = OptimizeOptions {
+                image_format: Some(ImageFormat::Png),
+                anonymize_textures,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&n_blob, &n_json, Index::new(0)).unwrap();
+            let img = image::load_from_memory(data).unwrap().to_rgba8();
+            (img, n_json.accessors.len())
+        };
+
+        let (plain, accessors) = run(None);
+        let (checkers, checker_accessors) =
+            run(Some(TextureAnonymization::Checkerboard { cells: 2 }));
+        assert_eq!(checker_accessors, accessors);
+        assert_eq!(checkers.get_pixel(0, 0).0, [192, 192, 192, 255]);
+        assert_eq!(checkers.get_pixel(16, 0).0, [96, 96, 96, 255]);
+        assert_eq!(checkers.get_pixel(16, 16).0, [192, 192, 192, 255]);
+
+        // The source gradient spans the whole range; blurred, its edges pull inward
+        let (blurred, _) = run(Some(TextureAnonymization::Blur { radius: 4.0 }));
+        assert_eq!(plain.get_pixel(0, 0).0[0], 0);
+        assert!(blurred.get_pixel(0, 0).0[0] > 0);
+        assert!(blurred.get_pixel(31, 0).0[0] < plain.get_pixel(31, 0).0[0]);
+    }
+}
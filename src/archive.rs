@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{Cursor, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    error::OptimizeError,
+    opt::optimize_with,
+    options::OptimizeOptions,
+    source::{pack_gltf, percent_decode},
+};
+
+/// Read every file of a zip or tar archive into memory, keyed by its path
+fn archive_entries<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<HashMap<String, Vec<u8>>, OptimizeError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut entries = HashMap::new();
+    if &magic == b"PK\x03\x04" {
+        let mut zip = zip::ZipArchive::new(reader).map_err(OptimizeError::archive)?;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(OptimizeError::archive)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            entries.insert(
+                file.name().map_err(OptimizeError::archive)?.into_owned(),
+                data,
+            );
+        }
+    } else {
+        let mut tar = tar::Archive::new(reader);
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.to_string_lossy().into_owned();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            entries.insert(path, data);
+        }
+    }
+    Ok(entries)
+}
+
+/// Archive path of a URI relative to the `.gltf` file's directory, with `.` and `..` resolved
+fn entry_path(base: &str, uri: &str) -> String {
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    let decoded = percent_decode(uri);
+    for part in decoded.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Parse an archived `.gltf` and pack it with the archive entries its URIs point to
+fn pack_archived_gltf(
+    entries: &HashMap<String, Vec<u8>>,
+    gltf_path: &str,
+) -> Result<Vec<u8>, OptimizeError> {
+    let json = gltf::json::deserialize::from_slice(&entries[gltf_path])?;
+    let base = gltf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let resolver = |uri: &str| -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let path = entry_path(base, uri);
+        entries
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| format!("archive has no file '{path}' referenced by '{uri}'").into())
+    };
+    pack_gltf(json, Vec::new(), &resolver)
+}
+
+/// Turn a zip or tar archive (as sold by asset stores) into a single GLB
+///
+/// The archive must hold one `.gltf` file; its buffer and image URIs are resolved to archive
+/// entries relative to it, and base64 `data:` URIs are decoded. An archive holding a single
+/// `.glb` instead is returned as is.
+pub fn glb_from_archive<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>, OptimizeError> {
+    let entries = archive_entries(reader)?;
+    let with_extension = |extension: &str| -> Vec<&String> {
+        let mut found: Vec<_> = entries
+            .keys()
+            .filter(|name| name.to_ascii_lowercase().ends_with(extension))
+            .filter(|name| !name.starts_with("__MACOSX/"))
+            .collect();
+        found.sort();
+        found
+    };
+
+    match (
+        with_extension(".gltf").as_slice(),
+        with_extension(".glb").as_slice(),
+    ) {
+        ([gltf], _) => pack_archived_gltf(&entries, gltf),
+        ([], [glb]) => Ok(entries[*glb].clone()),
+        (gltf, glb) => Err(OptimizeError::ArchiveEntries {
+            found: gltf.iter().chain(glb).map(|f| f.to_string()).collect(),
+        }),
+    }
+}
+
+/// Optimize a zip or tar archive of a `.gltf` and its resources into one GLB
+pub fn optimize_archive<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    let glb = glb_from_archive(reader)?;
+    optimize_with(&mut Cursor::new(glb), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::{glb::read_glb, testing::SyntheticGlb};
+
+    /// The synthetic quad as `scene/model.gltf` and a `scene/model data.bin` it references
+    fn gltf_files() -> (Vec<u8>, Vec<(&'static str, Vec<u8>)>) {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        json.buffers[0].uri = Some("./model%20data.bin".into());
+        let gltf = serde_json::to_vec(&json).unwrap();
+        let files = vec![
+            ("__MACOSX/scene/._model.gltf", vec![0; 4]),
+            ("scene/model.gltf", gltf),
+            ("scene/model data.bin", blob),
+        ];
+        (glb, files)
+    }
+
+    fn assert_same_scene(packed: &[u8], glb: &[u8]) {
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(packed)).unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        assert!(n_json.buffers[0].uri.is_none());
+        assert_eq!(n_json.meshes.len(), o_json.meshes.len());
+        assert_eq!(n_blob[..o_blob.len()], o_blob[..]);
+    }
+
+    #[test]
+    fn zipped_gltf_is_packed_into_a_glb() {
+        let (glb, files) = gltf_files();
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in &files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let archive = zip.finish().unwrap().into_inner();
+
+        let packed = glb_from_archive(&mut Cursor::new(&archive)).unwrap();
+        assert_same_scene(&packed, &glb);
+        assert!(optimize_archive(&mut Cursor::new(&archive), &OptimizeOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn tarred_gltf_is_packed_into_a_glb() {
+        let (glb, files) = gltf_files();
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, data) in &files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            tar.append_data(&mut header, name, &data[..]).unwrap();
+        }
+        let archive = tar.into_inner().unwrap();
+
+        let packed = glb_from_archive(&mut Cursor::new(&archive)).unwrap();
+        assert_same_scene(&packed, &glb);
+    }
+
+    #[test]
+    fn archived_glb_is_returned_as_is() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(glb.len() as u64);
+        header.set_cksum();
+        tar.append_data(&mut header, "model.glb", &glb[..]).unwrap();
+        let archive = tar.into_inner().unwrap();
+
+        assert_eq!(glb_from_archive(&mut Cursor::new(archive)).unwrap(), glb);
+    }
+
+    #[test]
+    fn uris_resolve_relative_to_the_gltf() {
+        assert_eq!(entry_path("a/b", "../c/./d%20e.png"), "a/c/d e.png");
+        assert_eq!(entry_path("", "tex.png"), "tex.png");
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::{HashMap, HashSet};
+
+use gltf::json::{
+    Image, Index, Material, Root, Texture,
+    accessor::Type,
+    image::MimeType,
+    mesh::Semantic,
+    texture::{Sampler, WrappingMode},
+    validation::Checked,
+};
+use image::{RgbaImage, imageops};
+
+use crate::{
+    blob::BlobWriter,
+    crop::{RewrittenDocument, extension_textures, write_uvs},
+    decode::load_source_image,
+    error::OptimizeError,
+    opt::{encode_png, get_image_data, get_texcoord_data},
+    stats::image_dimensions,
+    texcoords::UV_EPSILON,
+};
+
+/// Texture atlases merging small material textures, see
+/// [`OptimizeOptions::atlas_textures`](crate::OptimizeOptions::atlas_textures)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct AtlasOptions {
+    /// Longest base color side packed into an atlas; materials with larger textures keep
+    /// their own; 512 by default
+    pub max_source_size: u32,
+    /// Longest atlas side; materials that don't fit start another atlas; 2048 by default
+    pub max_atlas_size: u32,
+    /// Texels around each packed texture filled with its edge, against bleeding under
+    /// filtering and mips; 4 by default
+    pub padding: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        AtlasOptions {
+            max_source_size: 512,
+            max_atlas_size: 2048,
+            padding: 4,
+        }
+    }
+}
+
+/// Core texture slots in a fixed order: base color, metallic/roughness, normal, occlusion,
+/// emissive; each with its texture, UV set and whether it has extensions
+pub(crate) type Slots = [Option<(Index<Texture>, u32, bool)>; 5];
+
+pub(crate) fn slots(mat: &Material) -> Slots {
+    let pbr = &mat.pbr_metallic_roughness;
+    let info = |i: &Option<gltf::json::texture::Info>| {
+        i.as_ref().map(|i| {
+            let ext = i.extensions.as_ref().is_some_and(|e| !e.others.is_empty());
+            (i.index, i.tex_coord, ext)
+        })
+    };
+    [
+        info(&pbr.base_color_texture),
+        info(&pbr.metallic_roughness_texture),
+        mat.normal_texture.as_ref().map(|n| {
+            let ext = n.extensions.as_ref().is_some_and(|e| !e.others.is_empty());
+            (n.index, n.tex_coord, ext)
+        }),
+        mat.occlusion_texture.as_ref().map(|o| {
+            let ext = o.extensions.as_ref().is_some_and(|e| !e.others.is_empty());
+            (o.index, o.tex_coord, ext)
+        }),
+        info(&mat.emissive_texture),
+    ]
+}
+
+/// Point the slots of `mat` at `textures`, in [`slots`] order; `None` leaves a slot alone
+fn set_slots(mat: &mut Material, textures: [Option<Index<Texture>>; 5]) {
+    let pbr = &mut mat.pbr_metallic_roughness;
+    let [base, mr, normal, occlusion, emissive] = textures;
+    if let (Some(info), Some(t)) = (&mut pbr.base_color_texture, base) {
+        info.index = t;
+    }
+    if let (Some(info), Some(t)) = (&mut pbr.metallic_roughness_texture, mr) {
+        info.index = t;
+    }
+    if let (Some(info), Some(t)) = (&mut mat.normal_texture, normal) {
+        info.index = t;
+    }
+    if let (Some(info), Some(t)) = (&mut mat.occlusion_texture, occlusion) {
+        info.index = t;
+    }
+    if let (Some(info), Some(t)) = (&mut mat.emissive_texture, emissive) {
+        info.index = t;
+    }
+}
+
+/// A material whose textures can move into an atlas
+struct Candidate {
+    material: usize,
+    /// Image of each slot, in [`slots`] order
+    images: [Option<usize>; 5],
+    /// Texture of each slot, whose sampler the atlas copies
+    textures: [Option<usize>; 5],
+    /// Size of the base color image, which every slot is packed at
+    size: (u32, u32),
+    /// UV accessors read by the material's primitives, with their UVs
+    accessors: Vec<(usize, Vec<[f32; 2]>)>,
+}
+
+/// Placement of item `i` at texel `(x, y)` of a page
+type Placement = (usize, u32, u32);
+
+/// Shelf-pack `sizes` (plus `padding` on every side) into pages of at most `max` texels a side
+///
+/// Returns each page with its placements and the size it takes up. Items that can't fit even
+/// alone are left out.
+fn pack(sizes: &[(u32, u32)], max: u32, padding: u32) -> Vec<(Vec<Placement>, (u32, u32))> {
+    let mut order: Vec<usize> = (0..sizes.len())
+        .filter(|&i| sizes[i].0 + 2 * padding <= max && sizes[i].1 + 2 * padding <= max)
+        .collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), std::cmp::Reverse(sizes[i].0)));
+
+    let mut pages = Vec::new();
+    let mut page: Vec<Placement> = Vec::new();
+    let (mut x, mut y, mut shelf, mut width) = (0, 0, 0, 0);
+    for i in order {
+        let (w, h) = (sizes[i].0 + 2 * padding, sizes[i].1 + 2 * padding);
+        if x + w > max {
+            (x, y, shelf) = (0, y + shelf, 0);
+        }
+        if y + h > max {
+            pages.push((std::mem::take(&mut page), (width, y + shelf)));
+            (x, y, shelf, width) = (0, 0, 0, 0);
+        }
+        page.push((i, x + padding, y + padding));
+        x += w;
+        shelf = shelf.max(h);
+        width = width.max(x);
+    }
+    if !page.is_empty() {
+        pages.push((page, (width, y + shelf)));
+    }
+    pages
+}
+
+/// Copy `src` to `(x, y)` of `atlas`, repeating its edge texels `padding` texels outward
+fn blit_padded(atlas: &mut RgbaImage, src: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let (w, h) = src.dimensions();
+    for ty in y - padding..y + h + padding {
+        let sy = (ty as i64 - y as i64).clamp(0, h as i64 - 1) as u32;
+        for tx in x - padding..x + w + padding {
+            let sx = (tx as i64 - x as i64).clamp(0, w as i64 - 1) as u32;
+            atlas.put_pixel(tx, ty, *src.get_pixel(sx, sy));
+        }
+    }
+}
+
+/// Materials that can be atlased, grouped by everything but their textures
+///
+/// A material qualifies when it has a base color texture no larger than `max_source_size`,
+/// every slot reads the same UV set without extensions, its extensions hold no textures, and
+/// every primitive drawing it has that UV set within `[0, 1]` in an accessor no other
+/// material reads. Only materials alike in every other property (factors, alpha mode, slots
+/// present, UV set and base color filtering) share a group.
+fn candidate_groups(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &AtlasOptions,
+) -> Result<Vec<Vec<Candidate>>, OptimizeError> {
+    let mut accessors: HashMap<usize, HashSet<usize>> = HashMap::new();
+    let mut readers: HashMap<usize, HashSet<usize>> = HashMap::new();
+    let mut blocked: HashSet<usize> = HashSet::new();
+    for primitive in o_json.meshes.iter().flat_map(|m| &m.primitives) {
+        let Some(m) = primitive.material.map(|m| m.value()) else {
+            continue;
+        };
+        let Some(mat) = o_json.materials.get(m) else {
+            continue;
+        };
+        if primitive.extensions.is_some() {
+            blocked.insert(m);
+        }
+        for (_, tex_coord, _) in slots(mat).into_iter().flatten() {
+            let semantic = Checked::Valid(Semantic::TexCoords(tex_coord));
+            match primitive.attributes.get(&semantic) {
+                Some(accessor) => {
+                    accessors.entry(m).or_default().insert(accessor.value());
+                    readers.entry(accessor.value()).or_default().insert(m);
+                }
+                None => {
+                    blocked.insert(m);
+                }
+            }
+        }
+    }
+
+    let image_of = |texture: Index<Texture>| {
+        o_json
+            .textures
+            .get(texture.value())
+            .map(|t| t.source.value())
+            .filter(|&i| i < o_json.images.len())
+    };
+    let mut keys: Vec<String> = Vec::new();
+    let mut groups: Vec<Vec<Candidate>> = Vec::new();
+    'materials: for (m, mat) in o_json.materials.iter().enumerate() {
+        let slots = slots(mat);
+        let Some((base, tex_coord, _)) = slots[0] else {
+            continue;
+        };
+        if blocked.contains(&m) || !accessors.contains_key(&m) {
+            continue;
+        }
+        if let Some(ext) = &mat.extensions {
+            let mut found = Vec::new();
+            extension_textures(&serde_json::to_value(ext)?, &mut found);
+            if !found.is_empty() {
+                continue;
+            }
+        }
+
+        let mut images = [None; 5];
+        let mut textures = [None; 5];
+        for (s, slot) in slots.iter().enumerate() {
+            let Some((texture, slot_tex_coord, has_extension)) = *slot else {
+                continue;
+            };
+            let image = image_of(texture);
+            if has_extension || slot_tex_coord != tex_coord || image.is_none() {
+                continue 'materials;
+            }
+            if get_image_data(o_blob, o_json, texture).is_none() {
+                continue 'materials;
+            }
+            images[s] = image;
+            textures[s] = Some(texture.value());
+        }
+        let Some(size) = get_image_data(o_blob, o_json, base).and_then(image_dimensions) else {
+            continue;
+        };
+        if size.0.max(size.1) > options.max_source_size {
+            continue;
+        }
+
+        let mut uvs = Vec::new();
+        for &acc in &accessors[&m] {
+            let Some(accessor) = o_json.accessors.get(acc) else {
+                continue 'materials;
+            };
+            if readers[&acc].len() > 1
+                || accessor.sparse.is_some()
+                || accessor.type_ != Checked::Valid(Type::Vec2)
+            {
+                continue 'materials;
+            }
+            let Some(data) = get_texcoord_data(o_blob, o_json, Index::new(acc as u32)) else {
+                continue 'materials;
+            };
+            let tiling = data
+                .iter()
+                .flatten()
+                .any(|&c| !(-UV_EPSILON..=1.0 + UV_EPSILON).contains(&c));
+            if tiling {
+                continue 'materials;
+            }
+            uvs.push((acc, data));
+        }
+
+        // Everything but the name and texture indices has to match
+        let mut key = mat.clone();
+        key.name = None;
+        set_slots(&mut key, [Some(Index::new(0)); 5]);
+        let filters = o_json.textures[base.value()]
+            .sampler
+            .and_then(|s| o_json.samplers.get(s.value()))
+            .map(|s| (s.mag_filter, s.min_filter));
+        let key = format!("{} {:?}", serde_json::to_string(&key)?, filters);
+
+        let candidate = Candidate {
+            material: m,
+            images,
+            textures,
+            size,
+            accessors: uvs,
+        };
+        match keys.iter().position(|k| *k == key) {
+            Some(g) => groups[g].push(candidate),
+            None => {
+                keys.push(key);
+                groups.push(vec![candidate]);
+            }
+        }
+    }
+    groups.retain(|g| g.len() > 1);
+    Ok(groups)
+}
+
+/// Pack the textures of similar materials into shared atlases and draw with one material each
+///
+/// Materials are grouped as in [`candidate_groups`] and shelf-packed by base color size. Each
+/// atlas page gets one PNG image per slot the group uses (other slots are scaled to the base
+/// color size), a texture with a clamp-to-edge copy of the first material's sampler, and a
+/// copy of the first material pointing at them. Primitives switch to that material and their
+/// UVs are remapped to the sub-rects. The old materials and textures are left unreferenced.
+/// Returns `None` when no page holds two or more materials.
+pub(crate) fn atlas_textures(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &AtlasOptions,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let padding = options.padding;
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut atlased = false;
+
+    for group in candidate_groups(o_blob, o_json, options)? {
+        let sizes: Vec<_> = group.iter().map(|c| c.size).collect();
+        for (page, (width, height)) in pack(&sizes, options.max_atlas_size, padding) {
+            if page.len() < 2 {
+                continue;
+            }
+            let first = &group[page[0].0];
+
+            let mut textures = [None; 5];
+            for (s, texture) in textures.iter_mut().enumerate() {
+                let Some(first_texture) = first.textures[s] else {
+                    continue;
+                };
+                let mut atlas = RgbaImage::new(width, height);
+                for &(i, x, y) in &page {
+                    let candidate = &group[i];
+                    let (Some(image), Some(source)) = (candidate.images[s], candidate.textures[s])
+                    else {
+                        continue;
+                    };
+                    let Some(data) = get_image_data(o_blob, o_json, Index::new(source as u32))
+                    else {
+                        continue;
+                    };
+                    let mut rgba = load_source_image(o_json, image, data, Vec::new())?.to_rgba8();
+                    let (w, h) = candidate.size;
+                    if rgba.dimensions() != (w, h) {
+                        rgba = imageops::resize(&rgba, w, h, imageops::FilterType::Triangle);
+                    }
+                    blit_padded(&mut atlas, &rgba, x, y, padding);
+                }
+
+                let mut png = Vec::new();
+                encode_png(
+                    atlas.as_raw(),
+                    width,
+                    height,
+                    image::ColorType::Rgba8,
+                    &mut png,
+                )?;
+                let view = n_blob.push_view(&mut n_json, &png, None);
+                let image = n_json.push(Image {
+                    buffer_view: Some(view),
+                    mime_type: Some(MimeType("image/png".to_string())),
+                    name: None,
+                    uri: None,
+                    extensions: None,
+                    extras: Default::default(),
+                });
+                let mut sampler = o_json.textures[first_texture]
+                    .sampler
+                    .and_then(|s| o_json.samplers.get(s.value()))
+                    .cloned()
+                    .unwrap_or_default();
+                sampler.wrap_s = Checked::Valid(WrappingMode::ClampToEdge);
+                sampler.wrap_t = Checked::Valid(WrappingMode::ClampToEdge);
+                let sampler: Index<Sampler> = n_json.push(sampler);
+                *texture = Some(n_json.push(Texture {
+                    name: None,
+                    sampler: Some(sampler),
+                    source: image,
+                    extensions: None,
+                    extras: Default::default(),
+                }));
+            }
+
+            let mut material = o_json.materials[first.material].clone();
+            set_slots(&mut material, textures);
+            let material = n_json.push(material);
+
+            let (atlas_w, atlas_h) = (width as f32, height as f32);
+            for &(i, x, y) in &page {
+                let candidate = &group[i];
+                let (w, h) = (candidate.size.0 as f32, candidate.size.1 as f32);
+                for (acc, uvs) in &candidate.accessors {
+                    let remapped = uvs.iter().map(|uv| {
+                        let [u, v] = uv.map(|c| c.clamp(0.0, 1.0));
+                        [(x as f32 + u * w) / atlas_w, (y as f32 + v * h) / atlas_h]
+                    });
+                    write_uvs(&mut n_blob, &mut n_json, *acc, remapped);
+                }
+                for primitive in n_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+                    if primitive.material.map(|m| m.value()) == Some(candidate.material) {
+                        primitive.material = Some(material);
+                    }
+                }
+            }
+            atlased = true;
+        }
+    }
+
+    Ok(atlased.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::mesh::Semantic;
+
+    use super::*;
+    use crate::{
+        glb::read_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn small_textures_are_packed_into_an_atlas() {
+        let glb = SyntheticGlb {
+            textures: 3,
+            primitives_per_mesh: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let (n_json, n_blob) = atlas_textures(&o_blob, &o_json, &AtlasOptions::default())
+            .unwrap()
+            .unwrap();
+        let primitives = &n_json.meshes[0].primitives;
+        let material = primitives[0].material.unwrap();
+        assert!(primitives.iter().all(|p| p.material == Some(material)));
+        let pbr = &n_json.materials[material.value()].pbr_metallic_roughness;
+        let texture = pbr.base_color_texture.as_ref().unwrap().index;
+        let data = get_image_data(&n_blob, &n_json, texture).unwrap();
+        let atlas = image::load_from_memory(data).unwrap().to_rgba8();
+        // Three 32px textures with 4 texels of padding, side by side
+        assert_eq!(atlas.dimensions(), (120, 40));
+
+        // Each primitive samples its own texture, tinted by its index
+        let semantic = Checked::Valid(Semantic::TexCoords(0));
+        for (p, primitive) in primitives.iter().enumerate() {
+            let uvs = get_texcoord_data(&n_blob, &n_json, primitive.attributes[&semantic]);
+            let uvs = uvs.unwrap();
+            let center = [0, 1].map(|c| (uvs[0][c] + uvs[2][c]) / 2.0);
+            let x = (center[0] * 120.0) as u32;
+            let y = (center[1] * 40.0) as u32;
+            assert_eq!(atlas.get_pixel(x, y)[2], (p * 67 % 256) as u8);
+        }
+
+        // Through the whole pipeline the merged primitive draws with one material
+        let options = OptimizeOptions {
+            atlas_textures: Some(AtlasOptions::default()),
+            merge_primitives: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(n_json.materials.len(), 1);
+        assert_eq!(n_json.textures.len(), 1);
+        assert_eq!(n_json.meshes[0].primitives.len(), 1);
+    }
+
+    #[test]
+    fn dangling_uv_accessors_are_left_out() {
+        let glb = SyntheticGlb {
+            textures: 2,
+            primitives_per_mesh: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        o_json.meshes[0].primitives[1]
+            .attributes
+            .insert(Checked::Valid(Semantic::TexCoords(0)), Index::new(99));
+        let atlas = atlas_textures(&o_blob, &o_json, &AtlasOptions::default()).unwrap();
+        assert!(atlas.is_none());
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    any::Any,
+    fmt,
+    io::{Read, Seek},
+};
+
+use gltf::json::{Index, Root};
+use image::{RgbaImage, imageops};
+
+use crate::{
+    decode::load_image,
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    opt::{get_image_data, optimize_document},
+    options::OptimizeOptions,
+    simplify::{SimplifyOptions, SimplifyTarget},
+    stats::{compute_gpu_stats, image_dimensions},
+    transcode::{TextureCompressionProfile, TextureCompressionProfiles},
+};
+
+/// Candidates optimized at once; each encodes its textures on its own threads as well
+const PARALLEL_CANDIDATES: usize = 4;
+
+/// Smallest texture size tried
+const MIN_TEXTURE_SIZE: u32 = 64;
+
+/// Basis ETC1S quality levels tried for color and data textures when KTX2 output is built in
+const BASIS_QUALITIES: [u32; 2] = [200, 96];
+
+/// Limits the output of [`auto_optimize`] must stay within; unset limits don't constrain
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Objective {
+    /// Largest GLB size in bytes
+    pub max_bytes: Option<u64>,
+    /// Lowest PSNR in dB any texture may have against its source
+    pub min_psnr: Option<f64>,
+    /// Most triangles the default scene may draw, counting every mesh instance
+    pub max_triangles: Option<u64>,
+}
+
+/// Output [`auto_optimize`] picked, with the settings that produced it and its measurements
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct AutoOptimized {
+    pub glb: Vec<u8>,
+    pub options: OptimizeOptions,
+    pub bytes: u64,
+    /// PSNR in dB of the worst texture against its source, `None` without measured textures
+    pub psnr: Option<f64>,
+    /// Triangles the default scene draws
+    pub triangles: u64,
+    /// How many configurations were tried
+    pub candidates: usize,
+}
+
+/// Measurements of one candidate [`auto_optimize`] tried
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct CandidateMeasurement {
+    pub bytes: u64,
+    /// PSNR in dB of the worst texture against its source, `None` without measured textures
+    pub psnr: Option<f64>,
+    /// Triangles the default scene draws
+    pub triangles: u64,
+}
+
+impl fmt::Display for CandidateMeasurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes, {} triangles", self.bytes, self.triangles)?;
+        match self.psnr {
+            Some(psnr) => write!(f, " and a worst texture PSNR of {psnr:.1} dB"),
+            None => f.write_str(" and no measured textures"),
+        }
+    }
+}
+
+/// One candidate's output and measurements
+struct Evaluated {
+    options: OptimizeOptions,
+    glb: Vec<u8>,
+    psnr: Option<f64>,
+    triangles: u64,
+}
+
+impl Evaluated {
+    fn measurement(&self) -> CandidateMeasurement {
+        CandidateMeasurement {
+            bytes: self.glb.len() as u64,
+            psnr: self.psnr,
+            triangles: self.triangles,
+        }
+    }
+
+    fn meets(&self, objective: &Objective) -> bool {
+        objective
+            .max_bytes
+            .is_none_or(|max| self.glb.len() as u64 <= max)
+            && objective
+                .min_psnr
+                .is_none_or(|min| self.psnr.is_none_or(|psnr| psnr >= min))
+            && objective
+                .max_triangles
+                .is_none_or(|max| self.triangles <= max)
+    }
+}
+
+/// Decode an encoded texture to RGBA8, transcoding Basis Universal KTX2 when built in
+fn decode_rgba(data: &[u8]) -> Option<RgbaImage> {
+    load_image(data, Vec::new())
+        .ok()
+        .map(|image| image.to_rgba8())
+        .or_else(|| decode_basis(data))
+}
+
+#[cfg(feature = "ktx2")]
+fn decode_basis(data: &[u8]) -> Option<RgbaImage> {
+    let mut texture = ktx2_rw::Ktx2Texture::from_memory(data).ok()?;
+    if !texture.needs_transcoding() {
+        return None;
+    }
+    texture
+        .transcode_basis(ktx2_rw::TranscodeFormat::Rgba32)
+        .ok()?;
+    let (width, height) = (texture.width(), texture.height());
+    RgbaImage::from_raw(
+        width,
+        height,
+        texture.get_image_data(0, 0, 0).ok()?.to_vec(),
+    )
+}
+
+#[cfg(not(feature = "ktx2"))]
+fn decode_basis(_data: &[u8]) -> Option<RgbaImage> {
+    None
+}
+
+/// PSNR of `output` against `source`, scaling `output` back up to the source size first so
+/// downscaling counts as loss too
+fn psnr(source: &RgbaImage, output: &RgbaImage) -> f64 {
+    let (width, height) = source.dimensions();
+    let scaled;
+    let output = if output.dimensions() == (width, height) {
+        output
+    } else {
+        scaled = imageops::resize(output, width, height, imageops::FilterType::Triangle);
+        &scaled
+    };
+    let squared: f64 = source
+        .as_raw()
+        .iter()
+        .zip(output.as_raw())
+        .map(|(&s, &o)| (s as f64 - o as f64).powi(2))
+        .sum();
+    let mse = squared / source.as_raw().len().max(1) as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+/// ETC1S profiles at `quality` for every slot but normal maps, which keep their default
+fn basis_profiles(quality: u32) -> TextureCompressionProfiles {
+    let defaults = TextureCompressionProfiles::default();
+    let at = |profile: TextureCompressionProfile| TextureCompressionProfile {
+        quality_level: quality,
+        ..profile
+    };
+    TextureCompressionProfiles {
+        base_color: at(defaults.base_color),
+        metallic_roughness: at(defaults.metallic_roughness),
+        occlusion: at(defaults.occlusion),
+        emissive: at(defaults.emissive),
+        ..defaults
+    }
+}
+
+/// The configurations to try: texture sizes from the largest source side down, Basis
+/// qualities when KTX2 output is built in, and simplification when there are too many
+/// triangles
+fn candidates(o_blob: &[u8], o_json: &Root, objective: &Objective) -> Vec<OptimizeOptions> {
+    let defaults = OptimizeOptions::default();
+    let largest = (0..o_json.textures.len())
+        .filter_map(|t| get_image_data(o_blob, o_json, Index::new(t as u32)))
+        .filter_map(image_dimensions)
+        .map(|(w, h)| w.max(h))
+        .max();
+    let mut sizes: Vec<u32> = match largest {
+        Some(largest) => (0..4)
+            .map(|halvings| (largest >> halvings).max(MIN_TEXTURE_SIZE))
+            .collect(),
+        None => vec![defaults.texture_size],
+    };
+    sizes.dedup();
+
+    let mut formats = vec![None];
+    if cfg!(feature = "ktx2") && largest.is_some() {
+        formats.extend(BASIS_QUALITIES.map(Some));
+    }
+
+    let triangles = compute_gpu_stats(o_blob, o_json).triangles;
+    let simplify = match objective.max_triangles {
+        Some(max) if triangles > max => {
+            let ratio = max as f32 / triangles as f32;
+            [0.95, 0.7]
+                .map(|margin| {
+                    Some(SimplifyOptions {
+                        target: SimplifyTarget::Ratio(ratio * margin),
+                        ..Default::default()
+                    })
+                })
+                .to_vec()
+        }
+        _ => vec![None],
+    };
+
+    let mut candidates = Vec::new();
+    for &simplify in &simplify {
+        for &texture_size in &sizes {
+            for &quality in &formats {
+                candidates.push(OptimizeOptions {
+                    texture_size,
+                    convert_to_ktx2: quality.is_some(),
+                    compression_profiles: quality
+                        .map_or(defaults.compression_profiles, basis_profiles),
+                    simplify,
+                    // Candidates already run side by side
+                    encoder_threads: Some(1),
+                    ..defaults.clone()
+                });
+            }
+        }
+    }
+    candidates
+}
+
+/// Error for a candidate whose evaluation panicked, keeping the panic message when it has one
+fn panicked(panic: Box<dyn Any + Send>) -> OptimizeError {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|m| m.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    OptimizeError::Panicked {
+        task: "candidate evaluation",
+        message,
+    }
+}
+
+/// Optimize the document with `options` and measure the result
+fn evaluate(
+    o_blob: &[u8],
+    o_json: &Root,
+    sources: &[Option<RgbaImage>],
+    options: OptimizeOptions,
+) -> Result<Evaluated, OptimizeError> {
+    let (mut n_json, n_blob, remap) = optimize_document(o_blob, o_json, &options, None, None)?;
+    retain_extensions(&mut n_json, &[])?;
+
+    let triangles = compute_gpu_stats(&n_blob, &n_json).triangles;
+    let psnr = sources
+        .iter()
+        .enumerate()
+        .filter_map(|(texture, source)| {
+            let source = source.as_ref()?;
+            let new = remap.texture(texture)?;
+            let data = get_image_data(&n_blob, &n_json, Index::new(new as u32))?;
+            Some(psnr(source, &decode_rgba(data)?))
+        })
+        .reduce(f64::min);
+    Ok(Evaluated {
+        options,
+        glb: write_glb(&n_json, n_blob.into_vec())?,
+        psnr,
+        triangles,
+    })
+}
+
+/// Experimental: search a few configurations for the best output meeting `objective`
+///
+/// Tries texture sizes from the largest source texture down to an eighth of it, Basis
+/// Universal ETC1S at two quality levels when the `ktx2` feature is enabled, and, when the
+/// default scene draws more than `max_triangles`, simplification just under the limit. Up to
+/// four candidates are optimized at once. Texture quality is the PSNR of the worst texture
+/// against its source, with the output scaled back up to the source size.
+///
+/// With `max_bytes` set, the best output is the one of highest texture quality (then most
+/// triangles) that fits; without it, the smallest output meeting the other limits. Fails
+/// with [`OptimizeError::ObjectiveUnmet`] when no candidate meets every limit, and with the
+/// first candidate's error when none could be optimized at all. A candidate that panics
+/// counts as failed with [`OptimizeError::Panicked`].
+pub fn auto_optimize<R: Read + Seek>(
+    reader: &mut R,
+    objective: Objective,
+) -> Result<AutoOptimized, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let sources: Vec<Option<RgbaImage>> = (0..o_json.textures.len())
+        .map(|t| get_image_data(&o_blob, &o_json, Index::new(t as u32)).and_then(decode_rgba))
+        .collect();
+    let candidates = candidates(&o_blob, &o_json, &objective);
+    let count = candidates.len();
+
+    let mut evaluated = Vec::new();
+    let mut first_error = None;
+    for batch in candidates.chunks(PARALLEL_CANDIDATES) {
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|options| {
+                    let (o_blob, o_json, sources) = (&o_blob, &o_json, &sources);
+                    scope.spawn(move || evaluate(o_blob, o_json, sources, options.clone()))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|panic| Err(panicked(panic))))
+                .collect()
+        });
+        for result in results {
+            match result {
+                Ok(candidate) => evaluated.push(candidate),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+    if evaluated.is_empty()
+        && let Some(e) = first_error
+    {
+        return Err(e);
+    }
+
+    let quality = |e: &Evaluated| (e.psnr.unwrap_or(f64::INFINITY), e.triangles);
+    let feasible = evaluated
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.meets(&objective));
+    let best = if objective.max_bytes.is_some() {
+        feasible
+            .max_by(|(i, a), (j, b)| {
+                let (qa, qb) = (quality(a), quality(b));
+                qa.0.total_cmp(&qb.0)
+                    .then(qa.1.cmp(&qb.1))
+                    .then(b.glb.len().cmp(&a.glb.len()))
+                    .then(j.cmp(i))
+            })
+            .map(|(i, _)| i)
+    } else {
+        feasible
+            .min_by_key(|(i, e)| (e.glb.len(), *i))
+            .map(|(i, _)| i)
+    };
+
+    let Some(best) = best else {
+        let smallest = evaluated.iter().min_by_key(|e| e.glb.len());
+        return Err(OptimizeError::ObjectiveUnmet {
+            smallest: smallest.map(Evaluated::measurement),
+        });
+    };
+    let best = evaluated.swap_remove(best);
+    Ok(AutoOptimized {
+        bytes: best.glb.len() as u64,
+        glb: best.glb,
+        options: best.options,
+        psnr: best.psnr,
+        triangles: best.triangles,
+        candidates: count,
+    })
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    #[test]
+    fn auto_optimize_meets_the_objective() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            texture_size: 256,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run = |objective| auto_optimize(&mut Cursor::new(&glb), objective);
+
+        // Without a size limit the smallest output wins
+        let smallest = run(Objective::default()).unwrap();
+        assert_eq!(smallest.options.texture_size, 64);
+        assert_eq!(smallest.bytes, smallest.glb.len() as u64);
+        assert_eq!(smallest.triangles, 4);
+
+        // With one, the best quality that fits
+        let best = run(Objective {
+            max_bytes: Some(u64::MAX),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(best.options.texture_size, 256);
+        assert!(best.psnr.unwrap() > smallest.psnr.unwrap());
+        let tight = run(Objective {
+            max_bytes: Some(smallest.bytes),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(tight.bytes <= smallest.bytes);
+
+        // Small planar quads are never simplified, so no candidate gets under 4 triangles
+        let unreachable = run(Objective {
+            max_triangles: Some(2),
+            ..Default::default()
+        });
+        let Err(OptimizeError::ObjectiveUnmet {
+            smallest: Some(closest),
+        }) = unreachable
+        else {
+            panic!("expected an unmet objective, got {unreachable:?}");
+        };
+        assert_eq!(closest.triangles, 4);
+        assert_eq!(closest.bytes, smallest.bytes);
+        assert_eq!(closest.psnr, smallest.psnr);
+    }
+
+    #[test]
+    fn panicked_candidates_become_errors() {
+        let panic = std::thread::spawn(|| panic!("out of {}", "memory"))
+            .join()
+            .unwrap_err();
+        let error = panicked(panic);
+        assert!(matches!(
+            &error,
+            OptimizeError::Panicked { message, .. } if message == "out of memory"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "candidate evaluation panicked: out of memory"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    manifest::asset_manifest,
+    opt::{TextureType, optimize_document_cached},
+    options::OptimizeOptions,
+};
+
+/// Version of the state file layout, bumped when entries change meaning
+const STATE_VERSION: u64 = 1;
+
+/// 64-bit FNV-1a, stable across runs and builds so keys written by one run match the next
+fn fnv1a(seed: u64, data: &[u8]) -> u64 {
+    data.iter().fold(seed, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Hash of every setting, so state and cached textures of other settings are never reused
+fn options_hash(options: &OptimizeOptions) -> u64 {
+    fnv1a(FNV_OFFSET, format!("{options:?}").as_bytes())
+}
+
+/// Write through a temporary file renamed into place, so an interrupted write leaves the old
+/// file or none
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+/// Manifest written beside `output`, its path with the extension `manifest.json`
+fn manifest_path(output: &Path) -> PathBuf {
+    output.with_extension("manifest.json")
+}
+
+/// Encoded textures kept on disk between runs of a [`BatchJob`], one file per encode
+///
+/// Reads and writes are best effort: a missing or unwritable entry is simply encoded again.
+pub(crate) struct TextureCache {
+    dir: PathBuf,
+    options: u64,
+}
+
+impl TextureCache {
+    /// Key of one encode of a source image into a slot of `size`
+    ///
+    /// The encoded source file is hashed when there is one, the decoded pixels otherwise.
+    pub(crate) fn key(
+        &self,
+        img: &DynamicImage,
+        img_data: &[u8],
+        size: u32,
+        texture_type: TextureType,
+        ktx2: bool,
+    ) -> u64 {
+        let source = match img_data {
+            [] => fnv1a(
+                fnv1a(
+                    FNV_OFFSET,
+                    &[img.width(), img.height()].map(u32::to_le_bytes).concat(),
+                ),
+                img.as_bytes(),
+            ),
+            data => fnv1a(FNV_OFFSET, data),
+        };
+        let slot = format!("{source:x} {size} {} {ktx2}", texture_type.label());
+        fnv1a(self.options, slot.as_bytes())
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.bin"))
+    }
+
+    pub(crate) fn get(&self, key: u64) -> Option<Vec<u8>> {
+        fs::read(self.path(key)).ok()
+    }
+
+    pub(crate) fn put(&self, key: u64, data: &[u8]) {
+        if fs::create_dir_all(&self.dir).is_ok() {
+            let _ = write_atomic(&self.path(key), data);
+        }
+    }
+}
+
+/// Outcome of a [`BatchJob`] run
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct BatchReport {
+    /// Inputs optimized by this run
+    pub optimized: Vec<PathBuf>,
+    /// Inputs a previous run already completed with the same contents and settings
+    pub skipped: Vec<PathBuf>,
+    /// Inputs that could not be read, optimized or written, with the reason
+    pub failed: Vec<(PathBuf, OptimizeError)>,
+}
+
+/// Optimize many GLB files with one set of options, optionally resumable after an interruption
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    pub options: OptimizeOptions,
+    /// Input and output path of each file, processed in order
+    pub files: Vec<(PathBuf, PathBuf)>,
+    /// Write an [`asset_manifest`](crate::asset_manifest) beside every output,
+    /// `out/chair.glb` getting `out/chair.manifest.json`
+    pub manifests: bool,
+}
+
+impl BatchJob {
+    pub fn new(options: OptimizeOptions) -> Self {
+        BatchJob {
+            options,
+            files: Vec::new(),
+            manifests: false,
+        }
+    }
+
+    /// Write a manifest beside every output, see [`BatchJob::manifests`]
+    pub fn manifests(mut self, manifests: bool) -> Self {
+        self.manifests = manifests;
+        self
+    }
+
+    /// Add a file to optimize from `input` to `output`
+    pub fn file(mut self, input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        self.files.push((input.into(), output.into()));
+        self
+    }
+
+    /// Optimize every file, carrying on past failures
+    pub fn run(&self) -> BatchReport {
+        let mut report = BatchReport::default();
+        for (input, output) in &self.files {
+            match self.optimize_file(input, output, None) {
+                Ok(_) => report.optimized.push(input.clone()),
+                Err(e) => report.failed.push((input.clone(), e)),
+            }
+        }
+        report
+    }
+
+    /// Optimize every file not completed by an earlier run recorded in `state_path`
+    ///
+    /// The state file lists each completed input with a hash of its contents and of the
+    /// options, and is rewritten after every file. An input is skipped when its entry matches
+    /// and its output still exists. Encoded textures are kept in a directory next to the state
+    /// file (its path with the extension `textures`), so a file interrupted mid-way only
+    /// encodes the textures it had not reached; the directory is removed once every file is
+    /// done. Fails only when the state file can't be read or written.
+    pub fn resume(&self, state_path: impl AsRef<Path>) -> Result<BatchReport, OptimizeError> {
+        let state_path = state_path.as_ref();
+        let options = options_hash(&self.options);
+        let mut completed = match fs::read(state_path) {
+            Ok(data) => {
+                let state: Value = serde_json::from_slice(&data)?;
+                match state["version"].as_u64() {
+                    Some(STATE_VERSION) => state["files"].as_object().cloned().unwrap_or_default(),
+                    _ => Map::new(),
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let cache = TextureCache {
+            dir: state_path.with_extension("textures"),
+            options,
+        };
+
+        let mut report = BatchReport::default();
+        for (input, output) in &self.files {
+            let key = input.to_string_lossy().into_owned();
+            let input_hash = fs::read(input).ok().map(|data| fnv1a(FNV_OFFSET, &data));
+            let done = completed.get(&key).is_some_and(|entry| {
+                input_hash.is_some_and(|h| entry["input"] == format!("{h:016x}"))
+                    && entry["options"] == format!("{options:016x}")
+                    && entry["output"] == output.to_string_lossy().as_ref()
+                    && output.exists()
+                    && (!self.manifests || manifest_path(output).exists())
+            });
+            if done {
+                report.skipped.push(input.clone());
+                continue;
+            }
+
+            match self.optimize_file(input, output, Some(&cache)) {
+                Ok(input_hash) => {
+                    completed.insert(
+                        key,
+                        json!({
+                            "output": output.to_string_lossy(),
+                            "input": format!("{input_hash:016x}"),
+                            "options": format!("{options:016x}"),
+                        }),
+                    );
+                    let state = json!({ "version": STATE_VERSION, "files": completed });
+                    write_atomic(state_path, &serde_json::to_vec_pretty(&state)?)?;
+                    report.optimized.push(input.clone());
+                }
+                Err(e) => report.failed.push((input.clone(), e)),
+            }
+        }
+
+        if report.failed.is_empty() && cache.dir.exists() {
+            fs::remove_dir_all(&cache.dir)?;
+        }
+        Ok(report)
+    }
+
+    /// Optimize one file, returning the hash of its input
+    fn optimize_file(
+        &self,
+        input: &Path,
+        output: &Path,
+        cache: Option<&TextureCache>,
+    ) -> Result<u64, OptimizeError> {
+        let data = fs::read(input)?;
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&data))?;
+        let (mut n_json, n_blob, _) =
+            optimize_document_cached(&o_blob, &o_json, &self.options, cache)?;
+        retain_extensions(&mut n_json, &[])?;
+        let glb = write_glb(&n_json, n_blob)?;
+        fs::write(output, &glb)?;
+        if self.manifests {
+            let manifest = asset_manifest(&mut Cursor::new(&glb))?;
+            fs::write(manifest_path(output), manifest)?;
+        }
+        Ok(fnv1a(FNV_OFFSET, &data))
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use super::*;
+    use crate::{opt::optimize_with, testing::SyntheticGlb};
+
+    /// Directory under the system temp dir, removed when the test ends, passed or failed
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("gltf_opt_{name}_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            ScratchDir(dir)
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn batch_jobs_resume_where_they_stopped() {
+        let scratch = ScratchDir::new("batch");
+        let dir = &scratch.0;
+        let glb = |textures| {
+            SyntheticGlb {
+                textures,
+                primitives_per_mesh: textures,
+                ..Default::default()
+            }
+            .build()
+            .unwrap()
+        };
+        let (one, two) = (glb(1), glb(2));
+        std::fs::write(dir.join("one.glb"), &one).unwrap();
+        std::fs::write(dir.join("two.glb"), &two).unwrap();
+        std::fs::write(dir.join("broken.glb"), b"not a glb").unwrap();
+
+        let options = OptimizeOptions::default();
+        let job = BatchJob::new(options.clone())
+            .file(dir.join("one.glb"), dir.join("one.opt.glb"))
+            .file(dir.join("broken.glb"), dir.join("broken.opt.glb"))
+            .file(dir.join("two.glb"), dir.join("two.opt.glb"));
+        let state = dir.join("batch.json");
+        let report = job.resume(&state).unwrap();
+        assert_eq!(report.optimized.len(), 2);
+        assert_eq!(report.failed.len(), 1);
+        let expected = optimize_with(&mut Cursor::new(&two), &options).unwrap();
+        assert_eq!(std::fs::read(dir.join("two.opt.glb")).unwrap(), expected);
+        // Kept while a file is still to do, with the texture both files share stored once
+        let cached = std::fs::read_dir(dir.join("batch.textures"))
+            .unwrap()
+            .count();
+        assert_eq!(cached, 2);
+
+        // Completed files are skipped, and a lost output is rebuilt from cached textures
+        std::fs::remove_file(dir.join("two.opt.glb")).unwrap();
+        let report = job.resume(&state).unwrap();
+        assert_eq!(report.skipped, [dir.join("one.glb")]);
+        assert_eq!(report.optimized, [dir.join("two.glb")]);
+        assert_eq!(std::fs::read(dir.join("two.opt.glb")).unwrap(), expected);
+
+        // Other settings redo everything, and the cache goes once nothing fails
+        let job = BatchJob {
+            options: OptimizeOptions {
+                texture_size: 8,
+                ..options
+            },
+            files: vec![(dir.join("one.glb"), dir.join("one.opt.glb"))],
+            manifests: false,
+        };
+        let report = job.resume(&state).unwrap();
+        assert_eq!(report.optimized, [dir.join("one.glb")]);
+        assert!(!dir.join("batch.textures").exists());
+    }
+
+    #[test]
+    fn batch_manifests_list_output_resources() {
+        let scratch = ScratchDir::new("manifest");
+        let dir = &scratch.0;
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        std::fs::write(dir.join("chair.glb"), &glb).unwrap();
+
+        let report = BatchJob::new(OptimizeOptions::default())
+            .file(dir.join("chair.glb"), dir.join("chair.opt.glb"))
+            .manifests(true)
+            .run();
+        assert!(report.failed.is_empty());
+        let output = std::fs::read(dir.join("chair.opt.glb")).unwrap();
+        let written = std::fs::read_to_string(dir.join("chair.opt.manifest.json")).unwrap();
+        assert_eq!(written, asset_manifest(&mut Cursor::new(&output)).unwrap());
+
+        let manifest: serde_json::Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(manifest["bytes"], output.len());
+        let textures = manifest["textures"].as_array().unwrap();
+        assert_eq!(textures.len(), 2);
+        assert_eq!(textures[0]["width"], 32);
+        assert!(textures[0]["bytes"].as_u64().unwrap() > 0);
+        let meshes = manifest["meshes"].as_array().unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert!(meshes[0]["triangles"].as_u64().unwrap() > 0);
+        assert_eq!(manifest["animations"], serde_json::json!([]));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::fallback::TextureEncoding;
+
+/// What [`OptimizeOptions::best_effort`](crate::OptimizeOptions::best_effort) or a texture
+/// fallback chain did with something that failed to optimize
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum BestEffortFallback {
+    /// Texture encoded with the next encoding of its slot's
+    /// [`texture_fallbacks`](crate::OptimizeOptions::texture_fallbacks) chain
+    EncoderFallback(TextureEncoding),
+    /// Textures copied without resizing or re-encoding
+    VerbatimTextures,
+    /// Textures removed, leaving the material's factors
+    TexturesDropped,
+    /// Geometry copied without the geometry codec
+    UnencodedGeometry,
+}
+
+/// Input object that failed to optimize and was copied or stripped instead
+///
+/// Indices refer to the document after source rewrites such as
+/// [`OptimizeOptions::split_primitives`](crate::OptimizeOptions::split_primitives); without
+/// those they are input indices.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct BestEffortIssue {
+    pub material: Option<usize>,
+    pub texture: Option<usize>,
+    /// Mesh and primitive index
+    pub primitive: Option<(usize, usize)>,
+    pub error: String,
+    pub fallback: BestEffortFallback,
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        meshopt::chunk_range,
+        opt::{ImageFormat, get_image_data, optimize_with},
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    /// Two textured quads whose first image is zeroed out
+    fn broken_texture_glb() -> Vec<u8> {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, mut blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let view = &json.buffer_views[json.images[0].buffer_view.unwrap().value()];
+        blob[chunk_range(view)].fill(0);
+        write_glb(&json, blob).unwrap()
+    }
+
+    #[test]
+    fn broken_textures_are_copied_verbatim() {
+        let glb = broken_texture_glb();
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        assert!(optimize_with(&mut Cursor::new(&glb), &options).is_err());
+
+        let best_effort = OptimizeOptions {
+            best_effort: true,
+            ..options.clone()
+        };
+        let optimized = Optimizer::new(&best_effort)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let issues = optimized.issues;
+        assert_eq!(issues.len(), 1);
+        let issue = &issues[0];
+        assert_eq!(issue.material, Some(0));
+        assert_eq!(issue.fallback, BestEffortFallback::VerbatimTextures);
+        assert!(!issue.error.is_empty());
+
+        let (json, blob) = read_glb(&mut Cursor::new(optimized.glb)).unwrap();
+        assert_eq!(json.materials.len(), 2);
+        let texture = json.materials[0]
+            .pbr_metallic_roughness
+            .base_color_texture
+            .as_ref()
+            .unwrap()
+            .index;
+        let data = get_image_data(&blob, &json, texture).unwrap();
+        assert!(data.iter().all(|&b| b == 0));
+    }
+}
@@ -0,0 +1,115 @@
+/// Command line split into positional arguments and `--name[=value]` options
+///
+/// Options are taken out as they are read; whatever is left at [`Args::finish`] is unknown.
+pub struct Args {
+    pub positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Split `args`; options named in `valued` take the next argument as their value unless
+    /// written as `--name=value`
+    pub fn parse(args: impl IntoIterator<Item = String>, valued: &[&str]) -> Result<Args, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.to_string(),
+                None if arg == "-o" => "output".to_string(),
+                None => {
+                    positional.push(arg);
+                    continue;
+                }
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None if valued.contains(&name.as_str()) => {
+                    let value = args.next().ok_or(format!("--{name} needs a value"))?;
+                    (name, Some(value))
+                }
+                None => (name, None),
+            };
+            options.push((name, value));
+        }
+        Ok(Args {
+            positional,
+            options,
+        })
+    }
+
+    /// Whether the valueless option `--name` was given
+    pub fn flag(&mut self, name: &str) -> Result<bool, String> {
+        let values = self.take(name);
+        match values.iter().any(Option::is_some) {
+            true => Err(format!("--{name} takes no value")),
+            false => Ok(!values.is_empty()),
+        }
+    }
+
+    /// Value of `--name`, the last one when given several times
+    pub fn value(&mut self, name: &str) -> Option<String> {
+        self.take(name).into_iter().flatten().last()
+    }
+
+    /// Every value of `--name`, comma-separated lists split
+    pub fn values(&mut self, name: &str) -> Vec<String> {
+        let values = self.take(name).into_iter().flatten();
+        values
+            .flat_map(|v| v.split(',').map(str::to_string).collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn take(&mut self, name: &str) -> Vec<Option<String>> {
+        let (taken, rest) = std::mem::take(&mut self.options)
+            .into_iter()
+            .partition(|(n, _)| n == name);
+        self.options = rest;
+        taken.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// Fail on options nobody asked for
+    pub fn finish(self) -> Result<Vec<String>, String> {
+        match self.options.first() {
+            Some((name, _)) => Err(format!("unknown option --{name}")),
+            None => Ok(self.positional),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Args {
+        Args::parse(
+            line.split(' ').map(str::to_string),
+            &["output", "texture-size"],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn options_take_values_only_when_declared() {
+        let mut args = parse("in.glb -o out.glb --ktx2 --texture-size 512 --texture-size=256");
+        assert_eq!(args.value("output").as_deref(), Some("out.glb"));
+        assert_eq!(args.value("texture-size").as_deref(), Some("256"));
+        assert_eq!(args.flag("ktx2"), Ok(true));
+        assert_eq!(args.flag("stats"), Ok(false));
+        assert_eq!(args.finish().unwrap(), ["in.glb"]);
+
+        let mut args = parse("in.glb --fail-on=a,b --fail-on=c");
+        assert_eq!(args.values("fail-on"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn misused_options_are_errors() {
+        assert!(parse("in.glb --stats=yes").flag("stats").is_err());
+        assert_eq!(
+            parse("in.glb --colour").finish().unwrap_err(),
+            "unknown option --colour"
+        );
+        let missing = Args::parse(["--output".to_string()], &["output"]);
+        assert_eq!(missing.err().as_deref(), Some("--output needs a value"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::process::ExitCode;
+
+use gltf_opt::OptimizeError;
+
+/// Exit codes, kept stable so CI scripts can branch on them
+///
+/// 1 covers bad command lines and failures that are not the input's fault, such as an
+/// output that can't be written.
+pub const FAILED: u8 = 1;
+/// Warnings the `--fail-on-warning` or `--fail-on` flags ask to fail on
+pub const WARNINGS: u8 = 2;
+/// The input can't be read or is not a valid glTF/GLB
+pub const INPUT_ERROR: u8 = 3;
+/// The input needs something this build or this crate doesn't support
+pub const UNSUPPORTED: u8 = 4;
+
+/// Why a run failed, with the exit code to report it with
+#[derive(Debug)]
+pub struct Failure {
+    pub code: u8,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(code: u8, message: impl Into<String>) -> Self {
+        Failure {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Failure to optimize or read `path`, coded by what went wrong
+    pub fn optimize(path: &str, error: OptimizeError) -> Self {
+        Failure::new(error_code(&error), format!("{path}: {error}"))
+    }
+
+    pub fn exit(&self) -> ExitCode {
+        eprintln!("gltf-opt: {}", self.message);
+        ExitCode::from(self.code)
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::new(FAILED, message)
+    }
+}
+
+impl From<&str> for Failure {
+    fn from(message: &str) -> Self {
+        Failure::new(FAILED, message)
+    }
+}
+
+fn error_code(error: &OptimizeError) -> u8 {
+    if error.feature_not_enabled().is_some() {
+        return UNSUPPORTED;
+    }
+    match error {
+        OptimizeError::UnsupportedVersion { .. }
+        | OptimizeError::Unsupported { .. }
+        | OptimizeError::UnsupportedImage { .. } => UNSUPPORTED,
+        OptimizeError::InvalidInput(_)
+        | OptimizeError::Json(_)
+        | OptimizeError::Decode { .. }
+        | OptimizeError::Accessor { .. }
+        | OptimizeError::TextureOutOfBounds { .. }
+        | OptimizeError::MissingObject { .. }
+        | OptimizeError::MissingAttribute { .. }
+        | OptimizeError::MissingImageData { .. }
+        | OptimizeError::ImageSize { .. }
+        | OptimizeError::Truncated { .. }
+        | OptimizeError::NotFormat { .. }
+        | OptimizeError::MissingChunk { .. }
+        | OptimizeError::Malformed(_)
+        | OptimizeError::Hint { .. }
+        | OptimizeError::CubeMapFaces { .. }
+        | OptimizeError::Rejected { .. } => INPUT_ERROR,
+        OptimizeError::Texture { source, .. } => error_code(source),
+        _ => FAILED,
+    }
+}
+
+/// Which warnings fail the run: all of them with `--fail-on-warning`, or the kinds named
+/// with `--fail-on`
+pub struct WarningGate {
+    all: bool,
+    kinds: Vec<String>,
+}
+
+impl WarningGate {
+    /// Gate for the flags, failing on kinds no warning has
+    pub fn new(all: bool, kinds: Vec<String>, known: &[&str]) -> Result<Self, Failure> {
+        if let Some(kind) = kinds.iter().find(|k| !known.contains(&k.as_str())) {
+            let known = known.join(", ");
+            return Err(format!("unknown warning type {kind}, expected one of {known}").into());
+        }
+        Ok(WarningGate { all, kinds })
+    }
+
+    pub fn fails_on(&self, kind: &str) -> bool {
+        self.all || self.kinds.iter().any(|k| k == kind)
+    }
+
+    /// Print `(kind, message)` warnings to stderr, failing with [`WARNINGS`] when one of
+    /// them is gated
+    pub fn check(&self, path: &str, warnings: &[(&str, String)]) -> Result<(), Failure> {
+        for (kind, message) in warnings {
+            eprintln!("{path}: warning [{kind}]: {message}");
+        }
+        match warnings
+            .iter()
+            .filter(|(kind, _)| self.fails_on(kind))
+            .count()
+        {
+            0 => Ok(()),
+            n => Err(Failure::new(
+                WARNINGS,
+                format!("{path}: {n} warning(s) failed the run"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gltf_opt::SceneWarning;
+
+    use super::*;
+
+    #[test]
+    fn errors_map_to_their_codes() {
+        let unsupported = OptimizeError::UnsupportedVersion {
+            version: "1.0".to_string(),
+        };
+        assert_eq!(error_code(&unsupported), UNSUPPORTED);
+        let basis = OptimizeError::Unsupported {
+            what: "Basis Universal KTX2 input".to_string(),
+        };
+        assert_eq!(error_code(&basis), UNSUPPORTED);
+        let invalid = OptimizeError::TextureOutOfBounds { texture: 3 };
+        assert_eq!(error_code(&invalid), INPUT_ERROR);
+        let option = OptimizeError::InvalidOption("texture_size 0".to_string());
+        assert_eq!(error_code(&option), FAILED);
+    }
+
+    #[test]
+    fn gates_fail_on_the_kinds_asked_for() {
+        let warnings = [("no-default-scene", "2 scenes but no default".to_string())];
+        let gate = |all, kinds: &[&str]| {
+            let kinds = kinds.iter().map(|k| k.to_string()).collect();
+            WarningGate::new(all, kinds, &SceneWarning::KINDS).unwrap()
+        };
+        assert!(gate(false, &[]).check("a.glb", &warnings).is_ok());
+        assert!(
+            gate(false, &["many-materials"])
+                .check("a.glb", &warnings)
+                .is_ok()
+        );
+        let failure = gate(false, &["no-default-scene"])
+            .check("a.glb", &warnings)
+            .unwrap_err();
+        assert_eq!(failure.code, WARNINGS);
+        assert!(gate(true, &[]).check("a.glb", &warnings).is_err());
+
+        let unknown = WarningGate::new(false, vec!["typo".to_string()], &SceneWarning::KINDS);
+        assert_eq!(unknown.err().map(|f| f.code), Some(FAILED));
+    }
+}
@@ -0,0 +1,268 @@
+//! `gltf-opt`: optimize, compare, inspect and validate GLB files from the command line
+
+mod args;
+mod exit;
+mod summary;
+
+use std::{
+    fs,
+    io::{Cursor, Write},
+    process::ExitCode,
+    time::Instant,
+};
+
+use gltf_opt::{
+    SceneWarning, asset_manifest, commands_options, diff_glbs, model_info, prelude::*,
+    scene_warnings, validate_glb,
+};
+
+use crate::{
+    args::Args,
+    exit::{Failure, INPUT_ERROR, WarningGate},
+    summary::{Timings, diff_summary, info_summary, summary},
+};
+
+const USAGE: &str = "\
+usage: gltf-opt <input.glb> -o <output.glb> [options]
+       gltf-opt diff <a.glb> <b.glb> [--json]
+       gltf-opt inspect <model.glb> [--json]
+       gltf-opt validate <model.glb> [--fail-on-warning] [--fail-on <types>]
+
+options:
+  --preset <key>           start from a preset: default, mobile, preview, threejs,
+                           babylonjs, unity-gltfast, filament-android, quicklook-usdz
+  --commands <file.json>   start from the settings a JSON command list describes
+  --texture-size <px>      largest texture width or height, 1024 by default
+  --ktx2                   encode textures as KTX2 with Basis Universal
+  --center-pivot           move the model so the bottom center of its bounds sits at
+                           the origin
+  --remove-normal-texture  drop normal maps
+  --meshopt                compress geometry with EXT_meshopt_compression
+  --stats                  print sizes, per-texture changes and timings to stderr
+  --json                   print the full report as JSON to stdout; with diff and
+                           inspect, print JSON instead of a summary
+  --fail-on-warning        exit with 2 when the output has any warning
+  --fail-on <types>        exit with 2 on warnings of these comma-separated types:
+                           many-materials, unindexed-mesh, oversized-texture,
+                           no-default-scene, misslotted-texture, missing-tex-coord,
+                           low-texel-density;
+                           validate also knows skinning and keyframe-timing
+
+exit codes:
+  0  success
+  1  bad command line, or the output could not be written
+  2  warnings --fail-on-warning or --fail-on fail on; the output is still written
+  3  the input can't be read or is not a valid glTF/GLB, or validate found errors
+  4  the input needs a feature this build or this tool doesn't support";
+
+/// Options that take a value
+const VALUED: [&str; 5] = ["output", "preset", "commands", "texture-size", "fail-on"];
+
+/// Warning types `validate` reports besides the [`SceneWarning`] kinds
+const ISSUE_KINDS: [&str; 2] = ["skinning", "keyframe-timing"];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let result = match args[0].as_str() {
+        "diff" => diff(args[1..].to_vec()),
+        "inspect" => inspect(args[1..].to_vec()),
+        "validate" => validate(args[1..].to_vec()),
+        _ => optimize(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => failure.exit(),
+    }
+}
+
+/// Settings the options ask for, on top of a preset or command list when one is named
+fn options(args: &mut Args) -> Result<OptimizeOptions, Failure> {
+    let mut options = match (args.value("preset"), args.value("commands")) {
+        (Some(_), Some(_)) => return Err("--preset and --commands exclude each other".into()),
+        (Some(key), None) => OptimizeOptions::presets()
+            .iter()
+            .find(|p| p.key == key)
+            .map(|p| p.options.clone())
+            .ok_or(format!("unknown preset {key}"))?,
+        (None, Some(path)) => {
+            let commands = fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?;
+            commands_options(&commands).map_err(|e| format!("{path}: {e}"))?
+        }
+        (None, None) => OptimizeOptions::default(),
+    };
+    if let Some(size) = args.value("texture-size") {
+        let size = size
+            .parse()
+            .map_err(|_| format!("--texture-size {size} is not a size in pixels"))?;
+        options = options.texture_size(size);
+    }
+    if args.flag("ktx2")? {
+        options = options.ktx2(true);
+    }
+    if args.flag("center-pivot")? {
+        options = options.center_pivot(true);
+    }
+    if args.flag("remove-normal-texture")? {
+        options = options.remove_normal_texture(true);
+    }
+    if args.flag("meshopt")? {
+        options = options.meshopt_compression(true);
+    }
+    Ok(options)
+}
+
+fn optimize(args: Vec<String>) -> Result<(), Failure> {
+    let mut args = Args::parse(args, &VALUED)?;
+    let options = options(&mut args)?;
+    let output = args.value("output").ok_or("no output, name one with -o")?;
+    let (stats, json) = (args.flag("stats")?, args.flag("json")?);
+    let gate = WarningGate::new(
+        args.flag("fail-on-warning")?,
+        args.values("fail-on"),
+        &SceneWarning::KINDS,
+    )?;
+    let [input] =
+        <[String; 1]>::try_from(args.finish()?).map_err(|_| "expected exactly one input file")?;
+
+    let start = Instant::now();
+    let glb = fs::read(&input).map_err(|e| Failure::new(INPUT_ERROR, format!("{input}: {e}")))?;
+    let read = start.elapsed();
+    let start = Instant::now();
+    let Optimized {
+        glb: out, report, ..
+    } = Optimizer::new(&options)
+        .report(true)
+        .run(&mut Cursor::new(glb))
+        .map_err(|e| Failure::optimize(&input, e))?;
+    let report = report.unwrap_or_default();
+    let optimize = start.elapsed();
+    let start = Instant::now();
+    fs::write(&output, &out).map_err(|e| format!("{output}: {e}"))?;
+    let write = start.elapsed();
+
+    if stats {
+        let timings = Timings {
+            read,
+            optimize,
+            write,
+        };
+        eprint!("{input} -> {output}\n{}", summary(&report, &timings));
+    }
+    if json {
+        // A closed pipe, as with `| head`, is not worth failing over
+        let _ = writeln!(std::io::stdout(), "{}", report.to_json());
+    }
+    let warnings =
+        scene_warnings(&mut Cursor::new(out)).map_err(|e| Failure::optimize(&output, e))?;
+    gate.check(&output, &warning_lines(&warnings))
+}
+
+fn warning_lines(warnings: &[SceneWarning]) -> Vec<(&'static str, String)> {
+    warnings.iter().map(|w| (w.kind(), w.to_string())).collect()
+}
+
+/// Read the one file a subcommand takes
+fn single_input(args: Args, command: &str) -> Result<(String, Cursor<Vec<u8>>), Failure> {
+    let [path] =
+        <[String; 1]>::try_from(args.finish()?).map_err(|_| format!("{command} takes one file"))?;
+    let glb = fs::read(&path).map_err(|e| Failure::new(INPUT_ERROR, format!("{path}: {e}")))?;
+    Ok((path, Cursor::new(glb)))
+}
+
+/// Print what changed between two files
+fn diff(args: Vec<String>) -> Result<(), Failure> {
+    let mut args = Args::parse(args, &[])?;
+    let json = args.flag("json")?;
+    let [a, b] = <[String; 2]>::try_from(args.finish()?).map_err(|_| "diff takes two files")?;
+
+    let open = |path: &str| {
+        fs::read(path)
+            .map(Cursor::new)
+            .map_err(|e| Failure::new(INPUT_ERROR, format!("{path}: {e}")))
+    };
+    let diff = diff_glbs(&mut open(&a)?, &mut open(&b)?)
+        .map_err(|e| Failure::optimize(&format!("{a} or {b}"), e))?;
+    let text = match json {
+        true => diff.to_json(),
+        false => format!("{a} -> {b}\n{}", diff_summary(&diff)),
+    };
+    let _ = writeln!(std::io::stdout(), "{}", text.trim_end());
+    Ok(())
+}
+
+/// Print what a file holds
+fn inspect(args: Vec<String>) -> Result<(), Failure> {
+    let mut args = Args::parse(args, &[])?;
+    let json = args.flag("json")?;
+    let (path, mut glb) = single_input(args, "inspect")?;
+
+    let text = match json {
+        true => asset_manifest(&mut glb).map_err(|e| Failure::optimize(&path, e))?,
+        false => {
+            let info = model_info(&mut glb).map_err(|e| Failure::optimize(&path, e))?;
+            format!("{path}\n{}", info_summary(&info))
+        }
+    };
+    let _ = writeln!(std::io::stdout(), "{}", text.trim_end());
+    Ok(())
+}
+
+/// Run every validator on a file, failing with [`INPUT_ERROR`] on errors and with
+/// [`exit::WARNINGS`] on warnings the flags gate
+fn validate(args: Vec<String>) -> Result<(), Failure> {
+    let mut args = Args::parse(args, &["fail-on"])?;
+    let known: Vec<&str> = SceneWarning::KINDS.into_iter().chain(ISSUE_KINDS).collect();
+    let gate = WarningGate::new(
+        args.flag("fail-on-warning")?,
+        args.values("fail-on"),
+        &known,
+    )?;
+    let (path, mut glb) = single_input(args, "validate")?;
+
+    let validation = validate_glb(&mut glb).map_err(|e| Failure::optimize(&path, e))?;
+    for error in &validation.errors {
+        eprintln!("{path}: error: {error}");
+    }
+    if !validation.errors.is_empty() {
+        let count = validation.errors.len();
+        return Err(Failure::new(
+            INPUT_ERROR,
+            format!("{path}: {count} error(s)"),
+        ));
+    }
+
+    let mut warnings = warning_lines(&validation.warnings);
+    for issue in &validation.skinning {
+        let message = format!(
+            "mesh {} primitive {}: {} vertices weight joints past the skin's {} joints, {} have \
+             invalid weights and {} have weights not adding up to 1; set \
+             `repair_skin_weights`",
+            issue.mesh,
+            issue.primitive,
+            issue.out_of_range_joints,
+            issue.joint_count,
+            issue.invalid_weights,
+            issue.unnormalized_weights
+        );
+        warnings.push(("skinning", message));
+    }
+    for issue in &validation.keyframe_timing {
+        let mut message = format!(
+            "animation {} sampler {} key {} at {} s is not after the key before, at {} s",
+            issue.animation, issue.sampler, issue.key, issue.time, issue.previous
+        );
+        if issue.repaired.is_some() {
+            message.push_str("; `repair_animation_timing` fixes it");
+        }
+        warnings.push(("keyframe-timing", message));
+    }
+    gate.check(&path, &warnings)?;
+    if validation.is_clean() {
+        println!("{path}: valid");
+    }
+    Ok(())
+}
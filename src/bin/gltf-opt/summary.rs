@@ -0,0 +1,181 @@
+use std::{fmt::Write, time::Duration};
+
+use gltf_opt::{BeforeAfter, GlbDiff, GrowthFallback, ModelInfo, OptimizeReport, StoredImage};
+
+/// Seconds spent reading the input, optimizing it and writing the output
+pub struct Timings {
+    pub read: Duration,
+    pub optimize: Duration,
+    pub write: Duration,
+}
+
+/// `bytes` in B, KiB or MiB
+pub fn size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// `before -> after` with the change in percent, `show` formatting each side
+fn change(values: BeforeAfter, show: fn(u64) -> String) -> String {
+    let mut line = format!("{} -> {}", show(values.before), show(values.after));
+    if values.before > 0 && values.before != values.after {
+        let percent = (values.after as f64 / values.before as f64 - 1.0) * 100.0;
+        let _ = write!(line, "  {percent:+.1}%");
+    }
+    line
+}
+
+fn image(image: Option<&StoredImage>) -> String {
+    match image {
+        Some(i) => format!(
+            "{}x{} {} {}",
+            i.width,
+            i.height,
+            i.mime_type.as_deref().unwrap_or("?"),
+            size(i.bytes)
+        ),
+        None => "not embedded".to_string(),
+    }
+}
+
+/// Sizes per part of the file, scene counts and every texture, one line each
+fn report_lines(report: &OptimizeReport) -> String {
+    let mut out = String::new();
+    let count = |n: u64| n.to_string();
+    for (label, values, show) in [
+        ("file", report.file, size as fn(u64) -> String),
+        ("json", report.json, size),
+        ("geometry", report.geometry, size),
+        ("animation", report.animation, size),
+        ("textures", report.texture_bytes, size),
+        ("draw calls", report.draw_calls, count),
+        ("vertices", report.vertices, count),
+        ("triangles", report.triangles, count),
+    ] {
+        let _ = writeln!(out, "{label:<12}{}", change(values, show));
+    }
+    for texture in &report.textures {
+        let after = match texture.output_texture {
+            Some(_) => image(texture.after.as_ref()),
+            None => "dropped".to_string(),
+        };
+        let label = format!("texture {}", texture.texture);
+        let before = image(texture.before.as_ref());
+        let _ = writeln!(out, "{label:<12}{before} -> {after}");
+    }
+    match report.growth_fallback {
+        Some(GrowthFallback::TexturesPassedThrough) => {
+            out.push_str("re-encoded textures grew the file, so they were copied as they are\n")
+        }
+        Some(GrowthFallback::InputReturned) => {
+            out.push_str("the output was larger than the input, which was kept\n")
+        }
+        _ => {}
+    }
+    out
+}
+
+/// Human summary of an optimization: [`report_lines`] and the time each step took
+pub fn summary(report: &OptimizeReport, timings: &Timings) -> String {
+    let mut out = report_lines(report);
+    let _ = writeln!(
+        out,
+        "read {:.2} s, optimize {:.2} s, write {:.2} s",
+        timings.read.as_secs_f64(),
+        timings.optimize.as_secs_f64(),
+        timings.write.as_secs_f64()
+    );
+    out
+}
+
+/// Human summary of a diff: [`report_lines`], the object counts that differ and the
+/// extensions added and removed
+pub fn diff_summary(diff: &GlbDiff) -> String {
+    let mut out = report_lines(&diff.report);
+    for (name, count) in diff.objects.iter().filter(|(_, c)| c.before != c.after) {
+        let _ = writeln!(out, "{name:<12}{} -> {}", count.before, count.after);
+    }
+    for extension in &diff.extensions_added {
+        let _ = writeln!(out, "+ {extension}");
+    }
+    for extension in &diff.extensions_removed {
+        let _ = writeln!(out, "- {extension}");
+    }
+    out
+}
+
+/// Human summary of a file: size, generator, object counts, extensions, scene counts and
+/// every image with its GPU memory
+pub fn info_summary(info: &ModelInfo) -> String {
+    let mut out = String::new();
+    let stats = &info.stats;
+    let mut file = size(stats.wire.raw);
+    for (name, compressed) in [("gzip", stats.wire.gzip), ("brotli", stats.wire.brotli)] {
+        if let Some(bytes) = compressed {
+            let _ = write!(file, ", {} {name}", size(bytes));
+        }
+    }
+    let _ = writeln!(out, "{:<12}{file}", "file");
+    if let Some(generator) = &info.generator {
+        let _ = writeln!(out, "{:<12}{generator}", "generator");
+    }
+    let objects: Vec<String> = info
+        .objects
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| format!("{count} {name}"))
+        .collect();
+    let _ = writeln!(out, "{:<12}{}", "objects", objects.join(", "));
+    for extension in &info.extensions_used {
+        let required = info.extensions_required.contains(extension);
+        let _ = writeln!(
+            out,
+            "{:<12}{extension}{}",
+            "extension",
+            if required { " (required)" } else { "" }
+        );
+    }
+    for (label, value) in [
+        ("draw calls", stats.draw_calls.to_string()),
+        ("triangles", stats.triangles.to_string()),
+        ("vertices", stats.vertices.to_string()),
+        ("vertex data", size(stats.vertex_buffer_bytes)),
+        ("index data", size(stats.index_buffer_bytes)),
+    ] {
+        let _ = writeln!(out, "{label:<12}{value}");
+    }
+    for texture in &stats.textures {
+        let label = format!("image {}", texture.image);
+        let _ = writeln!(
+            out,
+            "{label:<12}{}x{} {} {}, {} as RGBA8 on the GPU",
+            texture.width,
+            texture.height,
+            texture.mime_type.as_deref().unwrap_or("?"),
+            size(texture.encoded_bytes),
+            size(texture.transcoded.rgba8)
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_and_changes_are_readable() {
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(3 << 20), "3.0 MiB");
+
+        let mut values = BeforeAfter::default();
+        (values.before, values.after) = (2048, 512);
+        assert_eq!(change(values, size), "2.0 KiB -> 512 B  -75.0%");
+        values.after = 2048;
+        assert_eq!(change(values, size), "2.0 KiB -> 2.0 KiB");
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    ops::{Deref, Range},
+};
+
+use gltf::json::{
+    Index, Root,
+    buffer::{Buffer, Target, View},
+    validation::Checked,
+};
+
+/// Binary chunk under construction
+///
+/// Every range is aligned to 4 bytes and byte ranges identical to one written earlier are
+/// reused instead of appended again, so two views may point at the same bytes. Read access
+/// goes through `Deref<Target = [u8]>`.
+#[derive(Debug, Clone)]
+pub struct BlobWriter {
+    data: Vec<u8>,
+    dedup: bool,
+    /// Offsets of written ranges by content hash
+    ranges: HashMap<u64, Vec<usize>>,
+    /// Ranges handed out more than once
+    shared: Vec<Range<usize>>,
+}
+
+impl Default for BlobWriter {
+    fn default() -> Self {
+        BlobWriter::new()
+    }
+}
+
+impl From<Vec<u8>> for BlobWriter {
+    /// Continue writing after existing bytes, which are never reused for dedup
+    fn from(data: Vec<u8>) -> Self {
+        BlobWriter {
+            data,
+            ..BlobWriter::new()
+        }
+    }
+}
+
+impl From<BlobWriter> for Vec<u8> {
+    fn from(writer: BlobWriter) -> Self {
+        writer.data
+    }
+}
+
+impl Deref for BlobWriter {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+pub(crate) fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl BlobWriter {
+    /// Empty writer with dedup enabled
+    pub fn new() -> Self {
+        BlobWriter {
+            data: Vec::new(),
+            dedup: true,
+            ranges: HashMap::new(),
+            shared: Vec::new(),
+        }
+    }
+
+    /// Empty writer with dedup enabled and room for `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> Self {
+        BlobWriter {
+            data: Vec::with_capacity(capacity),
+            ..BlobWriter::new()
+        }
+    }
+
+    /// Turn content-based dedup on or off for later writes
+    pub fn set_dedup(&mut self, dedup: bool) {
+        self.dedup = dedup;
+    }
+
+    /// Pad with zeros up to a multiple of `alignment` bytes
+    pub fn align(&mut self, alignment: usize) {
+        let len = self.data.len().next_multiple_of(alignment.max(1));
+        self.data.resize(len, 0);
+    }
+
+    /// Write bytes at a 4-byte aligned offset and return it
+    ///
+    /// With dedup on, an earlier range holding the same bytes is returned instead.
+    pub fn append(&mut self, data: &[u8]) -> usize {
+        let hash = self.dedup.then(|| content_hash(data));
+        self.append_with_hash(data, hash)
+    }
+
+    /// [`append`](Self::append) with the content hash computed ahead, e.g. on another thread
+    pub(crate) fn append_hashed(&mut self, data: &[u8], hash: u64) -> usize {
+        self.append_with_hash(data, self.dedup.then_some(hash))
+    }
+
+    fn append_with_hash(&mut self, data: &[u8], hash: Option<u64>) -> usize {
+        if let Some(hash) = hash
+            && !data.is_empty()
+        {
+            let mut existing = self.ranges.get(&hash).into_iter().flatten().copied();
+            let found =
+                existing.find(|&offset| self.data.get(offset..offset + data.len()) == Some(data));
+            if let Some(offset) = found {
+                self.shared.push(offset..offset + data.len());
+                return offset;
+            }
+        }
+
+        self.align(4);
+        let offset = self.data.len();
+        self.data.extend_from_slice(data);
+        if let Some(hash) = hash {
+            self.ranges.entry(hash).or_default().push(offset);
+        }
+        offset
+    }
+
+    /// Write bytes as a new buffer view of buffer 0 tagged with `target`
+    pub fn push_view(
+        &mut self,
+        json: &mut Root,
+        data: &[u8],
+        target: Option<Target>,
+    ) -> Index<View> {
+        let offset = self.append(data);
+        json.push(View {
+            buffer: Index::<Buffer>::new(0),
+            byte_length: data.len().into(),
+            byte_offset: (offset > 0).then(|| offset.into()),
+            byte_stride: None,
+            name: None,
+            target: target.map(Checked::Valid),
+            extensions: None,
+            extras: Default::default(),
+        })
+    }
+
+    /// Replace bytes in place, refused (`None`) when another view may share them
+    pub fn overwrite(&mut self, offset: usize, data: &[u8]) -> Option<()> {
+        let range = offset..offset + data.len();
+        if self
+            .shared
+            .iter()
+            .any(|s| s.start < range.end && range.start < s.end)
+        {
+            return None;
+        }
+        self.data.get_mut(range)?.copy_from_slice(data);
+        Some(())
+    }
+
+    /// Drop everything written from `len` on
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        self.shared.retain(|s| s.end <= len);
+    }
+
+    /// The bytes written so far
+    pub fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_aligned_and_deduplicated() {
+        let mut blob = BlobWriter::new();
+        assert_eq!(blob.append(&[1, 2, 3]), 0);
+        assert_eq!(blob.append(&[4, 5]), 4);
+        assert_eq!(blob.append(&[1, 2, 3]), 0);
+        assert_eq!(blob.len(), 6);
+
+        blob.set_dedup(false);
+        assert_eq!(blob.append(&[1, 2, 3]), 8);
+        blob.align(16);
+        assert_eq!(
+            blob.into_vec(),
+            [1, 2, 3, 0, 4, 5, 0, 0, 1, 2, 3, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn existing_bytes_are_not_reused() {
+        let mut blob = BlobWriter::from(vec![7, 7, 7, 7]);
+        assert_eq!(blob.append(&[7, 7, 7, 7]), 4);
+        assert_eq!(blob.append(&[7, 7, 7, 7]), 4);
+    }
+
+    #[test]
+    fn shared_ranges_refuse_overwrites() {
+        let mut blob = BlobWriter::new();
+        blob.append(&[1, 2, 3, 4]);
+        blob.append(&[5, 6, 7, 8]);
+        assert_eq!(blob.overwrite(4, &[9, 9]), Some(()));
+        blob.append(&[1, 2, 3, 4]);
+        assert_eq!(blob.overwrite(2, &[0, 0, 0, 0]), None);
+        assert_eq!(blob.overwrite(8, &[0]), None);
+        assert_eq!(&blob[..], [1, 2, 3, 4, 9, 9, 7, 8]);
+
+        blob.truncate(0);
+        blob.append(&[1, 2, 3, 4]);
+        assert_eq!(blob.overwrite(0, &[0]), Some(()));
+    }
+
+    #[test]
+    fn views_record_offset_length_and_target() {
+        let mut json = Root::default();
+        let mut blob = BlobWriter::new();
+        blob.push_view(&mut json, &[1], None);
+        let index = blob.push_view(&mut json, &[2, 3], Some(Target::ArrayBuffer));
+
+        let view = &json.buffer_views[index.value()];
+        assert_eq!(view.byte_offset.map(|o| o.0), Some(4));
+        assert_eq!(view.byte_length.0, 2);
+        assert_eq!(view.target, Some(Checked::Valid(Target::ArrayBuffer)));
+        assert!(json.buffer_views[0].byte_offset.is_none());
+    }
+}
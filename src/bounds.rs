@@ -0,0 +1,286 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Index, Node, Root, mesh::Semantic, validation::Checked};
+
+use crate::{
+    error::OptimizeError,
+    glb::{merge_extras, read_glb, write_glb},
+    math::{IDENTITY, Mat4, bounds, mul, node_matrix, sub, transform_point},
+    opt::get_position_data,
+};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        ]
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        sub(self.max, self.min)
+    }
+}
+
+/// Bounding sphere centered on the AABB center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// Box and sphere enclosing the same point set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingVolume {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl BoundingVolume {
+    fn from_points(points: &[[f32; 3]]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let (min, max) = bounds(points);
+        let aabb = Aabb { min, max };
+        let center = aabb.center();
+        let radius = points
+            .iter()
+            .map(|p| {
+                let d = sub(*p, center);
+                d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+            })
+            .fold(0.0f32, f32::max)
+            .sqrt();
+
+        Some(BoundingVolume {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        })
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "min": self.aabb.min,
+            "max": self.aabb.max,
+            "center": self.sphere.center,
+            "radius": self.sphere.radius,
+        })
+    }
+}
+
+/// Bounding volumes indexed like the glTF `meshes` and `scenes` arrays
+///
+/// Mesh volumes are in mesh-local space; scene volumes are in world space with every node
+/// transform applied. Entries are `None` when there is no readable position data.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BoundingVolumes {
+    pub meshes: Vec<Option<BoundingVolume>>,
+    pub scenes: Vec<Option<BoundingVolume>>,
+}
+
+/// Positions of every primitive in a mesh, in mesh-local space
+fn mesh_positions(o_blob: &[u8], o_json: &Root, mesh_idx: usize) -> Vec<[f32; 3]> {
+    let Some(mesh) = o_json.meshes.get(mesh_idx) else {
+        return Vec::new();
+    };
+
+    mesh.primitives
+        .iter()
+        .filter_map(|p| p.attributes.get(&Checked::Valid(Semantic::Positions)))
+        .filter_map(|idx| get_position_data(o_blob, o_json, *idx))
+        .flatten()
+        .collect()
+}
+
+/// Visit every mesh instance reachable from a scene with its world transform
+pub(crate) fn for_each_mesh_instance<F: FnMut(Index<Node>, usize, &Mat4)>(
+    o_json: &Root,
+    roots: &[Index<Node>],
+    mut f: F,
+) {
+    fn visit<F: FnMut(Index<Node>, usize, &Mat4)>(
+        o_json: &Root,
+        node_idx: Index<Node>,
+        parent: &Mat4,
+        depth: usize,
+        f: &mut F,
+    ) {
+        // Guard against cyclic hierarchies in malformed files
+        if depth > o_json.nodes.len() {
+            return;
+        }
+        let Some(node) = o_json.nodes.get(node_idx.value()) else {
+            return;
+        };
+
+        let world = mul(parent, &node_matrix(node));
+
+        if let Some(mesh_idx) = node.mesh {
+            f(node_idx, mesh_idx.value(), &world);
+        }
+
+        for child in node.children.iter().flatten() {
+            visit(o_json, *child, &world, depth + 1, f);
+        }
+    }
+
+    for root in roots {
+        visit(o_json, *root, &IDENTITY, 0, &mut f);
+    }
+}
+
+/// Root nodes of the default scene, or every parentless node when the file has no scenes
+pub(crate) fn scene_roots(o_json: &Root) -> Vec<Index<Node>> {
+    if let Some(scene) = o_json
+        .scenes
+        .get(o_json.scene.map(|s| s.value()).unwrap_or(0))
+    {
+        return scene.nodes.clone();
+    }
+
+    (0..o_json.nodes.len())
+        .filter(|&i| {
+            !o_json
+                .nodes
+                .iter()
+                .any(|n| n.children.iter().flatten().any(|c| c.value() == i))
+        })
+        .map(|i| Index::new(i as u32))
+        .collect()
+}
+
+pub(crate) fn compute_bounding_volumes(o_blob: &[u8], o_json: &Root) -> BoundingVolumes {
+    let meshes = (0..o_json.meshes.len())
+        .map(|i| BoundingVolume::from_points(&mesh_positions(o_blob, o_json, i)))
+        .collect();
+
+    let scenes = o_json
+        .scenes
+        .iter()
+        .map(|scene| {
+            let mut points = Vec::new();
+            for_each_mesh_instance(o_json, &scene.nodes, |_, mesh_idx, world| {
+                points.extend(
+                    mesh_positions(o_blob, o_json, mesh_idx)
+                        .into_iter()
+                        .map(|p| transform_point(world, p)),
+                );
+            });
+            BoundingVolume::from_points(&points)
+        })
+        .collect();
+
+    BoundingVolumes { meshes, scenes }
+}
+
+/// Compute per-mesh and per-scene bounding volumes without modifying the GLB
+pub fn bounding_volumes<R: Read + Seek>(reader: &mut R) -> Result<BoundingVolumes, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    Ok(compute_bounding_volumes(&o_blob, &o_json))
+}
+
+/// Write bounding volumes into `extras.bounds` of each mesh and scene
+///
+/// The stored object has `min`/`max` (AABB) and `center`/`radius` (sphere). Existing extras
+/// entries are kept. The computed volumes are returned alongside the new GLB.
+pub fn add_bounding_volumes<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<(Vec<u8>, BoundingVolumes), OptimizeError> {
+    let (mut n_json, n_blob) = read_glb(reader)?;
+    let volumes = compute_bounding_volumes(&n_blob, &n_json);
+
+    for (mesh, volume) in n_json.meshes.iter_mut().zip(&volumes.meshes) {
+        if let Some(volume) = volume {
+            mesh.extras = merge_extras(&mesh.extras, "bounds", volume.to_json());
+        }
+    }
+    for (scene, volume) in n_json.scenes.iter_mut().zip(&volumes.scenes) {
+        if let Some(volume) = volume {
+            scene.extras = merge_extras(&scene.extras, "bounds", volume.to_json());
+        }
+    }
+
+    Ok((write_glb(&n_json, n_blob)?, volumes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    fn two_meshes() -> Vec<u8> {
+        SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn scene_volumes_apply_node_transforms() {
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(two_meshes())).unwrap();
+        o_json.nodes[1].scale = Some([2.0, 2.0, 2.0]);
+        let volumes = compute_bounding_volumes(&o_blob, &o_json);
+
+        let mesh = volumes.meshes[1].unwrap();
+        assert_eq!(
+            mesh.aabb,
+            Aabb {
+                min: [2.0, 0.0, 0.0],
+                max: [3.0, 1.0, 0.0]
+            }
+        );
+        assert_eq!(mesh.sphere.center, [2.5, 0.5, 0.0]);
+        assert!((mesh.sphere.radius - 0.5f32.hypot(0.5)).abs() < 1e-6);
+
+        // The second mesh is scaled to x 4..6, y 0..2
+        let scene = volumes.scenes[0].unwrap();
+        assert_eq!(
+            scene.aabb,
+            Aabb {
+                min: [0.0; 3],
+                max: [6.0, 2.0, 0.0]
+            }
+        );
+        assert_eq!(scene.aabb.center(), [3.0, 1.0, 0.0]);
+        assert_eq!(scene.aabb.size(), [6.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn volumes_are_written_next_to_existing_extras() {
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(two_meshes())).unwrap();
+        o_json.meshes[0].extras = crate::glb::to_extras(serde_json::json!({ "author": "a" }));
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let (out, volumes) = add_bounding_volumes(&mut Cursor::new(glb)).unwrap();
+        assert_eq!(bounding_volumes(&mut Cursor::new(&out)).unwrap(), volumes);
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let extras: serde_json::Value =
+            serde_json::from_str(n_json.meshes[0].extras.as_ref().unwrap().get()).unwrap();
+        assert_eq!(extras["author"], "a");
+        assert_eq!(extras["bounds"]["max"], serde_json::json!([1.0, 1.0, 0.0]));
+        assert!(n_json.scenes[0].extras.is_some());
+    }
+
+    #[test]
+    fn roots_without_scenes_are_parentless_nodes() {
+        let (mut o_json, _) = read_glb(&mut Cursor::new(two_meshes())).unwrap();
+        o_json.scenes.clear();
+        o_json.scene = None;
+        o_json.nodes[0].children = Some(vec![Index::new(1)]);
+        assert_eq!(scene_roots(&o_json), [Index::new(0)]);
+    }
+}
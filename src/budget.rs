@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use gltf::json::{Index, Root, Texture};
+
+use crate::{
+    bounds::{for_each_mesh_instance, scene_roots},
+    environment::{is_equirectangular, texture_dimensions},
+    opt::{TextureType, get_image_data},
+    options::OptimizeOptions,
+    stats::{image_dimensions, transcoded_sizes},
+};
+
+/// Smallest edge a budgeted texture is shrunk to
+const MIN_BUDGET_SIZE: u32 = 4;
+
+struct Candidate {
+    width: u32,
+    height: u32,
+    size: u32,
+    uses: u64,
+    power_of_two: bool,
+}
+
+impl Candidate {
+    /// Output dimensions for a size cap, mirroring the resize in `resize_to_*`
+    fn dims(&self, size: u32) -> (u32, u32) {
+        texture_dimensions(self.width, self.height, size, self.power_of_two)
+    }
+
+    /// Edge the size cap currently limits (the height of a panorama)
+    fn edge(&self) -> u32 {
+        let (w, h) = self.dims(self.size);
+        if is_equirectangular(self.width, self.height) {
+            h
+        } else {
+            w.max(h)
+        }
+    }
+
+    fn cost(&self, size: u32, ktx2: bool) -> u64 {
+        let (w, h) = self.dims(size);
+        let sizes = transcoded_sizes(w, h);
+        if ktx2 { sizes.etc2_rgba } else { sizes.rgba8 }
+    }
+}
+
+/// Pick a per-texture size cap so the decoded texture total stays under `budget` bytes
+///
+/// Starts from the sizes `optimize` would use anyway and halves the texture with the highest
+/// cost per use in the default scene until the total fits or every texture hits the minimum.
+/// KTX2 outputs are costed at 8 bpp (ETC2/BC7/ASTC), everything else as RGBA8.
+pub(crate) fn plan_texture_budget(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    budget: u64,
+    texture_size: u32,
+) -> HashMap<usize, u32> {
+    let convert_to_ktx2 = options.writes_ktx2();
+    let mut candidates: HashMap<usize, Candidate> = HashMap::new();
+    let mut add = |texture: Index<Texture>, size: u32, uses: u64| {
+        if let Some(candidate) = candidates.get_mut(&texture.value()) {
+            candidate.size = candidate.size.max(size);
+            candidate.uses += uses;
+        } else if let Some((width, height)) =
+            get_image_data(o_blob, o_json, texture).and_then(image_dimensions)
+        {
+            candidates.insert(
+                texture.value(),
+                Candidate {
+                    width,
+                    height,
+                    size,
+                    uses,
+                    power_of_two: options.power_of_two_textures,
+                },
+            );
+        }
+    };
+
+    let mut material_uses: HashMap<usize, u64> = HashMap::new();
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, _| {
+        for primitive in o_json
+            .meshes
+            .get(mesh_idx)
+            .iter()
+            .flat_map(|m| &m.primitives)
+        {
+            if let Some(material) = primitive.material {
+                *material_uses.entry(material.value()).or_default() += 1;
+            }
+        }
+    });
+
+    for (i, material) in o_json.materials.iter().enumerate() {
+        // Materials outside the default scene still get processed, so count them once
+        let uses = material_uses.get(&i).copied().unwrap_or(1);
+        let pbr = &material.pbr_metallic_roughness;
+        let slots = [
+            pbr.base_color_texture
+                .as_ref()
+                .map(|i| (i.index, TextureType::BaseColor)),
+            pbr.metallic_roughness_texture
+                .as_ref()
+                .map(|i| (i.index, TextureType::MetallicRoughness)),
+            material
+                .normal_texture
+                .as_ref()
+                .filter(|_| !options.remove_normal_texture)
+                .map(|i| (i.index, TextureType::Normal)),
+            material
+                .occlusion_texture
+                .as_ref()
+                .map(|i| (i.index, TextureType::Occlusion)),
+            material
+                .emissive_texture
+                .as_ref()
+                .map(|i| (i.index, TextureType::Emissive)),
+        ];
+        // Textures copied verbatim cannot shrink, so they are not candidates
+        for (texture, texture_type) in slots.into_iter().flatten() {
+            if options.processes(texture_type) {
+                add(
+                    texture,
+                    options.slot_texture_sizes.size(texture_type, texture_size),
+                    uses,
+                );
+            }
+        }
+    }
+
+    let mut total: u64 = candidates
+        .values()
+        .map(|c| c.cost(c.size, convert_to_ktx2))
+        .sum();
+
+    while total > budget {
+        let next = candidates
+            .iter()
+            .filter(|(_, c)| c.edge() > MIN_BUDGET_SIZE)
+            .max_by(|(ia, a), (ib, b)| {
+                let pa = a.cost(a.size, convert_to_ktx2) as f64 / a.uses as f64;
+                let pb = b.cost(b.size, convert_to_ktx2) as f64 / b.uses as f64;
+                pa.total_cmp(&pb).then(ib.cmp(ia))
+            })
+            .map(|(i, _)| *i);
+
+        let Some(candidate) = next.and_then(|i| candidates.get_mut(&i)) else {
+            break;
+        };
+        let before = candidate.cost(candidate.size, convert_to_ktx2);
+        candidate.size = (candidate.edge() / 2).max(MIN_BUDGET_SIZE);
+        total = total - before + candidate.cost(candidate.size, convert_to_ktx2);
+    }
+
+    candidates.into_iter().map(|(i, c)| (i, c.size)).collect()
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::optimize_with,
+        stats::gpu_stats,
+        testing::SyntheticGlb,
+    };
+
+    /// Two 64 px textures, the first drawn by three primitives and the second by one
+    fn unevenly_used_textures() -> (Root, Vec<u8>) {
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 4,
+            textures: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        for (p, primitive) in json.meshes[0].primitives.iter_mut().enumerate() {
+            primitive.material = Some(Index::new((p == 3) as u32));
+        }
+        (json, blob)
+    }
+
+    #[test]
+    fn least_used_texture_shrinks_first() {
+        let (json, blob) = unevenly_used_textures();
+        let full = transcoded_sizes(64, 64).rgba8;
+        let options = OptimizeOptions::default();
+
+        let plan = plan_texture_budget(&blob, &json, &options, 2 * full, 64);
+        assert_eq!(plan, HashMap::from([(0, 64), (1, 64)]));
+        let plan = plan_texture_budget(&blob, &json, &options, full + full / 2, 64);
+        assert_eq!(plan, HashMap::from([(0, 64), (1, 32)]));
+        // Nothing fits: every texture ends at the smallest size
+        let plan = plan_texture_budget(&blob, &json, &options, 1, 64);
+        assert_eq!(
+            plan,
+            HashMap::from([(0, MIN_BUDGET_SIZE), (1, MIN_BUDGET_SIZE)])
+        );
+    }
+
+    #[test]
+    fn optimized_textures_fit_the_budget() {
+        let (json, blob) = unevenly_used_textures();
+        let glb = write_glb(&json, blob).unwrap();
+        let full = transcoded_sizes(64, 64).rgba8;
+        let budget = full + full / 2;
+        let options = OptimizeOptions {
+            texture_size: 64,
+            gpu_texture_budget: Some(budget),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let stats = gpu_stats(&mut Cursor::new(out)).unwrap();
+        assert!(stats.texture_bytes_rgba8() <= budget);
+        let mut widths: Vec<u32> = stats.textures.iter().map(|t| t.width).collect();
+        widths.sort();
+        assert_eq!(widths, [32, 64]);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, ops::Range};
+
+use gltf::json::{Buffer, Root};
+
+use crate::{
+    blob::BlobWriter,
+    external::content_hash,
+    meshopt::{chunk_buffer, chunk_range, move_to_buffer},
+};
+
+/// Binary buffer written next to the GLB and referenced by URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExternalBuffer {
+    /// Relative URI stored in the GLB, e.g. `model_buffer1.bin`
+    pub uri: String,
+    pub data: Vec<u8>,
+}
+
+/// Spread the views stored in the binary chunk over buffers of at most `max_bytes` each
+///
+/// Views are taken in order and start a new buffer when the current one would grow past the
+/// cap; a view larger than the cap gets a buffer of its own. Views sharing bytes keep sharing
+/// them. The first buffer stays the binary chunk and is returned with the added buffers.
+/// With `hash_uris` each name carries a hash of the buffer bytes.
+pub(crate) fn split_buffers(
+    n_json: &mut Root,
+    n_blob: &[u8],
+    max_bytes: u64,
+    uri_prefix: &str,
+    hash_uris: bool,
+) -> (Vec<u8>, Vec<ExternalBuffer>) {
+    let first_added = n_json.buffers.len().max(1);
+    let mut buffers = vec![BlobWriter::new()];
+    let mut placed: HashMap<Range<usize>, (usize, usize)> = HashMap::new();
+    for view in &mut n_json.buffer_views {
+        if chunk_buffer(view) != 0 {
+            continue;
+        }
+        let range = chunk_range(view);
+        if let Some(&(buffer, offset)) = placed.get(&range) {
+            move_to_buffer(view, buffer, offset);
+            continue;
+        }
+
+        let current = buffers.last_mut().expect("at least the binary chunk");
+        let end = current.len().next_multiple_of(4) + range.len();
+        if !current.is_empty() && end as u64 > max_bytes {
+            buffers.push(BlobWriter::new());
+        }
+        let index = buffers.len() - 1;
+        let data = n_blob.get(range.clone()).unwrap_or_default();
+        let offset = buffers[index].append(data);
+        let buffer = if index == 0 {
+            0
+        } else {
+            first_added + index - 1
+        };
+        move_to_buffer(view, buffer, offset);
+        placed.insert(range, (buffer, offset));
+    }
+
+    let mut buffers = buffers.into_iter().map(BlobWriter::into_vec);
+    let chunk = buffers.next().unwrap_or_default();
+    let external: Vec<ExternalBuffer> = buffers
+        .enumerate()
+        .map(|(i, data)| ExternalBuffer {
+            uri: match hash_uris {
+                false => format!("{uri_prefix}buffer{}.bin", i + 1),
+                true => format!("{uri_prefix}buffer{}.{}.bin", i + 1, content_hash(&data)),
+            },
+            data,
+        })
+        .collect();
+    if n_json.buffers.is_empty() && !external.is_empty() {
+        n_json.push(Buffer {
+            byte_length: chunk.len().into(),
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+    for buffer in &external {
+        n_json.push(Buffer {
+            byte_length: buffer.data.len().into(),
+            name: None,
+            uri: Some(buffer.uri.clone()),
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+    (chunk, external)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        meshopt::chunk_range,
+        opt::optimize_with,
+        optimizer::{Optimized, Optimizer},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn buffers_stay_under_cap() {
+        let glb = SyntheticGlb {
+            meshes: 4,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default();
+        let whole = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (whole_json, whole_blob) = read_glb(&mut Cursor::new(whole)).unwrap();
+
+        let cap = 256;
+        let Optimized {
+            glb: split,
+            buffers,
+            ..
+        } = Optimizer::new(&options)
+            .buffer_cap(cap, "model_")
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(split)).unwrap();
+        assert!(!buffers.is_empty());
+        assert_eq!(json.buffers.len(), buffers.len() + 1);
+        assert!(blob.len() as u64 <= cap);
+        for (buffer, external) in json.buffers[1..].iter().zip(&buffers) {
+            assert_eq!(buffer.uri.as_deref(), Some(external.uri.as_str()));
+            assert_eq!(buffer.byte_length.0, external.data.len() as u64);
+            assert!(external.data.len() as u64 <= cap);
+        }
+        assert_eq!(buffers[0].uri, "model_buffer1.bin");
+
+        // Every view holds the same bytes, wherever it went
+        assert_eq!(json.buffer_views.len(), whole_json.buffer_views.len());
+        for (view, whole_view) in json.buffer_views.iter().zip(&whole_json.buffer_views) {
+            let data = match view.buffer.value() {
+                0 => &blob[..],
+                b => &buffers[b - 1].data[..],
+            };
+            assert_eq!(
+                &data[chunk_range(view)],
+                &whole_blob[chunk_range(whole_view)]
+            );
+        }
+    }
+
+    #[test]
+    fn buffer_uris_can_carry_a_content_hash() {
+        let glb = SyntheticGlb {
+            meshes: 4,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            content_hash_uris: true,
+            ..Default::default()
+        };
+
+        let Optimized {
+            glb: split,
+            buffers,
+            ..
+        } = Optimizer::new(&options)
+            .buffer_cap(256, "model_")
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let (json, _) = read_glb(&mut Cursor::new(split)).unwrap();
+        assert!(!buffers.is_empty());
+        for (i, (buffer, external)) in json.buffers[1..].iter().zip(&buffers).enumerate() {
+            let hash = content_hash(&external.data);
+            assert_eq!(external.uri, format!("model_buffer{}.{hash}.bin", i + 1));
+            assert_eq!(buffer.uri.as_deref(), Some(external.uri.as_str()));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{
+    Camera, Node, Scene,
+    camera::{Perspective, Type},
+    scene::UnitQuaternion,
+    validation::Checked,
+};
+
+use crate::{
+    bounds::{BoundingSphere, compute_bounding_volumes, scene_roots},
+    error::OptimizeError,
+    glb::{read_glb, to_extras, write_glb},
+};
+
+/// Placement of an injected perspective camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct CameraFraming {
+    /// Vertical field of view in radians
+    pub yfov: f32,
+    /// Aspect ratio written to the camera; `None` lets the viewer use its viewport
+    pub aspect_ratio: Option<f32>,
+    /// Rotation around the vertical axis in radians, 0 looks from +Z towards the model
+    pub yaw: f32,
+    /// Elevation above the horizon in radians, positive looks down at the model
+    pub pitch: f32,
+    /// Multiplier on the bounding sphere radius, values above 1 leave empty space around the model
+    pub margin: f32,
+}
+
+impl Default for CameraFraming {
+    fn default() -> Self {
+        CameraFraming {
+            yfov: 45f32.to_radians(),
+            aspect_ratio: None,
+            yaw: 30f32.to_radians(),
+            pitch: 20f32.to_radians(),
+            margin: 1.1,
+        }
+    }
+}
+
+/// Camera pose fitted around a bounding sphere
+pub(crate) struct FramedView {
+    pub eye: [f32; 3],
+    pub rotation: [f32; 4],
+    pub distance: f32,
+    pub radius: f32,
+}
+
+impl CameraFraming {
+    pub(crate) fn fit(&self, sphere: &BoundingSphere) -> FramedView {
+        let center = sphere.center;
+        let radius = (sphere.radius * self.margin).max(f32::EPSILON);
+
+        // Narrowest of the vertical and horizontal FOV decides the fitting distance
+        let half_fov = match self.aspect_ratio {
+            Some(aspect) if aspect < 1.0 => ((self.yfov / 2.0).tan() * aspect).atan(),
+            _ => self.yfov / 2.0,
+        };
+        let distance = radius / half_fov.sin();
+
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        let eye = [
+            center[0] + sy * cp * distance,
+            center[1] + sp * distance,
+            center[2] + cy * cp * distance,
+        ];
+
+        // Yaw around +Y followed by pitching down around the local X axis
+        let (qy_s, qy_c) = (self.yaw / 2.0).sin_cos();
+        let (qp_s, qp_c) = (-self.pitch / 2.0).sin_cos();
+        let rotation = [qy_c * qp_s, qy_s * qp_c, -qy_s * qp_s, qy_c * qp_c];
+
+        FramedView {
+            eye,
+            rotation,
+            distance,
+            radius,
+        }
+    }
+}
+
+/// Insert a camera node that frames the default scene's bounding sphere
+///
+/// Run this on the optimized output so the framing reflects any pivot or scale changes.
+/// The camera is added as a root node of the default scene (or scene 0) named `framing_camera`.
+pub fn add_framing_camera<R: Read + Seek>(
+    reader: &mut R,
+    framing: &CameraFraming,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut n_json, n_blob) = read_glb(reader)?;
+
+    if n_json.scenes.is_empty() {
+        let roots = scene_roots(&n_json);
+        let idx = n_json.push(Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes: roots,
+        });
+        n_json.scene = Some(idx);
+    }
+
+    let scene_idx = n_json.scene.map(|s| s.value()).unwrap_or(0);
+    let volume = compute_bounding_volumes(&n_blob, &n_json)
+        .scenes
+        .get(scene_idx)
+        .copied()
+        .flatten()
+        .ok_or(OptimizeError::NoGeometry { task: "frame" })?;
+
+    let view = framing.fit(&volume.sphere);
+    let (distance, radius) = (view.distance, view.radius);
+
+    let camera = n_json.push(Camera {
+        name: Some("framing_camera".to_string()),
+        orthographic: None,
+        perspective: Some(Perspective {
+            aspect_ratio: framing.aspect_ratio,
+            yfov: framing.yfov,
+            zfar: Some(distance + radius),
+            znear: (distance - radius).max(distance * 0.01),
+            extensions: None,
+            extras: Default::default(),
+        }),
+        type_: Checked::Valid(Type::Perspective),
+        extensions: None,
+        extras: Default::default(),
+    });
+
+    let node = n_json.push(Node {
+        camera: Some(camera),
+        name: Some("framing_camera".to_string()),
+        rotation: Some(UnitQuaternion(view.rotation)),
+        translation: Some(view.eye),
+        extras: to_extras(serde_json::json!({ "framingCamera": true })),
+        ..Default::default()
+    });
+
+    if let Some(scene) = n_json.scenes.get_mut(scene_idx) {
+        scene.nodes.push(node);
+    }
+
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    /// Rotate `v` by the unit quaternion `q` (x, y, z, w)
+    fn rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+        let [x, y, z, w] = q;
+        let u = [x, y, z];
+        let cross = |a: [f32; 3], b: [f32; 3]| {
+            [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ]
+        };
+        let t = cross(u, v).map(|c| 2.0 * c);
+        let ut = cross(u, t);
+        [0, 1, 2].map(|i| v[i] + w * t[i] + ut[i])
+    }
+
+    #[test]
+    fn camera_looks_at_the_sphere_from_its_angles() {
+        let sphere = BoundingSphere {
+            center: [1.0, 2.0, 3.0],
+            radius: 2.0,
+        };
+        let framing = CameraFraming {
+            yaw: 0.0,
+            pitch: 0.0,
+            margin: 1.0,
+            ..Default::default()
+        };
+        let view = framing.fit(&sphere);
+        let distance = 2.0 / (framing.yfov / 2.0).sin();
+        assert!((view.distance - distance).abs() < 1e-5);
+        assert!((view.eye[2] - (3.0 + distance)).abs() < 1e-5);
+        assert_eq!(view.rotation, [0.0, 0.0, 0.0, 1.0]);
+
+        let view = CameraFraming::default().fit(&sphere);
+        let forward = rotate(view.rotation, [0.0, 0.0, -1.0]);
+        let to_center = [0, 1, 2].map(|i| (sphere.center[i] - view.eye[i]) / view.distance);
+        for i in 0..3 {
+            assert!(
+                (forward[i] - to_center[i]).abs() < 1e-5,
+                "{forward:?} {to_center:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn narrow_aspect_ratios_move_the_camera_back() {
+        let sphere = BoundingSphere {
+            center: [0.0; 3],
+            radius: 1.0,
+        };
+        let wide = CameraFraming::default().fit(&sphere);
+        let narrow = CameraFraming {
+            aspect_ratio: Some(0.5),
+            ..Default::default()
+        }
+        .fit(&sphere);
+        assert!(narrow.distance > wide.distance);
+    }
+
+    #[test]
+    fn camera_node_joins_the_default_scene() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let out = add_framing_camera(&mut Cursor::new(glb), &CameraFraming::default()).unwrap();
+        let (json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let node = json.scenes[0].nodes.last().unwrap();
+        let node = &json.nodes[node.value()];
+        assert_eq!(node.name.as_deref(), Some("framing_camera"));
+        let perspective = json.cameras[node.camera.unwrap().value()]
+            .perspective
+            .as_ref()
+            .unwrap();
+        assert!(perspective.znear > 0.0 && perspective.zfar.unwrap() > perspective.znear);
+    }
+
+    #[test]
+    fn scenes_without_geometry_are_refused() {
+        let glb = write_glb(&Default::default(), Vec::new()).unwrap();
+        let err = add_framing_camera(&mut Cursor::new(glb), &CameraFraming::default()).unwrap_err();
+        assert!(matches!(err, OptimizeError::NoGeometry { task: "frame" }));
+    }
+}
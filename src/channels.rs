@@ -0,0 +1,266 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Image, Texture, image::MimeType};
+use image::{GrayImage, RgbaImage, imageops};
+
+use crate::{
+    blob::BlobWriter,
+    decode::load_image,
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    opt::{TextureType, encode_png, optimize_document},
+    options::OptimizeOptions,
+    variants::set_slot,
+};
+
+/// One color channel of an RGBA image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Red => 0,
+            Channel::Green => 1,
+            Channel::Blue => 2,
+            Channel::Alpha => 3,
+        }
+    }
+}
+
+/// Where a channel of a combined image comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChannelInput<'a> {
+    /// The same value for every pixel
+    Constant(u8),
+    /// One channel of an encoded image (PNG, JPEG, WebP, ...); grayscale images have the same
+    /// value in red, green and blue
+    Image { data: &'a [u8], channel: Channel },
+}
+
+impl<'a> ChannelInput<'a> {
+    /// The gray value of a grayscale image, or its red channel
+    pub fn gray(data: &'a [u8]) -> Self {
+        ChannelInput::Image {
+            data,
+            channel: Channel::Red,
+        }
+    }
+}
+
+/// Split an encoded image into red, green, blue and alpha, each as a grayscale PNG of the
+/// image's size
+///
+/// Images without alpha get a fully opaque alpha channel.
+pub fn split_channels(image: &[u8]) -> Result<[Vec<u8>; 4], OptimizeError> {
+    let rgba = load_image(image, Vec::new())?.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let mut pngs: [Vec<u8>; 4] = Default::default();
+    for (c, png) in pngs.iter_mut().enumerate() {
+        let gray = GrayImage::from_fn(width, height, |x, y| image::Luma([rgba[(x, y)][c]]));
+        encode_png(gray.as_raw(), width, height, image::ColorType::L8, png)?;
+    }
+    Ok(pngs)
+}
+
+/// Build a PNG from red, green, blue and alpha inputs taken from different sources
+///
+/// The result has the size of the largest input image, and smaller ones are scaled up to it.
+/// It is RGB unless alpha is anything but `Constant(255)`. At least one input must be an image.
+pub fn combine_channels(channels: [ChannelInput; 4]) -> Result<Vec<u8>, OptimizeError> {
+    let mut images = Vec::new();
+    for input in &channels {
+        if let ChannelInput::Image { data, .. } = input {
+            images.push(load_image(data, Vec::new())?.to_rgba8());
+        }
+    }
+    let width = images.iter().map(|i| i.width()).max();
+    let height = images.iter().map(|i| i.height()).max();
+    let (Some(width), Some(height)) = (width, height) else {
+        return Err(OptimizeError::InvalidOption(
+            "combining channels needs at least one image".into(),
+        ));
+    };
+    for image in &mut images {
+        if image.dimensions() != (width, height) {
+            *image = imageops::resize(image, width, height, imageops::FilterType::Triangle);
+        }
+    }
+
+    let mut combined = RgbaImage::new(width, height);
+    let mut sources = images.iter();
+    for (c, input) in channels.iter().enumerate() {
+        match input {
+            ChannelInput::Constant(value) => combined.pixels_mut().for_each(|p| p[c] = *value),
+            ChannelInput::Image { channel, .. } => {
+                let source = sources.next().expect("one decoded image per image input");
+                for (p, s) in combined.pixels_mut().zip(source.pixels()) {
+                    p[c] = s[channel.index()];
+                }
+            }
+        }
+    }
+
+    let mut png = Vec::new();
+    if channels[3] == ChannelInput::Constant(255) {
+        let rgb = image::DynamicImage::ImageRgba8(combined).to_rgb8();
+        encode_png(
+            rgb.as_raw(),
+            width,
+            height,
+            image::ColorType::Rgb8,
+            &mut png,
+        )?;
+    } else {
+        encode_png(
+            combined.as_raw(),
+            width,
+            height,
+            image::ColorType::Rgba8,
+            &mut png,
+        )?;
+    }
+    Ok(png)
+}
+
+/// Give material `material` an occlusion/roughness/metallic texture packed from three inputs
+///
+/// The inputs go to red, green and blue as glTF expects, and the packed texture becomes the
+/// material's metallic/roughness texture and, unless `occlusion` is a constant, its occlusion
+/// texture too. Metallic and roughness factors are set to 1 so the texture values apply as
+/// they are. Without `options` everything else is copied as is and the replaced textures stay
+/// in the file; with them the result is optimized like
+/// [`optimize_with`](crate::optimize_with), which resizes and encodes the packed
+/// texture like any other and prunes the replaced ones.
+pub fn set_orm_texture<R: Read + Seek>(
+    reader: &mut R,
+    material: usize,
+    occlusion: ChannelInput,
+    roughness: ChannelInput,
+    metallic: ChannelInput,
+    options: Option<&OptimizeOptions>,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut o_json, o_blob) = read_glb(reader)?;
+    let sampler = {
+        let mat = o_json
+            .materials
+            .get(material)
+            .ok_or(OptimizeError::MissingObject {
+                kind: "material",
+                index: material,
+            })?;
+        mat.pbr_metallic_roughness
+            .metallic_roughness_texture
+            .as_ref()
+            .map(|t| t.index)
+            .or(mat.occlusion_texture.as_ref().map(|t| t.index))
+            .and_then(|t| o_json.textures.get(t.value()))
+            .and_then(|t| t.sampler)
+    };
+    let png = combine_channels([occlusion, roughness, metallic, ChannelInput::Constant(255)])?;
+
+    let mut blob = BlobWriter::from(o_blob);
+    let view = blob.push_view(&mut o_json, &png, None);
+    let image = o_json.push(Image {
+        buffer_view: Some(view),
+        mime_type: Some(MimeType("image/png".to_string())),
+        name: None,
+        uri: None,
+        extensions: None,
+        extras: Default::default(),
+    });
+    let texture = o_json.push(Texture {
+        name: None,
+        sampler,
+        source: image,
+        extensions: None,
+        extras: Default::default(),
+    });
+    let mat = &mut o_json.materials[material];
+    set_slot(mat, TextureType::MetallicRoughness, texture);
+    mat.pbr_metallic_roughness.metallic_factor = Default::default();
+    mat.pbr_metallic_roughness.roughness_factor = Default::default();
+    if matches!(occlusion, ChannelInput::Image { .. }) {
+        set_slot(mat, TextureType::Occlusion, texture);
+    }
+
+    let Some(options) = options else {
+        return write_glb(&o_json, blob.into_vec());
+    };
+    let (mut n_json, n_blob, _) = optimize_document(&blob, &o_json, options, None, None)?;
+    retain_extensions(&mut n_json, &[])?;
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::get_image_data, testing::SyntheticGlb};
+
+    #[test]
+    fn orm_texture_is_packed_from_gray_inputs() {
+        let gray = |size: u32, value: u8| {
+            let mut png = Vec::new();
+            let pixels = vec![value; (size * size) as usize];
+            encode_png(&pixels, size, size, image::ColorType::L8, &mut png).unwrap();
+            png
+        };
+        let (occlusion, roughness) = (gray(8, 200), gray(4, 100));
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            texture_size: 4,
+            ..Default::default()
+        };
+        let out = set_orm_texture(
+            &mut Cursor::new(glb),
+            0,
+            ChannelInput::gray(&occlusion),
+            ChannelInput::gray(&roughness),
+            ChannelInput::Constant(30),
+            Some(&options),
+        )
+        .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+
+        let mat = &json.materials[0];
+        let orm = mat
+            .pbr_metallic_roughness
+            .metallic_roughness_texture
+            .as_ref()
+            .unwrap()
+            .index;
+        assert_eq!(mat.occlusion_texture.as_ref().unwrap().index, orm);
+        assert_eq!(mat.pbr_metallic_roughness.metallic_factor.0, 1.0);
+        let data = get_image_data(&blob, &json, orm).unwrap();
+        let image = image::load_from_memory(data).unwrap().to_rgba8();
+        // Metallic/roughness maps default to half of `texture_size`
+        assert_eq!(image.dimensions(), (2, 2));
+        // Resizing may round a value by one
+        let close = |pixel: [u8; 4]| {
+            pixel
+                .iter()
+                .zip([200u8, 100, 30, 255])
+                .all(|(&v, e)| v.abs_diff(e) <= 1)
+        };
+        assert!(image.pixels().all(|p| close(p.0)));
+
+        let [r, g, b, a] = split_channels(data).unwrap();
+        let value = |png: &[u8]| image::load_from_memory(png).unwrap().to_luma8()[(0, 0)].0[0];
+        assert!(close([value(&r), value(&g), value(&b), value(&a)]));
+    }
+}
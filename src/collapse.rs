@@ -0,0 +1,99 @@
+use gltf::json::{
+    Index, Material, Root,
+    material::{PbrBaseColorFactor, PbrMetallicRoughness, StrengthFactor},
+    mesh::Semantic,
+    validation::Checked,
+};
+
+use crate::{crop::RewrittenDocument, variants::VARIANTS_EXTENSION};
+
+/// Name of the material [`collapse_materials`] draws everything with
+const COLLAPSED_MATERIAL: &str = "collapsed";
+
+/// Neutral light grey, fully rough dielectric
+const NEUTRAL_BASE_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+/// Draw every primitive with one untextured neutral material
+///
+/// The material is double-sided when any source material was, so proxies keep their
+/// silhouettes. UV sets, tangents and vertex colors go with the textures, as does every
+/// `KHR_materials_variants` mapping. Source materials and textures are left unreferenced for
+/// the output to drop.
+pub(crate) fn collapse_materials(o_blob: &[u8], o_json: &Root) -> RewrittenDocument {
+    let mut n_json = o_json.clone();
+    let double_sided = o_json.materials.iter().any(|m| m.double_sided);
+    n_json.materials = vec![Material {
+        name: Some(COLLAPSED_MATERIAL.to_string()),
+        double_sided,
+        pbr_metallic_roughness: PbrMetallicRoughness {
+            base_color_factor: PbrBaseColorFactor(NEUTRAL_BASE_COLOR),
+            metallic_factor: StrengthFactor(0.0),
+            roughness_factor: StrengthFactor(1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    }];
+
+    for primitive in n_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+        primitive.material = Some(Index::new(0));
+        primitive.attributes.retain(|semantic, _| {
+            !matches!(
+                semantic,
+                Checked::Valid(Semantic::TexCoords(_) | Semantic::Tangents | Semantic::Colors(_))
+            )
+        });
+        for target in primitive.targets.iter_mut().flatten() {
+            target.tangents = None;
+        }
+        if let Some(extensions) = &mut primitive.extensions {
+            extensions.others.remove(VARIANTS_EXTENSION);
+        }
+    }
+    if let Some(extensions) = &mut n_json.extensions {
+        extensions.others.remove(VARIANTS_EXTENSION);
+    }
+    n_json.extensions_used.retain(|e| e != VARIANTS_EXTENSION);
+    n_json
+        .extensions_required
+        .retain(|e| e != VARIANTS_EXTENSION);
+    (n_json, o_blob.to_vec())
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::read_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn collapsed_materials_leave_one_untextured_material() {
+        let glb = SyntheticGlb {
+            textures: 3,
+            primitives_per_mesh: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            collapse_materials: true,
+            merge_primitives: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(n_json.materials.len(), 1);
+        assert!(n_json.textures.is_empty() && n_json.images.is_empty());
+        let primitives = &n_json.meshes[0].primitives;
+        assert_eq!(primitives.len(), 1);
+        assert_eq!(primitives[0].material, Some(Index::new(0)));
+        assert!(
+            primitives[0]
+                .attributes
+                .keys()
+                .all(|s| matches!(s, Checked::Valid(Semantic::Positions | Semantic::Normals)))
+        );
+    }
+}
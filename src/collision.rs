@@ -0,0 +1,489 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek},
+};
+
+use gltf::json::{
+    Index, Mesh, Node, Root,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    error::OptimizeError,
+    glb::{push_indices, push_positions, read_glb, to_extras, write_glb},
+    math::{bounds, cross, dot, sub},
+    opt::{get_index_data, get_position_data},
+};
+
+/// Kind of collision geometry generated for each mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum CollisionShape {
+    /// A single convex hull wrapping every primitive of the mesh
+    ConvexHull,
+    /// The render geometry decimated by clustering vertices on a uniform grid
+    Decimated {
+        /// Number of grid cells along the longest axis of the mesh bounds
+        grid_resolution: u32,
+    },
+}
+
+impl CollisionShape {
+    fn name(&self) -> &'static str {
+        match self {
+            CollisionShape::ConvexHull => "convex_hull",
+            CollisionShape::Decimated { .. } => "decimated",
+        }
+    }
+}
+
+/// Collect the triangle soup of every triangle-list primitive in a mesh
+fn collect_mesh_triangles(o_blob: &[u8], o_json: &Root, mesh: &Mesh) -> (Vec<[f32; 3]>, Vec<u32>) {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for primitive in &mesh.primitives {
+        if !matches!(primitive.mode, Checked::Valid(Mode::Triangles)) {
+            continue;
+        }
+
+        let Some(pos_idx) = primitive
+            .attributes
+            .get(&Checked::Valid(Semantic::Positions))
+        else {
+            continue;
+        };
+        let Some(prim_positions) = get_position_data(o_blob, o_json, *pos_idx) else {
+            continue;
+        };
+
+        let base = positions.len() as u32;
+        let prim_indices = match primitive.indices {
+            Some(idx) => match get_index_data(o_blob, o_json, idx) {
+                Some(i) => i,
+                None => continue,
+            },
+            None => (0..prim_positions.len() as u32).collect(),
+        };
+
+        indices.extend(
+            prim_indices
+                .chunks_exact(3)
+                .filter(|t| t.iter().all(|&i| (i as usize) < prim_positions.len()))
+                .flatten()
+                .map(|&i| base + i),
+        );
+        positions.extend(prim_positions);
+    }
+
+    (positions, indices)
+}
+
+/// Axis-aligned box as 8 corners and 12 outward-facing triangles
+fn box_hull(positions: &[[f32; 3]]) -> (Vec<[f32; 3]>, Vec<u32>) {
+    let (min, max) = bounds(positions);
+
+    let corners = (0..8)
+        .map(|c| {
+            [
+                if c & 1 == 0 { min[0] } else { max[0] },
+                if c & 2 == 0 { min[1] } else { max[1] },
+                if c & 4 == 0 { min[2] } else { max[2] },
+            ]
+        })
+        .collect();
+
+    let indices = vec![
+        0, 2, 1, 1, 2, 3, // -Z
+        4, 5, 6, 5, 7, 6, // +Z
+        0, 1, 4, 1, 5, 4, // -Y
+        2, 6, 3, 3, 6, 7, // +Y
+        0, 4, 2, 2, 4, 6, // -X
+        1, 3, 5, 3, 7, 5, // +X
+    ];
+
+    (corners, indices)
+}
+
+/// Incremental 3D convex hull; falls back to the bounding box for degenerate input
+fn convex_hull(positions: &[[f32; 3]]) -> (Vec<[f32; 3]>, Vec<u32>) {
+    if positions.len() < 4 {
+        return box_hull(positions);
+    }
+
+    let (min, max) = bounds(positions);
+    let extent = sub(max, min);
+    let eps = extent[0].max(extent[1]).max(extent[2]) * 1e-5;
+
+    // Seed tetrahedron: two furthest extreme points, then furthest from line, then from plane
+    let extremes: Vec<usize> = (0..3)
+        .flat_map(|axis| {
+            let lo = (0..positions.len())
+                .min_by(|&a, &b| positions[a][axis].total_cmp(&positions[b][axis]));
+            let hi = (0..positions.len())
+                .max_by(|&a, &b| positions[a][axis].total_cmp(&positions[b][axis]));
+            [lo, hi]
+        })
+        .flatten()
+        .collect();
+
+    let mut best = (0, 0, 0.0f32);
+    for &a in &extremes {
+        for &b in &extremes {
+            let d = sub(positions[a], positions[b]);
+            if dot(d, d) > best.2 {
+                best = (a, b, dot(d, d));
+            }
+        }
+    }
+    let (i0, i1) = (best.0, best.1);
+
+    let dir = sub(positions[i1], positions[i0]);
+    let i2 = (0..positions.len()).max_by(|&a, &b| {
+        let ca = cross(dir, sub(positions[a], positions[i0]));
+        let cb = cross(dir, sub(positions[b], positions[i0]));
+        dot(ca, ca).total_cmp(&dot(cb, cb))
+    });
+    let Some(i2) = i2 else {
+        return box_hull(positions);
+    };
+
+    let normal = cross(dir, sub(positions[i2], positions[i0]));
+    let i3 = (0..positions.len()).max_by(|&a, &b| {
+        dot(normal, sub(positions[a], positions[i0]))
+            .abs()
+            .total_cmp(&dot(normal, sub(positions[b], positions[i0])).abs())
+    });
+    let Some(i3) = i3 else {
+        return box_hull(positions);
+    };
+
+    let normal_len = dot(normal, normal).sqrt();
+    if normal_len <= eps * eps
+        || (dot(normal, sub(positions[i3], positions[i0])) / normal_len).abs() <= eps
+    {
+        return box_hull(positions);
+    }
+
+    let mut faces: Vec<[usize; 3]> = Vec::new();
+    let centroid = [0, 1, 2]
+        .map(|k| (positions[i0][k] + positions[i1][k] + positions[i2][k] + positions[i3][k]) / 4.0);
+    for face in [[i0, i1, i2], [i0, i1, i3], [i0, i2, i3], [i1, i2, i3]] {
+        let n = cross(
+            sub(positions[face[1]], positions[face[0]]),
+            sub(positions[face[2]], positions[face[0]]),
+        );
+        if dot(n, sub(centroid, positions[face[0]])) > 0.0 {
+            faces.push([face[0], face[2], face[1]]);
+        } else {
+            faces.push(face);
+        }
+    }
+
+    let is_above = |face: &[usize; 3], p: [f32; 3]| {
+        let a = positions[face[0]];
+        let n = cross(sub(positions[face[1]], a), sub(positions[face[2]], a));
+        let len = dot(n, n).sqrt();
+        len > 0.0 && dot(n, sub(p, a)) / len > eps
+    };
+
+    for (pi, &p) in positions.iter().enumerate() {
+        let visible: Vec<usize> = (0..faces.len())
+            .filter(|&f| is_above(&faces[f], p))
+            .collect();
+        if visible.is_empty() {
+            continue;
+        }
+
+        // Horizon = edges of visible faces whose twin belongs to a hidden face
+        let visible_edges: HashSet<(usize, usize)> = visible
+            .iter()
+            .flat_map(|&f| {
+                let [a, b, c] = faces[f];
+                [(a, b), (b, c), (c, a)]
+            })
+            .collect();
+        let horizon: Vec<(usize, usize)> = visible_edges
+            .iter()
+            .filter(|&&(a, b)| !visible_edges.contains(&(b, a)))
+            .copied()
+            .collect();
+
+        let visible_set: HashSet<usize> = visible.into_iter().collect();
+        faces = faces
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !visible_set.contains(i))
+            .map(|(_, f)| f)
+            .collect();
+        faces.extend(horizon.into_iter().map(|(a, b)| [a, b, pi]));
+    }
+
+    // Compact the vertex list down to the points referenced by hull faces
+    let mut remap: HashMap<usize, u32> = HashMap::new();
+    let mut hull_positions = Vec::new();
+    let mut hull_indices = Vec::with_capacity(faces.len() * 3);
+    for face in &faces {
+        for &v in face {
+            let idx = *remap.entry(v).or_insert_with(|| {
+                hull_positions.push(positions[v]);
+                (hull_positions.len() - 1) as u32
+            });
+            hull_indices.push(idx);
+        }
+    }
+
+    (hull_positions, hull_indices)
+}
+
+/// Vertex clustering decimation on a uniform grid sized by the longest axis
+fn decimate(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    grid_resolution: u32,
+) -> (Vec<[f32; 3]>, Vec<u32>) {
+    let (min, max) = bounds(positions);
+    let extent = sub(max, min);
+    let longest = extent[0].max(extent[1]).max(extent[2]);
+    let cell = if longest > 0.0 {
+        longest / grid_resolution.max(1) as f32
+    } else {
+        1.0
+    };
+
+    let mut cells: HashMap<[i64; 3], u32> = HashMap::new();
+    let mut sums: Vec<([f64; 3], u32)> = Vec::new();
+    let vertex_cell: Vec<u32> = positions
+        .iter()
+        .map(|p| {
+            let key = [0, 1, 2].map(|k| ((p[k] - min[k]) / cell).floor() as i64);
+            let id = *cells.entry(key).or_insert_with(|| {
+                sums.push(([0.0; 3], 0));
+                (sums.len() - 1) as u32
+            });
+            let sum = &mut sums[id as usize];
+            for (acc, v) in sum.0.iter_mut().zip(p) {
+                *acc += *v as f64;
+            }
+            sum.1 += 1;
+            id
+        })
+        .collect();
+
+    let n_positions = sums
+        .iter()
+        .map(|(s, n)| [0, 1, 2].map(|k| (s[k] / *n as f64) as f32))
+        .collect();
+
+    let mut seen: HashSet<[u32; 3]> = HashSet::new();
+    let mut n_indices = Vec::new();
+    for tri in indices.chunks_exact(3) {
+        let t = [0, 1, 2].map(|k| vertex_cell[tri[k] as usize]);
+        if t[0] == t[1] || t[1] == t[2] || t[0] == t[2] {
+            continue;
+        }
+
+        // Rotate so the smallest index leads, keeping winding for the dedup key
+        let lead = (0..3).min_by_key(|&k| t[k]).unwrap_or(0);
+        let key = [t[lead], t[(lead + 1) % 3], t[(lead + 2) % 3]];
+        if seen.insert(key) {
+            n_indices.extend_from_slice(&t);
+        }
+    }
+
+    (n_positions, n_indices)
+}
+
+/// Emit a collision mesh for every node that references a mesh
+///
+/// Each source mesh gets one extra mesh named `<mesh>_collider` holding only positions and
+/// indices, tagged with `extras: {"collider": "<shape>", "sourceMesh": <index>}`. A child node
+/// referencing it is attached to every node using the source mesh, so node transforms apply.
+pub fn add_collision_meshes<R: Read + Seek>(
+    reader: &mut R,
+    shape: CollisionShape,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut n_json, n_blob) = read_glb(reader)?;
+    let mut n_blob = BlobWriter::from(n_blob);
+
+    let mut collider_meshes: HashMap<usize, Index<Mesh>> = HashMap::new();
+
+    for node_idx in 0..n_json.nodes.len() {
+        let Some(mesh_idx) = n_json.nodes[node_idx].mesh else {
+            continue;
+        };
+
+        let collider = match collider_meshes.get(&mesh_idx.value()) {
+            Some(collider) => *collider,
+            None => {
+                let Some(mesh) = n_json.meshes.get(mesh_idx.value()) else {
+                    continue;
+                };
+
+                let (positions, indices) = collect_mesh_triangles(&n_blob, &n_json, mesh);
+                if positions.is_empty() || indices.is_empty() {
+                    continue;
+                }
+
+                let (c_positions, c_indices) = match shape {
+                    CollisionShape::ConvexHull => convex_hull(&positions),
+                    CollisionShape::Decimated { grid_resolution } => {
+                        decimate(&positions, &indices, grid_resolution)
+                    }
+                };
+                if c_indices.is_empty() {
+                    continue;
+                }
+
+                let name = mesh
+                    .name
+                    .as_ref()
+                    .map(|n| format!("{n}_collider"))
+                    .unwrap_or_else(|| format!("mesh_{}_collider", mesh_idx.value()));
+
+                let pos_acc = push_positions(&mut n_blob, &mut n_json, &c_positions);
+                let idx_acc = push_indices(&mut n_blob, &mut n_json, &c_indices);
+
+                let mut primitive = Primitive {
+                    attributes: Default::default(),
+                    extensions: None,
+                    extras: Default::default(),
+                    indices: Some(idx_acc),
+                    material: None,
+                    mode: Checked::Valid(Mode::Triangles),
+                    targets: None,
+                };
+                primitive
+                    .attributes
+                    .insert(Checked::Valid(Semantic::Positions), pos_acc);
+
+                let collider = n_json.push(Mesh {
+                    extensions: None,
+                    extras: to_extras(serde_json::json!({
+                        "collider": shape.name(),
+                        "sourceMesh": mesh_idx.value(),
+                    })),
+                    name: Some(name),
+                    primitives: vec![primitive],
+                    weights: None,
+                });
+                collider_meshes.insert(mesh_idx.value(), collider);
+                collider
+            }
+        };
+
+        let node_name = n_json.nodes[node_idx]
+            .name
+            .as_ref()
+            .map(|n| format!("{n}_collider"));
+        let child = n_json.push(Node {
+            mesh: Some(collider),
+            name: node_name,
+            extras: to_extras(serde_json::json!({ "collider": shape.name() })),
+            ..Default::default()
+        });
+        n_json.nodes[node_idx]
+            .children
+            .get_or_insert_with(Vec::new)
+            .push(child);
+    }
+
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    /// Corners of a unit cube followed by points inside it
+    fn cube_with_interior() -> Vec<[f32; 3]> {
+        let mut points: Vec<[f32; 3]> = (0..8)
+            .map(|c| [c & 1, c >> 1 & 1, c >> 2 & 1].map(|v| v as f32))
+            .collect();
+        points.extend([[0.5, 0.5, 0.5], [0.2, 0.7, 0.4], [0.9, 0.1, 0.3]]);
+        points
+    }
+
+    #[test]
+    fn convex_hull_wraps_every_point_with_outward_faces() {
+        let points = cube_with_interior();
+        let (hull, indices) = convex_hull(&points);
+        // Interior points are dropped and each cube face takes two triangles
+        assert_eq!(hull.len(), 8);
+        assert_eq!(indices.len(), 12 * 3);
+
+        let center = [0.5, 0.5, 0.5];
+        for tri in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| hull[tri[k] as usize]);
+            let normal = cross(sub(b, a), sub(c, a));
+            assert!(dot(normal, sub(a, center)) > 0.0, "inward face {tri:?}");
+            for &p in &points {
+                assert!(dot(normal, sub(p, a)) <= 1e-5, "{p:?} outside face {tri:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn flat_input_falls_back_to_its_box() {
+        let (hull, indices) = convex_hull(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(hull.len(), 8);
+        assert_eq!(indices.len(), 36);
+    }
+
+    #[test]
+    fn decimation_merges_vertices_per_grid_cell() {
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [0.1, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let (merged, indices) = decimate(&positions, &[0, 1, 3, 1, 2, 3], 2);
+        // The first two vertices share a cell, which collapses the first triangle
+        assert_eq!(merged.len(), 3);
+        assert_eq!(indices.len(), 3);
+    }
+
+    #[test]
+    fn every_mesh_node_gets_a_collider_child() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = add_collision_meshes(&mut Cursor::new(glb), CollisionShape::ConvexHull).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(json.meshes.len(), 4);
+        for m in 0..2 {
+            let node = &json.nodes[m];
+            let child = &json.nodes[node.children.as_ref().unwrap()[0].value()];
+            let collider = &json.meshes[child.mesh.unwrap().value()];
+            assert_eq!(
+                collider.name.as_deref(),
+                Some(&*format!("mesh_{m}_collider"))
+            );
+            let extras: serde_json::Value =
+                serde_json::from_str(collider.extras.as_ref().unwrap().get()).unwrap();
+            assert_eq!(extras["sourceMesh"], m);
+
+            let primitive = &collider.primitives[0];
+            let positions = get_position_data(
+                &blob,
+                &json,
+                primitive.attributes[&Checked::Valid(Semantic::Positions)],
+            )
+            .unwrap();
+            assert!(
+                positions
+                    .iter()
+                    .all(|p| p[0] >= 2.0 * m as f32 && p[1] <= 1.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::{Read, Seek};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    compat::TargetProfile,
+    error::OptimizeError,
+    opt::{ImageFormat, optimize_with},
+    options::OptimizeOptions,
+    simplify::{SimplifyOptions, SimplifyTarget},
+};
+
+/// Every profile a `targetProfile` command can name
+const PROFILES: [TargetProfile; 5] = [
+    TargetProfile::ThreeJs,
+    TargetProfile::BabylonJs,
+    TargetProfile::UnityGltfast,
+    TargetProfile::FilamentAndroid,
+    TargetProfile::QuickLookUsdz,
+];
+
+/// Setting a flag command switches
+type Flag = fn(&mut OptimizeOptions) -> &mut bool;
+
+/// Parameters of one command, failing on names the command doesn't take
+struct Params<'a> {
+    op: &'a str,
+    object: &'a Map<String, Value>,
+}
+
+impl Params<'_> {
+    fn error(&self, message: String) -> OptimizeError {
+        OptimizeError::InvalidOption(format!("{}: {message}", self.op))
+    }
+
+    fn check_known(&self, known: &[&str]) -> Result<(), OptimizeError> {
+        match self
+            .object
+            .keys()
+            .find(|k| *k != "op" && !known.contains(&k.as_str()))
+        {
+            Some(key) => Err(self.error(format!("unknown parameter {key}"))),
+            None => Ok(()),
+        }
+    }
+
+    fn u64(&self, key: &str) -> Result<Option<u64>, OptimizeError> {
+        self.object
+            .get(key)
+            .map(|v| {
+                v.as_u64()
+                    .ok_or_else(|| self.error(format!("{key} {v} is not a non-negative integer")))
+            })
+            .transpose()
+    }
+
+    fn u32(&self, key: &str) -> Result<Option<u32>, OptimizeError> {
+        self.u64(key)?
+            .map(|v| u32::try_from(v).map_err(|_| self.error(format!("{key} {v} is too large"))))
+            .transpose()
+    }
+
+    fn f32(&self, key: &str) -> Result<Option<f32>, OptimizeError> {
+        self.object
+            .get(key)
+            .map(|v| {
+                v.as_f64()
+                    .map(|f| f as f32)
+                    .ok_or_else(|| self.error(format!("{key} {v} is not a number")))
+            })
+            .transpose()
+    }
+
+    /// A flag that defaults to `true`, so `{"op": "centerPivot"}` turns it on
+    fn enabled(&self) -> Result<bool, OptimizeError> {
+        self.object.get("enabled").map_or(Ok(true), |v| {
+            v.as_bool()
+                .ok_or_else(|| self.error(format!("enabled {v} is not a boolean")))
+        })
+    }
+
+    fn str(&self, key: &str) -> Result<&str, OptimizeError> {
+        match self.object.get(key) {
+            Some(Value::String(s)) => Ok(s),
+            Some(v) => Err(self.error(format!("{key} {v} is not a string"))),
+            None => Err(self.error(format!("{key} is missing"))),
+        }
+    }
+}
+
+/// Apply one command to `options`
+fn apply_command(options: &mut OptimizeOptions, params: &Params) -> Result<(), OptimizeError> {
+    // Commands that only switch a setting on or off
+    let flags: [(&str, Flag); 17] = [
+        ("centerPivot", |o| &mut o.center_pivot),
+        ("removeNormalTextures", |o| &mut o.remove_normal_texture),
+        ("powerOfTwoTextures", |o| &mut o.power_of_two_textures),
+        ("stableIndices", |o| &mut o.stable_indices),
+        ("pruneUnused", |o| &mut o.prune_unused),
+        ("cropTextures", |o| &mut o.crop_textures),
+        ("fixMisslottedTextures", |o| &mut o.fix_misslotted_textures),
+        ("inferTextureClasses", |o| &mut o.infer_texture_classes),
+        ("halfPrecisionAttributes", |o| {
+            &mut o.half_precision_attributes
+        }),
+        ("quantizeAttributes", |o| &mut o.quantize_attributes),
+        ("meshoptCompression", |o| &mut o.meshopt_compression),
+        ("mergePrimitives", |o| &mut o.merge_primitives),
+        ("dedupeMeshes", |o| &mut o.dedupe_meshes),
+        ("bakeTextureTransforms", |o| &mut o.bake_texture_transforms),
+        ("streamingLayout", |o| &mut o.streaming_layout),
+        ("textureTable", |o| &mut o.texture_table),
+        ("neverGrow", |o| &mut o.never_grow),
+    ];
+    if let Some((_, field)) = flags.iter().find(|(name, _)| *name == params.op) {
+        params.check_known(&["enabled"])?;
+        *field(options) = params.enabled()?;
+        return Ok(());
+    }
+
+    match params.op {
+        "resizeTextures" => {
+            params.check_known(&["size", "powerOfTwo"])?;
+            options.texture_size = params
+                .u32("size")?
+                .ok_or_else(|| params.error("size is missing".into()))?;
+            if let Some(v) = params.object.get("powerOfTwo") {
+                options.power_of_two_textures = v
+                    .as_bool()
+                    .ok_or_else(|| params.error(format!("powerOfTwo {v} is not a boolean")))?;
+            }
+        }
+        "toKtx2" => {
+            params.check_known(&["mipmaps"])?;
+            options.convert_to_ktx2 = true;
+            if let Some(v) = params.object.get("mipmaps") {
+                options.ktx2_mipmaps = v
+                    .as_bool()
+                    .ok_or_else(|| params.error(format!("mipmaps {v} is not a boolean")))?;
+            }
+        }
+        "imageFormat" => {
+            params.check_known(&["format"])?;
+            options.image_format = Some(match params.str("format")? {
+                "jpeg" => ImageFormat::Jpeg,
+                "png" => ImageFormat::Png,
+                "ktx2" => ImageFormat::Ktx2,
+                "webp" => ImageFormat::WebP,
+                other => return Err(params.error(format!("unknown format {other}"))),
+            });
+        }
+        "textureBudget" => {
+            params.check_known(&["bytes"])?;
+            options.gpu_texture_budget = Some(
+                params
+                    .u64("bytes")?
+                    .ok_or_else(|| params.error("bytes is missing".into()))?,
+            );
+        }
+        "simplify" => {
+            params.check_known(&["ratio", "triangles", "maxError", "planarMaxTriangles"])?;
+            let target = match (params.f32("ratio")?, params.u64("triangles")?) {
+                (Some(ratio), None) => SimplifyTarget::Ratio(ratio),
+                (None, Some(triangles)) => SimplifyTarget::Triangles(triangles as usize),
+                _ => return Err(params.error("takes either ratio or triangles".into())),
+            };
+            let mut simplify = SimplifyOptions {
+                target,
+                ..Default::default()
+            };
+            if let Some(max_error) = params.f32("maxError")? {
+                simplify.max_error = max_error;
+            }
+            if let Some(max) = params.u64("planarMaxTriangles")? {
+                simplify.planar_max_triangles = max as usize;
+            }
+            options.simplify = Some(simplify);
+        }
+        "targetProfile" => {
+            params.check_known(&["name"])?;
+            let name = params.str("name")?;
+            let profile = PROFILES
+                .into_iter()
+                .find(|p| p.key() == name)
+                .ok_or_else(|| params.error(format!("unknown profile {name}")))?;
+            *options = OptimizeOptions::for_target(profile);
+        }
+        op => return Err(OptimizeError::InvalidOption(format!("unknown op {op}"))),
+    }
+    Ok(())
+}
+
+/// Settings a list of commands describes, applied in order to the defaults
+///
+/// `commands` is a JSON array of objects naming an `op` and its parameters, as taken by
+/// [`run_commands`]. A `targetProfile` command may only come first. Errors name the position
+/// of the failing command.
+pub fn commands_options(commands: &str) -> Result<OptimizeOptions, OptimizeError> {
+    let commands: Value = serde_json::from_str(commands)
+        .map_err(|e| OptimizeError::InvalidOption(format!("commands are not JSON: {e}")))?;
+    let Value::Array(commands) = commands else {
+        return Err(OptimizeError::InvalidOption(
+            "commands are not an array".into(),
+        ));
+    };
+
+    let mut options = OptimizeOptions::default();
+    for (i, command) in commands.iter().enumerate() {
+        let at = |e: OptimizeError| match e {
+            OptimizeError::InvalidOption(message) => {
+                OptimizeError::InvalidOption(format!("command {i}: {message}"))
+            }
+            e => e,
+        };
+        let Some(object) = command.as_object() else {
+            return Err(at(OptimizeError::InvalidOption("not an object".into())));
+        };
+        let Some(op) = object.get("op").and_then(Value::as_str) else {
+            return Err(at(OptimizeError::InvalidOption("op is missing".into())));
+        };
+        // A profile sets every option, so it would silently undo the commands before it
+        if op == "targetProfile" && i > 0 {
+            return Err(at(OptimizeError::InvalidOption(
+                "targetProfile must be the first command".into(),
+            )));
+        }
+        apply_command(&mut options, &Params { op, object }).map_err(at)?;
+    }
+    Ok(options)
+}
+
+/// Optimize a GLB as a list of named commands from JSON describes
+///
+/// For orchestrators outside Rust and for recipes kept next to the assets, e.g.
+/// `[{"op":"resizeTextures","size":1024},{"op":"centerPivot"},{"op":"toKtx2"}]`. Each command
+/// sets options in list order, starting from the defaults or from a leading `targetProfile`, so
+/// a later command overrides an earlier one; the file is then optimized in one pass, which
+/// keeps textures from being re-encoded once per command.
+pub fn run_commands<R: Read + Seek>(
+    reader: &mut R,
+    commands: &str,
+) -> Result<Vec<u8>, OptimizeError> {
+    optimize_with(reader, &commands_options(commands)?)
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    #[test]
+    fn commands_set_options_in_order() {
+        use crate::glb::read_glb;
+
+        let options = commands_options(
+            r#"[
+                {"op": "resizeTextures", "size": 512},
+                {"op": "centerPivot"},
+                {"op": "toKtx2", "mipmaps": true},
+                {"op": "simplify", "ratio": 0.25},
+                {"op": "pruneUnused", "enabled": false}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(options.texture_size, 512);
+        assert!(options.center_pivot && options.convert_to_ktx2 && options.ktx2_mipmaps);
+        assert_eq!(
+            options.simplify.map(|s| s.target),
+            Some(SimplifyTarget::Ratio(0.25))
+        );
+        assert!(!options.prune_unused);
+
+        // Later commands build on a profile, which can't silently undo earlier ones
+        let options = commands_options(
+            r#"[{"op": "targetProfile", "name": "threejs"}, {"op": "textureTable"}]"#,
+        )
+        .unwrap();
+        assert_eq!(options.target_profile, Some(TargetProfile::ThreeJs));
+        assert!(options.texture_table);
+
+        let error = |commands: &str| commands_options(commands).unwrap_err().to_string();
+        let late_profile = error(
+            r#"[{"op": "resizeTextures", "size": 256},
+                {"op": "targetProfile", "name": "threejs"}]"#,
+        );
+        assert!(late_profile.contains("command 1: targetProfile must be the first command"));
+        assert!(error(r#"[{"op": "centerPivot"}, {"op": "explode"}]"#).contains("command 1"));
+        assert!(error(r#"[{"op": "resizeTextures", "size": -1}]"#).contains("size"));
+        assert!(error(r#"[{"op": "centerPivot", "size": 3}]"#).contains("unknown parameter"));
+        assert!(error(r#"{"op": "centerPivot"}"#).contains("not an array"));
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = run_commands(
+            &mut Cursor::new(glb),
+            r#"[{"op": "resizeTextures", "size": 16}, {"op": "textureTable"}]"#,
+        )
+        .unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let table = &n_json.extensions.as_ref().unwrap().others["GLTFOPT_texture_table"];
+        assert_eq!(table["textures"][0]["width"], 16);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    io::{Read, Seek},
+    sync::LazyLock,
+};
+
+use serde_json::Value;
+
+use crate::{error::OptimizeError, glb::read_glb, opt::ImageFormat, options::OptimizeOptions};
+
+/// Extension/viewer support matrix, keyed by [`TargetProfile::key`]
+static PROFILE_MATRIX: LazyLock<Value> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("../data/profiles.json"))
+        .expect("bundled profile matrix is valid JSON")
+});
+
+/// Viewer or engine the output is meant to load in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TargetProfile {
+    ThreeJs,
+    BabylonJs,
+    UnityGltfast,
+    FilamentAndroid,
+    QuickLookUsdz,
+}
+
+impl TargetProfile {
+    /// Key of the profile in the bundled support matrix
+    pub fn key(&self) -> &'static str {
+        match self {
+            TargetProfile::ThreeJs => "threejs",
+            TargetProfile::BabylonJs => "babylonjs",
+            TargetProfile::UnityGltfast => "unity-gltfast",
+            TargetProfile::FilamentAndroid => "filament-android",
+            TargetProfile::QuickLookUsdz => "quicklook-usdz",
+        }
+    }
+
+    /// Human readable name including the version the matrix was written against
+    pub fn name(&self) -> String {
+        self.entry()
+            .and_then(|p| p["name"].as_str().map(str::to_string))
+            .unwrap_or_else(|| self.key().to_string())
+    }
+
+    /// Optimizer settings the target is known to load, starting from the defaults
+    ///
+    /// Texture size, KTX2 output, attribute quantization and meshopt compression come from
+    /// the matrix; outputs that need an optional decoder on the target stay off.
+    pub(crate) fn preset(&self) -> OptimizeOptions {
+        let defaults = OptimizeOptions::default();
+        let preset = self.entry().map_or(&Value::Null, |p| &p["preset"]);
+        let flag = |key: &str| preset[key].as_bool().unwrap_or(false);
+
+        OptimizeOptions {
+            texture_size: preset["texture_size"]
+                .as_u64()
+                .map_or(defaults.texture_size, |size| size as u32),
+            convert_to_ktx2: flag("ktx2"),
+            quantize_attributes: flag("quantize"),
+            meshopt_compression: flag("meshopt"),
+            target_profile: Some(*self),
+            ..defaults
+        }
+    }
+
+    fn entry(&self) -> Option<&'static Value> {
+        PROFILE_MATRIX.get(self.key())
+    }
+}
+
+/// Problem found when matching a file's extensions against a [`TargetProfile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompatibilityIssue {
+    /// The target cannot load the extension at all
+    Unsupported { extension: String },
+    /// The target loads the extension only with an extra decoder installed
+    NeedsDecoder { extension: String, decoder: String },
+    /// Both extensions are used but the target handles only one of them per file
+    Conflict { first: String, second: String },
+}
+
+/// Check a set of extension names against a target profile
+pub fn check_extensions(profile: TargetProfile, extensions: &[String]) -> Vec<CompatibilityIssue> {
+    let Some(entry) = profile.entry() else {
+        return Vec::new();
+    };
+    let listed = |key: &str, ext: &str| {
+        entry[key]
+            .as_array()
+            .is_some_and(|list| list.iter().any(|e| e.as_str() == Some(ext)))
+    };
+
+    let mut issues = Vec::new();
+    for ext in extensions {
+        if let Some(decoder) = entry["decoders"][ext.as_str()].as_str() {
+            issues.push(CompatibilityIssue::NeedsDecoder {
+                extension: ext.clone(),
+                decoder: decoder.to_string(),
+            });
+        } else if !listed("supported", ext) {
+            issues.push(CompatibilityIssue::Unsupported {
+                extension: ext.clone(),
+            });
+        }
+    }
+
+    for group in entry["exclusive"].as_array().into_iter().flatten() {
+        let used: Vec<&str> = group
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|e| extensions.iter().any(|x| x == e))
+            .collect();
+        if let [first, second, ..] = used[..] {
+            issues.push(CompatibilityIssue::Conflict {
+                first: first.to_string(),
+                second: second.to_string(),
+            });
+        }
+    }
+
+    issues
+}
+
+/// Check every extension a GLB uses or requires against a target profile
+pub fn check_compatibility<R: Read + Seek>(
+    reader: &mut R,
+    profile: TargetProfile,
+) -> Result<Vec<CompatibilityIssue>, OptimizeError> {
+    let (o_json, _) = read_glb(reader)?;
+
+    let mut extensions = o_json.extensions_used.clone();
+    for ext in &o_json.extensions_required {
+        if !extensions.contains(ext) {
+            extensions.push(ext.clone());
+        }
+    }
+
+    Ok(check_extensions(profile, &extensions))
+}
+
+/// Turn off optimizer outputs the target profile cannot load
+///
+/// KTX2 conversion falls back to JPEG/PNG when the profile has no `KHR_texture_basisu`
+/// support at all; an optional decoder is assumed to be installed. WebP output does the same
+/// without `EXT_texture_webp`. Half-precision and
+/// quantized attributes stay float without `KHR_mesh_quantization`, and buffers uncompressed
+/// without `EXT_meshopt_compression`.
+pub(crate) fn downgrade_for_profile(options: &OptimizeOptions) -> OptimizeOptions {
+    let mut options = options.clone();
+
+    if let Some(profile) = options.target_profile {
+        let unsupported = |extension: &str| {
+            check_extensions(profile, &[extension.to_string()])
+                .iter()
+                .any(|i| matches!(i, CompatibilityIssue::Unsupported { .. }))
+        };
+        if options.writes_ktx2() && unsupported("KHR_texture_basisu") {
+            options.convert_to_ktx2 = false;
+            options.image_format = None;
+        }
+        if options.image_format == Some(ImageFormat::WebP) && unsupported("EXT_texture_webp") {
+            options.image_format = None;
+        }
+        if options.half_precision_attributes && unsupported("KHR_mesh_quantization") {
+            options.half_precision_attributes = false;
+        }
+        if options.quantize_attributes && unsupported("KHR_mesh_quantization") {
+            options.quantize_attributes = false;
+        }
+        if options.meshopt_compression && unsupported("EXT_meshopt_compression") {
+            options.meshopt_compression = false;
+        }
+    }
+
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        testing::SyntheticGlb,
+    };
+
+    fn names(extensions: &[&str]) -> Vec<String> {
+        extensions.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn extensions_are_matched_against_the_profile() {
+        let issues = check_extensions(
+            TargetProfile::ThreeJs,
+            &names(&[
+                "KHR_texture_transform",
+                "KHR_texture_basisu",
+                "KHR_materials_variants",
+                "KHR_draco_mesh_compression",
+                "EXT_meshopt_compression",
+            ]),
+        );
+        assert_eq!(
+            issues,
+            [
+                CompatibilityIssue::NeedsDecoder {
+                    extension: "KHR_texture_basisu".into(),
+                    decoder: "GLTFLoader.setKTX2Loader".into(),
+                },
+                CompatibilityIssue::Unsupported {
+                    extension: "KHR_materials_variants".into(),
+                },
+                CompatibilityIssue::NeedsDecoder {
+                    extension: "KHR_draco_mesh_compression".into(),
+                    decoder: "GLTFLoader.setDRACOLoader".into(),
+                },
+                CompatibilityIssue::NeedsDecoder {
+                    extension: "EXT_meshopt_compression".into(),
+                    decoder: "GLTFLoader.setMeshoptDecoder".into(),
+                },
+                CompatibilityIssue::Conflict {
+                    first: "KHR_draco_mesh_compression".into(),
+                    second: "EXT_meshopt_compression".into(),
+                },
+            ]
+        );
+        assert!(check_extensions(TargetProfile::BabylonJs, &names(&["MSFT_lod"])).is_empty());
+    }
+
+    #[test]
+    fn required_extensions_are_checked_too() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.extensions_required = names(&["KHR_mesh_quantization"]);
+        let glb = write_glb(&json, blob).unwrap();
+        let issues = check_compatibility(&mut Cursor::new(glb), TargetProfile::QuickLookUsdz);
+        assert_eq!(
+            issues.unwrap(),
+            [CompatibilityIssue::Unsupported {
+                extension: "KHR_mesh_quantization".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unsupported_outputs_are_turned_off() {
+        let options = OptimizeOptions {
+            convert_to_ktx2: true,
+            quantize_attributes: true,
+            meshopt_compression: true,
+            target_profile: Some(TargetProfile::QuickLookUsdz),
+            ..Default::default()
+        };
+        let downgraded = downgrade_for_profile(&options);
+        assert!(!downgraded.convert_to_ktx2);
+        assert!(!downgraded.quantize_attributes);
+        assert!(!downgraded.meshopt_compression);
+
+        // A decoder the target can install counts as support
+        let options = OptimizeOptions {
+            target_profile: Some(TargetProfile::ThreeJs),
+            ..options
+        };
+        assert_eq!(downgrade_for_profile(&options), options);
+    }
+
+    #[test]
+    fn presets_follow_the_matrix() {
+        let babylon = TargetProfile::BabylonJs.preset();
+        assert!(babylon.convert_to_ktx2);
+        assert!(babylon.quantize_attributes);
+        assert!(babylon.meshopt_compression);
+        assert_eq!(babylon.texture_size, 2048);
+
+        // three.js needs a decoder for meshopt, so its preset leaves it off
+        let three = TargetProfile::ThreeJs.preset();
+        assert!(three.quantize_attributes);
+        assert!(!three.meshopt_compression);
+
+        let quicklook = TargetProfile::QuickLookUsdz.preset();
+        assert!(!quicklook.quantize_attributes);
+        assert!(!quicklook.meshopt_compression);
+        assert_eq!(quicklook.target_profile, Some(TargetProfile::QuickLookUsdz));
+        assert_eq!(TargetProfile::FilamentAndroid.preset().texture_size, 1024);
+        assert_eq!(TargetProfile::UnityGltfast.name(), "Unity glTFast 6");
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::{Read, Seek};
+
+use crate::{error::OptimizeError, opt::optimize_with, options::OptimizeOptions};
+
+/// Post-compression for serving a GLB from static hosting
+///
+/// Each format needs its cargo feature (`gzip`, `brotli`, `zstd`); this API exists once any
+/// of them is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WireCompression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl WireCompression {
+    /// File extension for the compressed GLB, e.g. `glb.gz`
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            WireCompression::Gzip => "glb.gz",
+            #[cfg(feature = "brotli")]
+            WireCompression::Brotli => "glb.br",
+            #[cfg(feature = "zstd")]
+            WireCompression::Zstd => "glb.zst",
+        }
+    }
+
+    /// HTTP `Content-Encoding` to serve the file with
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            WireCompression::Gzip => "gzip",
+            #[cfg(feature = "brotli")]
+            WireCompression::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            WireCompression::Zstd => "zstd",
+        }
+    }
+}
+
+/// Gzip at the best level
+#[cfg(feature = "gzip")]
+pub(crate) fn gzip(data: &[u8]) -> Result<Vec<u8>, OptimizeError> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Brotli at quality 11 with a 4 MiB window
+#[cfg(feature = "brotli")]
+pub(crate) fn brotli(data: &[u8]) -> Result<Vec<u8>, OptimizeError> {
+    use std::io::Write;
+
+    let mut compressed = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        encoder.write_all(data)?;
+    }
+    Ok(compressed)
+}
+
+/// Zstandard at level 19
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd(data: &[u8]) -> Result<Vec<u8>, OptimizeError> {
+    Ok(zstd::encode_all(data, 19)?)
+}
+
+/// Compress finished GLB bytes
+pub fn compress_glb(glb: &[u8], compression: WireCompression) -> Result<Vec<u8>, OptimizeError> {
+    match compression {
+        #[cfg(feature = "gzip")]
+        WireCompression::Gzip => gzip(glb),
+        #[cfg(feature = "brotli")]
+        WireCompression::Brotli => brotli(glb),
+        #[cfg(feature = "zstd")]
+        WireCompression::Zstd => zstd(glb),
+    }
+}
+
+/// Optimize a GLB and compress the result, ready to upload as a pre-compressed artifact
+///
+/// Serve the bytes as `model.{file_extension}` or with the matching `Content-Encoding`.
+pub fn optimize_compressed<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+    compression: WireCompression,
+) -> Result<Vec<u8>, OptimizeError> {
+    let glb = optimize_with(reader, options)?;
+    compress_glb(&glb, compression)
+}
+
+#[cfg(all(test, any(feature = "gzip", feature = "brotli", feature = "zstd")))]
+mod tests {
+    use std::io::Cursor;
+    #[cfg(any(feature = "gzip", feature = "brotli"))]
+    use std::io::Read;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    fn optimized(compression: WireCompression) -> (Vec<u8>, Vec<u8>) {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let options = OptimizeOptions::default();
+        let plain = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let compressed =
+            optimize_compressed(&mut Cursor::new(&glb), &options, compression).unwrap();
+        (plain, compressed)
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_output_inflates_to_the_optimized_glb() {
+        let (plain, compressed) = optimized(WireCompression::Gzip);
+        let mut inflated = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, plain);
+        assert_eq!(WireCompression::Gzip.file_extension(), "glb.gz");
+        assert_eq!(WireCompression::Gzip.content_encoding(), "gzip");
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_output_inflates_to_the_optimized_glb() {
+        let (plain, compressed) = optimized(WireCompression::Brotli);
+        let mut inflated = Vec::new();
+        brotli::Decompressor::new(&compressed[..], 4096)
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, plain);
+        assert_eq!(WireCompression::Brotli.content_encoding(), "br");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_output_inflates_to_the_optimized_glb() {
+        let (plain, compressed) = optimized(WireCompression::Zstd);
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), plain);
+        assert_eq!(WireCompression::Zstd.file_extension(), "glb.zst");
+    }
+}
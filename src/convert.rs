@@ -0,0 +1,223 @@
+//! Typed reads and writes of accessor components
+//!
+//! Follows the glTF normalization rules: unsigned values map to `[0, 1]`, signed values to
+//! `[-1, 1]` with the most negative integer clamped, and writes round to the nearest step.
+
+use gltf::json::{
+    Accessor, Index, Root,
+    accessor::{ComponentType, GenericComponentType, IndexComponentType},
+    buffer::View,
+    validation::Checked,
+};
+
+use crate::glb::accessor_stride;
+
+/// Convert a float to IEEE 754 half-precision bits
+pub fn f32_to_f16(value: f32) -> u16 {
+    half::f16::from_f32(value).to_bits()
+}
+
+/// Convert IEEE 754 half-precision bits to a float
+pub fn f16_to_f32(bits: u16) -> f32 {
+    half::f16::from_bits(bits).to_f32()
+}
+
+/// Decode one little-endian component, applying normalization for integer types
+///
+/// `bytes` must hold at least `component.size()` bytes.
+pub fn decode_component(component: ComponentType, normalized: bool, bytes: &[u8]) -> f32 {
+    let c = bytes;
+    match (component, normalized) {
+        (ComponentType::F32, _) => f32::from_le_bytes([c[0], c[1], c[2], c[3]]),
+        (ComponentType::I8, true) => (c[0] as i8 as f32 / 127.0).max(-1.0),
+        (ComponentType::U8, true) => c[0] as f32 / 255.0,
+        (ComponentType::I16, true) => (i16::from_le_bytes([c[0], c[1]]) as f32 / 32767.0).max(-1.0),
+        (ComponentType::U16, true) => u16::from_le_bytes([c[0], c[1]]) as f32 / 65535.0,
+        (ComponentType::I8, false) => c[0] as i8 as f32,
+        (ComponentType::U8, false) => c[0] as f32,
+        (ComponentType::I16, false) => i16::from_le_bytes([c[0], c[1]]) as f32,
+        (ComponentType::U16, false) => u16::from_le_bytes([c[0], c[1]]) as f32,
+        (ComponentType::U32, _) => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+    }
+}
+
+/// Encode one component as little-endian bytes, clamping to the type's range
+pub fn encode_component(component: ComponentType, normalized: bool, value: f32, out: &mut Vec<u8>) {
+    let scaled = |max: f32| {
+        if normalized {
+            (value * max).round()
+        } else {
+            value.round()
+        }
+    };
+    match component {
+        ComponentType::F32 => out.extend_from_slice(&value.to_le_bytes()),
+        ComponentType::I8 => out.push(scaled(127.0).clamp(-128.0, 127.0) as i8 as u8),
+        ComponentType::U8 => out.push(scaled(255.0).clamp(0.0, 255.0) as u8),
+        ComponentType::I16 => {
+            let v = scaled(32767.0).clamp(-32768.0, 32767.0) as i16;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        ComponentType::U16 => {
+            let v = scaled(65535.0).clamp(0.0, 65535.0) as u16;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        ComponentType::U32 => {
+            let v = value.round().max(0.0) as u32;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+/// Read every element of an accessor with `N` components as floats
+///
+/// Handles strided and interleaved views, and applies sparse substitutions over the view or,
+/// without one, over zeros. Returns `None` for a component count other than `N`, data
+/// running past the end of the blob, or a sparse accessor without a view counting more elements
+/// than the blob has bytes.
+pub fn read_accessor<const N: usize>(
+    blob: &[u8],
+    json: &Root,
+    index: Index<Accessor>,
+) -> Option<Vec<[f32; N]>> {
+    let acc = json.accessors.get(index.value())?;
+    let component = match (acc.component_type, acc.type_) {
+        (Checked::Valid(GenericComponentType(component)), Checked::Valid(ty))
+            if ty.multiplicity() == N =>
+        {
+            component
+        }
+        _ => return None,
+    };
+    let size = component.size();
+    let count = acc.count.0 as usize;
+    let element = |bytes: &[u8]| {
+        let mut element = [0.0; N];
+        for (c, value) in element.iter_mut().enumerate() {
+            *value = decode_component(component, acc.normalized, &bytes[c * size..]);
+        }
+        element
+    };
+
+    let mut elements = match acc.buffer_view {
+        Some(view) => {
+            let view = json.buffer_views.get(view.value())?;
+            let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0)
+                + acc.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+            let stride = accessor_stride(json, index)?;
+            let data = blob.get(offset..)?;
+            (0..count)
+                .map(|i| Some(element(data.get(i * stride..i * stride + size * N)?)))
+                .collect::<Option<Vec<_>>>()?
+        }
+        None if acc.sparse.is_some() => {
+            // Nothing in the blob bounds the count, so allow no more elements than it has
+            // bytes; the base attribute a sparse accessor stands in for is stored there
+            if count > blob.len() {
+                return None;
+            }
+            vec![[0.0; N]; count]
+        }
+        None => return None,
+    };
+
+    if let Some(sparse) = &acc.sparse {
+        let Checked::Valid(IndexComponentType(index_type)) = sparse.indices.component_type else {
+            return None;
+        };
+        let view_data = |view: Index<View>, byte_offset: u64| {
+            let view = json.buffer_views.get(view.value())?;
+            let start = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0) + byte_offset as usize;
+            blob.get(start..)
+        };
+        let indices = view_data(sparse.indices.buffer_view, sparse.indices.byte_offset.0)?;
+        let values = view_data(sparse.values.buffer_view, sparse.values.byte_offset.0)?;
+        let index_size = index_type.size();
+        for i in 0..sparse.count.0 as usize {
+            let target = match *indices.get(i * index_size..(i + 1) * index_size)? {
+                [b] => b as usize,
+                [a, b] => u16::from_le_bytes([a, b]) as usize,
+                [a, b, c, d] => u32::from_le_bytes([a, b, c, d]) as usize,
+                _ => return None,
+            };
+            let value = values.get(i * size * N..(i + 1) * size * N)?;
+            *elements.get_mut(target)? = element(value);
+        }
+    }
+
+    Some(elements)
+}
+
+/// Encode elements for a vertex buffer, padding each to a 4-byte stride
+///
+/// Returns the bytes and the stride, which differs from the element size only for padded
+/// 8/16-bit types (e.g. a normalized i16 VEC3 takes 8 bytes).
+pub fn write_elements<const N: usize>(
+    values: &[[f32; N]],
+    component: ComponentType,
+    normalized: bool,
+) -> (Vec<u8>, usize) {
+    let stride = (component.size() * N).next_multiple_of(4);
+    let mut bytes = Vec::with_capacity(values.len() * stride);
+    for element in values {
+        for &value in element {
+            encode_component(component, normalized, value, &mut bytes);
+        }
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+    }
+    (bytes, stride)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse_only(count: u64) -> (Vec<u8>, Root) {
+        // One u16 index and one VEC3 substitution
+        let mut blob = vec![1, 0, 0, 0];
+        blob.extend([1.0f32, 2.0, 3.0].iter().flat_map(|v| v.to_le_bytes()));
+        let json = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": blob.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 2 },
+                { "buffer": 0, "byteOffset": 4, "byteLength": 12 },
+            ],
+            "accessors": [{
+                "componentType": 5126, "count": count, "type": "VEC3",
+                "sparse": {
+                    "count": 1,
+                    "indices": { "bufferView": 0, "componentType": 5123 },
+                    "values": { "bufferView": 1 },
+                },
+            }],
+        }))
+        .unwrap();
+        (blob, json)
+    }
+
+    #[test]
+    fn sparse_accessors_without_view_fill_zeros() {
+        let (blob, json) = sparse_only(2);
+        assert_eq!(
+            read_accessor::<3>(&blob, &json, Index::new(0)),
+            Some(vec![[0.0; 3], [1.0, 2.0, 3.0]])
+        );
+    }
+
+    #[test]
+    fn sparse_counts_beyond_the_blob_are_refused() {
+        let (blob, json) = sparse_only(1_000_000_000);
+        assert_eq!(read_accessor::<3>(&blob, &json, Index::new(0)), None);
+    }
+
+    #[test]
+    fn components_round_trip() {
+        let mut bytes = Vec::new();
+        encode_component(ComponentType::I16, true, -0.5, &mut bytes);
+        assert_eq!(bytes.len(), 2);
+        let value = decode_component(ComponentType::I16, true, &bytes);
+        assert!((value + 0.5).abs() < 1e-4);
+        assert_eq!(f16_to_f32(f32_to_f16(0.25)), 0.25);
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::{HashMap, HashSet};
+
+use gltf::json::{
+    Index, Material, Root, Texture,
+    accessor::{ComponentType, GenericComponentType, Type},
+    buffer::Target,
+    image::MimeType,
+    mesh::Semantic,
+    texture::{Sampler, WrappingMode},
+    validation::Checked,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    blob::BlobWriter,
+    decode::load_source_image,
+    error::OptimizeError,
+    opt::{encode_png, get_image_data, get_texcoord_data},
+    texcoords::UV_EPSILON,
+};
+
+/// Texels of the largest image kept around the used UV range, for filtering and mips
+const CROP_MARGIN_TEXELS: f32 = 2.0;
+
+/// Crops keeping more than this fraction of an image's area are not worth a re-encode
+const MAX_CROPPED_AREA: f32 = 0.75;
+
+/// Overshoot past `[0, 1]` treated as an exporter quirk rather than intended tiling
+const UV_OVERSHOOT_TOLERANCE: f32 = 0.02;
+
+/// Extension carrying per-slot UV offset, rotation and scale
+const TEXTURE_TRANSFORM: &str = "KHR_texture_transform";
+
+/// Source document rewritten by a UV pass (new views appended, indices unchanged)
+pub(crate) type RewrittenDocument = (Root, Vec<u8>);
+
+/// Minimal union-find over images followed by accessors
+struct Groups(Vec<usize>);
+
+impl Groups {
+    fn find(&mut self, i: usize) -> usize {
+        let parent = self.0[i];
+        if parent == i {
+            return i;
+        }
+        let root = self.find(parent);
+        self.0[i] = root;
+        root
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0[a] = b;
+    }
+}
+
+/// Core texture slots of a material with the UV set each samples and whether it has extensions
+fn material_slots(mat: &Material) -> Vec<(Index<Texture>, u32, bool)> {
+    let textures = mat
+        .pbr_metallic_roughness
+        .base_color_texture
+        .iter()
+        .chain(&mat.pbr_metallic_roughness.metallic_roughness_texture)
+        .chain(&mat.emissive_texture)
+        .map(|i| i.index)
+        .chain(mat.normal_texture.iter().map(|n| n.index))
+        .chain(mat.occlusion_texture.iter().map(|o| o.index));
+    // Baked transforms leave an empty payload behind
+    textures
+        .zip(slot_extensions(mat))
+        .map(|(texture, (tex_coord, ext))| (texture, tex_coord, ext.is_some_and(|e| !e.is_empty())))
+        .collect()
+}
+
+/// Extension payloads of a material's core texture slots, with the UV set each samples
+fn slot_extensions(mat: &Material) -> Vec<(u32, Option<&Map<String, Value>>)> {
+    let pbr = &mat.pbr_metallic_roughness;
+    pbr.base_color_texture
+        .iter()
+        .chain(&pbr.metallic_roughness_texture)
+        .chain(&mat.emissive_texture)
+        .map(|i| (i.tex_coord, i.extensions.as_ref().map(|e| &e.others)))
+        .chain(
+            mat.normal_texture
+                .iter()
+                .map(|n| (n.tex_coord, n.extensions.as_ref().map(|e| &e.others))),
+        )
+        .chain(
+            mat.occlusion_texture
+                .iter()
+                .map(|o| (o.tex_coord, o.extensions.as_ref().map(|e| &e.others))),
+        )
+        .collect()
+}
+
+/// Mutable counterpart of [`slot_extensions`], in the same slot order
+fn slot_extensions_mut(mat: &mut Material) -> Vec<Option<&mut Map<String, Value>>> {
+    let pbr = &mut mat.pbr_metallic_roughness;
+    let infos = [
+        &mut pbr.base_color_texture,
+        &mut pbr.metallic_roughness_texture,
+        &mut mat.emissive_texture,
+    ];
+    let mut maps: Vec<_> = infos
+        .into_iter()
+        .flatten()
+        .map(|i| i.extensions.as_mut().map(|e| &mut e.others))
+        .collect();
+    if let Some(n) = &mut mat.normal_texture {
+        maps.push(n.extensions.as_mut().map(|e| &mut e.others));
+    }
+    if let Some(o) = &mut mat.occlusion_texture {
+        maps.push(o.extensions.as_mut().map(|e| &mut e.others));
+    }
+    maps
+}
+
+/// How one texture slot reads its UVs
+#[derive(Clone, Copy, PartialEq)]
+enum UvUse {
+    /// Untransformed coordinates
+    Plain,
+    /// `uv * scale + offset`
+    Transformed { offset: [f32; 2], scale: [f32; 2] },
+    /// A transform that cannot be baked (rotation, `texCoord` override or other extensions)
+    Fixed,
+}
+
+impl UvUse {
+    fn of(tex_coord: u32, extensions: Option<&Map<String, Value>>) -> UvUse {
+        let Some(extensions) = extensions.filter(|e| !e.is_empty()) else {
+            return UvUse::Plain;
+        };
+        let transform = match extensions.get(TEXTURE_TRANSFORM) {
+            Some(Value::Object(t)) if extensions.len() == 1 => t,
+            _ => return UvUse::Fixed,
+        };
+        let pair = |key: &str, default: f32| match transform.get(key) {
+            None => Some([default; 2]),
+            Some(Value::Array(v)) if v.len() == 2 => {
+                Some([v[0].as_f64()? as f32, v[1].as_f64()? as f32])
+            }
+            _ => None,
+        };
+        let rotated = transform
+            .get("rotation")
+            .is_some_and(|r| r.as_f64() != Some(0.0));
+        let redirected = transform
+            .get("texCoord")
+            .is_some_and(|t| t.as_u64() != Some(tex_coord as u64));
+        match (pair("offset", 0.0), pair("scale", 1.0)) {
+            (Some(offset), Some(scale)) if !rotated && !redirected => {
+                UvUse::Transformed { offset, scale }
+            }
+            _ => UvUse::Fixed,
+        }
+    }
+}
+
+/// Texture indices an extension payload points at (`{"index": n}` objects)
+pub(crate) fn extension_textures(value: &serde_json::Value, found: &mut Vec<usize>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(i) = map.get("index").and_then(|i| i.as_u64()) {
+                found.push(i as usize);
+            }
+            map.values().for_each(|v| extension_textures(v, found));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| extension_textures(v, found)),
+        _ => {}
+    }
+}
+
+/// Pixel rect of `[u0, u1]` on an image `size` texels wide, or `None` if not on whole texels
+fn texel_range(u0: f32, u1: f32, size: u32) -> Option<(u32, u32)> {
+    let (a, b) = (u0 * size as f32, u1 * size as f32);
+    let on_grid = |x: f32| (x - x.round()).abs() < 1e-3;
+    (on_grid(a) && on_grid(b)).then(|| (a.round() as u32, (b - a).round() as u32))
+}
+
+/// Widen a used range by the margin and snap it outward to a `grid`-cell lattice
+fn snapped_range(min: f32, max: f32, margin: f32, grid: u32) -> (f32, f32) {
+    let g = grid as f32;
+    let lo = ((min - margin).max(0.0) * g).floor() / g;
+    let hi = ((max + margin).min(1.0) * g).ceil() / g;
+    (lo, hi.max(lo + 1.0 / g))
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Images and the UV accessors sampling them, which must be rewritten together
+struct UvGroup {
+    images: Vec<usize>,
+    accessors: Vec<usize>,
+    /// Decoded UVs of each accessor
+    uvs: Vec<Vec<[f32; 2]>>,
+}
+
+impl UvGroup {
+    /// Smallest rect holding every UV in the group
+    fn bounds(&self) -> Option<([f32; 2], [f32; 2])> {
+        let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+        for uv in self.uvs.iter().flatten() {
+            for c in 0..2 {
+                min[c] = min[c].min(uv[c]);
+                max[c] = max[c].max(uv[c]);
+            }
+        }
+        (min[0] <= max[0]).then_some((min, max))
+    }
+}
+
+/// Group images with the UV accessors sampling them
+///
+/// Every texture read through the same coordinates lands in one group. Groups are dropped
+/// when a texture has a transform or other extension, a slot's UV set is missing, or an
+/// accessor is sparse or unreadable.
+fn uv_groups(o_blob: &[u8], o_json: &Root) -> Result<Vec<UvGroup>, OptimizeError> {
+    let n_images = o_json.images.len();
+    let mut groups = Groups((0..n_images + o_json.accessors.len()).collect());
+    let mut blocked: HashSet<usize> = HashSet::new();
+    let image_of = |texture: Index<Texture>| {
+        o_json
+            .textures
+            .get(texture.value())
+            .map(|t| t.source.value())
+            .filter(|&i| i < n_images)
+    };
+
+    for mat in &o_json.materials {
+        if let Some(ext) = &mat.extensions {
+            let mut found = Vec::new();
+            extension_textures(&serde_json::to_value(ext)?, &mut found);
+            blocked.extend(
+                found
+                    .into_iter()
+                    .filter_map(|t| image_of(Index::new(t as u32))),
+            );
+        }
+        for (texture, _, has_extension) in material_slots(mat) {
+            if has_extension && let Some(image) = image_of(texture) {
+                blocked.insert(image);
+            }
+        }
+    }
+
+    for mesh in &o_json.meshes {
+        for primitive in &mesh.primitives {
+            let Some(mat) = primitive
+                .material
+                .and_then(|m| o_json.materials.get(m.value()))
+            else {
+                continue;
+            };
+            for (texture, tex_coord, _) in material_slots(mat) {
+                let Some(image) = image_of(texture) else {
+                    continue;
+                };
+                let semantic = Checked::Valid(Semantic::TexCoords(tex_coord));
+                // Missing or dangling UVs leave nothing to crop by
+                match primitive.attributes.get(&semantic) {
+                    Some(accessor) if accessor.value() < o_json.accessors.len() => {
+                        groups.join(image, n_images + accessor.value())
+                    }
+                    _ => {
+                        blocked.insert(image);
+                    }
+                }
+            }
+        }
+    }
+
+    // Collect group members; blocked images poison their whole group
+    let mut members: Vec<(Vec<usize>, Vec<usize>)> = vec![Default::default(); groups.0.len()];
+    for i in 0..groups.0.len() {
+        let root = groups.find(i);
+        if i < n_images {
+            members[root].0.push(i);
+        } else {
+            members[root].1.push(i - n_images);
+        }
+    }
+
+    let mut result = Vec::new();
+    for (images, accessors) in members {
+        if images.is_empty() || accessors.is_empty() || images.iter().any(|i| blocked.contains(i)) {
+            continue;
+        }
+        let uvs: Option<Vec<_>> = accessors
+            .iter()
+            .map(|&acc| {
+                let accessor = &o_json.accessors[acc];
+                if accessor.sparse.is_some() || accessor.type_ != Checked::Valid(Type::Vec2) {
+                    return None;
+                }
+                get_texcoord_data(o_blob, o_json, Index::new(acc as u32))
+            })
+            .collect();
+        if let Some(uvs) = uvs {
+            result.push(UvGroup {
+                images,
+                accessors,
+                uvs,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Point a UV accessor at freshly written float data
+pub(crate) fn write_uvs<I: Iterator<Item = [f32; 2]>>(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    accessor: usize,
+    uvs: I,
+) {
+    let bytes: Vec<u8> = uvs.flatten().flat_map(f32::to_le_bytes).collect();
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ArrayBuffer));
+    let accessor = &mut n_json.accessors[accessor];
+    accessor.buffer_view = Some(view);
+    accessor.byte_offset = None;
+    accessor.component_type = Checked::Valid(GenericComponentType(ComponentType::F32));
+    accessor.normalized = false;
+    accessor.min = None;
+    accessor.max = None;
+}
+
+/// Bake scale/offset `KHR_texture_transform`s into the UV accessors and drop the extension
+///
+/// A UV accessor is rewritten only when every slot sampling it applies the same transform
+/// without rotation, and a slot loses its transform only when all accessors it reads were
+/// rewritten. Accessors also read by textures from material extensions are left alone.
+/// Returns `None` when nothing was baked.
+pub(crate) fn bake_texture_transforms(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    // Every (material, slot) reading each UV accessor
+    let mut readers: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    let mut blocked: HashSet<usize> = HashSet::new();
+    for primitive in o_json.meshes.iter().flat_map(|m| &m.primitives) {
+        let Some(m) = primitive.material.map(|m| m.value()) else {
+            continue;
+        };
+        let Some(mat) = o_json.materials.get(m) else {
+            continue;
+        };
+        for (slot, (tex_coord, _)) in slot_extensions(mat).into_iter().enumerate() {
+            let semantic = Checked::Valid(Semantic::TexCoords(tex_coord));
+            if let Some(accessor) = primitive.attributes.get(&semantic) {
+                readers.entry(accessor.value()).or_default().push((m, slot));
+            }
+        }
+        if mat.extensions.is_some() {
+            blocked.extend(
+                primitive
+                    .attributes
+                    .iter()
+                    .filter_map(|(semantic, accessor)| {
+                        matches!(semantic, Checked::Valid(Semantic::TexCoords(_)))
+                            .then_some(accessor.value())
+                    }),
+            );
+        }
+    }
+
+    let uses: Vec<Vec<UvUse>> = o_json
+        .materials
+        .iter()
+        .map(|mat| {
+            slot_extensions(mat)
+                .into_iter()
+                .map(|(tex_coord, ext)| UvUse::of(tex_coord, ext))
+                .collect()
+        })
+        .collect();
+
+    // Accessors whose readers agree on one transform
+    let mut baked: HashMap<usize, UvUse> = readers
+        .iter()
+        .filter(|(acc, _)| !blocked.contains(acc))
+        .filter_map(|(&acc, slots)| {
+            let first = uses[slots[0].0][slots[0].1];
+            let agreed = matches!(first, UvUse::Transformed { .. })
+                && slots.iter().all(|&(m, slot)| uses[m][slot] == first);
+            agreed.then_some((acc, first))
+        })
+        .collect();
+
+    // A slot whose transform stays on one accessor must keep it on all of them
+    loop {
+        let kept: HashSet<(usize, usize)> = readers
+            .iter()
+            .filter(|(acc, _)| !baked.contains_key(acc))
+            .flat_map(|(_, slots)| slots.iter().copied())
+            .collect();
+        let before = baked.len();
+        baked.retain(|acc, _| readers[acc].iter().all(|slot| !kept.contains(slot)));
+        if baked.len() == before {
+            break;
+        }
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut stripped: HashSet<(usize, usize)> = HashSet::new();
+    for (&acc, &transform) in &baked {
+        let UvUse::Transformed { offset, scale } = transform else {
+            continue;
+        };
+        let Some(accessor) = o_json.accessors.get(acc) else {
+            continue;
+        };
+        if accessor.sparse.is_some() || accessor.type_ != Checked::Valid(Type::Vec2) {
+            continue;
+        }
+        let Some(uvs) = get_texcoord_data(o_blob, o_json, Index::new(acc as u32)) else {
+            continue;
+        };
+        let transformed = uvs
+            .into_iter()
+            .map(|[u, v]| [u * scale[0] + offset[0], v * scale[1] + offset[1]]);
+        write_uvs(&mut n_blob, &mut n_json, acc, transformed);
+        stripped.extend(readers[&acc].iter().copied());
+    }
+    if stripped.is_empty() {
+        return Ok(None);
+    }
+
+    for (m, mat) in n_json.materials.iter_mut().enumerate() {
+        for (slot, extensions) in slot_extensions_mut(mat).into_iter().enumerate() {
+            if let Some(extensions) = extensions
+                && stripped.contains(&(m, slot))
+            {
+                extensions.remove(TEXTURE_TRANSFORM);
+            }
+        }
+    }
+    if !serde_json::to_string(&n_json.materials)?.contains(TEXTURE_TRANSFORM) {
+        n_json.extensions_used.retain(|e| e != TEXTURE_TRANSFORM);
+        n_json
+            .extensions_required
+            .retain(|e| e != TEXTURE_TRANSFORM);
+    }
+    Ok(Some((n_json, n_blob.into_vec())))
+}
+
+/// Clamp UVs that overshoot `[0, 1]` slightly and switch their textures to clamp-to-edge
+///
+/// Exporters often leave UVs a hair outside the unit square on textures that were never
+/// meant to tile; with the default `REPEAT` wrap those texels pick up the opposite edge,
+/// which gets worse after cropping or atlasing. Groups overshooting by more than 2% are taken
+/// as deliberate tiling and left alone. Affected textures get a clamping copy of their
+/// sampler. Returns `None` when nothing changed.
+pub(crate) fn normalize_uv_range(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut clamped_samplers: HashMap<Option<usize>, Index<Sampler>> = HashMap::new();
+    let mut changed = false;
+
+    for group in uv_groups(o_blob, o_json)? {
+        let Some((min, max)) = group.bounds() else {
+            continue;
+        };
+        let overshoot = min
+            .iter()
+            .map(|&m| -m)
+            .chain(max.iter().map(|&m| m - 1.0))
+            .fold(0.0f32, f32::max);
+        if overshoot <= UV_EPSILON || overshoot > UV_OVERSHOOT_TOLERANCE {
+            continue;
+        }
+
+        for (&acc, uvs) in group.accessors.iter().zip(&group.uvs) {
+            let clamped = uvs.iter().map(|uv| uv.map(|c| c.clamp(0.0, 1.0)));
+            write_uvs(&mut n_blob, &mut n_json, acc, clamped);
+        }
+
+        for texture in 0..n_json.textures.len() {
+            if !group
+                .images
+                .contains(&n_json.textures[texture].source.value())
+            {
+                continue;
+            }
+            let original = n_json.textures[texture].sampler.map(|s| s.value());
+            let sampler = match clamped_samplers.get(&original) {
+                Some(&sampler) => sampler,
+                None => {
+                    let mut sampler = original
+                        .and_then(|s| n_json.samplers.get(s))
+                        .cloned()
+                        .unwrap_or_default();
+                    sampler.wrap_s = Checked::Valid(WrappingMode::ClampToEdge);
+                    sampler.wrap_t = Checked::Valid(WrappingMode::ClampToEdge);
+                    let sampler = n_json.push(sampler);
+                    clamped_samplers.insert(original, sampler);
+                    sampler
+                }
+            };
+            n_json.textures[texture].sampler = Some(sampler);
+        }
+        changed = true;
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+/// Crop textures to the UV range their primitives sample, rewriting those UVs to match
+///
+/// Textures read through the same coordinates get the same crop (see [`uv_groups`]). A group
+/// is left alone when its UVs tile (leave `[0, 1]`) or the crop would keep most of the image.
+/// Cropped images become PNG and rewritten UVs float; both get fresh buffer views so indices
+/// stay put. Returns `None` when nothing was cropped.
+pub(crate) fn crop_to_uv_range(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut cropped_any = false;
+
+    for group in uv_groups(o_blob, o_json)? {
+        let Some((min, max)) = group.bounds() else {
+            continue;
+        };
+        if min.iter().any(|&m| m < -UV_EPSILON) || max.iter().any(|&m| m > 1.0 + UV_EPSILON) {
+            continue;
+        }
+
+        // Decode and find a texel lattice shared by every image in the group
+        let mut decoded = Vec::with_capacity(group.images.len());
+        for &image in &group.images {
+            let texture = o_json
+                .textures
+                .iter()
+                .position(|t| t.source.value() == image)
+                .map(|t| Index::new(t as u32));
+            let Some(data) = texture.and_then(|t| get_image_data(o_blob, o_json, t)) else {
+                break;
+            };
+            decoded.push(load_source_image(o_json, image, data, Vec::new())?);
+        }
+        if decoded.len() != group.images.len() {
+            continue;
+        }
+        let grid_u = decoded.iter().fold(0, |g, img| gcd(g, img.width()));
+        let grid_v = decoded.iter().fold(0, |g, img| gcd(g, img.height()));
+        let largest = decoded
+            .iter()
+            .map(|img| img.width().max(img.height()))
+            .max()
+            .unwrap_or(1);
+        let margin = CROP_MARGIN_TEXELS / largest as f32;
+        let (u0, u1) = snapped_range(min[0], max[0], margin, grid_u.max(1));
+        let (v0, v1) = snapped_range(min[1], max[1], margin, grid_v.max(1));
+        if (u1 - u0) * (v1 - v0) > MAX_CROPPED_AREA {
+            continue;
+        }
+
+        let rects: Option<Vec<_>> = decoded
+            .iter()
+            .map(|img| {
+                Some((
+                    texel_range(u0, u1, img.width())?,
+                    texel_range(v0, v1, img.height())?,
+                ))
+            })
+            .collect();
+        let Some(rects) = rects else {
+            continue;
+        };
+
+        for ((&image, img), ((x, w), (y, h))) in group.images.iter().zip(&decoded).zip(rects) {
+            let mut png = Vec::new();
+            let cropped = img.crop_imm(x, y, w, h);
+            encode_png(cropped.as_bytes(), w, h, cropped.color(), &mut png)?;
+
+            let view = n_blob.push_view(&mut n_json, &png, None);
+            let n_image = &mut n_json.images[image];
+            n_image.buffer_view = Some(view);
+            n_image.mime_type = Some(MimeType("image/png".to_string()));
+        }
+
+        for (&acc, uvs) in group.accessors.iter().zip(&group.uvs) {
+            let remapped = uvs
+                .iter()
+                .map(|[u, v]| [(u - u0) / (u1 - u0), (v - v0) / (v1 - v0)]);
+            write_uvs(&mut n_blob, &mut n_json, acc, remapped);
+        }
+        cropped_any = true;
+    }
+
+    Ok(cropped_any.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{glb::read_glb, testing::SyntheticGlb};
+
+    /// One textured quad whose TEXCOORD_0 is rewritten to `uvs`
+    fn quad_with_uvs(uvs: [[f32; 2]; 4]) -> (Root, Vec<u8>) {
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let accessor = uv_accessor(&json);
+        write_uvs(&mut blob, &mut json, accessor, uvs.into_iter());
+        (json, blob.into_vec())
+    }
+
+    fn uv_accessor(json: &Root) -> usize {
+        json.meshes[0].primitives[0].attributes[&Checked::Valid(Semantic::TexCoords(0))].value()
+    }
+
+    fn uvs(json: &Root, blob: &[u8]) -> Vec<[f32; 2]> {
+        get_texcoord_data(blob, json, Index::new(uv_accessor(json) as u32)).unwrap()
+    }
+
+    fn image_size(json: &Root, blob: &[u8]) -> (u32, u32) {
+        let data = get_image_data(blob, json, Index::new(0)).unwrap();
+        let image = image::load_from_memory(data).unwrap();
+        (image.width(), image.height())
+    }
+
+    #[test]
+    fn textures_are_cropped_to_their_uv_range() {
+        let (json, blob) = quad_with_uvs([[0.25, 0.25], [0.5, 0.25], [0.5, 0.5], [0.25, 0.5]]);
+        let (n_json, n_blob) = crop_to_uv_range(&blob, &json).unwrap().unwrap();
+        // 16 texels plus a 2 texel margin on each side
+        assert_eq!(image_size(&n_json, &n_blob), (20, 20));
+        for (uv, expected) in
+            uvs(&n_json, &n_blob)
+                .iter()
+                .zip([[0.1, 0.1], [0.9, 0.1], [0.9, 0.9], [0.1, 0.9]])
+        {
+            for c in 0..2 {
+                assert!((uv[c] - expected[c]).abs() < 1e-5, "{uv:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn tiling_and_full_range_uvs_are_not_cropped() {
+        let (json, blob) = quad_with_uvs([[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]]);
+        assert!(crop_to_uv_range(&blob, &json).unwrap().is_none());
+        let (json, blob) = quad_with_uvs([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert!(crop_to_uv_range(&blob, &json).unwrap().is_none());
+    }
+
+    #[test]
+    fn slight_overshoot_is_clamped_with_a_clamping_sampler() {
+        let (json, blob) = quad_with_uvs([[-0.01, 0.0], [1.01, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        let (n_json, n_blob) = normalize_uv_range(&blob, &json).unwrap().unwrap();
+        assert!(
+            uvs(&n_json, &n_blob)
+                .iter()
+                .flatten()
+                .all(|c| (0.0..=1.0).contains(c))
+        );
+        let sampler = &n_json.samplers[n_json.textures[0].sampler.unwrap().value()];
+        assert_eq!(sampler.wrap_s, Checked::Valid(WrappingMode::ClampToEdge));
+
+        // Deliberate tiling stays as it is
+        let (json, blob) = quad_with_uvs([[-0.5, 0.0], [1.5, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        assert!(normalize_uv_range(&blob, &json).unwrap().is_none());
+    }
+
+    #[test]
+    fn dangling_uv_accessors_block_their_images() {
+        let (mut json, blob) = quad_with_uvs([[0.25, 0.25], [0.5, 0.25], [0.5, 0.5], [0.25, 0.5]]);
+        json.meshes[0].primitives[0]
+            .attributes
+            .insert(Checked::Valid(Semantic::TexCoords(0)), Index::new(99));
+        assert!(uv_groups(&blob, &json).unwrap().is_empty());
+        assert!(crop_to_uv_range(&blob, &json).unwrap().is_none());
+        assert!(normalize_uv_range(&blob, &json).unwrap().is_none());
+    }
+
+    #[test]
+    fn scale_and_offset_transforms_are_baked() {
+        let (mut json, blob) = quad_with_uvs([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        let transform = serde_json::json!({ "offset": [0.5, 0.0], "scale": [0.5, 2.0] });
+        let slot = json.materials[0]
+            .pbr_metallic_roughness
+            .base_color_texture
+            .as_mut()
+            .unwrap();
+        slot.extensions = Some(Default::default());
+        slot.extensions
+            .as_mut()
+            .unwrap()
+            .others
+            .insert(TEXTURE_TRANSFORM.to_string(), transform);
+        json.extensions_used.push(TEXTURE_TRANSFORM.to_string());
+
+        let (n_json, n_blob) = bake_texture_transforms(&blob, &json).unwrap().unwrap();
+        assert_eq!(
+            uvs(&n_json, &n_blob),
+            [[0.5, 0.0], [1.0, 0.0], [1.0, 2.0], [0.5, 2.0]]
+        );
+        assert!(n_json.extensions_used.is_empty());
+    }
+}
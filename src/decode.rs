@@ -0,0 +1,394 @@
+use std::io::Cursor;
+
+use gltf::json::{Index, Root, Texture};
+use image::{
+    ColorType, DynamicImage, GrayAlphaImage, GrayImage, ImageDecoder, ImageReader, RgbImage,
+    RgbaImage, metadata::Orientation,
+};
+#[cfg(feature = "ktx2")]
+use ktx2_rw::{Ktx2Texture, VkFormat};
+
+#[cfg(feature = "ktx2")]
+use crate::convert::f16_to_f32;
+use crate::{
+    crop::extension_textures,
+    error::OptimizeError,
+    features::{FeatureNotEnabled, missing_decoder},
+};
+
+/// File identifier at the start of every KTX2 file
+pub(crate) const KTX2_MAGIC: [u8; 8] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB];
+
+/// Decode an embedded image, including uncompressed KTX2
+///
+/// Formats whose cargo feature is disabled fail with [`FeatureNotEnabled`]. An EXIF orientation
+/// is applied to the pixels, so the image comes out upright and the tag can be dropped. 8-bit
+/// pixels are written into `buffer`, reusing its allocation; it may be empty.
+pub(crate) fn load_image(data: &[u8], buffer: Vec<u8>) -> Result<DynamicImage, OptimizeError> {
+    if let Some(missing) = missing_decoder(sniff_format(data)) {
+        return Err(missing.into());
+    }
+    if data.starts_with(&KTX2_MAGIC) {
+        return load_ktx2(data);
+    }
+
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = decode_pixels(decoder, buffer)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Whether an encoded image carries an EXIF orientation other than upright
+pub(crate) fn is_reoriented(data: &[u8]) -> bool {
+    let orientation = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok());
+    orientation.is_some_and(|o| o != Orientation::NoTransforms)
+}
+
+/// Pixels of a decoder as stored, 8-bit ones written into `buffer`
+fn decode_pixels(
+    decoder: impl ImageDecoder,
+    mut buffer: Vec<u8>,
+) -> Result<DynamicImage, OptimizeError> {
+    let (width, height) = decoder.dimensions();
+    let color = decoder.color_type();
+    if !matches!(
+        color,
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    ) {
+        return Ok(DynamicImage::from_decoder(decoder)?);
+    }
+
+    buffer.clear();
+    buffer.resize(decoder.total_bytes() as usize, 0);
+    decoder.read_image(&mut buffer)?;
+    let image = match color {
+        ColorType::L8 => GrayImage::from_raw(width, height, buffer).map(Into::into),
+        ColorType::La8 => GrayAlphaImage::from_raw(width, height, buffer).map(Into::into),
+        ColorType::Rgb8 => RgbImage::from_raw(width, height, buffer).map(Into::into),
+        _ => RgbaImage::from_raw(width, height, buffer).map(Into::into),
+    };
+    image.ok_or(OptimizeError::ImageSize { width, height })
+}
+
+/// Decode uncompressed KTX2
+///
+/// KTX2 files are read through `ktx2_rw`, which also inflates Zstd supercompression; only
+/// the base level of the first layer and face is used. Basis Universal and block-compressed
+/// KTX2 payloads are rejected. Float formats are clamped to `[0, 1]` and stored as 8-bit.
+#[cfg(feature = "ktx2")]
+fn load_ktx2(data: &[u8]) -> Result<DynamicImage, OptimizeError> {
+    let texture = Ktx2Texture::from_memory(data).map_err(OptimizeError::ktx2)?;
+    if texture.needs_transcoding() {
+        return Err(OptimizeError::Unsupported {
+            what: "Basis Universal KTX2 input".to_string(),
+        });
+    }
+    let (width, height) = (texture.width(), texture.height());
+    let pixels = texture
+        .get_image_data(0, 0, 0)
+        .map_err(OptimizeError::ktx2)?;
+
+    let image = match texture.vk_format() {
+        VkFormat::R8Unorm => GrayImage::from_raw(width, height, pixels.to_vec()).map(Into::into),
+        VkFormat::R8G8Unorm => {
+            RgbImage::from_raw(width, height, widen(pixels, 2, |p| [p[0], p[1], 0])).map(Into::into)
+        }
+        VkFormat::R8G8B8Unorm => RgbImage::from_raw(width, height, pixels.to_vec()).map(Into::into),
+        VkFormat::B8G8R8Unorm => {
+            RgbImage::from_raw(width, height, widen(pixels, 3, |p| [p[2], p[1], p[0]]))
+                .map(Into::into)
+        }
+        VkFormat::R8G8B8A8Unorm | VkFormat::R8G8B8A8Srgb => {
+            RgbaImage::from_raw(width, height, pixels.to_vec()).map(Into::into)
+        }
+        VkFormat::B8G8R8A8Unorm | VkFormat::B8G8R8A8Srgb => RgbaImage::from_raw(
+            width,
+            height,
+            widen(pixels, 4, |p| [p[2], p[1], p[0], p[3]]),
+        )
+        .map(Into::into),
+        VkFormat::R16Sfloat | VkFormat::R16G16Sfloat | VkFormat::R16G16B16A16Sfloat => {
+            let channels = match texture.vk_format() {
+                VkFormat::R16Sfloat => 1,
+                VkFormat::R16G16Sfloat => 2,
+                _ => 4,
+            };
+            let values: Vec<f32> = pixels
+                .chunks_exact(2)
+                .map(|c| f16_to_f32(u16::from_le_bytes([c[0], c[1]])))
+                .collect();
+            float_image(width, height, &values, channels)
+        }
+        VkFormat::R32Sfloat | VkFormat::R32G32Sfloat | VkFormat::R32G32B32A32Sfloat => {
+            let channels = match texture.vk_format() {
+                VkFormat::R32Sfloat => 1,
+                VkFormat::R32G32Sfloat => 2,
+                _ => 4,
+            };
+            let values: Vec<f32> = pixels
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect();
+            float_image(width, height, &values, channels)
+        }
+        format => {
+            return Err(OptimizeError::Unsupported {
+                what: format!("KTX2 format {format:?}"),
+            });
+        }
+    };
+    image.ok_or(OptimizeError::ImageSize { width, height })
+}
+
+#[cfg(not(feature = "ktx2"))]
+fn load_ktx2(_data: &[u8]) -> Result<DynamicImage, OptimizeError> {
+    Err(FeatureNotEnabled { feature: "ktx2" }.into())
+}
+
+/// Image or file format recognised from the first bytes of `data`
+pub(crate) fn sniff_format(data: &[u8]) -> &'static str {
+    let starts = |magic: &[u8]| data.starts_with(magic);
+    let brand = data.get(4..12);
+    match data {
+        [] => "empty",
+        _ if starts(b"\x89PNG\r\n\x1a\n") => "PNG",
+        _ if starts(&[0xFF, 0xD8, 0xFF]) => "JPEG",
+        _ if starts(b"RIFF") && data.get(8..12) == Some(b"WEBP") => "WebP",
+        _ if starts(&KTX2_MAGIC) => "KTX2",
+        _ if starts(&[0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB]) => "KTX1",
+        _ if starts(b"sB") => "Basis Universal",
+        _ if starts(b"DDS ") => "DDS",
+        _ if starts(b"GIF8") => "GIF",
+        _ if starts(b"BM") => "BMP",
+        _ if starts(b"II*\0") || starts(b"MM\0*") => "TIFF",
+        _ if starts(b"#?RADIANCE") || starts(b"#?RGBE") => "Radiance HDR",
+        _ if starts(&[0x76, 0x2F, 0x31, 0x01]) => "OpenEXR",
+        _ if brand == Some(b"ftypavif") => "AVIF",
+        _ if brand == Some(b"ftypheic") || brand == Some(b"ftypmif1") => "HEIF",
+        _ if starts(b"version https://git-lfs") => "Git LFS pointer",
+        _ if starts(b"<") => "HTML/XML text",
+        _ if data.iter().all(|&b| b == 0) => "all zero bytes",
+        _ => "unknown",
+    }
+}
+
+/// MIME type of an image whose bytes are a format glTF viewers load
+pub(crate) fn sniffed_mime_type(data: &[u8]) -> Option<&'static str> {
+    match sniff_format(data) {
+        "PNG" => Some("image/png"),
+        "JPEG" => Some("image/jpeg"),
+        "WebP" => Some("image/webp"),
+        "KTX2" => Some("image/ktx2"),
+        _ => None,
+    }
+}
+
+/// Core and extension texture slots that use an image, e.g. `material 3 'Wood' normalTexture`
+fn image_users(o_json: &Root, image: usize) -> Vec<String> {
+    let textures: Vec<usize> = (o_json.textures.iter().enumerate())
+        .filter(|(_, t)| t.source.value() == image)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut users = Vec::new();
+    for (m, mat) in o_json.materials.iter().enumerate() {
+        let pbr = &mat.pbr_metallic_roughness;
+        let slots = [
+            (
+                "baseColorTexture",
+                pbr.base_color_texture.as_ref().map(|i| i.index),
+            ),
+            (
+                "metallicRoughnessTexture",
+                pbr.metallic_roughness_texture.as_ref().map(|i| i.index),
+            ),
+            (
+                "normalTexture",
+                mat.normal_texture.as_ref().map(|i| i.index),
+            ),
+            (
+                "occlusionTexture",
+                mat.occlusion_texture.as_ref().map(|i| i.index),
+            ),
+            (
+                "emissiveTexture",
+                mat.emissive_texture.as_ref().map(|i| i.index),
+            ),
+        ];
+        let mut names: Vec<String> = slots
+            .into_iter()
+            .filter(|(_, t)| t.is_some_and(|t| textures.contains(&t.value())))
+            .map(|(slot, _)| slot.to_string())
+            .collect();
+        for (name, payload) in mat.extensions.iter().flat_map(|e| &e.others) {
+            let mut found = Vec::new();
+            extension_textures(payload, &mut found);
+            if found.iter().any(|t| textures.contains(t)) {
+                names.push(name.clone());
+            }
+        }
+        if !names.is_empty() {
+            let label = mat
+                .name
+                .as_ref()
+                .map_or(String::new(), |n| format!(" '{n}'"));
+            users.push(format!("material {m}{label} {}", names.join(", ")));
+        }
+    }
+    users
+}
+
+/// Decode source image `image`, describing the image in detail when that fails
+///
+/// The error names the image, its declared MIME type, the format its leading bytes look like
+/// and every material slot using it, so a bad file can be found in a large scene.
+///
+/// 8-bit pixels go into `buffer`, which may be empty.
+pub(crate) fn load_source_image(
+    o_json: &Root,
+    image: usize,
+    data: &[u8],
+    buffer: Vec<u8>,
+) -> Result<DynamicImage, OptimizeError> {
+    load_image(data, buffer).map_err(|e| {
+        if let OptimizeError::FeatureNotEnabled(_) = e {
+            return e;
+        }
+        let source = o_json.images.get(image);
+        let mut what = format!("image {image}");
+        if let Some(name) = source.and_then(|i| i.name.as_ref().or(i.uri.as_ref())) {
+            what += &format!(" '{name}'");
+        }
+        let declared = source
+            .and_then(|i| i.mime_type.as_ref())
+            .map_or("none", |m| m.0.as_str());
+        let detected = sniff_format(data);
+        let head: Vec<String> = data.iter().take(8).map(|b| format!("{b:02x}")).collect();
+        let mut details = format!(
+            "{what} ({} bytes, declared MIME {declared}, detected {detected}, starts with [{}])",
+            data.len(),
+            head.join(" ")
+        );
+        let users = image_users(o_json, image);
+        if !users.is_empty() {
+            details += &format!(", used by {}", users.join("; "));
+        }
+        if detected == "Git LFS pointer" {
+            details += "; the file was never fetched, run `git lfs pull`";
+        }
+        OptimizeError::Decode {
+            image,
+            details,
+            source: Box::new(e),
+        }
+    })
+}
+
+/// [`load_source_image`] for the image behind a texture
+pub(crate) fn load_texture_image(
+    o_json: &Root,
+    texture: Index<Texture>,
+    data: &[u8],
+    buffer: Vec<u8>,
+) -> Result<DynamicImage, OptimizeError> {
+    let image = o_json
+        .textures
+        .get(texture.value())
+        .map_or(usize::MAX, |t| t.source.value());
+    load_source_image(o_json, image, data, buffer)
+}
+
+/// Rearrange packed pixels of `channels` bytes each
+#[cfg(feature = "ktx2")]
+fn widen<const N: usize>(pixels: &[u8], channels: usize, f: impl Fn(&[u8]) -> [u8; N]) -> Vec<u8> {
+    pixels.chunks_exact(channels).flat_map(f).collect()
+}
+
+/// 8-bit image from 1, 2 or 4 float channels per pixel
+#[cfg(feature = "ktx2")]
+fn float_image(width: u32, height: u32, values: &[f32], channels: usize) -> Option<DynamicImage> {
+    let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let pixels: Vec<u8> = values
+        .chunks_exact(channels)
+        .flat_map(|p| match channels {
+            1 => vec![byte(p[0])],
+            2 => vec![byte(p[0]), byte(p[1]), 0],
+            _ => p.iter().map(|&v| byte(v)).collect(),
+        })
+        .collect();
+    match channels {
+        1 => GrayImage::from_raw(width, height, pixels).map(Into::into),
+        2 => RgbImage::from_raw(width, height, pixels).map(Into::into),
+        _ => RgbaImage::from_raw(width, height, pixels).map(Into::into),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "ktx2"))]
+    #[test]
+    fn ktx2_input_needs_the_feature() {
+        let mut data = KTX2_MAGIC.to_vec();
+        data.extend([0; 64]);
+        let err = load_image(&data, Vec::new()).unwrap_err();
+        assert_eq!(err.feature_not_enabled().map(|e| e.feature), Some("ktx2"));
+    }
+
+    #[cfg(feature = "ktx2")]
+    #[test]
+    fn ktx2_pixels_are_widened_to_rgb_and_rgba() {
+        let bgr = widen(&[1, 2, 3, 4, 5, 6], 3, |p| [p[2], p[1], p[0]]);
+        assert_eq!(bgr, [3, 2, 1, 6, 5, 4]);
+
+        let rg = float_image(2, 1, &[0.0, 1.0, 2.0, -1.0], 2).unwrap();
+        assert_eq!(rg.as_bytes(), [0, 255, 0, 255, 0, 0]);
+        let gray = float_image(1, 1, &[0.5], 1).unwrap();
+        assert_eq!(gray.as_bytes(), [128]);
+        assert!(float_image(2, 2, &[0.0; 4], 4).is_none());
+    }
+
+    #[test]
+    fn ktx2_is_sniffed_as_an_image_format() {
+        assert_eq!(sniff_format(&KTX2_MAGIC), "KTX2");
+        assert_eq!(sniffed_mime_type(&KTX2_MAGIC), Some("image/ktx2"));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn decode_errors_describe_the_image_and_its_users() {
+        use crate::{glb::read_glb, testing::SyntheticGlb};
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, _) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:00\n";
+        let err = load_texture_image(&json, Index::new(0), pointer, Vec::new()).unwrap_err();
+        let OptimizeError::Decode { image, details, .. } = err else {
+            panic!("expected a decode error, got {err}");
+        };
+        assert_eq!(image, 0);
+        for part in [
+            "image 0 (",
+            "declared MIME image/png",
+            "detected Git LFS pointer",
+            "starts with [76 65 72 73 69 6f 6e 20]",
+            "used by material 0 'material_0' baseColorTexture",
+            "git lfs pull",
+        ] {
+            assert!(details.contains(part), "{part:?} missing from {details:?}");
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use gltf::json::{Accessor, Index, Mesh, Root};
+
+use crate::{
+    blob::content_hash,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{accessor_stride, element_size},
+};
+
+/// Append `bytes` to `key` behind its length, so adjacent fields can't run into each other
+fn push_field(key: &mut Vec<u8>, bytes: &[u8]) {
+    key.extend((bytes.len() as u64).to_le_bytes());
+    key.extend(bytes);
+}
+
+/// Layout and element bytes of an accessor, `None` when its data can't be read
+///
+/// Sparse accessors are keyed by index, so they only match themselves.
+fn accessor_key(o_blob: &[u8], o_json: &Root, index: Index<Accessor>) -> Option<Vec<u8>> {
+    let acc = o_json.accessors.get(index.value())?;
+    let mut key = Vec::new();
+    push_field(
+        &mut key,
+        format!(
+            "{:?} {:?} {} {}",
+            acc.component_type, acc.type_, acc.normalized, acc.count.0
+        )
+        .as_bytes(),
+    );
+    if acc.sparse.is_some() {
+        push_field(&mut key, format!("sparse {}", index.value()).as_bytes());
+        return Some(key);
+    }
+    let Some(view) = acc.buffer_view else {
+        // Zeros throughout
+        return Some(key);
+    };
+    let view = o_json.buffer_views.get(view.value())?;
+    let size = element_size(acc)?;
+    let stride = accessor_stride(o_json, index)?;
+    let start =
+        view.byte_offset.map_or(0, |o| o.0 as usize) + acc.byte_offset.map_or(0, |o| o.0 as usize);
+    for i in 0..acc.count.0 as usize {
+        let at = start + i * stride;
+        key.extend(o_blob.get(at..at + size)?);
+    }
+    Some(key)
+}
+
+/// Everything a mesh draws: per primitive its mode, material, extensions, extras and the
+/// contents of its accessors, plus the default morph weights. Names are left out.
+fn mesh_key(o_blob: &[u8], o_json: &Root, mesh: &Mesh) -> Option<Vec<u8>> {
+    let mut key = Vec::new();
+    push_field(&mut key, format!("{:?}", mesh.weights).as_bytes());
+    for primitive in &mesh.primitives {
+        let header = format!(
+            "{:?} {:?} {} {}",
+            primitive.mode,
+            primitive.material.map(|m| m.value()),
+            serde_json::to_string(&primitive.extensions).ok()?,
+            primitive.extras.as_ref().map_or("", |e| e.get()),
+        );
+        push_field(&mut key, header.as_bytes());
+        for (semantic, accessor) in &primitive.attributes {
+            push_field(&mut key, format!("{semantic:?}").as_bytes());
+            push_field(&mut key, &accessor_key(o_blob, o_json, *accessor)?);
+        }
+        if let Some(indices) = primitive.indices {
+            push_field(&mut key, b"indices");
+            push_field(&mut key, &accessor_key(o_blob, o_json, indices)?);
+        }
+        for target in primitive.targets.iter().flatten() {
+            let attributes = [
+                ("POSITION", target.positions),
+                ("NORMAL", target.normals),
+                ("TANGENT", target.tangents),
+            ];
+            push_field(&mut key, b"target");
+            for (name, accessor) in attributes {
+                if let Some(accessor) = accessor {
+                    push_field(&mut key, name.as_bytes());
+                    push_field(&mut key, &accessor_key(o_blob, o_json, accessor)?);
+                }
+            }
+        }
+    }
+    Some(key)
+}
+
+/// Point nodes drawing a copy of an earlier mesh at that mesh instead
+///
+/// Meshes match when their primitives draw the same accessor contents with the same modes,
+/// materials, extensions and extras, whichever accessors hold them; names don't matter. The
+/// copies are left unreferenced for `prune_unused` to drop. Returns `None` when no mesh has
+/// an earlier copy.
+pub(crate) fn dedupe_meshes(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    // Hash first and compare full keys only on a hash match, so keys are never all held
+    let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut canonical: Vec<usize> = (0..o_json.meshes.len()).collect();
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        let Some(key) = mesh_key(o_blob, o_json, mesh) else {
+            continue;
+        };
+        let earlier = by_hash.entry(content_hash(&key)).or_default();
+        let same = earlier
+            .iter()
+            .copied()
+            .find(|&e| mesh_key(o_blob, o_json, &o_json.meshes[e]).as_ref() == Some(&key));
+        match same {
+            Some(e) => canonical[m] = e,
+            None => earlier.push(m),
+        }
+    }
+    if canonical.iter().enumerate().all(|(m, &c)| m == c) {
+        return Ok(None);
+    }
+
+    let mut n_json = o_json.clone();
+    for node in &mut n_json.nodes {
+        if let Some(mesh) = &mut node.mesh
+            && let Some(&c) = canonical.get(mesh.value())
+        {
+            *mesh = Index::new(c as u32);
+        }
+    }
+    Ok(Some((n_json, o_blob.to_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::write_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn duplicate_meshes_collapse_to_one() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        // A copy of mesh 0 reading copies of its accessors, and one drawn as points
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        let accessors = doc["accessors"].as_array().unwrap().clone();
+        let n = accessors.len();
+        doc["accessors"].as_array_mut().unwrap().extend(accessors);
+        let mut copy = doc["meshes"][0].clone();
+        copy["name"] = "copy".into();
+        for (_, accessor) in copy["primitives"][0]["attributes"].as_object_mut().unwrap() {
+            *accessor = (accessor.as_u64().unwrap() as usize + n).into();
+        }
+        copy["primitives"][0]["indices"] =
+            (copy["primitives"][0]["indices"].as_u64().unwrap() as usize + n).into();
+        let mut points = doc["meshes"][0].clone();
+        points["primitives"][0]["mode"] = 0.into();
+        doc["meshes"].as_array_mut().unwrap().extend([copy, points]);
+        let nodes = doc["nodes"].as_array_mut().unwrap();
+        nodes.push(serde_json::json!({ "mesh": 1, "translation": [3.0, 0.0, 0.0] }));
+        nodes.push(serde_json::json!({ "mesh": 2, "translation": [6.0, 0.0, 0.0] }));
+        doc["scenes"][0]["nodes"] = serde_json::json!([0, 1, 2]);
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let run = |dedupe_meshes| {
+            let options = OptimizeOptions {
+                dedupe_meshes,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            read_glb(&mut Cursor::new(out)).unwrap().0
+        };
+        assert_eq!(run(false).meshes.len(), 3);
+        let n_json = run(true);
+        assert_eq!(n_json.meshes.len(), 2);
+        let meshes: Vec<_> = n_json
+            .nodes
+            .iter()
+            .map(|n| n.mesh.unwrap().value())
+            .collect();
+        assert_eq!(meshes, [0, 0, 1]);
+        assert_eq!(n_json.nodes[1].translation, Some([3.0, 0.0, 0.0]));
+    }
+}
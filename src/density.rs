@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use gltf::json::Root;
+
+use crate::{
+    atlas::slots,
+    bounds::{for_each_mesh_instance, scene_roots},
+    error::OptimizeError,
+    stats::primitive_triangles,
+};
+
+/// Shrink the textures of materials drawing few triangles, see
+/// [`OptimizeOptions::triangle_texture_scaling`](crate::OptimizeOptions::triangle_texture_scaling)
+///
+/// A material drawing `full_size_share` of the default scene's triangles or more keeps its
+/// slot sizes; below that its textures scale with the square root of its share, so texel
+/// density follows surface detail, down to `min_scale`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct TriangleTextureScaling {
+    /// Share of the scene's triangles, in `(0, 1]`, from which textures keep their full size
+    pub full_size_share: f32,
+    /// Smallest factor, in `(0, 1]`, applied to a slot's size
+    pub min_scale: f32,
+}
+
+impl Default for TriangleTextureScaling {
+    fn default() -> Self {
+        TriangleTextureScaling {
+            full_size_share: 0.1,
+            min_scale: 0.25,
+        }
+    }
+}
+
+/// Size factor of each texture from the triangles its materials draw in the default scene
+///
+/// Triangles are counted once per mesh instance. A texture shared by several materials takes
+/// the largest factor, and textures of materials the default scene doesn't draw triangles with
+/// are left out, keeping their full size.
+pub(crate) fn triangle_texture_scales(
+    o_json: &Root,
+    scaling: &TriangleTextureScaling,
+) -> Result<HashMap<usize, f32>, OptimizeError> {
+    let in_range = |value: f32| value > 0.0 && value <= 1.0;
+    if !in_range(scaling.full_size_share) || !in_range(scaling.min_scale) {
+        return Err(OptimizeError::InvalidOption(format!(
+            "{scaling:?} has a share or scale outside (0, 1]"
+        )));
+    }
+
+    let mut material_triangles: HashMap<usize, u64> = HashMap::new();
+    let mut total = 0;
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, _| {
+        for primitive in o_json
+            .meshes
+            .get(mesh_idx)
+            .iter()
+            .flat_map(|m| &m.primitives)
+        {
+            let triangles = primitive_triangles(o_json, primitive);
+            total += triangles;
+            if let Some(material) = primitive.material {
+                *material_triangles.entry(material.value()).or_default() += triangles;
+            }
+        }
+    });
+
+    let mut scales: HashMap<usize, f32> = HashMap::new();
+    for (i, material) in o_json.materials.iter().enumerate() {
+        let scale = match material_triangles.get(&i) {
+            Some(&triangles) if triangles > 0 => {
+                let share = triangles as f64 / total as f64;
+                let scale = (share / scaling.full_size_share as f64).sqrt() as f32;
+                scale.clamp(scaling.min_scale, 1.0)
+            }
+            _ => 1.0,
+        };
+        for (texture, _, _) in slots(material).into_iter().flatten() {
+            let entry = scales.entry(texture.value()).or_insert(scale);
+            *entry = entry.max(scale);
+        }
+    }
+    scales.retain(|_, scale| *scale < 1.0);
+    Ok(scales)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        stats::image_dimensions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn textures_scale_with_the_triangles_their_materials_draw() {
+        // Mesh 0 is drawn nine times, mesh 1 once: a 90/10 split of the triangles
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let drawn = json.nodes.iter().position(|n| n.mesh.is_some()).unwrap();
+        for _ in 0..8 {
+            let node = json.nodes[drawn].clone();
+            json.nodes.push(node);
+            let index = Index::new(json.nodes.len() as u32 - 1);
+            json.scenes[0].nodes.push(index);
+        }
+        let glb = write_glb(&json, blob).unwrap();
+
+        let run = |scaling| {
+            let options = OptimizeOptions {
+                texture_size: 64,
+                triangle_texture_scaling: Some(scaling),
+                ..Default::default()
+            };
+            optimize_with(&mut Cursor::new(&glb), &options)
+        };
+        let out = run(TriangleTextureScaling {
+            full_size_share: 0.5,
+            min_scale: 0.25,
+        })
+        .unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let side = |mesh: usize| {
+            let material = n_json.meshes[mesh].primitives[0].material.unwrap();
+            let info = &n_json.materials[material.value()].pbr_metallic_roughness;
+            let texture = info.base_color_texture.as_ref().unwrap().index;
+            image_dimensions(get_image_data(&n_blob, &n_json, texture).unwrap())
+                .unwrap()
+                .0
+        };
+        // sqrt(0.1 / 0.5) of 64
+        assert_eq!((side(0), side(1)), (64, 29));
+
+        let e = run(TriangleTextureScaling {
+            full_size_share: 0.0,
+            min_scale: 0.25,
+        });
+        assert!(matches!(e, Err(OptimizeError::InvalidOption(_))));
+    }
+}
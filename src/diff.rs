@@ -0,0 +1,160 @@
+use std::io::{Cursor, Read, Seek};
+
+use gltf::json::Root;
+use serde_json::{Value, json};
+
+use crate::{
+    error::OptimizeError,
+    glb::read_glb,
+    inspect::object_counts,
+    report::{BeforeAfter, Document, OptimizeReport, compare},
+};
+
+/// Structural differences between two GLBs, see [`diff_glbs`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GlbDiff {
+    /// Sizes per part of the file, scene counts and textures, which are matched by index
+    pub report: OptimizeReport,
+    /// Length of every top-level array, by its glTF name, e.g. `("materials", 12 -> 3)`
+    pub objects: Vec<(&'static str, BeforeAfter)>,
+    /// Extensions only the second file uses
+    pub extensions_added: Vec<String>,
+    /// Extensions only the first file uses
+    pub extensions_removed: Vec<String>,
+}
+
+impl GlbDiff {
+    /// The whole diff as pretty-printed JSON: the [`OptimizeReport::to_json`] keys plus
+    /// `objects`, `extensionsAdded` and `extensionsRemoved`
+    pub fn to_json(&self) -> String {
+        let mut diff = self.report.to_value();
+        let objects: serde_json::Map<String, Value> = self
+            .objects
+            .iter()
+            .map(|&(name, count)| (name.to_string(), count.to_json()))
+            .collect();
+        diff["objects"] = objects.into();
+        diff["extensionsAdded"] = json!(self.extensions_added);
+        diff["extensionsRemoved"] = json!(self.extensions_removed);
+        format!("{diff:#}")
+    }
+}
+
+/// Compare two GLBs, typically a file and its optimized output, to review what changed
+///
+/// Reports object counts, extensions, byte sizes per category as in [`OptimizeReport`], and
+/// the stored size and format of each texture. Optimization can drop or reorder textures,
+/// so texture `i` of the first file is compared with texture `i` of the second.
+pub fn diff_glbs<A: Read + Seek, B: Read + Seek>(
+    before: &mut A,
+    after: &mut B,
+) -> Result<GlbDiff, OptimizeError> {
+    let mut glbs = [Vec::new(), Vec::new()];
+    before.read_to_end(&mut glbs[0])?;
+    after.read_to_end(&mut glbs[1])?;
+    let (a_json, a_blob) = read_glb(&mut Cursor::new(&glbs[0]))?;
+    let (b_json, b_blob) = read_glb(&mut Cursor::new(&glbs[1]))?;
+
+    let a = Document {
+        glb: &glbs[0],
+        json: &a_json,
+        blob: &a_blob,
+    };
+    let b = Document {
+        glb: &glbs[1],
+        json: &b_json,
+        blob: &b_blob,
+    };
+    let textures = b_json.textures.len();
+    let report = compare(&a, &b, |t| (t < textures).then_some(t));
+
+    let objects = object_counts(&a_json)
+        .into_iter()
+        .zip(object_counts(&b_json))
+        .map(|((name, before), (_, after))| {
+            let count = BeforeAfter {
+                before: before as u64,
+                after: after as u64,
+            };
+            (name, count)
+        })
+        .collect();
+    let only_in = |x: &Root, y: &Root| -> Vec<String> {
+        let mut only: Vec<String> = x
+            .extensions_used
+            .iter()
+            .filter(|e| !y.extensions_used.contains(e))
+            .cloned()
+            .collect();
+        only.sort();
+        only
+    };
+    Ok(GlbDiff {
+        report,
+        objects,
+        extensions_added: only_in(&b_json, &a_json),
+        extensions_removed: only_in(&a_json, &b_json),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SyntheticGlb;
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    use crate::{opt::optimize_with, options::OptimizeOptions};
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn diff_shows_what_optimization_changed() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default()
+            .texture_size(16)
+            .meshopt_compression(true);
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+
+        let diff = diff_glbs(&mut Cursor::new(&glb), &mut Cursor::new(&out)).unwrap();
+        assert_eq!(diff.extensions_added, ["EXT_meshopt_compression"]);
+        assert!(diff.extensions_removed.is_empty());
+        assert_eq!(diff.report.file.before, glb.len() as u64);
+        assert_eq!(diff.report.file.after, out.len() as u64);
+        let meshes = diff.objects.iter().find(|(name, _)| *name == "meshes");
+        assert_eq!(meshes.map(|(_, m)| (m.before, m.after)), Some((2, 2)));
+        let widths: Vec<_> = diff
+            .report
+            .textures
+            .iter()
+            .map(|t| {
+                (
+                    t.before.as_ref().unwrap().width,
+                    t.after.as_ref().unwrap().width,
+                )
+            })
+            .collect();
+        assert_eq!(widths, [(64, 16), (64, 16)]);
+
+        let json: Value = serde_json::from_str(&diff.to_json()).unwrap();
+        assert_eq!(
+            json["objects"]["meshes"],
+            json!({ "before": 2, "after": 2 })
+        );
+        assert_eq!(json["extensionsAdded"], json!(["EXT_meshopt_compression"]));
+    }
+
+    #[test]
+    fn identical_files_have_no_changes() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let diff = diff_glbs(&mut Cursor::new(&glb), &mut Cursor::new(&glb)).unwrap();
+        assert!(diff.objects.iter().all(|(_, c)| c.before == c.after));
+        assert!(diff.extensions_added.is_empty() && diff.extensions_removed.is_empty());
+        assert_eq!(diff.report.file.saved(), 0);
+    }
+}
@@ -0,0 +1,139 @@
+use image::{DynamicImage, RgbaImage};
+
+use crate::opt::TextureType;
+
+/// Peak dither offset in 8-bit levels, half a 5-bit step (about what ETC1S endpoints keep)
+const DITHER_AMPLITUDE: f32 = 4.0;
+
+/// 4x4 Bayer threshold matrix
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Noise pattern added to color channels before lossy encoding to break up banding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Dither {
+    /// 4x4 Bayer matrix; regular, cheap, survives block compression well
+    Ordered,
+    /// Interleaved gradient noise, a blue-noise approximation with no visible pattern
+    BlueNoise,
+}
+
+impl Dither {
+    /// Offset for a pixel in `-1.0..1.0`
+    fn offset(&self, x: u32, y: u32) -> f32 {
+        let t = match self {
+            Dither::Ordered => (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0,
+            Dither::BlueNoise => {
+                (52.982_918 * (0.067_110_56 * x as f32 + 0.005_837_15 * y as f32).fract()).fract()
+            }
+        };
+        t * 2.0 - 1.0
+    }
+}
+
+/// Dithering per texture class; all off by default
+///
+/// Typically enabled for base color (skyboxes, car paint gradients) and left off for normal
+/// and metallic-roughness maps, where noise reads as surface detail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DitherOptions {
+    pub base_color: Option<Dither>,
+    pub normal: Option<Dither>,
+    pub metallic_roughness: Option<Dither>,
+    pub occlusion: Option<Dither>,
+    pub emissive: Option<Dither>,
+}
+
+impl DitherOptions {
+    /// Dithering for a texture slot, if any
+    pub fn for_type(&self, texture_type: TextureType) -> Option<Dither> {
+        match texture_type {
+            TextureType::BaseColor => self.base_color,
+            TextureType::Normal => self.normal,
+            TextureType::MetallicRoughness => self.metallic_roughness,
+            TextureType::Occlusion => self.occlusion,
+            TextureType::Emissive => self.emissive,
+        }
+    }
+}
+
+/// Add the dither pattern to the color channels, leaving alpha alone
+pub(crate) fn dither_rgba(img: &mut RgbaImage, dither: Dither) {
+    for (x, y, p) in img.enumerate_pixels_mut() {
+        let offset = dither.offset(x, y) * DITHER_AMPLITUDE;
+        for c in &mut p.0[..3] {
+            *c = (*c as f32 + offset).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// Rebuild a dynamic image with the source's channel layout (JPEG has no alpha)
+pub(crate) fn with_source_layout(source: &DynamicImage, rgba: RgbaImage) -> DynamicImage {
+    if source.color().has_alpha() {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_stay_in_range_and_average_out() {
+        for dither in [Dither::Ordered, Dither::BlueNoise] {
+            let offsets: Vec<f32> = (0..16)
+                .flat_map(|y| (0..16).map(move |x| dither.offset(x, y)))
+                .collect();
+            assert!(offsets.iter().all(|o| (-1.0..1.0).contains(o)));
+            let mean = offsets.iter().sum::<f32>() / offsets.len() as f32;
+            assert!(mean.abs() < 0.05, "{dither:?}: {mean}");
+        }
+        // The Bayer pattern repeats every 4 pixels
+        assert_eq!(Dither::Ordered.offset(1, 2), Dither::Ordered.offset(5, 6));
+    }
+
+    #[test]
+    fn dithering_moves_color_but_not_alpha() {
+        let mut img = RgbaImage::from_pixel(8, 8, image::Rgba([100, 0, 255, 77]));
+        dither_rgba(&mut img, Dither::Ordered);
+        let reds: Vec<u8> = img.pixels().map(|p| p[0]).collect();
+        assert!(
+            reds.iter()
+                .all(|r| r.abs_diff(100) <= DITHER_AMPLITUDE as u8)
+        );
+        assert!(reds.iter().any(|&r| r != 100));
+        assert!(img.pixels().all(|p| p[3] == 77));
+        assert!(img.pixels().any(|p| p[1] == 0) && img.pixels().any(|p| p[2] == 255));
+    }
+
+    #[test]
+    fn dithering_is_chosen_per_slot() {
+        let options = DitherOptions {
+            base_color: Some(Dither::BlueNoise),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.for_type(TextureType::BaseColor),
+            Some(Dither::BlueNoise)
+        );
+        assert_eq!(options.for_type(TextureType::Normal), None);
+    }
+
+    #[test]
+    fn source_layout_drops_alpha_for_opaque_sources() {
+        let rgba = RgbaImage::new(2, 2);
+        let rgb = DynamicImage::ImageRgb8(image::RgbImage::new(2, 2));
+        assert_eq!(
+            with_source_layout(&rgb, rgba.clone()).color(),
+            image::ColorType::Rgb8
+        );
+        let source = DynamicImage::ImageRgba8(rgba.clone());
+        assert_eq!(
+            with_source_layout(&source, rgba).color(),
+            image::ColorType::Rgba8
+        );
+    }
+}
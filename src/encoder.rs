@@ -0,0 +1,101 @@
+use std::error::Error;
+
+use image::RgbaImage;
+
+use crate::opt::TextureType;
+
+/// Output of a [`TextureEncoder`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedTexture {
+    pub data: Vec<u8>,
+    /// Written to the image's `mimeType`
+    pub mime_type: String,
+    /// Extensions the format needs, added to `extensionsUsed` and `extensionsRequired`
+    pub extensions: Vec<String>,
+}
+
+/// Bring-your-own texture format (ASTC, PVRTC, proprietary, ...)
+///
+/// The optimizer still decodes and resizes; the encoder receives RGBA8 pixels already at the
+/// target size for the slot.
+pub trait TextureEncoder: Send + Sync {
+    fn encode(
+        &self,
+        image: &RgbaImage,
+        texture_type: TextureType,
+    ) -> Result<EncodedTexture, Box<dyn Error + Send + Sync>>;
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::read_glb, opt::get_image_data, optimizer::Optimizer, options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    /// Stores raw pixels behind a vendor extension and records what it was asked to encode
+    #[derive(Default)]
+    struct Raw {
+        seen: Mutex<Vec<(TextureType, u32, u32)>>,
+    }
+
+    impl TextureEncoder for Raw {
+        fn encode(
+            &self,
+            image: &RgbaImage,
+            texture_type: TextureType,
+        ) -> Result<EncodedTexture, Box<dyn Error + Send + Sync>> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push((texture_type, image.width(), image.height()));
+            Ok(EncodedTexture {
+                data: image.as_raw().clone(),
+                mime_type: "image/x-raw".to_string(),
+                extensions: vec!["VENDOR_raw_texture".to_string()],
+            })
+        }
+    }
+
+    #[test]
+    fn encoder_output_replaces_images() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let encoder = Raw::default();
+        let options = OptimizeOptions {
+            texture_size: 16,
+            convert_to_ktx2: true,
+            ..Default::default()
+        };
+        let out = Optimizer::new(&options)
+            .texture_encoder(&encoder)
+            .run(&mut Cursor::new(glb))
+            .unwrap()
+            .glb;
+
+        assert_eq!(
+            encoder.seen.into_inner().unwrap(),
+            [(TextureType::BaseColor, 16, 16); 2]
+        );
+
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(json.extensions_used, ["VENDOR_raw_texture"]);
+        assert_eq!(json.extensions_required, ["VENDOR_raw_texture"]);
+        for (t, image) in json.images.iter().enumerate() {
+            assert_eq!(image.mime_type.as_ref().unwrap().0, "image/x-raw");
+            let data = get_image_data(&blob, &json, Index::new(t as u32)).unwrap();
+            assert_eq!(data.len(), 16 * 16 * 4);
+        }
+    }
+}
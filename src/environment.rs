@@ -0,0 +1,488 @@
+use std::{collections::HashMap, f32::consts::PI, io::Cursor};
+
+use gltf::json::Root;
+use image::RgbaImage;
+#[cfg(feature = "ktx2")]
+use ktx2_rw::{BasisCompressionParams, Ktx2Texture};
+
+use crate::{
+    blob::BlobWriter,
+    decode::load_source_image,
+    error::OptimizeError,
+    opt::{add_image, resize_to_jpg, resize_to_png},
+    options::OptimizeOptions,
+    quality::DEFAULT_JPEG_QUALITY,
+};
+
+/// Root extension carrying image-based lights
+pub(crate) const IBL_EXTENSION: &str = "EXT_lights_image_based";
+
+/// Shortest edge an image needs before a 2:1 shape is taken as an environment map
+const MIN_EQUIRECT_HEIGHT: u32 = 256;
+
+/// Whether an image looks like an equirectangular panorama (2:1 within ~1.5%)
+pub fn is_equirectangular(width: u32, height: u32) -> bool {
+    height >= MIN_EQUIRECT_HEIGHT && width.abs_diff(height * 2) <= width / 64
+}
+
+/// Output size for a texture capped at `size`
+///
+/// The longer side is scaled down to `size` keeping the aspect ratio, except for panoramas,
+/// which become exactly 2:1 with `size` capping the height. `power_of_two` then rounds each
+/// side to the nearest power of two that stays within the cap.
+pub(crate) fn texture_dimensions(
+    width: u32,
+    height: u32,
+    size: u32,
+    power_of_two: bool,
+) -> (u32, u32) {
+    let panorama = is_equirectangular(width, height);
+    let (width, height) = if panorama {
+        if height > size {
+            (size * 2, size)
+        } else {
+            (width, height)
+        }
+    } else if (width > size || height > size) && width > 0 && height > 0 {
+        let longer = width.max(height) as u64;
+        let scale = |side: u32| ((side as u64 * size as u64 + longer / 2) / longer).max(1) as u32;
+        (scale(width), scale(height))
+    } else {
+        (width, height)
+    };
+    if !power_of_two {
+        return (width, height);
+    }
+
+    let cap = |bound: u32| 1u32 << (u32::BITS - 1 - bound.max(1).leading_zeros());
+    let round = |side: u32, bound: u32| {
+        let side = side.max(1);
+        let up = side.next_power_of_two();
+        let down = (up / 2).max(1);
+        let nearest = if up - side < side - down { up } else { down };
+        nearest.min(cap(bound))
+    };
+    let width_bound = if panorama { size * 2 } else { size };
+    (round(width, width_bound), round(height, size))
+}
+
+/// Bilinear sample of an equirectangular image, `u` wrapping around the horizon
+fn sample_equirect(image: &RgbaImage, u: f32, v: f32) -> [u8; 4] {
+    let (width, height) = image.dimensions();
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let px = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as u32).min(height - 1);
+        image.get_pixel(x, y).0
+    };
+    let (p00, p10) = (px(x0, y0), px(x0 + 1.0, y0));
+    let (p01, p11) = (px(x0, y0 + 1.0), px(x0 + 1.0, y0 + 1.0));
+
+    std::array::from_fn(|c| {
+        let top = p00[c] as f32 * (1.0 - fx) + p10[c] as f32 * fx;
+        let bottom = p01[c] as f32 * (1.0 - fx) + p11[c] as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    })
+}
+
+/// Project a panorama onto the six faces of a cube map
+///
+/// Faces come in KTX2 order (+X, -X, +Y, -Y, +Z, -Z) using the usual GL orientation; the
+/// panorama's center column faces -Z.
+pub fn equirect_to_cubemap(image: &RgbaImage, face_size: u32) -> Vec<RgbaImage> {
+    let direction = |face: usize, a: f32, b: f32| match face {
+        0 => [1.0, -b, -a],
+        1 => [-1.0, -b, a],
+        2 => [a, 1.0, b],
+        3 => [a, -1.0, -b],
+        4 => [a, -b, 1.0],
+        _ => [-a, -b, -1.0],
+    };
+
+    (0..6)
+        .map(|face| {
+            RgbaImage::from_fn(face_size, face_size, |x, y| {
+                let a = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+                let b = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+                let [dx, dy, dz] = direction(face, a, b);
+                let len = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                let u = 0.5 + dx.atan2(-dz) / (2.0 * PI);
+                let v = (dy / len).clamp(-1.0, 1.0).acos() / PI;
+                image::Rgba(sample_equirect(image, u, v))
+            })
+        })
+        .collect()
+}
+
+/// Encode six square faces as a Basis Universal (ETC1S) KTX2 cube map
+///
+/// The encoder uses as many threads as `options` give a Basis Universal encode, see
+/// [`OptimizeOptions::encoder_threads`] and [`OptimizeOptions::low_priority`].
+#[cfg(feature = "ktx2")]
+pub fn cubemap_to_ktx2(
+    faces: &[RgbaImage],
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    let size = faces.first().map_or(0, |f| f.width());
+    if faces.len() != 6 || faces.iter().any(|f| f.dimensions() != (size, size)) {
+        return Err(OptimizeError::CubeMapFaces {
+            dimensions: faces.iter().map(|f| f.dimensions()).collect(),
+        });
+    }
+
+    let mut ktx2_tex =
+        Ktx2Texture::create(size, size, 1, 1, 6, 1, ktx2_rw::VkFormat::R8G8B8A8Unorm)
+            .map_err(OptimizeError::ktx2)?;
+    for (face, image) in faces.iter().enumerate() {
+        ktx2_tex
+            .set_image_data(0, 0, face as u32, image.as_raw())
+            .map_err(OptimizeError::ktx2)?;
+    }
+    ktx2_tex
+        .set_metadata("Tool", b"glb_opt")
+        .map_err(OptimizeError::ktx2)?;
+    ktx2_tex
+        .set_metadata("Dimensions", format!("{size}x{size}").as_bytes())
+        .map_err(OptimizeError::ktx2)?;
+
+    let etc1s_params = BasisCompressionParams::builder()
+        .uastc(false)
+        .thread_count(options.basis_threads())
+        .quality_level(150)
+        .endpoint_rdo_threshold(1.25)
+        .selector_rdo_threshold(1.25)
+        .build();
+    ktx2_tex
+        .compress_basis(&etc1s_params)
+        .map_err(OptimizeError::ktx2)?;
+    ktx2_tex
+        .set_metadata("CompressionMode", b"ETC1S")
+        .map_err(OptimizeError::ktx2)?;
+
+    ktx2_tex.write_to_memory().map_err(OptimizeError::ktx2)
+}
+
+#[cfg(not(feature = "ktx2"))]
+pub fn cubemap_to_ktx2(
+    _faces: &[RgbaImage],
+    _options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    Err(crate::features::FeatureNotEnabled { feature: "ktx2" }.into())
+}
+
+/// Copy `EXT_lights_image_based` lights, re-embedding their specular cube map faces
+///
+/// Mip levels larger than `size` are dropped; when even the last level is larger, its faces
+/// are resized. Irradiance coefficients, rotation and intensity carry over unchanged, and
+/// scenes keep pointing at the same light indices.
+pub(crate) fn copy_image_based_lights(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    size: u32,
+) -> Result<(), OptimizeError> {
+    let Some(mut payload) = o_json
+        .extensions
+        .as_ref()
+        .and_then(|e| e.others.get(IBL_EXTENSION))
+        .cloned()
+    else {
+        return Ok(());
+    };
+
+    let mut copied: HashMap<(usize, u32), usize> = HashMap::new();
+    for light in payload["lights"].as_array_mut().into_iter().flatten() {
+        let Some(levels) = light["specularImages"].as_array() else {
+            continue;
+        };
+        let levels: Vec<Vec<usize>> = levels
+            .iter()
+            .map(|faces| {
+                faces
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|i| i.as_u64().map(|i| i as usize))
+                    .collect()
+            })
+            .collect();
+        let base_size = light["specularImageSize"].as_u64().unwrap_or(0) as u32;
+        let level_size = |level: usize| base_size.checked_shr(level as u32).unwrap_or(0);
+
+        // First mip level that fits, else the last one resized down
+        let first = (0..levels.len())
+            .find(|&level| level_size(level) <= size)
+            .unwrap_or(levels.len().saturating_sub(1));
+        let resize_to = (level_size(first) > size).then_some(size);
+
+        let mut n_levels = Vec::new();
+        for faces in levels.iter().skip(first) {
+            let mut n_faces = Vec::new();
+            for &face in faces {
+                let key = (face, resize_to.unwrap_or(0));
+                let idx = match copied.get(&key) {
+                    Some(&idx) => idx,
+                    None => {
+                        let idx = copy_face(n_blob, n_json, o_blob, o_json, face, resize_to)?;
+                        copied.insert(key, idx);
+                        idx
+                    }
+                };
+                n_faces.push(idx);
+            }
+            n_levels.push(n_faces);
+        }
+
+        light["specularImages"] = serde_json::json!(n_levels);
+        light["specularImageSize"] = level_size(first).min(size).into();
+    }
+
+    n_json
+        .extensions
+        .get_or_insert_with(Default::default)
+        .others
+        .insert(IBL_EXTENSION.to_string(), payload);
+    if !n_json.extensions_used.iter().any(|e| e == IBL_EXTENSION) {
+        n_json.extensions_used.push(IBL_EXTENSION.to_string());
+    }
+
+    Ok(())
+}
+
+/// Copy one cube map face image, optionally resizing it to `resize_to` square
+fn copy_face(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    image: usize,
+    resize_to: Option<u32>,
+) -> Result<usize, OptimizeError> {
+    let o_image = o_json
+        .images
+        .get(image)
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: image,
+        })?;
+
+    // Images referenced by URI stay external
+    let Some(view) = o_image
+        .buffer_view
+        .and_then(|v| o_json.buffer_views.get(v.value()))
+    else {
+        return Ok(n_json.push(o_image.clone()).value());
+    };
+    let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+    let data = o_blob
+        .get(offset..offset.saturating_add(view.byte_length.0 as usize))
+        .ok_or_else(|| OptimizeError::Truncated {
+            what: format!("image {image}"),
+        })?;
+    let mime_type = o_image
+        .mime_type
+        .as_ref()
+        .map_or("image/png", |m| m.0.as_str());
+
+    let idx = match resize_to {
+        // Re-encode in the source format, with PNG standing in for anything but JPEG
+        Some(size) => {
+            let img = load_source_image(o_json, image, data, Vec::new())?;
+            let mut resized = Vec::new();
+            let writer = Cursor::new(&mut resized);
+            let mime_type = if mime_type == "image/jpeg" {
+                resize_to_jpg(
+                    &img,
+                    data,
+                    size,
+                    size,
+                    DEFAULT_JPEG_QUALITY,
+                    &mut Vec::new(),
+                    writer,
+                )?;
+                "image/jpeg"
+            } else {
+                resize_to_png(&img, data, size, size, &mut Vec::new(), writer)?;
+                "image/png"
+            };
+            add_image(n_blob, n_json, o_image, &resized, mime_type)
+        }
+        None => add_image(n_blob, n_json, o_image, data, mime_type),
+    };
+
+    Ok(idx.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panoramas_are_recognized_by_shape() {
+        assert!(is_equirectangular(1024, 512));
+        assert!(is_equirectangular(1030, 512));
+        assert!(!is_equirectangular(1100, 512));
+        assert!(!is_equirectangular(256, 128));
+    }
+
+    #[test]
+    fn panoramas_stay_two_to_one() {
+        assert_eq!(texture_dimensions(4096, 2048, 1024, false), (2048, 1024));
+        assert_eq!(texture_dimensions(1030, 512, 1024, false), (1030, 512));
+        assert_eq!(texture_dimensions(1030, 512, 1024, true), (1024, 512));
+        assert_eq!(texture_dimensions(3000, 1000, 1024, false), (1024, 341));
+        assert_eq!(texture_dimensions(3000, 1000, 1024, true), (1024, 256));
+    }
+
+    #[test]
+    fn cube_faces_sample_the_matching_part_of_the_panorama() {
+        // Sky on top, ground below
+        let panorama = RgbaImage::from_fn(64, 32, |_, y| {
+            if y < 16 {
+                image::Rgba([0, 0, 255, 255])
+            } else {
+                image::Rgba([0, 255, 0, 255])
+            }
+        });
+        let faces = equirect_to_cubemap(&panorama, 8);
+        assert_eq!(faces.len(), 6);
+        assert!(faces.iter().all(|f| f.dimensions() == (8, 8)));
+        assert!(faces[2].pixels().all(|p| p.0 == [0, 0, 255, 255]));
+        assert!(faces[3].pixels().all(|p| p.0 == [0, 255, 0, 255]));
+        for face in [0, 1, 4, 5] {
+            assert_eq!(faces[face].get_pixel(4, 0).0, [0, 0, 255, 255]);
+            assert_eq!(faces[face].get_pixel(4, 7).0, [0, 255, 0, 255]);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "ktx2"))]
+    fn cube_map_encoding_needs_ktx2() {
+        let faces = vec![RgbaImage::new(4, 4); 6];
+        let e = cubemap_to_ktx2(&faces, &OptimizeOptions::default()).unwrap_err();
+        assert_eq!(
+            e.feature_not_enabled(),
+            Some(&crate::features::FeatureNotEnabled { feature: "ktx2" })
+        );
+    }
+
+    /// Quad with an image-based light of two mip levels, 16 and 8 px, as PNG faces
+    #[cfg(feature = "png")]
+    fn lit_glb() -> Vec<u8> {
+        use gltf::json::{Image, image::MimeType};
+
+        use crate::{
+            glb::{read_glb, write_glb},
+            opt::encode_png,
+            testing::SyntheticGlb,
+        };
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let mut levels = Vec::new();
+        for size in [16, 8] {
+            let mut faces = Vec::new();
+            for face in 0..6u8 {
+                let pixels = RgbaImage::from_pixel(size, size, image::Rgba([face * 40, 0, 0, 255]));
+                let mut png = Vec::new();
+                encode_png(
+                    pixels.as_raw(),
+                    size,
+                    size,
+                    image::ColorType::Rgba8,
+                    &mut png,
+                )
+                .unwrap();
+                let view = blob.push_view(&mut json, &png, None);
+                faces.push(
+                    json.push(Image {
+                        buffer_view: Some(view),
+                        mime_type: Some(MimeType("image/png".to_string())),
+                        name: None,
+                        uri: None,
+                        extensions: None,
+                        extras: Default::default(),
+                    })
+                    .value(),
+                );
+            }
+            levels.push(faces);
+        }
+        json.extensions
+            .get_or_insert_with(Default::default)
+            .others
+            .insert(
+                IBL_EXTENSION.to_string(),
+                serde_json::json!({"lights": [{
+                    "intensity": 2.0,
+                    "specularImageSize": 16,
+                    "specularImages": levels,
+                }]}),
+            );
+        json.extensions_used.push(IBL_EXTENSION.to_string());
+        write_glb(&json, blob.into_vec()).unwrap()
+    }
+
+    /// Size of the light and of each of its faces in the optimized output
+    #[cfg(feature = "png")]
+    fn optimized_light(size: u32) -> (serde_json::Value, Vec<Vec<(u32, u32)>>) {
+        use crate::{glb::read_glb, opt::optimize_with, options::OptimizeOptions};
+
+        let options = OptimizeOptions {
+            environment_texture_size: Some(size),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(lit_glb()), &options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let light = json.extensions.as_ref().unwrap().others[IBL_EXTENSION]["lights"][0].clone();
+        let sizes = light["specularImages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|faces| {
+                faces
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|face| {
+                        let image = &json.images[face.as_u64().unwrap() as usize];
+                        let view = &json.buffer_views[image.buffer_view.unwrap().value()];
+                        let offset = view.byte_offset.map_or(0, |o| o.0 as usize);
+                        let data = &blob[offset..offset + view.byte_length.0 as usize];
+                        let image = image::load_from_memory(data).unwrap();
+                        (image.width(), image.height())
+                    })
+                    .collect()
+            })
+            .collect();
+        (light, sizes)
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn mip_levels_larger_than_the_cap_are_dropped() {
+        let (light, sizes) = optimized_light(8);
+        assert_eq!(light["specularImageSize"], 8);
+        assert_eq!(light["intensity"], 2.0);
+        assert_eq!(sizes, [vec![(8, 8); 6]]);
+
+        let (light, sizes) = optimized_light(64);
+        assert_eq!(light["specularImageSize"], 16);
+        assert_eq!(sizes, [vec![(16, 16); 6], vec![(8, 8); 6]]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn the_last_level_is_resized_when_nothing_fits() {
+        let (light, sizes) = optimized_light(4);
+        assert_eq!(light["specularImageSize"], 4);
+        assert_eq!(sizes, [vec![(4, 4); 6]]);
+    }
+}
@@ -0,0 +1,312 @@
+use std::{error::Error, fmt, io};
+
+use crate::{
+    auto::CandidateMeasurement, features::FeatureNotEnabled, opt::TextureType, reject::RejectLimit,
+};
+
+/// Caller-supplied extension point that failed, see [`OptimizeError::Callback`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Stage {
+    /// A [`TextureEncoder`](crate::TextureEncoder)
+    TextureEncoder,
+    /// An [`ExternalTextureFilter`](crate::ExternalTextureFilter)
+    TextureFilter,
+    /// A [`GeometryCodec`](crate::GeometryCodec)
+    GeometryCodec,
+    /// A [`SourceResolver`](crate::SourceResolver)
+    SourceResolver,
+    /// A [`ThumbnailRenderer`](crate::ThumbnailRenderer)
+    ThumbnailRenderer,
+    /// An [`ExtensionHandler`](crate::ExtensionHandler)
+    ExtensionHandler,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::TextureEncoder => "texture encoder",
+            Stage::TextureFilter => "texture filter",
+            Stage::GeometryCodec => "geometry codec",
+            Stage::SourceResolver => "source resolver",
+            Stage::ThumbnailRenderer => "thumbnail renderer",
+            Stage::ExtensionHandler => "extension handler",
+        })
+    }
+}
+
+/// What is wrong with an accessor, see [`OptimizeError::Accessor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AccessorProblem {
+    /// The index points past the document's accessors
+    Missing,
+    /// Its data runs past the end of its buffer view or the BIN chunk
+    OutOfBounds,
+    /// Its buffer view's stride is shorter than one element
+    ShortStride,
+    /// It has no buffer view, or a sparse one, where plain stored data is needed
+    NoBufferView,
+    /// Its elements don't have the `expected` number of components
+    Components { expected: usize },
+    /// Its type or component type doesn't fit its use, e.g. `TEXCOORD_0`
+    Unusable { usage: &'static str },
+    /// Its data could not be decoded
+    Unreadable,
+}
+
+impl fmt::Display for AccessorProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessorProblem::Missing => f.write_str("does not exist"),
+            AccessorProblem::OutOfBounds => f.write_str("has data out of bounds"),
+            AccessorProblem::ShortStride => f.write_str("has a stride shorter than its elements"),
+            AccessorProblem::NoBufferView => f.write_str("has no plain buffer view"),
+            AccessorProblem::Components { expected } => {
+                write!(f, "does not hold {expected}-component elements")
+            }
+            AccessorProblem::Unusable { usage } => write!(f, "is not a valid {usage}"),
+            AccessorProblem::Unreadable => f.write_str("could not be read"),
+        }
+    }
+}
+
+/// Part of the input that is malformed, see [`OptimizeError::Malformed`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Malformation {
+    /// A `data:` URI's payload is not valid base64
+    DataUriBase64,
+    /// More than one buffer has no URI, though only the first can be the BIN chunk
+    BuffersWithoutUri,
+    /// A GLB's JSON chunk holds no readable JSON
+    JsonChunk,
+    /// The BIN buffer declares more bytes than its buffer views or the file can account for
+    BufferLength { declared: usize, file: usize },
+    /// A KTX2 file has more levels than its base level's size allows
+    Ktx2Levels {
+        levels: usize,
+        width: u32,
+        height: u32,
+    },
+    /// The document's `KHR_materials_variants` extension has no variants list
+    VariantsList,
+}
+
+impl fmt::Display for Malformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Malformation::DataUriBase64 => f.write_str("data URI has invalid base64"),
+            Malformation::BuffersWithoutUri => f.write_str("more than one buffer has no uri"),
+            Malformation::JsonChunk => f.write_str("JSON chunk holds no readable JSON"),
+            Malformation::BufferLength { declared, file } => write!(
+                f,
+                "buffer 0 declares {declared} bytes, more than its buffer views or the {file} \
+                 byte file can account for"
+            ),
+            Malformation::Ktx2Levels {
+                levels,
+                width,
+                height,
+            } => write!(
+                f,
+                "KTX2 has {levels} levels, more than its {width}x{height} size allows"
+            ),
+            Malformation::VariantsList => {
+                f.write_str("KHR_materials_variants has no variants list")
+            }
+        }
+    }
+}
+
+/// Why an optimization, or one of the readers and passes around it, failed
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum OptimizeError {
+    /// The input could not be parsed as a GLB or `.gltf` document
+    #[error("input is not a valid glTF/GLB: {0}")]
+    InvalidInput(#[from] gltf::Error),
+    /// The input is a glTF 1.x document or GLB, which this crate does not read
+    #[error(
+        "glTF {version} is not supported; convert the file to glTF 2.0 first, e.g. with \
+         `upgrade_gltf1` for simple assets or with glTF-Pipeline or Blender"
+    )]
+    UnsupportedVersion { version: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Image encoding or decoding failed in the `image` crate
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    /// Reading, writing or Basis Universal compression of a KTX2 texture failed
+    #[cfg(feature = "ktx2")]
+    #[error("KTX2 operation failed: {source}")]
+    Ktx2 {
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The SIMD resizer failed or got a pixel buffer that doesn't fit the image
+    #[cfg(feature = "resize")]
+    #[error("resizing failed: {source}")]
+    Resize {
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A ZIP archive could not be read
+    #[cfg(feature = "archive")]
+    #[error("ZIP archive could not be read: {source}")]
+    Archive {
+        source: Box<dyn Error + Send + Sync>,
+    },
+    #[error(transparent)]
+    FeatureNotEnabled(#[from] FeatureNotEnabled),
+    /// A texture index points past the document's textures
+    #[error("texture {texture} does not exist")]
+    TextureOutOfBounds { texture: usize },
+    /// An accessor is missing or its data can't be read
+    #[error("accessor {accessor} {problem}")]
+    Accessor {
+        accessor: usize,
+        problem: AccessorProblem,
+    },
+    /// An object index points past the document's objects of that kind, e.g. `image 3`
+    #[error("{kind} {index} does not exist")]
+    MissingObject { kind: &'static str, index: usize },
+    /// No object of `kind` has one of these names
+    #[error("no {kind} is named {}", names.join(", "))]
+    MissingName {
+        kind: &'static str,
+        names: Vec<String>,
+    },
+    /// A primitive lacks an attribute an operation reads, e.g. `Positions`
+    #[error("primitive has no {semantic} attribute")]
+    MissingAttribute { semantic: String },
+    /// A texture's image is not embedded in the GLB or its bytes can't be found
+    #[error("texture {texture} has no embedded image data")]
+    MissingImageData { texture: usize },
+    /// An image is in a format the operation can't take; `format` is the one its bytes show
+    #[error("image is {format}, not PNG, JPEG, WebP or KTX2")]
+    UnsupportedImage { format: &'static str },
+    /// Pixel data doesn't fill the image size it comes with
+    #[error("image data does not match {width}x{height}")]
+    ImageSize { width: u32, height: u32 },
+    /// The input does not start like the file it should be; `expected` names it, e.g.
+    /// `KTX2 file`
+    #[error("input is not a {expected}")]
+    NotFormat { expected: &'static str },
+    /// A GLB lacks a chunk an operation reads, e.g. `BIN`
+    #[error("GLB has no {chunk} chunk")]
+    MissingChunk { chunk: &'static str },
+    /// Part of the input is malformed
+    #[error("{0}")]
+    Malformed(Malformation),
+    /// A material's or mesh's optimization hints are malformed
+    #[error("{kind} {index} has a malformed hint: {reason}")]
+    Hint {
+        kind: &'static str,
+        index: usize,
+        reason: String,
+    },
+    /// Faces given for a cube map are not six squares of one size; `dimensions` lists theirs
+    #[error("cube map needs six square faces of the same size, got {dimensions:?}")]
+    CubeMapFaces { dimensions: Vec<(u32, u32)> },
+    /// Output data of `what` is larger than a GLB's 32-bit lengths can describe
+    #[error("{what} of {len} bytes exceeds the 4 GiB a GLB can hold")]
+    TooLarge { what: &'static str, len: usize },
+    /// A header, chunk or range of `what` runs past the end of its data
+    #[error("{what} runs past the end of the data")]
+    Truncated { what: String },
+    /// The input is valid but uses something this crate can't handle, e.g. Basis Universal
+    /// KTX2 textures as input
+    #[error("{what} is not supported")]
+    Unsupported { what: String },
+    /// An archive holds no or several `.gltf`/`.glb` files; `found` lists the ones it holds
+    #[cfg(feature = "archive")]
+    #[error("archive must hold exactly one .gltf or .glb file, found {found:?}")]
+    ArchiveEntries { found: Vec<String> },
+    /// The default scene has no triangles for an operation that needs them, e.g. `frame`
+    #[error("the scene has no geometry to {task}")]
+    NoGeometry { task: &'static str },
+    /// An embedded image could not be decoded; `details` names the image, its size, declared
+    /// and detected format and the material slots using it
+    #[error("failed to decode {details}: {source}")]
+    Decode {
+        image: usize,
+        details: String,
+        source: Box<OptimizeError>,
+    },
+    /// A texture used by a material slot could not be resized or encoded
+    #[error("failed to process {} texture {texture}: {source}", slot.label())]
+    Texture {
+        texture: usize,
+        slot: TextureType,
+        source: Box<OptimizeError>,
+    },
+    /// An option is out of its valid range
+    #[error("invalid option: {0}")]
+    InvalidOption(String),
+    /// A caller-supplied extension point returned an error
+    #[error("{stage} failed: {source}")]
+    Callback {
+        stage: Stage,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The input exceeds a limit of
+    /// [`OptimizeOptions::reject_if`](crate::OptimizeOptions::reject_if); nothing
+    /// was processed
+    #[error("input has {value} {limit}, more than the {max} allowed")]
+    Rejected {
+        limit: RejectLimit,
+        value: u64,
+        max: u64,
+    },
+    /// No configuration [`auto_optimize`](crate::auto_optimize) tried meets every limit of
+    /// its objective; `smallest` measures the smallest output, `None` when none was produced
+    #[error(
+        "no candidate meets the objective{}",
+        smallest.map(|s| format!("; the smallest has {s}")).unwrap_or_default()
+    )]
+    ObjectiveUnmet {
+        smallest: Option<CandidateMeasurement>,
+    },
+    /// Work run on a separate thread panicked; `message` is the panic's, empty when it
+    /// isn't a string
+    #[error("{task} panicked: {message}")]
+    Panicked { task: &'static str, message: String },
+    /// A [`ProgressSink`](crate::ProgressSink) asked to stop
+    #[error("optimization was cancelled")]
+    Cancelled,
+}
+
+impl OptimizeError {
+    #[cfg(feature = "ktx2")]
+    pub(crate) fn ktx2(source: ktx2_rw::Error) -> Self {
+        OptimizeError::Ktx2 {
+            source: Box::new(source),
+        }
+    }
+
+    #[cfg(feature = "resize")]
+    pub(crate) fn resize(source: impl Error + Send + Sync + 'static) -> Self {
+        OptimizeError::Resize {
+            source: Box::new(source),
+        }
+    }
+
+    #[cfg(feature = "archive")]
+    pub(crate) fn archive(source: zip::result::ZipError) -> Self {
+        OptimizeError::Archive {
+            source: Box::new(source),
+        }
+    }
+
+    /// Feature a build of the crate lacks, looking through texture and decode context
+    pub fn feature_not_enabled(&self) -> Option<&FeatureNotEnabled> {
+        match self {
+            OptimizeError::FeatureNotEnabled(e) => Some(e),
+            OptimizeError::Decode { source, .. } | OptimizeError::Texture { source, .. } => {
+                source.feature_not_enabled()
+            }
+            _ => None,
+        }
+    }
+}
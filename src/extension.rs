@@ -0,0 +1,584 @@
+use std::{collections::HashMap, error::Error};
+
+use gltf::json::Root;
+use serde_json::Value;
+
+use crate::{
+    blob::BlobWriter,
+    environment::IBL_EXTENSION,
+    error::{AccessorProblem, OptimizeError, Stage},
+    lod::LOD_EXTENSION,
+    material_extensions::MATERIAL_EXTENSIONS,
+    meshlet::MESHLET_EXTENSION,
+    meshopt::MESHOPT_EXTENSION,
+    streaming::STREAMING_EXTENSION,
+    texture_table::TEXTURE_TABLE_EXTENSION,
+    variants::VARIANTS_EXTENSION,
+};
+
+/// Object in the optimized document that carries an extension payload
+///
+/// Indices refer to the output. Scenes, nodes, meshes, primitives, samplers, cameras, skins
+/// and animations keep their source indices; materials, textures, images, accessors and
+/// buffer views are renumbered by the rebuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExtensionOwner {
+    Root,
+    Scene(usize),
+    Node(usize),
+    Mesh(usize),
+    Primitive { mesh: usize, primitive: usize },
+    Material(usize),
+    Texture(usize),
+    Image(usize),
+    Sampler(usize),
+    Camera(usize),
+    Skin(usize),
+    Animation(usize),
+    Accessor(usize),
+    BufferView(usize),
+}
+
+/// Plug-in that carries a vendor extension through the optimizer rebuild
+///
+/// Payloads of unregistered extensions are dropped, as they may hold indices that no longer
+/// point at the right objects.
+pub trait ExtensionHandler: Send + Sync {
+    /// Extension name as listed in `extensionsUsed`
+    fn name(&self) -> &str;
+
+    /// Fix up one payload in the output, copying referenced data through `ctx`
+    ///
+    /// Nested payloads (texture infos, camera projections) are kept but not passed here.
+    fn rebuild(
+        &self,
+        owner: ExtensionOwner,
+        payload: &mut Value,
+        ctx: &mut ExtensionContext,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// Source and output documents handed to an [`ExtensionHandler`]
+pub struct ExtensionContext<'a> {
+    o_blob: &'a [u8],
+    o_json: &'a Root,
+    n_blob: &'a mut BlobWriter,
+    n_json: &'a mut Root,
+    materials: HashMap<usize, Vec<usize>>,
+    textures: HashMap<usize, usize>,
+    images: HashMap<usize, usize>,
+    accessors: HashMap<usize, usize>,
+    views: HashMap<usize, usize>,
+}
+
+impl ExtensionContext<'_> {
+    /// The document as it was read
+    pub fn source(&self) -> &Root {
+        self.o_json
+    }
+
+    /// Binary chunk of the source document
+    pub fn source_blob(&self) -> &[u8] {
+        self.o_blob
+    }
+
+    /// The document being written
+    pub fn output(&self) -> &Root {
+        self.n_json
+    }
+
+    /// Output materials built from a source material, one per primitive that used it
+    pub fn materials(&self, source: usize) -> &[usize] {
+        self.materials.get(&source).map_or(&[], Vec::as_slice)
+    }
+
+    /// Write new bytes to the output as a buffer view, reusing identical bytes already written
+    pub fn write_buffer_view(&mut self, data: &[u8]) -> usize {
+        self.n_blob.push_view(self.n_json, data, None).value()
+    }
+
+    /// Copy a source buffer view's bytes into the output, once per view
+    pub fn copy_buffer_view(&mut self, source: usize) -> Result<usize, OptimizeError> {
+        if let Some(idx) = self.views.get(&source) {
+            return Ok(*idx);
+        }
+
+        let view = self
+            .o_json
+            .buffer_views
+            .get(source)
+            .ok_or(OptimizeError::MissingObject {
+                kind: "buffer view",
+                index: source,
+            })?;
+        let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+        let length = view.byte_length.0 as usize;
+        let data = self
+            .o_blob
+            .get(offset..offset.saturating_add(length))
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: format!("buffer view {source}"),
+            })?;
+
+        let idx = self.n_blob.push_view(self.n_json, data, None);
+        if let Some(n_view) = self.n_json.buffer_views.get_mut(idx.value()) {
+            n_view.byte_stride = view.byte_stride;
+            n_view.target = view.target;
+            n_view.name = view.name.clone();
+        }
+
+        self.views.insert(source, idx.value());
+        Ok(idx.value())
+    }
+
+    /// Copy a source accessor and the buffer views it reads from, once per accessor
+    pub fn copy_accessor(&mut self, source: usize) -> Result<usize, OptimizeError> {
+        if let Some(idx) = self.accessors.get(&source) {
+            return Ok(*idx);
+        }
+
+        let mut acc = self
+            .o_json
+            .accessors
+            .get(source)
+            .ok_or_else(|| OptimizeError::Accessor {
+                accessor: source,
+                problem: AccessorProblem::Missing,
+            })?
+            .clone();
+        if let Some(view) = acc.buffer_view {
+            acc.buffer_view = Some(gltf::json::Index::new(
+                self.copy_buffer_view(view.value())? as u32
+            ));
+        }
+        if let Some(sparse) = &mut acc.sparse {
+            sparse.indices.buffer_view = gltf::json::Index::new(
+                self.copy_buffer_view(sparse.indices.buffer_view.value())? as u32,
+            );
+            sparse.values.buffer_view = gltf::json::Index::new(
+                self.copy_buffer_view(sparse.values.buffer_view.value())? as u32,
+            );
+        }
+
+        let idx = self.n_json.push(acc).value();
+        self.accessors.insert(source, idx);
+        Ok(idx)
+    }
+
+    /// Copy a source texture and its image unchanged, once per texture
+    ///
+    /// Samplers keep their indices, so the sampler reference is left as is.
+    pub fn copy_texture(&mut self, source: usize) -> Result<usize, OptimizeError> {
+        if let Some(idx) = self.textures.get(&source) {
+            return Ok(*idx);
+        }
+
+        let mut texture = self
+            .o_json
+            .textures
+            .get(source)
+            .ok_or(OptimizeError::TextureOutOfBounds { texture: source })?
+            .clone();
+        texture.source = gltf::json::Index::new(self.copy_image(texture.source.value())? as u32);
+
+        let idx = self.n_json.push(texture).value();
+        self.textures.insert(source, idx);
+        Ok(idx)
+    }
+
+    /// Copy a source image unchanged, once per image
+    pub fn copy_image(&mut self, source: usize) -> Result<usize, OptimizeError> {
+        if let Some(idx) = self.images.get(&source) {
+            return Ok(*idx);
+        }
+
+        let mut image = self
+            .o_json
+            .images
+            .get(source)
+            .ok_or(OptimizeError::MissingObject {
+                kind: "image",
+                index: source,
+            })?
+            .clone();
+        if let Some(view) = image.buffer_view {
+            image.buffer_view = Some(gltf::json::Index::new(
+                self.copy_buffer_view(view.value())? as u32
+            ));
+        }
+
+        let idx = self.n_json.push(image).value();
+        self.images.insert(source, idx);
+        Ok(idx)
+    }
+
+    /// Replace `{"index": n}` objects stored under keys ending in `Texture` with copied textures
+    ///
+    /// This is the layout the `KHR_materials_*` extensions use for texture slots.
+    pub fn copy_texture_infos(&mut self, payload: &mut Value) -> Result<(), OptimizeError> {
+        let Some(object) = payload.as_object_mut() else {
+            return Ok(());
+        };
+        for (key, value) in object.iter_mut() {
+            if key.ends_with("Texture")
+                && let Some(index) = value.get("index").and_then(Value::as_u64)
+            {
+                value["index"] = self.copy_texture(index as usize)?.into();
+            } else {
+                self.copy_texture_infos(value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Set of [`ExtensionHandler`]s applied after the optimizer rebuild
+#[derive(Default)]
+pub struct ExtensionRegistry {
+    handlers: Vec<Box<dyn ExtensionHandler>>,
+}
+
+impl ExtensionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<H: ExtensionHandler + 'static>(&mut self, handler: H) -> &mut Self {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Rebuild the registered payloads in the output, dropping every other one not in `keep`
+    pub(crate) fn apply(
+        &self,
+        o_blob: &[u8],
+        o_json: &Root,
+        n_json: &mut Root,
+        n_blob: &mut BlobWriter,
+        keep: &[&str],
+    ) -> Result<(), OptimizeError> {
+        let names: Vec<&str> = self.handlers.iter().map(|h| h.name()).collect();
+
+        // Root payloads are not carried over by the rebuild
+        if let Some(o_ext) = &o_json.extensions {
+            let n_ext = n_json.extensions.get_or_insert_with(Default::default);
+            for name in &names {
+                if let Some(payload) = o_ext.others.get(*name) {
+                    n_ext.others.insert(name.to_string(), payload.clone());
+                }
+            }
+        }
+        retain_extensions(n_json, &[&names[..], keep].concat())?;
+
+        for name in &names {
+            if o_json.extensions_used.iter().any(|e| e == name)
+                && !n_json.extensions_used.iter().any(|e| e == name)
+            {
+                n_json.extensions_used.push(name.to_string());
+            }
+        }
+
+        let mut materials: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (o_mesh, n_mesh) in o_json.meshes.iter().zip(&n_json.meshes) {
+            for (o_p, n_p) in o_mesh.primitives.iter().zip(&n_mesh.primitives) {
+                if let (Some(o_mat), Some(n_mat)) = (o_p.material, n_p.material) {
+                    materials
+                        .entry(o_mat.value())
+                        .or_default()
+                        .push(n_mat.value());
+                }
+            }
+        }
+
+        // Payloads are rebuilt on the side since handlers may push new objects
+        let doc = serde_json::to_value(&*n_json)?;
+        let mut payloads = Vec::new();
+        for owner in owners(&doc) {
+            if let Some(Value::Object(ext)) = doc
+                .pointer(&owner.pointer())
+                .and_then(|o| o.get("extensions"))
+            {
+                for (name, payload) in ext {
+                    payloads.push((owner, name.clone(), payload.clone()));
+                }
+            }
+        }
+
+        let mut ctx = ExtensionContext {
+            o_blob,
+            o_json,
+            n_blob,
+            n_json,
+            materials,
+            textures: HashMap::new(),
+            images: HashMap::new(),
+            accessors: HashMap::new(),
+            views: HashMap::new(),
+        };
+        for (owner, name, payload) in &mut payloads {
+            if let Some(handler) = self.handlers.iter().find(|h| h.name() == name) {
+                handler
+                    .rebuild(*owner, payload, &mut ctx)
+                    .map_err(|source| OptimizeError::Callback {
+                        stage: Stage::ExtensionHandler,
+                        source,
+                    })?;
+            }
+        }
+
+        let mut doc = serde_json::to_value(&*ctx.n_json)?;
+        for (owner, name, payload) in payloads {
+            if let Some(slot) = doc
+                .pointer_mut(&owner.pointer())
+                .and_then(|o| o.get_mut("extensions"))
+                .and_then(|ext| ext.get_mut(&name))
+            {
+                *slot = payload;
+            }
+        }
+        *ctx.n_json = serde_json::from_value(doc)?;
+
+        Ok(())
+    }
+}
+
+impl ExtensionOwner {
+    /// JSON pointer to the owning object in the serialized document
+    fn pointer(&self) -> String {
+        match self {
+            ExtensionOwner::Root => String::new(),
+            ExtensionOwner::Scene(i) => format!("/scenes/{i}"),
+            ExtensionOwner::Node(i) => format!("/nodes/{i}"),
+            ExtensionOwner::Mesh(i) => format!("/meshes/{i}"),
+            ExtensionOwner::Primitive { mesh, primitive } => {
+                format!("/meshes/{mesh}/primitives/{primitive}")
+            }
+            ExtensionOwner::Material(i) => format!("/materials/{i}"),
+            ExtensionOwner::Texture(i) => format!("/textures/{i}"),
+            ExtensionOwner::Image(i) => format!("/images/{i}"),
+            ExtensionOwner::Sampler(i) => format!("/samplers/{i}"),
+            ExtensionOwner::Camera(i) => format!("/cameras/{i}"),
+            ExtensionOwner::Skin(i) => format!("/skins/{i}"),
+            ExtensionOwner::Animation(i) => format!("/animations/{i}"),
+            ExtensionOwner::Accessor(i) => format!("/accessors/{i}"),
+            ExtensionOwner::BufferView(i) => format!("/bufferViews/{i}"),
+        }
+    }
+}
+
+/// Builds the owner for an index into one of the document arrays
+type OwnerAt = fn(usize) -> ExtensionOwner;
+
+/// Every object in a serialized document that may carry an `extensions` map
+fn owners(doc: &Value) -> Vec<ExtensionOwner> {
+    let count = |v: &Value, key: &str| v.get(key).and_then(Value::as_array).map_or(0, Vec::len);
+    let arrays: [(&str, OwnerAt); 12] = [
+        ("scenes", ExtensionOwner::Scene),
+        ("nodes", ExtensionOwner::Node),
+        ("meshes", ExtensionOwner::Mesh),
+        ("materials", ExtensionOwner::Material),
+        ("textures", ExtensionOwner::Texture),
+        ("images", ExtensionOwner::Image),
+        ("samplers", ExtensionOwner::Sampler),
+        ("cameras", ExtensionOwner::Camera),
+        ("skins", ExtensionOwner::Skin),
+        ("animations", ExtensionOwner::Animation),
+        ("accessors", ExtensionOwner::Accessor),
+        ("bufferViews", ExtensionOwner::BufferView),
+    ];
+
+    let mut owners = vec![ExtensionOwner::Root];
+    for (key, owner) in arrays {
+        owners.extend((0..count(doc, key)).map(owner));
+    }
+    for mesh in 0..count(doc, "meshes") {
+        owners.extend(
+            (0..count(&doc["meshes"][mesh], "primitives"))
+                .map(|primitive| ExtensionOwner::Primitive { mesh, primitive }),
+        );
+    }
+    owners
+}
+
+/// Drop every extension payload whose name is not in `keep`
+///
+/// Extensions the optimizer rebuilds itself (`EXT_lights_image_based`, `MSFT_lod`,
+/// `KHR_materials_variants`, the `KHR_materials_*` material models, ...) always stay. `extras` are left alone, and kept payloads are not searched for nested extensions.
+pub(crate) fn retain_extensions(n_json: &mut Root, keep: &[&str]) -> Result<(), OptimizeError> {
+    fn visit(value: &mut Value, keep: &[&str]) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::Object(ext)) = object.get_mut("extensions") {
+                    ext.retain(|name, _| {
+                        keep.contains(&name.as_str())
+                            || MATERIAL_EXTENSIONS.contains(&name.as_str())
+                            || name == IBL_EXTENSION
+                            || name == LOD_EXTENSION
+                            || name == MESHLET_EXTENSION
+                            || name == MESHOPT_EXTENSION
+                            || name == STREAMING_EXTENSION
+                            || name == TEXTURE_TABLE_EXTENSION
+                            || name == VARIANTS_EXTENSION
+                    });
+                    if ext.is_empty() {
+                        object.remove("extensions");
+                    }
+                }
+                for (key, child) in object.iter_mut() {
+                    if key != "extras" && key != "extensions" {
+                        visit(child, keep);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| visit(item, keep)),
+            _ => {}
+        }
+    }
+
+    let mut doc = serde_json::to_value(&*n_json)?;
+    visit(&mut doc, keep);
+    *n_json = serde_json::from_value(doc)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::get_position_data,
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    /// Vendor extension on nodes pointing at an accessor
+    struct AccessorRef;
+
+    impl ExtensionHandler for AccessorRef {
+        fn name(&self) -> &str {
+            "VENDOR_accessor_ref"
+        }
+
+        fn rebuild(
+            &self,
+            owner: ExtensionOwner,
+            payload: &mut Value,
+            ctx: &mut ExtensionContext,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            if !matches!(owner, ExtensionOwner::Node(_) | ExtensionOwner::Root) {
+                return Err("unexpected owner".into());
+            }
+            if let Some(accessor) = payload["accessor"].as_u64() {
+                payload["accessor"] = ctx.copy_accessor(accessor as usize)?.into();
+            }
+            Ok(())
+        }
+    }
+
+    /// Handler that always fails
+    struct Failing;
+
+    impl ExtensionHandler for Failing {
+        fn name(&self) -> &str {
+            "VENDOR_accessor_ref"
+        }
+
+        fn rebuild(
+            &self,
+            _owner: ExtensionOwner,
+            _payload: &mut Value,
+            _ctx: &mut ExtensionContext,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            Err("no".into())
+        }
+    }
+
+    /// Two meshes whose first node references the second mesh's positions, plus an
+    /// unregistered extension on the same node and a root payload
+    fn vendor_glb() -> (Vec<u8>, usize) {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let positions = json.meshes[1].primitives[0].attributes
+            [&gltf::json::validation::Checked::Valid(gltf::Semantic::Positions)]
+            .value();
+        let mut doc = serde_json::to_value(&json).unwrap();
+        doc["nodes"][0]["extensions"] = json!({
+            "VENDOR_accessor_ref": {"accessor": positions},
+            "VENDOR_unknown": {"node": 1}
+        });
+        doc["extensions"] = json!({"VENDOR_accessor_ref": {"version": 2}});
+        doc["extensionsUsed"] = json!(["VENDOR_accessor_ref", "VENDOR_unknown"]);
+        let json: Root = serde_json::from_value(doc).unwrap();
+        (write_glb(&json, blob).unwrap(), positions)
+    }
+
+    #[test]
+    fn registered_payloads_are_rebuilt() {
+        let (glb, positions) = vendor_glb();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let mut registry = ExtensionRegistry::new();
+        registry.register(AccessorRef);
+        let out = Optimizer::new(&OptimizeOptions::default())
+            .extensions(&registry)
+            .run(&mut Cursor::new(glb))
+            .unwrap()
+            .glb;
+
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let doc = serde_json::to_value(&n_json).unwrap();
+        assert_eq!(
+            doc["extensions"]["VENDOR_accessor_ref"],
+            json!({"version": 2})
+        );
+        assert_eq!(doc["extensionsUsed"], json!(["VENDOR_accessor_ref"]));
+        let node = &doc["nodes"][0]["extensions"];
+        assert!(node.get("VENDOR_unknown").is_none());
+
+        let copied = node["VENDOR_accessor_ref"]["accessor"].as_u64().unwrap() as usize;
+        let source = get_position_data(&o_blob, &o_json, Index::new(positions as u32));
+        assert!(source.is_some());
+        assert_eq!(
+            get_position_data(&n_blob, &n_json, Index::new(copied as u32)),
+            source
+        );
+    }
+
+    #[test]
+    fn handler_errors_name_the_stage() {
+        let (glb, _) = vendor_glb();
+        let mut registry = ExtensionRegistry::new();
+        registry.register(Failing);
+        let err = Optimizer::new(&OptimizeOptions::default())
+            .extensions(&registry)
+            .run(&mut Cursor::new(glb))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            OptimizeError::Callback {
+                stage: Stage::ExtensionHandler,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn unregistered_payloads_are_dropped() {
+        let (glb, _) = vendor_glb();
+        let (mut json, _) = read_glb(&mut Cursor::new(glb)).unwrap();
+        retain_extensions(&mut json, &[]).unwrap();
+        let doc = serde_json::to_value(&json).unwrap();
+        assert!(doc["nodes"][0].get("extensions").is_none());
+    }
+}
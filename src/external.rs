@@ -0,0 +1,201 @@
+use gltf::json::{Index, Root, buffer::View, image::MimeType};
+
+use crate::{
+    blob::BlobWriter,
+    meshopt::{chunk_range, move_in_chunk},
+    streaming::refresh_manifest,
+    texture_table::refresh_texture_table,
+};
+
+/// Image written next to the GLB and referenced by URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExternalTexture {
+    /// Relative URI stored in the GLB, e.g. `model_tex0.ktx2`
+    pub uri: String,
+    pub data: Vec<u8>,
+}
+
+/// File extension for an encoded image
+fn file_extension(mime_type: Option<&MimeType>) -> &'static str {
+    match mime_type.map(|m| m.0.as_str()) {
+        Some("image/ktx2") => "ktx2",
+        Some("image/png") => "png",
+        Some("image/webp") => "webp",
+        _ => "jpg",
+    }
+}
+
+/// Short content hash for cache-busting file names (FNV-1a, stable across runs and builds)
+pub(crate) fn content_hash(data: &[u8]) -> String {
+    let hash = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:08x}", hash >> 32)
+}
+
+/// Move every embedded image out of the binary chunk
+///
+/// Returns the image bytes in image order and the re-laid-out blob; accessor views are
+/// renumbered to match. With `hash_uris` each name carries a hash of the image bytes.
+pub(crate) fn extract_images(
+    n_json: &mut Root,
+    n_blob: &[u8],
+    uri_prefix: &str,
+    hash_uris: bool,
+) -> (Vec<ExternalTexture>, Vec<u8>) {
+    let view_bytes = |json: &Root, idx: usize| {
+        json.buffer_views
+            .get(idx)
+            .and_then(|view| n_blob.get(chunk_range(view)))
+    };
+
+    let mut textures = Vec::new();
+    let mut removed = vec![false; n_json.buffer_views.len()];
+    for i in 0..n_json.images.len() {
+        let Some(idx_view) = n_json.images[i].buffer_view else {
+            continue;
+        };
+        let data = view_bytes(n_json, idx_view.value())
+            .unwrap_or_default()
+            .to_vec();
+        if let Some(flag) = removed.get_mut(idx_view.value()) {
+            *flag = true;
+        }
+
+        let image = &mut n_json.images[i];
+        let extension = file_extension(image.mime_type.as_ref());
+        let uri = match hash_uris {
+            true => format!("{uri_prefix}tex{i}.{}.{extension}", content_hash(&data)),
+            false => format!("{uri_prefix}tex{i}.{extension}"),
+        };
+        image.buffer_view = None;
+        image.uri = Some(uri.clone());
+        textures.push(ExternalTexture { uri, data });
+    }
+
+    // Copy the remaining views, recording where each one moved
+    let mut blob = BlobWriter::new();
+    let mut moved = vec![None; n_json.buffer_views.len()];
+    let mut views = Vec::new();
+    for (i, view) in n_json.buffer_views.iter().enumerate() {
+        if removed[i] {
+            continue;
+        }
+        let data = view_bytes(n_json, i).unwrap_or_default();
+
+        let mut view = view.clone();
+        move_in_chunk(&mut view, blob.append(data));
+        moved[i] = Some(Index::new(views.len() as u32));
+        views.push(view);
+    }
+    n_json.buffer_views = views;
+
+    let moved_view = |idx: Index<View>| moved.get(idx.value()).copied().flatten();
+    for acc in &mut n_json.accessors {
+        acc.buffer_view = acc.buffer_view.and_then(moved_view);
+        if let Some(sparse) = &mut acc.sparse {
+            sparse.indices.buffer_view =
+                moved_view(sparse.indices.buffer_view).unwrap_or(sparse.indices.buffer_view);
+            sparse.values.buffer_view =
+                moved_view(sparse.values.buffer_view).unwrap_or(sparse.values.buffer_view);
+        }
+    }
+    refresh_manifest(n_json);
+    refresh_texture_table(n_json, &blob);
+
+    (textures, blob.into_vec())
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{get_index_data, get_position_data},
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    fn textured_glb() -> Vec<u8> {
+        SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn images_move_out_of_the_glb() {
+        let options = OptimizeOptions {
+            image_format: Some(crate::opt::ImageFormat::Png),
+            ..Default::default()
+        };
+        let out = Optimizer::new(&options)
+            .external_textures("model_")
+            .run(&mut Cursor::new(textured_glb()))
+            .unwrap();
+        let uris: Vec<_> = out.textures.iter().map(|t| t.uri.as_str()).collect();
+        assert_eq!(uris, ["model_tex0.png", "model_tex1.png"]);
+        for texture in &out.textures {
+            assert_eq!(
+                image::guess_format(&texture.data).unwrap(),
+                image::ImageFormat::Png
+            );
+        }
+
+        let (json, blob) = read_glb(&mut Cursor::new(&out.glb)).unwrap();
+        for (image, texture) in json.images.iter().zip(&out.textures) {
+            assert!(image.buffer_view.is_none());
+            assert_eq!(image.uri.as_deref(), Some(texture.uri.as_str()));
+        }
+        let embedded = crate::opt::optimize_with(&mut Cursor::new(textured_glb()), &options);
+        let (embedded, _) = read_glb(&mut Cursor::new(embedded.unwrap())).unwrap();
+        assert_eq!(json.buffer_views.len(), embedded.buffer_views.len() - 2);
+
+        // Geometry still reads back after the views were renumbered
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(textured_glb())).unwrap();
+        for (o_mesh, n_mesh) in o_json.meshes.iter().zip(&json.meshes) {
+            let (o_p, n_p) = (&o_mesh.primitives[0], &n_mesh.primitives[0]);
+            assert_eq!(
+                get_index_data(&blob, &json, n_p.indices.unwrap()),
+                get_index_data(&o_blob, &o_json, o_p.indices.unwrap())
+            );
+            let position = gltf::json::validation::Checked::Valid(gltf::Semantic::Positions);
+            let positions = get_position_data(&blob, &json, n_p.attributes[&position]);
+            assert!(positions.is_some());
+            assert_eq!(
+                positions,
+                get_position_data(&o_blob, &o_json, o_p.attributes[&position])
+            );
+        }
+    }
+
+    #[test]
+    fn hashed_uris_follow_the_image_bytes() {
+        let options = OptimizeOptions {
+            image_format: Some(crate::opt::ImageFormat::Png),
+            content_hash_uris: true,
+            ..Default::default()
+        };
+        let externalize = || {
+            Optimizer::new(&options)
+                .external_textures("m_")
+                .run(&mut Cursor::new(textured_glb()))
+                .unwrap()
+        };
+        let out = externalize();
+        for (i, texture) in out.textures.iter().enumerate() {
+            let hash = content_hash(&texture.data);
+            assert_eq!(hash.len(), 8);
+            assert_eq!(texture.uri, format!("m_tex{i}.{hash}.png"));
+        }
+        assert_eq!(externalize(), out);
+        assert_ne!(content_hash(b"a"), content_hash(b"b"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Index, image::MimeType, mesh::Semantic, validation::Checked};
+
+use crate::{
+    decode::{sniff_format, sniffed_mime_type},
+    error::{AccessorProblem, OptimizeError},
+    glb::read_glb,
+    opt::{get_image_data, get_index_data, get_position_data},
+};
+
+/// Positions and triangle indices of one primitive, as stored in the GLB
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PrimitivePositions {
+    /// Dequantized POSITION values
+    pub positions: Vec<[f32; 3]>,
+    /// Index data widened to u32, `None` for non-indexed primitives
+    pub indices: Option<Vec<u32>>,
+}
+
+/// Copy the image bytes of texture `index` out of a GLB without optimizing it
+///
+/// The MIME type is the image's declared one, or the one its bytes look like when it has none.
+pub fn extract_texture<R: Read + Seek>(
+    reader: &mut R,
+    index: usize,
+) -> Result<(Vec<u8>, MimeType), OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let texture = o_json
+        .textures
+        .get(index)
+        .ok_or(OptimizeError::TextureOutOfBounds { texture: index })?;
+    let data = get_image_data(&o_blob, &o_json, Index::new(index as u32))
+        .ok_or(OptimizeError::MissingImageData { texture: index })?;
+
+    let declared = o_json
+        .images
+        .get(texture.source.value())
+        .and_then(|img| img.mime_type.clone());
+    let mime_type = match declared {
+        Some(mime_type) => mime_type,
+        None => MimeType(
+            sniffed_mime_type(data)
+                .ok_or(OptimizeError::UnsupportedImage {
+                    format: sniff_format(data),
+                })?
+                .to_string(),
+        ),
+    };
+    Ok((data.to_vec(), mime_type))
+}
+
+/// Read the positions and indices of every primitive of mesh `mesh_index` out of a GLB
+///
+/// Primitives without a POSITION accessor get empty positions.
+pub fn extract_mesh_positions<R: Read + Seek>(
+    reader: &mut R,
+    mesh_index: usize,
+) -> Result<Vec<PrimitivePositions>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let mesh = o_json
+        .meshes
+        .get(mesh_index)
+        .ok_or(OptimizeError::MissingObject {
+            kind: "mesh",
+            index: mesh_index,
+        })?;
+
+    mesh.primitives
+        .iter()
+        .map(|primitive| {
+            let positions = match primitive
+                .attributes
+                .get(&Checked::Valid(Semantic::Positions))
+            {
+                Some(&idx) => get_position_data(&o_blob, &o_json, idx).ok_or_else(|| {
+                    OptimizeError::Accessor {
+                        accessor: idx.value(),
+                        problem: AccessorProblem::Unreadable,
+                    }
+                })?,
+                None => Vec::new(),
+            };
+            let indices = match primitive.indices {
+                Some(idx) => Some(get_index_data(&o_blob, &o_json, idx).ok_or_else(|| {
+                    OptimizeError::Accessor {
+                        accessor: idx.value(),
+                        problem: AccessorProblem::Unreadable,
+                    }
+                })?),
+                None => None,
+            };
+            Ok(PrimitivePositions { positions, indices })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn texture_bytes_come_out_unchanged() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (data, mime_type) = extract_texture(&mut Cursor::new(&glb), 0).unwrap();
+        assert_eq!(mime_type.0, "image/png");
+        let (json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(Some(&data[..]), get_image_data(&blob, &json, Index::new(0)));
+
+        let err = extract_texture(&mut Cursor::new(&glb), 1).unwrap_err();
+        assert!(matches!(
+            err,
+            OptimizeError::TextureOutOfBounds { texture: 1 }
+        ));
+    }
+
+    #[test]
+    fn mesh_positions_come_out_per_primitive() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            primitives_per_mesh: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let primitives = extract_mesh_positions(&mut Cursor::new(&glb), 1).unwrap();
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(
+            primitives[1].indices.as_deref(),
+            Some(&[0, 1, 2, 0, 2, 3][..])
+        );
+        assert_eq!(
+            primitives[1].positions,
+            [
+                [2.0, 0.0, 1.0],
+                [3.0, 0.0, 1.0],
+                [3.0, 1.0, 1.0],
+                [2.0, 1.0, 1.0]
+            ]
+        );
+        let err = extract_mesh_positions(&mut Cursor::new(&glb), 2).unwrap_err();
+        assert!(matches!(
+            err,
+            OptimizeError::MissingObject {
+                kind: "mesh",
+                index: 2
+            }
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Extras, Root};
+use serde_json::Value;
+
+use crate::{
+    error::OptimizeError,
+    glb::{from_extras, read_glb},
+};
+
+/// Kind of glTF object whose `extras` a schema applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExtrasScope {
+    /// The document root
+    Root,
+    Scene,
+    Node,
+    Mesh,
+    Material,
+    Texture,
+    Image,
+    Camera,
+    Skin,
+    Animation,
+}
+
+/// Schema rule broken by an object's `extras`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtrasViolation {
+    pub scope: ExtrasScope,
+    /// Index of the object in its glTF array, 0 for [`ExtrasScope::Root`]
+    pub index: usize,
+    /// JSON pointer into the `extras` value, empty for the value itself
+    pub path: String,
+    pub message: String,
+}
+
+/// User-registered JSON schemas for `extras` conventions
+///
+/// Supports the commonly used subset of JSON Schema: `type`, `enum`, `const`, `required`,
+/// `properties`, `additionalProperties`, `items`, `minimum`/`maximum` (and exclusive forms),
+/// `minLength`/`maxLength` and `minItems`/`maxItems`. Objects without `extras` are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ExtrasSchemas {
+    schemas: Vec<(ExtrasScope, Value)>,
+}
+
+impl ExtrasSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a schema for every object of `scope`; several schemas per scope all apply
+    pub fn register(&mut self, scope: ExtrasScope, schema: Value) -> &mut Self {
+        self.schemas.push((scope, schema));
+        self
+    }
+
+    pub(crate) fn validate(&self, o_json: &Root) -> Vec<ExtrasViolation> {
+        let mut violations = Vec::new();
+
+        for (scope, schema) in &self.schemas {
+            let extras: Vec<&Extras> = match scope {
+                ExtrasScope::Root => vec![&o_json.extras],
+                ExtrasScope::Scene => o_json.scenes.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Node => o_json.nodes.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Mesh => o_json.meshes.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Material => o_json.materials.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Texture => o_json.textures.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Image => o_json.images.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Camera => o_json.cameras.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Skin => o_json.skins.iter().map(|o| &o.extras).collect(),
+                ExtrasScope::Animation => o_json.animations.iter().map(|o| &o.extras).collect(),
+            };
+
+            for (index, extras) in extras.into_iter().enumerate() {
+                let Some(value) = from_extras(extras) else {
+                    continue;
+                };
+                let mut errors = Vec::new();
+                check(schema, &value, String::new(), &mut errors);
+                violations.extend(errors.into_iter().map(|(path, message)| ExtrasViolation {
+                    scope: *scope,
+                    index,
+                    path,
+                    message,
+                }));
+            }
+        }
+
+        violations
+    }
+}
+
+fn type_matches(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Validate `value` against `schema`, collecting `(path, message)` pairs
+fn check(schema: &Value, value: &Value, path: String, errors: &mut Vec<(String, String)>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push((path, "value is not allowed".to_string()));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| type_matches(name, value)) {
+            errors.push((path, format!("expected type {}", names.join(" or "))));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(value)
+    {
+        errors.push((
+            path.clone(),
+            format!("{value} is not one of the allowed values"),
+        ));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push((path.clone(), format!("expected {expected}")));
+    }
+
+    if let Some(n) = value.as_f64() {
+        let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+        if let Some(min) = bound("minimum")
+            && n < min
+        {
+            errors.push((path.clone(), format!("{n} is less than {min}")));
+        }
+        if let Some(max) = bound("maximum")
+            && n > max
+        {
+            errors.push((path.clone(), format!("{n} is greater than {max}")));
+        }
+        if let Some(min) = bound("exclusiveMinimum")
+            && n <= min
+        {
+            errors.push((path.clone(), format!("{n} is not greater than {min}")));
+        }
+        if let Some(max) = bound("exclusiveMaximum")
+            && n >= max
+        {
+            errors.push((path.clone(), format!("{n} is not less than {max}")));
+        }
+    }
+
+    let limit = |key: &str| schema.get(key).and_then(Value::as_u64).map(|l| l as usize);
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count();
+        if let Some(min) = limit("minLength")
+            && len < min
+        {
+            errors.push((path.clone(), format!("string shorter than {min}")));
+        }
+        if let Some(max) = limit("maxLength")
+            && len > max
+        {
+            errors.push((path.clone(), format!("string longer than {max}")));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = limit("minItems")
+            && items.len() < min
+        {
+            errors.push((path.clone(), format!("fewer than {min} items")));
+        }
+        if let Some(max) = limit("maxItems")
+            && items.len() > max
+        {
+            errors.push((path.clone(), format!("more than {max} items")));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, format!("{path}/{i}"), errors);
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for key in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(key) {
+                errors.push((path.clone(), format!("missing required property \"{key}\"")));
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, child) in object {
+            let child_path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => check(child_schema, child, child_path, errors),
+                None => {
+                    if let Some(additional) = schema.get("additionalProperties") {
+                        check(additional, child, child_path, errors);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Validate every registered `extras` convention in a GLB
+pub fn validate_extras<R: Read + Seek>(
+    reader: &mut R,
+    schemas: &ExtrasSchemas,
+) -> Result<Vec<ExtrasViolation>, OptimizeError> {
+    let (o_json, _) = read_glb(reader)?;
+    Ok(schemas.validate(&o_json))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        glb::{to_extras, write_glb},
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    fn tagged_glb() -> Vec<u8> {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.nodes[0].extras = to_extras(json!({"lod": 2, "tags": ["prop", 7]}));
+        json.nodes[1].extras = to_extras(json!({"lod": -1, "owner": "art"}));
+        write_glb(&json, blob).unwrap()
+    }
+
+    fn node_schema() -> ExtrasSchemas {
+        let mut schemas = ExtrasSchemas::new();
+        schemas.register(
+            ExtrasScope::Node,
+            json!({
+                "type": "object",
+                "required": ["lod", "tags"],
+                "properties": {
+                    "lod": {"type": "integer", "minimum": 0},
+                    "tags": {"type": "array", "items": {"type": "string"}}
+                },
+                "additionalProperties": false
+            }),
+        );
+        schemas
+    }
+
+    #[test]
+    fn violations_point_into_the_extras() {
+        let violations = validate_extras(&mut Cursor::new(tagged_glb()), &node_schema()).unwrap();
+        let found: Vec<_> = violations
+            .iter()
+            .map(|v| (v.index, v.path.as_str(), v.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (0, "/tags/1", "expected type string"),
+                (1, "", "missing required property \"tags\""),
+                (1, "/lod", "-1 is less than 0"),
+                (1, "/owner", "value is not allowed"),
+            ]
+        );
+        assert!(violations.iter().all(|v| v.scope == ExtrasScope::Node));
+    }
+
+    #[test]
+    fn objects_without_extras_are_skipped() {
+        let mut schemas = ExtrasSchemas::new();
+        schemas.register(ExtrasScope::Mesh, json!(false));
+        schemas.register(ExtrasScope::Root, json!({"required": ["id"]}));
+        let violations = validate_extras(&mut Cursor::new(tagged_glb()), &schemas).unwrap();
+        assert!(violations.is_empty());
+    }
+
+    #[test]
+    fn scalar_rules_are_checked() {
+        let schema = json!({
+            "enum": ["a", "bcdef"],
+            "maxLength": 3,
+            "exclusiveMaximum": 1
+        });
+        let mut errors = Vec::new();
+        check(&schema, &json!("bcdef"), String::new(), &mut errors);
+        assert_eq!(
+            errors,
+            [(String::new(), "string longer than 3".to_string())]
+        );
+
+        errors.clear();
+        check(&schema, &json!(1), String::new(), &mut errors);
+        assert_eq!(errors.len(), 2);
+
+        errors.clear();
+        check(
+            &json!({"const": [1, 2], "minItems": 3}),
+            &json!([1, 2]),
+            "/x".into(),
+            &mut errors,
+        );
+        assert_eq!(
+            errors,
+            [("/x".to_string(), "fewer than 3 items".to_string())]
+        );
+    }
+
+    #[test]
+    fn extras_are_carried_through_optimization() {
+        let options = OptimizeOptions::default();
+        let schemas = node_schema();
+        let optimized = Optimizer::new(&options)
+            .extras_schemas(&schemas)
+            .run(&mut Cursor::new(tagged_glb()))
+            .unwrap();
+        assert_eq!(optimized.extras_violations.len(), 4);
+        let (json, _) = read_glb(&mut Cursor::new(optimized.glb)).unwrap();
+        assert_eq!(
+            from_extras(&json.nodes[1].extras),
+            Some(json!({"lod": -1, "owner": "art"}))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{
+    opt::{ImageFormat, TextureType},
+    transcode::TextureCompressionProfile,
+};
+
+/// Encoding a fallback chain retries a texture with
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum TextureEncoding {
+    /// KTX2 with Basis Universal, compressed with this profile
+    Ktx2(TextureCompressionProfile),
+    /// JPEG, PNG or WebP; [`ImageFormat::Ktx2`] encodes with the slot's
+    /// [`compression_profiles`](crate::OptimizeOptions::compression_profiles) entry
+    Image(ImageFormat),
+}
+
+/// Encodings tried in order, per material slot, when the built-in encoder fails on a texture
+///
+/// Each texture is retried on its own; the first encoding that succeeds is used and every
+/// step taken is reported as a [`BestEffortIssue`](crate::BestEffortIssue). Empty chains, the
+/// default, fail the texture as before. Missing cargo features are not retried.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextureFallbacks {
+    pub base_color: Vec<TextureEncoding>,
+    pub normal: Vec<TextureEncoding>,
+    pub metallic_roughness: Vec<TextureEncoding>,
+    pub occlusion: Vec<TextureEncoding>,
+    pub emissive: Vec<TextureEncoding>,
+}
+
+impl TextureFallbacks {
+    /// The same chain for every slot
+    pub fn all(chain: &[TextureEncoding]) -> Self {
+        TextureFallbacks {
+            base_color: chain.to_vec(),
+            normal: chain.to_vec(),
+            metallic_roughness: chain.to_vec(),
+            occlusion: chain.to_vec(),
+            emissive: chain.to_vec(),
+        }
+    }
+
+    /// Chain for a texture slot
+    pub fn for_type(&self, texture_type: TextureType) -> &[TextureEncoding] {
+        match texture_type {
+            TextureType::BaseColor => &self.base_color,
+            TextureType::Normal => &self.normal,
+            TextureType::MetallicRoughness => &self.metallic_roughness,
+            TextureType::Occlusion => &self.occlusion,
+            TextureType::Emissive => &self.emissive,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        best_effort::BestEffortFallback, glb::read_glb, inject::replace_texture,
+        optimizer::Optimizer, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn failed_encodes_step_through_the_slot_chain() {
+        // JPEG can't hold a side over 65535, PNG can
+        let mut png = Vec::new();
+        image::RgbImage::from_pixel(70_000, 2, image::Rgb([200, 100, 50]))
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(glb), 0, &png, None).unwrap();
+
+        let options = OptimizeOptions {
+            texture_size: 131_072,
+            image_format: Some(ImageFormat::Jpeg),
+            ..Default::default()
+        };
+        assert!(
+            Optimizer::new(&options)
+                .run(&mut Cursor::new(&glb))
+                .is_err()
+        );
+
+        let options = OptimizeOptions {
+            texture_fallbacks: TextureFallbacks {
+                base_color: vec![
+                    TextureEncoding::Image(ImageFormat::Jpeg),
+                    TextureEncoding::Image(ImageFormat::Png),
+                ],
+                ..Default::default()
+            },
+            ..options
+        };
+        let optimized = Optimizer::new(&options)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let steps: Vec<_> = optimized
+            .issues
+            .iter()
+            .map(|issue| (issue.texture, issue.fallback))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (
+                    Some(0),
+                    BestEffortFallback::EncoderFallback(TextureEncoding::Image(ImageFormat::Jpeg))
+                ),
+                (
+                    Some(0),
+                    BestEffortFallback::EncoderFallback(TextureEncoding::Image(ImageFormat::Png))
+                ),
+            ]
+        );
+        assert!(optimized.issues.iter().all(|issue| !issue.error.is_empty()));
+
+        let (json, _) = read_glb(&mut Cursor::new(optimized.glb)).unwrap();
+        let mime_types: Vec<_> = json
+            .images
+            .iter()
+            .filter_map(|img| img.mime_type.as_ref().map(|m| m.0.as_str()))
+            .collect();
+        assert_eq!(mime_types, ["image/png"]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{error::Error, fmt};
+
+/// Error for work that needs a cargo feature this build of the crate leaves out
+///
+/// Returned as [`OptimizeError::FeatureNotEnabled`](crate::OptimizeError::FeatureNotEnabled);
+/// [`OptimizeError::feature_not_enabled`](crate::OptimizeError::feature_not_enabled)
+/// also finds it under texture and decode context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureNotEnabled {
+    /// Cargo feature to enable, e.g. `ktx2`
+    pub feature: &'static str,
+}
+
+impl fmt::Display for FeatureNotEnabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gltf_opt was built without the `{}` feature",
+            self.feature
+        )
+    }
+}
+
+impl Error for FeatureNotEnabled {}
+
+/// Feature needed to decode an image in a format sniffed from its bytes, when it is disabled
+pub(crate) fn missing_decoder(format: &str) -> Option<FeatureNotEnabled> {
+    let feature = match format {
+        "PNG" if !cfg!(feature = "png") => "png",
+        "JPEG" if !cfg!(feature = "jpeg") => "jpeg",
+        "WebP" if !cfg!(feature = "webp") => "webp",
+        "GIF" | "BMP" | "TIFF" | "Radiance HDR" | "OpenEXR" | "AVIF" | "DDS"
+            if !cfg!(feature = "extra-image-formats") =>
+        {
+            "extra-image-formats"
+        }
+        _ => return None,
+    };
+    Some(FeatureNotEnabled { feature })
+}
+
+#[cfg(all(test, feature = "png", not(feature = "ktx2")))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn ktx2_output_needs_feature() {
+        let options = OptimizeOptions {
+            convert_to_ktx2: true,
+            ..Default::default()
+        };
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let e = optimize_with(&mut Cursor::new(glb), &options).unwrap_err();
+        assert_eq!(
+            e.feature_not_enabled(),
+            Some(&FeatureNotEnabled { feature: "ktx2" })
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    error::Error,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use image::RgbaImage;
+
+use crate::{encoder::EncodedTexture, opt::TextureType};
+
+/// Step run on every texture the optimizer encodes, between resizing and embedding
+///
+/// Both stages default to leaving the texture alone, so implement only the one needed: pixels
+/// for denoisers or watermarks, encoded bytes for tools like oxipng. Textures copied as they
+/// are (verbatim slots, [`ReencodeSkip`](crate::ReencodeSkip) matches) are not seen.
+pub trait ExternalTextureFilter: Send + Sync {
+    /// Replace the RGBA8 pixels of a texture already at its output size, before encoding
+    ///
+    /// Returning `None` lets the encoder work from the source pixels as usual. Replaced
+    /// pixels are encoded at their own size, without linear-light resizing or sharpening.
+    fn filter_pixels(
+        &self,
+        image: &RgbaImage,
+        texture_type: TextureType,
+    ) -> Result<Option<RgbaImage>, Box<dyn Error + Send + Sync>> {
+        let _ = (image, texture_type);
+        Ok(None)
+    }
+
+    /// Replace an encoded texture before it is embedded
+    fn filter_encoded(
+        &self,
+        texture: EncodedTexture,
+        texture_type: TextureType,
+    ) -> Result<EncodedTexture, Box<dyn Error + Send + Sync>> {
+        let _ = texture_type;
+        Ok(texture)
+    }
+}
+
+/// [`ExternalTextureFilter`] piping encoded textures through an external program
+///
+/// The program gets the texture on stdin and must write the replacement to stdout, e.g.
+/// `oxipng --strip safe -` or `magick - -quality 85 jpg:-`. It runs in the current directory
+/// with the optimizer's environment, and fails the texture when it exits unsuccessfully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandFilter<'a> {
+    pub program: &'a str,
+    pub args: &'a [&'a str],
+    /// MIME types passed to the program, others are left alone; empty for all
+    pub mime_types: &'a [&'a str],
+}
+
+impl CommandFilter<'_> {
+    /// Run the program on `data`, returning its standard output
+    fn run(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let mut child = Command::new(self.program)
+            .args(self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run {}: {e}", self.program))?;
+        let mut stdin = child.stdin.take().ok_or("stdin was not piped")?;
+        // Written from another thread, so a program streaming output can't block on a full pipe
+        let output = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || stdin.write_all(data));
+            let output = child.wait_with_output();
+            // A program may exit without reading all of its input; its status tells
+            let _ = writer.join();
+            output
+        })?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl ExternalTextureFilter for CommandFilter<'_> {
+    fn filter_encoded(
+        &self,
+        texture: EncodedTexture,
+        _texture_type: TextureType,
+    ) -> Result<EncodedTexture, Box<dyn Error + Send + Sync>> {
+        if !self.mime_types.is_empty() && !self.mime_types.contains(&texture.mime_type.as_str()) {
+            return Ok(texture);
+        }
+        let data = self.run(&texture.data)?;
+        if data.is_empty() {
+            return Err(format!("{} wrote no output", self.program).into());
+        }
+        Ok(EncodedTexture { data, ..texture })
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        error::{OptimizeError, Stage},
+        glb::read_glb,
+        opt::{ImageFormat, get_image_data},
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn texture_filters_see_pixels_and_encoded_bytes() {
+        #[derive(Default)]
+        struct Recording {
+            encoded: Mutex<Vec<(String, TextureType)>>,
+        }
+        impl ExternalTextureFilter for Recording {
+            fn filter_pixels(
+                &self,
+                image: &image::RgbaImage,
+                _texture_type: TextureType,
+            ) -> Result<Option<image::RgbaImage>, Box<dyn std::error::Error + Send + Sync>>
+            {
+                let mut image = image.clone();
+                image.pixels_mut().for_each(|p| p.0 = [255, 0, 0, 255]);
+                Ok(Some(image))
+            }
+
+            fn filter_encoded(
+                &self,
+                texture: EncodedTexture,
+                texture_type: TextureType,
+            ) -> Result<EncodedTexture, Box<dyn std::error::Error + Send + Sync>> {
+                let entry = (texture.mime_type.clone(), texture_type);
+                self.encoded.lock().unwrap().push(entry);
+                Ok(texture)
+            }
+        }
+
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        let filter = Recording::default();
+        let out = Optimizer::new(&options)
+            .texture_filter(&filter)
+            .run(&mut Cursor::new(&glb))
+            .unwrap()
+            .glb;
+        let encoded = filter.encoded.into_inner().unwrap();
+        let png = ("image/png".to_string(), TextureType::BaseColor);
+        assert_eq!(encoded, [png.clone(), png]);
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        for texture in 0..n_json.textures.len() {
+            let data = get_image_data(&n_blob, &n_json, Index::new(texture as u32)).unwrap();
+            let img = image::load_from_memory(data).unwrap().to_rgba8();
+            assert!(img.pixels().all(|p| p.0 == [255, 0, 0, 255]));
+        }
+
+        #[cfg(unix)]
+        {
+            use crate::opt::optimize_with;
+
+            let plain = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let cat = CommandFilter {
+                program: "cat",
+                args: &[],
+                mime_types: &["image/png"],
+            };
+            let piped = Optimizer::new(&options)
+                .texture_filter(&cat)
+                .run(&mut Cursor::new(&glb));
+            assert_eq!(piped.unwrap().glb, plain);
+
+            let failing = CommandFilter {
+                program: "false",
+                ..cat
+            };
+            let e = Optimizer::new(&options)
+                .texture_filter(&failing)
+                .run(&mut Cursor::new(&glb));
+            let Err(OptimizeError::Texture { source, .. }) = e else {
+                panic!("unexpected result {e:?}");
+            };
+            assert!(matches!(
+                *source,
+                OptimizeError::Callback {
+                    stage: Stage::TextureFilter,
+                    ..
+                }
+            ));
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::error::Error;
+
+use gltf::json::{
+    Accessor, Root,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+use serde_json::Value;
+
+use crate::{
+    blob::BlobWriter,
+    error::{AccessorProblem, OptimizeError, Stage},
+    glb::packed_accessor_data,
+};
+
+/// One accessor of a primitive handed to a [`GeometryCodec`]
+#[derive(Debug, Clone)]
+pub struct GeometryAttribute<'a> {
+    /// `None` for the index accessor
+    pub semantic: Option<Semantic>,
+    pub accessor: &'a Accessor,
+    /// Tightly packed element data, pivot offset already applied to positions
+    pub data: Vec<u8>,
+}
+
+/// Vertex and index data of one rebuilt primitive
+#[derive(Debug, Clone)]
+pub struct PrimitiveGeometry<'a> {
+    pub mode: Mode,
+    pub indices: Option<GeometryAttribute<'a>>,
+    pub attributes: Vec<GeometryAttribute<'a>>,
+}
+
+/// Result of encoding one primitive
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodedGeometry {
+    /// Bytes stored in a new buffer view
+    pub data: Vec<u8>,
+    /// Written to `primitive.extensions[codec.extension()]`, with `bufferView` set by the crate
+    pub payload: Value,
+    /// Keep the uncompressed buffer views for loaders without the extension
+    ///
+    /// Without a fallback the accessors keep their type, count and bounds but lose their
+    /// buffer view, and the extension becomes required.
+    pub keep_fallback: bool,
+}
+
+/// Replacement for how vertex and index buffers are written (custom meshlets, engine formats)
+///
+/// The optimizer still copies accessors, applies the pivot offset and fixes every index; the
+/// codec only decides which bytes end up in the buffer.
+pub trait GeometryCodec: Send + Sync {
+    /// Extension name stored on encoded primitives
+    fn extension(&self) -> &str;
+
+    /// Encode one primitive, or return `None` to keep it as plain accessors
+    fn encode(
+        &self,
+        geometry: &PrimitiveGeometry,
+    ) -> Result<Option<EncodedGeometry>, Box<dyn Error + Send + Sync>>;
+}
+
+/// Run the codec on a primitive whose accessors were just appended
+///
+/// `mark` is the blob length and buffer view count from before the primitive was copied, so
+/// the fallback data can be dropped again.
+pub(crate) fn encode_geometry(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    n_p: &mut Primitive,
+    mark: (usize, usize),
+    codec: &dyn GeometryCodec,
+) -> Result<(), OptimizeError> {
+    let attribute = |semantic: Option<Semantic>, idx: gltf::json::Index<Accessor>| {
+        let accessor = n_json.accessors.get(idx.value())?;
+        Some(GeometryAttribute {
+            semantic,
+            accessor,
+            data: packed_accessor_data(n_blob, n_json, idx)?,
+        })
+    };
+
+    let unreadable = |idx: gltf::json::Index<Accessor>| OptimizeError::Accessor {
+        accessor: idx.value(),
+        problem: AccessorProblem::Unreadable,
+    };
+    let indices = match n_p.indices {
+        Some(idx) => Some(attribute(None, idx).ok_or_else(|| unreadable(idx))?),
+        None => None,
+    };
+    let attributes = n_p
+        .attributes
+        .iter()
+        .filter_map(|(semantic, idx)| match semantic {
+            Checked::Valid(semantic) => Some((semantic.clone(), *idx)),
+            Checked::Invalid => None,
+        })
+        .map(|(semantic, idx)| attribute(Some(semantic), idx).ok_or_else(|| unreadable(idx)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mode = match n_p.mode {
+        Checked::Valid(mode) => mode,
+        Checked::Invalid => Mode::Triangles,
+    };
+
+    let Some(encoded) = codec
+        .encode(&PrimitiveGeometry {
+            mode,
+            indices,
+            attributes,
+        })
+        .map_err(|source| OptimizeError::Callback {
+            stage: Stage::GeometryCodec,
+            source,
+        })?
+    else {
+        return Ok(());
+    };
+
+    let accessors: Vec<usize> = n_p
+        .indices
+        .iter()
+        .chain(n_p.attributes.values())
+        .map(|idx| idx.value())
+        .collect();
+    if !encoded.keep_fallback {
+        n_blob.truncate(mark.0);
+        n_json.buffer_views.truncate(mark.1);
+        for i in &accessors {
+            if let Some(acc) = n_json.accessors.get_mut(*i) {
+                acc.buffer_view = None;
+                acc.byte_offset = None;
+            }
+        }
+    }
+
+    let view = n_blob.push_view(n_json, &encoded.data, None);
+    let mut payload = encoded.payload;
+    if let Value::Object(object) = &mut payload {
+        object.insert("bufferView".to_string(), view.value().into());
+    }
+
+    let name = codec.extension().to_string();
+    n_p.extensions
+        .get_or_insert_with(Default::default)
+        .others
+        .insert(name.clone(), payload);
+
+    if !n_json.extensions_used.contains(&name) {
+        n_json.extensions_used.push(name.clone());
+    }
+    if !encoded.keep_fallback && !n_json.extensions_required.contains(&name) {
+        n_json.extensions_required.push(name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::Mutex};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        glb::read_glb, opt::get_index_data, optimizer::Optimizer, options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    /// Concatenates indices and attributes, recording how many attributes each primitive had
+    struct Concat {
+        keep_fallback: bool,
+        attributes: Mutex<Vec<usize>>,
+    }
+
+    impl Concat {
+        fn new(keep_fallback: bool) -> Self {
+            Self {
+                keep_fallback,
+                attributes: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl GeometryCodec for Concat {
+        fn extension(&self) -> &str {
+            "VENDOR_concat"
+        }
+
+        fn encode(
+            &self,
+            geometry: &PrimitiveGeometry,
+        ) -> Result<Option<EncodedGeometry>, Box<dyn Error + Send + Sync>> {
+            let indices = geometry.indices.as_ref().ok_or("no indices")?;
+            self.attributes
+                .lock()
+                .unwrap()
+                .push(geometry.attributes.len());
+            let mut data = indices.data.clone();
+            for attribute in &geometry.attributes {
+                data.extend(&attribute.data);
+            }
+            Ok(Some(EncodedGeometry {
+                data,
+                payload: json!({"indices": indices.data.len()}),
+                keep_fallback: self.keep_fallback,
+            }))
+        }
+    }
+
+    /// Leaves every primitive alone
+    struct Decline;
+
+    impl GeometryCodec for Decline {
+        fn extension(&self) -> &str {
+            "VENDOR_decline"
+        }
+
+        fn encode(
+            &self,
+            _geometry: &PrimitiveGeometry,
+        ) -> Result<Option<EncodedGeometry>, Box<dyn Error + Send + Sync>> {
+            Ok(None)
+        }
+    }
+
+    fn quad() -> Vec<u8> {
+        SyntheticGlb::default().build().unwrap()
+    }
+
+    #[test]
+    fn encoded_primitives_drop_their_fallback() {
+        let codec = Concat::new(false);
+        let out = Optimizer::new(&OptimizeOptions::default())
+            .geometry_codec(&codec)
+            .run(&mut Cursor::new(quad()))
+            .unwrap()
+            .glb;
+        let attributes = codec.attributes.into_inner().unwrap();
+
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(json.extensions_used, ["VENDOR_concat"]);
+        assert_eq!(json.extensions_required, ["VENDOR_concat"]);
+        assert!(json.accessors.iter().all(|a| a.buffer_view.is_none()));
+        assert_eq!(json.buffer_views.len(), 1);
+
+        let primitive = &json.meshes[0].primitives[0];
+        assert_eq!(primitive.attributes.len(), attributes[0]);
+        let payload = &primitive.extensions.as_ref().unwrap().others["VENDOR_concat"];
+        assert_eq!(payload, &json!({"indices": 12, "bufferView": 0}));
+        assert_eq!(
+            &blob[..12],
+            bytemuck::cast_slice::<u16, u8>(&[0, 1, 2, 0, 2, 3])
+        );
+    }
+
+    #[test]
+    fn fallbacks_stay_readable() {
+        let out = Optimizer::new(&OptimizeOptions::default())
+            .geometry_codec(&Concat::new(true))
+            .run(&mut Cursor::new(quad()))
+            .unwrap()
+            .glb;
+
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(json.extensions_used, ["VENDOR_concat"]);
+        assert!(json.extensions_required.is_empty());
+        let indices = json.meshes[0].primitives[0].indices.unwrap();
+        assert_eq!(
+            get_index_data(&blob, &json, indices),
+            Some(vec![0, 1, 2, 0, 2, 3])
+        );
+    }
+
+    #[test]
+    fn declined_primitives_are_unchanged() {
+        let out = Optimizer::new(&OptimizeOptions::default())
+            .geometry_codec(&Decline)
+            .run(&mut Cursor::new(quad()))
+            .unwrap()
+            .glb;
+        let plain =
+            crate::opt::optimize_with(&mut Cursor::new(quad()), &OptimizeOptions::default())
+                .unwrap();
+        assert_eq!(out, plain);
+    }
+}
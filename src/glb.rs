@@ -0,0 +1,593 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{
+    Accessor, Index, Root,
+    accessor::{ComponentType, GenericComponentType, Type},
+    buffer::{Stride, Target},
+    validation::Checked,
+};
+
+use crate::blob::BlobWriter;
+use crate::convert::decode_component;
+use crate::error::OptimizeError;
+use crate::legacy::parse_error;
+use crate::positions::vec3_bounds;
+use crate::writer::GlbWriter;
+
+/// Load a GLB from a reader into an owned JSON root and BIN chunk
+///
+/// Validation is skipped so outputs using extensions unknown to `gltf` (e.g. `KHR_texture_basisu`
+/// in `extensionsRequired`) can be fed back into later passes. glTF 1.x input fails with
+/// [`OptimizeError::UnsupportedVersion`].
+pub(crate) fn read_glb<R: Read + Seek>(reader: &mut R) -> Result<(Root, Vec<u8>), OptimizeError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let data =
+        gltf::Gltf::from_slice_without_validation(&bytes).map_err(|e| parse_error(&bytes, e))?;
+    let blob = data.blob.unwrap_or_default();
+    Ok((data.document.into_json(), blob))
+}
+
+/// Serialize a JSON root and BIN chunk into GLB bytes, fixing up the buffer length
+pub(crate) fn write_glb(
+    n_json: &Root,
+    n_blob: impl Into<Vec<u8>>,
+) -> Result<Vec<u8>, OptimizeError> {
+    GlbWriter::from_bin(n_blob.into()).write(n_json)
+}
+
+/// Append tightly packed vec3 positions as a new accessor with min/max bounds
+pub(crate) fn push_positions(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    positions: &[[f32; 3]],
+) -> Index<Accessor> {
+    let bounds = vec3_bounds(positions.as_flattened(), 3);
+    let bytes: Vec<u8> = positions
+        .as_flattened()
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ArrayBuffer));
+
+    n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        count: positions.len().into(),
+        component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+        extensions: None,
+        extras: Default::default(),
+        type_: Checked::Valid(Type::Vec3),
+        min: bounds.map(|(min, _)| min.to_vec().into()),
+        max: bounds.map(|(_, max)| max.to_vec().into()),
+        name: None,
+        normalized: false,
+        sparse: None,
+    })
+}
+
+/// Append a tightly packed float vertex attribute (VEC2/VEC3/VEC4) as a new accessor
+pub(crate) fn push_attribute(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    values: &[f32],
+    type_: Type,
+) -> Index<Accessor> {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ArrayBuffer));
+
+    n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        count: (values.len() / type_.multiplicity()).into(),
+        component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+        extensions: None,
+        extras: Default::default(),
+        type_: Checked::Valid(type_),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    })
+}
+
+/// Append triangle indices as a new accessor, using u16 when the range allows it
+pub(crate) fn push_indices(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    indices: &[u32],
+) -> Index<Accessor> {
+    let use_u16 = indices.iter().all(|&i| i <= u16::MAX as u32);
+
+    let bytes: Vec<u8> = if use_u16 {
+        indices
+            .iter()
+            .flat_map(|&i| (i as u16).to_le_bytes())
+            .collect()
+    } else {
+        indices.iter().flat_map(|&i| i.to_le_bytes()).collect()
+    };
+
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ElementArrayBuffer));
+
+    n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        count: indices.len().into(),
+        component_type: Checked::Valid(GenericComponentType(if use_u16 {
+            ComponentType::U16
+        } else {
+            ComponentType::U32
+        })),
+        extensions: None,
+        extras: Default::default(),
+        type_: Checked::Valid(Type::Scalar),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    })
+}
+
+/// Build an `extras` payload from a JSON value
+pub(crate) fn to_extras(value: serde_json::Value) -> gltf::json::Extras {
+    serde_json::value::to_raw_value(&value).ok()
+}
+
+/// Parse an `extras` payload, `None` when absent or not valid JSON
+pub(crate) fn from_extras(extras: &gltf::json::Extras) -> Option<serde_json::Value> {
+    extras
+        .as_ref()
+        .and_then(|raw| serde_json::from_str(raw.get()).ok())
+}
+
+/// Insert `key` into an object-shaped `extras`, keeping any existing entries
+pub(crate) fn merge_extras(
+    extras: &gltf::json::Extras,
+    key: &str,
+    value: serde_json::Value,
+) -> gltf::json::Extras {
+    let mut map = from_extras(extras)
+        .and_then(|v| match v {
+            serde_json::Value::Object(map) => Some(map),
+            _ => None,
+        })
+        .unwrap_or_default();
+    map.insert(key.to_string(), value);
+    to_extras(serde_json::Value::Object(map))
+}
+
+/// Bytes of one accessor element (e.g. 12 for a float VEC3)
+pub(crate) fn element_size(accessor: &Accessor) -> Option<usize> {
+    match (accessor.component_type, accessor.type_) {
+        (Checked::Valid(ct), Checked::Valid(ty)) => Some(ct.0.size() * ty.multiplicity()),
+        _ => None,
+    }
+}
+
+/// Whether accessor `index` is a mesh attribute or morph target of some primitive
+fn is_vertex_attribute(json: &Root, index: usize) -> bool {
+    json.meshes.iter().flat_map(|m| &m.primitives).any(|p| {
+        p.attributes.values().any(|a| a.value() == index)
+            || p.targets.iter().flatten().any(|t| {
+                [t.positions, t.normals, t.tangents]
+                    .into_iter()
+                    .flatten()
+                    .any(|a| a.value() == index)
+            })
+    })
+}
+
+/// Distance in bytes between consecutive elements of accessor `index`
+///
+/// Uses `byteStride` when present, rejecting strides shorter than an element. Without it the
+/// data is tightly packed, as accessors sharing a view (animation key times and values,
+/// indices of several primitives) rely on. Some exporters pad every vertex anyway, so a
+/// vertex attribute alone in a view holding exactly `count` evenly padded elements is read
+/// at that padded stride instead.
+pub(crate) fn accessor_stride(json: &Root, index: Index<Accessor>) -> Option<usize> {
+    let accessor = json.accessors.get(index.value())?;
+    let view = json.buffer_views.get(accessor.buffer_view?.value())?;
+    let element_size = element_size(accessor)?;
+    if let Some(stride) = view.byte_stride {
+        return (stride.0 >= element_size).then_some(stride.0);
+    }
+
+    let count = accessor.count.0 as usize;
+    let available = (view.byte_length.0 as usize)
+        .saturating_sub(accessor.byte_offset.map(|o| o.0 as usize).unwrap_or(0));
+    let padded = available / count.max(1);
+    let evenly_padded = count > 1
+        && padded > element_size
+        && padded.is_multiple_of(4)
+        && padded * count == available;
+    let alone = || {
+        json.accessors
+            .iter()
+            .filter(|a| a.buffer_view == accessor.buffer_view)
+            .count()
+            == 1
+    };
+    if evenly_padded
+        && view.target != Some(Checked::Valid(Target::ElementArrayBuffer))
+        && alone()
+        && is_vertex_attribute(json, index.value())
+    {
+        Some(padded)
+    } else {
+        Some(element_size)
+    }
+}
+
+/// Copy an accessor's elements out of the blob without stride padding
+pub(crate) fn packed_accessor_data(
+    blob: &[u8],
+    json: &Root,
+    index: Index<Accessor>,
+) -> Option<Vec<u8>> {
+    let accessor = json.accessors.get(index.value())?;
+    let view = json.buffer_views.get(accessor.buffer_view?.value())?;
+    let element_size = element_size(accessor)?;
+    let stride = accessor_stride(json, index)?;
+    let start = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0)
+        + accessor.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+
+    // The count comes from the JSON, so check the view and blob hold that many elements
+    // before allocating for them
+    let count = accessor.count.0 as usize;
+    if count > 0 {
+        let len = (count - 1).checked_mul(stride)?.checked_add(element_size)?;
+        let in_view = accessor
+            .byte_offset
+            .map_or(0, |o| o.0 as usize)
+            .checked_add(len)?;
+        if in_view > view.byte_length.0 as usize || start.checked_add(len)? > blob.len() {
+            return None;
+        }
+    }
+    let mut data = Vec::with_capacity(count * element_size);
+    for i in 0..count {
+        let at = start + i * stride;
+        data.extend_from_slice(blob.get(at..at + element_size)?);
+    }
+    Some(data)
+}
+
+/// Copy the elements `vertices` of an accessor into a new vertex accessor
+///
+/// Elements are padded to 4 bytes as vertex attributes require, and `min`/`max` are
+/// recomputed when the source had them. Sparse accessors are not supported.
+pub(crate) fn gather_accessor(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    index: Index<Accessor>,
+    vertices: &[u32],
+) -> Option<Index<Accessor>> {
+    gather_accessors(o_blob, o_json, n_blob, n_json, &[(index, vertices)])
+}
+
+/// Copy the elements `vertices` of each accessor in turn into one new vertex accessor
+///
+/// Like [`gather_accessor`]; every accessor must have the component type, type and
+/// normalization of the first, whose other properties the result takes.
+pub(crate) fn gather_accessors(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    parts: &[(Index<Accessor>, &[u32])],
+) -> Option<Index<Accessor>> {
+    let accessor = o_json.accessors.get(parts.first()?.0.value())?;
+    let size = element_size(accessor)?;
+    let stride = size.next_multiple_of(4);
+
+    let component = |a: &Accessor| match a.component_type {
+        Checked::Valid(GenericComponentType(component)) => Some(component),
+        Checked::Invalid => None,
+    };
+    // Read and check every part before allocating the output
+    let mut sources = Vec::with_capacity(parts.len());
+    for (index, vertices) in parts {
+        let part = o_json.accessors.get(index.value())?;
+        if part.sparse.is_some()
+            || component(part) != component(accessor)
+            || part.type_ != accessor.type_
+            || part.normalized != accessor.normalized
+        {
+            return None;
+        }
+        let data = packed_accessor_data(o_blob, o_json, *index)?;
+        let elements = data.len() / size;
+        if vertices.iter().any(|&v| v as usize >= elements) {
+            return None;
+        }
+        sources.push((data, *vertices));
+    }
+    let count: usize = parts.iter().map(|(_, vertices)| vertices.len()).sum();
+    let mut bytes = Vec::with_capacity(count * stride);
+    for (data, vertices) in &sources {
+        for &v in *vertices {
+            let start = v as usize * size;
+            bytes.extend_from_slice(data.get(start..start + size)?);
+            bytes.resize(bytes.len().next_multiple_of(4), 0);
+        }
+    }
+
+    let mut n_accessor = accessor.clone();
+    if accessor.min.is_some() || accessor.max.is_some() {
+        let Checked::Valid(GenericComponentType(component)) = accessor.component_type else {
+            return None;
+        };
+        let n = size / component.size();
+        let (mut min, mut max) = (vec![f32::MAX; n], vec![f32::MIN; n]);
+        for element in bytes.chunks_exact(stride) {
+            for c in 0..n {
+                let value = decode_component(component, false, &element[c * component.size()..]);
+                min[c] = min[c].min(value);
+                max[c] = max[c].max(value);
+            }
+        }
+        // Integer components keep integer bounds
+        let json = |values: Vec<f32>| -> serde_json::Value {
+            match component {
+                ComponentType::F32 => values.into(),
+                _ => values.iter().map(|&v| v as i64).collect::<Vec<_>>().into(),
+            }
+        };
+        n_accessor.min = Some(json(min));
+        n_accessor.max = Some(json(max));
+    }
+
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ArrayBuffer));
+    if stride != size {
+        n_json.buffer_views[view.value()].byte_stride = Some(Stride(stride));
+    }
+    n_accessor.buffer_view = Some(view);
+    n_accessor.byte_offset = None;
+    n_accessor.count = count.into();
+    Some(n_json.push(n_accessor))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::{Accessor, buffer::View};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        animation::validate_animation_timing, convert::read_accessor, opt::optimize_with,
+        options::OptimizeOptions,
+    };
+
+    fn position_accessor(count: u64, byte_offset: Option<u64>) -> Accessor {
+        Accessor {
+            buffer_view: Some(Index::new(0)),
+            byte_offset: byte_offset.map(Into::into),
+            count: count.into(),
+            component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Checked::Valid(Type::Vec3),
+            min: None,
+            max: None,
+            name: None,
+            normalized: false,
+            sparse: None,
+        }
+    }
+
+    fn view(byte_length: u64, byte_stride: Option<usize>) -> View {
+        View {
+            buffer: Index::new(0),
+            byte_length: byte_length.into(),
+            byte_offset: None,
+            byte_stride: byte_stride.map(gltf::json::buffer::Stride),
+            name: None,
+            target: None,
+            extensions: None,
+            extras: Default::default(),
+        }
+    }
+
+    fn root_with(accessor: Accessor, view: View) -> Root {
+        Root {
+            accessors: vec![accessor],
+            buffer_views: vec![view],
+            ..Default::default()
+        }
+    }
+
+    /// `json` with accessor 0 as the position attribute of a mesh
+    fn drawn(mut json: Root) -> Root {
+        json.meshes.push(gltf::json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            primitives: vec![gltf::json::mesh::Primitive {
+                attributes: [(
+                    Checked::Valid(gltf::json::mesh::Semantic::Positions),
+                    Index::new(0),
+                )]
+                .into(),
+                extensions: None,
+                extras: Default::default(),
+                indices: None,
+                material: None,
+                mode: Checked::Valid(gltf::json::mesh::Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+        json
+    }
+
+    #[test]
+    fn stride_defaults_to_element_size() {
+        let json = root_with(position_accessor(4, None), view(48, None));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(12));
+        // Trailing alignment bytes are not taken as per-vertex padding
+        let json = drawn(root_with(position_accessor(4, None), view(50, None)));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(12));
+    }
+
+    #[test]
+    fn stride_uses_byte_stride() {
+        let json = root_with(position_accessor(4, Some(12)), view(96, Some(24)));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(24));
+        let json = root_with(position_accessor(4, Some(12)), view(96, Some(8)));
+        assert_eq!(accessor_stride(&json, Index::new(0)), None);
+    }
+
+    #[test]
+    fn stride_detects_padded_vertices_without_byte_stride() {
+        let json = drawn(root_with(position_accessor(4, None), view(64, None)));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(16));
+
+        // Data that isn't a vertex attribute, or shares its view, is tightly packed
+        let json = root_with(position_accessor(4, None), view(64, None));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(12));
+        let mut json = drawn(root_with(position_accessor(4, None), view(64, None)));
+        json.accessors.push(position_accessor(1, Some(48)));
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(12));
+    }
+
+    #[test]
+    fn packed_data_drops_stride_padding() {
+        let blob: Vec<u8> = (0..48).collect();
+        let json = root_with(position_accessor(2, None), view(48, Some(24)));
+        let data = packed_accessor_data(&blob, &json, Index::new(0)).unwrap();
+        assert_eq!(data, [&blob[0..12], &blob[24..36]].concat());
+    }
+
+    #[test]
+    fn counts_beyond_the_view_are_refused() {
+        let blob = vec![0u8; 48];
+        let json = root_with(position_accessor(1_000_000_000, None), view(48, None));
+        assert_eq!(packed_accessor_data(&blob, &json, Index::new(0)), None);
+
+        let mut n_blob = BlobWriter::new();
+        let mut n_json = Root::default();
+        let vertices = [0, 1];
+        let gathered = gather_accessor(
+            &blob,
+            &json,
+            &mut n_blob,
+            &mut n_json,
+            Index::new(0),
+            &vertices,
+        );
+        assert_eq!(gathered, None);
+        assert!(n_json.accessors.is_empty());
+    }
+
+    #[test]
+    fn gathered_vertices_are_copied_in_order() {
+        let blob: Vec<u8> = [[0.0f32, 1.0, 2.0], [3.0, 4.0, 5.0]]
+            .as_flattened()
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let json = root_with(position_accessor(2, None), view(24, None));
+        let mut n_blob = BlobWriter::new();
+        let mut n_json = Root::default();
+        let index = gather_accessor(
+            &blob,
+            &json,
+            &mut n_blob,
+            &mut n_json,
+            Index::new(0),
+            &[1, 1, 0],
+        )
+        .unwrap();
+        assert_eq!(n_json.accessors[index.value()].count.0, 3);
+        assert_eq!(
+            gather_accessor(&blob, &json, &mut n_blob, &mut n_json, Index::new(0), &[2]),
+            None
+        );
+        let n_blob = n_blob.into_vec();
+        assert_eq!(&n_blob[..12], &blob[12..24]);
+        assert_eq!(&n_blob[24..36], &blob[..12]);
+    }
+
+    /// Animation key times and values sharing one view, and the u16 indices of two
+    /// primitives sharing another, none of them with a `byteStride`
+    fn shared_views_glb() -> Vec<u8> {
+        let floats =
+            |values: &[f32]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+        let mut blob = floats(&[0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0]);
+        blob.extend(floats(&[
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ]));
+        blob.extend([0u16, 1, 2, 0, 2, 3].iter().flat_map(|i| i.to_le_bytes()));
+        let json: Root = serde_json::from_value(json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": blob.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 32 },
+                { "buffer": 0, "byteOffset": 32, "byteLength": 48, "target": 34962 },
+                { "buffer": 0, "byteOffset": 80, "byteLength": 12, "target": 34963 },
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR",
+                  "min": [0.0], "max": [1.0] },
+                { "bufferView": 0, "byteOffset": 8, "componentType": 5126, "count": 2,
+                  "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] },
+                { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" },
+                { "bufferView": 2, "byteOffset": 6, "componentType": 5123, "count": 3,
+                  "type": "SCALAR" },
+            ],
+            "meshes": [{ "primitives": [
+                { "attributes": { "POSITION": 2 }, "indices": 3 },
+                { "attributes": { "POSITION": 2 }, "indices": 4 },
+            ] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }],
+            "scene": 0,
+            "animations": [{
+                "channels": [{ "sampler": 0, "target": { "node": 0, "path": "translation" } }],
+                "samplers": [{ "input": 0, "output": 1 }],
+            }],
+        }))
+        .unwrap();
+        write_glb(&json, blob).unwrap()
+    }
+
+    #[test]
+    fn shared_views_are_tightly_packed() {
+        let glb = shared_views_glb();
+        let (json, _) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(accessor_stride(&json, Index::new(0)), Some(4));
+        assert_eq!(accessor_stride(&json, Index::new(3)), Some(2));
+
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        assert_eq!(
+            validate_animation_timing(&mut Cursor::new(&out)).unwrap(),
+            []
+        );
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+        let sampler = &n_json.animations[0].samplers[0];
+        let times = read_accessor::<1>(&n_blob, &n_json, sampler.input).unwrap();
+        assert_eq!(times, [[0.0], [1.0]]);
+        let values = read_accessor::<3>(&n_blob, &n_json, sampler.output).unwrap();
+        assert_eq!(values, [[0.0, 0.0, 0.0], [1.0, 2.0, 3.0]]);
+
+        let primitives = &n_json.meshes[0].primitives;
+        let indices: Vec<_> = primitives.iter().map(|p| p.indices.unwrap()).collect();
+        for acc in [sampler.input, sampler.output].iter().chain(&indices) {
+            let view = n_json.accessors[acc.value()].buffer_view.unwrap();
+            assert_eq!(n_json.buffer_views[view.value()].byte_stride, None);
+        }
+        let read = |i: Index<Accessor>| read_accessor::<1>(&n_blob, &n_json, i).unwrap();
+        assert_eq!(read(indices[0]), [[0.0], [1.0], [2.0]]);
+        assert_eq!(read(indices[1]), [[0.0], [2.0], [3.0]]);
+    }
+}
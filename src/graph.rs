@@ -0,0 +1,281 @@
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    io::{Read, Seek},
+};
+
+use gltf::json::Root;
+use serde_json::{Value, json};
+
+use crate::{
+    atlas::slots, crop::extension_textures, error::OptimizeError, glb::read_glb, opt::TextureType,
+};
+
+/// Output format of [`dump_graph`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GraphFormat {
+    /// `{"objects": [...], "edges": [...]}`, for scripts and diffing
+    #[default]
+    Json,
+    /// Graphviz `digraph`, for `dot -Tsvg`
+    Dot,
+}
+
+/// One object of the document graph
+struct GraphObject {
+    /// Kind and index, e.g. `node/3`
+    id: String,
+    kind: &'static str,
+    index: usize,
+    name: Option<String>,
+    /// Reachable from a scene
+    used: bool,
+}
+
+/// A reference from one object to another, labeled with the property holding it
+struct GraphEdge {
+    from: String,
+    to: String,
+    label: String,
+}
+
+/// Objects and references of a document, scenes down to images
+struct Graph {
+    objects: Vec<GraphObject>,
+    edges: Vec<GraphEdge>,
+}
+
+impl Graph {
+    fn new(json: &Root) -> Self {
+        let mut edges = Vec::new();
+        let mut edge = |from: String, to: String, label: &str| {
+            edges.push(GraphEdge {
+                from,
+                to,
+                label: label.to_string(),
+            });
+        };
+        for (i, scene) in json.scenes.iter().enumerate() {
+            for node in &scene.nodes {
+                edge(
+                    format!("scene/{i}"),
+                    format!("node/{}", node.value()),
+                    "nodes",
+                );
+            }
+        }
+        for (i, node) in json.nodes.iter().enumerate() {
+            let from = format!("node/{i}");
+            for child in node.children.iter().flatten() {
+                edge(from.clone(), format!("node/{}", child.value()), "children");
+            }
+            if let Some(mesh) = node.mesh {
+                edge(from.clone(), format!("mesh/{}", mesh.value()), "mesh");
+            }
+            if let Some(skin) = node.skin {
+                edge(from.clone(), format!("skin/{}", skin.value()), "skin");
+            }
+        }
+        for (i, skin) in json.skins.iter().enumerate() {
+            for joint in &skin.joints {
+                edge(
+                    format!("skin/{i}"),
+                    format!("node/{}", joint.value()),
+                    "joints",
+                );
+            }
+        }
+        for (i, mesh) in json.meshes.iter().enumerate() {
+            for (p, primitive) in mesh.primitives.iter().enumerate() {
+                if let Some(material) = primitive.material {
+                    let to = format!("material/{}", material.value());
+                    edge(format!("mesh/{i}"), to, &format!("primitive {p}"));
+                }
+            }
+        }
+        for (i, material) in json.materials.iter().enumerate() {
+            let from = format!("material/{i}");
+            for (slot, texture_type) in slots(material).into_iter().zip(TextureType::ALL) {
+                if let Some((texture, _, _)) = slot {
+                    let to = format!("texture/{}", texture.value());
+                    edge(from.clone(), to, texture_type.label());
+                }
+            }
+            let mut found = Vec::new();
+            if let Some(extensions) = &material.extensions
+                && let Ok(value) = serde_json::to_value(extensions)
+            {
+                extension_textures(&value, &mut found);
+            }
+            for texture in found {
+                edge(from.clone(), format!("texture/{texture}"), "extension");
+            }
+        }
+        for (i, texture) in json.textures.iter().enumerate() {
+            let to = format!("image/{}", texture.source.value());
+            edge(format!("texture/{i}"), to, "source");
+        }
+
+        let mut objects = Vec::new();
+        let mut add = |kind: &'static str, names: Vec<Option<&String>>| {
+            for (index, name) in names.into_iter().enumerate() {
+                objects.push(GraphObject {
+                    id: format!("{kind}/{index}"),
+                    kind,
+                    index,
+                    name: name.cloned(),
+                    used: false,
+                });
+            }
+        };
+        add(
+            "scene",
+            json.scenes.iter().map(|s| s.name.as_ref()).collect(),
+        );
+        add("node", json.nodes.iter().map(|n| n.name.as_ref()).collect());
+        add("skin", json.skins.iter().map(|s| s.name.as_ref()).collect());
+        add(
+            "mesh",
+            json.meshes.iter().map(|m| m.name.as_ref()).collect(),
+        );
+        add(
+            "material",
+            json.materials.iter().map(|m| m.name.as_ref()).collect(),
+        );
+        add(
+            "texture",
+            json.textures.iter().map(|t| t.name.as_ref()).collect(),
+        );
+        add(
+            "image",
+            json.images.iter().map(|i| i.name.as_ref()).collect(),
+        );
+
+        // Everything reachable from a scene is in use
+        let mut used: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = (0..json.scenes.len())
+            .filter_map(|i| objects.get(i).map(|o| o.id.as_str()))
+            .collect();
+        while let Some(id) = stack.pop() {
+            if used.insert(id) {
+                stack.extend(edges.iter().filter(|e| e.from == id).map(|e| e.to.as_str()));
+            }
+        }
+        let used: HashSet<String> = used.into_iter().map(str::to_string).collect();
+        for object in &mut objects {
+            object.used = used.contains(&object.id);
+        }
+        Graph { objects, edges }
+    }
+
+    fn to_json(&self) -> Value {
+        let objects: Vec<Value> = self
+            .objects
+            .iter()
+            .map(|o| {
+                json!({
+                    "id": o.id,
+                    "kind": o.kind,
+                    "index": o.index,
+                    "name": o.name,
+                    "used": o.used,
+                })
+            })
+            .collect();
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .map(|e| json!({ "from": e.from, "to": e.to, "label": e.label }))
+            .collect();
+        json!({ "objects": objects, "edges": edges })
+    }
+
+    fn to_dot(&self) -> String {
+        // Debug formatting quotes and escapes, which DOT strings accept
+        let mut dot = String::from("digraph gltf {\n    rankdir=LR;\n");
+        for o in &self.objects {
+            let label = match &o.name {
+                Some(name) => format!("{} {}\n{name}", o.kind, o.index),
+                None => format!("{} {}", o.kind, o.index),
+            };
+            let style = if o.used { "solid" } else { "dashed" };
+            let _ = writeln!(dot, "    {:?} [label={label:?}, style={style}];", o.id);
+        }
+        for e in &self.edges {
+            let _ = writeln!(dot, "    {:?} -> {:?} [label={:?}];", e.from, e.to, e.label);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Scenes, nodes, skins, meshes, materials, textures and images of a GLB and the references
+/// between them, as JSON or Graphviz DOT
+///
+/// Objects not reachable from any scene are marked `"used": false` (dashed in DOT); those
+/// are what optimization drops. Dump the input and the output of an optimization to see why
+/// objects were pruned, merged or duplicated; references to missing objects show up as edges
+/// to ids without an object.
+pub fn dump_graph<R: Read + Seek>(
+    reader: &mut R,
+    format: GraphFormat,
+) -> Result<String, OptimizeError> {
+    let (json, _) = read_glb(reader)?;
+    let graph = Graph::new(&json);
+    Ok(match format {
+        GraphFormat::Json => serde_json::to_string_pretty(&graph.to_json())?,
+        GraphFormat::Dot => graph.to_dot(),
+    })
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn graph_dumps_mark_unreachable_objects() {
+        // One primitive takes the first of two materials, leaving the second unused
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let graph = |glb: &[u8]| {
+            let dump = dump_graph(&mut Cursor::new(glb), GraphFormat::Json).unwrap();
+            serde_json::from_str::<serde_json::Value>(&dump).unwrap()
+        };
+        let used = |graph: &serde_json::Value, id: &str| {
+            let objects = graph["objects"].as_array().unwrap();
+            let object = objects.iter().find(|o| o["id"] == id)?;
+            object["used"].as_bool()
+        };
+
+        let before = graph(&glb);
+        assert_eq!(used(&before, "material/0"), Some(true));
+        assert_eq!(used(&before, "image/0"), Some(true));
+        assert_eq!(used(&before, "material/1"), Some(false));
+        assert_eq!(used(&before, "image/1"), Some(false));
+        let edges = before["edges"].as_array().unwrap();
+        let edge =
+            serde_json::json!({ "from": "material/0", "to": "texture/0", "label": "base color" });
+        assert!(edges.contains(&edge));
+
+        let out = optimize_with(&mut Cursor::new(&glb), &OptimizeOptions::default()).unwrap();
+        let after = graph(&out);
+        assert_eq!(used(&after, "material/0"), Some(true));
+        assert_eq!(used(&after, "material/1"), None);
+
+        let dot = dump_graph(&mut Cursor::new(&glb), GraphFormat::Dot).unwrap();
+        assert!(dot.starts_with("digraph gltf {"));
+        assert!(dot.contains("\"mesh/0\" -> \"material/0\" [label=\"primitive 0\"];"));
+        assert!(
+            dot.lines()
+                .any(|l| l.contains("\"material/1\" [") && l.ends_with("style=dashed];"))
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{error::OptimizeError, options::OptimizeOptions};
+
+/// What [`OptimizeOptions::never_grow`] did about an output larger than its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GrowthFallback {
+    /// Optimized again with every texture copied as it is, which came out no larger
+    TexturesPassedThrough,
+    /// Even that was larger, so the input is returned unchanged
+    InputReturned,
+}
+
+/// `options` with every texture slot copied instead of resized and re-encoded
+fn passthrough_textures(options: &OptimizeOptions) -> OptimizeOptions {
+    OptimizeOptions {
+        process_base_color: false,
+        process_normals: false,
+        process_metallic_roughness: false,
+        process_occlusion: false,
+        process_emissive: false,
+        ..options.clone()
+    }
+}
+
+/// Run `optimize`, falling back as [`OptimizeOptions::never_grow`] asks when it grows the file
+///
+/// `optimize` returns the output GLB and whatever else the caller collects alongside it;
+/// `unchanged` stands in for the latter when the input is returned.
+pub(crate) fn never_grow<T>(
+    input: &[u8],
+    options: &OptimizeOptions,
+    optimize: impl Fn(&OptimizeOptions) -> Result<(Vec<u8>, T), OptimizeError>,
+    unchanged: impl FnOnce() -> T,
+) -> Result<(Vec<u8>, T, Option<GrowthFallback>), OptimizeError> {
+    let (out, extra) = optimize(options)?;
+    if !options.never_grow || out.len() <= input.len() {
+        return Ok((out, extra, None));
+    }
+    let passthrough = passthrough_textures(options);
+    if passthrough != *options {
+        let (out, extra) = optimize(&passthrough)?;
+        if out.len() <= input.len() {
+            return Ok((out, extra, Some(GrowthFallback::TexturesPassedThrough)));
+        }
+    }
+    Ok((
+        input.to_vec(),
+        unchanged(),
+        Some(GrowthFallback::InputReturned),
+    ))
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        license::AssetLicense, opt::optimize_with, optimizer::Optimizer, testing::SyntheticGlb,
+        upscale::TextureUpscale,
+    };
+
+    #[test]
+    fn never_grow_falls_back_to_smaller_outputs() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run = |options: OptimizeOptions| {
+            let options = OptimizeOptions {
+                never_grow: true,
+                ..options
+            };
+            let out = Optimizer::new(&options)
+                .run(&mut Cursor::new(&glb))
+                .unwrap();
+            (out.glb, out.growth_fallback)
+        };
+
+        // Enlarged textures grow the file, copied ones don't
+        let upscaled = OptimizeOptions {
+            upscale_textures: Some(TextureUpscale::default()),
+            ..Default::default()
+        };
+        let (out, fallback) = run(upscaled.clone());
+        assert_eq!(fallback, Some(GrowthFallback::TexturesPassedThrough));
+        assert!(out.len() <= glb.len());
+
+        // A long attribution grows the JSON whatever happens to textures
+        let attributed = OptimizeOptions {
+            asset_license: AssetLicense {
+                attribution: Some(include_str!("../README.md").to_string()),
+                ..Default::default()
+            },
+            ..upscaled
+        };
+        let (out, fallback) = run(attributed.clone());
+        assert_eq!(fallback, Some(GrowthFallback::InputReturned));
+        assert_eq!(out, glb);
+        let same = optimize_with(
+            &mut Cursor::new(&glb),
+            &OptimizeOptions {
+                never_grow: true,
+                ..attributed
+            },
+        )
+        .unwrap();
+        assert_eq!(same, glb);
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::{HashMap, HashSet};
+
+use gltf::json::{
+    Index, Root, Texture,
+    accessor::Type,
+    mesh::{Mode, Semantic},
+    validation::Checked,
+};
+use image::{ColorType, DynamicImage, RgbaImage};
+
+use crate::{
+    atlas::slots,
+    blob::BlobWriter,
+    crop::{RewrittenDocument, extension_textures},
+    decode::load_source_image,
+    error::OptimizeError,
+    opt::{TextureType, encode_png, get_image_data, get_index_data, get_texcoord_data},
+    options::SlotTextureSizes,
+};
+
+/// Texel coverage of one image: which texels any triangle samples
+struct Coverage {
+    width: u32,
+    height: u32,
+    covered: Vec<bool>,
+}
+
+impl Coverage {
+    fn new(width: u32, height: u32) -> Self {
+        Coverage {
+            width,
+            height,
+            covered: vec![false; width as usize * height as usize],
+        }
+    }
+
+    /// Mark the texels of a UV triangle, wrapping past the image edges
+    ///
+    /// Texels whose center lies inside are covered; a sliver covering no center marks the
+    /// texels holding its corners instead. Returns `false` for a triangle spanning a whole
+    /// repeat of the image, which covers everything.
+    fn add_triangle(&mut self, uvs: [[f32; 2]; 3]) -> bool {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let p = uvs.map(|[u, v]| [u * w, v * h]);
+        let min = [0, 1].map(|c| p.iter().map(|q| q[c]).fold(f32::MAX, f32::min));
+        let max = [0, 1].map(|c| p.iter().map(|q| q[c]).fold(f32::MIN, f32::max));
+        if !min.iter().chain(&max).all(|c| c.is_finite())
+            || max[0] - min[0] >= w
+            || max[1] - min[1] >= h
+        {
+            return false;
+        }
+
+        let edge = |a: [f32; 2], b: [f32; 2], x: f32, y: f32| {
+            (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+        };
+        let sign = edge(p[0], p[1], p[2][0], p[2][1]).signum();
+        let mut any = false;
+        if sign != 0.0 {
+            for y in min[1].floor() as i64..=max[1].ceil() as i64 {
+                for x in min[0].floor() as i64..=max[0].ceil() as i64 {
+                    let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let inside = [(1, 2), (2, 0), (0, 1)]
+                        .iter()
+                        .all(|&(a, b)| edge(p[a], p[b], cx, cy) * sign >= 0.0);
+                    if inside {
+                        self.mark(x, y);
+                        any = true;
+                    }
+                }
+            }
+        }
+        if !any {
+            for q in p {
+                self.mark(q[0].floor() as i64, q[1].floor() as i64);
+            }
+        }
+        true
+    }
+
+    fn mark(&mut self, x: i64, y: i64) {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.covered[y * self.width as usize + x] = true;
+    }
+}
+
+/// Indices of the up to 8 texels around `(x, y)`
+fn neighbors(x: usize, y: usize, w: usize, h: usize) -> impl Iterator<Item = usize> {
+    (-1i64..=1)
+        .flat_map(move |dy| (-1i64..=1).map(move |dx| (dx, dy)))
+        .filter(|&d| d != (0, 0))
+        .filter_map(move |(dx, dy)| {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+            let inside = (0..w as i64).contains(&nx) && (0..h as i64).contains(&ny);
+            inside.then(|| ny as usize * w + nx as usize)
+        })
+}
+
+/// Grow covered texels into the uncovered ones around them, `radius` texels deep
+///
+/// Each ring of new texels takes the average of its already filled neighbors, so chart colors
+/// spread outward instead of the gutter's background. Returns whether any texel changed.
+fn dilate(img: &mut RgbaImage, coverage: &Coverage, radius: u32) -> bool {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut filled = coverage.covered.clone();
+    let mut ring: Vec<usize> = (0..w * h)
+        .filter(|&i| !filled[i] && neighbors(i % w, i / w, w, h).any(|n| filled[n]))
+        .collect();
+    let mut changed = false;
+    let mut queued = vec![false; w * h];
+    for _ in 0..radius {
+        if ring.is_empty() {
+            break;
+        }
+        let colors: Vec<[u8; 4]> = ring
+            .iter()
+            .map(|&i| {
+                let (mut sum, mut count) = ([0u32; 4], 0);
+                for n in neighbors(i % w, i / w, w, h).filter(|&n| filled[n]) {
+                    let pixel = img.get_pixel((n % w) as u32, (n / w) as u32).0;
+                    for c in 0..4 {
+                        sum[c] += pixel[c] as u32;
+                    }
+                    count += 1;
+                }
+                sum.map(|s| (s / count.max(1)) as u8)
+            })
+            .collect();
+        for (&i, color) in ring.iter().zip(colors) {
+            let pixel = img.get_pixel_mut((i % w) as u32, (i / w) as u32);
+            changed |= pixel.0 != color;
+            pixel.0 = color;
+            filled[i] = true;
+        }
+        let mut next = Vec::new();
+        for &i in &ring {
+            for n in neighbors(i % w, i / w, w, h) {
+                if !filled[n] && !queued[n] {
+                    queued[n] = true;
+                    next.push(n);
+                }
+            }
+        }
+        ring = next;
+    }
+    changed
+}
+
+/// Triangles of a primitive as vertex indices, `None` for other modes or unreadable indices
+fn triangles(
+    o_blob: &[u8],
+    o_json: &Root,
+    primitive: &gltf::json::mesh::Primitive,
+) -> Option<Vec<u32>> {
+    if !matches!(primitive.mode, Checked::Valid(Mode::Triangles)) {
+        return None;
+    }
+    match primitive.indices {
+        Some(indices) => get_index_data(o_blob, o_json, indices),
+        None => {
+            let position = primitive
+                .attributes
+                .get(&Checked::Valid(Semantic::Positions))?;
+            Some((0..o_json.accessors.get(position.value())?.count.0 as u32).collect())
+        }
+    }
+}
+
+/// How an image is sampled: the triangles' UVs and the smallest output size of its slots
+struct ImageUse {
+    /// UV accessor and the triangle indices reading it
+    reads: Vec<(usize, Vec<u32>)>,
+    size: u32,
+}
+
+/// Pad UV chart borders with their own colors, against seams once textures are shrunk,
+/// mipmapped or block-compressed
+///
+/// For every image sampled by material slots, texels no triangle samples are filled outward
+/// from the charts, `padding` texels deep at the size the image is written at (deeper at source
+/// resolution when it gets shrunk). Images also used by material extensions, through
+/// transformed or missing UV sets, by non-triangle primitives, or tiled across whole repeats
+/// are left alone, as are 16-bit and float images. Changed images are stored as PNG. Returns
+/// `None` when no image changed.
+pub(crate) fn dilate_gutters(
+    o_blob: &[u8],
+    o_json: &Root,
+    padding: u32,
+    texture_size: u32,
+    slot_texture_sizes: &SlotTextureSizes,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let image_of = |texture: Index<Texture>| {
+        o_json
+            .textures
+            .get(texture.value())
+            .map(|t| t.source.value())
+    };
+    let mut uses: HashMap<usize, ImageUse> = HashMap::new();
+    let mut blocked: HashSet<usize> = HashSet::new();
+    for mat in &o_json.materials {
+        if let Some(ext) = &mat.extensions {
+            let mut found = Vec::new();
+            extension_textures(&serde_json::to_value(ext)?, &mut found);
+            blocked.extend(
+                found
+                    .into_iter()
+                    .filter_map(|t| image_of(Index::new(t as u32))),
+            );
+        }
+    }
+
+    for primitive in o_json.meshes.iter().flat_map(|m| &m.primitives) {
+        let Some(mat) = primitive
+            .material
+            .and_then(|m| o_json.materials.get(m.value()))
+        else {
+            continue;
+        };
+        let indices = triangles(o_blob, o_json, primitive);
+        for (slot, texture_type) in slots(mat).into_iter().zip(TextureType::ALL) {
+            let Some((texture, tex_coord, has_extension)) = slot else {
+                continue;
+            };
+            let Some(image) = image_of(texture) else {
+                continue;
+            };
+            let accessor = primitive
+                .attributes
+                .get(&Checked::Valid(Semantic::TexCoords(tex_coord)))
+                .filter(|a| {
+                    o_json.accessors.get(a.value()).is_some_and(|a| {
+                        a.sparse.is_none() && a.type_ == Checked::Valid(Type::Vec2)
+                    })
+                });
+            match (accessor, &indices) {
+                (Some(accessor), Some(indices)) if !has_extension => {
+                    let size = slot_texture_sizes.size(texture_type, texture_size);
+                    let image_use = uses.entry(image).or_insert(ImageUse {
+                        reads: Vec::new(),
+                        size,
+                    });
+                    image_use.size = image_use.size.min(size);
+                    image_use.reads.push((accessor.value(), indices.clone()));
+                }
+                _ => {
+                    blocked.insert(image);
+                }
+            }
+        }
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed_any = false;
+    let mut images: Vec<_> = uses
+        .into_iter()
+        .filter(|(i, _)| !blocked.contains(i))
+        .collect();
+    images.sort_by_key(|(i, _)| *i);
+    'images: for (image, image_use) in images {
+        let Some(texture) = o_json
+            .textures
+            .iter()
+            .position(|t| t.source.value() == image)
+        else {
+            continue;
+        };
+        let Some(data) = get_image_data(o_blob, o_json, Index::new(texture as u32)) else {
+            continue;
+        };
+        let decoded = load_source_image(o_json, image, data, Vec::new())?;
+        let color = decoded.color();
+        if !matches!(
+            color,
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+        ) {
+            continue;
+        }
+
+        let mut coverage = Coverage::new(decoded.width(), decoded.height());
+        for (accessor, indices) in &image_use.reads {
+            let Some(uvs) = get_texcoord_data(o_blob, o_json, Index::new(*accessor as u32)) else {
+                continue 'images;
+            };
+            for triangle in indices.chunks_exact(3) {
+                let corner = |i: u32| uvs.get(i as usize).copied();
+                let (Some(a), Some(b), Some(c)) = (
+                    corner(triangle[0]),
+                    corner(triangle[1]),
+                    corner(triangle[2]),
+                ) else {
+                    continue 'images;
+                };
+                if !coverage.add_triangle([a, b, c]) {
+                    continue 'images;
+                }
+            }
+        }
+        if coverage.covered.iter().all(|&c| c) || !coverage.covered.contains(&true) {
+            continue;
+        }
+
+        let longest = decoded.width().max(decoded.height());
+        let scale = longest as f32 / image_use.size.clamp(1, longest) as f32;
+        let radius = (padding as f32 * scale).ceil() as u32;
+        let mut rgba = decoded.to_rgba8();
+        if !dilate(&mut rgba, &coverage, radius) {
+            continue;
+        }
+        let dilated = match color {
+            ColorType::L8 => DynamicImage::ImageRgba8(rgba).to_luma8().into(),
+            ColorType::La8 => DynamicImage::ImageRgba8(rgba).to_luma_alpha8().into(),
+            ColorType::Rgb8 => DynamicImage::ImageRgba8(rgba).to_rgb8().into(),
+            _ => DynamicImage::ImageRgba8(rgba),
+        };
+        let mut png = Vec::new();
+        encode_png(
+            dilated.as_bytes(),
+            dilated.width(),
+            dilated.height(),
+            dilated.color(),
+            &mut png,
+        )?;
+        let view = n_blob.push_view(&mut n_json, &png, None);
+        let n_image = &mut n_json.images[image];
+        n_image.buffer_view = Some(view);
+        n_image.uri = None;
+        n_image.mime_type = Some(gltf::json::image::MimeType("image/png".to_string()));
+        changed_any = true;
+    }
+
+    Ok(changed_any.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageEncoder, Rgba, RgbaImage, codecs::png::PngEncoder};
+
+    use super::*;
+    use crate::{crop::write_uvs, glb::read_glb, inject::replace_texture, testing::SyntheticGlb};
+
+    #[test]
+    fn gutters_are_filled_from_chart_borders() {
+        // Red chart in the top-left quarter of a black image
+        let source = RgbaImage::from_fn(32, 32, |x, y| {
+            if x < 16 && y < 16 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(source.as_raw(), 32, 32, image::ExtendedColorType::Rgba8)
+            .unwrap();
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(&glb), 0, &png, None).unwrap();
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let uv = o_json.meshes[0].primitives[0].attributes
+            [&Checked::Valid(gltf::json::mesh::Semantic::TexCoords(0))];
+        let uvs = get_texcoord_data(&o_blob, &o_json, uv).unwrap();
+        let mut o_blob = BlobWriter::from(o_blob);
+        write_uvs(
+            &mut o_blob,
+            &mut o_json,
+            uv.value(),
+            uvs.iter().map(|[u, v]| [u * 0.5, v * 0.5]),
+        );
+        let o_blob = o_blob.into_vec();
+
+        let (n_json, n_blob) = dilate_gutters(&o_blob, &o_json, 2, 1024, &Default::default())
+            .unwrap()
+            .unwrap();
+        let data = get_image_data(&n_blob, &n_json, Index::new(0)).unwrap();
+        let dilated = image::load_from_memory(data).unwrap().to_rgba8();
+        assert_eq!(dilated.get_pixel(8, 8), &Rgba([255, 0, 0, 255]));
+        assert_eq!(dilated.get_pixel(17, 8), &Rgba([255, 0, 0, 255]));
+        assert_eq!(dilated.get_pixel(8, 17), &Rgba([255, 0, 0, 255]));
+        assert_eq!(dilated.get_pixel(18, 8), &Rgba([0, 0, 0, 255]));
+        assert_eq!(dilated.get_pixel(30, 30), &Rgba([0, 0, 0, 255]));
+        // At a quarter of the size, each output texel spans four source texels
+        let (n_json, n_blob) = dilate_gutters(&o_blob, &o_json, 2, 8, &Default::default())
+            .unwrap()
+            .unwrap();
+        let data = get_image_data(&n_blob, &n_json, Index::new(0)).unwrap();
+        let dilated = image::load_from_memory(data).unwrap().to_rgba8();
+        assert_eq!(dilated.get_pixel(23, 8), &Rgba([255, 0, 0, 255]));
+        assert_eq!(dilated.get_pixel(24, 8), &Rgba([0, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use gltf::json::{Extras, Root};
+use serde_json::Value;
+
+use crate::{atlas::slots, crop::extension_textures, error::OptimizeError, glb::from_extras};
+
+/// Material and mesh `extras` key holding optimization hints
+pub const HINTS_KEY: &str = "gltf_opt";
+
+/// Hints an artist left on a material or mesh under [`HINTS_KEY`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ObjectHints {
+    /// Leave the object as it is: textures copied without resizing or re-encoding for a
+    /// material, geometry left out of merging, simplification, LODs and splitting for a mesh
+    pub skip: bool,
+    /// Cap on the longer side of the textures the object uses
+    pub max_texture_size: Option<u32>,
+}
+
+/// Hints of an object, failing on entries of the wrong type or unknown names
+fn parse_hints(extras: &Extras) -> Result<ObjectHints, String> {
+    let Some(hints) = from_extras(extras).and_then(|e| e.get(HINTS_KEY).cloned()) else {
+        return Ok(ObjectHints::default());
+    };
+    let Value::Object(hints) = hints else {
+        return Err(format!("{HINTS_KEY} is not an object"));
+    };
+    let mut parsed = ObjectHints::default();
+    for (key, value) in hints {
+        match key.as_str() {
+            "skip" => {
+                parsed.skip = value
+                    .as_bool()
+                    .ok_or_else(|| format!("{HINTS_KEY}.skip {value} is not a boolean"))?;
+            }
+            "max_texture_size" => {
+                let size = value
+                    .as_u64()
+                    .and_then(|s| u32::try_from(s).ok())
+                    .filter(|&s| s > 0)
+                    .ok_or_else(|| {
+                        format!("{HINTS_KEY}.max_texture_size {value} is not a positive integer")
+                    })?;
+                parsed.max_texture_size = Some(size);
+            }
+            _ => return Err(format!("{HINTS_KEY}.{key} is not a known hint")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Hints of an object, ignoring malformed ones; [`validate_hints`] reports those up front
+pub(crate) fn object_hints(extras: &Extras) -> ObjectHints {
+    parse_hints(extras).unwrap_or_default()
+}
+
+/// Fail on the first material or mesh whose hints are malformed, naming it
+pub(crate) fn validate_hints(o_json: &Root) -> Result<(), OptimizeError> {
+    let materials = o_json.materials.iter().map(|m| ("material", &m.extras));
+    let meshes = o_json.meshes.iter().map(|m| ("mesh", &m.extras));
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    for (kind, extras) in materials.chain(meshes) {
+        let index = indices.entry(kind).or_default();
+        parse_hints(extras).map_err(|reason| OptimizeError::Hint {
+            kind,
+            index: *index,
+            reason,
+        })?;
+        *index += 1;
+    }
+    Ok(())
+}
+
+/// Smallest `max_texture_size` hinted for each texture, by the materials using it and the
+/// meshes drawing those materials
+pub(crate) fn hinted_texture_sizes(o_json: &Root) -> HashMap<usize, u32> {
+    let mut material_caps: HashMap<usize, u32> = HashMap::new();
+    let cap = |caps: &mut HashMap<usize, u32>, key: usize, size: u32| {
+        let entry = caps.entry(key).or_insert(size);
+        *entry = (*entry).min(size);
+    };
+    for (i, material) in o_json.materials.iter().enumerate() {
+        if let Some(size) = object_hints(&material.extras).max_texture_size {
+            cap(&mut material_caps, i, size);
+        }
+    }
+    for mesh in &o_json.meshes {
+        if let Some(size) = object_hints(&mesh.extras).max_texture_size {
+            for material in mesh.primitives.iter().filter_map(|p| p.material) {
+                cap(&mut material_caps, material.value(), size);
+            }
+        }
+    }
+
+    let mut sizes = HashMap::new();
+    for (material, size) in material_caps {
+        let Some(material) = o_json.materials.get(material) else {
+            continue;
+        };
+        let mut textures: Vec<usize> = slots(material)
+            .into_iter()
+            .flatten()
+            .map(|(texture, _, _)| texture.value())
+            .collect();
+        for payload in material.extensions.iter().flat_map(|e| e.others.values()) {
+            extension_textures(payload, &mut textures);
+        }
+        for texture in textures {
+            cap(&mut sizes, texture, size);
+        }
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn extras_hints_cap_skip_and_reject() {
+        let annotate = |glb: &[u8], edit: &dyn Fn(&mut serde_json::Value)| {
+            let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+            let mut doc = serde_json::to_value(&o_json).unwrap();
+            edit(&mut doc);
+            write_glb(&serde_json::from_value(doc).unwrap(), o_blob).unwrap()
+        };
+        let side = |out: &[u8], texture: u32| {
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&n_blob, &n_json, Index::new(texture)).unwrap();
+            image::load_from_memory(data).unwrap().width()
+        };
+        let options = OptimizeOptions {
+            texture_size: 16,
+            merge_primitives: true,
+            ..Default::default()
+        };
+
+        let glb = SyntheticGlb {
+            meshes: 3,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = annotate(&glb, &|doc| {
+            doc["materials"][0]["extras"] =
+                serde_json::json!({ "gltf_opt": { "max_texture_size": 8 } });
+            doc["materials"][1]["extras"] = serde_json::json!({ "gltf_opt": { "skip": true } });
+        });
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        assert_eq!([side(&out, 0), side(&out, 1), side(&out, 2)], [8, 32, 16]);
+
+        // Skipped meshes keep their primitives apart
+        let glb = SyntheticGlb {
+            meshes: 2,
+            primitives_per_mesh: 2,
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = annotate(&glb, &|doc| {
+            doc["meshes"][1]["extras"] = serde_json::json!({ "gltf_opt": { "skip": true } });
+        });
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(&out)).unwrap();
+        let primitives: Vec<usize> = n_json.meshes.iter().map(|m| m.primitives.len()).collect();
+        assert_eq!(primitives, [1, 2]);
+
+        for hints in [
+            serde_json::json!({ "max_texture_size": 0 }),
+            serde_json::json!({ "skip": "yes" }),
+            serde_json::json!({ "max_texture": 256 }),
+        ] {
+            let glb = annotate(&glb, &|doc| {
+                doc["meshes"][0]["extras"] = serde_json::json!({ "gltf_opt": hints });
+            });
+            let e = optimize_with(&mut Cursor::new(&glb), &options).unwrap_err();
+            assert!(
+                matches!(&e, OptimizeError::Hint { kind: "mesh", index: 0, reason } if reason.starts_with("gltf_opt.")),
+                "{e}"
+            );
+        }
+    }
+}
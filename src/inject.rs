@@ -0,0 +1,157 @@
+use std::io::{Read, Seek};
+
+use gltf::json::image::MimeType;
+
+use crate::{
+    blob::BlobWriter,
+    decode::{sniff_format, sniffed_mime_type},
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    opt::{optimize_document, replace_views},
+    options::OptimizeOptions,
+};
+
+/// Swap the image of texture `texture_index` for `image` and write the GLB again
+///
+/// `image` must be PNG, JPEG, WebP or KTX2; the image's MIME type follows its bytes. Without
+/// `options` everything else is copied as is; with them the result is optimized like
+/// [`optimize_with`](crate::optimize_with), resizing and converting the new image too.
+pub fn replace_texture<R: Read + Seek>(
+    reader: &mut R,
+    texture_index: usize,
+    image: &[u8],
+    options: Option<&OptimizeOptions>,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut o_json, o_blob) = read_glb(reader)?;
+    let mime_type = sniffed_mime_type(image).ok_or(OptimizeError::UnsupportedImage {
+        format: sniff_format(image),
+    })?;
+    let source = o_json
+        .textures
+        .get(texture_index)
+        .ok_or(OptimizeError::TextureOutOfBounds {
+            texture: texture_index,
+        })?
+        .source
+        .value();
+    let view = o_json
+        .images
+        .get(source)
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: source,
+        })?
+        .buffer_view;
+
+    // A view only this image reads is rewritten in place; otherwise the image gets a new one
+    let shared = view.is_none_or(|view| {
+        o_json
+            .images
+            .iter()
+            .filter(|img| img.buffer_view == Some(view))
+            .count()
+            > 1
+    });
+    let blob = match view {
+        Some(view) if !shared => replace_views(&mut o_json, &o_blob, |i| {
+            (i == view.value()).then_some(image)
+        }),
+        _ => {
+            let mut blob = BlobWriter::from(o_blob);
+            let view = blob.push_view(&mut o_json, image, None);
+            o_json.images[source].buffer_view = Some(view);
+            o_json.images[source].uri = None;
+            blob.into_vec()
+        }
+    };
+    o_json.images[source].mime_type = Some(MimeType(mime_type.to_string()));
+
+    let Some(options) = options else {
+        return write_glb(&o_json, blob);
+    };
+    let (mut n_json, n_blob, _) = optimize_document(&blob, &o_json, options, None, None)?;
+    retain_extensions(&mut n_json, &[])?;
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        extract::extract_texture,
+        opt::{ImageFormat, encode_png},
+        testing::SyntheticGlb,
+    };
+
+    fn two_textures() -> Vec<u8> {
+        SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn red_png() -> Vec<u8> {
+        let mut png = Vec::new();
+        let pixels = [255, 0, 0, 255].repeat(16);
+        encode_png(&pixels, 4, 4, image::ColorType::Rgba8, &mut png).unwrap();
+        png
+    }
+
+    fn texture(glb: &[u8], index: usize) -> Vec<u8> {
+        extract_texture(&mut Cursor::new(glb), index).unwrap().0
+    }
+
+    #[test]
+    fn replaced_images_are_copied_as_is() {
+        let glb = two_textures();
+        let out = replace_texture(&mut Cursor::new(&glb), 0, &red_png(), None).unwrap();
+        assert_eq!(texture(&out, 0), red_png());
+        assert_eq!(texture(&out, 1), texture(&glb, 1));
+
+        let err = replace_texture(&mut Cursor::new(&glb), 0, b"GIF89a", None).unwrap_err();
+        assert!(matches!(
+            err,
+            OptimizeError::UnsupportedImage { format: "GIF" }
+        ));
+        let err = replace_texture(&mut Cursor::new(&glb), 2, &red_png(), None).unwrap_err();
+        assert!(matches!(
+            err,
+            OptimizeError::TextureOutOfBounds { texture: 2 }
+        ));
+    }
+
+    #[test]
+    fn images_sharing_a_view_keep_their_bytes() {
+        let (mut json, blob) = read_glb(&mut Cursor::new(two_textures())).unwrap();
+        json.images[1].buffer_view = json.images[0].buffer_view;
+        let glb = write_glb(&json, blob).unwrap();
+        let original = texture(&glb, 0);
+
+        let out = replace_texture(&mut Cursor::new(&glb), 0, &red_png(), None).unwrap();
+        assert_eq!(texture(&out, 0), red_png());
+        assert_eq!(texture(&out, 1), original);
+    }
+
+    #[test]
+    fn replaced_images_are_optimized_with_options() {
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        let out = replace_texture(
+            &mut Cursor::new(two_textures()),
+            0,
+            &red_png(),
+            Some(&options),
+        )
+        .unwrap();
+        let image = image::load_from_memory(&texture(&out, 0)).unwrap();
+        assert!(image.to_rgba8().pixels().all(|p| p.0 == [255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{Cursor, Read, Seek};
+
+use gltf::json::{Path, Root, validation::Validate};
+
+use crate::{
+    animation::{KeyframeTimingIssue, timing_issues},
+    error::OptimizeError,
+    glb::read_glb,
+    skinning::{SkinningIssue, skinning_issues},
+    stats::{GpuStats, WireSizes, compute_gpu_stats},
+    warnings::{SceneWarning, find_scene_warnings},
+};
+
+/// Length of every top-level array of `json`, by its glTF name
+pub(crate) fn object_counts(json: &Root) -> [(&'static str, usize); 13] {
+    [
+        ("scenes", json.scenes.len()),
+        ("nodes", json.nodes.len()),
+        ("meshes", json.meshes.len()),
+        ("materials", json.materials.len()),
+        ("textures", json.textures.len()),
+        ("images", json.images.len()),
+        ("samplers", json.samplers.len()),
+        ("accessors", json.accessors.len()),
+        ("bufferViews", json.buffer_views.len()),
+        ("buffers", json.buffers.len()),
+        ("animations", json.animations.len()),
+        ("skins", json.skins.len()),
+        ("cameras", json.cameras.len()),
+    ]
+}
+
+/// What a GLB holds, at a glance, see [`model_info`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ModelInfo {
+    /// `asset.generator`, the tool that wrote the file
+    pub generator: Option<String>,
+    /// Length of every top-level array, by its glTF name, e.g. `("materials", 12)`
+    pub objects: Vec<(&'static str, usize)>,
+    pub extensions_used: Vec<String>,
+    pub extensions_required: Vec<String>,
+    /// Draw calls, triangles, vertex and texture memory of the default scene and the size of
+    /// the file, as [`gpu_stats`](crate::gpu_stats) reports them
+    pub stats: GpuStats,
+}
+
+/// Summarize a GLB: generator, object counts, extensions and [`GpuStats`]
+pub fn model_info<R: Read + Seek>(reader: &mut R) -> Result<ModelInfo, OptimizeError> {
+    let mut glb = Vec::new();
+    reader.read_to_end(&mut glb)?;
+    let (json, blob) = read_glb(&mut Cursor::new(&glb))?;
+    Ok(ModelInfo {
+        generator: json.asset.generator.clone(),
+        objects: object_counts(&json).to_vec(),
+        extensions_used: json.extensions_used.clone(),
+        extensions_required: json.extensions_required.clone(),
+        stats: GpuStats {
+            wire: WireSizes::measure(&glb)?,
+            ..compute_gpu_stats(&blob, &json)
+        },
+    })
+}
+
+/// Everything [`validate_glb`] found in a file
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Validation {
+    /// Breaks of the glTF 2.0 schema and references to missing objects, as `path: problem`,
+    /// e.g. `meshes[0].primitives[0].material: Index out of bounds`; loaders reject these
+    pub errors: Vec<String>,
+    /// The other lists are only filled when there are no errors
+    pub warnings: Vec<SceneWarning>,
+    pub skinning: Vec<SkinningIssue>,
+    pub keyframe_timing: Vec<KeyframeTimingIssue>,
+}
+
+impl Validation {
+    /// No errors, warnings or issues
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+            && self.warnings.is_empty()
+            && self.skinning.is_empty()
+            && self.keyframe_timing.is_empty()
+    }
+}
+
+/// Check any GLB, optimized or not, with the crate's validators
+///
+/// Runs the glTF schema and reference checks, then [`scene_warnings`](crate::scene_warnings),
+/// [`validate_skinning`](crate::validate_skinning) and
+/// [`validate_animation_timing`](crate::validate_animation_timing). Fails only when the file
+/// can't be parsed at all.
+pub fn validate_glb<R: Read + Seek>(reader: &mut R) -> Result<Validation, OptimizeError> {
+    let (json, blob) = read_glb(reader)?;
+    let mut errors = Vec::new();
+    json.validate(&json, Path::new, &mut |path, error| {
+        errors.push(format!("{}: {error}", path()));
+    });
+    if !errors.is_empty() {
+        return Ok(Validation {
+            errors,
+            ..Default::default()
+        });
+    }
+    Ok(Validation {
+        errors,
+        warnings: find_scene_warnings(&blob, &json),
+        skinning: skinning_issues(&blob, &json),
+        keyframe_timing: timing_issues(&blob, &json),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{glb::write_glb, testing::SyntheticGlb};
+
+    #[test]
+    fn info_counts_objects() {
+        let glb = SyntheticGlb {
+            meshes: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let info = model_info(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(info.generator.as_deref(), Some("gltf_opt::testing"));
+        assert!(info.objects.contains(&("meshes", 3)));
+        assert_eq!(info.stats.draw_calls, 3);
+        assert_eq!(info.stats.wire.raw, glb.len() as u64);
+    }
+
+    #[test]
+    fn validation_reports_broken_references() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        assert!(validate_glb(&mut Cursor::new(&glb)).unwrap().is_clean());
+
+        let (mut json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        json.meshes[0].primitives[0].material = Some(Index::new(7));
+        json.scene = None;
+        let broken = write_glb(&json, blob).unwrap();
+        let validation = validate_glb(&mut Cursor::new(broken)).unwrap();
+        assert_eq!(
+            validation.errors,
+            ["meshes[0].primitives[0].material: Index out of bounds"]
+        );
+        // Warnings wait for the errors to be fixed
+        assert!(validation.warnings.is_empty());
+    }
+}
@@ -0,0 +1,563 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::{AccessorProblem, OptimizeError},
+    source::{SourceResolver, pack_gltf},
+};
+
+/// Buffer id under which `KHR_binary_glTF` (glTF 1.0 GLB) stores the binary body
+const BINARY_BUFFER: &str = "binary_glTF";
+
+/// Bytes of the glTF 1.0 GLB header: magic, version, length, content length and format
+const GLB1_HEADER: usize = 20;
+
+/// Version of a glTF 1.x document or GLB, `None` for anything else
+///
+/// A GLB is told by its header version. A JSON document is 1.x when `asset.version` says so,
+/// or when it has no version but keeps its meshes or accessors in an object keyed by id.
+pub(crate) fn legacy_version(data: &[u8]) -> Option<String> {
+    if data.starts_with(b"glTF") {
+        let version = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?);
+        return (version == 1).then(|| "1.0".to_string());
+    }
+    let doc: Value = serde_json::from_slice(data).ok()?;
+    match doc.pointer("/asset/version").and_then(Value::as_str) {
+        Some(version) => version.starts_with("1.").then(|| version.to_string()),
+        None => ["meshes", "accessors"]
+            .iter()
+            .any(|key| doc.get(key).is_some_and(Value::is_object))
+            .then(|| "1.0".to_string()),
+    }
+}
+
+/// Error for a document that failed to parse, naming its version when it is glTF 1.x
+pub(crate) fn parse_error(data: &[u8], error: gltf::Error) -> OptimizeError {
+    match legacy_version(data) {
+        Some(version) => OptimizeError::UnsupportedVersion { version },
+        None => error.into(),
+    }
+}
+
+/// Objects of a glTF 1.0 dictionary with the index each gets in the 2.0 array
+struct Dictionary<'a> {
+    entries: Vec<(&'a str, &'a Value)>,
+    index: HashMap<&'a str, usize>,
+}
+
+impl<'a> Dictionary<'a> {
+    fn new(doc: &'a Value, key: &str) -> Self {
+        let mut entries: Vec<(&str, &Value)> = doc
+            .get(key)
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(id, value)| (id.as_str(), value))
+            .collect();
+        // The GLB body has to be buffer 0
+        if let Some(p) = entries.iter().position(|(id, _)| *id == BINARY_BUFFER) {
+            let body = entries.remove(p);
+            entries.insert(0, body);
+        }
+        let index = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (id, _))| (*id, i))
+            .collect();
+        Dictionary { entries, index }
+    }
+
+    /// Index of the object `value` names by id
+    fn get(&self, value: Option<&Value>) -> Option<usize> {
+        self.index.get(value?.as_str()?).copied()
+    }
+}
+
+/// Copy `keys` that are set from `from` into `to`
+fn copy_fields(from: &Value, to: &mut Map<String, Value>, keys: &[&str]) {
+    for key in keys {
+        if let Some(value) = from.get(*key) {
+            to.insert(key.to_string(), value.clone());
+        }
+    }
+}
+
+/// 2.0 attribute name of a 1.0 semantic, `None` for technique-specific ones
+fn attribute_name(semantic: &str) -> Option<String> {
+    let (base, set) = match semantic.split_once('_') {
+        Some((base, set)) if set.parse::<u32>().is_ok() => (base, set),
+        _ => (semantic, "0"),
+    };
+    match base {
+        "POSITION" | "NORMAL" | "TANGENT" if semantic == base => Some(base.to_string()),
+        "TEXCOORD" | "COLOR" => Some(format!("{base}_{set}")),
+        "JOINT" | "JOINTS" => Some(format!("JOINTS_{set}")),
+        "WEIGHT" | "WEIGHTS" => Some(format!("WEIGHTS_{set}")),
+        _ => None,
+    }
+}
+
+/// Metallic-roughness material approximating a 1.0 material's common values
+///
+/// Reads `KHR_materials_common` values, or the technique parameters of the same names:
+/// `diffuse` (color or texture id) becomes the base color, `emission` the emissive factor,
+/// `shininess` the roughness and `transparency` the alpha. Techniques and shaders are dropped.
+fn upgrade_material(material: &Value, textures: &Dictionary) -> Value {
+    let common = material.pointer("/extensions/KHR_materials_common");
+    let values = common
+        .and_then(|c| c.get("values"))
+        .or_else(|| material.get("values"))
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    let color = |key: &str| -> Option<Vec<f64>> {
+        let array = values.get(key)?.as_array()?;
+        array.iter().map(Value::as_f64).collect()
+    };
+
+    let mut pbr = json!({ "metallicFactor": 0.0 });
+    match color("diffuse") {
+        Some(mut c) if c.len() >= 3 => {
+            c.resize(4, 1.0);
+            pbr["baseColorFactor"] = json!(c);
+        }
+        _ => {
+            if let Some(texture) = textures.get(values.get("diffuse")) {
+                pbr["baseColorTexture"] = json!({ "index": texture });
+            }
+        }
+    }
+    let shininess = values.get("shininess").and_then(Value::as_f64);
+    pbr["roughnessFactor"] = shininess
+        .map_or(1.0, |s| (2.0 / (s.max(0.0) + 2.0)).sqrt())
+        .into();
+
+    let mut out = json!({ "pbrMetallicRoughness": pbr });
+    if let Some(name) = material.get("name") {
+        out["name"] = name.clone();
+    }
+    if let Some(emission) = color("emission").filter(|c| c.len() >= 3) {
+        out["emissiveFactor"] = json!(
+            emission[..3]
+                .iter()
+                .map(|c| c.clamp(0.0, 1.0))
+                .collect::<Vec<_>>()
+        );
+    }
+    if let Some(transparency) = values.get("transparency").and_then(Value::as_f64)
+        && transparency < 1.0
+    {
+        let alpha = out["pbrMetallicRoughness"]["baseColorFactor"]
+            .get(3)
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0);
+        if out["pbrMetallicRoughness"].get("baseColorFactor").is_none() {
+            out["pbrMetallicRoughness"]["baseColorFactor"] = json!([1.0, 1.0, 1.0, 1.0]);
+        }
+        out["pbrMetallicRoughness"]["baseColorFactor"][3] = (alpha * transparency).into();
+        out["alphaMode"] = "BLEND".into();
+    }
+    if common
+        .and_then(|c| c.get("doubleSided"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        out["doubleSided"] = true.into();
+    }
+    out
+}
+
+/// glTF 2.0 document holding the geometry, node tree and basic materials of a 1.0 one
+fn upgrade_document(doc: &Value) -> Result<Value, OptimizeError> {
+    let buffers = Dictionary::new(doc, "buffers");
+    let views = Dictionary::new(doc, "bufferViews");
+    let accessors = Dictionary::new(doc, "accessors");
+    let meshes = Dictionary::new(doc, "meshes");
+    let materials = Dictionary::new(doc, "materials");
+    let textures = Dictionary::new(doc, "textures");
+    let images = Dictionary::new(doc, "images");
+    let samplers = Dictionary::new(doc, "samplers");
+    let nodes = Dictionary::new(doc, "nodes");
+    let scenes = Dictionary::new(doc, "scenes");
+
+    let n_buffers: Vec<Value> = buffers
+        .entries
+        .iter()
+        .map(|(id, buffer)| {
+            let mut out = Map::new();
+            copy_fields(buffer, &mut out, &["byteLength", "name"]);
+            if *id != BINARY_BUFFER {
+                copy_fields(buffer, &mut out, &["uri"]);
+            }
+            Value::Object(out)
+        })
+        .collect();
+
+    // Strides moved from accessors to views in 2.0, so a view read at several strides is split
+    let mut n_views: Vec<Value> = Vec::new();
+    let mut strided: HashMap<(usize, u64), usize> = HashMap::new();
+    let mut view_at = |view: usize, stride: u64| {
+        *strided.entry((view, stride)).or_insert_with(|| {
+            let (_, source) = views.entries[view];
+            let mut out = Map::new();
+            out.insert(
+                "buffer".into(),
+                buffers.get(source.get("buffer")).unwrap_or(0).into(),
+            );
+            copy_fields(
+                source,
+                &mut out,
+                &["byteOffset", "byteLength", "target", "name"],
+            );
+            if stride > 0 {
+                out.insert("byteStride".into(), stride.into());
+            }
+            n_views.push(Value::Object(out));
+            n_views.len() - 1
+        })
+    };
+    let mut n_accessors = Vec::new();
+    for (i, (_, accessor)) in accessors.entries.iter().enumerate() {
+        let view = views
+            .get(accessor.get("bufferView"))
+            .ok_or(OptimizeError::Accessor {
+                accessor: i,
+                problem: AccessorProblem::NoBufferView,
+            })?;
+        let stride = accessor
+            .get("byteStride")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let mut out = Map::new();
+        out.insert("bufferView".into(), view_at(view, stride).into());
+        copy_fields(
+            accessor,
+            &mut out,
+            &[
+                "byteOffset",
+                "componentType",
+                "count",
+                "type",
+                "min",
+                "max",
+                "name",
+            ],
+        );
+        n_accessors.push(Value::Object(out));
+    }
+    // Views no accessor reads still hold images
+    let mut image_views = HashMap::new();
+    for (_, image) in &images.entries {
+        let binary = image.pointer("/extensions/KHR_binary_glTF/bufferView");
+        if let Some(view) = views.get(binary) {
+            image_views.insert(view, view_at(view, 0));
+        }
+    }
+
+    let n_meshes: Vec<Value> = meshes
+        .entries
+        .iter()
+        .map(|(_, mesh)| {
+            let primitives: Vec<Value> = mesh
+                .get("primitives")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .map(|primitive| {
+                    let attributes: Map<String, Value> = primitive
+                        .get("attributes")
+                        .and_then(Value::as_object)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|(semantic, accessor)| {
+                            Some((
+                                attribute_name(semantic)?,
+                                accessors.get(Some(accessor))?.into(),
+                            ))
+                        })
+                        .collect();
+                    let mut out = json!({ "attributes": attributes });
+                    if let Some(indices) = accessors.get(primitive.get("indices")) {
+                        out["indices"] = indices.into();
+                    }
+                    if let Some(material) = materials.get(primitive.get("material")) {
+                        out["material"] = material.into();
+                    }
+                    if let Some(mode) = primitive.get("mode") {
+                        out["mode"] = mode.clone();
+                    }
+                    out
+                })
+                .collect();
+            let mut out = json!({ "primitives": primitives });
+            if let Some(name) = mesh.get("name") {
+                out["name"] = name.clone();
+            }
+            out
+        })
+        .collect();
+
+    let n_materials: Vec<Value> = materials
+        .entries
+        .iter()
+        .map(|(_, material)| upgrade_material(material, &textures))
+        .collect();
+    let n_textures = textures
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(i, (_, texture))| {
+            let source = images
+                .get(texture.get("source"))
+                .ok_or(OptimizeError::MissingImageData { texture: i })?;
+            let mut out = json!({ "source": source });
+            if let Some(sampler) = samplers.get(texture.get("sampler")) {
+                out["sampler"] = sampler.into();
+            }
+            Ok(out)
+        })
+        .collect::<Result<Vec<Value>, OptimizeError>>()?;
+    let n_images: Vec<Value> = images
+        .entries
+        .iter()
+        .map(|(_, image)| {
+            let mut out = Map::new();
+            copy_fields(image, &mut out, &["name"]);
+            let binary = image.pointer("/extensions/KHR_binary_glTF");
+            match binary.and_then(|b| views.get(b.get("bufferView"))) {
+                Some(view) => {
+                    out.insert("bufferView".into(), image_views[&view].into());
+                    copy_fields(binary.unwrap_or(&Value::Null), &mut out, &["mimeType"]);
+                }
+                None => copy_fields(image, &mut out, &["uri"]),
+            }
+            Value::Object(out)
+        })
+        .collect();
+    let n_samplers: Vec<Value> = samplers
+        .entries
+        .iter()
+        .map(|(_, sampler)| {
+            let mut out = Map::new();
+            copy_fields(
+                sampler,
+                &mut out,
+                &["magFilter", "minFilter", "wrapS", "wrapT"],
+            );
+            Value::Object(out)
+        })
+        .collect();
+
+    // 2.0 nodes draw one mesh; further meshes of a node move to child nodes
+    let mut n_nodes: Vec<Value> = Vec::new();
+    let mut extra_nodes = Vec::new();
+    for (_, node) in &nodes.entries {
+        let mut out = Map::new();
+        copy_fields(
+            node,
+            &mut out,
+            &["name", "matrix", "translation", "rotation", "scale"],
+        );
+        let mut children: Vec<usize> = node
+            .get("children")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|c| nodes.get(Some(c)))
+            .collect();
+        let node_meshes: Vec<usize> = node
+            .get("meshes")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|m| meshes.get(Some(m)))
+            .collect();
+        if let Some((first, rest)) = node_meshes.split_first() {
+            out.insert("mesh".into(), (*first).into());
+            for mesh in rest {
+                children.push(nodes.entries.len() + extra_nodes.len());
+                extra_nodes.push(json!({ "mesh": mesh }));
+            }
+        }
+        if !children.is_empty() {
+            out.insert("children".into(), children.into());
+        }
+        n_nodes.push(Value::Object(out));
+    }
+    n_nodes.extend(extra_nodes);
+
+    let n_scenes: Vec<Value> = scenes
+        .entries
+        .iter()
+        .map(|(_, scene)| {
+            let roots: Vec<usize> = scene
+                .get("nodes")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|n| nodes.get(Some(n)))
+                .collect();
+            let mut out = json!({ "nodes": roots });
+            if let Some(name) = scene.get("name") {
+                out["name"] = name.clone();
+            }
+            out
+        })
+        .collect();
+
+    let mut asset = json!({ "version": "2.0", "generator": "gltf_opt glTF 1.0 upgrade" });
+    if let Some(copyright) = doc.pointer("/asset/copyright") {
+        asset["copyright"] = copyright.clone();
+    }
+    let mut out = json!({
+        "asset": asset,
+        "buffers": n_buffers,
+        "bufferViews": n_views,
+        "accessors": n_accessors,
+        "meshes": n_meshes,
+        "materials": n_materials,
+        "textures": n_textures,
+        "images": n_images,
+        "samplers": n_samplers,
+        "nodes": n_nodes,
+        "scenes": n_scenes,
+    });
+    let first_scene = (!scenes.entries.is_empty()).then_some(0);
+    if let Some(scene) = scenes.get(doc.get("scene")).or(first_scene) {
+        out["scene"] = scene.into();
+    }
+    // Empty arrays are not valid glTF
+    if let Value::Object(map) = &mut out {
+        map.retain(|_, v| !v.as_array().is_some_and(Vec::is_empty));
+    }
+    Ok(out)
+}
+
+/// Convert a simple glTF 1.0 `.gltf` or GLB into a self-contained glTF 2.0 GLB
+///
+/// A minimal shim for assets that are geometry and basic materials: buffers, views,
+/// accessors, meshes, nodes and scenes are carried over, `KHR_binary_glTF` bodies and images
+/// included. Materials become metallic-roughness approximations of their common `diffuse`,
+/// `emission`, `shininess` and `transparency` values; techniques, shaders, skins, animations,
+/// cameras and lights are dropped. External buffers and images are loaded through
+/// `resolver`. The result can be optimized like any other GLB.
+pub fn upgrade_gltf1<R: Read + Seek>(
+    reader: &mut R,
+    resolver: &dyn SourceResolver,
+) -> Result<Vec<u8>, OptimizeError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    if legacy_version(&data).is_none() {
+        return Err(OptimizeError::NotFormat {
+            expected: "glTF 1.0 document",
+        });
+    }
+
+    let (content, body) = if data.starts_with(b"glTF") {
+        let field = |at: usize| -> Option<usize> {
+            Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+        };
+        let (length, content_length) =
+            field(8)
+                .zip(field(12))
+                .ok_or_else(|| OptimizeError::Truncated {
+                    what: "GLB header".to_string(),
+                })?;
+        let content_end = GLB1_HEADER + content_length;
+        let content =
+            data.get(GLB1_HEADER..content_end)
+                .ok_or_else(|| OptimizeError::Truncated {
+                    what: "GLB content".to_string(),
+                })?;
+        let body = data.get(content_end..length.min(data.len())).unwrap_or(&[]);
+        (content, body.to_vec())
+    } else {
+        (data.as_slice(), Vec::new())
+    };
+
+    let doc: Value = serde_json::from_slice(content)?;
+    let upgraded = upgrade_document(&doc)?;
+    pack_gltf(serde_json::from_value(upgraded)?, body, resolver)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::validation::Checked;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions};
+
+    #[test]
+    fn gltf1_is_rejected_and_upgraded() {
+        use crate::{glb::read_glb, source::glb_from_gltf};
+
+        let positions: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        let doc = serde_json::json!({
+            "asset": { "version": "1.0" },
+            "extensionsUsed": ["KHR_binary_glTF", "KHR_materials_common"],
+            "buffers": { "binary_glTF": { "byteLength": 36, "uri": "data:," } },
+            "bufferViews": { "positions": { "buffer": "binary_glTF", "byteLength": 36, "target": 34962 } },
+            "accessors": { "position": {
+                "bufferView": "positions", "byteOffset": 0, "byteStride": 12, "componentType": 5126,
+                "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]
+            } },
+            "materials": { "red": { "extensions": { "KHR_materials_common": {
+                "technique": "LAMBERT", "doubleSided": true, "values": { "diffuse": [1, 0, 0, 1] }
+            } } } },
+            "meshes": { "triangle": { "primitives": [
+                { "attributes": { "POSITION": "position" }, "material": "red", "mode": 4 }
+            ] } },
+            "nodes": { "root": { "meshes": ["triangle", "triangle"] } },
+            "scenes": { "main": { "nodes": ["root"] } },
+            "scene": "main"
+        });
+        let content = serde_json::to_vec(&doc).unwrap();
+        let mut glb1 = b"glTF".to_vec();
+        for field in [1, 20 + content.len() + positions.len(), content.len(), 0] {
+            glb1.extend((field as u32).to_le_bytes());
+        }
+        glb1.extend(&content);
+        glb1.extend(&positions);
+
+        let unsupported = |e: OptimizeError| matches!(e, OptimizeError::UnsupportedVersion { version } if version == "1.0");
+        let options = OptimizeOptions::default();
+        assert!(unsupported(
+            optimize_with(&mut Cursor::new(&glb1), &options).unwrap_err()
+        ));
+        let resolver = |uri: &str| -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+            Err(format!("unexpected uri {uri}").into())
+        };
+        assert!(unsupported(
+            glb_from_gltf(&mut Cursor::new(&content), &resolver).unwrap_err()
+        ));
+
+        let glb = upgrade_gltf1(&mut Cursor::new(&glb1), &resolver).unwrap();
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(n_json.asset.version, "2.0");
+        assert_eq!(n_json.meshes.len(), 1);
+        // The second mesh of the node moves to a child drawing the same mesh
+        assert_eq!(n_json.nodes.len(), 2);
+        assert_eq!(n_json.nodes[1].mesh, n_json.nodes[0].mesh);
+        let material = &n_json.materials[0];
+        assert_eq!(
+            material.pbr_metallic_roughness.base_color_factor.0,
+            [1.0, 0.0, 0.0, 1.0]
+        );
+        assert!(material.double_sided);
+        let position = n_json.meshes[0].primitives[0].attributes
+            [&Checked::Valid(gltf::json::mesh::Semantic::Positions)];
+        assert_eq!(
+            n_json.accessors[position.value()].max,
+            Some(serde_json::json!([1, 1, 0]))
+        );
+    }
+}
@@ -1,5 +1,217 @@
+mod alpha;
+mod anchor;
+mod animation;
+mod anonymize;
+#[cfg(feature = "archive")]
+mod archive;
+mod atlas;
+mod auto;
+mod batch;
+mod best_effort;
+mod blob;
+mod bounds;
+mod budget;
+mod buffers;
+mod camera;
+mod channels;
+mod collapse;
+mod collision;
+mod commands;
+mod compat;
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+mod compress;
+pub mod convert;
+mod crop;
+mod decode;
+mod dedupe;
+mod density;
+mod diff;
+mod dither;
+mod encoder;
+mod environment;
+mod error;
+mod extension;
+mod external;
+mod extract;
+mod extras;
+mod fallback;
+mod features;
+mod filter;
+mod geometry;
+mod glb;
+mod graph;
+mod growth;
+mod gutter;
+mod hints;
+mod inject;
+mod inspect;
+mod legacy;
+mod license;
+mod lod;
+mod manifest;
+mod material;
+mod material_extensions;
+mod math;
+mod merge;
+mod meshlet;
+mod meshopt;
+mod metrics;
+mod mips;
+mod morph;
 mod opt;
+mod optimizer;
+mod options;
+mod positions;
+mod presets;
+mod preview;
+mod progress;
+mod prune;
+mod quality;
+mod quantize;
+mod readers;
+mod reencode;
+mod reject;
+mod remap;
+mod rename;
+mod reorder;
+mod report;
+mod rescale;
+mod salvage;
+mod scratch;
+mod sharpen;
+mod simplify;
+mod skinning;
+mod slot_check;
+mod source;
+mod specgloss;
+mod split;
+mod sprite;
+mod srgb;
+mod stats;
+mod streaming;
+mod subtree;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod texcoords;
+mod texture_only;
+mod texture_table;
+mod thumbnail;
+mod tiles;
+mod transcode;
+mod upload;
+mod upscale;
+mod variants;
+mod viewer;
+mod views;
+mod vrm;
+mod warnings;
+mod watermark;
+mod writer;
 
+pub use alpha::AlphaConversion;
+pub use anchor::{Anchor, add_anchors};
+pub use animation::{
+    AnimationFilter, AnimationMarker, CubicResampling, KeyframeTimingIssue, filter_animations,
+    validate_animation_timing,
+};
+pub use anonymize::TextureAnonymization;
+#[cfg(feature = "archive")]
+pub use archive::{glb_from_archive, optimize_archive};
+pub use atlas::AtlasOptions;
+pub use auto::{AutoOptimized, CandidateMeasurement, Objective, auto_optimize};
+pub use batch::{BatchJob, BatchReport};
+pub use best_effort::{BestEffortFallback, BestEffortIssue};
+pub use blob::BlobWriter;
+pub use bounds::{
+    Aabb, BoundingSphere, BoundingVolume, BoundingVolumes, add_bounding_volumes, bounding_volumes,
+};
+pub use buffers::ExternalBuffer;
+pub use camera::{CameraFraming, add_framing_camera};
+pub use channels::{Channel, ChannelInput, combine_channels, set_orm_texture, split_channels};
+pub use collision::{CollisionShape, add_collision_meshes};
+pub use commands::{commands_options, run_commands};
+pub use compat::{CompatibilityIssue, TargetProfile, check_compatibility, check_extensions};
+#[cfg(any(feature = "gzip", feature = "brotli", feature = "zstd"))]
+pub use compress::{WireCompression, compress_glb, optimize_compressed};
+pub use density::TriangleTextureScaling;
+pub use diff::{GlbDiff, diff_glbs};
+pub use dither::{Dither, DitherOptions};
+pub use encoder::{EncodedTexture, TextureEncoder};
+pub use environment::{cubemap_to_ktx2, equirect_to_cubemap, is_equirectangular};
+pub use error::{AccessorProblem, Malformation, OptimizeError, Stage};
+pub use extension::{ExtensionContext, ExtensionHandler, ExtensionOwner, ExtensionRegistry};
+pub use external::ExternalTexture;
+pub use extract::{PrimitivePositions, extract_mesh_positions, extract_texture};
+pub use extras::{ExtrasSchemas, ExtrasScope, ExtrasViolation, validate_extras};
+pub use fallback::{TextureEncoding, TextureFallbacks};
+pub use features::FeatureNotEnabled;
+pub use filter::{CommandFilter, ExternalTextureFilter};
+pub use geometry::{EncodedGeometry, GeometryAttribute, GeometryCodec, PrimitiveGeometry};
+pub use graph::{GraphFormat, dump_graph};
+pub use growth::GrowthFallback;
+pub use hints::HINTS_KEY;
+pub use image::RgbaImage;
+pub use inject::replace_texture;
+pub use inspect::{ModelInfo, Validation, model_info, validate_glb};
+pub use legacy::upgrade_gltf1;
+pub use license::AssetLicense;
+pub use lod::LodOptions;
+pub use manifest::asset_manifest;
+pub use material::{MaterialEdit, edit_materials};
+pub use meshlet::MeshletOptions;
+pub use metrics::{Counter, Histogram, MetricsSink};
+pub use mips::MipRounding;
+pub use opt::{
+    ImageFormat, TextureType, TextureVariant, optimize, optimize_variants, optimize_with,
+};
+pub use optimizer::{Optimized, Optimizer};
+pub use options::{OptimizeOptions, SlotTextureSizes};
+pub use presets::PresetInfo;
+pub use preview::{MaterialPreview, material_previews};
+pub use progress::{ProgressSink, ProgressStage};
+pub use readers::{IndicesIter, PositionsIter, Uv0Iter};
+pub use reencode::ReencodeSkip;
+pub use reject::{RejectLimit, RejectLimits};
+pub use remap::RemapTables;
+pub use rename::{NamedObject, Rename, rename_objects};
+pub use reorder::VertexReorder;
+pub use report::{BeforeAfter, OptimizeReport, StoredImage, TextureChange};
+pub use rescale::Rescale;
+pub use salvage::{GlbRepair, optimize_salvaged, salvage_glb};
+pub use sharpen::Sharpen;
+pub use simplify::{SimplifyOptions, SimplifyTarget};
+pub use skinning::{SkinningIssue, validate_skinning};
+pub use source::{SourceResolver, glb_from_gltf, optimize_gltf};
+pub use split::GridSplit;
+pub use stats::{
+    GpuStats, MaterialDensity, TextureMemory, TextureUsage, TranscodedSizes, WireSizes, gpu_stats,
+};
+pub use subtree::{NodeSelector, extract_subtree};
+pub use texture_only::optimize_textures_only;
+#[cfg(feature = "software-thumbnail")]
+pub use thumbnail::SoftwareRenderer;
+pub use thumbnail::{ThumbnailRenderer, render_thumbnail};
+pub use tiles::{Subdivision, TileContent, Tileset, TilingOptions, optimize_tiled};
+pub use transcode::{
+    BasisMode, TextureCompressionProfile, TextureCompressionProfiles, TranscodeTargets,
+};
+pub use upload::{TextureBytes, UploadRanges, ViewBytes, ViewUsage, upload_ranges};
+pub use upscale::{TextureUpscale, UpscaleFilter};
+pub use variants::{MaterialOverride, MaterialVariant, material_variant_glbs};
+pub use viewer::{VIEWER_HINTS_KEY, ViewerHints};
+pub use views::AccessorViews;
+pub use vrm::{optimize_vrm, vrm_extensions};
+pub use warnings::{SceneWarning, scene_warnings, scene_warnings_with_density};
+pub use watermark::{Watermark, WatermarkPlacement};
+pub use writer::GlbWriter;
+
+/// The everyday API: optimize a GLB with [`OptimizeOptions`] and handle the result
+///
+/// Everything else is exported from the crate root.
 pub mod prelude {
-    pub use super::opt::optimize;
+    pub use super::error::OptimizeError;
+    pub use super::opt::{optimize, optimize_with};
+    pub use super::optimizer::{Optimized, Optimizer};
+    pub use super::options::OptimizeOptions;
+    pub use super::report::OptimizeReport;
 }
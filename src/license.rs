@@ -0,0 +1,128 @@
+use gltf::json::Asset;
+use serde_json::{Map, Value};
+
+use crate::{error::OptimizeError, glb::merge_extras};
+
+/// `asset.extras` key holding [`AssetLicense::license`]
+const LICENSE_KEY: &str = "license";
+
+/// `asset.extras` key holding [`AssetLicense::attribution`]
+const ATTRIBUTION_KEY: &str = "attribution";
+
+/// Copyright and license information written to the output's `asset`, see
+/// [`OptimizeOptions::asset_license`](crate::OptimizeOptions::asset_license)
+///
+/// The source's `asset.copyright` and `asset.extras` are kept either way; set fields replace
+/// or add to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AssetLicense {
+    /// `asset.copyright`, e.g. "© 2026 Example Studio"
+    pub copyright: Option<String>,
+    /// License name or URL, written to `asset.extras.license`, e.g. "CC-BY-4.0"
+    pub license: Option<String>,
+    /// Credit line required by the license, written to `asset.extras.attribution`
+    pub attribution: Option<String>,
+    /// Object merged into `asset.extras` key by key, e.g. a marketplace's own block
+    pub extras: Option<Map<String, Value>>,
+}
+
+impl AssetLicense {
+    /// Write the set fields to `asset`
+    ///
+    /// Fails when `extras` repeats `license` or `attribution` while that field is set.
+    pub(crate) fn apply(&self, asset: &mut Asset) -> Result<(), OptimizeError> {
+        if let Some(copyright) = &self.copyright {
+            asset.copyright = Some(copyright.clone());
+        }
+        let mut entries: Vec<(String, Value)> = self
+            .extras
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        for (key, value) in [
+            (LICENSE_KEY, &self.license),
+            (ATTRIBUTION_KEY, &self.attribution),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            if entries.iter().any(|(k, _)| k == key) {
+                return Err(OptimizeError::InvalidOption(format!(
+                    "asset_license.extras repeats the `{key}` field"
+                )));
+            }
+            entries.push((key.to_string(), value.as_str().into()));
+        }
+        for (key, value) in entries {
+            asset.extras = merge_extras(&asset.extras, &key, value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Root;
+
+    use super::*;
+    use crate::{
+        glb::{from_extras, read_glb, write_glb},
+        opt::optimize_with,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn asset_license_is_kept_and_written() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["asset"]["copyright"] = "© Original Author".into();
+        doc["asset"]["extras"] = serde_json::json!({ "source": "https://example.com/chair" });
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let run = |asset_license| {
+            let options = OptimizeOptions {
+                asset_license,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options)?;
+            Ok::<_, OptimizeError>(read_glb(&mut Cursor::new(out)).unwrap().0.asset)
+        };
+        let asset = run(AssetLicense::default()).unwrap();
+        assert_eq!(asset.copyright.as_deref(), Some("© Original Author"));
+        assert_eq!(
+            from_extras(&asset.extras).unwrap()["source"],
+            "https://example.com/chair"
+        );
+
+        let marketplace = serde_json::json!({ "marketplace": { "id": 42 } });
+        let asset = run(AssetLicense {
+            copyright: Some("© 2026 Example Studio".to_string()),
+            license: Some("CC-BY-4.0".to_string()),
+            attribution: Some("Chair by Example Studio".to_string()),
+            extras: marketplace.as_object().cloned(),
+        })
+        .unwrap();
+        assert_eq!(asset.copyright.as_deref(), Some("© 2026 Example Studio"));
+        let extras = from_extras(&asset.extras).unwrap();
+        assert_eq!(extras["source"], "https://example.com/chair");
+        assert_eq!(extras["license"], "CC-BY-4.0");
+        assert_eq!(extras["attribution"], "Chair by Example Studio");
+        assert_eq!(extras["marketplace"]["id"], 42);
+
+        let repeated = serde_json::json!({ "license": "MIT" });
+        let e = run(AssetLicense {
+            license: Some("CC-BY-4.0".to_string()),
+            extras: repeated.as_object().cloned(),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(e, OptimizeError::InvalidOption(_)));
+    }
+}
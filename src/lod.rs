@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use gltf::json::{Index, Material, Mesh, Node, Root, Texture};
+use serde_json::Value;
+
+use crate::{
+    blob::BlobWriter, crop::RewrittenDocument, error::OptimizeError, glb::merge_extras,
+    hints::object_hints, simplify::simplify_primitive,
+};
+
+/// Node extension listing the lower levels of detail of a node
+pub(crate) const LOD_EXTENSION: &str = "MSFT_lod";
+
+/// Node `extras` key with the smallest screen coverage each level is shown at
+const SCREEN_COVERAGE_KEY: &str = "MSFT_screencoverage";
+
+/// Levels of detail written with `MSFT_lod`, see
+/// [`OptimizeOptions::lods`](crate::OptimizeOptions::lods)
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct LodOptions {
+    /// Share of the source triangles kept by each level after the full-detail one, decreasing
+    pub ratios: Vec<f32>,
+    /// Largest distance the surface may move, relative to the primitive's bounding box
+    /// diagonal; a level stops short of its ratio rather than exceed it
+    pub max_error: f32,
+    /// Texture size of each level relative to the level before; `None` shares the full-detail
+    /// textures
+    pub texture_scale: Option<f32>,
+}
+
+impl Default for LodOptions {
+    fn default() -> Self {
+        LodOptions {
+            ratios: vec![0.5, 0.25],
+            max_error: 0.05,
+            texture_scale: None,
+        }
+    }
+}
+
+/// Copy of `material` for one level, with textures of its own so they can be sized apart
+fn level_material(
+    n_json: &mut Root,
+    material: Index<Material>,
+    textures: &mut HashMap<usize, Index<Texture>>,
+) -> Option<Index<Material>> {
+    let mut copy = n_json.materials.get(material.value())?.clone();
+    let mut retarget = |index: &mut Index<Texture>| {
+        let texture = *textures.entry(index.value()).or_insert_with(|| {
+            let copy = n_json.textures[index.value()].clone();
+            n_json.push(copy)
+        });
+        *index = texture;
+    };
+    let pbr = &mut copy.pbr_metallic_roughness;
+    if let Some(info) = &mut pbr.base_color_texture {
+        retarget(&mut info.index);
+    }
+    if let Some(info) = &mut pbr.metallic_roughness_texture {
+        retarget(&mut info.index);
+    }
+    if let Some(info) = &mut copy.normal_texture {
+        retarget(&mut info.index);
+    }
+    if let Some(info) = &mut copy.occlusion_texture {
+        retarget(&mut info.index);
+    }
+    if let Some(info) = &mut copy.emissive_texture {
+        retarget(&mut info.index);
+    }
+    if let Some(name) = &mut copy.name {
+        name.push_str("_LOD");
+    }
+    Some(n_json.push(copy))
+}
+
+/// Smallest screen coverage of each level: a level is shown down to the triangle ratio of the
+/// next one, and the last level is never culled
+fn screen_coverage(ratios: &[f32]) -> Value {
+    // Widened through their shortest decimal form, so 0.1 isn't written as 0.10000000149
+    ratios
+        .iter()
+        .chain(&[0.0])
+        .map(|r| r.to_string().parse::<f64>().unwrap_or(0.0))
+        .collect()
+}
+
+/// Add simplified copies of every mesh a node draws and link them with `MSFT_lod`
+///
+/// Each ratio makes a mesh per source mesh, skipped when no primitive could be simplified, and
+/// a node outside the scene for each node drawing it. The source node lists those in its
+/// `MSFT_lod` payload and their screen coverage in `extras`. With a texture scale, level
+/// meshes use copies of their materials and textures, which [`lod_texture_scales`] sizes.
+/// Returns `None` when no level was added.
+pub(crate) fn add_lod_levels(
+    o_blob: &[u8],
+    o_json: &Root,
+    lods: &LodOptions,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    if lods.ratios.is_empty() {
+        return Ok(None);
+    }
+    if lods.ratios.iter().any(|r| !(0.0..=1.0).contains(r))
+        || lods.ratios.windows(2).any(|w| w[1] > w[0])
+    {
+        return Err(OptimizeError::InvalidOption(format!(
+            "LOD ratios {:?} are not decreasing within 0..=1",
+            lods.ratios
+        )));
+    }
+    if !(0.0..).contains(&lods.max_error) {
+        return Err(OptimizeError::InvalidOption(format!(
+            "LOD max_error {} is negative",
+            lods.max_error
+        )));
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+
+    // Level meshes of each drawn source mesh, by level
+    let mut levels: HashMap<usize, Vec<Option<Index<Mesh>>>> = HashMap::new();
+    for mesh in o_json.nodes.iter().filter_map(|n| n.mesh) {
+        if levels.contains_key(&mesh.value()) {
+            continue;
+        }
+        let Some(source) = o_json.meshes.get(mesh.value()) else {
+            continue;
+        };
+        if object_hints(&source.extras).skip {
+            continue;
+        }
+        let mut meshes = Vec::with_capacity(lods.ratios.len());
+        for (level, &ratio) in lods.ratios.iter().enumerate() {
+            let mut simplified_any = false;
+            let mut level_mesh = source.clone();
+            for (p, primitive) in source.primitives.iter().enumerate() {
+                if let Some(simplified) = simplify_primitive(
+                    o_blob,
+                    o_json,
+                    &mut n_blob,
+                    &mut n_json,
+                    primitive,
+                    ratio,
+                    lods.max_error,
+                ) {
+                    level_mesh.primitives[p] = simplified;
+                    simplified_any = true;
+                }
+            }
+            if !simplified_any {
+                meshes.push(None);
+                continue;
+            }
+            level_mesh.name = source
+                .name
+                .as_ref()
+                .map(|n| format!("{n}_LOD{}", level + 1));
+            meshes.push(Some(n_json.push(level_mesh)));
+        }
+        levels.insert(mesh.value(), meshes);
+    }
+    if levels.values().flatten().all(Option::is_none) {
+        return Ok(None);
+    }
+
+    // Level materials get textures of their own, shared within a level
+    if lods.texture_scale.is_some() {
+        for level in 0..lods.ratios.len() {
+            let mut materials: HashMap<usize, Index<Material>> = HashMap::new();
+            let mut textures: HashMap<usize, Index<Texture>> = HashMap::new();
+            let meshes: Vec<Index<Mesh>> = levels.values().filter_map(|l| l[level]).collect();
+            for mesh in meshes {
+                for p in 0..n_json.meshes[mesh.value()].primitives.len() {
+                    let Some(material) = n_json.meshes[mesh.value()].primitives[p].material else {
+                        continue;
+                    };
+                    let copy = match materials.get(&material.value()) {
+                        Some(&copy) => Some(copy),
+                        None => level_material(&mut n_json, material, &mut textures),
+                    };
+                    if let Some(copy) = copy {
+                        materials.insert(material.value(), copy);
+                        n_json.meshes[mesh.value()].primitives[p].material = Some(copy);
+                    }
+                }
+            }
+        }
+    }
+
+    for (n, node) in o_json.nodes.iter().enumerate() {
+        let Some(meshes) = node.mesh.and_then(|m| levels.get(&m.value())) else {
+            continue;
+        };
+        let mut ids = Vec::new();
+        let mut ratios = Vec::new();
+        for (level, mesh) in meshes.iter().enumerate() {
+            let Some(mesh) = *mesh else {
+                continue;
+            };
+            let lod_node = Node {
+                mesh: Some(mesh),
+                name: node.name.as_ref().map(|n| format!("{n}_LOD{}", level + 1)),
+                children: None,
+                extensions: None,
+                extras: Default::default(),
+                ..node.clone()
+            };
+            ids.push(n_json.push(lod_node).value());
+            ratios.push(lods.ratios[level]);
+        }
+        if ids.is_empty() {
+            continue;
+        }
+
+        let node = &mut n_json.nodes[n];
+        node.extensions
+            .get_or_insert_with(Default::default)
+            .others
+            .insert(LOD_EXTENSION.to_string(), serde_json::json!({ "ids": ids }));
+        node.extras = merge_extras(&node.extras, SCREEN_COVERAGE_KEY, screen_coverage(&ratios));
+    }
+    if !n_json.extensions_used.iter().any(|e| e == LOD_EXTENSION) {
+        n_json.extensions_used.push(LOD_EXTENSION.to_string());
+    }
+
+    Ok(Some((n_json, n_blob.into_vec())))
+}
+
+/// Level nodes a node's `MSFT_lod` payload lists
+pub(crate) fn lod_ids(node: &Node) -> Vec<usize> {
+    node.extensions
+        .as_ref()
+        .and_then(|e| e.others.get(LOD_EXTENSION))
+        .and_then(|lod| lod.get("ids"))
+        .and_then(Value::as_array)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_u64().map(|id| id as usize))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Size factor of each texture only lower levels of detail use, `texture_scale` per level
+///
+/// A texture takes the factor of the most detailed level drawing it, so one also drawn at full
+/// detail is left out.
+pub(crate) fn lod_texture_scales(o_json: &Root, texture_scale: f32) -> HashMap<usize, f32> {
+    // Level each node is drawn at, 0 unless a MSFT_lod payload lists it
+    let mut node_levels = vec![0; o_json.nodes.len()];
+    for node in &o_json.nodes {
+        for (level, id) in lod_ids(node).into_iter().enumerate() {
+            if let Some(slot) = node_levels.get_mut(id) {
+                *slot = level + 1;
+            }
+        }
+    }
+
+    let mut texture_levels: HashMap<usize, usize> = HashMap::new();
+    for (node, &level) in o_json.nodes.iter().zip(&node_levels) {
+        let Some(mesh) = node.mesh.and_then(|m| o_json.meshes.get(m.value())) else {
+            continue;
+        };
+        let materials = mesh
+            .primitives
+            .iter()
+            .filter_map(|p| o_json.materials.get(p.material?.value()));
+        for material in materials {
+            let pbr = &material.pbr_metallic_roughness;
+            let textures = [
+                pbr.base_color_texture.as_ref().map(|i| i.index),
+                pbr.metallic_roughness_texture.as_ref().map(|i| i.index),
+                material.normal_texture.as_ref().map(|i| i.index),
+                material.occlusion_texture.as_ref().map(|i| i.index),
+                material.emissive_texture.as_ref().map(|i| i.index),
+            ];
+            for texture in textures.into_iter().flatten() {
+                let slot = texture_levels.entry(texture.value()).or_insert(level);
+                *slot = (*slot).min(level);
+            }
+        }
+    }
+
+    texture_levels
+        .into_iter()
+        .filter(|(_, level)| *level > 0)
+        .map(|(texture, level)| (texture, texture_scale.powi(level as i32)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::{accessor::Type, validation::Checked};
+
+    use super::*;
+    use crate::{
+        glb::write_glb,
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        stats::image_dimensions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn lod_levels_are_linked_with_msft_lod() {
+        use crate::glb::{from_extras, push_attribute, push_indices, push_positions, read_glb};
+        use gltf::json::mesh::Semantic;
+
+        // A flat 10x10 quad grid in place of the synthetic quad
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let points: Vec<[f32; 2]> = (0..11u32)
+            .flat_map(|y| (0..11u32).map(move |x| [x as f32 / 10.0, y as f32 / 10.0]))
+            .collect();
+        let positions: Vec<[f32; 3]> = points.iter().map(|&[x, y]| [x, y, 0.0]).collect();
+        let indices: Vec<u32> = (0..10u32)
+            .flat_map(|y| (0..10u32).map(move |x| y * 11 + x))
+            .flat_map(|v| [v, v + 1, v + 12, v, v + 12, v + 11])
+            .collect();
+        let position = push_positions(&mut blob, &mut json, &positions);
+        let uv = push_attribute(&mut blob, &mut json, points.as_flattened(), Type::Vec2);
+        let indices = push_indices(&mut blob, &mut json, &indices);
+        let primitive = &mut json.meshes[0].primitives[0];
+        primitive.attributes = [
+            (Checked::Valid(Semantic::Positions), position),
+            (Checked::Valid(Semantic::TexCoords(0)), uv),
+        ]
+        .into();
+        primitive.indices = Some(indices);
+        let glb = write_glb(&json, blob.into_vec()).unwrap();
+
+        let options = OptimizeOptions {
+            lods: Some(LodOptions {
+                ratios: vec![0.5, 0.1],
+                max_error: 0.05,
+                texture_scale: Some(0.5),
+            }),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(n_json.extensions_used.contains(&LOD_EXTENSION.to_string()));
+        assert!(
+            !n_json
+                .extensions_required
+                .contains(&LOD_EXTENSION.to_string())
+        );
+
+        let node = n_json.nodes.iter().find(|n| n.mesh.is_some()).unwrap();
+        let lod = &node.extensions.as_ref().unwrap().others[LOD_EXTENSION];
+        let ids: Vec<usize> = serde_json::from_value(lod["ids"].clone()).unwrap();
+        assert_eq!(ids.len(), 2);
+        let coverage = from_extras(&node.extras).unwrap()["MSFT_screencoverage"].clone();
+        assert_eq!(coverage, serde_json::json!([0.5, 0.1, 0.0]));
+
+        // Levels draw fewer triangles and smaller textures than the node they stand in for
+        let level = |node: &gltf::json::Node| {
+            let primitive = &n_json.meshes[node.mesh.unwrap().value()].primitives[0];
+            let triangles = n_json.accessors[primitive.indices.unwrap().value()].count.0 / 3;
+            let material = &n_json.materials[primitive.material.unwrap().value()];
+            let texture = material
+                .pbr_metallic_roughness
+                .base_color_texture
+                .as_ref()
+                .unwrap()
+                .index;
+            let data = get_image_data(&n_blob, &n_json, texture).unwrap();
+            (triangles, image_dimensions(data).unwrap().0)
+        };
+        let full = level(node);
+        assert_eq!(full, (200, 32));
+        let mut previous = full;
+        for id in ids {
+            let lod_node = &n_json.nodes[id];
+            assert!(
+                n_json
+                    .scenes
+                    .iter()
+                    .all(|s| !s.nodes.contains(&Index::new(id as u32)))
+            );
+            let current = level(lod_node);
+            assert!(current.0 < previous.0 || (current.0 == previous.0 && previous.0 < 200));
+            assert_eq!(current.1, previous.1 / 2);
+            previous = current;
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::{Cursor, Read, Seek};
+
+use gltf::json::Root;
+use serde_json::{Value, json};
+
+use crate::{
+    error::OptimizeError,
+    glb::read_glb,
+    report::stored_image,
+    stats::{primitive_triangles, vertex_count},
+};
+
+/// Version of the manifest layout, bumped when fields change meaning
+const MANIFEST_VERSION: u64 = 1;
+
+/// Manifest of a parsed GLB of `file_bytes` bytes
+///
+/// Textures list the image they sample as stored: dimensions, stored mip levels and bytes
+/// for embedded images, the URI for external ones.
+pub(crate) fn manifest(json: &Root, blob: &[u8], file_bytes: u64) -> Value {
+    let textures: Vec<Value> = json
+        .textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| {
+            let image = json.images.get(texture.source.value());
+            let mut entry = json!({
+                "index": i,
+                "name": texture.name,
+                "image": texture.source.value(),
+                "mimeType": image.and_then(|img| img.mime_type.as_ref()).map(|m| &m.0),
+            });
+            if let Some(uri) = image.and_then(|img| img.uri.as_ref()) {
+                entry["uri"] = uri.as_str().into();
+            }
+            if let Some(stored) = stored_image(json, blob, i) {
+                entry["width"] = stored.width.into();
+                entry["height"] = stored.height.into();
+                entry["levels"] = stored.levels.len().into();
+                entry["bytes"] = stored.bytes.into();
+            }
+            entry
+        })
+        .collect();
+    let meshes: Vec<Value> = json
+        .meshes
+        .iter()
+        .enumerate()
+        .map(|(i, mesh)| {
+            let (vertices, triangles) = mesh.primitives.iter().fold((0, 0), |(v, t), p| {
+                (v + vertex_count(json, p), t + primitive_triangles(json, p))
+            });
+            let targets = mesh.primitives.first().and_then(|p| p.targets.as_ref());
+            json!({
+                "index": i,
+                "name": mesh.name,
+                "primitives": mesh.primitives.len(),
+                "vertices": vertices,
+                "triangles": triangles,
+                "morphTargets": targets.map_or(0, Vec::len),
+            })
+        })
+        .collect();
+    let animations: Vec<Value> = json
+        .animations
+        .iter()
+        .enumerate()
+        .map(|(i, animation)| {
+            // Keyframe times start at 0 or later, so the largest input max is the duration
+            let duration = animation
+                .samplers
+                .iter()
+                .filter_map(|s| {
+                    json.accessors
+                        .get(s.input.value())?
+                        .max
+                        .as_ref()?
+                        .get(0)?
+                        .as_f64()
+                })
+                .fold(0.0, f64::max);
+            json!({
+                "index": i,
+                "name": animation.name,
+                "channels": animation.channels.len(),
+                "duration": duration,
+            })
+        })
+        .collect();
+
+    json!({
+        "version": MANIFEST_VERSION,
+        "bytes": file_bytes,
+        "textures": textures,
+        "meshes": meshes,
+        "materials": json.materials.len(),
+        "animations": animations,
+        "extensionsUsed": json.extensions_used,
+        "extensionsRequired": json.extensions_required,
+    })
+}
+
+/// Machine-readable summary of a GLB, so asset databases can index it without parsing glTF
+///
+/// Pretty-printed JSON with the file size, every texture (name, MIME type, dimensions, stored
+/// mip levels and bytes), every mesh (primitives, vertices, triangles, morph targets), the
+/// material count, every animation (channels, duration in seconds) and the extensions used
+/// and required. [`BatchJob::manifests`](crate::BatchJob::manifests) writes one
+/// beside every output.
+pub fn asset_manifest<R: Read + Seek>(reader: &mut R) -> Result<String, OptimizeError> {
+    let mut glb = Vec::new();
+    reader.read_to_end(&mut glb)?;
+    let (json, blob) = read_glb(&mut Cursor::new(&glb))?;
+    Ok(serde_json::to_string_pretty(&manifest(
+        &json,
+        &blob,
+        glb.len() as u64,
+    ))?)
+}
@@ -0,0 +1,186 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{
+    Material, Root,
+    material::{EmissiveFactor, PbrBaseColorFactor, StrengthFactor},
+};
+
+use crate::{
+    error::OptimizeError,
+    glb::{read_glb, write_glb},
+};
+
+/// Factor values to set on every material named `name`; `None` keeps the current value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialEdit {
+    pub name: String,
+    pub base_color_factor: Option<[f32; 4]>,
+    pub metallic_factor: Option<f32>,
+    pub roughness_factor: Option<f32>,
+    pub emissive_factor: Option<[f32; 3]>,
+}
+
+impl MaterialEdit {
+    /// Error for the first factor outside the 0..=1 range glTF allows
+    fn validate(&self) -> Result<(), OptimizeError> {
+        let factors = self
+            .base_color_factor
+            .iter()
+            .flatten()
+            .map(|v| ("baseColorFactor", *v))
+            .chain(self.metallic_factor.map(|v| ("metallicFactor", v)))
+            .chain(self.roughness_factor.map(|v| ("roughnessFactor", v)))
+            .chain(
+                self.emissive_factor
+                    .iter()
+                    .flatten()
+                    .map(|v| ("emissiveFactor", *v)),
+            );
+        for (factor, value) in factors {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(OptimizeError::InvalidOption(format!(
+                    "material '{}': {factor} {value} is outside 0..=1",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Set the factors of one edit on `material`, whatever its name
+pub(crate) fn apply_material_edit(
+    material: &mut Material,
+    edit: &MaterialEdit,
+) -> Result<(), OptimizeError> {
+    edit.validate()?;
+    let pbr = &mut material.pbr_metallic_roughness;
+    if let Some(factor) = edit.base_color_factor {
+        pbr.base_color_factor = PbrBaseColorFactor(factor);
+    }
+    if let Some(factor) = edit.metallic_factor {
+        pbr.metallic_factor = StrengthFactor(factor);
+    }
+    if let Some(factor) = edit.roughness_factor {
+        pbr.roughness_factor = StrengthFactor(factor);
+    }
+    if let Some(factor) = edit.emissive_factor {
+        material.emissive_factor = EmissiveFactor(factor);
+    }
+    Ok(())
+}
+
+/// Apply `edits` in order, returning the names that matched no material
+pub(crate) fn apply_material_edits(
+    n_json: &mut Root,
+    edits: &[MaterialEdit],
+) -> Result<Vec<String>, OptimizeError> {
+    let mut unmatched = Vec::new();
+    for edit in edits {
+        edit.validate()?;
+        let mut matched = false;
+        for material in &mut n_json.materials {
+            if material.name.as_deref() != Some(edit.name.as_str()) {
+                continue;
+            }
+            matched = true;
+            apply_material_edit(material, edit)?;
+        }
+        if !matched {
+            unmatched.push(edit.name.clone());
+        }
+    }
+    Ok(unmatched)
+}
+
+/// Set material factors by material name and write the GLB again, leaving the binary chunk as is
+///
+/// Returns the GLB and the names of edits that matched no material.
+pub fn edit_materials<R: Read + Seek>(
+    reader: &mut R,
+    edits: &[MaterialEdit],
+) -> Result<(Vec<u8>, Vec<String>), OptimizeError> {
+    let (mut o_json, o_blob) = read_glb(reader)?;
+    let unmatched = apply_material_edits(&mut o_json, edits)?;
+    Ok((write_glb(&o_json, o_blob)?, unmatched))
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        opt::ImageFormat, optimizer::Optimizer, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    fn two_materials() -> Vec<u8> {
+        SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    fn tint() -> Vec<MaterialEdit> {
+        vec![
+            MaterialEdit {
+                name: "material_1".into(),
+                base_color_factor: Some([0.5, 0.5, 0.5, 1.0]),
+                roughness_factor: Some(0.25),
+                ..Default::default()
+            },
+            MaterialEdit {
+                name: "missing".into(),
+                metallic_factor: Some(1.0),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn assert_tinted(json: &Root) {
+        let pbr = |m: usize| &json.materials[m].pbr_metallic_roughness;
+        assert_eq!(pbr(0).base_color_factor.0, [1.0; 4]);
+        assert_eq!(pbr(1).base_color_factor.0, [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(pbr(1).roughness_factor.0, 0.25);
+        assert_eq!(pbr(1).metallic_factor.0, 1.0);
+    }
+
+    #[test]
+    fn edits_set_factors_by_name() {
+        let glb = two_materials();
+        let (out, unmatched) = edit_materials(&mut Cursor::new(&glb), &tint()).unwrap();
+        assert_eq!(unmatched, ["missing"]);
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_tinted(&json);
+        assert_eq!(blob, read_glb(&mut Cursor::new(&glb)).unwrap().1);
+    }
+
+    #[test]
+    fn edits_are_kept_through_optimization() {
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        let edits = tint();
+        let optimized = Optimizer::new(&options)
+            .material_edits(&edits)
+            .run(&mut Cursor::new(two_materials()))
+            .unwrap();
+        assert_eq!(optimized.unmatched_edits, ["missing"]);
+        assert_tinted(&read_glb(&mut Cursor::new(optimized.glb)).unwrap().0);
+    }
+
+    #[test]
+    fn factors_outside_the_unit_range_are_refused() {
+        let edit = MaterialEdit {
+            name: "material_0".into(),
+            emissive_factor: Some([0.0, 2.0, 0.0]),
+            ..Default::default()
+        };
+        let err = edit_materials(&mut Cursor::new(two_materials()), &[edit]).unwrap_err();
+        assert!(matches!(err, OptimizeError::InvalidOption(m) if m.contains("emissiveFactor 2")));
+    }
+}
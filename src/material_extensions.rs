@@ -0,0 +1,194 @@
+use gltf::json::Root;
+use serde_json::Value;
+
+use crate::{error::OptimizeError, opt::TextureType};
+
+/// `KHR_materials_*` extensions whose payloads are carried through the rebuild, their
+/// textures copied along
+pub(crate) const MATERIAL_EXTENSIONS: [&str; 11] = [
+    "KHR_materials_anisotropy",
+    "KHR_materials_clearcoat",
+    "KHR_materials_diffuse_transmission",
+    "KHR_materials_dispersion",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_ior",
+    "KHR_materials_iridescence",
+    "KHR_materials_sheen",
+    "KHR_materials_specular",
+    "KHR_materials_transmission",
+    "KHR_materials_volume",
+];
+
+/// Texture infos of [`MATERIAL_EXTENSIONS`] resized and re-encoded like a core slot
+///
+/// Single-channel data in red or green is handled as metallic/roughness, colors as emissive.
+/// Textures not listed, such as those keeping data in alpha (`sheenRoughnessTexture`,
+/// `specularTexture`) or direction vectors (`anisotropyTexture`), are copied as they are.
+const EXTENSION_TEXTURES: [(&str, &str, TextureType); 10] = [
+    (
+        "KHR_materials_clearcoat",
+        "clearcoatTexture",
+        TextureType::MetallicRoughness,
+    ),
+    (
+        "KHR_materials_clearcoat",
+        "clearcoatRoughnessTexture",
+        TextureType::MetallicRoughness,
+    ),
+    (
+        "KHR_materials_clearcoat",
+        "clearcoatNormalTexture",
+        TextureType::Normal,
+    ),
+    (
+        "KHR_materials_diffuse_transmission",
+        "diffuseTransmissionColorTexture",
+        TextureType::Emissive,
+    ),
+    (
+        "KHR_materials_iridescence",
+        "iridescenceTexture",
+        TextureType::MetallicRoughness,
+    ),
+    (
+        "KHR_materials_iridescence",
+        "iridescenceThicknessTexture",
+        TextureType::MetallicRoughness,
+    ),
+    (
+        "KHR_materials_sheen",
+        "sheenColorTexture",
+        TextureType::Emissive,
+    ),
+    (
+        "KHR_materials_specular",
+        "specularColorTexture",
+        TextureType::Emissive,
+    ),
+    (
+        "KHR_materials_transmission",
+        "transmissionTexture",
+        TextureType::MetallicRoughness,
+    ),
+    (
+        "KHR_materials_volume",
+        "thicknessTexture",
+        TextureType::MetallicRoughness,
+    ),
+];
+
+/// Slot type a texture info of a material extension is processed as, `None` to copy it
+pub(crate) fn extension_texture_type(extension: &str, key: &str) -> Option<TextureType> {
+    EXTENSION_TEXTURES
+        .iter()
+        .find(|(e, k, _)| *e == extension && *k == key)
+        .map(|(_, _, texture_type)| *texture_type)
+}
+
+/// Replace `{"index": n}` objects stored under keys ending in `Texture` with what `copy`
+/// returns for the key and source texture
+pub(crate) fn rewrite_texture_infos(
+    payload: &mut Value,
+    copy: &mut dyn FnMut(&str, usize) -> Result<usize, OptimizeError>,
+) -> Result<(), OptimizeError> {
+    let Some(object) = payload.as_object_mut() else {
+        return Ok(());
+    };
+    for (key, value) in object.iter_mut() {
+        if key.ends_with("Texture")
+            && let Some(index) = value.get("index").and_then(Value::as_u64)
+        {
+            value["index"] = copy(key, index as usize)?.into();
+        } else {
+            rewrite_texture_infos(value, copy)?;
+        }
+    }
+    Ok(())
+}
+
+/// List every [`MATERIAL_EXTENSIONS`] entry an output material carries as used
+pub(crate) fn list_material_extensions(n_json: &mut Root) {
+    for name in MATERIAL_EXTENSIONS {
+        let carried = n_json
+            .materials
+            .iter()
+            .filter_map(|m| m.extensions.as_ref())
+            .any(|e| e.others.contains_key(name));
+        if carried && !n_json.extensions_used.iter().any(|e| e == name) {
+            n_json.extensions_used.push(name.to_string());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn textures_used_only_by_material_extensions_are_carried_over() {
+        // Material 0 draws; textures 1 and 2 are referenced by its extensions alone
+        let glb = SyntheticGlb {
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["materials"][0]["extensions"] = json!({
+            "KHR_materials_clearcoat": {
+                "clearcoatFactor": 1.0,
+                "clearcoatNormalTexture": { "index": 1, "scale": 0.5 }
+            },
+            "KHR_materials_sheen": { "sheenRoughnessTexture": { "index": 2 } },
+            "VENDOR_materials_glitter": { "glitterTexture": { "index": 2 } }
+        });
+        doc["extensionsUsed"] = json!([
+            "KHR_materials_clearcoat",
+            "KHR_materials_sheen",
+            "VENDOR_materials_glitter"
+        ]);
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let options = OptimizeOptions {
+            texture_size: 16,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        gltf::Gltf::from_slice(&out).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+        assert_eq!(n_json.textures.len(), 3);
+        let extensions = &n_json.materials[0].extensions.as_ref().unwrap().others;
+        assert!(!extensions.contains_key("VENDOR_materials_glitter"));
+        assert_eq!(
+            n_json.extensions_used,
+            ["KHR_materials_clearcoat", "KHR_materials_sheen"]
+        );
+
+        let size = |payload: &Value| {
+            let texture = payload["index"].as_u64().unwrap() as u32;
+            let data = get_image_data(&n_blob, &n_json, Index::new(texture)).unwrap();
+            image::load_from_memory(data).unwrap().width()
+        };
+        let clearcoat = &extensions["KHR_materials_clearcoat"];
+        assert_eq!(clearcoat["clearcoatNormalTexture"]["scale"], 0.5);
+        // Resized like a normal map, while alpha-channel data is copied as it is
+        assert_eq!(size(&clearcoat["clearcoatNormalTexture"]), 16);
+        assert_eq!(
+            size(&extensions["KHR_materials_sheen"]["sheenRoughnessTexture"]),
+            32
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use gltf::json::{
+    Accessor, Index, Root,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{gather_accessors, push_indices},
+    hints::object_hints,
+    opt::get_index_data,
+};
+
+/// What primitives must share to be drawn as one
+#[derive(Debug, PartialEq)]
+struct MergeKey {
+    material: Option<usize>,
+    mode: Mode,
+    /// Semantic, component type, type and normalization of every attribute, in order
+    attributes: Vec<(String, String, bool)>,
+    extras: Option<String>,
+}
+
+/// Key and vertex count of a primitive that can be merged, `None` for one left alone
+///
+/// Strips, fans and loops can't simply be concatenated, and morph targets, extensions and
+/// sparse or mismatched attributes are left to other passes.
+fn merge_key(o_json: &Root, primitive: &Primitive) -> Option<(MergeKey, usize)> {
+    let mode = match primitive.mode {
+        Checked::Valid(mode @ (Mode::Points | Mode::Lines | Mode::Triangles)) => mode,
+        _ => return None,
+    };
+    if primitive.targets.as_ref().is_some_and(|t| !t.is_empty()) || primitive.extensions.is_some() {
+        return None;
+    }
+    let position = primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    let count = o_json.accessors.get(position.value())?.count.0 as usize;
+
+    let mut attributes = Vec::with_capacity(primitive.attributes.len());
+    for (semantic, accessor) in &primitive.attributes {
+        let accessor = o_json.accessors.get(accessor.value())?;
+        if accessor.sparse.is_some() || accessor.count.0 as usize != count {
+            return None;
+        }
+        let layout = serde_json::to_string(&(accessor.component_type, accessor.type_)).ok()?;
+        attributes.push((
+            serde_json::to_string(semantic).ok()?,
+            layout,
+            accessor.normalized,
+        ));
+    }
+
+    let key = MergeKey {
+        material: primitive.material.map(|m| m.value()),
+        mode,
+        attributes,
+        extras: primitive.extras.as_ref().map(|e| e.get().to_string()),
+    };
+    Some((key, count))
+}
+
+/// Concatenate `group` into one primitive, `None` when an accessor can't be read
+fn merge_group(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    group: &[(&Primitive, usize)],
+) -> Option<Primitive> {
+    let (first, _) = group.first()?;
+
+    let mut indices = Vec::new();
+    let mut vertices: Vec<Vec<u32>> = Vec::with_capacity(group.len());
+    for (primitive, count) in group {
+        let offset = vertices.iter().map(Vec::len).sum::<usize>() as u32;
+        let own = match primitive.indices {
+            Some(idx) => get_index_data(o_blob, o_json, idx)?,
+            None => (0..*count as u32).collect(),
+        };
+        if own.iter().any(|&i| i as usize >= *count) {
+            return None;
+        }
+        indices.extend(own.iter().map(|i| i + offset));
+        vertices.push((0..*count as u32).collect());
+    }
+
+    let mut merged = (*first).clone();
+    for semantic in first.attributes.keys() {
+        let parts: Vec<(Index<Accessor>, &[u32])> = group
+            .iter()
+            .zip(&vertices)
+            .map(|((primitive, _), v)| Some((*primitive.attributes.get(semantic)?, v.as_slice())))
+            .collect::<Option<_>>()?;
+        let gathered = gather_accessors(o_blob, o_json, n_blob, n_json, &parts)?;
+        merged.attributes.insert(semantic.clone(), gathered);
+    }
+    merged.indices = Some(push_indices(n_blob, n_json, &indices));
+    Some(merged)
+}
+
+/// Merge the primitives of each mesh that share a material, attribute layout and mode
+///
+/// Each set becomes one primitive at the place of its first member, with vertex attributes
+/// concatenated and indices offset to match; unindexed primitives get sequential indices.
+/// Primitives of different meshes are never merged, since their nodes may place them apart.
+/// Returns `None` when nothing was merged.
+pub(crate) fn merge_primitives(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed = false;
+
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        if object_hints(&mesh.extras).skip {
+            continue;
+        }
+        // Groups of mergeable primitives, in order of their first member
+        let mut groups: Vec<(MergeKey, Vec<(&Primitive, usize)>)> = Vec::new();
+        let mut order = Vec::with_capacity(mesh.primitives.len());
+        for primitive in &mesh.primitives {
+            let Some((key, count)) = merge_key(o_json, primitive) else {
+                order.push(Err(primitive));
+                continue;
+            };
+            match groups.iter().position(|(k, _)| *k == key) {
+                Some(g) => groups[g].1.push((primitive, count)),
+                None => {
+                    order.push(Ok(groups.len()));
+                    groups.push((key, vec![(primitive, count)]));
+                }
+            }
+        }
+        if groups.iter().all(|(_, members)| members.len() < 2) {
+            continue;
+        }
+
+        let mut primitives = Vec::with_capacity(order.len());
+        for slot in order {
+            let group = match slot {
+                Ok(g) => &groups[g].1,
+                Err(primitive) => {
+                    primitives.push(primitive.clone());
+                    continue;
+                }
+            };
+            let merged = match group.len() {
+                1 => None,
+                _ => merge_group(o_blob, o_json, &mut n_blob, &mut n_json, group),
+            };
+            match merged {
+                Some(merged) => {
+                    primitives.push(merged);
+                    changed = true;
+                }
+                None => primitives.extend(group.iter().map(|(p, _)| (*p).clone())),
+            }
+        }
+        n_json.meshes[m].primitives = primitives;
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn primitives_sharing_a_material_merge() {
+        use crate::{glb::read_glb, stats::compute_gpu_stats};
+
+        // Primitives take the two materials in turn
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 4,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let stats = |merge_primitives| {
+            let options = OptimizeOptions {
+                merge_primitives,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            (compute_gpu_stats(&n_blob, &n_json), n_json)
+        };
+        let (separate, _) = stats(false);
+        let (merged, n_json) = stats(true);
+        assert_eq!(separate.draw_calls, 4);
+        assert_eq!(merged.draw_calls, 2);
+        assert_eq!(merged.triangles, separate.triangles);
+        assert_eq!(merged.vertices, separate.vertices);
+
+        let primitives = &n_json.meshes[0].primitives;
+        assert_ne!(primitives[0].material, primitives[1].material);
+        for primitive in primitives {
+            let position =
+                primitive.attributes[&Checked::Valid(gltf::json::mesh::Semantic::Positions)];
+            let position = &n_json.accessors[position.value()];
+            assert!(position.min.is_some() && position.max.is_some());
+        }
+    }
+}
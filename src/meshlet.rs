@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use gltf::json::{
+    Accessor, Index, Root,
+    accessor::{ComponentType, GenericComponentType, Type},
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    error::OptimizeError,
+    glb::push_indices,
+    opt::{get_index_data, get_position_data},
+};
+
+/// Primitive extension holding meshlet ranges and bounds
+pub(crate) const MESHLET_EXTENSION: &str = "GLTFOPT_meshlets";
+
+/// Limits of the clusters built by
+/// [`OptimizeOptions::meshlets`](crate::OptimizeOptions::meshlets)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MeshletOptions {
+    /// Most unique vertices per meshlet
+    pub max_vertices: usize,
+    /// Most triangles per meshlet
+    pub max_triangles: usize,
+}
+
+impl Default for MeshletOptions {
+    fn default() -> Self {
+        MeshletOptions {
+            max_vertices: 64,
+            max_triangles: 124,
+        }
+    }
+}
+
+/// Group triangles into meshlets, returning the triangle indices of each
+///
+/// Greedy: a meshlet grows by the neighbouring triangle that adds the fewest new vertices,
+/// falling back to the next unassigned triangle in index order when it has no neighbours left.
+fn build_meshlets(
+    indices: &[u32],
+    vertex_count: usize,
+    limits: &MeshletOptions,
+) -> Vec<Vec<usize>> {
+    let triangle_count = indices.len() / 3;
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for &v in tri {
+            adjacency[v as usize].push(t);
+        }
+    }
+
+    let mut assigned = vec![false; triangle_count];
+    // Meshlet a vertex was last added to, plus one
+    let mut in_meshlet = vec![0usize; vertex_count];
+    let mut meshlets = Vec::new();
+    let mut next = 0;
+
+    while next < triangle_count {
+        let id = meshlets.len() + 1;
+        let mut triangles = Vec::new();
+        let mut vertices = 0;
+        let mut candidates: Vec<usize> = Vec::new();
+
+        loop {
+            let new_vertices = |t: usize| {
+                indices[t * 3..t * 3 + 3]
+                    .iter()
+                    .filter(|&&v| in_meshlet[v as usize] != id)
+                    .count()
+            };
+            candidates.retain(|&t| !assigned[t]);
+            let neighbour = candidates
+                .iter()
+                .copied()
+                .min_by_key(|&t| (new_vertices(t), t))
+                .filter(|&t| vertices + new_vertices(t) <= limits.max_vertices);
+            let best = match neighbour {
+                Some(t) => t,
+                None => {
+                    while next < triangle_count && assigned[next] {
+                        next += 1;
+                    }
+                    if next == triangle_count || vertices + new_vertices(next) > limits.max_vertices
+                    {
+                        break;
+                    }
+                    next
+                }
+            };
+
+            assigned[best] = true;
+            triangles.push(best);
+            for &v in &indices[best * 3..best * 3 + 3] {
+                if in_meshlet[v as usize] != id {
+                    in_meshlet[v as usize] = id;
+                    vertices += 1;
+                    candidates.extend(adjacency[v as usize].iter().filter(|&&t| !assigned[t]));
+                }
+            }
+            if triangles.len() >= limits.max_triangles {
+                break;
+            }
+        }
+
+        if triangles.is_empty() {
+            break;
+        }
+        meshlets.push(triangles);
+    }
+    meshlets
+}
+
+/// Bounding sphere (center, radius) of the vertices of some triangles
+fn bounding_sphere(positions: &[[f32; 3]], indices: &[u32], triangles: &[usize]) -> [f32; 4] {
+    let vertices = || {
+        triangles
+            .iter()
+            .flat_map(|&t| &indices[t * 3..t * 3 + 3])
+            .map(|&v| positions[v as usize])
+    };
+    let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+    for p in vertices() {
+        for a in 0..3 {
+            min[a] = min[a].min(p[a]);
+            max[a] = max[a].max(p[a]);
+        }
+    }
+    let center = [0, 1, 2].map(|a| (min[a] + max[a]) * 0.5);
+    let radius = vertices()
+        .map(|p| (0..3).map(|a| (p[a] - center[a]).powi(2)).sum::<f32>())
+        .fold(0.0f32, f32::max)
+        .sqrt();
+    [center[0], center[1], center[2], radius]
+}
+
+/// Append float or u32 elements as a new accessor
+fn push_elements(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    bytes: &[u8],
+    count: usize,
+    component: ComponentType,
+    type_: Type,
+) -> Index<Accessor> {
+    let view = n_blob.push_view(n_json, bytes, None);
+    n_json.push(Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        count: count.into(),
+        component_type: Checked::Valid(GenericComponentType(component)),
+        extensions: None,
+        extras: Default::default(),
+        type_: Checked::Valid(type_),
+        min: None,
+        max: None,
+        name: None,
+        normalized: false,
+        sparse: None,
+    })
+}
+
+/// Overwrite a tightly packed index accessor's data in place, `None` when its bytes are shared
+fn overwrite_indices(
+    n_blob: &mut BlobWriter,
+    n_json: &Root,
+    index: Index<Accessor>,
+    indices: &[u32],
+) -> Option<()> {
+    let accessor = n_json.accessors.get(index.value())?;
+    let view = n_json.buffer_views.get(accessor.buffer_view?.value())?;
+    let size = match accessor.component_type {
+        Checked::Valid(GenericComponentType(ComponentType::U8)) => 1,
+        Checked::Valid(GenericComponentType(ComponentType::U16)) => 2,
+        Checked::Valid(GenericComponentType(ComponentType::U32)) => 4,
+        _ => return None,
+    };
+    if view.byte_stride.is_some_and(|s| s.0 != size) {
+        return None;
+    }
+    let start = view.byte_offset.map_or(0, |o| o.0 as usize)
+        + accessor.byte_offset.map_or(0, |o| o.0 as usize);
+    let bytes: Vec<u8> = indices
+        .iter()
+        .flat_map(|v| v.to_le_bytes()[..size].to_vec())
+        .collect();
+    n_blob.overwrite(start, &bytes)
+}
+
+/// Build meshlets for one output primitive, `None` when it is skipped
+fn add_meshlets(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    primitive: &mut Primitive,
+    shared: bool,
+    limits: &MeshletOptions,
+) -> Option<()> {
+    if primitive.mode != Checked::Valid(Mode::Triangles) {
+        return None;
+    }
+    let position = *primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    let positions = get_position_data(n_blob, n_json, position)?;
+    let indices = match primitive.indices {
+        Some(idx) => get_index_data(n_blob, n_json, idx)?,
+        None => (0..positions.len() as u32).collect(),
+    };
+    if indices.len() < 3 || indices.iter().any(|&i| i as usize >= positions.len()) {
+        return None;
+    }
+
+    let meshlets = build_meshlets(&indices, positions.len(), limits);
+    let mut reordered = Vec::with_capacity(indices.len());
+    let mut ranges = Vec::with_capacity(meshlets.len() * 8);
+    let mut bounds = Vec::with_capacity(meshlets.len() * 16);
+    for triangles in &meshlets {
+        let first = reordered.len() as u32;
+        for &t in triangles {
+            reordered.extend_from_slice(&indices[t * 3..t * 3 + 3]);
+        }
+        ranges.extend(first.to_le_bytes());
+        ranges.extend((triangles.len() as u32 * 3).to_le_bytes());
+        let sphere = bounding_sphere(&positions, &indices, triangles);
+        bounds.extend(sphere.iter().flat_map(|v| v.to_le_bytes()));
+    }
+    // A trailing partial triangle stays where it was
+    reordered.extend_from_slice(&indices[reordered.len()..]);
+
+    let overwritten = primitive
+        .indices
+        .filter(|_| !shared)
+        .and_then(|idx| overwrite_indices(n_blob, n_json, idx, &reordered));
+    if overwritten.is_none() {
+        primitive.indices = Some(push_indices(n_blob, n_json, &reordered));
+    }
+    let ranges = push_elements(
+        n_blob,
+        n_json,
+        &ranges,
+        meshlets.len(),
+        ComponentType::U32,
+        Type::Vec2,
+    );
+    let bounds = push_elements(
+        n_blob,
+        n_json,
+        &bounds,
+        meshlets.len(),
+        ComponentType::F32,
+        Type::Vec4,
+    );
+
+    primitive
+        .extensions
+        .get_or_insert_with(Default::default)
+        .others
+        .insert(
+            MESHLET_EXTENSION.to_string(),
+            serde_json::json!({
+                "maxVertices": limits.max_vertices,
+                "maxTriangles": limits.max_triangles,
+                "ranges": ranges.value(),
+                "bounds": bounds.value(),
+            }),
+        );
+    Some(())
+}
+
+/// Split every triangle primitive of the output into meshlets for GPU-driven rendering
+///
+/// Each primitive's index buffer is reordered so that every meshlet is a contiguous range of
+/// indices, and the ranges and bounding spheres are stored in a
+/// [`MESHLET_EXTENSION`] payload. Primitives whose buffers a geometry codec replaced are skipped.
+pub(crate) fn add_meshlet_metadata(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    limits: &MeshletOptions,
+) -> Result<(), OptimizeError> {
+    if limits.max_vertices < 3 || limits.max_triangles == 0 {
+        return Err(OptimizeError::InvalidOption(
+            "meshlets need at least 3 vertices and 1 triangle".to_string(),
+        ));
+    }
+
+    let mut index_uses: HashMap<usize, usize> = HashMap::new();
+    for primitive in n_json.meshes.iter().flat_map(|m| &m.primitives) {
+        if let Some(idx) = primitive.indices {
+            *index_uses.entry(idx.value()).or_default() += 1;
+        }
+    }
+
+    let mut added = false;
+    for m in 0..n_json.meshes.len() {
+        for p in 0..n_json.meshes[m].primitives.len() {
+            let mut primitive = n_json.meshes[m].primitives[p].clone();
+            let shared = primitive
+                .indices
+                .is_some_and(|idx| index_uses[&idx.value()] > 1);
+            if add_meshlets(n_blob, n_json, &mut primitive, shared, limits).is_some() {
+                n_json.meshes[m].primitives[p] = primitive;
+                added = true;
+            }
+        }
+    }
+
+    if added
+        && !n_json
+            .extensions_used
+            .iter()
+            .any(|e| e == MESHLET_EXTENSION)
+    {
+        n_json.extensions_used.push(MESHLET_EXTENSION.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        convert::read_accessor, glb::read_glb, opt::optimize_with, options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn meshlets_respect_their_limits() {
+        // A strip of eight triangles over ten vertices
+        let indices: Vec<u32> = (0..8u32).flat_map(|t| [t, t + 1, t + 2]).collect();
+        let limits = MeshletOptions {
+            max_vertices: 5,
+            max_triangles: 2,
+        };
+        let meshlets = build_meshlets(&indices, 10, &limits);
+
+        let mut seen: Vec<usize> = meshlets.iter().flatten().copied().collect();
+        seen.sort();
+        assert_eq!(seen, (0..8).collect::<Vec<_>>());
+        for triangles in &meshlets {
+            assert!(triangles.len() <= limits.max_triangles);
+            let mut vertices: Vec<u32> = (triangles.iter())
+                .flat_map(|&t| indices[t * 3..t * 3 + 3].to_vec())
+                .collect();
+            vertices.sort();
+            vertices.dedup();
+            assert!(vertices.len() <= limits.max_vertices);
+        }
+    }
+
+    #[test]
+    fn primitives_carry_meshlet_ranges_and_bounds() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let options = OptimizeOptions {
+            meshlets: Some(MeshletOptions {
+                max_vertices: 3,
+                max_triangles: 1,
+            }),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(json.extensions_used.iter().any(|e| e == MESHLET_EXTENSION));
+
+        let primitive = &json.meshes[0].primitives[0];
+        let payload = &primitive.extensions.as_ref().unwrap().others[MESHLET_EXTENSION];
+        let accessor = |key: &str| Index::new(payload[key].as_u64().unwrap() as u32);
+        let ranges = read_accessor::<2>(&blob, &json, accessor("ranges")).unwrap();
+        assert_eq!(ranges, [[0.0, 3.0], [3.0, 3.0]]);
+        let spheres = read_accessor::<4>(&blob, &json, accessor("bounds")).unwrap();
+        assert_eq!(spheres.len(), 2);
+
+        let indices = get_index_data(&blob, &json, primitive.indices.unwrap()).unwrap();
+        let position = primitive.attributes[&Checked::Valid(Semantic::Positions)];
+        let positions = get_position_data(&blob, &json, position).unwrap();
+        for (range, sphere) in ranges.iter().zip(&spheres) {
+            let start = range[0] as usize;
+            for &v in &indices[start..start + range[1] as usize] {
+                let p = positions[v as usize];
+                let d = (0..3).map(|a| (p[a] - sphere[a]).powi(2)).sum::<f32>();
+                assert!(d.sqrt() <= sphere[3] + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn meshlets_need_room_for_a_triangle() {
+        let limits = MeshletOptions {
+            max_vertices: 2,
+            max_triangles: 1,
+        };
+        let mut json = Root::default();
+        let err = add_meshlet_metadata(&mut BlobWriter::new(), &mut json, &limits).unwrap_err();
+        assert!(matches!(err, OptimizeError::InvalidOption(_)));
+    }
+}
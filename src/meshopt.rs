@@ -0,0 +1,859 @@
+use std::{collections::HashMap, ops::Range};
+
+use gltf::json::{
+    Buffer, Index, Root,
+    accessor::{ComponentType, GenericComponentType},
+    buffer::View,
+    mesh::Mode,
+    validation::Checked,
+};
+use serde_json::{Value, json};
+
+use crate::{
+    blob::BlobWriter, error::OptimizeError, glb::accessor_stride, opt::require_extensions,
+    views::count_view_refs,
+};
+
+pub(crate) const MESHOPT_EXTENSION: &str = "EXT_meshopt_compression";
+
+/// Header byte of the vertex codec, version 0
+const VERTEX_HEADER: u8 = 0xa0;
+/// Header byte of the triangle index codec, version 1
+const INDEX_HEADER: u8 = 0xe1;
+
+/// Deltas are packed in groups of this many bytes
+const BYTE_GROUP_SIZE: usize = 16;
+const VERTEX_BLOCK_MAX: usize = 256;
+const VERTEX_BLOCK_BYTES: usize = 8192;
+/// The first vertex is written last, padded to at least this size
+const TAIL_MIN_SIZE: usize = 32;
+
+/// `feb << 4 | fec` pairs the index codec stores in 4 bits, as in the reference encoder
+const CODE_AUX_TABLE: [u8; 16] = [
+    0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0, 0,
+];
+
+fn zigzag8(v: u8) -> u8 {
+    ((v as i8) >> 7) as u8 ^ (v << 1)
+}
+
+/// Write 16 bytes at `bits` per value, values that do not fit following as whole bytes
+fn encode_bytes_group(out: &mut Vec<u8>, group: &[u8], bits: usize) {
+    match bits {
+        0 => {}
+        8 => out.extend_from_slice(group),
+        _ => {
+            let sentinel = (1u8 << bits) - 1;
+            for chunk in group.chunks(8 / bits) {
+                let byte = chunk
+                    .iter()
+                    .fold(0u8, |byte, &v| (byte << bits) | v.min(sentinel));
+                out.push(byte);
+            }
+            out.extend(group.iter().filter(|&&v| v >= sentinel));
+        }
+    }
+}
+
+/// Size of [`encode_bytes_group`] output at `bits`
+fn bytes_group_size(group: &[u8], bits: usize) -> Option<usize> {
+    match bits {
+        0 => group.iter().all(|&v| v == 0).then_some(0),
+        8 => Some(BYTE_GROUP_SIZE),
+        _ => {
+            let sentinel = (1u8 << bits) - 1;
+            let spilled = group.iter().filter(|&&v| v >= sentinel).count();
+            Some(BYTE_GROUP_SIZE * bits / 8 + spilled)
+        }
+    }
+}
+
+/// Write a multiple of 16 bytes as a 2-bit width header per group followed by the groups
+fn encode_bytes(out: &mut Vec<u8>, buffer: &[u8]) {
+    let groups = buffer.len() / BYTE_GROUP_SIZE;
+    let header = out.len();
+    out.resize(header + groups.div_ceil(4), 0);
+    for (i, group) in buffer.chunks_exact(BYTE_GROUP_SIZE).enumerate() {
+        let (code, bits) = [0, 2, 4, 8]
+            .into_iter()
+            .enumerate()
+            .filter_map(|(code, bits)| Some((code, bits, bytes_group_size(group, bits)?)))
+            .min_by_key(|&(_, _, size)| size)
+            .map(|(code, bits, _)| (code as u8, bits))
+            .unwrap_or((3, 8));
+        out[header + i / 4] |= code << ((i % 4) * 2);
+        encode_bytes_group(out, group, bits);
+    }
+}
+
+/// Encode `stride`-byte vertices with the meshoptimizer vertex codec
+///
+/// Each byte of a vertex is delta-coded against the same byte of the previous vertex, block by
+/// block, and the deltas bit-packed in groups of 16.
+pub(crate) fn encode_vertex_buffer(data: &[u8], stride: usize) -> Vec<u8> {
+    let count = data.len() / stride;
+    let block_size = ((VERTEX_BLOCK_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX);
+    let first = data.get(..stride).unwrap_or_default();
+
+    let mut out = vec![VERTEX_HEADER];
+    let mut last = first.to_vec();
+    let mut buffer = vec![0u8; block_size];
+    for start in (0..count).step_by(block_size) {
+        let block = &data[start * stride..(start + block_size).min(count) * stride];
+        let vertices = block.len() / stride;
+        let aligned = vertices.next_multiple_of(BYTE_GROUP_SIZE);
+        for k in 0..stride {
+            let mut previous = last[k];
+            for (i, vertex) in block.chunks_exact(stride).enumerate() {
+                buffer[i] = zigzag8(vertex[k].wrapping_sub(previous));
+                previous = vertex[k];
+            }
+            buffer[vertices..aligned].fill(0);
+            encode_bytes(&mut out, &buffer[..aligned]);
+        }
+        last.copy_from_slice(&block[block.len() - stride..]);
+    }
+
+    out.resize(out.len() + TAIL_MIN_SIZE.saturating_sub(stride), 0);
+    out.extend_from_slice(first);
+    out
+}
+
+fn encode_vbyte(out: &mut Vec<u8>, mut v: u32) {
+    loop {
+        out.push((v & 127) as u8 | if v > 127 { 128 } else { 0 });
+        v >>= 7;
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+fn encode_index(out: &mut Vec<u8>, index: u32, last: u32) {
+    let d = index.wrapping_sub(last);
+    encode_vbyte(out, (d << 1) ^ ((d as i32) >> 31) as u32);
+}
+
+/// Position of `v` counting back from the newest entry of a 16-entry FIFO
+fn vertex_fifo_find(fifo: &[u32; 16], offset: usize, v: u32) -> Option<usize> {
+    (0..16).find(|i| fifo[(offset + 15 - i) & 15] == v)
+}
+
+/// Newest FIFO edge matching an edge of `abc`: its age and the triangle rotation it implies
+fn edge_fifo_find(
+    fifo: &[[u32; 2]; 16],
+    offset: usize,
+    [a, b, c]: [u32; 3],
+) -> Option<(usize, usize)> {
+    (0..16).find_map(|i| match fifo[(offset + 15 - i) & 15] {
+        e if e == [a, b] => Some((i, 0)),
+        e if e == [b, c] => Some((i, 1)),
+        e if e == [c, a] => Some((i, 2)),
+        _ => None,
+    })
+}
+
+/// Encode a triangle list with the meshoptimizer index codec
+///
+/// Triangles are coded against FIFOs of recent edges and vertices, so indices in vertex
+/// cache order compress best.
+pub(crate) fn encode_index_buffer(indices: &[u32]) -> Vec<u8> {
+    const ORDER: [[usize; 3]; 3] = [[0, 1, 2], [1, 2, 0], [2, 0, 1]];
+    const FEC_MAX: usize = 13;
+
+    let mut edges = [[u32::MAX; 2]; 16];
+    let mut vertices = [u32::MAX; 16];
+    let (mut edge_offset, mut vertex_offset) = (0, 0);
+    let (mut next, mut last) = (0u32, 0u32);
+    fn push<T>(fifo: &mut [T; 16], offset: &mut usize, value: T) {
+        fifo[*offset] = value;
+        *offset = (*offset + 1) & 15;
+    }
+
+    let mut codes = Vec::with_capacity(indices.len() / 3);
+    let mut data = Vec::new();
+    for tri in indices.chunks_exact(3) {
+        let tri = [tri[0], tri[1], tri[2]];
+        let edge = edge_fifo_find(&edges, edge_offset, tri).filter(|&(age, _)| age < 15);
+        if let Some((fe, rotation)) = edge {
+            let [a, b, c] = ORDER[rotation].map(|i| tri[i]);
+            let fc = vertex_fifo_find(&vertices, vertex_offset, c);
+            let mut fec = match fc {
+                Some(fc) if (1..FEC_MAX).contains(&fc) => fc,
+                _ if c == next => {
+                    next += 1;
+                    0
+                }
+                _ => 15,
+            };
+            // Strip-like runs code their free vertex relative to the last one
+            if fec == 15 && c.wrapping_add(1) == last {
+                fec = 13;
+                last = c;
+            }
+            if fec == 15 && c == last.wrapping_add(1) {
+                fec = 14;
+                last = c;
+            }
+            codes.push(((fe << 4) | fec) as u8);
+            if fec == 15 {
+                encode_index(&mut data, c, last);
+                last = c;
+            }
+            if fec == 0 || fec >= FEC_MAX {
+                push(&mut vertices, &mut vertex_offset, c);
+            }
+            push(&mut edges, &mut edge_offset, [c, b]);
+            push(&mut edges, &mut edge_offset, [a, c]);
+        } else {
+            let rotation = match tri {
+                [_, b, _] if b == next => 1,
+                [_, _, c] if c == next => 2,
+                _ => 0,
+            };
+            let [a, b, c] = ORDER[rotation].map(|i| tri[i]);
+            // 0, 1, 2 after other vertices restarts the numbering
+            let reset = [a, b, c] == [0, 1, 2] && next > 0;
+            if reset {
+                next = 0;
+                vertices = [u32::MAX; 16];
+            }
+
+            let fb = vertex_fifo_find(&vertices, vertex_offset, b);
+            let fc = vertex_fifo_find(&vertices, vertex_offset, c);
+            let mut free = |v: u32, fifo: Option<usize>| match fifo {
+                Some(f) if f < 14 => f + 1,
+                _ if v == next => {
+                    next += 1;
+                    0
+                }
+                _ => 15,
+            };
+            let fea = free(a, None);
+            let feb = free(b, fb);
+            let fec = free(c, fc);
+
+            let aux = ((feb << 4) | fec) as u8;
+            match CODE_AUX_TABLE[..14].iter().position(|&e| e == aux) {
+                Some(i) if fea == 0 && !reset => codes.push(0xf0 | i as u8),
+                _ => {
+                    codes.push(0xf0 | 14 | fea as u8);
+                    data.push(aux);
+                }
+            }
+            for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+                if fe == 15 {
+                    encode_index(&mut data, v, last);
+                    last = v;
+                }
+            }
+            for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+                if fe == 0 || fe == 15 {
+                    push(&mut vertices, &mut vertex_offset, v);
+                }
+            }
+            push(&mut edges, &mut edge_offset, [b, a]);
+            push(&mut edges, &mut edge_offset, [c, b]);
+            push(&mut edges, &mut edge_offset, [a, c]);
+        }
+    }
+
+    // The table doubles as the padding decoders rely on
+    let mut out = Vec::with_capacity(1 + codes.len() + data.len() + CODE_AUX_TABLE.len());
+    out.push(INDEX_HEADER);
+    out.extend_from_slice(&codes);
+    out.extend_from_slice(&data);
+    out.extend_from_slice(&CODE_AUX_TABLE);
+    out
+}
+
+/// Where a view's bytes sit in the binary chunk: the compressed payload of an
+/// [`MESHOPT_EXTENSION`] view, the view itself otherwise
+pub(crate) fn chunk_range(view: &View) -> Range<usize> {
+    let payload = view
+        .extensions
+        .as_ref()
+        .and_then(|e| e.others.get(MESHOPT_EXTENSION));
+    let (offset, length) = match payload {
+        Some(p) => (
+            p["byteOffset"].as_u64().unwrap_or(0) as usize,
+            p["byteLength"].as_u64().unwrap_or(0) as usize,
+        ),
+        None => (
+            view.byte_offset.map_or(0, |o| o.0 as usize),
+            view.byte_length.0 as usize,
+        ),
+    };
+    offset..offset + length
+}
+
+/// Move a view's bytes, as found by [`chunk_range`], to `offset` in the binary chunk
+pub(crate) fn move_in_chunk(view: &mut View, offset: usize) {
+    let payload = view
+        .extensions
+        .as_mut()
+        .and_then(|e| e.others.get_mut(MESHOPT_EXTENSION));
+    match payload {
+        Some(p) => p["byteOffset"] = offset.into(),
+        None => view.byte_offset = (offset > 0).then(|| offset.into()),
+    }
+}
+
+/// Buffer holding a view's bytes as found by [`chunk_range`]
+pub(crate) fn chunk_buffer(view: &View) -> usize {
+    view.extensions
+        .as_ref()
+        .and_then(|e| e.others.get(MESHOPT_EXTENSION))
+        .map_or(view.buffer.value(), |p| {
+            p["buffer"].as_u64().unwrap_or(0) as usize
+        })
+}
+
+/// Move a view's bytes, as found by [`chunk_range`], to `offset` in buffer `buffer`
+pub(crate) fn move_to_buffer(view: &mut View, buffer: usize, offset: usize) {
+    let payload = view
+        .extensions
+        .as_mut()
+        .and_then(|e| e.others.get_mut(MESHOPT_EXTENSION));
+    match payload {
+        Some(p) => p["buffer"] = buffer.into(),
+        None => view.buffer = Index::new(buffer as u32),
+    }
+    move_in_chunk(view, offset);
+}
+
+/// How a view's data can be compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Use {
+    /// Vertex attribute or morph target data
+    Attributes,
+    /// Indices of a triangle list
+    Triangles,
+    /// Anything else
+    Other,
+}
+
+/// Where [`compress_views`] put a view
+enum Placement {
+    /// Copied as is to this offset in the binary chunk
+    Copied(usize),
+    /// Replaced by this extension payload
+    Compressed(Value),
+}
+
+/// Compress mesh buffer views with `EXT_meshopt_compression`
+///
+/// Vertex views with a stride of a multiple of 4 bytes use the attribute codec, 16- and
+/// 32-bit triangle list indices the index codec; other views and views that would not
+/// shrink stay as they are. Compressed views move to a fallback buffer without data, so
+/// the extension is required. Returns the new binary chunk, `None` when nothing shrank.
+pub(crate) fn compress_views(
+    n_json: &mut Root,
+    n_blob: &[u8],
+) -> Result<Option<Vec<u8>>, OptimizeError> {
+    let view_count = n_json.buffer_views.len();
+    let mut refs = vec![0; view_count];
+    count_view_refs(&serde_json::to_value(&*n_json)?, &mut refs);
+
+    let mut uses: HashMap<usize, Use> = HashMap::new();
+    let mut claim = |acc: usize, claimed: Use| {
+        uses.entry(acc)
+            .and_modify(|u| {
+                if *u != claimed {
+                    *u = Use::Other;
+                }
+            })
+            .or_insert(claimed);
+    };
+    for p in n_json.meshes.iter().flat_map(|m| &m.primitives) {
+        if let Some(indices) = p.indices {
+            let triangles = p.mode == Checked::Valid(Mode::Triangles);
+            claim(
+                indices.value(),
+                if triangles {
+                    Use::Triangles
+                } else {
+                    Use::Other
+                },
+            );
+        }
+        for acc in p.attributes.values() {
+            claim(acc.value(), Use::Attributes);
+        }
+        for target in p.targets.iter().flatten() {
+            for acc in [target.positions, target.normals, target.tangents]
+                .into_iter()
+                .flatten()
+            {
+                claim(acc.value(), Use::Attributes);
+            }
+        }
+    }
+
+    // Accessors of each view, when all of them are read the same way
+    let mut view_uses: Vec<Option<Use>> = vec![None; view_count];
+    let mut view_accessors: Vec<Vec<usize>> = vec![Vec::new(); view_count];
+    for (i, acc) in n_json.accessors.iter().enumerate() {
+        let Some(view) = acc
+            .buffer_view
+            .map(|v| v.value())
+            .filter(|&v| v < view_count)
+        else {
+            continue;
+        };
+        let used = uses.get(&i).copied().unwrap_or(Use::Other);
+        view_uses[view] = Some(match view_uses[view] {
+            Some(u) if u != used => Use::Other,
+            _ => used,
+        });
+        view_accessors[view].push(i);
+    }
+
+    // Copy every view, compressed when that makes it smaller
+    let mut blob = BlobWriter::new();
+    let mut placed = Vec::with_capacity(view_count);
+    for (i, view) in n_json.buffer_views.iter().enumerate() {
+        let data = n_blob.get(chunk_range(view)).unwrap_or_default();
+        let direct = refs[i] == view_accessors[i].len() && view.buffer.value() == 0;
+        let encoded = match view_uses[i].filter(|_| direct && view.extensions.is_none()) {
+            Some(Use::Attributes) => {
+                let stride =
+                    view.byte_stride
+                        .map(|s| s.0)
+                        .or_else(|| match &view_accessors[i][..] {
+                            [acc] => accessor_stride(n_json, Index::new(*acc as u32)),
+                            _ => None,
+                        });
+                stride
+                    .filter(|&s| s.is_multiple_of(4) && s <= 256 && data.len().is_multiple_of(s))
+                    .map(|s| (encode_vertex_buffer(data, s), s, "ATTRIBUTES"))
+            }
+            Some(Use::Triangles) => match &view_accessors[i][..] {
+                [acc] => triangle_indices(&n_json.accessors[*acc], data)
+                    .map(|(indices, size)| (encode_index_buffer(&indices), size, "TRIANGLES")),
+                _ => None,
+            },
+            _ => None,
+        };
+        placed.push(
+            match encoded.filter(|(bytes, _, _)| bytes.len() < data.len()) {
+                Some((bytes, stride, mode)) => Placement::Compressed(json!({
+                    "buffer": 0,
+                    "byteOffset": blob.append(&bytes),
+                    "byteLength": bytes.len(),
+                    "byteStride": stride,
+                    "count": data.len() / stride,
+                    "mode": mode,
+                })),
+                None => Placement::Copied(blob.append(data)),
+            },
+        );
+    }
+    if placed.iter().all(|p| matches!(p, Placement::Copied(_))) {
+        return Ok(None);
+    }
+
+    if n_json.buffers.is_empty() {
+        n_json.push(Buffer {
+            byte_length: 0usize.into(),
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+    let mut fallback_extensions = gltf::json::extensions::buffer::Buffer::default();
+    fallback_extensions
+        .others
+        .insert(MESHOPT_EXTENSION.to_string(), json!({ "fallback": true }));
+    let fallback = n_json.push(Buffer {
+        byte_length: 0usize.into(),
+        name: None,
+        uri: None,
+        extensions: Some(fallback_extensions),
+        extras: Default::default(),
+    });
+
+    // Compressed views keep their layout in the fallback buffer, which holds no data
+    let mut fallback_length = 0;
+    for (view, placement) in n_json.buffer_views.iter_mut().zip(placed) {
+        match placement {
+            Placement::Copied(offset) => move_in_chunk(view, offset),
+            Placement::Compressed(payload) => {
+                view.buffer = fallback;
+                view.byte_offset = (fallback_length > 0).then(|| fallback_length.into());
+                fallback_length =
+                    (fallback_length + view.byte_length.0 as usize).next_multiple_of(4);
+                view.extensions
+                    .get_or_insert_with(Default::default)
+                    .others
+                    .insert(MESHOPT_EXTENSION.to_string(), payload);
+            }
+        }
+    }
+    n_json.buffers[fallback.value()].byte_length = fallback_length.into();
+
+    require_extensions(n_json, &[MESHOPT_EXTENSION.to_string()]);
+    Ok(Some(blob.into_vec()))
+}
+
+/// Indices of a 16- or 32-bit triangle list accessor filling its whole view, and their size
+fn triangle_indices(acc: &gltf::json::Accessor, data: &[u8]) -> Option<(Vec<u32>, usize)> {
+    let size = match acc.component_type {
+        Checked::Valid(GenericComponentType(ComponentType::U16)) => 2,
+        Checked::Valid(GenericComponentType(ComponentType::U32)) => 4,
+        _ => return None,
+    };
+    let count = acc.count.0 as usize;
+    if acc.byte_offset.is_some_and(|o| o.0 != 0)
+        || acc.sparse.is_some()
+        || count == 0
+        || !count.is_multiple_of(3)
+        || data.len() != count * size
+    {
+        return None;
+    }
+    let indices = data
+        .chunks_exact(size)
+        .map(|c| match c {
+            [a, b] => u16::from_le_bytes([*a, *b]) as u32,
+            _ => u32::from_le_bytes([c[0], c[1], c[2], c[3]]),
+        })
+        .collect();
+    Some((indices, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{glb::write_glb, opt::optimize_with, options::OptimizeOptions};
+
+    #[test]
+    fn meshopt_compression_moves_views_to_fallback_buffer() {
+        use crate::glb::read_glb;
+
+        // 20x20 quads, enough for both codecs to shrink the data
+        let positions: Vec<f32> = (0..21u16)
+            .flat_map(|y| (0..21u16).flat_map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let indices: Vec<u16> = (0..20u16)
+            .flat_map(|y| (0..20u16).map(move |x| y * 21 + x))
+            .flat_map(|v| [v, v + 1, v + 22, v, v + 22, v + 21])
+            .collect();
+        let mut blob = BlobWriter::new();
+        let position_offset = blob.append(bytemuck::cast_slice(&positions));
+        let index_offset = blob.append(bytemuck::cast_slice(&indices));
+        let json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": blob.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": position_offset, "byteLength": positions.len() * 4 },
+                { "buffer": 0, "byteOffset": index_offset, "byteLength": indices.len() * 2 },
+            ],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 441, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [20.0, 20.0, 0.0] },
+                { "bufferView": 1, "componentType": 5123, "count": indices.len(), "type": "SCALAR" },
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+            "nodes": [{ "mesh": 0 }],
+            "scenes": [{ "nodes": [0] }],
+        }))
+        .unwrap();
+        let glb = write_glb(&json, blob).unwrap();
+
+        let plain = optimize_with(&mut Cursor::new(&glb), &OptimizeOptions::default()).unwrap();
+        let options = OptimizeOptions::default().meshopt_compression(true);
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        assert!(out.len() < plain.len());
+
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let meshopt = "EXT_meshopt_compression".to_string();
+        assert!(n_json.extensions_required.contains(&meshopt));
+        let fallback = &n_json.buffers[1];
+        assert!(fallback.uri.is_none());
+        assert_eq!(
+            fallback.extensions.as_ref().unwrap().others[&meshopt],
+            serde_json::json!({ "fallback": true })
+        );
+
+        let mut modes = Vec::new();
+        for view in &n_json.buffer_views {
+            assert_eq!(view.buffer.value(), 1);
+            let payload = &view.extensions.as_ref().unwrap().others[&meshopt];
+            let start = payload["byteOffset"].as_u64().unwrap() as usize;
+            let length = payload["byteLength"].as_u64().unwrap() as usize;
+            let stride = payload["byteStride"].as_u64().unwrap();
+            assert!(start + length <= n_blob.len());
+            assert_eq!(
+                payload["count"].as_u64().unwrap() * stride,
+                view.byte_length.0
+            );
+            let mode = payload["mode"].as_str().unwrap();
+            let header = match mode {
+                "ATTRIBUTES" => 0xa0,
+                "TRIANGLES" => 0xe1,
+                _ => panic!("unexpected mode {mode}"),
+            };
+            assert_eq!(n_blob[start], header);
+            modes.push(mode.to_string());
+        }
+        modes.sort();
+        assert_eq!(modes, ["ATTRIBUTES", "TRIANGLES"]);
+    }
+
+    /// Reference decoder of the vertex codec, after meshoptimizer's `decodeVertexBuffer`
+    fn decode_vertex_buffer(data: &[u8], count: usize, stride: usize) -> Vec<u8> {
+        fn decode_bytes(data: &mut &[u8], out: &mut [u8]) {
+            let (header, rest) = data.split_at(out.len().div_ceil(BYTE_GROUP_SIZE * 4));
+            *data = rest;
+            for (i, group) in out.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+                let bits = [0, 2, 4, 8][(header[i / 4] >> ((i % 4) * 2) & 3) as usize];
+                match bits {
+                    0 => group.fill(0),
+                    8 => {
+                        group.copy_from_slice(&data[..BYTE_GROUP_SIZE]);
+                        *data = &data[BYTE_GROUP_SIZE..];
+                    }
+                    _ => {
+                        let sentinel = (1u8 << bits) - 1;
+                        let (packed, rest) = data.split_at(BYTE_GROUP_SIZE * bits / 8);
+                        *data = rest;
+                        for (j, v) in group.iter_mut().enumerate() {
+                            let shift = 8 - bits - (j % (8 / bits)) * bits;
+                            *v = packed[j / (8 / bits)] >> shift & sentinel;
+                            if *v == sentinel {
+                                *v = data[0];
+                                *data = &data[1..];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        assert_eq!(data[0], VERTEX_HEADER);
+        let mut last = data[data.len() - stride..].to_vec();
+        let mut data = &data[1..];
+        let block_size =
+            ((VERTEX_BLOCK_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX);
+        let mut out = vec![0u8; count * stride];
+        let mut buffer = vec![0u8; block_size];
+        for start in (0..count).step_by(block_size) {
+            let block = &mut out[start * stride..(start + block_size).min(count) * stride];
+            let vertices = block.len() / stride;
+            for k in 0..stride {
+                decode_bytes(
+                    &mut data,
+                    &mut buffer[..vertices.next_multiple_of(BYTE_GROUP_SIZE)],
+                );
+                let mut previous = last[k];
+                for (i, &delta) in buffer[..vertices].iter().enumerate() {
+                    previous =
+                        previous.wrapping_add(((delta >> 1) as i8 ^ -((delta & 1) as i8)) as u8);
+                    block[i * stride + k] = previous;
+                }
+            }
+            last.copy_from_slice(&block[block.len() - stride..]);
+        }
+        out
+    }
+
+    /// Reference decoder of the index codec, after meshoptimizer's `decodeIndexBuffer`
+    fn decode_index_buffer(data: &[u8], count: usize) -> Vec<u32> {
+        fn decode_index(data: &mut &[u8], last: u32) -> u32 {
+            let mut v = 0u32;
+            for shift in (0..35).step_by(7) {
+                let byte = data[0];
+                *data = &data[1..];
+                v |= ((byte & 127) as u32) << shift;
+                if byte < 128 {
+                    break;
+                }
+            }
+            last.wrapping_add((v >> 1) ^ (v & 1).wrapping_neg())
+        }
+        fn push<T>(fifo: &mut [T; 16], offset: &mut usize, value: T) {
+            fifo[*offset] = value;
+            *offset = (*offset + 1) & 15;
+        }
+
+        assert_eq!(data[0], INDEX_HEADER);
+        let (codes, mut data) = data[1..].split_at(count / 3);
+        let table = &data[data.len() - CODE_AUX_TABLE.len()..];
+        let mut edges = [[u32::MAX; 2]; 16];
+        let mut vertices = [u32::MAX; 16];
+        let (mut edge_offset, mut vertex_offset) = (0usize, 0usize);
+        let (mut next, mut last) = (0u32, 0u32);
+        let mut out = Vec::with_capacity(count);
+        for &code in codes {
+            if code < 0xf0 {
+                let [a, b] = edges[(edge_offset + 15 - (code >> 4) as usize) & 15];
+                let fec = (code & 15) as usize;
+                let c = match fec {
+                    0 => {
+                        next += 1;
+                        next - 1
+                    }
+                    1..13 => vertices[(vertex_offset + 15 - fec) & 15],
+                    13 => last.wrapping_sub(1),
+                    14 => last.wrapping_add(1),
+                    _ => decode_index(&mut data, last),
+                };
+                if fec >= 13 {
+                    last = c;
+                }
+                if fec == 0 || fec >= 13 {
+                    push(&mut vertices, &mut vertex_offset, c);
+                }
+                out.extend([a, b, c]);
+                push(&mut edges, &mut edge_offset, [c, b]);
+                push(&mut edges, &mut edge_offset, [a, c]);
+            } else {
+                let (fea, aux) = if code < 0xfe {
+                    (0, table[(code & 15) as usize])
+                } else {
+                    let aux = data[0];
+                    data = &data[1..];
+                    if aux == 0 {
+                        next = 0;
+                    }
+                    (if code == 0xfe { 0 } else { 15 }, aux)
+                };
+                let (feb, fec) = ((aux >> 4) as usize, (aux & 15) as usize);
+                let mut vertex = |fe: usize| match fe {
+                    0 => {
+                        next += 1;
+                        next - 1
+                    }
+                    15 => 0,
+                    _ => vertices[(vertex_offset + 16 - fe) & 15],
+                };
+                let mut tri = [vertex(fea), vertex(feb), vertex(fec)];
+                for (v, fe) in tri.iter_mut().zip([fea, feb, fec]) {
+                    if fe == 15 {
+                        *v = decode_index(&mut data, last);
+                        last = *v;
+                    }
+                }
+                let [a, b, c] = tri;
+                for (v, fe) in [(a, 0), (b, feb), (c, fec)] {
+                    if fe == 0 || fe == 15 {
+                        push(&mut vertices, &mut vertex_offset, v);
+                    }
+                }
+                out.extend(tri);
+                push(&mut edges, &mut edge_offset, [b, a]);
+                push(&mut edges, &mut edge_offset, [c, b]);
+                push(&mut edges, &mut edge_offset, [a, c]);
+            }
+        }
+        out
+    }
+
+    /// Deterministic noise for incompressible test data
+    fn noise(seed: u32) -> impl Iterator<Item = u32> {
+        std::iter::successors(Some(seed), |x| {
+            Some(x.wrapping_mul(1664525).wrapping_add(1013904223))
+        })
+        .skip(1)
+    }
+
+    #[test]
+    fn vertex_buffers_decode_to_the_same_bytes() {
+        for stride in [4, 12, 16, 32] {
+            for count in [1, 15, 16, 17, 300, 1000] {
+                let ramp: Vec<u8> = (0..count * stride)
+                    .map(|i| (i / stride + i % stride) as u8)
+                    .collect();
+                let random: Vec<u8> = noise(count as u32)
+                    .take(count * stride)
+                    .map(|x| (x >> 24) as u8)
+                    .collect();
+                let zeros = vec![0u8; count * stride];
+                for data in [ramp, random, zeros] {
+                    let encoded = encode_vertex_buffer(&data, stride);
+                    assert_eq!(
+                        decode_vertex_buffer(&encoded, count, stride),
+                        data,
+                        "stride {stride}, count {count}"
+                    );
+                }
+            }
+        }
+        // Smooth data packs into far fewer bytes than it takes raw
+        let positions: Vec<u8> = (0..1024u16)
+            .flat_map(|i| [i, i, 0, 0])
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert!(encode_vertex_buffer(&positions, 8).len() < positions.len() / 2);
+    }
+
+    #[test]
+    fn index_buffers_decode_to_the_same_triangles() {
+        let grid: Vec<u32> = (0..30u32)
+            .flat_map(|y| (0..30u32).map(move |x| y * 31 + x))
+            .flat_map(|v| [v, v + 1, v + 32, v, v + 32, v + 31])
+            .collect();
+        // Far-apart indices take multi-byte deltas, and 0, 1, 2 after other triangles resets
+        let scattered: Vec<u32> = noise(7).take(300).map(|x| x >> 12).collect();
+        let restarted = [
+            [0, 1, 2, 2, 1, 3].as_slice(),
+            &grid[..60],
+            &[0, 1, 2, 5, 4, 3],
+        ]
+        .concat();
+        let strip: Vec<u32> = (0..50u32).flat_map(|i| [i + 2, i + 1, i]).collect();
+        // Triangles may come back rotated, with their winding intact
+        let rotated = |indices: &[u32]| -> Vec<[u32; 3]> {
+            indices
+                .chunks_exact(3)
+                .map(|t| {
+                    let first = (0..3).min_by_key(|&i| t[i]).unwrap();
+                    [0, 1, 2].map(|i| t[(first + i) % 3])
+                })
+                .collect()
+        };
+        for indices in [vec![0, 1, 2], grid.clone(), scattered, restarted, strip] {
+            let encoded = encode_index_buffer(&indices);
+            let decoded = decode_index_buffer(&encoded, indices.len());
+            assert_eq!(rotated(&decoded), rotated(&indices));
+        }
+        assert!(encode_index_buffer(&grid).len() < grid.len());
+    }
+
+    /// `kVertexBuffer`/`kVertexDataV0` and `kIndexBuffer`/`kIndexDataV1` of meshoptimizer's tests
+    #[test]
+    fn codecs_match_meshoptimizer_reference_data() {
+        // px, py, pz, nu and nv (one byte each), tx, ty
+        let vertices: Vec<u8> = [
+            [0u16, 0, 0, 0, 0, 0],
+            [300, 0, 0, 0, 500, 0],
+            [0, 300, 0, 0, 0, 500],
+            [300, 300, 0, 0, 500, 500],
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+        let mut vertex_data = vec![
+            0xa0, 0x01, 0x3f, 0x00, 0x00, 0x00, 0x58, 0x57, 0x58, 0x01, 0x26, 0x00, 0x00, 0x00,
+            0x01, 0x0c, 0x00, 0x00, 0x00, 0x58, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, 0x3f, 0x00, 0x00, 0x00, 0x17, 0x18, 0x17, 0x01, 0x26, 0x00, 0x00, 0x00,
+            0x01, 0x0c, 0x00, 0x00, 0x00, 0x17, 0x01, 0x08,
+        ];
+        vertex_data.resize(85, 0);
+        assert_eq!(encode_vertex_buffer(&vertices, 12), vertex_data);
+
+        let index_data = [
+            0xe1, 0xf0, 0x10, 0xfe, 0xff, 0xf0, 0x0c, 0xff, 0x02, 0x02, 0x02, 0x00, 0x76, 0x87,
+            0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0x00, 0x00,
+        ];
+        assert_eq!(
+            encode_index_buffer(&[0, 1, 2, 2, 1, 3, 4, 6, 5, 7, 8, 9]),
+            index_data
+        );
+    }
+}
@@ -0,0 +1,110 @@
+/// Counter reported to a [`MetricsSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Counter {
+    /// Textures resized and re-encoded, once per output image
+    TexturesProcessed,
+    /// Size of the input GLB
+    BytesIn,
+    /// Size of the output GLB
+    BytesOut,
+}
+
+impl Counter {
+    /// Prometheus-style metric name
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::TexturesProcessed => "gltf_opt_textures_processed_total",
+            Counter::BytesIn => "gltf_opt_bytes_in_total",
+            Counter::BytesOut => "gltf_opt_bytes_out_total",
+        }
+    }
+}
+
+/// Histogram reported to a [`MetricsSink`], observed in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Histogram {
+    /// Resizing and encoding one texture
+    TextureEncodeSeconds,
+    /// One whole optimization, from reading the input to writing the output
+    OptimizeSeconds,
+}
+
+impl Histogram {
+    /// Prometheus-style metric name
+    pub fn name(self) -> &'static str {
+        match self {
+            Histogram::TextureEncodeSeconds => "gltf_opt_texture_encode_seconds",
+            Histogram::OptimizeSeconds => "gltf_opt_optimize_seconds",
+        }
+    }
+}
+
+/// Receiver for counters and histograms, e.g. an adapter to a monitoring client
+///
+/// Both methods default to doing nothing, so a sink only implements what it records. They take
+/// `&self`; use atomics or a lock to accumulate.
+pub trait MetricsSink: Send + Sync {
+    /// Add `value` to `counter`
+    fn increment(&self, counter: Counter, value: u64) {
+        let _ = (counter, value);
+    }
+
+    /// Record one observation of `histogram`
+    fn observe(&self, histogram: Histogram, value: f64) {
+        let _ = (histogram, value);
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::{collections::HashMap, io::Cursor, sync::Mutex};
+
+    use super::*;
+    use crate::{optimizer::Optimizer, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn metrics_report_textures_and_sizes() {
+        #[derive(Default)]
+        struct Recorder {
+            counters: Mutex<HashMap<Counter, u64>>,
+            histograms: Mutex<HashMap<Histogram, usize>>,
+        }
+        impl MetricsSink for Recorder {
+            fn increment(&self, counter: Counter, value: u64) {
+                *self.counters.lock().unwrap().entry(counter).or_default() += value;
+            }
+            fn observe(&self, histogram: Histogram, _value: f64) {
+                *self
+                    .histograms
+                    .lock()
+                    .unwrap()
+                    .entry(histogram)
+                    .or_default() += 1;
+            }
+        }
+
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let recorder = Recorder::default();
+        let optimized = Optimizer::new(&OptimizeOptions::default())
+            .metrics(&recorder)
+            .run(&mut Cursor::new(&glb))
+            .unwrap()
+            .glb;
+
+        let counters = recorder.counters.into_inner().unwrap();
+        assert_eq!(counters[&Counter::BytesIn], glb.len() as u64);
+        assert_eq!(counters[&Counter::BytesOut], optimized.len() as u64);
+        assert_eq!(counters[&Counter::TexturesProcessed], 2);
+        let histograms = recorder.histograms.into_inner().unwrap();
+        assert_eq!(histograms[&Histogram::TextureEncodeSeconds], 2);
+        assert_eq!(histograms[&Histogram::OptimizeSeconds], 1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    decode::KTX2_MAGIC,
+    error::{Malformation, OptimizeError},
+};
+
+/// Base level rounding applied before building KTX2 mip chains, see
+/// [`OptimizeOptions::ktx2_mip_rounding`](crate::OptimizeOptions::ktx2_mip_rounding)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MipRounding {
+    /// Keep the base level size; odd sides are halved rounding down, as KTX2 and Vulkan do
+    #[default]
+    Keep,
+    /// Round each side above 1 down to an even size, so the first mip level halves exactly
+    Even,
+    /// Round each side to the nearest power of two within the size cap, like
+    /// [`power_of_two_textures`](crate::OptimizeOptions::power_of_two_textures), so
+    /// every level halves exactly
+    PowerOfTwo,
+}
+
+impl MipRounding {
+    /// Base level size for `width` x `height` under this rounding
+    ///
+    /// Power-of-two rounding needs the size cap and is applied with the other sizing instead.
+    pub(crate) fn round(self, width: u32, height: u32) -> (u32, u32) {
+        let even = |side: u32| if side > 1 { side & !1 } else { side };
+        match self {
+            MipRounding::Even => (even(width), even(height)),
+            MipRounding::Keep | MipRounding::PowerOfTwo => (width, height),
+        }
+    }
+}
+
+/// Size of every level of a full mip chain, the base first, down to 1x1
+///
+/// Each level is half the one above rounded down and at least 1, the rule KTX2 readers size
+/// levels by.
+pub(crate) fn mip_chain(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let (mut w, mut h) = (width.max(1), height.max(1));
+    let mut levels = vec![(w, h)];
+    while (w, h) != (1, 1) {
+        w = (w / 2).max(1);
+        h = (h / 2).max(1);
+        levels.push((w, h));
+    }
+    levels
+}
+
+/// KTX2 `supercompressionScheme` value for BasisLZ (ETC1S), whose global data lists images
+const KTX2_SUPERCOMPRESSION_BASISLZ: u32 = 1;
+
+/// Bytes of a BasisLZ global data header before its image descriptors
+const BASISLZ_HEADER_LEN: usize = 20;
+
+/// Bytes of one BasisLZ image descriptor
+const BASISLZ_IMAGE_DESC_LEN: usize = 20;
+
+/// Key/value data entries of a KTX2 file, with `Dimensions` rewritten for a new base level
+fn retagged_kvd(kvd: &[u8], width: u32, height: u32) -> Result<Vec<u8>, OptimizeError> {
+    let mut out = Vec::with_capacity(kvd.len());
+    let mut at = 0;
+    while at + 4 <= kvd.len() {
+        let len = u32::from_le_bytes(kvd[at..at + 4].try_into().unwrap_or_default()) as usize;
+        let entry = kvd
+            .get(at + 4..at + 4 + len)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 key/value entry".to_string(),
+            })?;
+        let entry = match entry.strip_prefix(b"Dimensions\0") {
+            Some(_) => [
+                b"Dimensions\0".as_slice(),
+                format!("{width}x{height}").as_bytes(),
+            ]
+            .concat(),
+            None => entry.to_vec(),
+        };
+        out.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        out.extend_from_slice(&entry);
+        out.resize(out.len().next_multiple_of(4), 0);
+        at = (at + 4 + len).next_multiple_of(4);
+    }
+    Ok(out)
+}
+
+/// A Basis Universal KTX2 file without its `skip` largest levels
+///
+/// Lower levels of a mip chain are complete textures of their own, so a larger texture's
+/// tail stands in for a smaller encode of the same image. Handles the 2D files the built-in
+/// encoder writes: ETC1S (BasisLZ, whose global data loses the dropped levels' image
+/// descriptors) and UASTC, raw or Zstandard-supercompressed. Levels are stored smallest first
+/// as the spec asks, and the `Dimensions` metadata follows the new base level.
+pub(crate) fn ktx2_mip_tail(ktx2: &[u8], skip: usize) -> Result<Vec<u8>, OptimizeError> {
+    let u32_at = |at: usize| -> Result<u32, OptimizeError> {
+        let bytes = ktx2
+            .get(at..at + 4)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 header".to_string(),
+            })?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    };
+    let u64_at = |at: usize| -> Result<u64, OptimizeError> {
+        let bytes = ktx2
+            .get(at..at + 8)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 header".to_string(),
+            })?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    };
+    let slice = |offset: u64, len: u64| -> Result<&[u8], OptimizeError> {
+        ktx2.get(offset as usize..(offset + len) as usize)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 data".to_string(),
+            })
+    };
+    if !ktx2.starts_with(&KTX2_MAGIC) {
+        return Err(OptimizeError::NotFormat {
+            expected: "KTX2 file",
+        });
+    }
+    let (vk_format, width, height) = (u32_at(12)?, u32_at(20)?, u32_at(24)?);
+    let (depth, layers, faces) = (u32_at(28)?, u32_at(32)?, u32_at(36)?);
+    let level_count = u32_at(40)? as usize;
+    let scheme = u32_at(44)?;
+    if vk_format != 0 || depth > 1 || layers > 1 || faces != 1 {
+        return Err(OptimizeError::Unsupported {
+            what: "cutting a mip tail from KTX2 that is not 2D Basis Universal".to_string(),
+        });
+    }
+    if skip == 0 || skip >= level_count {
+        return Err(OptimizeError::InvalidOption(format!(
+            "cannot drop {skip} of {level_count} KTX2 levels"
+        )));
+    }
+
+    let levels = (0..level_count)
+        .map(|i| {
+            Ok((
+                u64_at(80 + i * 24)?,
+                u64_at(88 + i * 24)?,
+                u64_at(96 + i * 24)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, OptimizeError>>()?;
+    let dfd = slice(u32_at(48)? as u64, u32_at(52)? as u64)?;
+    let (base_width, base_height) =
+        mip_chain(width, height)
+            .get(skip)
+            .copied()
+            .ok_or(OptimizeError::Malformed(Malformation::Ktx2Levels {
+                levels: level_count,
+                width,
+                height,
+            }))?;
+    let kvd = retagged_kvd(
+        slice(u32_at(56)? as u64, u32_at(60)? as u64)?,
+        base_width,
+        base_height,
+    )?;
+    let mut sgd = slice(u64_at(64)?, u64_at(72)?)?.to_vec();
+    if scheme == KTX2_SUPERCOMPRESSION_BASISLZ {
+        let dropped = BASISLZ_HEADER_LEN..BASISLZ_HEADER_LEN + skip * BASISLZ_IMAGE_DESC_LEN;
+        if sgd.len() < dropped.end {
+            return Err(OptimizeError::Truncated {
+                what: "BasisLZ global data".to_string(),
+            });
+        }
+        sgd.drain(dropped);
+    }
+    // Unsupercompressed UASTC levels align to its 16-byte blocks
+    let alignment = if scheme == 0 { 16 } else { 1 };
+
+    let kept = level_count - skip;
+    let mut out = ktx2[..80].to_vec();
+    out[20..24].copy_from_slice(&base_width.to_le_bytes());
+    out[24..28].copy_from_slice(&base_height.to_le_bytes());
+    out[40..44].copy_from_slice(&(kept as u32).to_le_bytes());
+    out.resize(80 + kept * 24, 0);
+    let dfd_offset = out.len() as u32;
+    out.extend_from_slice(dfd);
+    let kvd_offset = out.len() as u32;
+    out.extend_from_slice(&kvd);
+    let sgd_offset = match sgd.is_empty() {
+        true => 0,
+        false => {
+            out.resize(out.len().next_multiple_of(8), 0);
+            out.len() as u64
+        }
+    };
+    out.extend_from_slice(&sgd);
+    out[48..52].copy_from_slice(&dfd_offset.to_le_bytes());
+    out[52..56].copy_from_slice(&(dfd.len() as u32).to_le_bytes());
+    out[56..60].copy_from_slice(&kvd_offset.to_le_bytes());
+    out[60..64].copy_from_slice(&(kvd.len() as u32).to_le_bytes());
+    out[64..72].copy_from_slice(&sgd_offset.to_le_bytes());
+    out[72..80].copy_from_slice(&(sgd.len() as u64).to_le_bytes());
+
+    for level in (skip..level_count).rev() {
+        let (offset, length, uncompressed) = levels[level];
+        let data = slice(offset, length)?;
+        out.resize(out.len().next_multiple_of(alignment), 0);
+        let entry = 80 + (level - skip) * 24;
+        let n_offset = out.len() as u64;
+        out[entry..entry + 8].copy_from_slice(&n_offset.to_le_bytes());
+        out[entry + 8..entry + 16].copy_from_slice(&length.to_le_bytes());
+        out[entry + 16..entry + 24].copy_from_slice(&uncompressed.to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chains_round_odd_sides_down() {
+        assert_eq!(mip_chain(5, 3), [(5, 3), (2, 1), (1, 1)]);
+        assert_eq!(mip_chain(1, 4), [(1, 4), (1, 2), (1, 1)]);
+        assert_eq!(MipRounding::Even.round(25, 1), (24, 1));
+        assert_eq!(MipRounding::Keep.round(25, 7), (25, 7));
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::{HashMap, HashSet};
+
+use gltf::json::{
+    Accessor, Index, Mesh, Node, Root,
+    accessor::{
+        ComponentType, GenericComponentType, IndexComponentType, Type,
+        sparse::{Indices, Sparse, Values},
+    },
+    animation::Property,
+    mesh::{MorphTarget, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    convert::read_accessor,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{push_attribute, push_positions},
+    opt::add_accessor,
+};
+
+/// Copy a primitive's morph targets, storing their deltas sparsely when `threshold` is set
+///
+/// Must run after any geometry codec, which truncates the blob back to the primitive's start.
+pub(crate) fn add_morph_targets(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    targets: &[MorphTarget],
+    threshold: Option<f32>,
+) -> Vec<MorphTarget> {
+    let mut copy = |idx: Option<Index<Accessor>>| {
+        let idx = idx?;
+        threshold
+            .and_then(|t| add_sparse_deltas(n_blob, n_json, o_blob, o_json, idx, t))
+            .or_else(|| add_accessor(n_blob, n_json, o_blob, o_json, idx))
+    };
+
+    targets
+        .iter()
+        .map(|target| MorphTarget {
+            positions: copy(target.positions),
+            normals: copy(target.normals),
+            tangents: copy(target.tangents),
+        })
+        .collect()
+}
+
+/// Write a VEC3 delta accessor as a sparse accessor over zeros
+///
+/// Deltas with every component within `threshold` of zero are dropped. Returns `None` when
+/// the dense layout is smaller or the source cannot be read (already sparse, say).
+fn add_sparse_deltas(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    idx: Index<Accessor>,
+    threshold: f32,
+) -> Option<Index<Accessor>> {
+    let acc = o_json.accessors.get(idx.value())?;
+    let mut deltas = read_accessor::<3>(o_blob, o_json, idx)?;
+    let mut moved: Vec<usize> = (0..deltas.len())
+        .filter(|&i| deltas[i].iter().any(|c| c.abs() > threshold))
+        .collect();
+    // A sparse block needs at least one entry, and readers such as `gltf` reject accessors
+    // with neither data nor a sparse block
+    if moved.is_empty() && !deltas.is_empty() {
+        moved.push(0);
+        deltas[0] = [0.0; 3];
+    }
+
+    let (index_type, index_size) = match deltas.len() {
+        0..=256 => (ComponentType::U8, 1),
+        257..=65536 => (ComponentType::U16, 2),
+        _ => (ComponentType::U32, 4),
+    };
+    let dense_bytes = deltas.len() * 12;
+    if moved.len() * (index_size + 12) >= dense_bytes {
+        return None;
+    }
+
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    if moved.len() < deltas.len() {
+        (min, max) = ([0.0; 3], [0.0; 3]);
+    }
+    for &i in &moved {
+        for c in 0..3 {
+            min[c] = min[c].min(deltas[i][c]);
+            max[c] = max[c].max(deltas[i][c]);
+        }
+    }
+
+    let sparse = (!moved.is_empty()).then(|| {
+        let indices: Vec<u8> = moved
+            .iter()
+            .flat_map(|&i| match index_size {
+                1 => vec![i as u8],
+                2 => (i as u16).to_le_bytes().to_vec(),
+                _ => (i as u32).to_le_bytes().to_vec(),
+            })
+            .collect();
+        let values: Vec<u8> = moved
+            .iter()
+            .flat_map(|&i| deltas[i])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+
+        Sparse {
+            count: moved.len().into(),
+            indices: Indices {
+                buffer_view: n_blob.push_view(n_json, &indices, None),
+                byte_offset: Default::default(),
+                component_type: Checked::Valid(IndexComponentType(index_type)),
+                extensions: None,
+                extras: Default::default(),
+            },
+            values: Values {
+                buffer_view: n_blob.push_view(n_json, &values, None),
+                byte_offset: Default::default(),
+                extensions: None,
+                extras: Default::default(),
+            },
+            extensions: None,
+            extras: Default::default(),
+        }
+    });
+
+    Some(n_json.push(Accessor {
+        buffer_view: None,
+        byte_offset: None,
+        component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+        normalized: false,
+        min: (!deltas.is_empty()).then(|| min.to_vec().into()),
+        max: (!deltas.is_empty()).then(|| max.to_vec().into()),
+        sparse,
+        ..acc.clone()
+    }))
+}
+
+/// Morph target decision for one mesh: which targets stay, and the default weights
+struct MeshTargets {
+    kept: Vec<usize>,
+    weights: Vec<f32>,
+}
+
+impl MeshTargets {
+    /// Dropped targets that move the base geometry, with their default weight
+    fn baked(&self) -> Vec<(usize, f32)> {
+        self.weights
+            .iter()
+            .enumerate()
+            .filter(|(t, w)| **w != 0.0 && !self.kept.contains(t))
+            .map(|(t, w)| (t, *w))
+            .collect()
+    }
+
+    /// Keep the per-target entries of a weights array, which may repeat in blocks
+    fn trim<T: Clone>(&self, values: &[T]) -> Vec<T> {
+        let count = self.weights.len();
+        values
+            .chunks(count)
+            .flat_map(|block| self.kept.iter().filter_map(|&t| block.get(t).cloned()))
+            .collect()
+    }
+}
+
+/// Summed length of a target's position deltas over every primitive of the mesh
+fn target_significance(o_blob: &[u8], o_json: &Root, mesh: &Mesh, target: usize) -> f32 {
+    mesh.primitives
+        .iter()
+        .filter_map(|p| p.targets.as_ref()?.get(target)?.positions)
+        .filter_map(|idx| read_accessor::<3>(o_blob, o_json, idx))
+        .flatten()
+        .map(|d| (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt())
+        .sum()
+}
+
+/// Add `weight * delta` of each baked target to a base attribute
+///
+/// Normals and tangent directions are renormalized. Returns `None` when no baked target has
+/// deltas for the attribute or the data cannot be read.
+fn bake_attribute<const N: usize>(
+    o_blob: &[u8],
+    o_json: &Root,
+    base: Index<Accessor>,
+    deltas: &[(Index<Accessor>, f32)],
+    renormalize: bool,
+) -> Option<Vec<[f32; N]>> {
+    if deltas.is_empty() {
+        return None;
+    }
+    let mut values = read_accessor::<N>(o_blob, o_json, base)?;
+    for &(delta, weight) in deltas {
+        let delta = read_accessor::<3>(o_blob, o_json, delta)?;
+        for (v, d) in values.iter_mut().zip(delta) {
+            for c in 0..3 {
+                v[c] += weight * d[c];
+            }
+        }
+    }
+    if renormalize {
+        for v in &mut values {
+            let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            if len > 0.0 {
+                v.iter_mut().take(3).for_each(|c| *c /= len);
+            }
+        }
+    }
+    Some(values)
+}
+
+/// Bake dropped targets into a primitive's POSITION/NORMAL/TANGENT, appending new accessors
+fn bake_primitive(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &Root,
+    p: &Primitive,
+    baked: &[(usize, f32)],
+) -> Vec<(Checked<Semantic>, Index<Accessor>)> {
+    let targets = p.targets.as_deref().unwrap_or_default();
+    let deltas = |pick: fn(&MorphTarget) -> Option<Index<Accessor>>| -> Vec<_> {
+        baked
+            .iter()
+            .filter_map(|&(t, w)| Some((pick(targets.get(t)?)?, w)))
+            .collect()
+    };
+
+    let mut replaced = Vec::new();
+    for (semantic, &base) in &p.attributes {
+        let idx = match semantic {
+            Checked::Valid(Semantic::Positions) => {
+                bake_attribute::<3>(o_blob, o_json, base, &deltas(|t| t.positions), false)
+                    .map(|values| push_positions(n_blob, n_json, &values))
+            }
+            Checked::Valid(Semantic::Normals) => {
+                bake_attribute::<3>(o_blob, o_json, base, &deltas(|t| t.normals), true)
+                    .map(|values| push_attribute(n_blob, n_json, values.as_flattened(), Type::Vec3))
+            }
+            Checked::Valid(Semantic::Tangents) => {
+                bake_attribute::<4>(o_blob, o_json, base, &deltas(|t| t.tangents), true)
+                    .map(|values| push_attribute(n_blob, n_json, values.as_flattened(), Type::Vec4))
+            }
+            _ => None,
+        };
+        if let Some(idx) = idx {
+            replaced.push((semantic.clone(), idx));
+        }
+    }
+    replaced
+}
+
+/// Keep only the `limit` most significant morph targets of each mesh
+///
+/// Significance is the summed length of a target's position deltas. Dropped targets are
+/// baked into the base geometry at the mesh's default `weights`, so the rest pose is kept;
+/// `limit == 0` strips morphing entirely. Node weights and weight animations are trimmed to
+/// the kept targets, and animations left without channels are removed. Returns `None` when
+/// no mesh has more targets than the limit.
+pub(crate) fn limit_morph_targets(
+    o_blob: &[u8],
+    o_json: &Root,
+    limit: usize,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut decisions: HashMap<usize, MeshTargets> = HashMap::new();
+    for (i, mesh) in o_json.meshes.iter().enumerate() {
+        let count = mesh
+            .primitives
+            .iter()
+            .map(|p| p.targets.as_ref().map_or(0, Vec::len))
+            .max()
+            .unwrap_or(0);
+        if count <= limit {
+            continue;
+        }
+
+        let mut ranked: Vec<(usize, f32)> = (0..count)
+            .map(|t| (t, target_significance(o_blob, o_json, mesh, t)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut kept: Vec<usize> = ranked.iter().take(limit).map(|(t, _)| *t).collect();
+        kept.sort_unstable();
+
+        let mut weights = mesh.weights.clone().unwrap_or_default();
+        weights.resize(count, 0.0);
+        decisions.insert(i, MeshTargets { kept, weights });
+    }
+    if decisions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+
+    for (&i, decision) in &decisions {
+        let baked = decision.baked();
+        for (j, p) in o_json.meshes[i].primitives.iter().enumerate() {
+            let replaced = bake_primitive(&mut n_blob, &mut n_json, o_blob, o_json, p, &baked);
+
+            let n_p = &mut n_json.meshes[i].primitives[j];
+            n_p.attributes.extend(replaced);
+            let kept = n_p.targets.as_deref().map(|t| decision.trim(t));
+            n_p.targets = kept.filter(|t| !t.is_empty());
+        }
+
+        let mesh = &mut n_json.meshes[i];
+        mesh.weights = Some(decision.trim(&decision.weights)).filter(|w| !w.is_empty());
+        for node in &mut n_json.nodes {
+            if node.mesh.map(|m| m.value()) == Some(i)
+                && let Some(weights) = &node.weights
+            {
+                node.weights = Some(decision.trim(weights)).filter(|w| !w.is_empty());
+            }
+        }
+    }
+
+    // Weight animations carry one value per target (three for cubic splines) per keyframe
+    let mesh_of = |node: Index<Node>| o_json.nodes.get(node.value())?.mesh.map(|m| m.value());
+    let mut outputs: HashMap<(usize, usize), &MeshTargets> = HashMap::new();
+    let mut dropped: HashSet<(usize, usize)> = HashSet::new();
+    for (a, animation) in o_json.animations.iter().enumerate() {
+        for (c, channel) in animation.channels.iter().enumerate() {
+            let Some(decision) = mesh_of(channel.target.node).and_then(|m| decisions.get(&m))
+            else {
+                continue;
+            };
+            if channel.target.path != Checked::Valid(Property::MorphTargetWeights) {
+                continue;
+            }
+            if decision.kept.is_empty() {
+                dropped.insert((a, c));
+            } else {
+                outputs.insert((a, channel.sampler.value()), decision);
+            }
+        }
+    }
+
+    for ((a, s), decision) in outputs {
+        let Some(sampler) = o_json.animations[a].samplers.get(s) else {
+            continue;
+        };
+        let Some(values) = read_accessor::<1>(o_blob, o_json, sampler.output) else {
+            continue;
+        };
+        let values = decision.trim(values.as_flattened());
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let view = n_blob.push_view(&mut n_json, &bytes, None);
+        let output = n_json.push(Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: values.len().into(),
+            component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+            normalized: false,
+            min: None,
+            max: None,
+            sparse: None,
+            ..o_json.accessors[sampler.output.value()].clone()
+        });
+        n_json.animations[a].samplers[s].output = output;
+    }
+
+    for (a, animation) in n_json.animations.iter_mut().enumerate() {
+        let mut c = 0;
+        animation.channels.retain(|_| {
+            c += 1;
+            !dropped.contains(&(a, c - 1))
+        });
+    }
+    n_json.animations.retain(|a| !a.channels.is_empty());
+
+    Ok(Some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::optimize_with,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    /// Moves the top right corner up by 0.5
+    const LIFT: [[f32; 3]; 4] = [[0.0; 3], [0.0; 3], [0.0, 0.5, 0.0], [0.0; 3]];
+    /// Nudges one corner by less than a millimeter
+    const NUDGE: [[f32; 3]; 4] = [[0.0; 3], [0.0, 0.0, 0.0005], [0.0; 3], [0.0; 3]];
+
+    /// The synthetic quad with the two targets above and default weights `[0.5, 1.0]`
+    fn morphed_glb() -> Vec<u8> {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let targets = [LIFT, NUDGE]
+            .map(|deltas| MorphTarget {
+                positions: Some(push_positions(&mut blob, &mut json, &deltas)),
+                normals: None,
+                tangents: None,
+            })
+            .to_vec();
+        json.meshes[0].primitives[0].targets = Some(targets);
+        json.meshes[0].weights = Some(vec![0.5, 1.0]);
+        write_glb(&json, blob.into_vec()).unwrap()
+    }
+
+    type Vectors = Vec<[f32; 3]>;
+
+    /// Base positions and the position deltas of every target of the first primitive
+    fn optimized(options: &OptimizeOptions) -> (Root, Vectors, Vec<Vectors>) {
+        let out = optimize_with(&mut Cursor::new(morphed_glb()), options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let p = &json.meshes[0].primitives[0];
+        let base = read_accessor::<3>(
+            &blob,
+            &json,
+            p.attributes[&Checked::Valid(Semantic::Positions)],
+        )
+        .unwrap();
+        let targets = p
+            .targets
+            .iter()
+            .flatten()
+            .map(|t| read_accessor::<3>(&blob, &json, t.positions.unwrap()).unwrap())
+            .collect();
+        (json, base, targets)
+    }
+
+    #[test]
+    fn targets_are_copied_dense_or_sparse() {
+        let (json, _, targets) = optimized(&OptimizeOptions::default());
+        assert_eq!(targets, [LIFT.to_vec(), NUDGE.to_vec()]);
+        assert!(json.accessors.iter().all(|a| a.sparse.is_none()));
+
+        let options = OptimizeOptions {
+            sparse_morph_targets: Some(0.001),
+            ..Default::default()
+        };
+        let (json, _, targets) = optimized(&options);
+        assert_eq!(targets, [LIFT.to_vec(), vec![[0.0; 3]; 4]]);
+        let sparse: Vec<_> = json
+            .accessors
+            .iter()
+            .filter_map(|a| a.sparse.as_ref().map(|s| (a.buffer_view, s.count.0)))
+            .collect();
+        assert_eq!(sparse, [(None, 1), (None, 1)]);
+    }
+
+    #[test]
+    fn target_accessors_survive_optimization() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let target = MorphTarget {
+            positions: Some(push_positions(&mut blob, &mut json, &LIFT)),
+            normals: Some(push_positions(&mut blob, &mut json, &NUDGE)),
+            tangents: Some(push_positions(&mut blob, &mut json, &LIFT)),
+        };
+        json.meshes[1].primitives[0].targets = Some(vec![target]);
+        json.meshes[1].weights = Some(vec![0.25]);
+        let glb = write_glb(&json, blob.into_vec()).unwrap();
+
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(json.meshes[0].primitives[0].targets.is_none());
+        assert_eq!(json.meshes[1].weights, Some(vec![0.25]));
+        let p = &json.meshes[1].primitives[0];
+        let [target] = p.targets.as_deref().unwrap() else {
+            panic!("expected one target");
+        };
+        let vertices = json.accessors[p.attributes[&Checked::Valid(Semantic::Positions)].value()]
+            .count
+            .0;
+        let read = |idx: Option<Index<Accessor>>| {
+            let idx = idx.unwrap();
+            assert_eq!(json.accessors[idx.value()].count.0, vertices);
+            read_accessor::<3>(&blob, &json, idx).unwrap()
+        };
+        assert_eq!(read(target.positions), LIFT);
+        assert_eq!(read(target.normals), NUDGE);
+        assert_eq!(read(target.tangents), LIFT);
+        // Position deltas keep the bounds the specification requires
+        let positions = &json.accessors[target.positions.unwrap().value()];
+        assert!(positions.min.is_some() && positions.max.is_some());
+    }
+
+    #[test]
+    fn dropped_targets_are_baked_at_their_default_weight() {
+        let (_, source, _) = optimized(&OptimizeOptions::default());
+
+        let options = OptimizeOptions {
+            max_morph_targets: Some(1),
+            ..Default::default()
+        };
+        let (json, base, targets) = optimized(&options);
+        assert_eq!(targets, [LIFT.to_vec()]);
+        assert_eq!(json.meshes[0].weights, Some(vec![0.5]));
+        assert_eq!(base[1][2], source[1][2] + 0.0005);
+        assert_eq!(base[2], source[2]);
+
+        let options = OptimizeOptions {
+            bake_morph_targets: true,
+            ..Default::default()
+        };
+        let (json, base, targets) = optimized(&options);
+        assert!(targets.is_empty());
+        assert!(json.meshes[0].primitives[0].targets.is_none());
+        assert_eq!(json.meshes[0].weights, None);
+        assert_eq!(base[2][1], source[2][1] + 0.25);
+    }
+}
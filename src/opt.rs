@@ -1,129 +1,424 @@
 use std::{
     borrow::Cow,
-    error::Error,
+    collections::{HashMap, HashSet, hash_map::Entry},
     io::{Cursor, Read, Seek, Write},
+    panic::resume_unwind,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
 };
 
+#[cfg(feature = "resize")]
 use fast_image_resize::IntoImageView;
-use gltf::json::{Index, Root, Texture, image::MimeType, mesh::Primitive};
-use image::{
-    ImageEncoder,
-    codecs::{jpeg::JpegEncoder, png::PngEncoder},
+use gltf::json::{
+    Index, Root, Texture,
+    accessor::{ComponentType, GenericComponentType},
+    image::MimeType,
+    mesh::Primitive,
+    validation::Checked,
 };
+#[cfg(any(feature = "png", feature = "jpeg"))]
+use image::ImageEncoder;
+#[cfg(feature = "jpeg")]
+use image::codecs::jpeg::JpegEncoder;
+#[cfg(feature = "png")]
+use image::codecs::png::PngEncoder;
+#[cfg(feature = "webp")]
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, RgbaImage};
+#[cfg(feature = "ktx2")]
 use ktx2_rw::{BasisCompressionParams, Ktx2Texture};
 
-/// Enum to specify the type of texture for appropriate compression settings
-#[derive(Debug, Clone, Copy)]
-enum TextureType {
-    BaseColor,         // sRGB color textures
-    Normal,            // Normal maps (need higher quality)
-    MetallicRoughness, // Material property textures
+#[cfg(all(feature = "ktx2", feature = "zstd"))]
+use crate::transcode::zstd_supercompress;
+use crate::{
+    alpha::{
+        ALPHA_PREMULTIPLIED_KEY, ALPHA_PRUNED_KEY, AlphaConversion, convert_alpha,
+        opaque_base_color, prune_opaque_alpha,
+    },
+    animation::{add_quantized_output, add_repaired_input, linearize_cubic_samplers},
+    anonymize::{TextureAnonymization, anonymize_rgba},
+    atlas::atlas_textures,
+    batch::TextureCache,
+    best_effort::{BestEffortFallback, BestEffortIssue},
+    blob::{BlobWriter, content_hash},
+    bounds::{for_each_mesh_instance, scene_roots},
+    budget::plan_texture_budget,
+    collapse::collapse_materials,
+    compat::downgrade_for_profile,
+    convert::{read_accessor, write_elements},
+    crop::{RewrittenDocument, bake_texture_transforms, crop_to_uv_range, normalize_uv_range},
+    decode::is_reoriented,
+    dedupe::dedupe_meshes,
+    density::triangle_texture_scales,
+    dither::{DitherOptions, dither_rgba, with_source_layout},
+    encoder::{EncodedTexture, TextureEncoder},
+    environment::{copy_image_based_lights, texture_dimensions},
+    error::{OptimizeError, Stage},
+    extension::retain_extensions,
+    fallback::{TextureEncoding, TextureFallbacks},
+    features::FeatureNotEnabled,
+    filter::ExternalTextureFilter,
+    geometry::{GeometryCodec, encode_geometry},
+    glb::{accessor_stride, element_size, merge_extras, push_positions, read_glb, write_glb},
+    gutter::dilate_gutters,
+    hints::{hinted_texture_sizes, object_hints, validate_hints},
+    lod::{LOD_EXTENSION, add_lod_levels, lod_ids, lod_texture_scales},
+    material_extensions::{
+        MATERIAL_EXTENSIONS, extension_texture_type, list_material_extensions,
+        rewrite_texture_infos,
+    },
+    math::{IDENTITY, Mat4, transform_point},
+    merge::merge_primitives,
+    meshlet::add_meshlet_metadata,
+    meshopt::compress_views,
+    metrics::{Counter, Histogram, MetricsSink},
+    mips::{MipRounding, ktx2_mip_tail, mip_chain},
+    morph::{add_morph_targets, limit_morph_targets},
+    optimizer::Optimizer,
+    options::{OptimizeOptions, SlotTextureSizes},
+    positions::{Bounds, f32_vec3_range, offset_vec3_bytes, position_bounds},
+    progress::Progress,
+    prune::{prune_samplers, prune_unreachable},
+    quality::{DEFAULT_JPEG_QUALITY, detail_score, jpeg_quality},
+    quantize::quantize_attributes as quantize_vertex_attributes,
+    reencode::ReencodeSkip,
+    remap::{RemapTables, note_index},
+    reorder::{VertexReorder, reorder_vertices},
+    rescale::rescale_scene,
+    scratch::TextureScratch,
+    sharpen::{Sharpen, sharpen_rgba},
+    simplify::simplify_meshes,
+    skinning::repair_skinning,
+    slot_check::{class_mismatches, slot_mismatches},
+    specgloss::convert_spec_gloss,
+    split::split_large_primitives,
+    sprite::source_size_textures,
+    srgb::{linear_to_srgb, srgb_to_linear},
+    stats::{image_dimensions, stored_level_count},
+    streaming::{layout_for_streaming, refresh_manifest},
+    texture_table::{refresh_texture_table, write_texture_table},
+    transcode::{TextureCompressionProfile, TextureCompressionProfiles, TranscodeTargets},
+    upscale::{TextureUpscale, upscale_image},
+    variants::{VARIANTS_EXTENSION, remap_variant_materials, variant_materials},
+    views::share_accessor_views,
+    watermark::{Watermark, stamp_watermark},
+};
+#[cfg(feature = "ktx2")]
+use crate::{
+    quality::basis_quality_level,
+    transcode::{BasisMode, TRANSCODE_TARGETS_KEY, reserved_key_error},
+};
+
+/// Material slot a texture is used in, which decides its compression settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TextureType {
+    /// sRGB color textures
+    BaseColor,
+    /// Normal maps (need higher quality)
+    Normal,
+    /// Material property textures
+    MetallicRoughness,
+    /// Ambient occlusion (often packed into the metallic/roughness texture)
+    Occlusion,
+    /// sRGB emissive color
+    Emissive,
+}
+
+impl TextureType {
+    /// Every slot type, in material order
+    pub const ALL: [TextureType; 5] = [
+        TextureType::BaseColor,
+        TextureType::MetallicRoughness,
+        TextureType::Normal,
+        TextureType::Occlusion,
+        TextureType::Emissive,
+    ];
+
+    /// Output size for the slot; data maps are kept at half resolution
+    pub(crate) fn default_size(self, texture_size: u32) -> u32 {
+        match self {
+            TextureType::MetallicRoughness | TextureType::Occlusion => texture_size / 2,
+            TextureType::BaseColor | TextureType::Normal | TextureType::Emissive => texture_size,
+        }
+    }
+
+    /// Whether the slot holds sRGB-encoded color rather than linear data
+    pub(crate) fn is_srgb(self) -> bool {
+        matches!(self, TextureType::BaseColor | TextureType::Emissive)
+    }
+
+    /// Slot name used in error messages
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            TextureType::BaseColor => "base color",
+            TextureType::Normal => "normal",
+            TextureType::MetallicRoughness => "metallic/roughness",
+            TextureType::Occlusion => "occlusion",
+            TextureType::Emissive => "emissive",
+        }
+    }
+}
+
+/// Encoding the built-in encoders write every processed texture as, see
+/// [`OptimizeOptions::image_format`](crate::OptimizeOptions::image_format)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    /// Same as `convert_to_ktx2`
+    Ktx2,
+    /// Lossless WebP referenced through `EXT_texture_webp`
+    WebP,
+}
+
+/// Resize to exactly `width` x `height` into `buffer`, keeping the image's pixel layout
+#[cfg(feature = "resize")]
+fn resize_pixels<'b>(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    buffer: &'b mut Vec<u8>,
+) -> Result<&'b [u8], OptimizeError> {
+    let pixel_type = img
+        .pixel_type()
+        .ok_or(fast_image_resize::ImageError::UnsupportedPixelType)
+        .map_err(OptimizeError::resize)?;
+    buffer.clear();
+    buffer.resize(width as usize * height as usize * pixel_type.size(), 0);
+    let mut dst_img =
+        fast_image_resize::images::Image::from_slice_u8(width, height, buffer, pixel_type)
+            .map_err(OptimizeError::resize)?;
+
+    let mut resizer = fast_image_resize::Resizer::new();
+    resizer
+        .resize(img, &mut dst_img, None)
+        .map_err(OptimizeError::resize)?;
+    Ok(buffer)
+}
+
+/// Resize with the Lanczos3 filter of `image`, for builds without `fast_image_resize`
+#[cfg(not(feature = "resize"))]
+fn resize_pixels<'b>(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+    buffer: &'b mut Vec<u8>,
+) -> Result<&'b [u8], OptimizeError> {
+    let resized = img.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+    buffer.clear();
+    buffer.extend_from_slice(resized.as_bytes());
+    Ok(buffer)
 }
 
-fn resize_to_jpg<W: Write>(
+/// Resize and encode as JPEG
+///
+/// `img_data` is the source file, copied through when no resize is needed and it already is a
+/// JPEG; pass an empty slice to always re-encode (e.g. after pixels were altered). Resized
+/// pixels go through `resized`, which is reused between calls.
+pub(crate) fn resize_to_jpg<W: Write>(
+    img: &DynamicImage,
     img_data: &[u8],
     width: u32,
     height: u32,
+    quality: u8,
+    resized: &mut Vec<u8>,
     mut buf: W,
-) -> Result<(), Box<dyn Error>> {
-    let img = image::load_from_memory(img_data)?;
-
-    // If image dimensions match target exactly, keep original bytes
-    if img.width() == width && img.height() == height {
+) -> Result<(), OptimizeError> {
+    // If image dimensions match target exactly, keep original bytes when they are JPEG
+    if img_data.starts_with(&[0xFF, 0xD8]) && img.width() == width && img.height() == height {
         buf.write_all(img_data)?;
         return Ok(());
     }
 
-    // Only resize if image dimensions are greater than target dimensions
-    if img.width() > width || img.height() > height {
-        let mut dst_img = fast_image_resize::images::Image::new(
-            width,
-            height,
-            img.pixel_type().ok_or("failed to create resize image")?,
-        );
-
-        let mut resizer = fast_image_resize::Resizer::new();
-        resizer.resize(&img, &mut dst_img, None)?;
-
-        JpegEncoder::new(&mut buf).write_image(
-            dst_img.buffer(),
-            width,
-            height,
-            img.color().into(),
-        )?;
+    // Resize unless the image already has the target dimensions
+    if (img.width(), img.height()) != (width, height) {
+        let resized = resize_pixels(img, width, height, resized)?;
+        encode_jpg(resized, width, height, img.color(), quality, buf)
     } else {
-        // If image is smaller than target size, re-encode without resizing
-        JpegEncoder::new(&mut buf).write_image(
+        // Already at the target size, re-encode without resizing
+        encode_jpg(
             img.as_bytes(),
             img.width(),
             img.height(),
-            img.color().into(),
-        )?;
+            img.color(),
+            quality,
+            buf,
+        )
     }
+}
 
+#[cfg(feature = "jpeg")]
+fn encode_jpg<W: Write>(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color: image::ColorType,
+    quality: u8,
+    mut buf: W,
+) -> Result<(), OptimizeError> {
+    JpegEncoder::new_with_quality(&mut buf, quality).write_image(
+        pixels,
+        width,
+        height,
+        color.into(),
+    )?;
     Ok(())
 }
 
-fn resize_to_png<W: Write>(
+#[cfg(not(feature = "jpeg"))]
+fn encode_jpg<W: Write>(
+    _pixels: &[u8],
+    _width: u32,
+    _height: u32,
+    _color: image::ColorType,
+    _quality: u8,
+    _buf: W,
+) -> Result<(), OptimizeError> {
+    Err(FeatureNotEnabled { feature: "jpeg" }.into())
+}
+
+/// Resize and encode as PNG; `img_data` and `resized` as for [`resize_to_jpg`]
+pub(crate) fn resize_to_png<W: Write>(
+    img: &DynamicImage,
     img_data: &[u8],
     width: u32,
     height: u32,
+    resized: &mut Vec<u8>,
     mut buf: W,
-) -> Result<(), Box<dyn Error>> {
-    let img = image::load_from_memory(img_data)?;
-
-    // If image dimensions match target exactly, keep original bytes
-    if img.width() == width && img.height() == height {
+) -> Result<(), OptimizeError> {
+    // If image dimensions match target exactly, keep original bytes when they are PNG
+    if img_data.starts_with(b"\x89PNG") && img.width() == width && img.height() == height {
         buf.write_all(img_data)?;
         return Ok(());
     }
 
-    // Only resize if image dimensions are greater than target dimensions
-    if img.width() > width || img.height() > height {
-        let mut dst_img = fast_image_resize::images::Image::new(
-            width,
-            height,
-            img.pixel_type().ok_or("failed to create resize image")?,
-        );
+    // Resize unless the image already has the target dimensions
+    if (img.width(), img.height()) != (width, height) {
+        let resized = resize_pixels(img, width, height, resized)?;
+        encode_png(resized, width, height, img.color(), buf)
+    } else {
+        // Already at the target size, re-encode without resizing
+        encode_png(img.as_bytes(), img.width(), img.height(), img.color(), buf)
+    }
+}
+
+#[cfg(feature = "png")]
+pub(crate) fn encode_png<W: Write>(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color: image::ColorType,
+    buf: W,
+) -> Result<(), OptimizeError> {
+    PngEncoder::new(buf).write_image(pixels, width, height, color.into())?;
+    Ok(())
+}
 
-        let mut resizer = fast_image_resize::Resizer::new();
-        resizer.resize(&img, &mut dst_img, None)?;
+#[cfg(not(feature = "png"))]
+pub(crate) fn encode_png<W: Write>(
+    _pixels: &[u8],
+    _width: u32,
+    _height: u32,
+    _color: image::ColorType,
+    _buf: W,
+) -> Result<(), OptimizeError> {
+    Err(FeatureNotEnabled { feature: "png" }.into())
+}
 
-        PngEncoder::new(&mut buf).write_image(
-            dst_img.buffer(),
-            width,
-            height,
-            img.color().into(),
-        )?;
+/// Resize and encode as lossless WebP; `img_data` and `resized` as for [`resize_to_jpg`]
+fn resize_to_webp<W: Write>(
+    img: &DynamicImage,
+    img_data: &[u8],
+    width: u32,
+    height: u32,
+    resized: &mut Vec<u8>,
+    mut buf: W,
+) -> Result<(), OptimizeError> {
+    // If image dimensions match target exactly, keep original bytes when they are WebP
+    if img_data.starts_with(b"RIFF")
+        && img_data.get(8..12) == Some(b"WEBP")
+        && img.width() == width
+        && img.height() == height
+    {
+        buf.write_all(img_data)?;
+        return Ok(());
+    }
+
+    // The encoder only takes 8-bit gray and RGB(A)
+    let eight_bit;
+    let img = match img.color() {
+        image::ColorType::L8
+        | image::ColorType::La8
+        | image::ColorType::Rgb8
+        | image::ColorType::Rgba8 => img,
+        _ => {
+            eight_bit = DynamicImage::from(img.to_rgba8());
+            &eight_bit
+        }
+    };
+    if (img.width(), img.height()) != (width, height) {
+        let resized = resize_pixels(img, width, height, resized)?;
+        encode_webp(resized, width, height, img.color(), buf)
     } else {
-        // If image is smaller than target size, re-encode without resizing
-        PngEncoder::new(&mut buf).write_image(
-            img.as_bytes(),
-            img.width(),
-            img.height(),
-            img.color().into(),
-        )?;
+        encode_webp(img.as_bytes(), width, height, img.color(), buf)
     }
+}
 
+#[cfg(feature = "webp")]
+fn encode_webp<W: Write>(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color: image::ColorType,
+    buf: W,
+) -> Result<(), OptimizeError> {
+    WebPEncoder::new_lossless(buf).write_image(pixels, width, height, color.into())?;
     Ok(())
 }
 
+#[cfg(not(feature = "webp"))]
+fn encode_webp<W: Write>(
+    _pixels: &[u8],
+    _width: u32,
+    _height: u32,
+    _color: image::ColorType,
+    _buf: W,
+) -> Result<(), OptimizeError> {
+    Err(FeatureNotEnabled { feature: "webp" }.into())
+}
+
 /// Unified function to resize and convert images to KTX2 with Basis Universal compression
 /// Uses appropriate compression settings based on texture type
 /// Preserves original color space (RGB vs RGBA)
+#[cfg(feature = "ktx2")]
+#[allow(clippy::too_many_arguments)]
 fn resize_to_ktx2<W: Write>(
-    img_data: &[u8],
+    img: &DynamicImage,
     width: u32,
     height: u32,
-    texture_type: TextureType,
+    profile: TextureCompressionProfile,
+    transcode_targets: TranscodeTargets,
+    metadata: &[(String, String)],
+    detail: Option<(f32, i8)>,
+    mipmaps: bool,
+    threads: u32,
+    resized: &mut Vec<u8>,
     mut buf: W,
-) -> Result<(), Box<dyn Error>> {
-    let img = image::load_from_memory(img_data)?;
-
-    // Get compression parameters based on texture type
-    let (quality_level, endpoint_rdo, selector_rdo) = match texture_type {
-        TextureType::Normal => (180, 1.0, 1.0), // Higher quality for normal maps
-        TextureType::BaseColor | TextureType::MetallicRoughness => (150, 1.25, 1.25), // Standard quality
+) -> Result<(), OptimizeError> {
+    if let Some(e) = metadata.iter().find_map(|(key, _)| reserved_key_error(key)) {
+        return Err(e);
+    }
+    let uastc = profile.mode == BasisMode::Uastc;
+    let zstd_level = profile.zstd_level.filter(|_| uastc);
+    if zstd_level.is_some() && !cfg!(feature = "zstd") {
+        return Err(FeatureNotEnabled { feature: "zstd" }.into());
+    }
+    // Auto quality: follow the image's detail around the slot's level
+    let quality_level = match detail {
+        Some((score, bias)) => basis_quality_level(profile.quality_level, score, bias),
+        None => profile.quality_level,
     };
 
     // Determine if original image has alpha channel
@@ -136,307 +431,1249 @@ fn resize_to_ktx2<W: Write>(
             | image::ColorType::La16
     );
 
-    let (vk_format, pixel_type) = if has_alpha {
-        (
-            ktx2_rw::VkFormat::R8G8B8A8Unorm,
-            fast_image_resize::PixelType::U8x4,
-        )
+    let vk_format = if has_alpha {
+        ktx2_rw::VkFormat::R8G8B8A8Unorm
     } else {
+        ktx2_rw::VkFormat::R8G8B8Unorm
+    };
+    // Images already in the target layout are read in place
+    let converted = match img {
+        DynamicImage::ImageRgba8(_) if has_alpha => Cow::Borrowed(img),
+        DynamicImage::ImageRgb8(_) if !has_alpha => Cow::Borrowed(img),
+        _ if has_alpha => Cow::Owned(DynamicImage::from(img.to_rgba8())),
+        _ => Cow::Owned(DynamicImage::from(img.to_rgb8())),
+    };
+
+    // Resize unless the image already has the target dimensions
+    let (final_width, final_height, final_data) = if (img.width(), img.height()) != (width, height)
+    {
         (
-            ktx2_rw::VkFormat::R8G8B8Unorm,
-            fast_image_resize::PixelType::U8x3,
+            width,
+            height,
+            resize_pixels(&converted, width, height, resized)?,
         )
+    } else {
+        (img.width(), img.height(), converted.as_bytes())
     };
 
-    // Only resize if image dimensions are greater than target dimensions
-    if img.width() > width || img.height() > height {
-        let (src_data, src_width, src_height) = if has_alpha {
-            let rgba_img = img.to_rgba8();
-            (rgba_img.into_raw(), img.width(), img.height())
-        } else {
-            let rgb_img = img.to_rgb8();
-            (rgb_img.into_raw(), img.width(), img.height())
-        };
-
-        let src_img = fast_image_resize::images::Image::from_vec_u8(
-            src_width, src_height, src_data, pixel_type,
-        )?;
-
-        let mut dst_img = fast_image_resize::images::Image::new(width, height, pixel_type);
-
-        let mut resizer = fast_image_resize::Resizer::new();
-        resizer.resize(&src_img, &mut dst_img, None)?;
+    let levels = if mipmaps {
+        mip_levels(final_width, final_height, final_data, has_alpha)?
+    } else {
+        Vec::new()
+    };
+    let mut ktx2_tex = Ktx2Texture::create(
+        final_width,
+        final_height,
+        1,
+        1,
+        1,
+        1 + levels.len() as u32,
+        vk_format,
+    )
+    .map_err(OptimizeError::ktx2)?;
+    ktx2_tex
+        .set_image_data(0, 0, 0, final_data)
+        .map_err(OptimizeError::ktx2)?;
+    for (level, data) in levels.iter().enumerate() {
+        ktx2_tex
+            .set_image_data(level as u32 + 1, 0, 0, data.as_bytes())
+            .map_err(OptimizeError::ktx2)?;
+    }
+    ktx2_tex
+        .set_metadata("Tool", b"glb_opt")
+        .map_err(OptimizeError::ktx2)?;
+    ktx2_tex
+        .set_metadata(
+            "Dimensions",
+            format!("{final_width}x{final_height}").as_bytes(),
+        )
+        .map_err(OptimizeError::ktx2)?;
+
+    let params = BasisCompressionParams::builder()
+        .uastc(uastc)
+        .thread_count(threads)
+        .quality_level(quality_level)
+        .endpoint_rdo_threshold(profile.endpoint_rdo_threshold)
+        .selector_rdo_threshold(profile.selector_rdo_threshold)
+        .uastc_flags(profile.uastc_level.min(4))
+        .uastc_rdo(profile.uastc_rdo_quality.is_some())
+        .uastc_rdo_quality_scalar(profile.uastc_rdo_quality.unwrap_or(1.0))
+        .build();
+    ktx2_tex
+        .compress_basis(&params)
+        .map_err(OptimizeError::ktx2)?;
+    ktx2_tex
+        .set_metadata(
+            "CompressionMode",
+            if uastc { b"UASTC".as_slice() } else { b"ETC1S" },
+        )
+        .map_err(OptimizeError::ktx2)?;
+    if !transcode_targets.is_empty() {
+        ktx2_tex
+            .set_metadata(
+                TRANSCODE_TARGETS_KEY,
+                transcode_targets.to_string().as_bytes(),
+            )
+            .map_err(OptimizeError::ktx2)?;
+    }
+    for (key, value) in metadata {
+        ktx2_tex
+            .set_metadata(key, value.as_bytes())
+            .map_err(OptimizeError::ktx2)?;
+    }
 
-        let mut ktx2_tex = Ktx2Texture::create(width, height, 1, 1, 1, 1, vk_format)?;
-        ktx2_tex.set_image_data(0, 0, 0, dst_img.buffer())?;
-        ktx2_tex.set_metadata("Tool", b"glb_opt")?;
-        ktx2_tex.set_metadata("Dimensions", format!("{width}x{height}").as_bytes())?;
+    let ktx2_data = ktx2_tex.write_to_memory().map_err(OptimizeError::ktx2)?;
+    #[cfg(feature = "zstd")]
+    let ktx2_data = match zstd_level {
+        Some(level) => zstd_supercompress(&ktx2_data, level)?,
+        None => ktx2_data,
+    };
+    buf.write_all(&ktx2_data)?;
 
-        let etc1s_params = BasisCompressionParams::builder()
-            .uastc(false)
-            .thread_count(num_cpus::get() as u32)
-            .quality_level(quality_level)
-            .endpoint_rdo_threshold(endpoint_rdo)
-            .selector_rdo_threshold(selector_rdo)
-            .build();
-        ktx2_tex.compress_basis(&etc1s_params)?;
-        ktx2_tex.set_metadata("CompressionMode", b"ETC1S")?;
+    Ok(())
+}
 
-        let ktx2_data = ktx2_tex.write_to_memory()?;
-        buf.write_all(&ktx2_data)?;
-    } else {
-        // If image is smaller or equal to target size, convert to KTX2 without resizing
-        let (final_data, final_width, final_height) = if has_alpha {
-            let rgba_img = img.to_rgba8();
-            (rgba_img.into_raw(), img.width(), img.height())
+/// Mip levels below an RGB8/RGBA8 base level, halving down to 1x1
+#[cfg(feature = "ktx2")]
+fn mip_levels(
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    has_alpha: bool,
+) -> Result<Vec<DynamicImage>, OptimizeError> {
+    let image = |width, height, pixels: Vec<u8>| -> Result<DynamicImage, OptimizeError> {
+        let image = if has_alpha {
+            image::RgbaImage::from_raw(width, height, pixels).map(DynamicImage::from)
         } else {
-            let rgb_img = img.to_rgb8();
-            (rgb_img.into_raw(), img.width(), img.height())
+            image::RgbImage::from_raw(width, height, pixels).map(DynamicImage::from)
         };
+        image.ok_or(OptimizeError::ImageSize { width, height })
+    };
 
-        let mut ktx2_tex = Ktx2Texture::create(final_width, final_height, 1, 1, 1, 1, vk_format)?;
-        ktx2_tex.set_image_data(0, 0, 0, &final_data)?;
-        ktx2_tex.set_metadata("Tool", b"glb_opt")?;
-        ktx2_tex.set_metadata(
-            "Dimensions",
-            format!("{}x{}", final_width, final_height).as_bytes(),
-        )?;
+    let base = image(width, height, pixels.to_vec())?;
+    let mut levels: Vec<DynamicImage> = Vec::new();
+    let mut buffer = Vec::new();
+    for (width, height) in mip_chain(width, height).into_iter().skip(1) {
+        let above = levels.last().unwrap_or(&base);
+        let pixels = resize_pixels(above, width, height, &mut buffer)?.to_vec();
+        levels.push(image(width, height, pixels)?);
+    }
+    Ok(levels)
+}
 
-        let etc1s_params = BasisCompressionParams::builder()
-            .uastc(false)
-            .thread_count(num_cpus::get() as u32)
-            .quality_level(quality_level)
-            .endpoint_rdo_threshold(endpoint_rdo)
-            .selector_rdo_threshold(selector_rdo)
-            .build();
-        ktx2_tex.compress_basis(&etc1s_params)?;
-        ktx2_tex.set_metadata("CompressionMode", b"ETC1S")?;
+#[cfg(not(feature = "ktx2"))]
+#[allow(clippy::too_many_arguments)]
+fn resize_to_ktx2<W: Write>(
+    _img: &DynamicImage,
+    _width: u32,
+    _height: u32,
+    _profile: TextureCompressionProfile,
+    _transcode_targets: TranscodeTargets,
+    _metadata: &[(String, String)],
+    _detail: Option<(f32, i8)>,
+    _mipmaps: bool,
+    _threads: u32,
+    _resized: &mut Vec<u8>,
+    _buf: W,
+) -> Result<(), OptimizeError> {
+    Err(FeatureNotEnabled { feature: "ktx2" }.into())
+}
 
-        let ktx2_data = ktx2_tex.write_to_memory()?;
-        buf.write_all(&ktx2_data)?;
+/// Output MIME type for a texture slot
+fn texture_mime_type(texture_type: TextureType, encoding: BuiltinEncoding) -> &'static str {
+    if encoding.convert_to_ktx2 {
+        return "image/ktx2";
     }
+    match (encoding.image_format, texture_type) {
+        (Some(ImageFormat::Jpeg), _) => "image/jpeg",
+        (Some(ImageFormat::Png), _) => "image/png",
+        (Some(ImageFormat::WebP), _) => "image/webp",
+        (_, TextureType::Normal) => "image/png",
+        (_, _) => "image/jpeg",
+    }
+}
 
-    Ok(())
+/// Settings of the built-in JPEG/PNG/KTX2 encoders
+#[derive(Clone, Copy)]
+struct BuiltinEncoding<'a> {
+    convert_to_ktx2: bool,
+    /// Format of non-KTX2 output, `None` for JPEG with PNG normal maps
+    image_format: Option<ImageFormat>,
+    ktx2_mipmaps: bool,
+    /// Base level rounding of mipmapped KTX2 output
+    mip_rounding: MipRounding,
+    compression_profiles: TextureCompressionProfiles,
+    /// Encodings a failed texture is retried with, per slot
+    fallbacks: &'a TextureFallbacks,
+    transcode_targets: TranscodeTargets,
+    ktx2_metadata: &'a [(String, String)],
+    auto_quality: Option<i8>,
+    dither: DitherOptions,
+    sharpen: Option<Sharpen>,
+    linear_light_resize: bool,
+    alpha_conversion: Option<AlphaConversion>,
+    watermark: Option<&'a Watermark>,
+    anonymization: Option<TextureAnonymization>,
+    /// Threads per Basis Universal encode
+    encoder_threads: u32,
+    /// Textures encoded at once
+    worker_threads: usize,
 }
 
-/// Helper function to update image name/URI when converting to KTX2
-fn update_image_name_for_ktx2(image_name: &Option<String>) -> Option<String> {
-    if let Some(name) = image_name {
-        // Replace common image extensions with .ktx2
-        let updated_name = name
-            .replace(".jpg", ".ktx2")
-            .replace(".jpeg", ".ktx2")
-            .replace(".png", ".ktx2")
-            .replace(".webp", ".ktx2")
-            .replace(".JPG", ".ktx2")
-            .replace(".JPEG", ".ktx2")
-            .replace(".PNG", ".ktx2")
-            .replace(".WEBP", ".ktx2");
-
-        // If no extension was found, just append .ktx2
-        if updated_name == *name {
-            Some(format!("{name}.ktx2"))
-        } else {
-            Some(updated_name)
+impl<'a> BuiltinEncoding<'a> {
+    fn new(options: &'a OptimizeOptions) -> Self {
+        BuiltinEncoding {
+            convert_to_ktx2: options.writes_ktx2(),
+            image_format: options.image_format,
+            ktx2_mipmaps: options.ktx2_mipmaps,
+            mip_rounding: options.ktx2_mip_rounding,
+            compression_profiles: options.compression_profiles,
+            fallbacks: &options.texture_fallbacks,
+            transcode_targets: options.transcode_targets,
+            ktx2_metadata: &options.ktx2_metadata,
+            auto_quality: options.auto_quality,
+            dither: options.dither,
+            sharpen: options.sharpen,
+            linear_light_resize: options.linear_light_resize,
+            alpha_conversion: options.alpha_conversion,
+            watermark: options.watermark.as_ref(),
+            anonymization: options.anonymize_textures,
+            encoder_threads: options.basis_threads(),
+            worker_threads: options.worker_threads(),
         }
-    } else {
-        None
     }
-}
 
-fn add_image(
-    n_blob: &mut Vec<u8>,
-    n_json: &mut Root,
-    img: &gltf::json::Image,
-    b: &[u8],
-    mime_type: &str,
-) -> Index<gltf::json::Image> {
-    let offset = n_blob.len();
-    let length = b.len();
+    /// This encoding switched to one step of a slot's fallback chain
+    fn fallback(self, texture_type: TextureType, fallback: TextureEncoding) -> Self {
+        match fallback {
+            TextureEncoding::Ktx2(profile) => {
+                let mut compression_profiles = self.compression_profiles;
+                *compression_profiles.for_type_mut(texture_type) = profile;
+                BuiltinEncoding {
+                    convert_to_ktx2: true,
+                    compression_profiles,
+                    ..self
+                }
+            }
+            TextureEncoding::Image(ImageFormat::Ktx2) => BuiltinEncoding {
+                convert_to_ktx2: true,
+                ..self
+            },
+            TextureEncoding::Image(format) => BuiltinEncoding {
+                convert_to_ktx2: false,
+                image_format: Some(format),
+                ..self
+            },
+        }
+    }
 
-    n_blob.extend_from_slice(b);
+    /// Whether this encoding changes the pixels of base color textures, which then can't be
+    /// copied from the source
+    fn alters_base_color(&self) -> bool {
+        self.watermark.is_some() || self.anonymization.is_some()
+    }
 
-    let view = gltf::json::buffer::View {
-        buffer: Index::<gltf::json::buffer::Buffer>::new(0),
-        byte_length: length.into(),
-        byte_offset: if offset == 0 {
-            None
+    /// Size `img` is encoded at for a slot of `size`, after mip rounding
+    fn output_dimensions(&self, img: &DynamicImage, size: u32, power_of_two: bool) -> (u32, u32) {
+        let rounding = self.mip_rounding();
+        let power_of_two = power_of_two || rounding == MipRounding::PowerOfTwo;
+        let (width, height) = texture_dimensions(img.width(), img.height(), size, power_of_two);
+        rounding.round(width, height)
+    }
+
+    /// Base level rounding this encoding applies, `Keep` unless it writes KTX2 mip chains
+    fn mip_rounding(&self) -> MipRounding {
+        if self.convert_to_ktx2 && self.ktx2_mipmaps {
+            self.mip_rounding
         } else {
-            Some(offset.into())
-        },
-        byte_stride: None,
-        name: None,
-        target: None,
-        extensions: None,
-        extras: Default::default(),
-    };
+            MipRounding::Keep
+        }
+    }
+}
 
-    let view_idx = n_json.push(view);
+/// Resize and re-encode a decoded image into the format used for its slot
+fn encode_texture(
+    img: &DynamicImage,
+    img_data: &[u8],
+    size: u32,
+    power_of_two: bool,
+    texture_type: TextureType,
+    encoding: BuiltinEncoding,
+    scratch: &mut TextureScratch,
+) -> Result<Vec<u8>, OptimizeError> {
+    let mut data = scratch.take_encoded();
+    let mut writer = Cursor::new(&mut data);
+    let (width, height) = encoding.output_dimensions(img, size, power_of_two);
+    let detail = encoding.auto_quality.map(|bias| (detail_score(img), bias));
+
+    // Alter pixels at the output size, then have the encoders re-encode them
+    let alpha = encoding
+        .alpha_conversion
+        .filter(|_| texture_type == TextureType::BaseColor && img.color().has_alpha());
+    let dither = encoding.dither.for_type(texture_type);
+    // Color maps are averaged in linear light and sharpened only when shrunk; sharpening
+    // enlarged or kept pixels would just add halos, and data maps would gain artifacts
+    let shrunk = texture_type.is_srgb() && (width < img.width() || height < img.height());
+    let linear = encoding.linear_light_resize && shrunk;
+    let sharpen = encoding.sharpen.filter(|_| shrunk);
+    let base_color = texture_type == TextureType::BaseColor;
+    let anonymization = encoding.anonymization.filter(|_| base_color);
+    let watermark = encoding.watermark.filter(|_| base_color);
+    let processed = if alpha.is_some()
+        || dither.is_some()
+        || sharpen.is_some()
+        || anonymization.is_some()
+        || watermark.is_some()
+        || linear
+    {
+        let mut rgba = match linear {
+            true => resize_rgba_linear(img, width, height)?,
+            false => resize_rgba_to(img, width, height)?.into_owned(),
+        };
+        if let Some(sharpen) = &sharpen {
+            sharpen_rgba(&mut rgba, sharpen);
+        }
+        if let Some(anonymization) = anonymization {
+            anonymize_rgba(&mut rgba, anonymization)?;
+        }
+        if let Some(watermark) = watermark {
+            stamp_watermark(&mut rgba, watermark)?;
+        }
+        if let Some(conversion) = alpha {
+            convert_alpha(&mut rgba, conversion);
+        }
+        if let Some(dither) = dither {
+            dither_rgba(&mut rgba, dither);
+        }
+        Some(with_source_layout(img, rgba))
+    } else {
+        None
+    };
+    let (img, img_data) = match &processed {
+        Some(processed) => (processed, &[][..]),
+        None => (img, img_data),
+    };
 
-    let mut n_img = img.clone();
+    if encoding.convert_to_ktx2 {
+        resize_to_ktx2(
+            img,
+            width,
+            height,
+            encoding.compression_profiles.for_type(texture_type),
+            encoding.transcode_targets,
+            encoding.ktx2_metadata,
+            detail,
+            encoding.ktx2_mipmaps,
+            encoding.encoder_threads,
+            &mut scratch.resized,
+            &mut writer,
+        )?;
+    } else {
+        match texture_mime_type(texture_type, encoding) {
+            "image/webp" => resize_to_webp(
+                img,
+                img_data,
+                width,
+                height,
+                &mut scratch.resized,
+                &mut writer,
+            )?,
+            "image/png" => resize_to_png(
+                img,
+                img_data,
+                width,
+                height,
+                &mut scratch.resized,
+                &mut writer,
+            )?,
+            _ => {
+                let quality = detail.map_or(DEFAULT_JPEG_QUALITY, |(score, bias)| {
+                    jpeg_quality(score, bias)
+                });
+                // glTF reads no alpha from other slots, and JPEG cannot hold it
+                let opaque;
+                let (img, img_data) = if !base_color && img.color().has_alpha() {
+                    opaque = DynamicImage::ImageRgb8(img.to_rgb8());
+                    (&opaque, &[][..])
+                } else {
+                    (img, img_data)
+                };
+                resize_to_jpg(
+                    img,
+                    img_data,
+                    width,
+                    height,
+                    quality,
+                    &mut scratch.resized,
+                    &mut writer,
+                )?
+            }
+        }
+    }
 
-    n_img.buffer_view = Some(view_idx);
-    n_img.mime_type = Some(MimeType(mime_type.to_string()));
+    Ok(data)
+}
 
-    // Update name and URI when converting to KTX2
-    if mime_type == "image/ktx2" {
-        // Update existing name/URI if they exist
-        n_img.name = update_image_name_for_ktx2(&img.name);
-        n_img.uri = update_image_name_for_ktx2(&img.uri);
+/// Fallback encodings a texture was retried with, each with the error of the attempt before
+type FallbackSteps = Vec<(TextureEncoding, String)>;
 
-        // If no name was set, generate a default KTX2 name
-        if n_img.name.is_none() {
-            n_img.name = Some(format!("texture_{}.ktx2", n_json.images.len()));
+/// [`encode_texture`], retrying the encodings of the slot's fallback chain in turn while it
+/// fails
+///
+/// Returns the encoding that succeeded with the steps taken to reach it. Missing features
+/// are not retried.
+fn encode_with_fallbacks<'a>(
+    img: &DynamicImage,
+    img_data: &[u8],
+    size: u32,
+    power_of_two: bool,
+    texture_type: TextureType,
+    encoding: BuiltinEncoding<'a>,
+    scratch: &mut TextureScratch,
+) -> Result<(Vec<u8>, BuiltinEncoding<'a>, FallbackSteps), OptimizeError> {
+    let encode = |encoding, scratch: &mut TextureScratch| {
+        encode_texture(
+            img,
+            img_data,
+            size,
+            power_of_two,
+            texture_type,
+            encoding,
+            scratch,
+        )
+    };
+    let mut error = match encode(encoding, scratch) {
+        Ok(data) => return Ok((data, encoding, Vec::new())),
+        Err(e) => e,
+    };
+    let mut steps = Vec::new();
+    for &fallback in encoding.fallbacks.for_type(texture_type) {
+        if let OptimizeError::FeatureNotEnabled(_) = error {
+            break;
+        }
+        steps.push((fallback, error.to_string()));
+        let retry = encoding.fallback(texture_type, fallback);
+        match encode(retry, scratch) {
+            Ok(data) => return Ok((data, retry, steps)),
+            Err(e) => error = e,
         }
     }
+    Err(error)
+}
 
-    n_json.push(n_img)
+/// Image whose encoding was postponed so it can be produced at several sizes
+struct TextureJob {
+    /// Output image holding an empty placeholder view
+    image: Index<gltf::json::Image>,
+    /// Source texture the pixels come from
+    texture: Index<Texture>,
+    texture_type: TextureType,
 }
 
-fn add_accessor(
-    n_blob: &mut Vec<u8>,
-    n_json: &mut Root,
-    o_blob: &[u8],
-    o_json: &gltf::json::Root,
-    idx: Index<gltf::json::Accessor>,
-) -> Option<Index<gltf::json::Accessor>> {
-    add_accessor_with_offset(n_blob, n_json, o_blob, o_json, idx, None)
+/// Where processed textures get encoded
+#[derive(Clone, Copy)]
+#[allow(clippy::large_enum_variant)]
+enum TextureFormat<'a> {
+    /// JPEG/PNG, or the format `convert_to_ktx2` or `image_format` pick
+    Builtin(BuiltinEncoding<'a>),
+    /// User encoder fed with resized RGBA pixels
+    Custom(&'a dyn TextureEncoder),
 }
 
-/// Add accessor with optional position offset for POSITION attributes
-fn add_accessor_with_offset(
-    n_blob: &mut Vec<u8>,
-    n_json: &mut Root,
-    o_blob: &[u8],
-    o_json: &gltf::json::Root,
-    idx: Index<gltf::json::Accessor>,
-    position_offset: Option<[f32; 3]>,
-) -> Option<Index<gltf::json::Accessor>> {
-    if let Some(acc) = o_json.accessors.get(idx.value())
-        && let Some(idx_view) = acc.buffer_view
-        && let Some(view) = o_json.buffer_views.get(idx_view.value())
-    {
-        let offset = match view.byte_offset {
-            Some(o) => o.0 as usize,
-            None => 0,
+/// Texture format plus the sizing and upscaling applied first and the sinks encodes are
+/// reported to
+#[derive(Clone, Copy)]
+struct TextureOutput<'a> {
+    format: TextureFormat<'a>,
+    slot_sizes: SlotTextureSizes,
+    power_of_two: bool,
+    upscale: Option<TextureUpscale>,
+    reencode_skip: ReencodeSkip,
+    /// Step run on pixels and encoded bytes around every encode
+    filter: Option<&'a dyn ExternalTextureFilter>,
+    metrics: Option<&'a dyn MetricsSink>,
+    progress: Option<&'a Progress<'a>>,
+    /// Encodes kept from earlier runs of a batch, for the built-in encoders
+    cache: Option<&'a TextureCache>,
+}
+
+impl TextureOutput<'_> {
+    fn encode(
+        &self,
+        img: &DynamicImage,
+        img_data: &[u8],
+        size: u32,
+        texture_type: TextureType,
+        scratch: &mut TextureScratch,
+    ) -> Result<(EncodedTexture, FallbackSteps), OptimizeError> {
+        if let Some(progress) = self.progress {
+            progress.check()?;
+        }
+        let start = Instant::now();
+        let cached = match (self.cache, self.format) {
+            // Filtered encodes depend on more than the key covers
+            (Some(cache), TextureFormat::Builtin(encoding)) if self.filter.is_none() => {
+                let key = cache.key(img, img_data, size, texture_type, encoding.convert_to_ktx2);
+                Some((cache, key, texture_mime_type(texture_type, encoding)))
+            }
+            _ => None,
+        };
+        if let Some((cache, key, mime_type)) = cached
+            && let Some(data) = cache.get(key)
+        {
+            self.report(start.elapsed().as_secs_f64());
+            let encoded = EncodedTexture {
+                data,
+                mime_type: mime_type.to_string(),
+                extensions: Vec::new(),
+            };
+            return Ok((encoded, Vec::new()));
+        }
+        // Enlarged pixels always get encoded, never copied from the source file
+        let upscaled = self
+            .upscale
+            .and_then(|upscale| upscale_image(img, &upscale, size));
+        let (img, img_data) = match &upscaled {
+            Some(upscaled) => (upscaled, &[][..]),
+            None => (img, img_data),
+        };
+        let filtered = match self.filter {
+            Some(filter) => {
+                let rgba = resize_rgba(img, size, self.power_of_two)?;
+                filter
+                    .filter_pixels(&rgba, texture_type)
+                    .map_err(|source| OptimizeError::Callback {
+                        stage: Stage::TextureFilter,
+                        source,
+                    })?
+                    .map(DynamicImage::ImageRgba8)
+            }
+            None => None,
         };
-        let length = view.byte_length.0 as usize;
-
-        if let Some(data) = &o_blob.get(offset..(offset + length)) {
-            let n_offset = n_blob.len();
-
-            // If we have a position offset and this is a VEC3 accessor, apply the offset
-            if let Some(pos_offset) = position_offset {
-                let acc_offset = acc.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
-                let stride = view.byte_stride.map(|s| s.0).unwrap_or(12); // 3 * f32 = 12 bytes
-                let count = acc.count.0 as usize;
-
-                let mut modified_data = data.to_vec();
-
-                for i in 0..count {
-                    let start = acc_offset + i * stride;
-                    if start + 12 <= modified_data.len() {
-                        // Read current position
-                        let x = f32::from_le_bytes([
-                            modified_data[start],
-                            modified_data[start + 1],
-                            modified_data[start + 2],
-                            modified_data[start + 3],
-                        ]);
-                        let y = f32::from_le_bytes([
-                            modified_data[start + 4],
-                            modified_data[start + 5],
-                            modified_data[start + 6],
-                            modified_data[start + 7],
-                        ]);
-                        let z = f32::from_le_bytes([
-                            modified_data[start + 8],
-                            modified_data[start + 9],
-                            modified_data[start + 10],
-                            modified_data[start + 11],
-                        ]);
-
-                        // Apply offset
-                        let new_x = x + pos_offset[0];
-                        let new_y = y + pos_offset[1];
-                        let new_z = z + pos_offset[2];
-
-                        // Write back
-                        modified_data[start..start + 4].copy_from_slice(&new_x.to_le_bytes());
-                        modified_data[start + 4..start + 8].copy_from_slice(&new_y.to_le_bytes());
-                        modified_data[start + 8..start + 12].copy_from_slice(&new_z.to_le_bytes());
+        let (img, img_data) = match &filtered {
+            Some(filtered) => (filtered, &[][..]),
+            None => (img, img_data),
+        };
+        let (encoded, fallbacks) = match self.format {
+            TextureFormat::Builtin(encoding) => {
+                let (data, encoding, fallbacks) = encode_with_fallbacks(
+                    img,
+                    img_data,
+                    size,
+                    self.power_of_two,
+                    texture_type,
+                    encoding,
+                    scratch,
+                )?;
+                let encoded = EncodedTexture {
+                    data,
+                    mime_type: texture_mime_type(texture_type, encoding).to_string(),
+                    extensions: Vec::new(),
+                };
+                (encoded, fallbacks)
+            }
+            TextureFormat::Custom(encoder) => {
+                let img = resize_rgba(img, size, self.power_of_two)?;
+                let encoded = encoder.encode(&img, texture_type).map_err(|source| {
+                    OptimizeError::Callback {
+                        stage: Stage::TextureEncoder,
+                        source,
                     }
-                }
+                })?;
+                (encoded, Vec::new())
+            }
+        };
+        let encoded = match self.filter {
+            Some(filter) => filter
+                .filter_encoded(encoded, texture_type)
+                .map_err(|source| OptimizeError::Callback {
+                    stage: Stage::TextureFilter,
+                    source,
+                })?,
+            None => encoded,
+        };
+        // The key stands for the slot's own encoding, not a fallback
+        if let Some((cache, key, _)) = cached
+            && fallbacks.is_empty()
+        {
+            cache.put(key, &encoded.data);
+        }
+        self.report(start.elapsed().as_secs_f64());
+        Ok((encoded, fallbacks))
+    }
 
-                n_blob.extend_from_slice(&modified_data);
-
-                // Update accessor min/max values
-                let mut n_acc = acc.clone();
-                if let Some(ref mut min_val) = n_acc.min
-                    && let Some(min_arr) = min_val.as_array_mut()
-                    && min_arr.len() >= 3
-                    && let (Some(x), Some(y), Some(z)) = (
-                        min_arr[0].as_f64(),
-                        min_arr[1].as_f64(),
-                        min_arr[2].as_f64(),
-                    )
-                {
-                    min_arr[0] = (x as f32 + pos_offset[0]).into();
-                    min_arr[1] = (y as f32 + pos_offset[1]).into();
-                    min_arr[2] = (z as f32 + pos_offset[2]).into();
-                }
-                if let Some(ref mut max_val) = n_acc.max
-                    && let Some(max_arr) = max_val.as_array_mut()
-                    && max_arr.len() >= 3
-                    && let (Some(x), Some(y), Some(z)) = (
-                        max_arr[0].as_f64(),
-                        max_arr[1].as_f64(),
-                        max_arr[2].as_f64(),
-                    )
-                {
-                    max_arr[0] = (x as f32 + pos_offset[0]).into();
-                    max_arr[1] = (y as f32 + pos_offset[1]).into();
-                    max_arr[2] = (z as f32 + pos_offset[2]).into();
-                }
+    /// Report one texture encode that took `seconds` to the metrics and progress sinks
+    fn report(&self, seconds: f64) {
+        self.report_metrics(seconds);
+        if let Some(progress) = self.progress {
+            progress.texture_done();
+        }
+    }
 
-                // create buffer_view
-                let mut n_view = view.clone();
-                n_view.byte_offset = Some(n_offset.into());
-                n_view.byte_length = modified_data.len().into();
+    /// Report one texture encode that took `seconds` to the metrics sink
+    fn report_metrics(&self, seconds: f64) {
+        if let Some(metrics) = self.metrics {
+            metrics.increment(Counter::TexturesProcessed, 1);
+            metrics.observe(Histogram::TextureEncodeSeconds, seconds);
+        }
+    }
 
-                let n_view_idx = n_json.push(n_view);
+    /// Whether a source texture is copied as it is into a slot of `size`
+    fn keeps_source(
+        &self,
+        o_blob: &[u8],
+        o_json: &Root,
+        texture: Index<Texture>,
+        texture_type: TextureType,
+        size: u32,
+    ) -> bool {
+        let altered = match self.format {
+            TextureFormat::Builtin(encoding) => {
+                encoding.alters_base_color() && texture_type == TextureType::BaseColor
+            }
+            TextureFormat::Custom(_) => false,
+        };
+        !altered
+            && get_image_data(o_blob, o_json, texture)
+                .is_some_and(|data| self.reencode_skip.keeps(data, size, self.power_of_two))
+    }
 
-                n_acc.buffer_view = Some(n_view_idx);
-                let idx_acc = n_json.push(n_acc);
+    /// MIME type known before encoding, which only the built-in encoders provide
+    fn deferred_mime_type(&self, texture_type: TextureType) -> Result<&'static str, OptimizeError> {
+        match self.format {
+            TextureFormat::Builtin(encoding) => Ok(texture_mime_type(texture_type, encoding)),
+            TextureFormat::Custom(_) => Err(OptimizeError::InvalidOption(
+                "custom texture encoders cannot be deferred".to_string(),
+            )),
+        }
+    }
 
-                return Some(idx_acc);
-            } else {
-                let length = data.len();
-                n_blob.extend_from_slice(data);
+    /// Alpha convention the output of a slot is converted to, if any
+    fn alpha_premultiplied(&self, texture_type: TextureType) -> Option<bool> {
+        match self.format {
+            TextureFormat::Builtin(encoding) if texture_type == TextureType::BaseColor => {
+                encoding.alpha_conversion.map(|c| c.premultiplied())
+            }
+            _ => None,
+        }
+    }
+}
 
-                // create buffer_view
-                let mut n_view = view.clone();
-                n_view.byte_offset = Some(n_offset.into());
-                n_view.byte_length = length.into();
+/// A source texture decoded and encoded for one material slot
+#[derive(Debug)]
+struct EncodedSlot {
+    encoded: EncodedTexture,
+    /// The base color alpha channel was dropped because nothing reads it
+    alpha_pruned: bool,
+    /// Fallback encodings taken after the slot's own encoding failed
+    fallbacks: FallbackSteps,
+}
 
-                let n_view_idx = n_json.push(n_view);
+/// Decode a source texture and encode it for a slot
+fn encode_slot(
+    o_json: &Root,
+    texture: Index<Texture>,
+    img_data: &[u8],
+    size: u32,
+    texture_type: TextureType,
+    output: TextureOutput,
+    scratch: &mut TextureScratch,
+) -> Result<EncodedSlot, OptimizeError> {
+    let mut img = scratch.decode(o_json, texture, img_data)?;
+    // Source bytes can't stand in for pixels the decoder turned upright
+    let mut img_data = if is_reoriented(img_data) {
+        &[][..]
+    } else {
+        img_data
+    };
+    // An alpha channel nobody reads is dropped so JPEG/RGB8 can be used
+    let alpha_pruned = texture_type == TextureType::BaseColor
+        && matches!(output.format, TextureFormat::Builtin(_))
+        && opaque_base_color(o_json, texture)
+        && match prune_opaque_alpha(&img) {
+            Some(rgb) => {
+                img = rgb;
+                img_data = &[];
+                true
+            }
+            None => false,
+        };
 
-                // create accessor
-                let mut n_acc = acc.clone();
-                n_acc.buffer_view = Some(n_view_idx);
+    let (encoded, fallbacks) = output.encode(&img, img_data, size, texture_type, scratch)?;
+    scratch.recycle(img);
+    Ok(EncodedSlot {
+        encoded,
+        alpha_pruned,
+        fallbacks,
+    })
+}
 
-                let idx_acc = n_json.push(n_acc);
+/// Source texture, slot type and size of a pre-encoded slot
+type PreencodedKey = (usize, TextureType, u32);
 
-                return Some(idx_acc);
-            }
-        }
+/// Encode the given slots with the built-in encoders on all cores, or half with `low_priority`
+///
+/// Slots go to whichever thread is free, so a few large textures don't hold up the rest.
+/// Results are only collected here; the material loop still writes them to the blob in
+/// its own order, so the output matches a serial encode byte for byte. Encodes that fail
+/// are left out for the loop to retry and report as before.
+fn preencode_textures(
+    o_blob: &[u8],
+    o_json: &Root,
+    slots: &[(Index<Texture>, TextureType, u32)],
+    output: TextureOutput,
+) -> HashMap<PreencodedKey, EncodedSlot> {
+    let TextureFormat::Builtin(encoding) = output.format else {
+        return HashMap::new();
+    };
+    // Filters run on the calling thread, in slot order
+    if slots.len() < 2 || output.filter.is_some() {
+        return HashMap::new();
     }
 
-    None
+    let TextureOutput {
+        slot_sizes,
+        power_of_two,
+        upscale,
+        reencode_skip,
+        progress,
+        cache,
+        ..
+    } = output;
+    let next = AtomicUsize::new(0);
+    let threads = encoding.worker_threads.clamp(1, slots.len());
+    // Every Basis Universal encode starts its own threads, so the workers split them
+    let encoding = BuiltinEncoding {
+        encoder_threads: (encoding.encoder_threads / threads as u32).max(1),
+        ..encoding
+    };
+    let encoded: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    // Metrics are reported from the calling thread once every slot is done,
+                    // progress as each one is
+                    let output = TextureOutput {
+                        format: TextureFormat::Builtin(encoding),
+                        slot_sizes,
+                        power_of_two,
+                        upscale,
+                        reencode_skip,
+                        filter: None,
+                        metrics: None,
+                        progress,
+                        cache,
+                    };
+                    let mut scratch = TextureScratch::default();
+                    let mut encoded = Vec::new();
+                    while let Some(&(texture, texture_type, size)) =
+                        slots.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let Some(data) = get_image_data(o_blob, o_json, texture) else {
+                            continue;
+                        };
+                        let start = Instant::now();
+                        if let Ok(slot) = encode_slot(
+                            o_json,
+                            texture,
+                            data,
+                            size,
+                            texture_type,
+                            output,
+                            &mut scratch,
+                        ) {
+                            let key = (texture.value(), texture_type, size);
+                            encoded.push((key, slot, start.elapsed().as_secs_f64()));
+                        }
+                    }
+                    encoded
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|panic| resume_unwind(panic)))
+            .collect()
+    });
+
+    encoded
+        .into_iter()
+        .map(|(key, slot, seconds)| {
+            output.report_metrics(seconds);
+            (key, slot)
+        })
+        .collect()
 }
 
-fn get_image_data<'a>(
-    o_blob: &'a [u8],
+/// Slots the material loop encodes with the given sizes, each listed once
+///
+/// Occlusion packed into the metallic/roughness texture and slot types copied verbatim
+/// are left out, like materials of meshes a preview leaves untextured and materials hinted
+/// to be skipped.
+fn texture_slot_encodes(
+    o_json: &Root,
+    remove_normal_texture: bool,
+    textured: &dyn Fn(usize) -> bool,
+    verbatim: &HashSet<TextureType>,
+    size_for: &dyn Fn(Index<Texture>, TextureType) -> u32,
+) -> Vec<(Index<Texture>, TextureType, u32)> {
+    let mut seen = HashSet::new();
+    let mut slots = Vec::new();
+    for (i, mat) in o_json.materials.iter().enumerate() {
+        if !textured(i) || object_hints(&mat.extras).skip {
+            continue;
+        }
+        let metallic_roughness = mat
+            .pbr_metallic_roughness
+            .metallic_roughness_texture
+            .as_ref()
+            .map(|mr| mr.index)
+            .filter(|_| !verbatim.contains(&TextureType::MetallicRoughness));
+        for (texture, texture_type) in material_texture_uses(mat, remove_normal_texture) {
+            let packed =
+                texture_type == TextureType::Occlusion && metallic_roughness == Some(texture);
+            if verbatim.contains(&texture_type) || packed {
+                continue;
+            }
+            if seen.insert((texture.value(), texture_type)) {
+                slots.push((texture, texture_type, size_for(texture, texture_type)));
+            }
+        }
+    }
+    slots
+}
+
+/// Decode to RGBA8, shrinking to the size cap like the built-in encoders
+///
+/// An RGBA8 image that needs no resize is borrowed as it is.
+fn resize_rgba(
+    img: &DynamicImage,
+    size: u32,
+    power_of_two: bool,
+) -> Result<Cow<'_, RgbaImage>, OptimizeError> {
+    let (width, height) = texture_dimensions(img.width(), img.height(), size, power_of_two);
+    resize_rgba_to(img, width, height)
+}
+
+/// Decode to RGBA8 at exactly `dst_width` x `dst_height`, borrowing an RGBA8 image of that
+/// size
+fn resize_rgba_to(
+    img: &DynamicImage,
+    dst_width: u32,
+    dst_height: u32,
+) -> Result<Cow<'_, RgbaImage>, OptimizeError> {
+    let (width, height) = (img.width(), img.height());
+    let rgba = match img {
+        DynamicImage::ImageRgba8(rgba) => Cow::Borrowed(rgba),
+        _ => Cow::Owned(img.to_rgba8()),
+    };
+    if (dst_width, dst_height) == (width, height) {
+        return Ok(rgba);
+    }
+
+    let src = match rgba {
+        Cow::Borrowed(_) => Cow::Borrowed(img),
+        Cow::Owned(rgba) => Cow::Owned(DynamicImage::from(rgba)),
+    };
+    let mut resized = Vec::new();
+    resize_pixels(&src, dst_width, dst_height, &mut resized)?;
+    RgbaImage::from_raw(dst_width, dst_height, resized)
+        .map(Cow::Owned)
+        .ok_or(OptimizeError::ImageSize {
+            width: dst_width,
+            height: dst_height,
+        })
+}
+
+/// Resize to exactly `width` x `height` in linear light
+///
+/// Color channels are decoded from sRGB before filtering and encoded again after, so averaged
+/// texels keep their brightness instead of darkening toward the shadows.
+fn resize_rgba_linear(
+    img: &DynamicImage,
+    width: u32,
+    height: u32,
+) -> Result<RgbaImage, OptimizeError> {
+    let to_linear: [f32; 256] = std::array::from_fn(|c| srgb_to_linear(c as u8));
+    let rgba = img.to_rgba8();
+    let linear = image::Rgba32FImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let [r, g, b] = [r, g, b].map(|c| to_linear[c as usize]);
+        image::Rgba([r, g, b, a as f32 / 255.0])
+    });
+
+    let mut resized = Vec::new();
+    resize_pixels(&DynamicImage::from(linear), width, height, &mut resized)?;
+    let pixels = resized
+        .chunks_exact(16)
+        .flat_map(|pixel| {
+            let [r, g, b, a] = [0, 4, 8, 12].map(|at| {
+                f32::from_ne_bytes([pixel[at], pixel[at + 1], pixel[at + 2], pixel[at + 3]])
+            });
+            let alpha = (a * 255.0).round().clamp(0.0, 255.0) as u8;
+            [
+                linear_to_srgb(r),
+                linear_to_srgb(g),
+                linear_to_srgb(b),
+                alpha,
+            ]
+        })
+        .collect();
+    RgbaImage::from_raw(width, height, pixels).ok_or(OptimizeError::ImageSize { width, height })
+}
+
+/// List extensions a custom encoder relies on as used and required
+pub(crate) fn require_extensions(n_json: &mut Root, extensions: &[String]) {
+    for ext in extensions {
+        if !n_json.extensions_used.contains(ext) {
+            n_json.extensions_used.push(ext.clone());
+        }
+        if !n_json.extensions_required.contains(ext) {
+            n_json.extensions_required.push(ext.clone());
+        }
+    }
+}
+
+/// Require `KHR_texture_basisu` and `EXT_texture_webp` exactly when output images use them,
+/// keeping those the input already required
+fn require_image_extensions(n_json: &mut Root, o_json: &Root) {
+    let extensions = [
+        ("KHR_texture_basisu", "image/ktx2"),
+        ("EXT_texture_webp", "image/webp"),
+    ];
+    for (extension, mime_type) in extensions {
+        let used = n_json
+            .images
+            .iter()
+            .any(|img| img.mime_type.as_ref().is_some_and(|m| m.0 == mime_type));
+        if used {
+            require_extensions(n_json, &[extension.to_string()]);
+        } else if !o_json.extensions_required.iter().any(|e| e == extension) {
+            n_json.extensions_required.retain(|e| e != extension);
+            n_json.extensions_used.retain(|e| e != extension);
+        }
+    }
+}
+
+/// Image name/URI with its file extension swapped for `extension`, or appended when it has none
+fn update_image_name(image_name: &Option<String>, extension: &str) -> Option<String> {
+    if let Some(name) = image_name {
+        // Replace common image extensions
+        let updated_name = [
+            ".jpg", ".jpeg", ".png", ".webp", ".ktx2", ".JPG", ".JPEG", ".PNG", ".WEBP", ".KTX2",
+        ]
+        .iter()
+        .fold(name.clone(), |n, from| {
+            n.replace(from, &format!(".{extension}"))
+        });
+
+        // If no extension was found, just append it
+        if updated_name == *name && !name.ends_with(&format!(".{extension}")) {
+            Some(format!("{name}.{extension}"))
+        } else {
+            Some(updated_name)
+        }
+    } else {
+        None
+    }
+}
+
+pub(crate) fn add_image(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    img: &gltf::json::Image,
+    b: &[u8],
+    mime_type: &str,
+) -> Index<gltf::json::Image> {
+    let view_idx = n_blob.push_view(n_json, b, None);
+
+    let mut n_img = img.clone();
+
+    n_img.buffer_view = Some(view_idx);
+    n_img.mime_type = Some(MimeType(mime_type.to_string()));
+
+    // Update name and URI when converting to KTX2 or WebP
+    if mime_type == "image/ktx2" {
+        // Update existing name/URI if they exist
+        n_img.name = update_image_name(&img.name, "ktx2");
+        n_img.uri = update_image_name(&img.uri, "ktx2");
+
+        // If no name was set, generate a default KTX2 name
+        if n_img.name.is_none() {
+            n_img.name = Some(format!("texture_{}.ktx2", n_json.images.len()));
+        }
+    } else if mime_type == "image/webp" {
+        n_img.name = update_image_name(&img.name, "webp");
+        n_img.uri = update_image_name(&img.uri, "webp");
+    }
+
+    n_json.push(n_img)
+}
+
+pub(crate) fn add_accessor(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    idx: Index<gltf::json::Accessor>,
+) -> Option<Index<gltf::json::Accessor>> {
+    add_accessor_with_offset(n_blob, n_json, o_blob, o_json, idx, None)
+}
+
+/// Add accessor with optional position offset for POSITION attributes
+///
+/// The view is copied whole, so interleaved attributes sharing it survive; only the position
+/// elements are rewritten, at the accessor's own stride. Quantized positions
+/// (`KHR_mesh_quantization`) can't hold an arbitrary offset, so they are dequantized to a new
+/// float accessor instead.
+fn add_accessor_with_offset(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    idx: Index<gltf::json::Accessor>,
+    position_offset: Option<[f32; 3]>,
+) -> Option<Index<gltf::json::Accessor>> {
+    if let Some(prepared) = prepare_accessor(o_blob, o_json, idx, position_offset) {
+        return Some(prepared.commit(n_blob, n_json));
+    }
+
+    // Quantized or sparse positions are decoded, offset and written out as floats
+    let Some(pos_offset) = position_offset else {
+        return add_resolved_accessor(n_blob, n_json, o_blob, o_json, idx);
+    };
+    if let Some(mut positions) = get_position_data(o_blob, o_json, idx) {
+        for p in &mut positions {
+            for c in 0..3 {
+                p[c] += pos_offset[c];
+            }
+        }
+        let n_idx = push_positions(n_blob, n_json, &positions);
+        n_json.accessors[n_idx.value()].name = o_json.accessors[idx.value()].name.clone();
+        return Some(n_idx);
+    }
+
+    // Unreadable positions are copied as they are
+    prepare_accessor(o_blob, o_json, idx, None).map(|prepared| prepared.commit(n_blob, n_json))
+}
+
+/// Dense copy of a sparse accessor, keeping its component type
+fn add_resolved_accessor(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    idx: Index<gltf::json::Accessor>,
+) -> Option<Index<gltf::json::Accessor>> {
+    use gltf::json::accessor::Type;
+
+    let acc = o_json.accessors.get(idx.value())?;
+    acc.sparse.as_ref()?;
+    let Checked::Valid(GenericComponentType(component)) = acc.component_type else {
+        return None;
+    };
+    let normalized = acc.normalized;
+    let (bytes, stride) = match acc.type_ {
+        Checked::Valid(Type::Scalar) => write_elements(
+            &read_accessor::<1>(o_blob, o_json, idx)?,
+            component,
+            normalized,
+        ),
+        Checked::Valid(Type::Vec2) => write_elements(
+            &read_accessor::<2>(o_blob, o_json, idx)?,
+            component,
+            normalized,
+        ),
+        Checked::Valid(Type::Vec3) => write_elements(
+            &read_accessor::<3>(o_blob, o_json, idx)?,
+            component,
+            normalized,
+        ),
+        Checked::Valid(Type::Vec4) => write_elements(
+            &read_accessor::<4>(o_blob, o_json, idx)?,
+            component,
+            normalized,
+        ),
+        _ => return None,
+    };
+
+    let view = n_blob.push_view(
+        n_json,
+        &bytes,
+        Some(gltf::json::buffer::Target::ArrayBuffer),
+    );
+    if stride != element_size(acc)? {
+        n_json.buffer_views[view.value()].byte_stride = Some(gltf::json::buffer::Stride(stride));
+    }
+
+    // min/max already describe the values after substitution
+    Some(n_json.push(gltf::json::Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        sparse: None,
+        ..acc.clone()
+    }))
+}
+
+/// Accessor copy built apart from the output, ready to be appended
+struct PreparedAccessor<'a> {
+    view: gltf::json::buffer::View,
+    accessor: gltf::json::Accessor,
+    data: Cow<'a, [u8]>,
+    /// Content hash for the blob's dedup
+    hash: u64,
+}
+
+impl PreparedAccessor<'_> {
+    fn commit(&self, n_blob: &mut BlobWriter, n_json: &mut Root) -> Index<gltf::json::Accessor> {
+        let mut n_view = self.view.clone();
+        n_view.byte_offset = Some(n_blob.append_hashed(&self.data, self.hash).into());
+        let mut n_acc = self.accessor.clone();
+        n_acc.buffer_view = Some(n_json.push(n_view));
+        n_json.push(n_acc)
+    }
+}
+
+/// Copy of an accessor's view, with `position_offset` applied to float VEC3 positions
+///
+/// `None` when the accessor is unreadable or sparse, or takes an offset but isn't float VEC3.
+fn prepare_accessor<'a>(
+    o_blob: &'a [u8],
+    o_json: &gltf::json::Root,
+    idx: Index<gltf::json::Accessor>,
+    position_offset: Option<[f32; 3]>,
+) -> Option<PreparedAccessor<'a>> {
+    let acc = o_json.accessors.get(idx.value())?;
+    // Sparse substitutions refer to views of their own, so those are resolved instead
+    if acc.sparse.is_some() {
+        return None;
+    }
+    let view = o_json.buffer_views.get(acc.buffer_view?.value())?;
+    let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+    let data = o_blob.get(offset..offset.checked_add(view.byte_length.0 as usize)?)?;
+
+    let mut n_view = view.clone();
+    let mut n_acc = acc.clone();
+
+    // Only float VEC3 positions can take the offset in place
+    let f32_vec3 = f32_vec3_range(o_json, idx, data.len());
+    let stride = accessor_stride(o_json, idx);
+
+    let data = match (position_offset, f32_vec3) {
+        (Some(pos_offset), Some((range, float_stride))) => {
+            let mut modified_data = data.to_vec();
+            let bounds = offset_vec3_bytes(&mut modified_data[range], float_stride, pos_offset);
+
+            // Bounds come from the written data; the source min/max may be stale, integer-typed
+            // or missing, and POSITION requires them
+            n_acc.min = bounds.map(|(min, _)| min.to_vec().into());
+            n_acc.max = bounds.map(|(_, max)| max.to_vec().into());
+            Cow::Owned(modified_data)
+        }
+        (Some(_), None) => return None,
+        (None, _) => Cow::Borrowed(data),
+    };
+
+    // A padded stride found without `byteStride` is written out explicitly
+    if view.byte_stride.is_none()
+        && let Some(stride) = stride
+        && element_size(acc) != Some(stride)
+    {
+        n_view.byte_stride = Some(gltf::json::buffer::Stride(stride));
+    }
+
+    Some(PreparedAccessor {
+        view: n_view,
+        accessor: n_acc,
+        hash: content_hash(&data),
+        data,
+    })
+}
+
+/// Accessor copies prepared ahead of the mesh loop, by source accessor and whether the pivot
+/// offset applies
+type PreparedAccessors<'a> = HashMap<(usize, bool), PreparedAccessor<'a>>;
+
+/// Fewer accessor copies than this are left to the mesh loop
+const PARALLEL_COPY_MIN_ACCESSORS: usize = 256;
+
+/// Prepare the accessor copies of every primitive on `threads` threads
+///
+/// Offsetting positions and hashing for dedup happen here, and the mesh loop only appends
+/// the results in order, so the output matches a serial copy byte for byte. Attributes going
+/// to half precision and quantized positions taking an offset are left to the loop.
+fn prepare_mesh_accessors<'a>(
+    o_blob: &'a [u8],
+    o_json: &gltf::json::Root,
+    pivot_offset: Option<[f32; 3]>,
+    half_precision: bool,
+    threads: usize,
+) -> PreparedAccessors<'a> {
+    use gltf::json::mesh::Semantic;
+
+    let mut keys: Vec<(usize, bool)> = o_json
+        .meshes
+        .iter()
+        .flat_map(|m| &m.primitives)
+        .flat_map(|p| {
+            let attributes = p
+                .attributes
+                .iter()
+                .filter(|(k, _)| {
+                    !half_precision
+                        || !matches!(
+                            k,
+                            Checked::Valid(
+                                Semantic::Normals | Semantic::Tangents | Semantic::TexCoords(_)
+                            )
+                        )
+                })
+                .map(|(k, v)| {
+                    let offset = matches!(k, Checked::Valid(Semantic::Positions));
+                    (v.value(), offset && pivot_offset.is_some())
+                });
+            p.indices
+                .map(|i| (i.value(), false))
+                .into_iter()
+                .chain(attributes)
+        })
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    if keys.len() < PARALLEL_COPY_MIN_ACCESSORS {
+        return HashMap::new();
+    }
+
+    let chunk = keys.len().div_ceil(threads.max(1));
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = keys
+            .chunks(chunk)
+            .map(|keys| {
+                scope.spawn(move || {
+                    keys.iter()
+                        .filter_map(|&(idx, offset)| {
+                            let position_offset = pivot_offset.filter(|_| offset);
+                            let prepared = prepare_accessor(
+                                o_blob,
+                                o_json,
+                                Index::new(idx as u32),
+                                position_offset,
+                            )?;
+                            Some(((idx, offset), prepared))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_else(|panic| resume_unwind(panic)))
+            .collect()
+    })
+}
+
+pub(crate) fn get_image_data<'a>(
+    o_blob: &'a [u8],
     o_json: &gltf::json::Root,
     texture_idx: Index<Texture>,
 ) -> Option<&'a [u8]> {
@@ -468,63 +1705,110 @@ fn get_image_data<'a>(
     o_blob.get(offset..(offset + length))
 }
 
+/// Report the fallback encodings a source texture was retried with
+fn record_fallbacks(
+    issues: &mut Vec<BestEffortIssue>,
+    texture: Index<Texture>,
+    fallbacks: FallbackSteps,
+) {
+    issues.extend(
+        fallbacks
+            .into_iter()
+            .map(|(encoding, error)| BestEffortIssue {
+                material: None,
+                texture: Some(texture.value()),
+                primitive: None,
+                error,
+                fallback: BestEffortFallback::EncoderFallback(encoding),
+            }),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
 fn add_texture(
-    n_blob: &mut Vec<u8>,
+    n_blob: &mut BlobWriter,
     n_json: &mut Root,
     o_blob: &[u8],
     o_json: &gltf::json::Root,
     info: &gltf::json::texture::Info,
     n_tex_size: u32,
-    convert_to_ktx2: bool,
-) -> Result<gltf::json::texture::Info, Box<dyn Error>> {
-    let bct_image_data = get_image_data(o_blob, o_json, info.index).ok_or_else(|| {
-        format!(
-            "Failed to get base color texture image data (texture index: {})",
-            info.index.value()
-        )
-    })?;
-
-    let mut new_bct_data: Vec<u8> = Vec::new();
-    let mut writer = Cursor::new(&mut new_bct_data);
-
-    let mime_type = if convert_to_ktx2 {
-        "image/ktx2"
-    } else {
-        "image/jpeg"
-    };
-
-    if convert_to_ktx2 {
-        resize_to_ktx2(
-            bct_image_data,
-            n_tex_size,
-            n_tex_size,
-            TextureType::BaseColor,
-            &mut writer,
-        )?;
-    } else {
-        resize_to_jpg(bct_image_data, n_tex_size, n_tex_size, &mut writer)?;
-    }
+    output: TextureOutput,
+    scratch: &mut TextureScratch,
+    fallbacks: &mut Vec<BestEffortIssue>,
+    preencoded: Option<EncodedSlot>,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<gltf::json::texture::Info, OptimizeError> {
+    let bct_image_data =
+        get_image_data(o_blob, o_json, info.index).ok_or(OptimizeError::MissingImageData {
+            texture: info.index.value(),
+        })?;
 
     // Get texture with proper error handling
-    let original_texture = o_json
-        .textures
-        .get(info.index.value())
-        .ok_or("Failed to get original texture")?;
+    let original_texture =
+        o_json
+            .textures
+            .get(info.index.value())
+            .ok_or(OptimizeError::TextureOutOfBounds {
+                texture: info.index.value(),
+            })?;
 
     // Get image with proper error handling
     let new_image = o_json
         .images
         .get(original_texture.source.value())
-        .ok_or("Failed to get original image")?
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: original_texture.source.value(),
+        })?
         .clone();
 
-    let idx_img = add_image(
-        n_blob,
-        n_json,
-        &new_image,
-        &new_bct_data.to_vec(),
-        mime_type,
-    );
+    let idx_img = match deferred {
+        Some(jobs) => {
+            let mime_type = output.deferred_mime_type(TextureType::BaseColor)?;
+            let idx_img = add_image(n_blob, n_json, &new_image, &[], mime_type);
+            jobs.push(TextureJob {
+                image: idx_img,
+                texture: info.index,
+                texture_type: TextureType::BaseColor,
+            });
+            idx_img
+        }
+        None => {
+            let slot = match preencoded {
+                Some(slot) => slot,
+                None => encode_slot(
+                    o_json,
+                    info.index,
+                    bct_image_data,
+                    n_tex_size,
+                    TextureType::BaseColor,
+                    output,
+                    scratch,
+                )?,
+            };
+            record_fallbacks(fallbacks, info.index, slot.fallbacks);
+            let encoded = slot.encoded;
+            require_extensions(n_json, &encoded.extensions);
+            let idx_img = add_image(
+                n_blob,
+                n_json,
+                &new_image,
+                &encoded.data,
+                &encoded.mime_type,
+            );
+            scratch.return_encoded(encoded.data);
+            if slot.alpha_pruned {
+                let image = &mut n_json.images[idx_img.value()];
+                image.extras = merge_extras(&image.extras, ALPHA_PRUNED_KEY, true.into());
+            }
+            idx_img
+        }
+    };
+
+    if let Some(premultiplied) = output.alpha_premultiplied(TextureType::BaseColor) {
+        let image = &mut n_json.images[idx_img.value()];
+        image.extras = merge_extras(&image.extras, ALPHA_PREMULTIPLIED_KEY, premultiplied.into());
+    }
 
     // Clone texture after validation
     let mut new_tex = original_texture.clone();
@@ -538,63 +1822,82 @@ fn add_texture(
     Ok(new_info)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_normal_texture(
-    n_blob: &mut Vec<u8>,
+    n_blob: &mut BlobWriter,
     n_json: &mut Root,
     o_blob: &[u8],
     o_json: &gltf::json::Root,
     normal: &gltf::json::material::NormalTexture,
     n_tex_size: u32,
-    convert_to_ktx2: bool,
-) -> Result<gltf::json::material::NormalTexture, Box<dyn Error>> {
-    let bct_image_data = get_image_data(o_blob, o_json, normal.index).ok_or_else(|| {
-        format!(
-            "Failed to get normal texture image data (texture index: {})",
-            normal.index.value()
-        )
-    })?;
-
-    let mut new_bct_data: Vec<u8> = Vec::new();
-    let mut writer = Cursor::new(&mut new_bct_data);
-
-    let mime_type = if convert_to_ktx2 {
-        "image/ktx2"
-    } else {
-        "image/png"
-    };
-
-    if convert_to_ktx2 {
-        resize_to_ktx2(
-            bct_image_data,
-            n_tex_size,
-            n_tex_size,
-            TextureType::Normal,
-            &mut writer,
-        )?;
-    } else {
-        resize_to_png(bct_image_data, n_tex_size, n_tex_size, &mut writer)?;
-    }
+    output: TextureOutput,
+    scratch: &mut TextureScratch,
+    fallbacks: &mut Vec<BestEffortIssue>,
+    preencoded: Option<EncodedSlot>,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<gltf::json::material::NormalTexture, OptimizeError> {
+    let bct_image_data =
+        get_image_data(o_blob, o_json, normal.index).ok_or(OptimizeError::MissingImageData {
+            texture: normal.index.value(),
+        })?;
 
     // Get texture with proper error handling
-    let original_texture = o_json
-        .textures
-        .get(normal.index.value())
-        .ok_or("Failed to get original normal texture")?;
+    let original_texture =
+        o_json
+            .textures
+            .get(normal.index.value())
+            .ok_or(OptimizeError::TextureOutOfBounds {
+                texture: normal.index.value(),
+            })?;
 
     // Get image with proper error handling
     let new_image = o_json
         .images
         .get(original_texture.source.value())
-        .ok_or("Failed to get original normal texture image")?
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: original_texture.source.value(),
+        })?
         .clone();
 
-    let idx_img = add_image(
-        n_blob,
-        n_json,
-        &new_image,
-        &new_bct_data.to_vec(),
-        mime_type,
-    );
+    let idx_img = match deferred {
+        Some(jobs) => {
+            let mime_type = output.deferred_mime_type(TextureType::Normal)?;
+            let idx_img = add_image(n_blob, n_json, &new_image, &[], mime_type);
+            jobs.push(TextureJob {
+                image: idx_img,
+                texture: normal.index,
+                texture_type: TextureType::Normal,
+            });
+            idx_img
+        }
+        None => {
+            let slot = match preencoded {
+                Some(slot) => slot,
+                None => encode_slot(
+                    o_json,
+                    normal.index,
+                    bct_image_data,
+                    n_tex_size,
+                    TextureType::Normal,
+                    output,
+                    scratch,
+                )?,
+            };
+            record_fallbacks(fallbacks, normal.index, slot.fallbacks);
+            let encoded = slot.encoded;
+            require_extensions(n_json, &encoded.extensions);
+            let idx_img = add_image(
+                n_blob,
+                n_json,
+                &new_image,
+                &encoded.data,
+                &encoded.mime_type,
+            );
+            scratch.return_encoded(encoded.data);
+            idx_img
+        }
+    };
 
     // Clone texture after validation
     let mut new_tex = original_texture.clone();
@@ -608,185 +1911,633 @@ fn add_normal_texture(
     Ok(new_normal)
 }
 
-fn add_metallic_roughness_texture(
-    n_blob: &mut Vec<u8>,
+/// Add a metallic/roughness, occlusion or emissive texture
+#[allow(clippy::too_many_arguments)]
+fn add_data_texture(
+    n_blob: &mut BlobWriter,
     n_json: &mut Root,
     o_blob: &[u8],
     o_json: &gltf::json::Root,
-    info: &gltf::json::texture::Info,
+    texture: Index<Texture>,
+    texture_type: TextureType,
     n_tex_size: u32,
-    convert_to_ktx2: bool,
-) -> Result<gltf::json::texture::Info, Box<dyn Error>> {
-    let bct_image_data = get_image_data(o_blob, o_json, info.index).ok_or_else(|| {
-        format!(
-            "Failed to get metallic/roughness texture image data (texture index: {})",
-            info.index.value()
-        )
-    })?;
-
-    let mut new_bct_data: Vec<u8> = Vec::new();
-    let mut writer = Cursor::new(&mut new_bct_data);
-
-    let mime_type = if convert_to_ktx2 {
-        "image/ktx2"
-    } else {
-        "image/jpeg"
-    };
-
-    if convert_to_ktx2 {
-        resize_to_ktx2(
-            bct_image_data,
-            n_tex_size,
-            n_tex_size,
-            TextureType::MetallicRoughness,
-            &mut writer,
-        )?;
-    } else {
-        resize_to_jpg(bct_image_data, n_tex_size, n_tex_size, &mut writer)?;
-    }
+    output: TextureOutput,
+    scratch: &mut TextureScratch,
+    fallbacks: &mut Vec<BestEffortIssue>,
+    preencoded: Option<EncodedSlot>,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<Index<Texture>, OptimizeError> {
+    let bct_image_data =
+        get_image_data(o_blob, o_json, texture).ok_or(OptimizeError::MissingImageData {
+            texture: texture.value(),
+        })?;
 
     // Get texture with proper error handling
-    let original_texture = o_json
-        .textures
-        .get(info.index.value())
-        .ok_or("Failed to get original metallic/roughness texture")?;
+    let original_texture =
+        o_json
+            .textures
+            .get(texture.value())
+            .ok_or(OptimizeError::TextureOutOfBounds {
+                texture: texture.value(),
+            })?;
 
     // Get image with proper error handling
     let new_image = o_json
         .images
         .get(original_texture.source.value())
-        .ok_or("Failed to get original metallic/roughness texture image")?
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: original_texture.source.value(),
+        })?
         .clone();
 
-    let idx_img = add_image(
-        n_blob,
-        n_json,
-        &new_image,
-        &new_bct_data.to_vec(),
-        mime_type,
-    );
+    let idx_img = match deferred {
+        Some(jobs) => {
+            let mime_type = output.deferred_mime_type(texture_type)?;
+            let idx_img = add_image(n_blob, n_json, &new_image, &[], mime_type);
+            jobs.push(TextureJob {
+                image: idx_img,
+                texture,
+                texture_type,
+            });
+            idx_img
+        }
+        None => {
+            let slot = match preencoded {
+                Some(slot) => slot,
+                None => encode_slot(
+                    o_json,
+                    texture,
+                    bct_image_data,
+                    n_tex_size,
+                    texture_type,
+                    output,
+                    scratch,
+                )?,
+            };
+            record_fallbacks(fallbacks, texture, slot.fallbacks);
+            let encoded = slot.encoded;
+            require_extensions(n_json, &encoded.extensions);
+            let idx_img = add_image(
+                n_blob,
+                n_json,
+                &new_image,
+                &encoded.data,
+                &encoded.mime_type,
+            );
+            scratch.return_encoded(encoded.data);
+            idx_img
+        }
+    };
 
     // Clone texture after validation
     let mut new_tex = original_texture.clone();
     new_tex.source = idx_img;
 
-    let idx_tex = n_json.push(new_tex);
+    Ok(n_json.push(new_tex))
+}
 
-    let mut new_info = info.clone();
-    new_info.index = idx_tex;
+/// Copy a texture and its image as they are, for slot types left unprocessed
+fn copy_texture_verbatim(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    texture: Index<Texture>,
+) -> Result<Index<Texture>, OptimizeError> {
+    let original_texture =
+        o_json
+            .textures
+            .get(texture.value())
+            .ok_or(OptimizeError::TextureOutOfBounds {
+                texture: texture.value(),
+            })?;
+    let image =
+        o_json
+            .images
+            .get(original_texture.source.value())
+            .ok_or(OptimizeError::MissingObject {
+                kind: "image",
+                index: original_texture.source.value(),
+            })?;
+
+    // Images referenced by URI stay external
+    let idx_img = match get_image_data(o_blob, o_json, texture) {
+        Some(data) => {
+            let mime_type = match &image.mime_type {
+                Some(mime_type) => mime_type.0.as_str(),
+                None if data.starts_with(b"\x89PNG") => "image/png",
+                None => "image/jpeg",
+            };
+            add_image(n_blob, n_json, image, data, mime_type)
+        }
+        None => n_json.push(image.clone()),
+    };
 
-    Ok(new_info)
+    let mut new_tex = original_texture.clone();
+    new_tex.source = idx_img;
+    Ok(n_json.push(new_tex))
+}
+
+/// Output textures built so far, keyed by source texture and slot type
+#[derive(Debug, Default)]
+struct TextureSlots {
+    /// Slot types copied as they are instead of resized and re-encoded
+    verbatim: HashSet<TextureType>,
+    /// First output texture built for each source texture and slot type
+    built: HashMap<(usize, TextureType), Index<Texture>>,
+    /// Buffers shared by every texture this pass decodes and encodes
+    scratch: TextureScratch,
+    /// Slots encoded ahead of the material loop, taken as they are built
+    preencoded: HashMap<PreencodedKey, EncodedSlot>,
+    /// Source textures encoded as the slot type they look like rather than the one they are in
+    misslotted: HashMap<(usize, TextureType), TextureType>,
+    /// Output texture copied for each source texture used by an extension slot of no type
+    copied: HashMap<usize, Index<Texture>>,
+    /// Fallback encodings taken so far, reported once every material is built
+    fallbacks: Vec<BestEffortIssue>,
+}
+
+impl TextureSlots {
+    /// Slot type whose settings a source texture used in `slot` is encoded with
+    fn handling(&self, texture: Index<Texture>, slot: TextureType) -> TextureType {
+        self.misslotted
+            .get(&(texture.value(), slot))
+            .copied()
+            .unwrap_or(slot)
+    }
+
+    /// First output texture built from each source texture
+    fn remap(&self, source_count: usize) -> Vec<Option<usize>> {
+        let mut remap = vec![None; source_count];
+        let built = self.built.iter().map(|(&(source, _), idx)| (source, idx));
+        for (source, idx) in built.chain(self.copied.iter().map(|(&s, idx)| (s, idx))) {
+            if let Some(slot) = remap.get_mut(source) {
+                *slot = Some(slot.map_or(idx.value(), |s: usize| s.min(idx.value())));
+            }
+        }
+        remap
+    }
 }
 
+/// Build (or reuse) the output texture for one material slot
 #[allow(clippy::too_many_arguments)]
-fn add_primitive(
-    n_blob: &mut Vec<u8>,
+fn add_texture_slot(
+    n_blob: &mut BlobWriter,
     n_json: &mut Root,
     o_blob: &[u8],
     o_json: &gltf::json::Root,
-    p: &gltf::json::mesh::Primitive,
+    texture: Index<Texture>,
+    texture_type: TextureType,
     n_tex_size: u32,
-    remove_normal_texture: bool,
-    convert_to_ktx2: bool,
-    pivot_offset: Option<[f32; 3]>,
-) -> Result<Primitive, Box<dyn Error>> {
-    let mut n_p = p.clone();
+    output: TextureOutput,
+    textures: &mut TextureSlots,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<Index<Texture>, OptimizeError> {
+    let texture_type = textures.handling(texture, texture_type);
+    let key = (texture.value(), texture_type);
+    if let Some(&idx_tex) = textures.built.get(&key) {
+        return Ok(idx_tex);
+    }
 
-    // copy indices
-    if let Some(indices) = p.indices {
-        n_p.indices = add_accessor(n_blob, n_json, o_blob, o_json, indices);
+    if textures.verbatim.contains(&texture_type)
+        || output.keeps_source(o_blob, o_json, texture, texture_type, n_tex_size)
+    {
+        let idx_tex = copy_texture_verbatim(n_blob, n_json, o_blob, o_json, texture)?;
+        textures.built.entry(key).or_insert(idx_tex);
+        return Ok(idx_tex);
     }
 
-    // copy attributes
-    n_p.attributes.clear();
-    for (k, v) in &p.attributes {
-        // Apply pivot offset only to POSITION attributes
-        let offset_to_apply = if matches!(
-            k,
-            gltf::json::validation::Checked::Valid(gltf::json::mesh::Semantic::Positions)
-        ) {
-            pivot_offset
-        } else {
-            None
-        };
+    let preencoded = textures
+        .preencoded
+        .remove(&(texture.value(), texture_type, n_tex_size));
+    let info = gltf::json::texture::Info {
+        index: texture,
+        tex_coord: 0,
+        extensions: None,
+        extras: Default::default(),
+    };
+    let idx_tex = match texture_type {
+        TextureType::BaseColor => {
+            add_texture(
+                n_blob,
+                n_json,
+                o_blob,
+                o_json,
+                &info,
+                n_tex_size,
+                output,
+                &mut textures.scratch,
+                &mut textures.fallbacks,
+                preencoded,
+                deferred,
+            )?
+            .index
+        }
+        TextureType::MetallicRoughness | TextureType::Occlusion | TextureType::Emissive => {
+            add_data_texture(
+                n_blob,
+                n_json,
+                o_blob,
+                o_json,
+                texture,
+                texture_type,
+                n_tex_size,
+                output,
+                &mut textures.scratch,
+                &mut textures.fallbacks,
+                preencoded,
+                deferred,
+            )?
+        }
+        TextureType::Normal => {
+            let normal = gltf::json::material::NormalTexture {
+                index: texture,
+                scale: 1.0,
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            };
+            add_normal_texture(
+                n_blob,
+                n_json,
+                o_blob,
+                o_json,
+                &normal,
+                n_tex_size,
+                output,
+                &mut textures.scratch,
+                &mut textures.fallbacks,
+                preencoded,
+                deferred,
+            )?
+            .index
+        }
+    };
 
-        if let Some(idx_acc) =
-            add_accessor_with_offset(n_blob, n_json, o_blob, o_json, *v, offset_to_apply)
-        {
-            n_p.attributes.insert(k.clone(), idx_acc);
+    textures.built.entry(key).or_insert(idx_tex);
+    Ok(idx_tex)
+}
+
+/// Name the texture and material slot that failed, passing [`FeatureNotEnabled`] through as is
+fn slot_error(e: OptimizeError, texture: Index<Texture>, slot: TextureType) -> OptimizeError {
+    if let OptimizeError::FeatureNotEnabled(_) = e {
+        return e;
+    }
+    OptimizeError::Texture {
+        texture: texture.value(),
+        slot,
+        source: Box::new(e),
+    }
+}
+
+/// Copy a material, resizing and re-encoding every texture it uses
+///
+/// Materials hinted to be skipped get their textures copied as they are.
+#[allow(clippy::too_many_arguments)]
+fn add_material(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    mat: &gltf::json::Material,
+    n_tex_size: u32,
+    remove_normal_texture: bool,
+    output: TextureOutput,
+    texture_sizes: &TextureSizes,
+    textures: &mut TextureSlots,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<gltf::json::Material, OptimizeError> {
+    if !object_hints(&mat.extras).skip {
+        return add_material_textures(
+            n_blob,
+            n_json,
+            o_blob,
+            o_json,
+            mat,
+            n_tex_size,
+            remove_normal_texture,
+            output,
+            texture_sizes,
+            textures,
+            deferred,
+        );
+    }
+    let processed = std::mem::replace(&mut textures.verbatim, TextureType::ALL.into());
+    let n_mat = add_material_textures(
+        n_blob,
+        n_json,
+        o_blob,
+        o_json,
+        mat,
+        n_tex_size,
+        remove_normal_texture,
+        output,
+        texture_sizes,
+        textures,
+        deferred,
+    );
+    textures.verbatim = processed;
+    n_mat
+}
+
+/// [`add_material`] with the slot types copied verbatim as they are set
+#[allow(clippy::too_many_arguments)]
+fn add_material_textures(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    mat: &gltf::json::Material,
+    n_tex_size: u32,
+    remove_normal_texture: bool,
+    output: TextureOutput,
+    texture_sizes: &TextureSizes,
+    textures: &mut TextureSlots,
+    mut deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<gltf::json::Material, OptimizeError> {
+    let size_for = |texture: Index<Texture>, texture_type: TextureType| {
+        let default = output.slot_sizes.size(texture_type, n_tex_size);
+        texture_sizes.size(texture, default)
+    };
+
+    let mut n_mat = mat.clone();
+
+    // resize base color tex
+    if let Some(bct_info) = &mat.pbr_metallic_roughness.base_color_texture {
+        match add_texture_slot(
+            n_blob,
+            n_json,
+            o_blob,
+            o_json,
+            bct_info.index,
+            TextureType::BaseColor,
+            size_for(bct_info.index, TextureType::BaseColor),
+            output,
+            textures,
+            deferred.as_deref_mut(),
+        ) {
+            Ok(index) => {
+                n_mat.pbr_metallic_roughness.base_color_texture = Some(gltf::json::texture::Info {
+                    index,
+                    ..bct_info.clone()
+                });
+            }
+            Err(e) => {
+                return Err(slot_error(e, bct_info.index, TextureType::BaseColor));
+            }
         }
     }
 
-    // add material
-    if let Some(idx_mat) = p.material
-        && let Some(mat) = o_json.materials.get(idx_mat.value())
-    {
-        let mut n_mat = mat.clone();
+    // resize metal/rough tex
+    if let Some(mr_info) = &mat.pbr_metallic_roughness.metallic_roughness_texture {
+        match add_texture_slot(
+            n_blob,
+            n_json,
+            o_blob,
+            o_json,
+            mr_info.index,
+            TextureType::MetallicRoughness,
+            size_for(mr_info.index, TextureType::MetallicRoughness),
+            output,
+            textures,
+            deferred.as_deref_mut(),
+        ) {
+            Ok(index) => {
+                n_mat.pbr_metallic_roughness.metallic_roughness_texture =
+                    Some(gltf::json::texture::Info {
+                        index,
+                        ..mr_info.clone()
+                    });
+            }
+            Err(e) => {
+                return Err(slot_error(e, mr_info.index, TextureType::MetallicRoughness));
+            }
+        }
+    }
 
-        // resize base color tex
-        if let Some(bct_info) = &mat.pbr_metallic_roughness.base_color_texture {
-            match add_texture(
+    if remove_normal_texture {
+        n_mat.normal_texture = None;
+    } else {
+        // resize normal map
+        if let Some(normal_tex) = &mat.normal_texture {
+            match add_texture_slot(
                 n_blob,
                 n_json,
                 o_blob,
                 o_json,
-                bct_info,
-                n_tex_size,
-                convert_to_ktx2,
+                normal_tex.index,
+                TextureType::Normal,
+                size_for(normal_tex.index, TextureType::Normal),
+                output,
+                textures,
+                deferred.as_deref_mut(),
             ) {
-                Ok(new_info) => {
-                    n_mat.pbr_metallic_roughness.base_color_texture = Some(new_info);
+                Ok(index) => {
+                    n_mat.normal_texture = Some(gltf::json::material::NormalTexture {
+                        index,
+                        ..normal_tex.clone()
+                    });
                 }
                 Err(e) => {
-                    return Err(format!("Failed to process base color texture: {e}").into());
+                    return Err(slot_error(e, normal_tex.index, TextureType::Normal));
                 }
             }
         }
+    }
 
-        // resize metal/rough tex
-        if let Some(mr_info) = &mat.pbr_metallic_roughness.metallic_roughness_texture {
-            match add_metallic_roughness_texture(
+    // resize occlusion map, sharing the metallic/roughness output when both are one ORM texture
+    if let Some(occlusion) = &mat.occlusion_texture {
+        let packed = mat
+            .pbr_metallic_roughness
+            .metallic_roughness_texture
+            .as_ref()
+            .filter(|mr| {
+                mr.index == occlusion.index
+                    && textures.verbatim.contains(&TextureType::MetallicRoughness)
+                        == textures.verbatim.contains(&TextureType::Occlusion)
+            })
+            .and(
+                n_mat
+                    .pbr_metallic_roughness
+                    .metallic_roughness_texture
+                    .as_ref(),
+            )
+            .map(|mr| mr.index);
+        let added = match packed {
+            Some(index) => Ok(index),
+            None => add_texture_slot(
                 n_blob,
                 n_json,
                 o_blob,
                 o_json,
-                mr_info,
-                n_tex_size / 2,
-                convert_to_ktx2,
-            ) {
-                Ok(new_info) => {
-                    n_mat.pbr_metallic_roughness.metallic_roughness_texture = Some(new_info);
-                }
-                Err(e) => {
-                    return Err(format!("Failed to process metallic/roughness texture: {e}").into());
-                }
+                occlusion.index,
+                TextureType::Occlusion,
+                size_for(occlusion.index, TextureType::Occlusion),
+                output,
+                textures,
+                deferred.as_deref_mut(),
+            ),
+        };
+        match added {
+            Ok(index) => {
+                n_mat.occlusion_texture = Some(gltf::json::material::OcclusionTexture {
+                    index,
+                    ..occlusion.clone()
+                });
+            }
+            Err(e) => {
+                return Err(slot_error(e, occlusion.index, TextureType::Occlusion));
             }
         }
+    }
 
-        if remove_normal_texture {
-            n_mat.normal_texture = None;
-        } else {
-            // resize normal map
-            if let Some(normal_tex) = &mat.normal_texture {
-                match add_normal_texture(
+    // resize emissive map
+    if let Some(emissive) = &mat.emissive_texture {
+        match add_texture_slot(
+            n_blob,
+            n_json,
+            o_blob,
+            o_json,
+            emissive.index,
+            TextureType::Emissive,
+            size_for(emissive.index, TextureType::Emissive),
+            output,
+            textures,
+            deferred.as_deref_mut(),
+        ) {
+            Ok(index) => {
+                n_mat.emissive_texture = Some(gltf::json::texture::Info {
+                    index,
+                    ..emissive.clone()
+                });
+            }
+            Err(e) => {
+                return Err(slot_error(e, emissive.index, TextureType::Emissive));
+            }
+        }
+    }
+
+    // Known extension payloads point at source textures; others are dropped or left to handlers
+    for (name, payload) in n_mat.extensions.iter_mut().flat_map(|e| &mut e.others) {
+        if !MATERIAL_EXTENSIONS.contains(&name.as_str()) {
+            continue;
+        }
+        rewrite_texture_infos(payload, &mut |key, source| {
+            let texture = Index::new(source as u32);
+            let idx_tex = match extension_texture_type(name, key) {
+                Some(texture_type) => add_texture_slot(
                     n_blob,
                     n_json,
                     o_blob,
                     o_json,
-                    normal_tex,
-                    n_tex_size,
-                    convert_to_ktx2,
-                ) {
-                    Ok(new_normal) => {
-                        n_mat.normal_texture = Some(new_normal);
-                    }
-                    Err(e) => {
-                        return Err(format!("Failed to process normal texture: {e}").into());
+                    texture,
+                    texture_type,
+                    size_for(texture, texture_type),
+                    output,
+                    textures,
+                    deferred.as_deref_mut(),
+                )
+                .map_err(|e| slot_error(e, texture, texture_type))?,
+                None => match textures.copied.get(&source) {
+                    Some(&idx_tex) => idx_tex,
+                    None => {
+                        let idx_tex =
+                            copy_texture_verbatim(n_blob, n_json, o_blob, o_json, texture)?;
+                        textures.copied.insert(source, idx_tex);
+                        idx_tex
                     }
-                }
-            }
+                },
+            };
+            Ok(idx_tex.value())
+        })?;
+    }
+
+    Ok(n_mat)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_primitive(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    p: &gltf::json::mesh::Primitive,
+    n_tex_size: u32,
+    remove_normal_texture: bool,
+    output: TextureOutput,
+    pivot_offset: Option<[f32; 3]>,
+    half_precision: bool,
+    texture_sizes: &TextureSizes,
+    textures: &mut TextureSlots,
+    codec: Option<&dyn GeometryCodec>,
+    prepared: &PreparedAccessors,
+    deferred: Option<&mut Vec<TextureJob>>,
+) -> Result<Primitive, OptimizeError> {
+    let mut n_p = p.clone();
+    let geometry_mark = (n_blob.len(), n_json.buffer_views.len());
+    let copy = |n_blob: &mut BlobWriter,
+                n_json: &mut Root,
+                idx: Index<gltf::json::Accessor>,
+                offset: Option<[f32; 3]>| {
+        match prepared.get(&(idx.value(), offset.is_some())) {
+            Some(prepared) => Some(prepared.commit(n_blob, n_json)),
+            None => add_accessor_with_offset(n_blob, n_json, o_blob, o_json, idx, offset),
+        }
+    };
+
+    // copy indices
+    if let Some(indices) = p.indices {
+        n_p.indices = copy(n_blob, n_json, indices, None);
+    }
+
+    // copy attributes
+    n_p.attributes.clear();
+    for (k, v) in &p.attributes {
+        // Apply pivot offset only to POSITION attributes
+        let offset_to_apply = if matches!(
+            k,
+            gltf::json::validation::Checked::Valid(gltf::json::mesh::Semantic::Positions)
+        ) {
+            pivot_offset
+        } else {
+            None
+        };
+
+        let half = half_precision
+            .then(|| add_half_precision_accessor(n_blob, n_json, o_blob, o_json, k, *v))
+            .flatten();
+        if half.is_some() {
+            require_extensions(n_json, &["KHR_mesh_quantization".to_string()]);
         }
 
+        if let Some(idx_acc) = half.or_else(|| copy(n_blob, n_json, *v, offset_to_apply)) {
+            n_p.attributes.insert(k.clone(), idx_acc);
+        }
+    }
+
+    if let Some(codec) = codec {
+        encode_geometry(n_blob, n_json, &mut n_p, geometry_mark, codec)?;
+    }
+
+    // add material
+    if let Some(idx_mat) = p.material
+        && let Some(mat) = o_json.materials.get(idx_mat.value())
+    {
+        let n_mat = add_material(
+            n_blob,
+            n_json,
+            o_blob,
+            o_json,
+            mat,
+            n_tex_size,
+            remove_normal_texture,
+            output,
+            texture_sizes,
+            textures,
+            deferred,
+        )?;
+
         // update material
         let idx_mat = n_json.push(n_mat);
         n_p.material = Some(idx_mat);
@@ -795,96 +2546,249 @@ fn add_primitive(
     Ok(n_p)
 }
 
-fn pad_to_4bytes(data: &mut Vec<u8>) {
-    while !data.len().is_multiple_of(4) {
-        data.push(0);
+/// Store a float NORMAL, TANGENT or TEXCOORD_n accessor as normalized 16-bit integers
+///
+/// Returns `None` (the caller copies the accessor as is) for other semantics, non-float input
+/// and UVs outside `[0, 1]`, which unsigned normalized values cannot hold.
+fn add_half_precision_accessor(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    semantic: &Checked<gltf::json::mesh::Semantic>,
+    idx: Index<gltf::json::Accessor>,
+) -> Option<Index<gltf::json::Accessor>> {
+    use gltf::json::mesh::Semantic;
+
+    let acc = o_json.accessors.get(idx.value())?;
+    if !matches!(
+        acc.component_type,
+        Checked::Valid(GenericComponentType(ComponentType::F32))
+    ) {
+        return None;
+    }
+
+    let (bytes, stride, component) = match semantic {
+        Checked::Valid(Semantic::Normals) => {
+            let values = read_accessor::<3>(o_blob, o_json, idx)?;
+            let (bytes, stride) = write_elements(&values, ComponentType::I16, true);
+            (bytes, stride, ComponentType::I16)
+        }
+        Checked::Valid(Semantic::Tangents) => {
+            let values = read_accessor::<4>(o_blob, o_json, idx)?;
+            let (bytes, stride) = write_elements(&values, ComponentType::I16, true);
+            (bytes, stride, ComponentType::I16)
+        }
+        Checked::Valid(Semantic::TexCoords(_)) => {
+            let values = read_accessor::<2>(o_blob, o_json, idx)?;
+            if values.iter().flatten().any(|v| !(0.0..=1.0).contains(v)) {
+                return None;
+            }
+            let (bytes, stride) = write_elements(&values, ComponentType::U16, true);
+            (bytes, stride, ComponentType::U16)
+        }
+        _ => return None,
+    };
+
+    let view = n_blob.push_view(
+        n_json,
+        &bytes,
+        Some(gltf::json::buffer::Target::ArrayBuffer),
+    );
+    if stride != element_size(acc)? / 2 {
+        n_json.buffer_views[view.value()].byte_stride = Some(gltf::json::buffer::Stride(stride));
     }
+
+    Some(n_json.push(gltf::json::Accessor {
+        buffer_view: Some(view),
+        byte_offset: None,
+        component_type: Checked::Valid(GenericComponentType(component)),
+        normalized: true,
+        min: None,
+        max: None,
+        sparse: None,
+        ..acc.clone()
+    }))
 }
 
-/// Get raw position data from an accessor as f32 vec3 values
-fn get_position_data(
+/// Get position data from an accessor as f32 vec3 values, dequantizing integer components
+pub(crate) fn get_position_data(
     o_blob: &[u8],
     o_json: &gltf::json::Root,
     accessor_idx: Index<gltf::json::Accessor>,
 ) -> Option<Vec<[f32; 3]>> {
+    read_accessor(o_blob, o_json, accessor_idx)
+}
+
+/// Get texture coordinates from an accessor, decoding normalized u8/u16 values to f32
+pub(crate) fn get_texcoord_data(
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    accessor_idx: Index<gltf::json::Accessor>,
+) -> Option<Vec<[f32; 2]>> {
+    let acc = o_json.accessors.get(accessor_idx.value())?;
+    match acc.component_type {
+        Checked::Valid(GenericComponentType(ComponentType::F32)) => {}
+        Checked::Valid(GenericComponentType(ComponentType::U16 | ComponentType::U8))
+            if acc.normalized => {}
+        _ => return None,
+    }
+    read_accessor(o_blob, o_json, accessor_idx)
+}
+
+/// Get triangle indices from an accessor, widening u8/u16 values to u32
+pub(crate) fn get_index_data(
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    accessor_idx: Index<gltf::json::Accessor>,
+) -> Option<Vec<u32>> {
     let acc = o_json.accessors.get(accessor_idx.value())?;
     let idx_view = acc.buffer_view?;
     let view = o_json.buffer_views.get(idx_view.value())?;
 
     let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
     let acc_offset = acc.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
-    let stride = view.byte_stride.map(|s| s.0).unwrap_or(12); // 3 * f32
     let count = acc.count.0 as usize;
 
+    let size = match acc.component_type {
+        Checked::Valid(GenericComponentType(ComponentType::U8)) => 1,
+        Checked::Valid(GenericComponentType(ComponentType::U16)) => 2,
+        Checked::Valid(GenericComponentType(ComponentType::U32)) => 4,
+        _ => return None,
+    };
+    let stride = view.byte_stride.map(|s| s.0).unwrap_or(size);
+
     let data = o_blob.get(offset..)?;
 
-    let mut positions = Vec::with_capacity(count);
-    for i in 0..count {
-        let start = acc_offset + i * stride;
-        if start + 12 > data.len() {
+    // The count comes from the JSON; check the view and blob hold that many indices before
+    // allocating for them
+    if count > 0 {
+        let end = acc_offset.checked_add((count - 1).checked_mul(stride)?.checked_add(size)?)?;
+        if end > view.byte_length.0 as usize || end > data.len() {
             return None;
         }
-        let x = f32::from_le_bytes([
-            data[start],
-            data[start + 1],
-            data[start + 2],
-            data[start + 3],
-        ]);
-        let y = f32::from_le_bytes([
-            data[start + 4],
-            data[start + 5],
-            data[start + 6],
-            data[start + 7],
-        ]);
-        let z = f32::from_le_bytes([
-            data[start + 8],
-            data[start + 9],
-            data[start + 10],
-            data[start + 11],
-        ]);
-        positions.push([x, y, z]);
+    }
+    let mut indices = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = acc_offset + i * stride;
+        let bytes = data.get(start..start + size)?;
+        indices.push(match size {
+            1 => bytes[0] as u32,
+            2 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            _ => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        });
     }
 
-    Some(positions)
+    Some(indices)
 }
 
-/// Calculate bounding box from all meshes in the GLTF
-fn calculate_bounding_box(
-    o_blob: &[u8],
-    o_json: &gltf::json::Root,
-) -> Option<([f32; 3], [f32; 3])> {
-    let mut min = [f32::MAX, f32::MAX, f32::MAX];
-    let mut max = [f32::MIN, f32::MIN, f32::MIN];
-    let mut found_positions = false;
-
-    for mesh in &o_json.meshes {
-        for primitive in &mesh.primitives {
-            // Look for POSITION attribute
-            for (semantic, acc_idx) in &primitive.attributes {
-                if matches!(
-                    semantic,
-                    gltf::json::validation::Checked::Valid(gltf::json::mesh::Semantic::Positions)
-                ) && let Some(positions) = get_position_data(o_blob, o_json, *acc_idx)
-                {
-                    found_positions = true;
-                    for pos in positions {
-                        min[0] = min[0].min(pos[0]);
-                        min[1] = min[1].min(pos[1]);
-                        min[2] = min[2].min(pos[2]);
-                        max[0] = max[0].max(pos[0]);
-                        max[1] = max[1].max(pos[1]);
-                        max[2] = max[2].max(pos[2]);
-                    }
+/// Offset added to every position by `center_pivot`, `None` when positions stay as they are
+pub(crate) fn pivot_offset(o_blob: &[u8], o_json: &Root, center_pivot: bool) -> Option<[f32; 3]> {
+    // Check if model has skeleton/skin or animations
+    // If so, skip pivot adjustment as it would cause incorrect deformation
+    let has_skeleton_or_animation = !o_json.skins.is_empty() || !o_json.animations.is_empty();
+
+    // Calculate pivot offset if requested (disabled for skinned/animated models)
+    if center_pivot && !has_skeleton_or_animation {
+        calculate_bounding_box(o_blob, o_json)
+            .map(|(min, max)| calculate_center_bottom_offset(min, max))
+    } else {
+        None
+    }
+}
+
+/// Whether a world transform only translates, so offsetting positions moves the instance
+/// by the same amount
+fn translates_only(world: &Mat4) -> bool {
+    world[..12]
+        .iter()
+        .zip(&IDENTITY[..12])
+        .all(|(a, b)| (a - b).abs() <= 1e-6)
+}
+
+/// World-space bounding box of the default scene's mesh instances
+///
+/// A mesh placed by several nodes counts once per node. Files where no node places a mesh
+/// fall back to the raw positions of every mesh.
+pub(crate) fn calculate_bounding_box(o_blob: &[u8], o_json: &gltf::json::Root) -> Option<Bounds> {
+    let mut bounds: Option<Bounds> = None;
+    let mut extend = |(min, max): Bounds| {
+        let total = bounds.get_or_insert((min, max));
+        for c in 0..3 {
+            total.0[c] = total.0[c].min(min[c]);
+            total.1[c] = total.1[c].max(max[c]);
+        }
+    };
+    let positions = |mesh: usize| {
+        o_json
+            .meshes
+            .get(mesh)
+            .into_iter()
+            .flat_map(|m| &m.primitives)
+            .filter_map(|p| {
+                p.attributes
+                    .get(&Checked::Valid(gltf::json::mesh::Semantic::Positions))
+                    .copied()
+            })
+    };
+
+    let mut instanced = false;
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh, world| {
+        instanced = true;
+        for idx in positions(mesh) {
+            if translates_only(world) {
+                // Translated bounds are exact, and read in place
+                if let Some((min, max)) = position_bounds(o_blob, o_json, idx) {
+                    let shift =
+                        |p: [f32; 3]| [p[0] + world[12], p[1] + world[13], p[2] + world[14]];
+                    extend((shift(min), shift(max)));
                 }
+            } else if let Some(points) = get_position_data(o_blob, o_json, idx)
+                && !points.is_empty()
+            {
+                let points: Vec<[f32; 3]> =
+                    points.iter().map(|p| transform_point(world, *p)).collect();
+                extend(crate::math::bounds(&points));
+            }
+        }
+    });
+    if !instanced {
+        for idx in (0..o_json.meshes.len()).flat_map(positions) {
+            if let Some(mesh_bounds) = position_bounds(o_blob, o_json, idx) {
+                extend(mesh_bounds);
             }
         }
     }
 
-    if found_positions {
-        Some((min, max))
-    } else {
-        None
+    bounds
+}
+
+/// Whether `center_pivot` can offset positions, which holds when no node of the default scene
+/// rotates or scales a mesh
+fn pivot_moves_positions(o_json: &Root) -> bool {
+    let mut translates = true;
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, _, world| {
+        translates &= translates_only(world);
+    });
+    translates
+}
+
+/// Copy of `o_json` with the default scene's root nodes moved by `offset`
+fn translate_roots(o_json: &Root, offset: [f32; 3]) -> Root {
+    let mut json = o_json.clone();
+    for root in scene_roots(o_json) {
+        let Some(node) = json.nodes.get_mut(root.value()) else {
+            continue;
+        };
+        let translation = match &mut node.matrix {
+            Some(matrix) => &mut matrix[12..15],
+            None => &mut node.translation.get_or_insert([0.0; 3])[..],
+        };
+        for (t, o) in translation.iter_mut().zip(offset) {
+            *t += o;
+        }
     }
+    json
 }
 
 /// Calculate the offset needed to move pivot to center-bottom
@@ -895,68 +2799,1292 @@ fn calculate_center_bottom_offset(min: [f32; 3], max: [f32; 3]) -> [f32; 3] {
     [-center_x, -min[1], -center_z]
 }
 
+/// Optimize a GLB with the original positional settings
+///
+/// Thin wrapper over [`optimize_with`]; new settings are only available through
+/// [`OptimizeOptions`].
 pub fn optimize<R: Read + Seek>(
     reader: &mut R,
     new_texture_size: u32,
     remove_normal_texture: bool,
     convert_to_ktx2: bool,
     center_pivot: bool,
-) -> Result<Vec<u8>, Box<dyn Error>> {
-    let o_data = gltf::Gltf::from_reader(reader)?;
-    let o_json = o_data.as_json();
-    let o_blob = o_data.blob.as_deref().ok_or("failed to get o_data")?;
+) -> Result<Vec<u8>, OptimizeError> {
+    optimize_with(
+        reader,
+        &OptimizeOptions {
+            texture_size: new_texture_size,
+            remove_normal_texture,
+            convert_to_ktx2,
+            center_pivot,
+            ..Default::default()
+        },
+    )
+}
 
-    let mut n_blob: Vec<u8> = Vec::new();
+/// Optimize a GLB with every setting taken from `options`
+///
+/// Thin wrapper over [`Optimizer::run`](crate::Optimizer::run) without hooks. Calls share no
+/// state and may run concurrently on different threads; see
+/// [`OptimizeOptions::encoder_threads`] for the threads each one starts.
+pub fn optimize_with<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    Ok(Optimizer::new(options).run(reader)?.glb)
+}
 
-    // Check if model has skeleton/skin or animations
-    // If so, skip pivot adjustment as it would cause incorrect deformation
-    let has_skeleton_or_animation = !o_json.skins.is_empty() || !o_json.animations.is_empty();
+/// Run the optimizer on a parsed document, leaving the output unserialized
+///
+/// Vendor extension payloads are still attached to copied objects; callers decide which to
+/// keep with [`retain_extensions`].
+pub(crate) fn optimize_document(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    encoder: Option<&dyn TextureEncoder>,
+    codec: Option<&dyn GeometryCodec>,
+) -> Result<(Root, BlobWriter, RemapTables), OptimizeError> {
+    optimize_document_reporting(
+        o_blob,
+        o_json,
+        options,
+        encoder,
+        codec,
+        None,
+        None,
+        None,
+        None,
+        &mut Vec::new(),
+    )
+}
 
-    // Calculate pivot offset if requested (disabled for skinned/animated models)
-    let pivot_offset = if center_pivot && !has_skeleton_or_animation {
-        calculate_bounding_box(o_blob, o_json)
-            .map(|(min, max)| calculate_center_bottom_offset(min, max))
-    } else {
-        None
+/// [`optimize_document`] reusing the texture encodes in `cache` and adding new ones to it
+pub(crate) fn optimize_document_cached(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    cache: Option<&TextureCache>,
+) -> Result<(Root, BlobWriter, RemapTables), OptimizeError> {
+    optimize_document_reporting(
+        o_blob,
+        o_json,
+        options,
+        None,
+        None,
+        None,
+        None,
+        None,
+        cache,
+        &mut Vec::new(),
+    )
+}
+
+/// [`optimize_document`] running encodes through `filter`, reporting them to `metrics` and
+/// work items to `progress`, reusing encodes kept in `cache` and collecting the fallbacks
+/// taken in best-effort mode
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn optimize_document_reporting(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    encoder: Option<&dyn TextureEncoder>,
+    codec: Option<&dyn GeometryCodec>,
+    filter: Option<&dyn ExternalTextureFilter>,
+    metrics: Option<&dyn MetricsSink>,
+    progress: Option<&Progress>,
+    cache: Option<&TextureCache>,
+    issues: &mut Vec<BestEffortIssue>,
+) -> Result<(Root, BlobWriter, RemapTables), OptimizeError> {
+    options.reject_if.check(o_blob, o_json)?;
+    let options = &downgrade_for_profile(options);
+    let rewritten = rewrite_source(o_blob, o_json, options)?;
+    let (o_json, o_blob) = match &rewritten {
+        Some((json, blob)) => (json, blob.as_slice()),
+        None => (o_json, o_blob),
     };
 
-    // Clone extensions and add KHR_texture_basisu if not already present
-    let mut extensions_required = o_json.extensions_required.clone();
-    if convert_to_ktx2 && !extensions_required.contains(&"KHR_texture_basisu".to_string()) {
-        extensions_required.push("KHR_texture_basisu".to_string());
-    }
+    let texture_sizes = plan_sizes(o_blob, o_json, options, options.texture_size)?;
+    build_optimized(
+        o_blob,
+        o_json,
+        options,
+        &texture_sizes,
+        encoder,
+        codec,
+        filter,
+        metrics,
+        progress,
+        cache,
+        None,
+        issues,
+    )
+}
 
-    let mut n_json = gltf::json::Root {
-        asset: o_json.asset.clone(),
-        scene: o_json.scene,
-        extensions_required: extensions_required.clone(),
-        extensions_used: extensions_required,
-        cameras: o_json.cameras.clone(),
-        nodes: o_json.nodes.clone(),
+/// One output of [`optimize_variants`]
+#[derive(Debug, Clone)]
+pub struct TextureVariant {
+    /// Base color and normal map size of this tier (metallic/roughness is half)
+    pub texture_size: u32,
+    pub glb: Vec<u8>,
+}
+
+/// Optimize once and emit one GLB per texture size tier
+///
+/// Geometry, skins and animations are processed a single time and each source image is
+/// decoded once; only the resize/encode step runs per tier. `options.texture_size` is
+/// ignored in favour of `texture_sizes`, and a GPU texture budget is applied to every tier.
+/// Each variant matches what [`optimize_with`] produces at that size.
+pub fn optimize_variants<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+    texture_sizes: &[u32],
+) -> Result<Vec<TextureVariant>, OptimizeError> {
+    let options = &downgrade_for_profile(options);
+
+    let (o_json, o_blob) = read_glb(reader)?;
+    options.reject_if.check(&o_blob, &o_json)?;
+    let rewritten = rewrite_source(&o_blob, &o_json, options)?;
+    let (o_json, o_blob) = match &rewritten {
+        Some((json, blob)) => (json, blob.as_slice()),
+        None => (&o_json, o_blob.as_slice()),
+    };
+
+    let mut jobs = Vec::new();
+    let (mut n_json, n_blob, _) = build_optimized(
+        o_blob,
+        o_json,
+        options,
+        &TextureSizes::default(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(&mut jobs),
+        &mut Vec::new(),
+    )?;
+    retain_extensions(&mut n_json, &[])?;
+
+    // Decode every source texture once and share it across tiers; decoded pixels and encoded
+    // files are kept, so only the resize buffer is reused
+    let mut scratch = TextureScratch::default();
+    let mut decoded: HashMap<usize, (&[u8], DynamicImage)> = HashMap::new();
+    let mut pruned: HashMap<usize, DynamicImage> = HashMap::new();
+    for job in &jobs {
+        if let Entry::Vacant(entry) = decoded.entry(job.texture.value()) {
+            let data = get_image_data(o_blob, o_json, job.texture).ok_or(
+                OptimizeError::MissingImageData {
+                    texture: job.texture.value(),
+                },
+            )?;
+            let img = scratch.decode(o_json, job.texture, data)?;
+            if opaque_base_color(o_json, job.texture)
+                && let Some(rgb) = prune_opaque_alpha(&img)
+            {
+                pruned.insert(job.texture.value(), rgb);
+            }
+            entry.insert((data, img));
+        }
+
+        if job.texture_type == TextureType::BaseColor
+            && pruned.contains_key(&job.texture.value())
+            && let Some(image) = n_json.images.get_mut(job.image.value())
+        {
+            image.extras = merge_extras(&image.extras, ALPHA_PRUNED_KEY, true.into());
+        }
+    }
+
+    let encoding = BuiltinEncoding::new(options);
+    let share_mips = options.share_variant_mips && encoding.convert_to_ktx2 && options.ktx2_mipmaps;
+    // Largest encode of every slot so far; tiers run largest first so smaller ones can cut it
+    let mut chains: HashMap<(usize, TextureType), Vec<u8>> = HashMap::new();
+    let mut order: Vec<usize> = (0..texture_sizes.len()).collect();
+    if share_mips {
+        order.sort_by_key(|&i| std::cmp::Reverse(texture_sizes[i]));
+    }
+
+    let mut variants: Vec<Option<TextureVariant>> = vec![None; texture_sizes.len()];
+    for tier in order {
+        let texture_size = texture_sizes[tier];
+        let budget_sizes = plan_sizes(o_blob, o_json, options, texture_size)?;
+
+        // Textures shared by several primitives are encoded once per tier
+        let mut encoded: HashMap<(usize, TextureType, u32), Vec<u8>> = HashMap::new();
+        let mut replacements: HashMap<usize, (usize, TextureType, u32)> = HashMap::new();
+        for job in &jobs {
+            let default = options
+                .slot_texture_sizes
+                .size(job.texture_type, texture_size);
+            let size = budget_sizes.size(job.texture, default);
+            let key = (job.texture.value(), job.texture_type, size);
+
+            if let Entry::Vacant(entry) = encoded.entry(key)
+                && let Some((data, img)) = decoded.get(&job.texture.value())
+            {
+                let (img, data) = match pruned.get(&job.texture.value()) {
+                    Some(rgb) if job.texture_type == TextureType::BaseColor => (rgb, &[][..]),
+                    _ => (img, *data),
+                };
+                let upscaled = options
+                    .upscale_textures
+                    .and_then(|upscale| upscale_image(img, &upscale, size));
+                let (img, data) = match &upscaled {
+                    Some(upscaled) => (upscaled, &[][..]),
+                    None => (img, data),
+                };
+                let slot = (job.texture.value(), job.texture_type);
+                let target = encoding.output_dimensions(img, size, options.power_of_two_textures);
+                let tail = chains.get(&slot).filter(|_| share_mips).and_then(|chain| {
+                    let (width, height) = image_dimensions(chain)?;
+                    let skip = mip_chain(width, height)
+                        .into_iter()
+                        .take(stored_level_count(chain))
+                        .position(|level| level == target)
+                        .filter(|&skip| skip > 0)?;
+                    ktx2_mip_tail(chain, skip).ok()
+                });
+                let data = match tail {
+                    Some(tail) => tail,
+                    None => encode_texture(
+                        img,
+                        data,
+                        size,
+                        options.power_of_two_textures,
+                        job.texture_type,
+                        encoding,
+                        &mut scratch,
+                    )?,
+                };
+                if share_mips {
+                    chains.entry(slot).or_insert_with(|| data.clone());
+                }
+                entry.insert(data);
+            }
+
+            if let Some(view) = n_json
+                .images
+                .get(job.image.value())
+                .and_then(|img| img.buffer_view)
+            {
+                replacements.insert(view.value(), key);
+            }
+        }
+
+        let mut v_json = n_json.clone();
+        let v_blob = replace_views(&mut v_json, &n_blob, |view| {
+            replacements
+                .get(&view)
+                .and_then(|key| encoded.get(key))
+                .map(Vec::as_slice)
+        });
+        refresh_manifest(&mut v_json);
+        refresh_texture_table(&mut v_json, &v_blob);
+
+        variants[tier] = Some(TextureVariant {
+            texture_size,
+            glb: write_glb(&v_json, v_blob)?,
+        });
+    }
+
+    Ok(variants.into_iter().flatten().collect())
+}
+
+/// Source document after the animation, morph and UV passes that are enabled, `None` when
+/// none changed it
+fn rewrite_source(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    validate_hints(o_json)?;
+    // Collapsed first, so no work is spent on materials about to go
+    let mut rewritten = options
+        .collapse_materials
+        .then(|| collapse_materials(o_blob, o_json));
+    // Everything downstream only knows metallic/roughness materials
+    let (json, blob) = match &rewritten {
+        Some((json, blob)) => (json, blob.as_slice()),
+        None => (o_json, o_blob),
+    };
+    if let Some(converted) = convert_spec_gloss(blob, json)? {
+        rewritten = Some(converted);
+    }
+    // Copies collapse before any per-mesh work is spent on them
+    if options.dedupe_meshes {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(deduped) = dedupe_meshes(blob, json)? {
+            rewritten = Some(deduped);
+        }
+    }
+    // Fixed before primitives are merged, simplified or split with the weights
+    if options.repair_skin_weights {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(repaired) = repair_skinning(blob, json)? {
+            rewritten = Some(repaired);
+        }
+    }
+    if let Some(resampling) = &options.resample_cubic_animations {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(linearized) = linearize_cubic_samplers(blob, json, resampling)? {
+            rewritten = Some(linearized);
+        }
+    }
+    // Baked UVs can then be normalized and cropped like any other
+    if options.bake_texture_transforms {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(baked) = bake_texture_transforms(blob, json)? {
+            rewritten = Some(baked);
+        }
+    }
+    // Before atlasing, whose padding repeats the texture edges
+    if let Some(padding) = options.gutter_padding {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        let sizes = &options.slot_texture_sizes;
+        if let Some(dilated) = dilate_gutters(blob, json, padding, options.texture_size, sizes)? {
+            rewritten = Some(dilated);
+        }
+    }
+    // Atlased primitives share materials, so they can then be merged
+    if let Some(atlas) = &options.atlas_textures {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(packed) = atlas_textures(blob, json, atlas)? {
+            rewritten = Some(packed);
+        }
+    }
+    let morph_limit = match options.bake_morph_targets {
+        true => Some(0),
+        false => options.max_morph_targets,
+    };
+    if let Some(limit) = morph_limit {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(limited) = limit_morph_targets(blob, json, limit)? {
+            rewritten = Some(limited);
+        }
+    }
+    // Merged primitives can still be simplified and split into cells
+    if options.merge_primitives {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(merged) = merge_primitives(blob, json)? {
+            rewritten = Some(merged);
+        }
+    }
+    if let Some(simplify) = &options.simplify {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(simplified) = simplify_meshes(blob, json, simplify)? {
+            rewritten = Some(simplified);
+        }
+    }
+    // Levels of detail build on the simplified meshes
+    if let Some(lods) = &options.lods {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(levels) = add_lod_levels(blob, json, lods)? {
+            rewritten = Some(levels);
+        }
+    }
+    if let Some(split) = &options.split_primitives {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(cells) = split_large_primitives(blob, json, split)? {
+            rewritten = Some(cells);
+        }
+    }
+    // The meshopt index codec works best on triangles in vertex cache order
+    let reorder = match options.reorder_vertices {
+        VertexReorder::Off if options.meshopt_compression => VertexReorder::Cache,
+        reorder => reorder,
+    };
+    if reorder != VertexReorder::Off {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(reordered) = reorder_vertices(blob, json, reorder)? {
+            rewritten = Some(reordered);
+        }
+    }
+    // Clamp stray UVs first so their groups become croppable
+    if options.normalize_uvs {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(normalized) = normalize_uv_range(blob, json)? {
+            rewritten = Some(normalized);
+        }
+    }
+    if options.crop_textures {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(cropped) = crop_to_uv_range(blob, json)? {
+            rewritten = Some(cropped);
+        }
+    }
+    if let Some(rescale) = options.rescale {
+        let (json, blob) = match &rewritten {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, o_blob),
+        };
+        if let Some(rescaled) = rescale_scene(blob, json, rescale)? {
+            rewritten = Some(rescaled);
+        }
+    }
+    Ok(rewritten)
+}
+
+/// Per-texture sizes chosen by the GPU budget, triangle scaling and `extras` hints
+fn plan_sizes(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    texture_size: u32,
+) -> Result<TextureSizes, OptimizeError> {
+    // Shrink individual textures until the decoded total fits the budget
+    let budget = match options.gpu_texture_budget {
+        Some(budget) => plan_texture_budget(o_blob, o_json, options, budget, texture_size),
+        None => HashMap::new(),
+    };
+    // Level textures shrink from the size the full-detail level ends up with
+    let lod = match options.lods.as_ref().and_then(|lods| lods.texture_scale) {
+        Some(scale) => lod_texture_scales(o_json, scale)
+            .into_iter()
+            .map(|(texture, scale)| {
+                let side = get_image_data(o_blob, o_json, Index::new(texture as u32))
+                    .and_then(image_dimensions)
+                    .map_or(u32::MAX, |(w, h)| w.max(h));
+                (texture, (scale, side))
+            })
+            .collect(),
+        None => HashMap::new(),
+    };
+    let triangles = match &options.triangle_texture_scaling {
+        Some(scaling) => triangle_texture_scales(o_json, scaling)?,
+        None => HashMap::new(),
+    };
+    Ok(TextureSizes {
+        budget,
+        source: source_size_textures(o_blob, o_json, options.detect_sprite_sheets),
+        lod,
+        triangles,
+        hinted: hinted_texture_sizes(o_json),
+    })
+}
+
+/// Per-texture sizes decided before the material loop, keyed by texture
+#[derive(Debug, Default)]
+struct TextureSizes {
+    /// Caps from the GPU texture budget, which can only lower the size picked for a slot
+    budget: HashMap<usize, u32>,
+    /// Source size of sprite sheets and other textures never resized
+    source: HashMap<usize, u32>,
+    /// Size factor and source size of textures only lower levels of detail use
+    lod: HashMap<usize, (f32, u32)>,
+    /// Size factor of textures whose materials draw few triangles
+    triangles: HashMap<usize, f32>,
+    /// Caps hinted in the `extras` of the materials and meshes using a texture
+    hinted: HashMap<usize, u32>,
+}
+
+impl TextureSizes {
+    /// Size of a texture in a slot whose own size is `default`
+    fn size(&self, texture: Index<Texture>, default: u32) -> u32 {
+        if let Some(&size) = self.source.get(&texture.value()) {
+            return size;
+        }
+        let default = match self.lod.get(&texture.value()) {
+            Some(&(scale, side)) => ((default.min(side) as f32 * scale).round() as u32).max(1),
+            None => default,
+        };
+        let default = match self.triangles.get(&texture.value()) {
+            Some(&scale) => ((default as f32 * scale).round() as u32).max(1),
+            None => default,
+        };
+        let default = self
+            .hinted
+            .get(&texture.value())
+            .map_or(default, |&size| size.min(default));
+        self.budget
+            .get(&texture.value())
+            .map_or(default, |&size| size.min(default))
+    }
+}
+
+/// Rough size of the output blob, reserved up front so large models don't reallocate it
+///
+/// Accessor views are copied about as they are; images count for at most a byte per pixel
+/// of the target size, since most shrink when re-encoded.
+fn estimated_blob_len(o_json: &Root, texture_size: u32) -> usize {
+    let view_len = |view: Option<Index<gltf::json::buffer::View>>| {
+        view.and_then(|v| o_json.buffer_views.get(v.value()))
+            .map_or(0, |v| v.byte_length.0 as usize)
+    };
+    let mut views: Vec<usize> = o_json
+        .accessors
+        .iter()
+        .filter_map(|a| a.buffer_view.map(|v| v.value()))
+        .collect();
+    views.sort_unstable();
+    views.dedup();
+
+    let max_image = texture_size as usize * texture_size as usize;
+    let accessors: usize = views
+        .iter()
+        .map(|&v| view_len(Some(Index::new(v as u32))))
+        .sum();
+    let images: usize = o_json
+        .images
+        .iter()
+        .map(|image| view_len(image.buffer_view).min(max_image))
+        .sum();
+    accessors + images
+}
+
+/// Lay out the binary chunk again, taking some buffer views' bytes from `replace`
+pub(crate) fn replace_views<'a, F: Fn(usize) -> Option<&'a [u8]>>(
+    n_json: &mut Root,
+    n_blob: &'a [u8],
+    replace: F,
+) -> Vec<u8> {
+    let mut blob = BlobWriter::with_capacity(n_blob.len());
+
+    for (i, view) in n_json.buffer_views.iter_mut().enumerate() {
+        let data = replace(i).unwrap_or_else(|| {
+            let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+            let length = view.byte_length.0 as usize;
+            n_blob
+                .get(offset..offset.saturating_add(length))
+                .unwrap_or_default()
+        });
+
+        let offset = blob.append(data);
+        view.byte_offset = (offset > 0).then(|| offset.into());
+        view.byte_length = data.len().into();
+    }
+
+    blob.into_vec()
+}
+
+/// Output lengths before an attempt, so a failed one can be discarded in best-effort mode
+struct OutputMark {
+    blob: usize,
+    views: usize,
+    accessors: usize,
+    images: usize,
+    textures: usize,
+    materials: usize,
+    deferred: usize,
+    fallbacks: usize,
+}
+
+impl OutputMark {
+    fn new(
+        n_blob: &BlobWriter,
+        n_json: &Root,
+        textures: &TextureSlots,
+        deferred: Option<&Vec<TextureJob>>,
+    ) -> Self {
+        OutputMark {
+            blob: n_blob.len(),
+            views: n_json.buffer_views.len(),
+            accessors: n_json.accessors.len(),
+            images: n_json.images.len(),
+            textures: n_json.textures.len(),
+            materials: n_json.materials.len(),
+            deferred: deferred.map_or(0, Vec::len),
+            fallbacks: textures.fallbacks.len(),
+        }
+    }
+
+    /// Drop everything written since the mark
+    fn rollback(
+        &self,
+        n_blob: &mut BlobWriter,
+        n_json: &mut Root,
+        textures: &mut TextureSlots,
+        deferred: Option<&mut Vec<TextureJob>>,
+    ) {
+        n_blob.truncate(self.blob);
+        n_json.buffer_views.truncate(self.views);
+        n_json.accessors.truncate(self.accessors);
+        n_json.images.truncate(self.images);
+        n_json.textures.truncate(self.textures);
+        n_json.materials.truncate(self.materials);
+        textures.built.retain(|_, idx| idx.value() < self.textures);
+        textures.copied.retain(|_, idx| idx.value() < self.textures);
+        textures.fallbacks.truncate(self.fallbacks);
+        if let Some(deferred) = deferred {
+            deferred.truncate(self.deferred);
+        }
+    }
+}
+
+/// [`add_material`] that copies the textures as they are when it fails, and drops them when
+/// even that fails
+#[allow(clippy::too_many_arguments)]
+fn add_material_best_effort(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    o_blob: &[u8],
+    o_json: &gltf::json::Root,
+    idx_mat: usize,
+    n_tex_size: u32,
+    remove_normal_texture: bool,
+    output: TextureOutput,
+    texture_sizes: &TextureSizes,
+    textures: &mut TextureSlots,
+    mut deferred: Option<&mut Vec<TextureJob>>,
+    issues: &mut Vec<BestEffortIssue>,
+) -> gltf::json::Material {
+    let mat = &o_json.materials[idx_mat];
+    let mark = OutputMark::new(n_blob, n_json, textures, deferred.as_deref());
+    let error = match add_material(
+        n_blob,
+        n_json,
+        o_blob,
+        o_json,
+        mat,
+        n_tex_size,
+        remove_normal_texture,
+        output,
+        texture_sizes,
+        textures,
+        deferred.as_deref_mut(),
+    ) {
+        Ok(n_mat) => return n_mat,
+        Err(e) => e.to_string(),
+    };
+    mark.rollback(n_blob, n_json, textures, deferred.as_deref_mut());
+
+    let processed = std::mem::replace(&mut textures.verbatim, TextureType::ALL.into());
+    let copied = add_material(
+        n_blob,
+        n_json,
+        o_blob,
+        o_json,
+        mat,
+        n_tex_size,
+        remove_normal_texture,
+        output,
+        texture_sizes,
+        textures,
+        None,
+    );
+    textures.verbatim = processed;
+    let (n_mat, fallback) = match copied {
+        Ok(n_mat) => (n_mat, BestEffortFallback::VerbatimTextures),
+        Err(_) => {
+            mark.rollback(n_blob, n_json, textures, deferred);
+            (
+                placeholder_material(mat),
+                BestEffortFallback::TexturesDropped,
+            )
+        }
+    };
+    issues.push(BestEffortIssue {
+        material: Some(idx_mat),
+        texture: None,
+        primitive: None,
+        error,
+        fallback,
+    });
+    n_mat
+}
+
+/// Copy meshes, skins and animations into a new document
+///
+/// With `deferred` set, images are left empty and recorded for later encoding. In
+/// best-effort mode materials and primitives that fail fall back as recorded in `issues`.
+#[allow(clippy::too_many_arguments)]
+fn build_optimized(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &OptimizeOptions,
+    texture_sizes: &TextureSizes,
+    encoder: Option<&dyn TextureEncoder>,
+    codec: Option<&dyn GeometryCodec>,
+    filter: Option<&dyn ExternalTextureFilter>,
+    metrics: Option<&dyn MetricsSink>,
+    progress: Option<&Progress>,
+    cache: Option<&TextureCache>,
+    mut deferred: Option<&mut Vec<TextureJob>>,
+    issues: &mut Vec<BestEffortIssue>,
+) -> Result<(Root, BlobWriter, RemapTables), OptimizeError> {
+    let OptimizeOptions {
+        texture_size: new_texture_size,
+        slot_texture_sizes,
+        power_of_two_textures,
+        remove_normal_texture,
+        convert_to_ktx2: _,
+        ktx2_mipmaps: _,
+        ktx2_mip_rounding: _,
+        share_variant_mips: _,
+        compression_profiles: _,
+        texture_fallbacks: _,
+        center_pivot,
+        rescale: _,
+        gpu_texture_budget: _,
+        triangle_texture_scaling: _,
+        target_profile: _,
+        stable_indices,
+        prune_unused,
+        transcode_targets: _,
+        ktx2_metadata: _,
+        asset_license: _,
+        viewer_hints: _,
+        image_format,
+        environment_texture_size,
+        preview_subset,
+        auto_quality: _,
+        dither: _,
+        sharpen: _,
+        linear_light_resize: _,
+        alpha_conversion: _,
+        watermark: _,
+        anonymize_textures: _,
+        crop_textures: _,
+        atlas_textures: _,
+        gutter_padding: _,
+        normalize_uvs: _,
+        process_base_color: _,
+        process_normals: _,
+        process_metallic_roughness: _,
+        process_occlusion: _,
+        process_emissive: _,
+        half_precision_attributes,
+        quantize_attributes,
+        meshopt_compression,
+        sparse_morph_targets,
+        max_morph_targets: _,
+        bake_morph_targets: _,
+        quantize_animations,
+        repair_animation_timing,
+        repair_skin_weights: _,
+        resample_cubic_animations: _,
+        bake_texture_transforms: _,
+        content_hash_uris: _,
+        upscale_textures,
+        skip_reencode,
+        detect_sprite_sheets: _,
+        fix_misslotted_textures,
+        infer_texture_classes,
+        simplify: _,
+        merge_primitives: _,
+        dedupe_meshes: _,
+        collapse_materials: _,
+        lods: _,
+        split_primitives: _,
+        meshlets,
+        reorder_vertices: _,
+        best_effort,
+        never_grow: _,
+        reject_if: _,
+        encoder_threads: _,
+        low_priority: _,
+        accessor_views,
+        streaming_layout,
+        texture_table,
+    } = *options;
+
+    let convert_to_ktx2 = options.writes_ktx2();
+    if convert_to_ktx2 && encoder.is_none() && !cfg!(feature = "ktx2") {
+        return Err(FeatureNotEnabled { feature: "ktx2" }.into());
+    }
+
+    // Objects no scene reaches are left out before anything is copied
+    let source = o_json;
+    let pruned = prune_unused
+        .then(|| prune_unreachable(o_json, stable_indices))
+        .flatten();
+    let o_json = pruned.as_ref().map_or(o_json, |(json, _)| json);
+
+    let mut n_blob = BlobWriter::with_capacity(estimated_blob_len(o_json, new_texture_size));
+
+    // Taken from the whole source, as callers placing things next to the model do
+    let pivot_offset = pivot_offset(o_blob, source, center_pivot);
+    // Positions take the offset unless a node rotates or scales a mesh, whose instances the
+    // root nodes then carry instead
+    let moved_roots;
+    let (o_json, pivot_offset) = match pivot_offset {
+        Some(offset) if !pivot_moves_positions(o_json) => {
+            moved_roots = translate_roots(o_json, offset);
+            (&moved_roots, None)
+        }
+        pivot_offset => (o_json, pivot_offset),
+    };
+
+    // A custom encoder replaces the built-in formats and reports its own extensions
+    let format = match encoder {
+        Some(encoder) => TextureFormat::Custom(encoder),
+        None => TextureFormat::Builtin(BuiltinEncoding::new(options)),
+    };
+    let output = TextureOutput {
+        format,
+        slot_sizes: slot_texture_sizes,
+        power_of_two: power_of_two_textures,
+        upscale: upscale_textures,
+        reencode_skip: skip_reencode,
+        filter,
+        metrics,
+        progress,
+        cache,
+    };
+
+    // Clone extensions and add KHR_texture_basisu if not already present
+    let mut extensions_required = o_json.extensions_required.clone();
+    if convert_to_ktx2
+        && encoder.is_none()
+        && !extensions_required.contains(&"KHR_texture_basisu".to_string())
+    {
+        extensions_required.push("KHR_texture_basisu".to_string());
+    }
+    if !convert_to_ktx2
+        && image_format == Some(ImageFormat::WebP)
+        && encoder.is_none()
+        && !extensions_required.contains(&"EXT_texture_webp".to_string())
+    {
+        extensions_required.push("EXT_texture_webp".to_string());
+    }
+
+    let mut n_json = gltf::json::Root {
+        asset: o_json.asset.clone(),
+        scene: o_json.scene,
+        extensions_required: extensions_required.clone(),
+        extensions_used: extensions_required,
+        cameras: o_json.cameras.clone(),
+        nodes: o_json.nodes.clone(),
         samplers: o_json.samplers.clone(),
         scenes: o_json.scenes.clone(),
+        extras: o_json.extras.clone(),
         ..Default::default()
     };
+    options.asset_license.apply(&mut n_json.asset)?;
+    options
+        .viewer_hints
+        .apply(&mut n_json.scenes, &n_json.nodes)?;
+    // Variant names live on the root; primitive mappings are rebuilt with the meshes
+    if let Some(payload) = o_json
+        .extensions
+        .as_ref()
+        .and_then(|e| e.others.get(VARIANTS_EXTENSION))
+    {
+        n_json
+            .extensions
+            .get_or_insert_with(Default::default)
+            .others
+            .insert(VARIANTS_EXTENSION.to_string(), payload.clone());
+        n_json.extensions_used.push(VARIANTS_EXTENSION.to_string());
+    }
+    // Level-of-detail links are carried over with the nodes
+    if n_json.nodes.iter().any(|n| !lod_ids(n).is_empty())
+        && !n_json.extensions_used.iter().any(|e| e == LOD_EXTENSION)
+    {
+        n_json.extensions_used.push(LOD_EXTENSION.to_string());
+    }
+
+    let mut textures = TextureSlots {
+        verbatim: TextureType::ALL
+            .into_iter()
+            .filter(|&t| !options.processes(t))
+            .collect(),
+        misslotted: if infer_texture_classes {
+            class_mismatches(o_blob, o_json)
+                .into_iter()
+                .map(|m| ((m.texture, m.slot), m.looks_like))
+                .collect()
+        } else if fix_misslotted_textures {
+            slot_mismatches(o_blob, o_json)
+                .into_iter()
+                .map(|m| ((m.texture, m.slot), m.looks_like))
+                .collect()
+        } else {
+            HashMap::new()
+        },
+        ..Default::default()
+    };
+    let mut materials: Vec<Option<usize>> = vec![None; o_json.materials.len()];
+    let mut accessors: Vec<Option<usize>> = vec![None; o_json.accessors.len()];
+
+    // Preview: only materials of the first meshes get textures, the rest get placeholders
+    let previewed: Option<HashSet<usize>> = preview_subset.map(|count| {
+        o_json
+            .meshes
+            .iter()
+            .take(count)
+            .flat_map(|m| m.primitives.iter().filter_map(|p| p.material))
+            .map(|m| m.value())
+            .collect()
+    });
+    let textured = |mat: usize| previewed.as_ref().is_none_or(|set| set.contains(&mat));
+    let mut placeholders: HashMap<usize, Index<gltf::json::Material>> = HashMap::new();
+
+    // Unique slots are encoded in parallel first; the loops below only append them
+    if deferred.is_none() {
+        let size_for = |texture: Index<Texture>, texture_type: TextureType| {
+            let default_size = slot_texture_sizes.size(texture_type, new_texture_size);
+            texture_sizes.size(texture, default_size)
+        };
+        let mut slots = texture_slot_encodes(
+            o_json,
+            remove_normal_texture,
+            &textured,
+            &textures.verbatim,
+            &size_for,
+        );
+        // Images kept as they are are copied by the material loop
+        slots.retain(|&(texture, texture_type, size)| {
+            !output.keeps_source(o_blob, o_json, texture, texture_type, size)
+        });
+        for (texture, texture_type, _) in &mut slots {
+            *texture_type = textures.handling(*texture, *texture_type);
+        }
+        if let Some(progress) = progress {
+            progress.set_texture_total(slots.len());
+        }
+        textures.preencoded = preencode_textures(o_blob, o_json, &slots, output);
+    }
+
+    // Stable indices: every texture and material is built once, in input order
+    if stable_indices {
+        for (idx_tex, texture_type) in texture_first_uses(o_json, remove_normal_texture, &textured)
+        {
+            let default_size = slot_texture_sizes.size(texture_type, new_texture_size);
+            let mark = OutputMark::new(&n_blob, &n_json, &textures, deferred.as_deref());
+            let added = add_texture_slot(
+                &mut n_blob,
+                &mut n_json,
+                o_blob,
+                o_json,
+                idx_tex,
+                texture_type,
+                texture_sizes.size(idx_tex, default_size),
+                output,
+                &mut textures,
+                deferred.as_deref_mut(),
+            );
+            // The material using it fails the same way and records the fallback
+            if let Err(e) = added {
+                if !best_effort {
+                    return Err(e);
+                }
+                mark.rollback(
+                    &mut n_blob,
+                    &mut n_json,
+                    &mut textures,
+                    deferred.as_deref_mut(),
+                );
+            }
+        }
 
-    for mesh in o_json.meshes.iter() {
+        for (i, mat) in o_json.materials.iter().enumerate() {
+            let n_mat = if !textured(i) {
+                placeholder_material(mat)
+            } else if best_effort {
+                add_material_best_effort(
+                    &mut n_blob,
+                    &mut n_json,
+                    o_blob,
+                    o_json,
+                    i,
+                    new_texture_size,
+                    remove_normal_texture,
+                    output,
+                    texture_sizes,
+                    &mut textures,
+                    deferred.as_deref_mut(),
+                    issues,
+                )
+            } else {
+                add_material(
+                    &mut n_blob,
+                    &mut n_json,
+                    o_blob,
+                    o_json,
+                    mat,
+                    new_texture_size,
+                    remove_normal_texture,
+                    output,
+                    texture_sizes,
+                    &mut textures,
+                    deferred.as_deref_mut(),
+                )?
+            };
+            materials[i] = Some(n_json.push(n_mat).value());
+        }
+    }
+
+    let prepared = prepare_mesh_accessors(
+        o_blob,
+        o_json,
+        pivot_offset,
+        half_precision_attributes,
+        options.worker_threads(),
+    );
+    for (idx_mesh, mesh) in o_json.meshes.iter().enumerate() {
+        if let Some(progress) = progress {
+            progress.check()?;
+        }
         let mut n_mesh = mesh.clone();
         n_mesh.primitives.clear();
-        for p in mesh.primitives.iter() {
-            let np = add_primitive(
+        for (idx_p, p) in mesh.primitives.iter().enumerate() {
+            let placeholder = p
+                .material
+                .filter(|m| !textured(m.value()))
+                .and_then(|m| o_json.materials.get(m.value()).map(|mat| (m, mat)));
+            // Primitives sharing a source material share the output material built first
+            let built = p
+                .material
+                .and_then(|m| materials.get(m.value()).copied().flatten());
+            let mut source = p.clone();
+            if stable_indices || placeholder.is_some() || built.is_some() {
+                source.material = None;
+            }
+
+            let mark = OutputMark::new(&n_blob, &n_json, &textures, deferred.as_deref());
+            let added = add_primitive(
                 &mut n_blob,
                 &mut n_json,
                 o_blob,
                 o_json,
-                p,
+                &source,
                 new_texture_size,
                 remove_normal_texture,
-                convert_to_ktx2,
+                output,
                 pivot_offset,
-            )?;
+                half_precision_attributes,
+                texture_sizes,
+                &mut textures,
+                codec,
+                &prepared,
+                deferred.as_deref_mut(),
+            );
+            let mut np = match added {
+                Ok(np) => np,
+                // Retry the geometry and the material apart to find which one failed
+                Err(_) if best_effort => {
+                    mark.rollback(
+                        &mut n_blob,
+                        &mut n_json,
+                        &mut textures,
+                        deferred.as_deref_mut(),
+                    );
+                    let mut geometry = source.clone();
+                    geometry.material = None;
+                    let add_geometry = |n_blob: &mut BlobWriter,
+                                        n_json: &mut Root,
+                                        textures: &mut TextureSlots,
+                                        codec| {
+                        add_primitive(
+                            n_blob,
+                            n_json,
+                            o_blob,
+                            o_json,
+                            &geometry,
+                            new_texture_size,
+                            remove_normal_texture,
+                            output,
+                            pivot_offset,
+                            half_precision_attributes,
+                            texture_sizes,
+                            textures,
+                            codec,
+                            &prepared,
+                            None,
+                        )
+                    };
+                    let mut np = match add_geometry(&mut n_blob, &mut n_json, &mut textures, codec)
+                    {
+                        Ok(np) => np,
+                        Err(e) => {
+                            mark.rollback(&mut n_blob, &mut n_json, &mut textures, None);
+                            issues.push(BestEffortIssue {
+                                material: None,
+                                texture: None,
+                                primitive: Some((idx_mesh, idx_p)),
+                                error: e.to_string(),
+                                fallback: BestEffortFallback::UnencodedGeometry,
+                            });
+                            add_geometry(&mut n_blob, &mut n_json, &mut textures, None)?
+                        }
+                    };
+                    if let Some(idx_mat) = source.material
+                        && idx_mat.value() < o_json.materials.len()
+                    {
+                        let n_mat = add_material_best_effort(
+                            &mut n_blob,
+                            &mut n_json,
+                            o_blob,
+                            o_json,
+                            idx_mat.value(),
+                            new_texture_size,
+                            remove_normal_texture,
+                            output,
+                            texture_sizes,
+                            &mut textures,
+                            deferred.as_deref_mut(),
+                            issues,
+                        );
+                        np.material = Some(n_json.push(n_mat));
+                    }
+                    np
+                }
+                Err(e) => return Err(e),
+            };
+            np.targets = p.targets.as_ref().map(|targets| {
+                add_morph_targets(
+                    &mut n_blob,
+                    &mut n_json,
+                    o_blob,
+                    o_json,
+                    targets,
+                    sparse_morph_targets,
+                )
+            });
+
+            if let Some((idx_mat, mat)) = placeholder
+                && !stable_indices
+            {
+                let n_idx = *placeholders
+                    .entry(idx_mat.value())
+                    .or_insert_with(|| n_json.push(placeholder_material(mat)));
+                np.material = Some(n_idx);
+            }
+
+            if let Some(n_idx) = built
+                && !stable_indices
+            {
+                np.material = Some(Index::new(n_idx as u32));
+            }
+
+            if let Some(idx_mat) = p.material
+                && let Some(slot) = materials.get_mut(idx_mat.value())
+            {
+                match (stable_indices, *slot, np.material) {
+                    (true, Some(n_idx), _) => np.material = Some(Index::new(n_idx as u32)),
+                    (false, None, Some(n_idx)) => *slot = Some(n_idx.value()),
+                    _ => {}
+                }
+            }
+
+            // Variant materials are built like the primitive's own, once each
+            let mut variant_built = HashMap::new();
+            for m in variant_materials(p) {
+                let Some(mat) = o_json.materials.get(m) else {
+                    continue;
+                };
+                let n_idx = match materials[m] {
+                    Some(n_idx) => n_idx,
+                    None => {
+                        let n_mat = if !textured(m) {
+                            placeholder_material(mat)
+                        } else if best_effort {
+                            add_material_best_effort(
+                                &mut n_blob,
+                                &mut n_json,
+                                o_blob,
+                                o_json,
+                                m,
+                                new_texture_size,
+                                remove_normal_texture,
+                                output,
+                                texture_sizes,
+                                &mut textures,
+                                deferred.as_deref_mut(),
+                                issues,
+                            )
+                        } else {
+                            add_material(
+                                &mut n_blob,
+                                &mut n_json,
+                                o_blob,
+                                o_json,
+                                mat,
+                                new_texture_size,
+                                remove_normal_texture,
+                                output,
+                                texture_sizes,
+                                &mut textures,
+                                deferred.as_deref_mut(),
+                            )?
+                        };
+                        let n_idx = n_json.push(n_mat).value();
+                        materials[m] = Some(n_idx);
+                        n_idx
+                    }
+                };
+                variant_built.insert(m, n_idx);
+            }
+            remap_variant_materials(&mut np, |m| variant_built.get(&m).copied());
+
+            if let (Some(old), Some(new)) = (p.indices, np.indices) {
+                note_index(&mut accessors, old.value(), new.value());
+            }
+            for (k, old) in &p.attributes {
+                if let Some(new) = np.attributes.get(k) {
+                    note_index(&mut accessors, old.value(), new.value());
+                }
+            }
+            let target_pairs = p.targets.iter().flatten().zip(np.targets.iter().flatten());
+            for (old, new) in target_pairs {
+                let slots = [
+                    (old.positions, new.positions),
+                    (old.normals, new.normals),
+                    (old.tangents, new.tangents),
+                ];
+                for (old, new) in slots {
+                    if let (Some(old), Some(new)) = (old, new) {
+                        note_index(&mut accessors, old.value(), new.value());
+                    }
+                }
+            }
             n_mesh.primitives.push(np);
         }
 
         n_json.push(n_mesh);
+        if let Some(progress) = progress {
+            progress.mesh_done(idx_mesh + 1, o_json.meshes.len());
+        }
+    }
+    // Best-effort fallbacks swallow a cancelled encode of the last mesh's textures
+    if let Some(progress) = progress {
+        progress.check()?;
+    }
+    if let Some(limits) = meshlets {
+        add_meshlet_metadata(&mut n_blob, &mut n_json, &limits)?;
     }
 
     // Process skins and their inverseBindMatrices accessors
@@ -967,6 +4095,9 @@ pub fn optimize<R: Read + Seek>(
         if let Some(ibm_idx) = skin.inverse_bind_matrices {
             n_skin.inverse_bind_matrices =
                 add_accessor(&mut n_blob, &mut n_json, o_blob, o_json, ibm_idx);
+            if let Some(new) = n_skin.inverse_bind_matrices {
+                note_index(&mut accessors, ibm_idx.value(), new.value());
+            }
         }
 
         n_json.push(n_skin);
@@ -978,25 +4109,42 @@ pub fn optimize<R: Read + Seek>(
         n_animation.samplers.clear();
 
         // Build a mapping from old sampler index to new sampler index
-        let mut sampler_index_map: std::collections::HashMap<usize, usize> =
-            std::collections::HashMap::new();
+        let mut sampler_index_map: HashMap<usize, usize> = HashMap::new();
 
         // Copy each sampler's input/output accessors
         for (old_idx, sampler) in animation.samplers.iter().enumerate() {
             let mut n_sampler = sampler.clone();
 
-            // Copy input accessor (timestamps/keyframes)
-            if let Some(input_idx) =
-                add_accessor(&mut n_blob, &mut n_json, o_blob, o_json, sampler.input)
+            // Copy input accessor (timestamps/keyframes), fixing small timing glitches
+            let repaired = repair_animation_timing
+                .then(|| {
+                    add_repaired_input(&mut n_blob, &mut n_json, o_blob, o_json, sampler.input)
+                })
+                .flatten();
+            if let Some(input_idx) = repaired
+                .or_else(|| add_accessor(&mut n_blob, &mut n_json, o_blob, o_json, sampler.input))
             {
                 n_sampler.input = input_idx;
+                note_index(&mut accessors, sampler.input.value(), input_idx.value());
             }
 
-            // Copy output accessor (transformation values)
-            if let Some(output_idx) =
-                add_accessor(&mut n_blob, &mut n_json, o_blob, o_json, sampler.output)
+            // Copy output accessor (transformation values), quantized when allowed
+            let quantized = quantize_animations.and_then(|max_error| {
+                add_quantized_output(
+                    &mut n_blob,
+                    &mut n_json,
+                    o_blob,
+                    o_json,
+                    animation,
+                    old_idx,
+                    max_error,
+                )
+            });
+            if let Some(output_idx) = quantized
+                .or_else(|| add_accessor(&mut n_blob, &mut n_json, o_blob, o_json, sampler.output))
             {
                 n_sampler.output = output_idx;
+                note_index(&mut accessors, sampler.output.value(), output_idx.value());
             }
 
             let new_idx = n_animation.samplers.len();
@@ -1015,32 +4163,1524 @@ pub fn optimize<R: Read + Seek>(
         n_json.push(n_animation);
     }
 
-    pad_to_4bytes(&mut n_blob);
+    copy_image_based_lights(
+        &mut n_blob,
+        &mut n_json,
+        o_blob,
+        o_json,
+        environment_texture_size.unwrap_or(new_texture_size),
+    )?;
+
+    // Meshlet bounds are in the float positions' space, so those stay as they are
+    if quantize_attributes
+        && let Some(blob) = quantize_vertex_attributes(&mut n_json, &n_blob, meshlets.is_none())?
+    {
+        n_blob = BlobWriter::from(blob);
+    }
 
-    n_json.push(gltf::json::Buffer {
-        byte_length: n_blob.len().into(),
-        name: None,
-        uri: None,
-        extensions: None,
-        extras: Default::default(),
-    });
+    // Fallback encodings can trade the format an image extension was required for
+    if !textures.fallbacks.is_empty() {
+        require_image_extensions(&mut n_json, o_json);
+        issues.append(&mut textures.fallbacks);
+    }
 
-    let json_bytes = gltf::json::serialize::to_vec(&n_json)?;
+    if let Some(blob) = share_accessor_views(&mut n_json, &n_blob, accessor_views)? {
+        n_blob = BlobWriter::from(blob);
+    }
+    if streaming_layout {
+        n_blob = BlobWriter::from(layout_for_streaming(&mut n_json, &n_blob)?);
+    }
+    if meshopt_compression && let Some(blob) = compress_views(&mut n_json, &n_blob)? {
+        n_blob = BlobWriter::from(blob);
+        refresh_manifest(&mut n_json);
+    }
 
-    let n_glb = gltf::binary::Glb {
-        header: gltf::binary::Header {
-            magic: *b"glTF",
-            version: 2,
-            length: (json_bytes.len() + n_blob.len()) as u32,
-        },
-        json: Cow::Owned(json_bytes),
-        bin: Some(Cow::Owned(n_blob)),
+    list_material_extensions(&mut n_json);
+    if prune_unused {
+        prune_samplers(&mut n_json);
+    }
+    if texture_table {
+        write_texture_table(&mut n_json, &n_blob);
+    }
+
+    // Nodes and meshes are copied one to one
+    let remap = RemapTables {
+        nodes: (0..o_json.nodes.len()).map(Some).collect(),
+        meshes: (0..o_json.meshes.len()).map(Some).collect(),
+        materials,
+        textures: textures.remap(o_json.textures.len()),
+        accessors,
+        node_names: o_json.nodes.iter().map(|n| n.name.clone()).collect(),
+        mesh_names: o_json.meshes.iter().map(|m| m.name.clone()).collect(),
+        material_names: o_json.materials.iter().map(|m| m.name.clone()).collect(),
     };
+    let remap = match &pruned {
+        Some((_, kept)) => kept.source_tables(remap, source),
+        None => remap,
+    };
+
+    Ok((n_json, n_blob, remap))
+}
+
+/// Copy of a material without any textures, used for meshes left out of a preview
+fn placeholder_material(mat: &gltf::json::Material) -> gltf::json::Material {
+    let mut n_mat = mat.clone();
+    n_mat.pbr_metallic_roughness.base_color_texture = None;
+    n_mat.pbr_metallic_roughness.metallic_roughness_texture = None;
+    n_mat.normal_texture = None;
+    n_mat.occlusion_texture = None;
+    n_mat.emissive_texture = None;
+    // Extension payloads may point at textures that were never built
+    n_mat.extensions = None;
+    n_mat
+}
+
+/// Texture and slot type of every slot a material fills, in slot order
+fn material_texture_uses(
+    mat: &gltf::json::Material,
+    remove_normal_texture: bool,
+) -> impl Iterator<Item = (Index<Texture>, TextureType)> {
+    let pbr = &mat.pbr_metallic_roughness;
+    [
+        pbr.base_color_texture
+            .as_ref()
+            .map(|i| (i.index, TextureType::BaseColor)),
+        pbr.metallic_roughness_texture
+            .as_ref()
+            .map(|i| (i.index, TextureType::MetallicRoughness)),
+        mat.normal_texture
+            .as_ref()
+            .filter(|_| !remove_normal_texture)
+            .map(|i| (i.index, TextureType::Normal)),
+        mat.occlusion_texture
+            .as_ref()
+            .map(|i| (i.index, TextureType::Occlusion)),
+        mat.emissive_texture
+            .as_ref()
+            .map(|i| (i.index, TextureType::Emissive)),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Slot type of each texture's first use, in texture index order
+///
+/// Materials accepted by `textured` are scanned in order; textures none of them uses (or
+/// only as a removed normal map) are left out.
+fn texture_first_uses(
+    o_json: &Root,
+    remove_normal_texture: bool,
+    textured: &dyn Fn(usize) -> bool,
+) -> Vec<(Index<Texture>, TextureType)> {
+    let mut first_uses: Vec<Option<TextureType>> = vec![None; o_json.textures.len()];
+
+    for mat in o_json
+        .materials
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| textured(i))
+        .map(|(_, mat)| mat)
+    {
+        for (idx_tex, texture_type) in material_texture_uses(mat, remove_normal_texture) {
+            if let Some(slot @ None) = first_uses.get_mut(idx_tex.value()) {
+                *slot = Some(texture_type);
+            }
+        }
+    }
+
+    first_uses
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, t)| t.map(|t| (Index::new(i as u32), t)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use gltf::json::{
+        Accessor,
+        accessor::Type,
+        buffer::{Stride, View},
+    };
+
+    use super::*;
+    use crate::{positions::vec3_bounds, testing::SyntheticGlb};
+
+    /// Four vertices of interleaved position + normal, 24 bytes each
+    fn interleaved_blob() -> Vec<u8> {
+        (0..4)
+            .flat_map(|i| [i as f32, 1.0, 2.0, 0.0, 0.0, 1.0])
+            .flat_map(f32::to_le_bytes)
+            .collect()
+    }
+
+    fn interleaved_json(blob_len: usize, byte_stride: Option<usize>) -> Root {
+        let mut json = Root::default();
+        let view = json.push(View {
+            buffer: Index::new(0),
+            byte_length: blob_len.into(),
+            byte_offset: None,
+            byte_stride: byte_stride.map(Stride),
+            name: None,
+            target: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+        json.push(Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: 4u64.into(),
+            component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Checked::Valid(Type::Vec3),
+            min: Some(serde_json::json!([0.0, 1.0, 2.0])),
+            max: Some(serde_json::json!([3.0, 1.0, 2.0])),
+            name: None,
+            normalized: false,
+            sparse: None,
+        });
+        json
+    }
+
+    /// Make accessor 0 the position attribute of a mesh
+    fn draw_positions(json: &mut Root) {
+        json.meshes.push(gltf::json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            primitives: vec![Primitive {
+                attributes: [(
+                    Checked::Valid(gltf::json::mesh::Semantic::Positions),
+                    Index::new(0),
+                )]
+                .into(),
+                extensions: None,
+                extras: Default::default(),
+                indices: None,
+                material: None,
+                mode: Checked::Valid(gltf::json::mesh::Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+    }
+
+    fn offset_positions(byte_stride: Option<usize>) -> (Root, Vec<u8>) {
+        let o_blob = interleaved_blob();
+        let mut o_json = interleaved_json(o_blob.len(), byte_stride);
+        draw_positions(&mut o_json);
+        let mut n_blob = BlobWriter::from(vec![0; 2]);
+        let mut n_json = Root::default();
+        add_accessor_with_offset(
+            &mut n_blob,
+            &mut n_json,
+            &o_blob,
+            &o_json,
+            Index::new(0),
+            Some([10.0, 0.0, -2.0]),
+        )
+        .unwrap();
+        (n_json, n_blob.into_vec())
+    }
+
+    fn floats(json: &Root, blob: &[u8]) -> Vec<f32> {
+        let view = &json.buffer_views[0];
+        let start = view.byte_offset.map_or(0, |o| o.0 as usize);
+        assert_eq!(start % 4, 0);
+        blob[start..start + view.byte_length.0 as usize]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    fn assert_offset(json: &Root, blob: &[u8]) {
+        let values = floats(json, blob);
+        for (i, vertex) in values.chunks_exact(6).enumerate() {
+            assert_eq!(vertex, [i as f32 + 10.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        }
+        let acc = &json.accessors[0];
+        assert_eq!(acc.min, Some(serde_json::json!([10.0, 1.0, 0.0])));
+        assert_eq!(acc.max, Some(serde_json::json!([13.0, 1.0, 0.0])));
+    }
+
+    #[test]
+    fn offset_keeps_interleaved_normals() {
+        let (json, blob) = offset_positions(Some(24));
+        assert_eq!(json.buffer_views[0].byte_stride, Some(Stride(24)));
+        assert_offset(&json, &blob);
+    }
+
+    #[test]
+    fn offset_handles_padded_vertices_without_byte_stride() {
+        let (json, blob) = offset_positions(None);
+        // The inferred stride is written so readers agree on the layout
+        assert_eq!(json.buffer_views[0].byte_stride, Some(Stride(24)));
+        assert_offset(&json, &blob);
+    }
+
+    #[test]
+    fn offset_recomputes_missing_or_stale_bounds() {
+        let o_blob = interleaved_blob();
+        for (min, max) in [
+            (None, None),
+            (
+                Some(serde_json::json!([0, 1, 2])),
+                Some(serde_json::json!([99, 99, 99])),
+            ),
+        ] {
+            let mut o_json = interleaved_json(o_blob.len(), Some(24));
+            o_json.accessors[0].min = min;
+            o_json.accessors[0].max = max;
+
+            let mut n_blob = BlobWriter::new();
+            let mut n_json = Root::default();
+            add_accessor_with_offset(
+                &mut n_blob,
+                &mut n_json,
+                &o_blob,
+                &o_json,
+                Index::new(0),
+                Some([10.0, 0.0, -2.0]),
+            )
+            .unwrap();
+            assert_offset(&n_json, &n_blob);
+        }
+    }
+
+    #[test]
+    fn offset_dequantizes_normalized_positions() {
+        // KHR_mesh_quantization layout: normalized i16 VEC3 padded to 8 bytes
+        let o_blob: Vec<u8> = [[32767i16, 0, -32767, 0], [0, 16384, 0, 0]]
+            .iter()
+            .flatten()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let mut o_json = interleaved_json(o_blob.len(), Some(8));
+        let acc = &mut o_json.accessors[0];
+        acc.count = 2u64.into();
+        acc.component_type = Checked::Valid(GenericComponentType(ComponentType::I16));
+        acc.normalized = true;
+
+        let positions = get_position_data(&o_blob, &o_json, Index::new(0)).unwrap();
+        assert_eq!(positions[0], [1.0, 0.0, -1.0]);
+
+        let mut n_blob = BlobWriter::new();
+        let mut n_json = Root::default();
+        let idx = add_accessor_with_offset(
+            &mut n_blob,
+            &mut n_json,
+            &o_blob,
+            &o_json,
+            Index::new(0),
+            Some([1.0, 0.0, 0.0]),
+        )
+        .unwrap();
+        let n_acc = &n_json.accessors[idx.value()];
+        assert!(matches!(
+            n_acc.component_type,
+            Checked::Valid(GenericComponentType(ComponentType::F32))
+        ));
+        assert!(!n_acc.normalized);
+        let values = floats(&n_json, &n_blob);
+        assert_eq!(values[..3], [2.0, 0.0, -1.0]);
+        assert!((values[4] - 16384.0 / 32767.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn prepared_accessors_match_serial_copies() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb {
+            meshes: 40,
+            primitives_per_mesh: 2,
+            interleaved: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let pivot = Some([1.0, -2.0, 0.5]);
+        let prepared = prepare_mesh_accessors(&o_blob, &o_json, pivot, false, 4);
+        assert!(prepared.len() >= PARALLEL_COPY_MIN_ACCESSORS);
+
+        let (mut parallel, mut serial) = (
+            (BlobWriter::new(), Root::default()),
+            (BlobWriter::new(), Root::default()),
+        );
+        let mut keys: Vec<_> = prepared.keys().copied().collect();
+        keys.sort_unstable();
+        for (idx, offset) in keys {
+            prepared[&(idx, offset)].commit(&mut parallel.0, &mut parallel.1);
+            let offset = pivot.filter(|_| offset);
+            add_accessor_with_offset(
+                &mut serial.0,
+                &mut serial.1,
+                &o_blob,
+                &o_json,
+                Index::new(idx as u32),
+                offset,
+            );
+        }
+        assert_eq!(*parallel.0, *serial.0);
+        assert_eq!(
+            serde_json::to_value(&parallel.1).unwrap(),
+            serde_json::to_value(&serial.1).unwrap()
+        );
+    }
+
+    #[test]
+    fn position_data_reads_interleaved_vertices() {
+        let o_blob = interleaved_blob();
+        let o_json = interleaved_json(o_blob.len(), Some(24));
+        let positions = get_position_data(&o_blob, &o_json, Index::new(0)).unwrap();
+        assert_eq!(positions[3], [3.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn variants_match_separate_optimizations() {
+        use crate::testing::{GlbTolerances, compare_glbs};
+
+        let glb = SyntheticGlb {
+            textures: 2,
+            primitives_per_mesh: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default();
+        let variants = optimize_variants(&mut Cursor::new(&glb), &options, &[32, 16]).unwrap();
+        assert_eq!(variants.len(), 2);
+        for variant in &variants {
+            let single = optimize_with(
+                &mut Cursor::new(&glb),
+                &options.clone().texture_size(variant.texture_size),
+            )
+            .unwrap();
+            let exact = GlbTolerances {
+                geometry: 0.0,
+                image: 0.0,
+            };
+            let differences = compare_glbs(&single, &variant.glb, &exact).unwrap();
+            assert!(
+                differences.is_empty(),
+                "tier {}: {differences:?}",
+                variant.texture_size
+            );
+        }
+    }
 
-    let mut result: Vec<u8> = Vec::new();
-    let writer = Cursor::new(&mut result);
+    #[test]
+    fn index_counts_beyond_the_view_are_refused() {
+        let o_blob: Vec<u8> = [0u16, 1, 2, 2, 1, 3]
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect();
+        let o_json = |count: u64| -> Root {
+            serde_json::from_value(serde_json::json!({
+                "asset": { "version": "2.0" },
+                "buffers": [{ "byteLength": 12 }],
+                "bufferViews": [{ "buffer": 0, "byteLength": 12 }],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5123, "count": count, "type": "SCALAR" },
+                ],
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            get_index_data(&o_blob, &o_json(6), Index::new(0)),
+            Some(vec![0, 1, 2, 2, 1, 3])
+        );
+        assert_eq!(get_index_data(&o_blob, &o_json(7), Index::new(0)), None);
+        assert_eq!(
+            get_index_data(&o_blob, &o_json(1_000_000_000), Index::new(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn bounding_box_matches_scalar_scan() {
+        // Enough vertices to fill several SIMD chunks plus a remainder
+        let positions: Vec<[f32; 3]> = (0..1003)
+            .map(|i| {
+                let t = i as f32;
+                [(t * 0.37).sin() * 50.0, (t * 1.3).cos() - 4.0, t * -0.01]
+            })
+            .collect();
+        let bytes: Vec<u8> = positions
+            .as_flattened()
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &positions {
+            for c in 0..3 {
+                min[c] = min[c].min(p[c]);
+                max[c] = max[c].max(p[c]);
+            }
+        }
+
+        // A blob starting off a float boundary is read through a copy
+        for shift in [0, 1] {
+            let mut padded = vec![0; shift];
+            padded.extend_from_slice(&bytes);
+            let o_blob = &padded[shift..];
+            let mut o_json = interleaved_json(o_blob.len(), None);
+            o_json.accessors[0].count = (positions.len() as u64).into();
+            draw_positions(&mut o_json);
+            assert_eq!(calculate_bounding_box(o_blob, &o_json), Some((min, max)));
+
+            let mut n_blob = BlobWriter::new();
+            let mut n_json = Root::default();
+            add_accessor_with_offset(
+                &mut n_blob,
+                &mut n_json,
+                o_blob,
+                &o_json,
+                Index::new(0),
+                Some([1.0, 2.0, 3.0]),
+            )
+            .unwrap();
+            let expected: Vec<f32> = positions
+                .iter()
+                .flat_map(|p| [p[0] + 1.0, p[1] + 2.0, p[2] + 3.0])
+                .collect();
+            assert_eq!(floats(&n_json, &n_blob), expected);
+        }
+    }
+
+    #[test]
+    fn pivot_follows_node_transforms() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let (min, max) = calculate_bounding_box(&o_blob, &o_json).unwrap();
+
+        // The mesh scaled by its own node, and instanced again under a translated parent
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["nodes"][0]["scale"] = serde_json::json!([2.0, 2.0, 2.0]);
+        let nodes = doc["nodes"].as_array_mut().unwrap();
+        nodes.push(serde_json::json!({ "mesh": 0, "translation": [10.0, 0.0, 0.0] }));
+        nodes.push(serde_json::json!({ "children": [1], "translation": [0.0, 4.0, 0.0] }));
+        doc["scenes"][0]["nodes"] = serde_json::json!([0, 2]);
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+
+        let scaled = (min.map(|v| v * 2.0), max.map(|v| v * 2.0));
+        let moved = |p: [f32; 3]| [p[0] + 10.0, p[1] + 4.0, p[2]];
+        let expected = (
+            std::array::from_fn(|c| scaled.0[c].min(moved(min)[c])),
+            std::array::from_fn(|c| scaled.1[c].max(moved(max)[c])),
+        );
+        assert_eq!(calculate_bounding_box(&o_blob, &o_json), Some(expected));
+        assert!(!pivot_moves_positions(&o_json));
 
-    n_glb.to_writer(writer)?;
+        // Positions stay as they are and the root nodes carry the offset
+        let options = OptimizeOptions {
+            center_pivot: true,
+            ..Default::default()
+        };
+        let glb = write_glb(&o_json, o_blob.clone()).unwrap();
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+        let offset = calculate_center_bottom_offset(expected.0, expected.1);
+        assert_eq!(n_json.nodes[0].translation, Some(offset));
+        assert_eq!(
+            n_json.nodes[2].translation,
+            Some([offset[0], 4.0 + offset[1], offset[2]])
+        );
+        assert_eq!(n_json.nodes[1].translation, Some([10.0, 0.0, 0.0]));
+        assert_eq!(
+            calculate_bounding_box(&n_blob, &n_json).map(|b| b.0[1]),
+            Some(0.0)
+        );
+    }
 
-    Ok(result)
+    #[test]
+    fn sparse_and_quantized_positions_take_pivot_offset() {
+        let base = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ];
+        let mut o_blob: Vec<u8> = base
+            .as_flattened()
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        // Sparse indices and values, then u16 positions padded to an 8-byte stride
+        o_blob.extend([2, 0, 1, 0]);
+        o_blob.extend(
+            [0.0f32, 5.0, 0.0, 3.0, 3.0, 3.0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes()),
+        );
+        o_blob.extend(
+            [0u16, 0, 0, 0, 2, 0, 0, 0, 0, 4, 0, 0]
+                .iter()
+                .flat_map(|v| v.to_le_bytes()),
+        );
+        let sparse = |index_offset: usize, value_offset: usize| {
+            serde_json::json!({
+                "count": 1,
+                "indices": { "bufferView": 1, "byteOffset": index_offset, "componentType": 5123 },
+                "values": { "bufferView": 2, "byteOffset": value_offset },
+            })
+        };
+        let o_json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": o_blob.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 48 },
+                { "buffer": 0, "byteOffset": 48, "byteLength": 4 },
+                { "buffer": 0, "byteOffset": 52, "byteLength": 24 },
+                { "buffer": 0, "byteOffset": 76, "byteLength": 24, "byteStride": 8 },
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                    "sparse": sparse(0, 0),
+                },
+                { "componentType": 5126, "count": 4, "type": "VEC3", "sparse": sparse(2, 12) },
+                { "bufferView": 3, "componentType": 5123, "count": 3, "type": "VEC3" },
+            ],
+        }))
+        .unwrap();
+
+        let expected: [&[[f32; 3]]; 3] = [
+            &[
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 5.0, 0.0],
+                [1.0, 1.0, 0.0],
+            ],
+            &[
+                [0.0, 0.0, 0.0],
+                [3.0, 3.0, 3.0],
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+            ],
+            &[[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 4.0, 0.0]],
+        ];
+        for (i, expected) in expected.into_iter().enumerate() {
+            let idx = Index::new(i as u32);
+            assert_eq!(get_position_data(&o_blob, &o_json, idx).unwrap(), expected);
+            assert_eq!(
+                position_bounds(&o_blob, &o_json, idx),
+                vec3_bounds(expected.as_flattened(), 3)
+            );
+
+            let mut n_blob = BlobWriter::new();
+            let mut n_json = Root::default();
+            let n_idx = add_accessor_with_offset(
+                &mut n_blob,
+                &mut n_json,
+                &o_blob,
+                &o_json,
+                idx,
+                Some([1.0, 2.0, 3.0]),
+            )
+            .unwrap();
+            let moved: Vec<[f32; 3]> = expected
+                .iter()
+                .map(|p| [p[0] + 1.0, p[1] + 2.0, p[2] + 3.0])
+                .collect();
+            let n_blob = n_blob.into_vec();
+            assert!(n_json.accessors[n_idx.value()].sparse.is_none());
+            assert_eq!(get_position_data(&n_blob, &n_json, n_idx).unwrap(), moved);
+
+            // Without an offset sparse accessors are written out dense in their own type
+            let mut n_blob = BlobWriter::new();
+            let mut n_json = Root::default();
+            let n_idx =
+                add_accessor_with_offset(&mut n_blob, &mut n_json, &o_blob, &o_json, idx, None)
+                    .unwrap();
+            let n_blob = n_blob.into_vec();
+            assert!(n_json.accessors[n_idx.value()].sparse.is_none());
+            assert_eq!(
+                get_position_data(&n_blob, &n_json, n_idx).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn synthetic_glbs_optimize() {
+        let plain = SyntheticGlb {
+            meshes: 3,
+            primitives_per_mesh: 2,
+            ..Default::default()
+        };
+        let variants = [
+            plain.clone(),
+            SyntheticGlb {
+                interleaved: true,
+                ..plain.clone()
+            },
+            SyntheticGlb {
+                sparse_morph_targets: true,
+                ..plain.clone()
+            },
+            SyntheticGlb {
+                skin: true,
+                ..plain.clone()
+            },
+        ];
+        for synthetic in variants {
+            let glb = synthetic.build().unwrap();
+            gltf::Gltf::from_slice(&glb).unwrap();
+            let out = optimize_with(&mut Cursor::new(&glb), &OptimizeOptions::default()).unwrap();
+            let doc = gltf::Gltf::from_slice(&out).unwrap();
+            assert_eq!(doc.meshes().count(), 3);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg", feature = "ktx2"))]
+    fn concurrent_optimizations_match_sequential() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<OptimizeOptions>();
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        for convert_to_ktx2 in [false, true] {
+            let options = OptimizeOptions {
+                texture_size: 16,
+                convert_to_ktx2,
+                encoder_threads: Some(1),
+                ..Default::default()
+            };
+            let expected = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            std::thread::scope(|scope| {
+                let runs: Vec<_> = (0..4)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            optimize_with(&mut Cursor::new(&glb), &options)
+                                .map_err(|e| e.to_string())
+                        })
+                    })
+                    .collect();
+                for run in runs {
+                    assert_eq!(run.join().unwrap().unwrap(), expected);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn texture_dimensions_keep_aspect_ratio() {
+        assert_eq!(texture_dimensions(2048, 512, 1024, false), (1024, 256));
+        assert_eq!(texture_dimensions(1000, 600, 1024, false), (1000, 600));
+        // Nearest power of two, but never above the cap
+        assert_eq!(texture_dimensions(1000, 600, 1024, true), (1024, 512));
+        assert_eq!(texture_dimensions(1000, 600, 1000, true), (512, 512));
+        assert_eq!(texture_dimensions(4096, 2048, 1024, true), (2048, 1024));
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn color_textures_are_downscaled_in_linear_light() {
+        use crate::{decode::load_image, glb::read_glb, inject::replace_texture};
+
+        // A one-texel black and white checkerboard, half as bright as white in linear light
+        let checker = image::RgbaImage::from_fn(64, 64, |x, y| {
+            let v = if (x + y) % 2 == 0 { 0 } else { 255 };
+            image::Rgba([v, v, v, 255])
+        });
+        let mut png = Vec::new();
+        image::DynamicImage::from(checker)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(glb), 0, &png, None).unwrap();
+
+        let center = |options: &OptimizeOptions| {
+            let out =
+                optimize_with(&mut Cursor::new(&glb), &options.clone().texture_size(16)).unwrap();
+            let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&blob, &json, Index::new(0)).unwrap();
+            let img = load_image(data, Vec::new()).unwrap().to_rgb8();
+            assert_eq!(img.dimensions(), (16, 16));
+            img.get_pixel(8, 8).0[0]
+        };
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        };
+        // sRGB 188 encodes linear 0.5, while averaging the encoded values gives 128
+        assert!(center(&options).abs_diff(188) <= 3);
+        let srgb = OptimizeOptions {
+            linear_light_resize: false,
+            ..options
+        };
+        assert!(center(&srgb).abs_diff(128) <= 3);
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn preencoded_textures_match_serial_encodes() {
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 4,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let data = gltf::Gltf::from_slice(&glb).unwrap();
+        let (o_json, o_blob) = (data.as_json(), data.blob.as_deref().unwrap());
+        let options = OptimizeOptions::default();
+        let output = TextureOutput {
+            format: TextureFormat::Builtin(BuiltinEncoding::new(&options)),
+            slot_sizes: options.slot_texture_sizes,
+            power_of_two: false,
+            upscale: None,
+            reencode_skip: ReencodeSkip::default(),
+            filter: None,
+            metrics: None,
+            progress: None,
+            cache: None,
+        };
+
+        let slots = texture_slot_encodes(o_json, false, &|_| true, &HashSet::new(), &|_, _| 16);
+        assert_eq!(slots.len(), 3);
+        let preencoded = preencode_textures(o_blob, o_json, &slots, output);
+        assert_eq!(preencoded.len(), 3);
+        let mut scratch = TextureScratch::default();
+        for (texture, texture_type, size) in slots {
+            let data = get_image_data(o_blob, o_json, texture).unwrap();
+            let serial = encode_slot(
+                o_json,
+                texture,
+                data,
+                size,
+                texture_type,
+                output,
+                &mut scratch,
+            )
+            .unwrap();
+            let key = (texture.value(), texture_type, size);
+            assert_eq!(preencoded[&key].encoded, serial.encoded);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn shared_material_is_built_once() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 2,
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let materials: Vec<_> = n_json.meshes[0]
+            .primitives
+            .iter()
+            .map(|p| p.material)
+            .collect();
+        assert_eq!(materials, [Some(Index::new(0)); 2]);
+        assert_eq!(
+            (
+                n_json.materials.len(),
+                n_json.textures.len(),
+                n_json.images.len()
+            ),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn texture_classes_are_inferred_from_names() {
+        use crate::{glb::read_glb, slot_check::name_class};
+
+        assert_eq!(
+            name_class("textures/brick_wall_n.png"),
+            Some(TextureType::Normal)
+        );
+        assert_eq!(
+            name_class("Rock_ORM_2k"),
+            Some(TextureType::MetallicRoughness)
+        );
+        assert_eq!(name_class("crate-ao.jpg"), Some(TextureType::Occlusion));
+        assert_eq!(name_class("Lamp_Emissive"), Some(TextureType::Emissive));
+        assert_eq!(
+            name_class("hull_bc_1024.ktx2"),
+            Some(TextureType::BaseColor)
+        );
+        assert_eq!(name_class("texture_0"), None);
+        assert_eq!(name_class("canyon"), None);
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.textures[0].name = Some("panel_nrm".to_string());
+        let glb = write_glb(&json, blob).unwrap();
+
+        // The gradient pixels alone don't look like a normal map, the name does
+        let mime_type = |infer_texture_classes| {
+            let options = OptimizeOptions {
+                infer_texture_classes,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+            n_json.images[0].mime_type.clone().unwrap().0
+        };
+        assert_eq!(mime_type(false), "image/jpeg");
+        assert_eq!(mime_type(true), "image/png");
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn slots_keep_tex_coord_sets_and_samplers() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut json = serde_json::to_value(&json).unwrap();
+        json["samplers"] = serde_json::json!([
+            { "wrapS": 33071, "wrapT": 33071 },
+            { "magFilter": 9728, "minFilter": 9728 },
+        ]);
+        json["textures"][0]["sampler"] = 0.into();
+        json["textures"][1]["sampler"] = 1.into();
+        json["materials"][0]["normalTexture"] = serde_json::json!({ "index": 1, "texCoord": 1 });
+        let primitive = &mut json["meshes"][0]["primitives"][0];
+        primitive["attributes"]["TEXCOORD_1"] = primitive["attributes"]["TEXCOORD_0"].clone();
+        let json: Root = serde_json::from_value(json).unwrap();
+        let glb = write_glb(&json, blob).unwrap();
+
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let material = &n_json.materials[0];
+        let base_color = material
+            .pbr_metallic_roughness
+            .base_color_texture
+            .as_ref()
+            .unwrap();
+        let normal = material.normal_texture.as_ref().unwrap();
+        assert_eq!((base_color.tex_coord, normal.tex_coord), (0, 1));
+
+        let sampler = |texture: Index<Texture>| {
+            let sampler = n_json.textures[texture.value()].sampler.unwrap();
+            serde_json::to_value(&n_json.samplers[sampler.value()]).unwrap()
+        };
+        assert_eq!(
+            sampler(base_color.index),
+            serde_json::to_value(&json.samplers[0]).unwrap()
+        );
+        assert_eq!(
+            sampler(normal.index),
+            serde_json::to_value(&json.samplers[1]).unwrap()
+        );
+        let attributes = &n_json.meshes[0].primitives[0].attributes;
+        assert!(attributes.contains_key(&Checked::Valid(gltf::json::mesh::Semantic::TexCoords(1))));
+    }
+
+    #[test]
+    fn second_uv_sets_are_kept_and_missing_ones_reported() {
+        use gltf::json::mesh::Semantic;
+
+        use crate::{
+            glb::read_glb,
+            tiles::{TilingOptions, optimize_tiled},
+            warnings::{SceneWarning, find_scene_warnings},
+        };
+
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut json = serde_json::to_value(&json).unwrap();
+        json["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"] = serde_json::json!({
+            "index": 0,
+            "extensions": { "KHR_texture_transform": { "texCoord": 1 } },
+        });
+        let json: Root = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            find_scene_warnings(&blob, &json),
+            [SceneWarning::MissingTexCoord {
+                mesh: 0,
+                primitive: 0,
+                material: 0,
+                tex_coord: 1,
+            }]
+        );
+
+        let mut json = serde_json::to_value(&json).unwrap();
+        let primitive = &mut json["meshes"][0]["primitives"][0];
+        primitive["attributes"]["TEXCOORD_1"] = primitive["attributes"]["TEXCOORD_0"].clone();
+        let json: Root = serde_json::from_value(json).unwrap();
+        assert_eq!(find_scene_warnings(&blob, &json), []);
+
+        let glb = write_glb(&json, blob).unwrap();
+        let tileset = optimize_tiled(
+            &mut Cursor::new(glb),
+            &OptimizeOptions::default(),
+            &TilingOptions::default(),
+        )
+        .unwrap();
+        let (t_json, _) = read_glb(&mut Cursor::new(&tileset.tiles[0].data)).unwrap();
+        let primitive = t_json.meshes[0]
+            .primitives
+            .iter()
+            .find(|p| p.material.is_some_and(|m| m.value() == 0))
+            .unwrap();
+        assert!(
+            primitive
+                .attributes
+                .contains_key(&Checked::Valid(Semantic::TexCoords(1)))
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "ktx2"))]
+    fn ktx2_mipmaps_reach_one_pixel() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 32,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            convert_to_ktx2: true,
+            ktx2_mipmaps: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let view = &n_json.buffer_views[n_json.images[0].buffer_view.unwrap().value()];
+        let offset = view.byte_offset.map_or(0, |o| o.0 as usize);
+        let ktx2 = Ktx2Texture::from_memory(&n_blob[offset..offset + view.byte_length.0 as usize])
+            .unwrap();
+        // 32, 16, 8, 4, 2, 1
+        assert_eq!(ktx2.levels(), 6);
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "ktx2"))]
+    fn ktx2_metadata_is_appended() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            convert_to_ktx2: true,
+            ktx2_metadata: vec![
+                ("AssetId".to_string(), "chair-042".to_string()),
+                ("License".to_string(), "CC-BY-4.0".to_string()),
+            ],
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let view = &n_json.buffer_views[n_json.images[0].buffer_view.unwrap().value()];
+        let offset = view.byte_offset.map_or(0, |o| o.0 as usize);
+        let ktx2 = Ktx2Texture::from_memory(&n_blob[offset..offset + view.byte_length.0 as usize])
+            .unwrap();
+        assert_eq!(ktx2.get_metadata("AssetId").unwrap(), b"chair-042");
+        assert_eq!(ktx2.get_metadata("Tool").unwrap(), b"glb_opt");
+
+        for key in ["Tool", "KTXwriter"] {
+            let options = OptimizeOptions {
+                ktx2_metadata: vec![(key.to_string(), "x".to_string())],
+                ..options.clone()
+            };
+            assert!(optimize_with(&mut Cursor::new(&glb), &options).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn errors_carry_their_context() {
+        use crate::{
+            encoder::{EncodedTexture, TextureEncoder},
+            error::Stage,
+        };
+
+        let e =
+            optimize_with(&mut Cursor::new(b"not a glb"), &OptimizeOptions::default()).unwrap_err();
+        assert!(matches!(e, OptimizeError::InvalidInput(_)));
+
+        struct Failing;
+        impl TextureEncoder for Failing {
+            fn encode(
+                &self,
+                _image: &image::RgbaImage,
+                _texture_type: TextureType,
+            ) -> Result<EncodedTexture, Box<dyn std::error::Error + Send + Sync>> {
+                Err("encoder offline".into())
+            }
+        }
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let e = Optimizer::new(&OptimizeOptions::default())
+            .texture_encoder(&Failing)
+            .run(&mut Cursor::new(glb))
+            .unwrap_err();
+        let OptimizeError::Texture {
+            texture: 0,
+            slot: TextureType::BaseColor,
+            source,
+        } = e
+        else {
+            panic!("unexpected error {e}");
+        };
+        assert!(matches!(
+            *source,
+            OptimizeError::Callback {
+                stage: Stage::TextureEncoder,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "webp"))]
+    fn webp_output_marks_extension_required() {
+        use crate::{TargetProfile, compat::downgrade_for_profile, glb::read_glb};
+
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::WebP),
+            texture_size: 64,
+            ..Default::default()
+        };
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+
+        let webp = "EXT_texture_webp".to_string();
+        assert!(json.extensions_required.contains(&webp));
+        assert!(json.extensions_used.contains(&webp));
+        assert!(!json.images.is_empty());
+        for image in &json.images {
+            assert_eq!(image.mime_type.as_ref().unwrap().0, "image/webp");
+            assert!(image.name.as_ref().is_none_or(|n| !n.ends_with(".png")));
+            let view = &json.buffer_views[image.buffer_view.unwrap().value()];
+            let start = view.byte_offset.map_or(0, |o| o.0 as usize);
+            let data = &blob[start..start + view.byte_length.0 as usize];
+            let decoded = image::load_from_memory(data).unwrap();
+            assert!(decoded.width() <= 64 && decoded.height() <= 64);
+        }
+
+        // Profiles without WebP support fall back to JPEG/PNG
+        let options = OptimizeOptions {
+            target_profile: Some(TargetProfile::UnityGltfast),
+            ..options
+        };
+        assert_eq!(downgrade_for_profile(&options).image_format, None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn low_priority_uses_fewer_threads_for_the_same_bytes() {
+        let low = OptimizeOptions {
+            low_priority: true,
+            ..Default::default()
+        };
+        let cpus = num_cpus::get();
+        assert_eq!(low.worker_threads(), (cpus / 2).max(1));
+        assert_eq!(low.basis_threads(), 1);
+        let pinned = OptimizeOptions {
+            encoder_threads: Some(3),
+            ..low.clone()
+        };
+        assert_eq!(pinned.basis_threads(), 3);
+        assert_eq!(OptimizeOptions::default().worker_threads(), cpus);
+
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run =
+            |options: &OptimizeOptions| optimize_with(&mut Cursor::new(&glb), options).unwrap();
+        assert_eq!(run(&low), run(&OptimizeOptions::default()));
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "ktx2"))]
+    fn ktx2_mip_chains_are_reported_and_rounded() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 25,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let levels = |ktx2_mip_rounding| {
+            let options = OptimizeOptions {
+                convert_to_ktx2: true,
+                ktx2_mipmaps: true,
+                ktx2_mip_rounding,
+                ..Default::default()
+            };
+            let out = Optimizer::new(&options)
+                .report(true)
+                .run(&mut Cursor::new(&glb))
+                .unwrap();
+            out.report.unwrap().textures[0]
+                .after
+                .as_ref()
+                .unwrap()
+                .levels
+                .clone()
+        };
+        let side = |sides: &[u32]| sides.iter().map(|&s| (s, s)).collect::<Vec<_>>();
+        assert_eq!(levels(MipRounding::Keep), side(&[25, 12, 6, 3, 1]));
+        assert_eq!(levels(MipRounding::Even), side(&[24, 12, 6, 3, 1]));
+        assert_eq!(levels(MipRounding::PowerOfTwo), side(&[32, 16, 8, 4, 2, 1]));
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn exif_orientation_is_applied_to_pixels() {
+        use image::{ImageEncoder, Rgb, RgbImage, codecs::jpeg::JpegEncoder};
+
+        use crate::{glb::read_glb, inject::replace_texture};
+
+        // Red on the left, blue on the right
+        let source = RgbImage::from_fn(32, 16, |x, _| {
+            if x < 16 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        });
+        let mut plain = Vec::new();
+        JpegEncoder::new_with_quality(&mut plain, 95)
+            .write_image(source.as_raw(), 32, 16, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        // APP1 segment with a one-entry IFD: orientation 6, rotate 90° clockwise
+        let mut exif = vec![0xFF, 0xE1, 0, 34];
+        exif.extend(b"Exif\0\0II\x2A\0\x08\0\0\0\x01\0");
+        exif.extend([0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0]);
+        let jpeg = [&plain[..2], &exif, &plain[2..]].concat();
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(&glb), 0, &jpeg, None).unwrap();
+        let options = OptimizeOptions {
+            skip_reencode: ReencodeSkip {
+                jpeg_below: Some(u64::MAX),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let data = get_image_data(&n_blob, &n_json, Index::new(0)).unwrap();
+        let image = image::load_from_memory(data).unwrap().to_rgb8();
+        assert!(image.height() > image.width());
+        let [r, _, b] = image.get_pixel(image.width() / 2, 0).0;
+        assert!(r > 200 && b < 60, "top should be red");
+        let [r, _, b] = image.get_pixel(image.width() / 2, image.height() - 1).0;
+        assert!(b > 200 && r < 60, "bottom should be blue");
+    }
+
+    #[test]
+    fn mip_tails_are_cut_from_ktx2_chains() {
+        use crate::{decode::KTX2_MAGIC, mips::ktx2_mip_tail};
+
+        // A 4x4 Basis file with three levels, each filled with its own byte
+        let build = |scheme: u32, image_descs: bool| {
+            let levels: [&[u8]; 3] = [&[0xA0; 16], &[0xA1; 16], &[0xA2; 16]];
+            let dfd = [7u8; 44];
+            let mut kvd = 14u32.to_le_bytes().to_vec();
+            kvd.extend_from_slice(b"Dimensions\x004x4");
+            kvd.resize(20, 0);
+            let mut sgd = Vec::new();
+            if image_descs {
+                sgd = vec![0; 20];
+                for level in 0..3u8 {
+                    sgd.extend_from_slice(&[level + 1; 20]);
+                }
+            }
+            let mut ktx2 = KTX2_MAGIC.to_vec();
+            ktx2.extend_from_slice(&[b'\r', b'\n', 0x1A, b'\n']);
+            for field in [0, 1, 4, 4, 0, 0, 1, 3, scheme, 152, 44, 196, 20] {
+                ktx2.extend_from_slice(&u32::to_le_bytes(field));
+            }
+            let sgd_offset = if sgd.is_empty() { 0 } else { 216 };
+            ktx2.extend_from_slice(&u64::to_le_bytes(sgd_offset));
+            ktx2.extend_from_slice(&(sgd.len() as u64).to_le_bytes());
+            ktx2.resize(152, 0);
+            ktx2.extend_from_slice(&dfd);
+            ktx2.extend_from_slice(&kvd);
+            ktx2.resize(216, 0);
+            ktx2.extend_from_slice(&sgd);
+            for level in (0..3).rev() {
+                let entry = 80 + level * 24;
+                let offset = ktx2.len() as u64;
+                ktx2[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+                ktx2[entry + 8..entry + 16].copy_from_slice(&16u64.to_le_bytes());
+                ktx2.extend_from_slice(levels[level]);
+            }
+            ktx2
+        };
+        let u32_at =
+            |data: &[u8], at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let u64_at =
+            |data: &[u8], at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        let level = |data: &[u8], i: usize| {
+            let (offset, len) = (
+                u64_at(data, 80 + i * 24) as usize,
+                u64_at(data, 88 + i * 24),
+            );
+            data[offset..offset + len as usize].to_vec()
+        };
+
+        for (scheme, image_descs) in [(0, false), (1, true), (2, false)] {
+            let tail = ktx2_mip_tail(&build(scheme, image_descs), 1).unwrap();
+            assert_eq!(image_dimensions(&tail), Some((2, 2)));
+            assert_eq!(stored_level_count(&tail), 2);
+            assert_eq!(level(&tail, 0), [0xA1; 16]);
+            assert_eq!(level(&tail, 1), [0xA2; 16]);
+            // Smallest level first, aligned to UASTC blocks when not supercompressed
+            assert!(u64_at(&tail, 104) < u64_at(&tail, 80));
+            if scheme == 0 {
+                assert_eq!(u64_at(&tail, 80) % 16, 0);
+            }
+
+            let (kvd, kvd_len) = (u32_at(&tail, 56) as usize, u32_at(&tail, 60) as usize);
+            assert_eq!(&tail[kvd + 4..kvd + 18], b"Dimensions\x002x2");
+            assert_eq!(kvd_len, 20);
+            let (sgd, sgd_len) = (u64_at(&tail, 64) as usize, u64_at(&tail, 72) as usize);
+            if image_descs {
+                assert_eq!(sgd % 8, 0);
+                assert_eq!(sgd_len, 60);
+                assert_eq!(tail[sgd + 20..sgd + 60], [[2; 20], [3; 20]].concat());
+            } else {
+                assert_eq!((sgd, sgd_len), (0, 0));
+            }
+        }
+        assert!(ktx2_mip_tail(&build(0, false), 3).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn previews_texture_only_the_first_meshes() {
+        let glb = SyntheticGlb {
+            meshes: 3,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            preview_subset: Some(1),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+
+        let (json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(json.textures.len(), 1);
+        assert_eq!(json.images.len(), 1);
+        assert_eq!(json.meshes.len(), 3);
+        let textured: Vec<_> = json
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let mat = &json.materials[mesh.primitives[0].material.unwrap().value()];
+                (
+                    mat.name.as_deref(),
+                    mat.pbr_metallic_roughness.base_color_texture.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            textured,
+            [
+                (Some("material_0"), true),
+                (Some("material_1"), false),
+                (Some("material_2"), false)
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn slots_are_processed_per_texture_type() {
+        let glb = SyntheticGlb {
+            textures: 3,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["materials"][0]["occlusionTexture"] = serde_json::json!({"index": 1});
+        doc["materials"][0]["emissiveTexture"] = serde_json::json!({"index": 2});
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob.clone()).unwrap();
+
+        let options = OptimizeOptions {
+            texture_size: 16,
+            process_emissive: false,
+            ..Default::default()
+        };
+        assert!(!options.processes(TextureType::Emissive));
+        assert!(options.processes(TextureType::Occlusion));
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let mat = &json.materials[0];
+        let image = |texture: Index<Texture>| get_image_data(&blob, &json, texture).unwrap();
+        let size = |texture| {
+            let image = image::load_from_memory(image(texture)).unwrap();
+            (image.width(), image.height())
+        };
+        let base_color = mat.pbr_metallic_roughness.base_color_texture.as_ref();
+        assert_eq!(size(base_color.unwrap().index), (16, 16));
+        // Occlusion defaults to half the texture size
+        let occlusion = mat.occlusion_texture.as_ref().unwrap().index;
+        assert_eq!(size(occlusion), (8, 8));
+        assert_eq!(&image(occlusion)[..2], [0xFF, 0xD8]);
+        let emissive = mat.emissive_texture.as_ref().unwrap().index;
+        assert_eq!(
+            image(emissive),
+            get_image_data(&o_blob, &o_json, Index::new(2)).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn occlusion_and_emissive_textures_are_carried_through() {
+        let glb = SyntheticGlb {
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        let material = &mut doc["materials"][0];
+        material["pbrMetallicRoughness"]["metallicRoughnessTexture"] =
+            serde_json::json!({"index": 1});
+        material["occlusionTexture"] = serde_json::json!({"index": 1, "strength": 0.6});
+        material["emissiveTexture"] = serde_json::json!({"index": 2, "texCoord": 0});
+        material["emissiveFactor"] = serde_json::json!([1.0, 0.5, 0.0]);
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let mat = &json.materials[0];
+        let occlusion = mat.occlusion_texture.as_ref().unwrap();
+        assert_eq!(occlusion.strength.0, 0.6);
+        // Occlusion packed with metallic/roughness keeps sharing the ORM texture
+        let orm = mat
+            .pbr_metallic_roughness
+            .metallic_roughness_texture
+            .as_ref();
+        assert_eq!(Some(occlusion.index), orm.map(|t| t.index));
+        let emissive = mat.emissive_texture.as_ref().unwrap();
+        assert_eq!(mat.emissive_factor.0, [1.0, 0.5, 0.0]);
+        for texture in [occlusion.index, emissive.index] {
+            image::load_from_memory(get_image_data(&blob, &json, texture).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn half_precision_attributes_read_back_within_a_step() {
+        use gltf::json::mesh::Semantic;
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb.clone())).unwrap();
+        let options = OptimizeOptions {
+            half_precision_attributes: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+
+        let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(
+            json.extensions_required
+                .iter()
+                .any(|e| e == "KHR_mesh_quantization")
+        );
+        let (o_p, n_p) = (
+            &o_json.meshes[0].primitives[0],
+            &json.meshes[0].primitives[0],
+        );
+        let component = |semantic: Semantic| {
+            let acc = &json.accessors[n_p.attributes[&Checked::Valid(semantic)].value()];
+            match acc.component_type {
+                Checked::Valid(GenericComponentType(c)) => (c, acc.normalized),
+                Checked::Invalid => panic!("invalid component type"),
+            }
+        };
+        assert_eq!(component(Semantic::Normals), (ComponentType::I16, true));
+        assert_eq!(
+            component(Semantic::TexCoords(0)),
+            (ComponentType::U16, true)
+        );
+        assert_eq!(component(Semantic::Positions), (ComponentType::F32, false));
+
+        let uvs = |blob: &[u8], json: &Root, p: &gltf::json::mesh::Primitive| {
+            let idx = p.attributes[&Checked::Valid(Semantic::TexCoords(0))];
+            read_accessor::<2>(blob, json, idx).unwrap()
+        };
+        for (new, old) in uvs(&blob, &json, n_p)
+            .iter()
+            .zip(uvs(&o_blob, &o_json, o_p))
+        {
+            for (n, o) in new.iter().zip(old) {
+                assert!((n - o).abs() <= 1.0 / 65535.0, "{n} vs {o}");
+            }
+        }
+    }
 }
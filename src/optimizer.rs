@@ -0,0 +1,463 @@
+use std::{
+    io::{Cursor, Read, Seek},
+    time::Instant,
+};
+
+use crate::{
+    anchor::{Anchor, insert_anchors},
+    best_effort::BestEffortIssue,
+    buffers::{ExternalBuffer, split_buffers},
+    encoder::TextureEncoder,
+    error::OptimizeError,
+    extension::{ExtensionRegistry, retain_extensions},
+    external::{ExternalTexture, extract_images},
+    extras::{ExtrasSchemas, ExtrasViolation},
+    filter::ExternalTextureFilter,
+    geometry::GeometryCodec,
+    glb::{read_glb, write_glb},
+    growth::{GrowthFallback, never_grow},
+    material::{MaterialEdit, apply_material_edits},
+    metrics::{Counter, Histogram, MetricsSink},
+    opt::{optimize_document_reporting, pivot_offset},
+    options::OptimizeOptions,
+    progress::{Progress, ProgressSink},
+    remap::RemapTables,
+    rename::{Rename, apply_renames},
+    report::{Document, OptimizeReport, compare},
+    rescale::rescale_factor,
+    streaming::refresh_manifest,
+    texture_table::refresh_texture_table,
+    variants::{MaterialVariant, add_material_variants},
+};
+
+/// Optimization of a GLB with [`OptimizeOptions`] and any of the optional hooks
+///
+/// Hooks are set one by one and combine freely; without any, [`run`](Optimizer::run) does
+/// what [`optimize_with`](crate::optimize_with) does. An optimizer only borrows its options
+/// and hooks, so it can be kept and run on any number of files. The hook traits require
+/// `Send + Sync`, so an optimizer can also be shared between threads.
+///
+/// ```no_run
+/// # use std::io::Cursor;
+/// # use gltf_opt::{prelude::*, Optimizer};
+/// # fn run(glb: Vec<u8>) -> Result<(), OptimizeError> {
+/// let options = OptimizeOptions::default();
+/// let optimized = Optimizer::new(&options)
+///     .report(true)
+///     .external_textures("model_")
+///     .run(&mut Cursor::new(glb))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct Optimizer<'a> {
+    options: &'a OptimizeOptions,
+    encoder: Option<&'a dyn TextureEncoder>,
+    codec: Option<&'a dyn GeometryCodec>,
+    filter: Option<&'a dyn ExternalTextureFilter>,
+    metrics: Option<&'a dyn MetricsSink>,
+    progress: Option<&'a dyn ProgressSink>,
+    extensions: Option<&'a ExtensionRegistry>,
+    renamer: Option<&'a dyn Rename>,
+    edits: &'a [MaterialEdit],
+    variants: &'a [MaterialVariant],
+    anchors: &'a [Anchor],
+    schemas: Option<&'a ExtrasSchemas>,
+    report: bool,
+    external_textures: Option<&'a str>,
+    buffer_cap: Option<(u64, &'a str)>,
+}
+
+/// Output of [`Optimizer::run`] and what its hooks collected along the way
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Optimized {
+    pub glb: Vec<u8>,
+    /// Where each input object ended up; combine with
+    /// [`OptimizeOptions::stable_indices`] for output that keeps input order, the tables then
+    /// showing the few objects that could not keep their index
+    pub remap: RemapTables,
+    /// How the size of each part of the file changed, with [`Optimizer::report`]
+    pub report: Option<OptimizeReport>,
+    /// Every fallback taken with [`OptimizeOptions::best_effort`] or
+    /// [`OptimizeOptions::texture_fallbacks`]
+    pub issues: Vec<BestEffortIssue>,
+    /// What [`OptimizeOptions::never_grow`] did about an output larger than its input
+    pub growth_fallback: Option<GrowthFallback>,
+    /// Names of [`Optimizer::material_edits`] that matched no material
+    pub unmatched_edits: Vec<String>,
+    /// `extras` of the input that break the [`Optimizer::extras_schemas`]
+    pub extras_violations: Vec<ExtrasViolation>,
+    /// Image files the GLB references, with [`Optimizer::external_textures`]
+    pub textures: Vec<ExternalTexture>,
+    /// Buffer files the GLB references, with [`Optimizer::buffer_cap`]
+    pub buffers: Vec<ExternalBuffer>,
+}
+
+/// What one rebuild produces besides the GLB, replaced wholesale when `never_grow` falls back
+#[derive(Default)]
+struct Rebuilt {
+    remap: RemapTables,
+    issues: Vec<BestEffortIssue>,
+    textures: Vec<ExternalTexture>,
+    buffers: Vec<ExternalBuffer>,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(options: &'a OptimizeOptions) -> Self {
+        Optimizer {
+            options,
+            encoder: None,
+            codec: None,
+            filter: None,
+            metrics: None,
+            progress: None,
+            extensions: None,
+            renamer: None,
+            edits: &[],
+            variants: &[],
+            anchors: &[],
+            schemas: None,
+            report: false,
+            external_textures: None,
+            buffer_cap: None,
+        }
+    }
+
+    /// Encode every processed texture with `encoder`
+    ///
+    /// `convert_to_ktx2` is ignored since the encoder picks the format.
+    pub fn texture_encoder(mut self, encoder: &'a dyn TextureEncoder) -> Self {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Write primitive geometry through `codec`
+    pub fn geometry_codec(mut self, codec: &'a dyn GeometryCodec) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    /// Run every texture the optimizer encodes through `filter`
+    ///
+    /// Textures are encoded one at a time, so the filter needs no synchronization. Failures
+    /// surface as [`OptimizeError::Callback`] with [`Stage::TextureFilter`](crate::Stage).
+    pub fn texture_filter(mut self, filter: &'a dyn ExternalTextureFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Report counters and timings to `sink`
+    ///
+    /// Nothing is reported for a failed optimization except the textures encoded before the
+    /// error.
+    pub fn metrics(mut self, sink: &'a dyn MetricsSink) -> Self {
+        self.metrics = Some(sink);
+        self
+    }
+
+    /// Report each texture and mesh to `sink`, stopping with [`OptimizeError::Cancelled`]
+    /// when it asks to
+    ///
+    /// Cancellation is checked between work items, so a texture encode already running
+    /// finishes first.
+    pub fn progress(mut self, sink: &'a dyn ProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// Pass vendor extension payloads through the registered handlers
+    ///
+    /// [`OptimizeOptions::prune_unused`] is turned off, since payloads refer to source indices.
+    pub fn extensions(mut self, registry: &'a ExtensionRegistry) -> Self {
+        self.extensions = Some(registry);
+        self
+    }
+
+    /// Rename nodes, meshes and materials before optimizing
+    ///
+    /// Names are changed on the input, so name lookups, material edits and variants see the
+    /// new names.
+    pub fn renames(mut self, renamer: &'a dyn Rename) -> Self {
+        self.renamer = Some(renamer);
+        self
+    }
+
+    /// Set material factors by material name before optimizing
+    ///
+    /// Names that match no material are listed in [`Optimized::unmatched_edits`].
+    pub fn material_edits(mut self, edits: &'a [MaterialEdit]) -> Self {
+        self.edits = edits;
+        self
+    }
+
+    /// Add material variants as `KHR_materials_variants`
+    ///
+    /// Each variant gets a copy of every material it overrides, with the override's factors
+    /// and maps; the maps go through the same resizing and encoding as the source textures.
+    /// Every primitive drawn with an overridden material maps it to its copies, and primitives
+    /// a variant leaves alone keep their material under it. Variants already in the file are
+    /// kept, the new ones listed after them.
+    pub fn material_variants(mut self, variants: &'a [MaterialVariant]) -> Self {
+        self.variants = variants;
+        self
+    }
+
+    /// Add empty nodes at positions given in the source model's coordinates
+    ///
+    /// Anchors move with the geometry when [`OptimizeOptions::center_pivot`] shifts it or
+    /// [`OptimizeOptions::rescale`] scales it, so they keep marking the same spot on the model.
+    pub fn anchors(mut self, anchors: &'a [Anchor]) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Check `extras` against `schemas`, listing the violations in
+    /// [`Optimized::extras_violations`]
+    ///
+    /// Validation runs on the input, so indices refer to the source document. Extras are
+    /// carried over unchanged whether or not they match.
+    pub fn extras_schemas(mut self, schemas: &'a ExtrasSchemas) -> Self {
+        self.schemas = Some(schemas);
+        self
+    }
+
+    /// Compare the output with the input in [`Optimized::report`]
+    pub fn report(mut self, report: bool) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// Keep geometry embedded but write textures as separate files
+    ///
+    /// The mesh can be shown as soon as the GLB arrives while textures stream in over HTTP.
+    /// Image URIs are `{uri_prefix}tex{image index}.{ktx2|jpg|png}`, or
+    /// `{uri_prefix}tex{image index}.{hash}.{ext}` with [`OptimizeOptions::content_hash_uris`];
+    /// write each of [`Optimized::textures`] under its URI next to the GLB.
+    pub fn external_textures(mut self, uri_prefix: &'a str) -> Self {
+        self.external_textures = Some(uri_prefix);
+        self
+    }
+
+    /// Keep each buffer at or below `max_buffer_bytes`
+    ///
+    /// For runtimes and CDNs that limit the size of a single file or allocation. The first
+    /// buffer stays the GLB's binary chunk; the rest are `{uri_prefix}buffer{n}.bin`, or
+    /// `{uri_prefix}buffer{n}.{hash}.bin` with [`OptimizeOptions::content_hash_uris`], to be
+    /// written next to the GLB from [`Optimized::buffers`]. A single view larger than the cap
+    /// can't be split and gets a buffer of its own. [`OptimizeOptions::streaming_layout`] is
+    /// turned off, since its manifest describes one binary chunk.
+    pub fn buffer_cap(mut self, max_buffer_bytes: u64, uri_prefix: &'a str) -> Self {
+        self.buffer_cap = Some((max_buffer_bytes, uri_prefix));
+        self
+    }
+
+    /// Optimize the GLB `reader` holds
+    ///
+    /// Calls share no state and may run concurrently on different threads; see
+    /// [`OptimizeOptions::encoder_threads`] for the threads each one starts.
+    pub fn run<R: Read + Seek>(&self, reader: &mut R) -> Result<Optimized, OptimizeError> {
+        let start = Instant::now();
+        let progress = self.progress.map(Progress::new);
+        if let Some(progress) = &progress {
+            progress.check()?;
+        }
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+        // Read without validation, which rejects legacy extensions converted on the way in
+        let (s_json, s_blob) = read_glb(&mut Cursor::new(&input))?;
+        let extras_violations = self
+            .schemas
+            .map(|schemas| schemas.validate(&s_json))
+            .unwrap_or_default();
+
+        let (edited, unmatched_edits) = match (self.renamer, self.edits) {
+            (None, []) => (None, Vec::new()),
+            (renamer, edits) => {
+                let mut o_json = s_json.clone();
+                if let Some(renamer) = renamer {
+                    apply_renames(&mut o_json, renamer);
+                }
+                let unmatched = apply_material_edits(&mut o_json, edits)?;
+                (Some(o_json), unmatched)
+            }
+        };
+        let o_json = edited.as_ref().unwrap_or(&s_json);
+        let with_variants = match self.variants {
+            [] => None,
+            variants => Some(add_material_variants(&s_blob, o_json, variants)?),
+        };
+        let (o_json, o_blob) = match &with_variants {
+            Some((json, blob)) => (json, blob.as_slice()),
+            None => (o_json, s_blob.as_slice()),
+        };
+        let options = OptimizeOptions {
+            prune_unused: self.options.prune_unused && self.extensions.is_none(),
+            streaming_layout: self.options.streaming_layout && self.buffer_cap.is_none(),
+            ..self.options.clone()
+        };
+
+        let optimize = |options: &OptimizeOptions| {
+            let mut issues = Vec::new();
+            let (mut n_json, mut n_blob, remap) = optimize_document_reporting(
+                o_blob,
+                o_json,
+                options,
+                self.encoder,
+                self.codec,
+                self.filter,
+                self.metrics,
+                progress.as_ref(),
+                None,
+                &mut issues,
+            )?;
+            if !self.anchors.is_empty() {
+                let offset = pivot_offset(o_blob, o_json, options.center_pivot).unwrap_or([0.0; 3]);
+                // The pivot is centered after rescaling, where the bottom center scales along
+                let scale = options
+                    .rescale
+                    .and_then(|rescale| rescale_factor(o_blob, o_json, rescale))
+                    .unwrap_or(1.0);
+                insert_anchors(&mut n_json, self.anchors, offset, scale);
+            }
+            let keep: Vec<&str> = self.codec.map(|c| c.extension()).into_iter().collect();
+            match self.extensions {
+                Some(registry) => {
+                    registry.apply(o_blob, o_json, &mut n_json, &mut n_blob, &keep)?
+                }
+                None => retain_extensions(&mut n_json, &keep)?,
+            }
+
+            let mut n_blob: Vec<u8> = n_blob.into();
+            let mut rebuilt = Rebuilt {
+                remap,
+                issues,
+                ..Default::default()
+            };
+            if let Some(uri_prefix) = self.external_textures {
+                (rebuilt.textures, n_blob) =
+                    extract_images(&mut n_json, &n_blob, uri_prefix, options.content_hash_uris);
+            }
+            if let Some((max_bytes, uri_prefix)) = self.buffer_cap {
+                (n_blob, rebuilt.buffers) = split_buffers(
+                    &mut n_json,
+                    &n_blob,
+                    max_bytes,
+                    uri_prefix,
+                    options.content_hash_uris,
+                );
+            }
+            if self.extensions.is_some()
+                || self.external_textures.is_some()
+                || self.buffer_cap.is_some()
+            {
+                refresh_manifest(&mut n_json);
+                refresh_texture_table(&mut n_json, &n_blob);
+            }
+            Ok((write_glb(&n_json, n_blob)?, rebuilt))
+        };
+        let unchanged = || Rebuilt {
+            remap: RemapTables {
+                textures: (0..o_json.textures.len()).map(Some).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (glb, rebuilt, growth_fallback) = never_grow(&input, &options, optimize, unchanged)?;
+
+        let report = if self.report {
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(&glb))?;
+            let before = Document {
+                glb: &input,
+                json: &s_json,
+                blob: &s_blob,
+            };
+            let after = Document {
+                glb: &glb,
+                json: &n_json,
+                blob: &n_blob,
+            };
+            Some(OptimizeReport {
+                growth_fallback,
+                ..compare(&before, &after, |t| rebuilt.remap.texture(t))
+            })
+        } else {
+            None
+        };
+        if let Some(sink) = self.metrics {
+            sink.increment(Counter::BytesIn, input.len() as u64);
+            sink.increment(Counter::BytesOut, glb.len() as u64);
+            sink.observe(Histogram::OptimizeSeconds, start.elapsed().as_secs_f64());
+        }
+
+        Ok(Optimized {
+            glb,
+            remap: rebuilt.remap,
+            report,
+            issues: rebuilt.issues,
+            growth_fallback,
+            unmatched_edits,
+            extras_violations,
+            textures: rebuilt.textures,
+            buffers: rebuilt.buffers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimizers_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Optimizer<'static>>();
+        assert_send_sync::<OptimizeOptions>();
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn hooks_combine_in_one_run() {
+        use crate::{opt::optimize_with, testing::SyntheticGlb};
+
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default();
+        let plain = Optimizer::new(&options)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        assert_eq!(
+            plain.glb,
+            optimize_with(&mut Cursor::new(&glb), &options).unwrap()
+        );
+        assert!(plain.report.is_none() && plain.textures.is_empty());
+
+        let anchors = [Anchor {
+            name: "hotspot".to_string(),
+            position: [0.0; 3],
+            extras: Default::default(),
+        }];
+        let out = Optimizer::new(&options)
+            .anchors(&anchors)
+            .external_textures("model_")
+            .report(true)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let uris: Vec<_> = out.textures.iter().map(|t| t.uri.as_str()).collect();
+        // The synthetic textures are identical and collapse into one image
+        assert_eq!(uris, ["model_tex0.jpg"]);
+        let (n_json, _) = read_glb(&mut Cursor::new(&out.glb)).unwrap();
+        assert!(n_json.images.iter().all(|i| i.uri.is_some()));
+        let anchor = n_json
+            .nodes
+            .iter()
+            .find(|n| n.name.as_deref() == Some("hotspot"));
+        assert!(anchor.is_some());
+        let report = out.report.unwrap();
+        assert_eq!(report.file.after, out.glb.len() as u64);
+    }
+}
@@ -0,0 +1,937 @@
+use crate::{
+    alpha::AlphaConversion,
+    animation::CubicResampling,
+    anonymize::TextureAnonymization,
+    atlas::AtlasOptions,
+    compat::TargetProfile,
+    density::TriangleTextureScaling,
+    dither::DitherOptions,
+    fallback::TextureFallbacks,
+    license::AssetLicense,
+    lod::LodOptions,
+    meshlet::MeshletOptions,
+    mips::MipRounding,
+    opt::{ImageFormat, TextureType},
+    presets::{self, PresetInfo},
+    reencode::ReencodeSkip,
+    reject::RejectLimits,
+    reorder::VertexReorder,
+    rescale::Rescale,
+    sharpen::Sharpen,
+    simplify::SimplifyOptions,
+    split::GridSplit,
+    transcode::{TextureCompressionProfiles, TranscodeTargets},
+    upscale::TextureUpscale,
+    viewer::ViewerHints,
+    views::AccessorViews,
+    watermark::Watermark,
+};
+
+/// Longest texture side per material slot
+///
+/// Unset slots take `texture_size`, or half of it for metallic/roughness and occlusion. Set
+/// sizes are still capped by `texture_size`, so multi-resolution tiers scale them down too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SlotTextureSizes {
+    pub base_color: Option<u32>,
+    pub normal: Option<u32>,
+    pub metallic_roughness: Option<u32>,
+    pub occlusion: Option<u32>,
+    pub emissive: Option<u32>,
+}
+
+impl SlotTextureSizes {
+    /// Longest side for textures of a slot at a given `texture_size`
+    pub fn size(&self, texture_type: TextureType, texture_size: u32) -> u32 {
+        let size = match texture_type {
+            TextureType::BaseColor => self.base_color,
+            TextureType::Normal => self.normal,
+            TextureType::MetallicRoughness => self.metallic_roughness,
+            TextureType::Occlusion => self.occlusion,
+            TextureType::Emissive => self.emissive,
+        };
+        size.map_or(texture_type.default_size(texture_size), |size| {
+            size.min(texture_size)
+        })
+    }
+}
+
+/// Settings for [`optimize_with`](crate::optimize_with)
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct OptimizeOptions {
+    /// Cap on the longer side of textures; metallic/roughness maps use half of it
+    ///
+    /// Textures keep their aspect ratio; panoramas are capped by their height instead.
+    pub texture_size: u32,
+    /// Per-slot caps replacing the default share of `texture_size`
+    pub slot_texture_sizes: SlotTextureSizes,
+    /// Round each texture side to the nearest power of two within the cap, for runtimes that
+    /// need power-of-two textures for mipmaps or wrapping (WebGL 1)
+    pub power_of_two_textures: bool,
+    /// Drop normal textures from every material
+    pub remove_normal_texture: bool,
+    /// Re-encode textures as KTX2 with Basis Universal compression
+    pub convert_to_ktx2: bool,
+    /// Store the full mip chain in KTX2 textures, each level filtered from the one above
+    ///
+    /// Without it KTX2 files hold the base level only and runtimes either shimmer or build
+    /// mips themselves, which they can't do for Basis textures without a round trip.
+    pub ktx2_mipmaps: bool,
+    /// Round the base level of mipmapped KTX2 textures first, for drivers that mishandle
+    /// chains with odd sides; [`MipRounding::Keep`] by default
+    pub ktx2_mip_rounding: MipRounding,
+    /// In [`optimize_variants`](crate::optimize_variants), cut the KTX2 textures of
+    /// smaller tiers from the mip chains already encoded for larger ones
+    ///
+    /// Needs `ktx2_mipmaps`. Saves most of the encode time of every tier after the largest;
+    /// the reused levels are filtered from the larger tier's base rather than resized from the
+    /// source, so they differ slightly from a separate encode.
+    pub share_variant_mips: bool,
+    /// Basis Universal mode, quality and supercompression of KTX2 output per material slot
+    pub compression_profiles: TextureCompressionProfiles,
+    /// Encodings each slot's textures are retried with when the built-in encoder fails on
+    /// them, e.g. UASTC, then ETC1S, then PNG
+    ///
+    /// Works with or without `best_effort`, which only steps in once a whole chain failed.
+    /// [`optimize_variants`](crate::optimize_variants) does not retry.
+    pub texture_fallbacks: TextureFallbacks,
+    /// Move the pivot to the bottom center (ignored for skinned/animated models)
+    ///
+    /// The bottom center is taken from the default scene in world space. Positions are offset
+    /// when nodes only translate meshes; otherwise the scene's root nodes are moved instead.
+    pub center_pivot: bool,
+    /// Scale the default scene uniformly, e.g. to meters or to fit a target size
+    ///
+    /// A new root node carries the scale and the scene's root nodes become its children, so
+    /// vertex data, animations and skins are untouched. The pivot is centered after scaling.
+    pub rescale: Option<Rescale>,
+    /// Cap on decoded texture memory in bytes, full mip chains included
+    ///
+    /// Textures are shrunk further than `texture_size` until the total fits, starting with the
+    /// ones that cost the most per use in the default scene.
+    pub gpu_texture_budget: Option<u64>,
+    /// Give materials drawing few of the scene's triangles smaller textures
+    ///
+    /// Small decorative meshes rarely show their textures at full size, so the bytes go to
+    /// the materials covering most of the model instead. Applied before `gpu_texture_budget`.
+    pub triangle_texture_scaling: Option<TriangleTextureScaling>,
+    /// Viewer the output must load in; outputs it cannot read are turned off
+    ///
+    /// Use [`check_compatibility`](crate::check_compatibility) on the result to see
+    /// extensions carried over from the input that still need attention.
+    pub target_profile: Option<TargetProfile>,
+    /// Keep materials and textures in input order
+    ///
+    /// Material indices always match the input. Textures keep their index unless an earlier
+    /// one is dropped (unused, or only a removed normal map); a texture used in slots needing
+    /// different encodings gets extra copies appended at the end.
+    /// [`Optimized::remap`](crate::Optimized::remap) reports where each one went.
+    pub stable_indices: bool,
+    /// Leave out nodes no scene reaches, along with the meshes, skins, cameras, materials,
+    /// samplers and animation channels only they use (on by default)
+    ///
+    /// Documents without scenes keep all their nodes. With `stable_indices` every material is
+    /// kept.
+    pub prune_unused: bool,
+    /// GPU formats KTX2 output is meant to be transcoded to, recorded in each file's metadata
+    pub transcode_targets: TranscodeTargets,
+    /// Extra key/value entries (asset IDs, license strings) written to every KTX2 texture
+    ///
+    /// Added after the optimizer's own `Tool`, `Dimensions`, `CompressionMode` and
+    /// `TranscodeTargets` entries. Those keys and the `KTX` prefix the format reserves are
+    /// rejected.
+    pub ktx2_metadata: Vec<(String, String)>,
+    /// Copyright, license and attribution written to the output's `asset`
+    ///
+    /// The source's `asset.copyright` and `asset.extras` are carried over either way.
+    pub asset_license: AssetLicense,
+    /// Viewer settings (start camera, exposure, environment, background) written to every
+    /// scene's `extras.viewer`, for in-house viewers driven by the file
+    pub viewer_hints: ViewerHints,
+    /// Format of every processed texture, replacing JPEG with PNG normal maps
+    ///
+    /// `Ktx2` works as `convert_to_ktx2`, which wins over the other formats when set. WebP is
+    /// lossless and marks `EXT_texture_webp` required; it needs the `webp` feature.
+    pub image_format: Option<ImageFormat>,
+    /// Cap on `EXT_lights_image_based` cube map faces; `texture_size` when unset
+    pub environment_texture_size: Option<u32>,
+    /// Quick preview: only materials of the first N meshes keep (and encode) their textures
+    ///
+    /// Other meshes keep their geometry but get texture-free copies of their materials,
+    /// so a low-cost preview can be shown while the full optimization runs.
+    pub preview_subset: Option<usize>,
+    /// Pick JPEG/Basis quality per texture from its detail, shifted by this bias
+    ///
+    /// Busy textures (high edge energy / entropy) get more quality and smooth gradients less.
+    /// The bias is in JPEG quality points (Basis levels move 2.5x as far); `Some(0)` is neutral
+    /// and `None` keeps the fixed defaults.
+    pub auto_quality: Option<i8>,
+    /// Enlarge textures smaller than a minimum size instead of passing them through
+    ///
+    /// Applies to custom encoders too, which then receive the enlarged pixels; an external
+    /// super-resolution step can run there.
+    pub upscale_textures: Option<TextureUpscale>,
+    /// Copy source images matching these rules as they are, skipping resizing and re-encoding
+    ///
+    /// Saves the quality loss and CPU of re-encoding inputs that are already small or well
+    /// compressed. Kept images keep their format and size, and skip alpha conversion,
+    /// dithering and upscaling. [`optimize_variants`](crate::optimize_variants)
+    /// checks the size rule against `texture_size`. Nothing is skipped by default.
+    pub skip_reencode: ReencodeSkip,
+    /// Keep sprite sheets and UI textures at their source size, found by name and by a grid
+    /// of sprites separated by transparent gutters
+    ///
+    /// Images with a `no_resize: true` entry in their `extras` are kept at their source size
+    /// either way.
+    pub detect_sprite_sheets: bool,
+    /// Encode textures whose pixels look like another slot's with that slot's settings
+    ///
+    /// Normal maps in base color or emissive slots are encoded like normal maps (linear, PNG or
+    /// UASTC), and color images in normal slots like base color. Textures stay in their slots;
+    /// [`scene_warnings`](crate::scene_warnings) reports them either way.
+    pub fix_misslotted_textures: bool,
+    /// Infer each slotted texture's class from its name and pixels, and encode textures whose
+    /// class differs from their slot with that class's settings
+    ///
+    /// Names ending in words such as `_n`, `_normal`, `_orm`, `_ao`, `_e` or `_bc` (on the
+    /// texture, its image or the image URI) decide first; unnamed textures fall back to the
+    /// normal map check of `fix_misslotted_textures`. Textures stay in their slots, and classes
+    /// that encode alike (occlusion and metallic/roughness, base color and emissive) are left be.
+    pub infer_texture_classes: bool,
+    /// Dithering applied per texture class before the built-in encoders, against banding
+    pub dither: DitherOptions,
+    /// Unsharp mask for base color and emissive textures the built-in encoders downscale
+    pub sharpen: Option<Sharpen>,
+    /// Downscale base color and emissive textures in linear light rather than on sRGB values,
+    /// which darkens them; on by default
+    pub linear_light_resize: bool,
+    /// Convert base color textures to or from premultiplied alpha while re-encoding
+    pub alpha_conversion: Option<AlphaConversion>,
+    /// Stamp an image onto base color textures while re-encoding, for protected previews
+    ///
+    /// Only the built-in encoders stamp; base color textures are then always re-encoded, so
+    /// `skip_reencode` keeps none of them. Verbatim base color slots stay unmarked.
+    pub watermark: Option<Watermark>,
+    /// Replace base color art with a checkerboard or a blurred version while re-encoding,
+    /// for sharing work-in-progress models; geometry is untouched
+    ///
+    /// Applied before `watermark`, with the same limits: only the built-in encoders do it,
+    /// and verbatim base color slots keep their art.
+    pub anonymize_textures: Option<TextureAnonymization>,
+    /// Crop textures to the UV sub-rect their primitives use, remapping those UVs
+    ///
+    /// Pays off for assets that reference a shared atlas but sample a single tile.
+    pub crop_textures: bool,
+    /// Pack the textures of materials alike but for their small textures into shared atlases,
+    /// remapping UVs and drawing them with one material per atlas
+    ///
+    /// The atlases are plain PNG images encoded like any other texture afterwards, so keep
+    /// `texture_size` at or above `max_atlas_size` to keep their resolution.
+    pub atlas_textures: Option<AtlasOptions>,
+    /// Fill texels outside the UV charts with the colors at the chart borders, this many texels
+    /// deep at output size, so shrinking, mips and block compression don't bleed the gutter's
+    /// background into the charts as seams
+    ///
+    /// Runs before atlasing; images it changes are stored as PNG before re-encoding.
+    pub gutter_padding: Option<u32>,
+    /// Clamp UVs that overshoot `[0, 1]` by up to 2% and give their textures clamp-to-edge
+    /// samplers, so exporter rounding does not turn into tiling seams
+    pub normalize_uvs: bool,
+    /// Resize and re-encode base color textures; when off they are copied unchanged
+    pub process_base_color: bool,
+    /// Resize and re-encode normal maps (see also `remove_normal_texture`)
+    pub process_normals: bool,
+    /// Resize and re-encode metallic/roughness textures
+    pub process_metallic_roughness: bool,
+    /// Resize and re-encode occlusion textures
+    pub process_occlusion: bool,
+    /// Resize and re-encode emissive textures
+    pub process_emissive: bool,
+    /// Store float NORMAL, TANGENT and TEXCOORD_n attributes at 16 bits, halving their size
+    ///
+    /// glTF has no half-float component type, so values are written as normalized 16-bit
+    /// integers under `KHR_mesh_quantization`; UV sets outside `[0, 1]` stay float. Turned off
+    /// when `target_profile` cannot load the extension.
+    pub half_precision_attributes: bool,
+    /// Quantize float vertex attributes under `KHR_mesh_quantization`
+    ///
+    /// POSITION becomes normalized 16-bit with a child node undoing the quantization, NORMAL
+    /// and TANGENT normalized 8-bit, TEXCOORD_n within `[0, 1]` normalized 16-bit. Positions
+    /// stay float for skinned and morphed meshes and when `meshlets` is set. Turned off when
+    /// `target_profile` cannot load the extension.
+    pub quantize_attributes: bool,
+    /// Compress vertex and index buffer views with `EXT_meshopt_compression`
+    ///
+    /// Vertices are put in post-transform cache order first, as with
+    /// [`VertexReorder::Cache`](crate::VertexReorder::Cache), unless
+    /// `reorder_vertices` picks an order. Turned off when `target_profile` cannot load the
+    /// extension.
+    pub meshopt_compression: bool,
+    /// Store morph target deltas as sparse accessors, dropping those within this distance of
+    /// zero
+    ///
+    /// `Some(0.0)` only drops exact zeros. Targets where most vertices move stay dense.
+    pub sparse_morph_targets: Option<f32>,
+    /// Keep at most this many morph targets per mesh, the ones moving vertices the most
+    ///
+    /// Dropped targets are baked into the base geometry at the mesh's default `weights`, and
+    /// node weights and weight animations are trimmed to match.
+    pub max_morph_targets: Option<usize>,
+    /// Bake the mesh's default `weights` into the base geometry and strip all morph targets
+    pub bake_morph_targets: bool,
+    /// Store rotation and morph weight animation outputs as normalized 8/16-bit integers,
+    /// picking the smallest type that stays within this error per component
+    ///
+    /// Translation and scale outputs must be float in glTF and are left alone.
+    pub quantize_animations: Option<f32>,
+    /// Fix sampler keys that repeat or step back by up to half the typical key interval
+    ///
+    /// [`validate_animation_timing`](crate::validate_animation_timing) lists what
+    /// gets repaired and what is left alone.
+    pub repair_animation_timing: bool,
+    /// Drop weights of out-of-range joints and renormalize skin weights that don't add up to 1
+    ///
+    /// [`validate_skinning`](crate::validate_skinning) lists what gets repaired.
+    pub repair_skin_weights: bool,
+    /// Resample `CUBICSPLINE` animation samplers to `LINEAR`, dropping the stored tangents
+    pub resample_cubic_animations: Option<CubicResampling>,
+    /// Bake scale/offset `KHR_texture_transform`s into the UVs and drop the extension
+    pub bake_texture_transforms: bool,
+    /// Put a hash of each external file's contents in its URI, e.g. `model_tex0.1a2b3c4d.jpg`
+    pub content_hash_uris: bool,
+    /// Cut triangle counts by collapsing vertices where the surface barely moves
+    pub simplify: Option<SimplifyOptions>,
+    /// Add simplified levels of detail to every drawn mesh, linked with `MSFT_lod`
+    pub lods: Option<LodOptions>,
+    /// Merge primitives of a mesh that share a material, attributes and mode into one draw
+    pub merge_primitives: bool,
+    /// Point nodes drawing identical copies of a mesh at its first copy
+    ///
+    /// Meshes match on accessor contents, modes, materials and extensions, not names. The
+    /// other copies are dropped by `prune_unused`.
+    pub dedupe_meshes: bool,
+    /// Draw every primitive with one neutral grey material and strip all textures, UV sets,
+    /// tangents and vertex colors, for silhouette, preview and occluder proxies
+    ///
+    /// Combine with `simplify` and `merge_primitives` for the smallest proxies.
+    pub collapse_materials: bool,
+    /// Split triangle primitives above a size into a grid of primitives that can be culled
+    pub split_primitives: Option<GridSplit>,
+    /// Experimental: reorder indices into meshlets and store their ranges and bounds
+    pub meshlets: Option<MeshletOptions>,
+    /// Reorder vertices (and triangles) for better compression ratios and GPU locality
+    pub reorder_vertices: VertexReorder,
+    /// Limits on triangles, textures and input size checked before anything is processed
+    ///
+    /// An input over a limit fails right away with
+    /// [`OptimizeError::Rejected`](crate::OptimizeError::Rejected), instead of
+    /// after minutes of texture encoding for an asset that would be turned down anyway.
+    pub reject_if: RejectLimits,
+    /// Copy materials and primitives that fail to optimize instead of failing the whole file
+    ///
+    /// [`Optimized::issues`](crate::Optimized::issues) lists what fell back.
+    pub best_effort: bool,
+    /// Never return a file larger than the input, e.g. when already optimized JPEGs grow as
+    /// KTX2
+    ///
+    /// A larger output is optimized again with every texture copied as it is, and when that
+    /// is still larger the input comes back unchanged.
+    /// [`Optimized::growth_fallback`](crate::Optimized::growth_fallback) says which happened.
+    pub never_grow: bool,
+    /// Threads the Basis Universal encodes of one optimization may use, one per CPU when
+    /// `None` (one with `low_priority`)
+    ///
+    /// Textures are themselves encoded one per CPU at a time, and the encodes running at once
+    /// split these threads, each keeping at least one. Lower it when running several
+    /// optimizations at once, which would otherwise each start a thread per CPU.
+    pub encoder_threads: Option<u32>,
+    /// Leave half the CPUs to the rest of the machine, e.g. the UI of a desktop app optimizing
+    /// in the background
+    ///
+    /// Textures are encoded and accessors copied on half the CPUs, and Basis Universal encodes
+    /// use one thread each unless `encoder_threads` says otherwise. Thread priorities are not
+    /// changed; worker threads inherit the calling thread's, so lower that one for more.
+    pub low_priority: bool,
+    /// Let the copied accessors of each primitive or mesh share buffer views
+    ///
+    /// Cuts the number of views, and the JSON describing them, on scenes with many primitives.
+    /// Index data and each vertex stride keep views of their own.
+    pub accessor_views: AccessorViews,
+    /// Experimental: write geometry first and textures last in the binary chunk, listing where
+    /// each part ends in a `GLTFOPT_streaming` extension so drawing can start early
+    pub streaming_layout: bool,
+    /// Write a `GLTFOPT_texture_table` root extension listing every texture's id, image,
+    /// sampler, color space, format, dimensions, mip levels and byte range, so GPU-driven
+    /// renderers can fill bindless descriptor arrays without walking materials
+    pub texture_table: bool,
+}
+
+impl OptimizeOptions {
+    /// Whether textures in a slot are resized and re-encoded rather than copied unchanged
+    pub fn processes(&self, texture_type: TextureType) -> bool {
+        match texture_type {
+            TextureType::BaseColor => self.process_base_color,
+            TextureType::Normal => self.process_normals,
+            TextureType::MetallicRoughness => self.process_metallic_roughness,
+            TextureType::Occlusion => self.process_occlusion,
+            TextureType::Emissive => self.process_emissive,
+        }
+    }
+
+    /// Whether the built-in encoders write KTX2, set by either KTX2 option
+    pub(crate) fn writes_ktx2(&self) -> bool {
+        self.convert_to_ktx2 || self.image_format == Some(ImageFormat::Ktx2)
+    }
+
+    /// Threads encoding textures or copying accessors at once, one per CPU unless
+    /// `low_priority` leaves half of them free
+    pub(crate) fn worker_threads(&self) -> usize {
+        let cpus = num_cpus::get().max(1);
+        if self.low_priority {
+            (cpus / 2).max(1)
+        } else {
+            cpus
+        }
+    }
+
+    /// Threads each Basis Universal encode uses
+    pub(crate) fn basis_threads(&self) -> u32 {
+        let default = if self.low_priority {
+            1
+        } else {
+            num_cpus::get() as u32
+        };
+        self.encoder_threads.unwrap_or(default).max(1)
+    }
+}
+
+impl OptimizeOptions {
+    /// Texture size and format, attribute quantization and meshopt compression the target
+    /// viewer is known to load
+    ///
+    /// Presets come from the bundled profile matrix and set `target_profile`, so later edits
+    /// that the target cannot load (such as turning KTX2 on for Quick Look) are undone.
+    pub fn for_target(profile: TargetProfile) -> Self {
+        profile.preset()
+    }
+
+    /// Named presets with descriptions and their concrete settings, for frontends filling a
+    /// dropdown: the defaults, `mobile`, `preview` and one per [`TargetProfile`], keyed by
+    /// [`TargetProfile::key`]
+    pub fn presets() -> &'static [PresetInfo] {
+        presets::presets()
+    }
+}
+
+/// Chainable setters, one per field and named after it, except [`ktx2`](Self::ktx2) for
+/// `convert_to_ktx2`
+///
+/// Options are `#[non_exhaustive]`, so outside this crate they are built from
+/// [`Default`] (or a preset) and then either chained through these setters or assigned
+/// field by field; the two are equivalent.
+impl OptimizeOptions {
+    pub fn texture_size(mut self, size: u32) -> Self {
+        self.texture_size = size;
+        self
+    }
+
+    pub fn remove_normal_texture(mut self, remove: bool) -> Self {
+        self.remove_normal_texture = remove;
+        self
+    }
+
+    /// Sets `convert_to_ktx2`
+    pub fn ktx2(mut self, convert: bool) -> Self {
+        self.convert_to_ktx2 = convert;
+        self
+    }
+
+    pub fn center_pivot(mut self, center: bool) -> Self {
+        self.center_pivot = center;
+        self
+    }
+
+    pub fn meshopt_compression(mut self, compress: bool) -> Self {
+        self.meshopt_compression = compress;
+        self
+    }
+
+    pub fn slot_texture_sizes(mut self, slot_texture_sizes: SlotTextureSizes) -> Self {
+        self.slot_texture_sizes = slot_texture_sizes;
+        self
+    }
+
+    pub fn power_of_two_textures(mut self, power_of_two_textures: bool) -> Self {
+        self.power_of_two_textures = power_of_two_textures;
+        self
+    }
+
+    pub fn ktx2_mipmaps(mut self, ktx2_mipmaps: bool) -> Self {
+        self.ktx2_mipmaps = ktx2_mipmaps;
+        self
+    }
+
+    pub fn ktx2_mip_rounding(mut self, ktx2_mip_rounding: MipRounding) -> Self {
+        self.ktx2_mip_rounding = ktx2_mip_rounding;
+        self
+    }
+
+    pub fn share_variant_mips(mut self, share_variant_mips: bool) -> Self {
+        self.share_variant_mips = share_variant_mips;
+        self
+    }
+
+    pub fn compression_profiles(
+        mut self,
+        compression_profiles: TextureCompressionProfiles,
+    ) -> Self {
+        self.compression_profiles = compression_profiles;
+        self
+    }
+
+    pub fn texture_fallbacks(mut self, texture_fallbacks: TextureFallbacks) -> Self {
+        self.texture_fallbacks = texture_fallbacks;
+        self
+    }
+
+    pub fn rescale(mut self, rescale: Option<Rescale>) -> Self {
+        self.rescale = rescale;
+        self
+    }
+
+    pub fn gpu_texture_budget(mut self, gpu_texture_budget: Option<u64>) -> Self {
+        self.gpu_texture_budget = gpu_texture_budget;
+        self
+    }
+
+    pub fn triangle_texture_scaling(
+        mut self,
+        triangle_texture_scaling: Option<TriangleTextureScaling>,
+    ) -> Self {
+        self.triangle_texture_scaling = triangle_texture_scaling;
+        self
+    }
+
+    pub fn target_profile(mut self, target_profile: Option<TargetProfile>) -> Self {
+        self.target_profile = target_profile;
+        self
+    }
+
+    pub fn stable_indices(mut self, stable_indices: bool) -> Self {
+        self.stable_indices = stable_indices;
+        self
+    }
+
+    pub fn prune_unused(mut self, prune_unused: bool) -> Self {
+        self.prune_unused = prune_unused;
+        self
+    }
+
+    pub fn transcode_targets(mut self, transcode_targets: TranscodeTargets) -> Self {
+        self.transcode_targets = transcode_targets;
+        self
+    }
+
+    pub fn ktx2_metadata(mut self, ktx2_metadata: Vec<(String, String)>) -> Self {
+        self.ktx2_metadata = ktx2_metadata;
+        self
+    }
+
+    pub fn asset_license(mut self, asset_license: AssetLicense) -> Self {
+        self.asset_license = asset_license;
+        self
+    }
+
+    pub fn viewer_hints(mut self, viewer_hints: ViewerHints) -> Self {
+        self.viewer_hints = viewer_hints;
+        self
+    }
+
+    pub fn image_format(mut self, image_format: Option<ImageFormat>) -> Self {
+        self.image_format = image_format;
+        self
+    }
+
+    pub fn environment_texture_size(mut self, environment_texture_size: Option<u32>) -> Self {
+        self.environment_texture_size = environment_texture_size;
+        self
+    }
+
+    pub fn preview_subset(mut self, preview_subset: Option<usize>) -> Self {
+        self.preview_subset = preview_subset;
+        self
+    }
+
+    pub fn auto_quality(mut self, auto_quality: Option<i8>) -> Self {
+        self.auto_quality = auto_quality;
+        self
+    }
+
+    pub fn upscale_textures(mut self, upscale_textures: Option<TextureUpscale>) -> Self {
+        self.upscale_textures = upscale_textures;
+        self
+    }
+
+    pub fn skip_reencode(mut self, skip_reencode: ReencodeSkip) -> Self {
+        self.skip_reencode = skip_reencode;
+        self
+    }
+
+    pub fn detect_sprite_sheets(mut self, detect_sprite_sheets: bool) -> Self {
+        self.detect_sprite_sheets = detect_sprite_sheets;
+        self
+    }
+
+    pub fn fix_misslotted_textures(mut self, fix_misslotted_textures: bool) -> Self {
+        self.fix_misslotted_textures = fix_misslotted_textures;
+        self
+    }
+
+    pub fn infer_texture_classes(mut self, infer_texture_classes: bool) -> Self {
+        self.infer_texture_classes = infer_texture_classes;
+        self
+    }
+
+    pub fn dither(mut self, dither: DitherOptions) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn sharpen(mut self, sharpen: Option<Sharpen>) -> Self {
+        self.sharpen = sharpen;
+        self
+    }
+
+    pub fn linear_light_resize(mut self, linear_light_resize: bool) -> Self {
+        self.linear_light_resize = linear_light_resize;
+        self
+    }
+
+    pub fn alpha_conversion(mut self, alpha_conversion: Option<AlphaConversion>) -> Self {
+        self.alpha_conversion = alpha_conversion;
+        self
+    }
+
+    pub fn watermark(mut self, watermark: Option<Watermark>) -> Self {
+        self.watermark = watermark;
+        self
+    }
+
+    pub fn anonymize_textures(mut self, anonymize_textures: Option<TextureAnonymization>) -> Self {
+        self.anonymize_textures = anonymize_textures;
+        self
+    }
+
+    pub fn crop_textures(mut self, crop_textures: bool) -> Self {
+        self.crop_textures = crop_textures;
+        self
+    }
+
+    pub fn atlas_textures(mut self, atlas_textures: Option<AtlasOptions>) -> Self {
+        self.atlas_textures = atlas_textures;
+        self
+    }
+
+    pub fn gutter_padding(mut self, gutter_padding: Option<u32>) -> Self {
+        self.gutter_padding = gutter_padding;
+        self
+    }
+
+    pub fn normalize_uvs(mut self, normalize_uvs: bool) -> Self {
+        self.normalize_uvs = normalize_uvs;
+        self
+    }
+
+    pub fn process_base_color(mut self, process_base_color: bool) -> Self {
+        self.process_base_color = process_base_color;
+        self
+    }
+
+    pub fn process_normals(mut self, process_normals: bool) -> Self {
+        self.process_normals = process_normals;
+        self
+    }
+
+    pub fn process_metallic_roughness(mut self, process_metallic_roughness: bool) -> Self {
+        self.process_metallic_roughness = process_metallic_roughness;
+        self
+    }
+
+    pub fn process_occlusion(mut self, process_occlusion: bool) -> Self {
+        self.process_occlusion = process_occlusion;
+        self
+    }
+
+    pub fn process_emissive(mut self, process_emissive: bool) -> Self {
+        self.process_emissive = process_emissive;
+        self
+    }
+
+    pub fn half_precision_attributes(mut self, half_precision_attributes: bool) -> Self {
+        self.half_precision_attributes = half_precision_attributes;
+        self
+    }
+
+    pub fn quantize_attributes(mut self, quantize_attributes: bool) -> Self {
+        self.quantize_attributes = quantize_attributes;
+        self
+    }
+
+    pub fn sparse_morph_targets(mut self, sparse_morph_targets: Option<f32>) -> Self {
+        self.sparse_morph_targets = sparse_morph_targets;
+        self
+    }
+
+    pub fn max_morph_targets(mut self, max_morph_targets: Option<usize>) -> Self {
+        self.max_morph_targets = max_morph_targets;
+        self
+    }
+
+    pub fn bake_morph_targets(mut self, bake_morph_targets: bool) -> Self {
+        self.bake_morph_targets = bake_morph_targets;
+        self
+    }
+
+    pub fn quantize_animations(mut self, quantize_animations: Option<f32>) -> Self {
+        self.quantize_animations = quantize_animations;
+        self
+    }
+
+    pub fn repair_animation_timing(mut self, repair_animation_timing: bool) -> Self {
+        self.repair_animation_timing = repair_animation_timing;
+        self
+    }
+
+    pub fn repair_skin_weights(mut self, repair_skin_weights: bool) -> Self {
+        self.repair_skin_weights = repair_skin_weights;
+        self
+    }
+
+    pub fn resample_cubic_animations(
+        mut self,
+        resample_cubic_animations: Option<CubicResampling>,
+    ) -> Self {
+        self.resample_cubic_animations = resample_cubic_animations;
+        self
+    }
+
+    pub fn bake_texture_transforms(mut self, bake_texture_transforms: bool) -> Self {
+        self.bake_texture_transforms = bake_texture_transforms;
+        self
+    }
+
+    pub fn content_hash_uris(mut self, content_hash_uris: bool) -> Self {
+        self.content_hash_uris = content_hash_uris;
+        self
+    }
+
+    pub fn simplify(mut self, simplify: Option<SimplifyOptions>) -> Self {
+        self.simplify = simplify;
+        self
+    }
+
+    pub fn lods(mut self, lods: Option<LodOptions>) -> Self {
+        self.lods = lods;
+        self
+    }
+
+    pub fn merge_primitives(mut self, merge_primitives: bool) -> Self {
+        self.merge_primitives = merge_primitives;
+        self
+    }
+
+    pub fn dedupe_meshes(mut self, dedupe_meshes: bool) -> Self {
+        self.dedupe_meshes = dedupe_meshes;
+        self
+    }
+
+    pub fn collapse_materials(mut self, collapse_materials: bool) -> Self {
+        self.collapse_materials = collapse_materials;
+        self
+    }
+
+    pub fn split_primitives(mut self, split_primitives: Option<GridSplit>) -> Self {
+        self.split_primitives = split_primitives;
+        self
+    }
+
+    pub fn meshlets(mut self, meshlets: Option<MeshletOptions>) -> Self {
+        self.meshlets = meshlets;
+        self
+    }
+
+    pub fn reorder_vertices(mut self, reorder_vertices: VertexReorder) -> Self {
+        self.reorder_vertices = reorder_vertices;
+        self
+    }
+
+    pub fn reject_if(mut self, reject_if: RejectLimits) -> Self {
+        self.reject_if = reject_if;
+        self
+    }
+
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.best_effort = best_effort;
+        self
+    }
+
+    pub fn never_grow(mut self, never_grow: bool) -> Self {
+        self.never_grow = never_grow;
+        self
+    }
+
+    pub fn encoder_threads(mut self, encoder_threads: Option<u32>) -> Self {
+        self.encoder_threads = encoder_threads;
+        self
+    }
+
+    pub fn low_priority(mut self, low_priority: bool) -> Self {
+        self.low_priority = low_priority;
+        self
+    }
+
+    pub fn accessor_views(mut self, accessor_views: AccessorViews) -> Self {
+        self.accessor_views = accessor_views;
+        self
+    }
+
+    pub fn streaming_layout(mut self, streaming_layout: bool) -> Self {
+        self.streaming_layout = streaming_layout;
+        self
+    }
+
+    pub fn texture_table(mut self, texture_table: bool) -> Self {
+        self.texture_table = texture_table;
+        self
+    }
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            texture_size: 1024,
+            slot_texture_sizes: SlotTextureSizes::default(),
+            power_of_two_textures: false,
+            remove_normal_texture: false,
+            convert_to_ktx2: false,
+            ktx2_mipmaps: false,
+            ktx2_mip_rounding: MipRounding::Keep,
+            share_variant_mips: false,
+            compression_profiles: TextureCompressionProfiles::default(),
+            texture_fallbacks: TextureFallbacks::default(),
+            center_pivot: false,
+            rescale: None,
+            gpu_texture_budget: None,
+            triangle_texture_scaling: None,
+            target_profile: None,
+            stable_indices: false,
+            prune_unused: true,
+            transcode_targets: TranscodeTargets::default(),
+            ktx2_metadata: Vec::new(),
+            asset_license: AssetLicense::default(),
+            viewer_hints: ViewerHints::default(),
+            image_format: None,
+            environment_texture_size: None,
+            preview_subset: None,
+            auto_quality: None,
+            upscale_textures: None,
+            skip_reencode: ReencodeSkip::default(),
+            detect_sprite_sheets: false,
+            fix_misslotted_textures: false,
+            infer_texture_classes: false,
+            dither: DitherOptions::default(),
+            sharpen: None,
+            linear_light_resize: true,
+            alpha_conversion: None,
+            watermark: None,
+            anonymize_textures: None,
+            crop_textures: false,
+            atlas_textures: None,
+            gutter_padding: None,
+            normalize_uvs: false,
+            process_base_color: true,
+            process_normals: true,
+            process_metallic_roughness: true,
+            process_occlusion: true,
+            process_emissive: true,
+            half_precision_attributes: false,
+            quantize_attributes: false,
+            meshopt_compression: false,
+            sparse_morph_targets: None,
+            max_morph_targets: None,
+            bake_morph_targets: false,
+            quantize_animations: None,
+            repair_animation_timing: false,
+            repair_skin_weights: false,
+            resample_cubic_animations: None,
+            bake_texture_transforms: false,
+            content_hash_uris: false,
+            simplify: None,
+            lods: None,
+            merge_primitives: false,
+            dedupe_meshes: false,
+            collapse_materials: false,
+            split_primitives: None,
+            meshlets: None,
+            reorder_vertices: VertexReorder::Off,
+            reject_if: RejectLimits::default(),
+            best_effort: false,
+            never_grow: false,
+            encoder_threads: None,
+            low_priority: false,
+            accessor_views: AccessorViews::PerAccessor,
+            streaming_layout: false,
+            texture_table: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        opt::{optimize, optimize_with},
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn setters_match_the_positional_settings() {
+        let options = OptimizeOptions::default()
+            .texture_size(256)
+            .remove_normal_texture(true)
+            .ktx2(false)
+            .center_pivot(true);
+        assert_eq!(options.texture_size, 256);
+        assert!(options.remove_normal_texture);
+        assert!(!options.convert_to_ktx2);
+        assert!(options.center_pivot);
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        assert_eq!(
+            optimize(&mut Cursor::new(&glb), 256, true, false, true).unwrap(),
+            optimize_with(&mut Cursor::new(&glb), &options).unwrap()
+        );
+    }
+    #[test]
+    fn setters_match_the_fields() {
+        let assigned = OptimizeOptions {
+            power_of_two_textures: true,
+            gpu_texture_budget: Some(1 << 20),
+            ktx2_metadata: vec![("AssetId".into(), "chair".into())],
+            reorder_vertices: VertexReorder::default(),
+            texture_table: true,
+            ..Default::default()
+        };
+        let chained = OptimizeOptions::default()
+            .power_of_two_textures(true)
+            .gpu_texture_budget(Some(1 << 20))
+            .ktx2_metadata(vec![("AssetId".into(), "chair".into())])
+            .reorder_vertices(VertexReorder::default())
+            .texture_table(true);
+        assert_eq!(chained, assigned);
+    }
+}
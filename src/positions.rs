@@ -0,0 +1,234 @@
+use std::borrow::Cow;
+
+use gltf::json::{
+    Index, Root,
+    accessor::{ComponentType, GenericComponentType, Type},
+    validation::Checked,
+};
+
+use crate::{glb::accessor_stride, opt::get_position_data};
+
+/// Vertices folded per step, so the min/max compares compile to packed SIMD instructions
+const LANES: usize = 8;
+
+/// Min and max corner of a set of positions
+pub(crate) type Bounds = ([f32; 3], [f32; 3]);
+
+/// Reinterpret little-endian bytes as floats, borrowing them when they are aligned
+fn le_floats(bytes: &[u8]) -> Cow<'_, [f32]> {
+    #[cfg(target_endian = "little")]
+    if let Ok(floats) = bytemuck::try_cast_slice(bytes) {
+        return Cow::Borrowed(floats);
+    }
+    Cow::Owned(
+        bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect(),
+    )
+}
+
+/// Run `f` over little-endian float bytes, in place when they are aligned
+fn with_le_floats_mut<T>(bytes: &mut [u8], f: impl FnOnce(&mut [f32]) -> T) -> T {
+    #[cfg(target_endian = "little")]
+    if let Ok(floats) = bytemuck::try_cast_slice_mut(bytes) {
+        return f(floats);
+    }
+    let mut floats = le_floats(bytes).into_owned();
+    let result = f(&mut floats);
+    for (dst, v) in bytes.chunks_exact_mut(4).zip(&floats) {
+        dst.copy_from_slice(&v.to_le_bytes());
+    }
+    result
+}
+
+/// Byte range and float stride of a float VEC3 accessor within its buffer view
+///
+/// `None` for other types, sparse accessors, offsets past the end of the view, and offsets or
+/// strides that are not float aligned. The range is cut short at the end of `view_len`, like the element-wise readers do.
+pub(crate) fn f32_vec3_range(
+    o_json: &Root,
+    idx: Index<gltf::json::Accessor>,
+    view_len: usize,
+) -> Option<(std::ops::Range<usize>, usize)> {
+    let acc = o_json.accessors.get(idx.value())?;
+    if acc.sparse.is_some()
+        || !matches!(
+            (acc.component_type, acc.type_),
+            (
+                Checked::Valid(GenericComponentType(ComponentType::F32)),
+                Checked::Valid(Type::Vec3)
+            )
+        )
+    {
+        return None;
+    }
+    let stride = accessor_stride(o_json, idx)?;
+    let start = acc.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+    if !stride.is_multiple_of(4) || !start.is_multiple_of(4) || start > view_len {
+        return None;
+    }
+
+    let fits = view_len.saturating_sub(start).saturating_sub(12) / stride + 1;
+    let count = if view_len >= start + 12 {
+        (acc.count.0 as usize).min(fits)
+    } else {
+        0
+    };
+    let end = if count == 0 {
+        start
+    } else {
+        start + (count - 1) * stride + 12
+    };
+    Some((start..end, stride / 4))
+}
+
+/// Fold one position into running bounds; NaN components are skipped
+#[inline(always)]
+fn fold(bounds: &mut Bounds, p: &[f32]) {
+    for (c, &v) in p[..3].iter().enumerate() {
+        if v < bounds.0[c] {
+            bounds.0[c] = v;
+        }
+        if v > bounds.1[c] {
+            bounds.1[c] = v;
+        }
+    }
+}
+
+/// Bounds of positions `stride` floats apart, or `None` when there are none
+pub(crate) fn vec3_bounds(values: &[f32], stride: usize) -> Option<Bounds> {
+    if values.len() < 3 {
+        return None;
+    }
+    let mut bounds = ([f32::MAX; 3], [f32::MIN; 3]);
+
+    if stride != 3 {
+        for p in values.chunks(stride) {
+            fold(&mut bounds, p);
+        }
+        return Some(bounds);
+    }
+
+    // Tightly packed: keep one running min/max per lane and merge the lanes at the end
+    let mut lane_min = [f32::MAX; 3 * LANES];
+    let mut lane_max = [f32::MIN; 3 * LANES];
+    let mut chunks = values.chunks_exact(3 * LANES);
+    for chunk in &mut chunks {
+        for i in 0..3 * LANES {
+            lane_min[i] = if chunk[i] < lane_min[i] {
+                chunk[i]
+            } else {
+                lane_min[i]
+            };
+            lane_max[i] = if chunk[i] > lane_max[i] {
+                chunk[i]
+            } else {
+                lane_max[i]
+            };
+        }
+    }
+    for (min, max) in lane_min.chunks_exact(3).zip(lane_max.chunks_exact(3)) {
+        for c in 0..3 {
+            bounds.0[c] = bounds.0[c].min(min[c]);
+            bounds.1[c] = bounds.1[c].max(max[c]);
+        }
+    }
+    for p in chunks.remainder().chunks_exact(3) {
+        fold(&mut bounds, p);
+    }
+    Some(bounds)
+}
+
+/// Add `offset` to positions `stride` floats apart
+pub(crate) fn offset_vec3(values: &mut [f32], stride: usize, offset: [f32; 3]) {
+    if stride == 3 {
+        let pattern: [f32; 3 * LANES] = std::array::from_fn(|i| offset[i % 3]);
+        let mut chunks = values.chunks_exact_mut(3 * LANES);
+        for chunk in &mut chunks {
+            for (v, o) in chunk.iter_mut().zip(&pattern) {
+                *v += o;
+            }
+        }
+        for (v, o) in chunks.into_remainder().iter_mut().zip(&pattern) {
+            *v += o;
+        }
+        return;
+    }
+
+    for p in values.chunks_mut(stride) {
+        for (v, o) in p.iter_mut().zip(&offset) {
+            *v += o;
+        }
+    }
+}
+
+/// Offset the float VEC3 elements in `bytes` in place and return their new bounds
+pub(crate) fn offset_vec3_bytes(
+    bytes: &mut [u8],
+    stride: usize,
+    offset: [f32; 3],
+) -> Option<Bounds> {
+    with_le_floats_mut(bytes, |values| {
+        offset_vec3(values, stride, offset);
+        vec3_bounds(values, stride)
+    })
+}
+
+/// Bounds of a POSITION accessor, read in place when it holds floats
+pub(crate) fn position_bounds(
+    o_blob: &[u8],
+    o_json: &Root,
+    idx: Index<gltf::json::Accessor>,
+) -> Option<Bounds> {
+    let view = o_json
+        .accessors
+        .get(idx.value())
+        .and_then(|acc| acc.buffer_view)
+        .and_then(|view| o_json.buffer_views.get(view.value()));
+    if let Some(view) = view {
+        let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+        let data = o_blob.get(offset..offset.checked_add(view.byte_length.0 as usize)?)?;
+        if let Some((range, stride)) = f32_vec3_range(o_json, idx, data.len()) {
+            return vec3_bounds(&le_floats(&data[range]), stride);
+        }
+    }
+
+    // Quantized and sparse positions are decoded first
+    let positions = get_position_data(o_blob, o_json, idx)?;
+    vec3_bounds(positions.as_flattened(), 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_of_fewer_vertices_than_a_chunk() {
+        let positions = [[0.0f32, 0.0, 0.0], [1.0, 2.0, -1.0], [0.5, -3.0, 4.0]];
+        assert_eq!(
+            vec3_bounds(positions.as_flattened(), 3),
+            Some(([0.0, -3.0, -1.0], [1.0, 2.0, 4.0]))
+        );
+    }
+
+    #[test]
+    fn offsets_past_the_view_have_no_range() {
+        let o_json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 24 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 24 }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3" },
+                {
+                    "bufferView": 0, "byteOffset": 400, "componentType": 5126, "count": 1,
+                    "type": "VEC3",
+                },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(f32_vec3_range(&o_json, Index::new(0), 24), Some((0..24, 3)));
+        assert_eq!(f32_vec3_range(&o_json, Index::new(1), 24), None);
+        assert_eq!(position_bounds(&[0; 24], &o_json, Index::new(1)), None);
+    }
+}
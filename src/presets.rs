@@ -0,0 +1,107 @@
+use std::sync::LazyLock;
+
+use crate::{compat::TargetProfile, options::OptimizeOptions};
+
+/// A named set of optimizer settings, for frontends listing choices to users
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PresetInfo {
+    /// Stable identifier, e.g. `mobile` or `threejs`
+    pub key: &'static str,
+    /// Human readable name
+    pub name: String,
+    /// One sentence on what the preset is for
+    pub description: String,
+    /// The concrete settings the preset stands for
+    pub options: OptimizeOptions,
+}
+
+static PRESETS: LazyLock<Vec<PresetInfo>> = LazyLock::new(|| {
+    let defaults = OptimizeOptions::default();
+    let mut presets = vec![
+        PresetInfo {
+            key: "default",
+            name: "Default".into(),
+            description: "Textures resized to 1024 px and re-encoded in their own formats, \
+                          geometry kept as it is"
+                .into(),
+            options: defaults.clone(),
+        },
+        PresetInfo {
+            key: "mobile",
+            name: "Mobile".into(),
+            description: "KTX2 textures with mipmaps, quantized and meshopt-compressed \
+                          geometry, for phones and other viewers short on GPU memory"
+                .into(),
+            options: OptimizeOptions {
+                convert_to_ktx2: true,
+                ktx2_mipmaps: true,
+                quantize_attributes: true,
+                meshopt_compression: true,
+                ..defaults.clone()
+            },
+        },
+        PresetInfo {
+            key: "preview",
+            name: "Preview".into(),
+            description: "Textures resized to 256 px and normal maps removed, for thumbnails \
+                          and quick looks"
+                .into(),
+            options: OptimizeOptions {
+                texture_size: 256,
+                remove_normal_texture: true,
+                ..defaults.clone()
+            },
+        },
+    ];
+    for profile in [
+        TargetProfile::ThreeJs,
+        TargetProfile::BabylonJs,
+        TargetProfile::UnityGltfast,
+        TargetProfile::FilamentAndroid,
+        TargetProfile::QuickLookUsdz,
+    ] {
+        let name = profile.name();
+        presets.push(PresetInfo {
+            key: profile.key(),
+            description: format!("Texture size and formats {name} is known to load"),
+            name,
+            options: OptimizeOptions::for_target(profile),
+        });
+    }
+    presets
+});
+
+/// Every preset, general ones first and then one per [`TargetProfile`]
+pub(crate) fn presets() -> &'static [PresetInfo] {
+    &PRESETS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn presets_list_their_settings() {
+        let presets = OptimizeOptions::presets();
+        let keys: HashSet<&str> = presets.iter().map(|p| p.key).collect();
+        assert_eq!(keys.len(), presets.len());
+        assert_eq!(presets[0].key, "default");
+        assert_eq!(presets[0].options, OptimizeOptions::default());
+        assert!(
+            presets
+                .iter()
+                .all(|p| !p.name.is_empty() && !p.description.is_empty())
+        );
+
+        let quick_look = presets.iter().find(|p| p.key == "quicklook-usdz").unwrap();
+        assert_eq!(
+            quick_look.options,
+            OptimizeOptions::for_target(crate::compat::TargetProfile::QuickLookUsdz)
+        );
+        let preview = presets.iter().find(|p| p.key == "preview").unwrap();
+        assert!(preview.options.texture_size < OptimizeOptions::default().texture_size);
+    }
+}
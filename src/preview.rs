@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    io::{Read, Seek},
+};
+
+use gltf::json::{
+    Index, Material, Mesh, Node, Root, Scene, Texture,
+    accessor::Type,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    error::OptimizeError,
+    glb::{push_attribute, push_indices, push_positions, read_glb, write_glb},
+    opt::get_image_data,
+    texcoords::drop_tex_coord_override,
+};
+
+const SPHERE_SEGMENTS: u32 = 32;
+const SPHERE_RINGS: u32 = 16;
+
+/// Standalone GLB showing a single material on a unit sphere
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MaterialPreview {
+    /// Index of the material in the source GLB
+    pub material: usize,
+    pub name: Option<String>,
+    pub glb: Vec<u8>,
+}
+
+/// UV sphere with positions, normals, tangents and texture coordinates
+struct Sphere {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<f32>,
+    tangents: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+fn uv_sphere() -> Sphere {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tangents = Vec::new();
+    let mut uvs = Vec::new();
+
+    for ring in 0..=SPHERE_RINGS {
+        let v = ring as f32 / SPHERE_RINGS as f32;
+        let theta = v * PI;
+        for segment in 0..=SPHERE_SEGMENTS {
+            let u = segment as f32 / SPHERE_SEGMENTS as f32;
+            let phi = u * 2.0 * PI;
+
+            let n = [
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                -theta.sin() * phi.sin(),
+            ];
+            positions.push(n);
+            normals.extend_from_slice(&n);
+            tangents.extend_from_slice(&[-phi.sin(), 0.0, -phi.cos(), 1.0]);
+            uvs.extend_from_slice(&[u, v]);
+        }
+    }
+
+    let mut indices = Vec::new();
+    let row = SPHERE_SEGMENTS + 1;
+    for ring in 0..SPHERE_RINGS {
+        for segment in 0..SPHERE_SEGMENTS {
+            let a = ring * row + segment;
+            let b = a + row;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+
+    Sphere {
+        positions,
+        normals,
+        tangents,
+        uvs,
+        indices,
+    }
+}
+
+/// Copy a texture (and its image bytes and sampler) into the preview document
+fn copy_texture(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    texture_idx: Index<Texture>,
+    copied: &mut HashMap<usize, Index<Texture>>,
+) -> Result<Index<Texture>, OptimizeError> {
+    if let Some(idx) = copied.get(&texture_idx.value()) {
+        return Ok(*idx);
+    }
+
+    let texture =
+        o_json
+            .textures
+            .get(texture_idx.value())
+            .ok_or(OptimizeError::TextureOutOfBounds {
+                texture: texture_idx.value(),
+            })?;
+    let image = o_json
+        .images
+        .get(texture.source.value())
+        .ok_or(OptimizeError::MissingObject {
+            kind: "image",
+            index: texture.source.value(),
+        })?;
+    let data =
+        get_image_data(o_blob, o_json, texture_idx).ok_or(OptimizeError::MissingImageData {
+            texture: texture_idx.value(),
+        })?;
+
+    let view = n_blob.push_view(n_json, data, None);
+    let mut n_image = image.clone();
+    n_image.buffer_view = Some(view);
+    n_image.uri = None;
+    let n_image_idx = n_json.push(n_image);
+
+    let mut n_texture = texture.clone();
+    n_texture.source = n_image_idx;
+    n_texture.sampler = match texture.sampler {
+        Some(sampler) => o_json
+            .samplers
+            .get(sampler.value())
+            .map(|s| n_json.push(s.clone())),
+        None => None,
+    };
+
+    let idx = n_json.push(n_texture);
+    copied.insert(texture_idx.value(), idx);
+    Ok(idx)
+}
+
+fn build_preview(
+    o_blob: &[u8],
+    o_json: &Root,
+    material: &Material,
+) -> Result<Vec<u8>, OptimizeError> {
+    let mut n_blob = BlobWriter::new();
+    let mut n_json = Root {
+        asset: o_json.asset.clone(),
+        extensions_used: o_json.extensions_used.clone(),
+        extensions_required: o_json.extensions_required.clone(),
+        ..Default::default()
+    };
+
+    // The sphere only carries TEXCOORD_0, so every slot is pointed at it, transform overrides
+    // included
+    let mut copied = HashMap::new();
+    let mut n_mat = material.clone();
+    if let Some(info) = &mut n_mat.pbr_metallic_roughness.base_color_texture {
+        info.index = copy_texture(
+            o_blob,
+            o_json,
+            &mut n_blob,
+            &mut n_json,
+            info.index,
+            &mut copied,
+        )?;
+        info.tex_coord = 0;
+        drop_tex_coord_override(info.extensions.as_mut().map(|e| &mut e.others));
+    }
+    if let Some(info) = &mut n_mat.pbr_metallic_roughness.metallic_roughness_texture {
+        info.index = copy_texture(
+            o_blob,
+            o_json,
+            &mut n_blob,
+            &mut n_json,
+            info.index,
+            &mut copied,
+        )?;
+        info.tex_coord = 0;
+        drop_tex_coord_override(info.extensions.as_mut().map(|e| &mut e.others));
+    }
+    if let Some(info) = &mut n_mat.normal_texture {
+        info.index = copy_texture(
+            o_blob,
+            o_json,
+            &mut n_blob,
+            &mut n_json,
+            info.index,
+            &mut copied,
+        )?;
+        info.tex_coord = 0;
+        drop_tex_coord_override(info.extensions.as_mut().map(|e| &mut e.others));
+    }
+    if let Some(info) = &mut n_mat.occlusion_texture {
+        info.index = copy_texture(
+            o_blob,
+            o_json,
+            &mut n_blob,
+            &mut n_json,
+            info.index,
+            &mut copied,
+        )?;
+        info.tex_coord = 0;
+        drop_tex_coord_override(info.extensions.as_mut().map(|e| &mut e.others));
+    }
+    if let Some(info) = &mut n_mat.emissive_texture {
+        info.index = copy_texture(
+            o_blob,
+            o_json,
+            &mut n_blob,
+            &mut n_json,
+            info.index,
+            &mut copied,
+        )?;
+        info.tex_coord = 0;
+        drop_tex_coord_override(info.extensions.as_mut().map(|e| &mut e.others));
+    }
+    let mat_idx = n_json.push(n_mat);
+
+    let sphere = uv_sphere();
+    let mut primitive = Primitive {
+        attributes: Default::default(),
+        extensions: None,
+        extras: Default::default(),
+        indices: Some(push_indices(&mut n_blob, &mut n_json, &sphere.indices)),
+        material: Some(mat_idx),
+        mode: Checked::Valid(Mode::Triangles),
+        targets: None,
+    };
+    let attributes = [
+        (
+            Semantic::Positions,
+            push_positions(&mut n_blob, &mut n_json, &sphere.positions),
+        ),
+        (
+            Semantic::Normals,
+            push_attribute(&mut n_blob, &mut n_json, &sphere.normals, Type::Vec3),
+        ),
+        (
+            Semantic::Tangents,
+            push_attribute(&mut n_blob, &mut n_json, &sphere.tangents, Type::Vec4),
+        ),
+        (
+            Semantic::TexCoords(0),
+            push_attribute(&mut n_blob, &mut n_json, &sphere.uvs, Type::Vec2),
+        ),
+    ];
+    for (semantic, acc) in attributes {
+        primitive.attributes.insert(Checked::Valid(semantic), acc);
+    }
+
+    let mesh = n_json.push(Mesh {
+        extensions: None,
+        extras: Default::default(),
+        name: Some("material_preview".to_string()),
+        primitives: vec![primitive],
+        weights: None,
+    });
+    let node = n_json.push(Node {
+        mesh: Some(mesh),
+        name: material.name.clone(),
+        ..Default::default()
+    });
+    let scene = n_json.push(Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: vec![node],
+    });
+    n_json.scene = Some(scene);
+
+    write_glb(&n_json, n_blob)
+}
+
+/// Build one standalone sphere GLB per material, embedding the material's textures
+///
+/// Pass the optimized output to get previews with the resized/re-encoded textures.
+pub fn material_previews<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<Vec<MaterialPreview>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+
+    o_json
+        .materials
+        .iter()
+        .enumerate()
+        .map(|(i, material)| {
+            Ok(MaterialPreview {
+                material: i,
+                name: material.name.clone(),
+                glb: build_preview(&o_blob, &o_json, material)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::get_position_data, testing::SyntheticGlb};
+
+    #[test]
+    fn every_material_gets_a_sphere_with_its_textures() {
+        let glb = SyntheticGlb {
+            textures: 2,
+            primitives_per_mesh: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let previews = material_previews(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(previews.len(), 2);
+
+        for (i, preview) in previews.iter().enumerate() {
+            assert_eq!(preview.material, i);
+            assert_eq!(preview.name.as_deref(), Some(&*format!("material_{i}")));
+            let (json, blob) = read_glb(&mut Cursor::new(&preview.glb)).unwrap();
+            assert_eq!((json.materials.len(), json.textures.len()), (1, 1));
+            assert_eq!(
+                get_image_data(&blob, &json, Index::new(0)),
+                get_image_data(&o_blob, &o_json, Index::new(i as u32))
+            );
+
+            let primitive = &json.meshes[0].primitives[0];
+            for semantic in [
+                Semantic::Normals,
+                Semantic::Tangents,
+                Semantic::TexCoords(0),
+            ] {
+                assert!(primitive.attributes.contains_key(&Checked::Valid(semantic)));
+            }
+            let position = primitive.attributes[&Checked::Valid(Semantic::Positions)];
+            let positions = get_position_data(&blob, &json, position).unwrap();
+            let radius = |p: &[f32; 3]| (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            assert!(positions.iter().all(|p| (radius(p) - 1.0).abs() < 1e-5));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::OptimizeError;
+
+/// Kind of work item reported to a [`ProgressSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProgressStage {
+    /// Resizing and encoding one texture slot
+    Textures,
+    /// Copying one mesh with its primitives, materials and textures not encoded before
+    Meshes,
+}
+
+/// Receiver for progress updates and source of cancellation, e.g. a progress bar with a
+/// cancel button
+///
+/// Both methods have defaults, so a sink only implements what it needs. Textures are encoded on
+/// all cores, so the sink is shared between threads and `on_progress` may be called from any of
+/// them.
+pub trait ProgressSink: Send + Sync {
+    /// `current` of `total` items of `stage` are done
+    ///
+    /// Textures are reported as they finish, not necessarily in order, and `total` counts the
+    /// slots planned up front. Meshes are reported in order once copied.
+    fn on_progress(&self, stage: ProgressStage, current: usize, total: usize) {
+        let _ = (stage, current, total);
+    }
+
+    /// Whether to stop at the next work item with [`OptimizeError::Cancelled`]; `false` by
+    /// default
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Item counts of one optimization, shared by the threads reporting to a [`ProgressSink`]
+pub(crate) struct Progress<'a> {
+    sink: &'a dyn ProgressSink,
+    textures_done: AtomicUsize,
+    textures_total: AtomicUsize,
+}
+
+impl<'a> Progress<'a> {
+    pub(crate) fn new(sink: &'a dyn ProgressSink) -> Self {
+        Progress {
+            sink,
+            textures_done: AtomicUsize::new(0),
+            textures_total: AtomicUsize::new(0),
+        }
+    }
+
+    /// Fail with [`OptimizeError::Cancelled`] once the sink asks to stop
+    pub(crate) fn check(&self) -> Result<(), OptimizeError> {
+        if self.sink.is_cancelled() {
+            return Err(OptimizeError::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_texture_total(&self, total: usize) {
+        self.textures_total.store(total, Ordering::Relaxed);
+    }
+
+    /// Report one more texture slot encoded
+    pub(crate) fn texture_done(&self) {
+        let current = self.textures_done.fetch_add(1, Ordering::Relaxed) + 1;
+        // Retries of failed slots in best-effort mode may encode more than planned
+        let total = self.textures_total.load(Ordering::Relaxed).max(current);
+        self.sink
+            .on_progress(ProgressStage::Textures, current, total);
+    }
+
+    /// Report the first `current` of `total` meshes copied
+    pub(crate) fn mesh_done(&self, current: usize, total: usize) {
+        self.sink.on_progress(ProgressStage::Meshes, current, total);
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Mutex, atomic::AtomicBool},
+    };
+
+    use super::*;
+    use crate::{optimizer::Optimizer, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn progress_is_reported_and_cancellable() {
+        #[derive(Default)]
+        struct Recorder {
+            updates: Mutex<Vec<(ProgressStage, usize, usize)>>,
+            cancel_after_first_mesh: bool,
+            cancelled: AtomicBool,
+        }
+        impl ProgressSink for Recorder {
+            fn on_progress(&self, stage: ProgressStage, current: usize, total: usize) {
+                self.updates.lock().unwrap().push((stage, current, total));
+                if self.cancel_after_first_mesh && stage == ProgressStage::Meshes {
+                    self.cancelled.store(true, Ordering::Relaxed);
+                }
+            }
+            fn is_cancelled(&self) -> bool {
+                self.cancelled.load(Ordering::Relaxed)
+            }
+        }
+
+        let glb = SyntheticGlb {
+            meshes: 3,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let recorder = Recorder::default();
+        Optimizer::new(&OptimizeOptions::default())
+            .progress(&recorder)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let updates = recorder.updates.into_inner().unwrap();
+        let textures: Vec<_> = updates
+            .iter()
+            .filter(|(stage, ..)| *stage == ProgressStage::Textures)
+            .map(|&(_, current, total)| (current, total))
+            .collect();
+        assert_eq!(textures, [(1, 2), (2, 2)]);
+        let meshes: Vec<_> = updates
+            .iter()
+            .filter(|(stage, ..)| *stage == ProgressStage::Meshes)
+            .map(|&(_, current, total)| (current, total))
+            .collect();
+        assert_eq!(meshes, [(1, 3), (2, 3), (3, 3)]);
+
+        // Asking to stop ends the run before the next mesh
+        let recorder = Recorder {
+            cancel_after_first_mesh: true,
+            ..Default::default()
+        };
+        let e = Optimizer::new(&OptimizeOptions::default())
+            .progress(&recorder)
+            .run(&mut Cursor::new(&glb))
+            .unwrap_err();
+        assert!(matches!(e, OptimizeError::Cancelled));
+        let meshes = recorder
+            .updates
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .filter(|(stage, ..)| *stage == ProgressStage::Meshes)
+            .count();
+        assert_eq!(meshes, 1);
+    }
+}
@@ -0,0 +1,312 @@
+use gltf::json::{Index, Root};
+
+use crate::{
+    lod::{LOD_EXTENSION, lod_ids},
+    remap::RemapTables,
+    variants::{remap_variant_materials, variant_materials},
+};
+
+/// Index of each kept source object in the pruned document, indexed by source index
+#[derive(Debug)]
+pub(crate) struct KeptObjects {
+    nodes: Vec<Option<usize>>,
+    meshes: Vec<Option<usize>>,
+    materials: Vec<Option<usize>>,
+}
+
+impl KeptObjects {
+    /// Tables built against the pruned document, mapped back to source indices
+    pub(crate) fn source_tables(&self, remap: RemapTables, source: &Root) -> RemapTables {
+        let through = |kept: &[Option<usize>], table: &[Option<usize>]| {
+            kept.iter()
+                .map(|i| i.and_then(|i| table.get(i).copied().flatten()))
+                .collect()
+        };
+        RemapTables {
+            nodes: through(&self.nodes, &remap.nodes),
+            meshes: through(&self.meshes, &remap.meshes),
+            materials: through(&self.materials, &remap.materials),
+            node_names: source.nodes.iter().map(|n| n.name.clone()).collect(),
+            mesh_names: source.meshes.iter().map(|m| m.name.clone()).collect(),
+            material_names: source.materials.iter().map(|m| m.name.clone()).collect(),
+            ..remap
+        }
+    }
+}
+
+/// Items flagged in `kept`, and the new index of each kept one
+fn compact<T: Clone>(items: &[T], kept: &[bool]) -> (Vec<T>, Vec<Option<usize>>) {
+    let mut table = vec![None; items.len()];
+    let mut compacted = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        if kept[i] {
+            table[i] = Some(compacted.len());
+            compacted.push(item.clone());
+        }
+    }
+    (compacted, table)
+}
+
+fn remap_index<T>(table: &[Option<usize>], index: Index<T>) -> Option<Index<T>> {
+    let new = table.get(index.value()).copied().flatten()?;
+    Some(Index::new(new as u32))
+}
+
+/// Source document without the objects no scene can reach, `None` when every one is reachable
+///
+/// Nodes are kept when a scene, a kept skin's joints or a kept node's `MSFT_lod` levels lead
+/// to them; documents without scenes keep all of them. Meshes, skins and cameras follow the
+/// kept nodes and materials the kept meshes, including their `KHR_materials_variants`
+/// mappings (all materials stay with `keep_materials`). Animation channels targeting dropped
+/// nodes go with their samplers, and animations left without channels are dropped. Textures, images and accessors are copied on demand later
+/// and are left as they are.
+pub(crate) fn prune_unreachable(
+    o_json: &Root,
+    keep_materials: bool,
+) -> Option<(Root, KeptObjects)> {
+    let mut kept_nodes = vec![o_json.scenes.is_empty(); o_json.nodes.len()];
+    let mut stack: Vec<usize> = o_json
+        .scenes
+        .iter()
+        .flat_map(|scene| &scene.nodes)
+        .map(|n| n.value())
+        .collect();
+    while let Some(i) = stack.pop() {
+        let Some(node) = o_json.nodes.get(i) else {
+            continue;
+        };
+        if std::mem::replace(&mut kept_nodes[i], true) {
+            continue;
+        }
+        stack.extend(node.children.iter().flatten().map(|c| c.value()));
+        stack.extend(lod_ids(node));
+        if let Some(skin) = node.skin.and_then(|s| o_json.skins.get(s.value())) {
+            stack.extend(skin.joints.iter().chain(&skin.skeleton).map(|j| j.value()));
+        }
+    }
+
+    let mut kept_meshes = vec![false; o_json.meshes.len()];
+    let mut kept_skins = vec![false; o_json.skins.len()];
+    let mut kept_cameras = vec![false; o_json.cameras.len()];
+    for (node, _) in o_json.nodes.iter().zip(&kept_nodes).filter(|(_, k)| **k) {
+        let slots = [
+            (node.mesh.map(|m| m.value()), &mut kept_meshes),
+            (node.skin.map(|s| s.value()), &mut kept_skins),
+            (node.camera.map(|c| c.value()), &mut kept_cameras),
+        ];
+        for (index, kept) in slots {
+            if let Some(slot) = index.and_then(|i| kept.get_mut(i)) {
+                *slot = true;
+            }
+        }
+    }
+
+    let mut kept_materials = vec![keep_materials; o_json.materials.len()];
+    let used_materials = o_json
+        .meshes
+        .iter()
+        .zip(&kept_meshes)
+        .filter(|(_, k)| **k)
+        .flat_map(|(mesh, _)| &mesh.primitives)
+        .flat_map(|p| {
+            p.material
+                .map(|m| m.value())
+                .into_iter()
+                .chain(variant_materials(p))
+        });
+    for material in used_materials {
+        if let Some(slot) = kept_materials.get_mut(material) {
+            *slot = true;
+        }
+    }
+
+    let drops_channels = o_json.animations.iter().flat_map(|a| &a.channels).any(|c| {
+        !kept_nodes
+            .get(c.target.node.value())
+            .copied()
+            .unwrap_or(false)
+    });
+    let all = |kept: &[bool]| kept.iter().all(|k| *k);
+    if all(&kept_nodes)
+        && all(&kept_meshes)
+        && all(&kept_skins)
+        && all(&kept_cameras)
+        && all(&kept_materials)
+        && !drops_channels
+    {
+        return None;
+    }
+
+    let mut n_json = o_json.clone();
+    let (nodes, node_table) = compact(&o_json.nodes, &kept_nodes);
+    let (meshes, mesh_table) = compact(&o_json.meshes, &kept_meshes);
+    let (skins, skin_table) = compact(&o_json.skins, &kept_skins);
+    let (cameras, camera_table) = compact(&o_json.cameras, &kept_cameras);
+    let (materials, material_table) = compact(&o_json.materials, &kept_materials);
+
+    n_json.nodes = nodes;
+    for node in &mut n_json.nodes {
+        if let Some(children) = &mut node.children {
+            children.retain_mut(|c| remap_index(&node_table, *c).map(|n| *c = n).is_some());
+        }
+        node.mesh = node.mesh.and_then(|m| remap_index(&mesh_table, m));
+        node.skin = node.skin.and_then(|s| remap_index(&skin_table, s));
+        node.camera = node.camera.and_then(|c| remap_index(&camera_table, c));
+        let lod = node
+            .extensions
+            .as_mut()
+            .and_then(|e| e.others.get_mut(LOD_EXTENSION));
+        if let Some(ids) = lod.and_then(|lod| lod.get_mut("ids")) {
+            let remapped: Vec<usize> = serde_json::from_value::<Vec<usize>>(ids.take())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| node_table.get(id).copied().flatten())
+                .collect();
+            *ids = remapped.into();
+        }
+    }
+    for scene in &mut n_json.scenes {
+        scene.nodes = scene
+            .nodes
+            .iter()
+            .filter_map(|n| remap_index(&node_table, *n))
+            .collect();
+    }
+    n_json.meshes = meshes;
+    for primitive in n_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+        primitive.material = primitive
+            .material
+            .and_then(|m| remap_index(&material_table, m));
+        remap_variant_materials(primitive, |m| material_table.get(m).copied().flatten());
+    }
+    n_json.skins = skins;
+    for skin in &mut n_json.skins {
+        skin.joints = skin
+            .joints
+            .iter()
+            .filter_map(|j| remap_index(&node_table, *j))
+            .collect();
+        skin.skeleton = skin.skeleton.and_then(|s| remap_index(&node_table, s));
+    }
+    n_json.cameras = cameras;
+    n_json.materials = materials;
+
+    n_json.animations.retain_mut(|animation| {
+        if animation.channels.is_empty() {
+            return true;
+        }
+        animation.channels.retain_mut(|c| {
+            remap_index(&node_table, c.target.node)
+                .map(|n| c.target.node = n)
+                .is_some()
+        });
+        let mut used = vec![false; animation.samplers.len()];
+        for channel in &animation.channels {
+            if let Some(slot) = used.get_mut(channel.sampler.value()) {
+                *slot = true;
+            }
+        }
+        let (samplers, sampler_table) = compact(&animation.samplers, &used);
+        animation.samplers = samplers;
+        for channel in &mut animation.channels {
+            if let Some(sampler) = remap_index(&sampler_table, channel.sampler) {
+                channel.sampler = sampler;
+            }
+        }
+        !animation.channels.is_empty()
+    });
+
+    let kept = KeptObjects {
+        nodes: node_table,
+        meshes: mesh_table,
+        materials: material_table,
+    };
+    Some((n_json, kept))
+}
+
+/// Drop texture samplers no output texture refers to, renumbering the rest
+pub(crate) fn prune_samplers(n_json: &mut Root) {
+    let mut used = vec![false; n_json.samplers.len()];
+    for sampler in n_json.textures.iter().filter_map(|t| t.sampler) {
+        if let Some(slot) = used.get_mut(sampler.value()) {
+            *slot = true;
+        }
+    }
+    if used.iter().all(|u| *u) {
+        return;
+    }
+
+    let (samplers, table) = compact(&n_json.samplers, &used);
+    n_json.samplers = samplers;
+    for texture in &mut n_json.textures {
+        texture.sampler = texture.sampler.and_then(|s| remap_index(&table, s));
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::write_glb, opt::optimize_with, optimizer::Optimizer, options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn unreachable_objects_are_pruned() {
+        use crate::{glb::read_glb, optimizer::Optimized};
+
+        // The second mesh's node is in no scene, so its mesh, material and sampler go too
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut json = serde_json::to_value(&json).unwrap();
+        json["scenes"][0]["nodes"] = serde_json::json!([0]);
+        json["samplers"] = serde_json::json!([{ "wrapS": 33071 }, { "wrapT": 33071 }]);
+        json["textures"][0]["sampler"] = 1.into();
+        json["textures"][1]["sampler"] = 0.into();
+        let json: Root = serde_json::from_value(json).unwrap();
+        let glb = write_glb(&json, blob).unwrap();
+
+        let options = OptimizeOptions::default();
+        let Optimized {
+            glb: out, remap, ..
+        } = Optimizer::new(&options)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        let counts = |json: &Root| {
+            [
+                json.nodes.len(),
+                json.meshes.len(),
+                json.materials.len(),
+                json.textures.len(),
+                json.samplers.len(),
+            ]
+        };
+        assert_eq!(counts(&n_json), [1, 1, 1, 1, 1]);
+        assert_eq!(n_json.textures[0].sampler, Some(Index::new(0)));
+        assert_eq!(
+            serde_json::to_value(&n_json.samplers[0]).unwrap(),
+            serde_json::to_value(&json.samplers[1]).unwrap()
+        );
+        assert_eq!(remap.nodes, [Some(0), None]);
+        assert_eq!(remap.meshes, [Some(0), None]);
+        assert_eq!(remap.materials, [Some(0), None]);
+        assert_eq!(remap.find_mesh("mesh_1"), None);
+
+        let options = OptimizeOptions {
+            prune_unused: false,
+            ..options
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(counts(&n_json), [2, 2, 2, 2, 2]);
+    }
+}
@@ -0,0 +1,112 @@
+use image::DynamicImage;
+
+/// JPEG quality used when auto quality is off (the `image` encoder default)
+pub(crate) const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// Side of the thumbnail the detail analysis runs on
+const ANALYSIS_SIZE: u32 = 128;
+
+/// Mean luma gradient (0-255 scale) treated as fully detailed
+const FULL_EDGE_ENERGY: f32 = 32.0;
+
+/// How much fine detail an image carries, from 0 (flat color) to 1 (noise)
+///
+/// Averages the luma edge energy and the luma histogram entropy of a small thumbnail, so
+/// smooth gradients score low and busy photographic textures score high.
+pub(crate) fn detail_score(img: &DynamicImage) -> f32 {
+    let luma = img.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).to_luma8();
+    let (width, height) = luma.dimensions();
+    if width < 2 || height < 2 {
+        return 0.0;
+    }
+
+    let mut gradient = 0.0;
+    let mut histogram = [0u32; 256];
+    for (x, y, p) in luma.enumerate_pixels() {
+        let v = p.0[0];
+        histogram[v as usize] += 1;
+        if x + 1 < width && y + 1 < height {
+            let dx = v.abs_diff(luma.get_pixel(x + 1, y).0[0]);
+            let dy = v.abs_diff(luma.get_pixel(x, y + 1).0[0]);
+            gradient += (dx as f32 + dy as f32) / 2.0;
+        }
+    }
+    let edge = gradient / ((width - 1) * (height - 1)) as f32 / FULL_EDGE_ENERGY;
+
+    let total = (width * height) as f32;
+    let entropy: f32 = histogram
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f32 / total;
+            -p * p.log2()
+        })
+        .sum();
+
+    ((edge.min(1.0) + entropy / 8.0) / 2.0).clamp(0.0, 1.0)
+}
+
+/// JPEG quality for a detail score; neutral bias lands near the default for typical textures
+pub(crate) fn jpeg_quality(score: f32, bias: i8) -> u8 {
+    (60.0 + score * 35.0 + bias as f32)
+        .round()
+        .clamp(10.0, 100.0) as u8
+}
+
+/// Basis ETC1S quality level for a detail score, centered on the slot's fixed level
+#[cfg(feature = "ktx2")]
+pub(crate) fn basis_quality_level(base: u32, score: f32, bias: i8) -> u32 {
+    (base as f32 - 50.0 + score * 100.0 + bias as f32 * 2.5)
+        .round()
+        .clamp(1.0, 255.0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GrayImage, Luma};
+
+    use super::*;
+
+    fn noise(size: u32) -> DynamicImage {
+        let mut state = 0x2545_f491u32;
+        DynamicImage::ImageLuma8(GrayImage::from_fn(size, size, |_, _| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            Luma([state as u8])
+        }))
+    }
+
+    #[test]
+    fn detail_ranks_flat_smooth_and_busy_images() {
+        let flat = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 64, Luma([128])));
+        let gradient =
+            DynamicImage::ImageLuma8(GrayImage::from_fn(256, 256, |x, _| Luma([x as u8])));
+        let (flat, gradient, noise) = (
+            detail_score(&flat),
+            detail_score(&gradient),
+            detail_score(&noise(256)),
+        );
+        assert_eq!(flat, 0.0);
+        assert!(gradient > flat && gradient < 0.6, "{gradient}");
+        assert!(noise > 0.9, "{noise}");
+    }
+
+    #[test]
+    fn jpeg_quality_follows_detail_and_bias() {
+        assert_eq!(jpeg_quality(0.0, 0), 60);
+        assert_eq!(jpeg_quality(1.0, 0), 95);
+        assert_eq!(jpeg_quality(0.5, -10), 68);
+        assert_eq!(jpeg_quality(1.0, 100), 100);
+        assert_eq!(jpeg_quality(0.0, -100), 10);
+    }
+
+    #[test]
+    #[cfg(feature = "ktx2")]
+    fn basis_quality_centers_on_the_slot_level() {
+        assert_eq!(basis_quality_level(128, 0.5, 0), 128);
+        assert_eq!(basis_quality_level(128, 0.5, 4), 138);
+        assert_eq!(basis_quality_level(250, 1.0, 0), 255);
+        assert_eq!(basis_quality_level(10, 0.0, -20), 1);
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use gltf::json::{
+    Accessor, Index, Node, Root,
+    accessor::{ComponentType, GenericComponentType},
+    buffer::Stride,
+    mesh::Semantic,
+    validation::Checked,
+};
+use serde_json::Value;
+
+use crate::{
+    convert::{encode_component, read_accessor, write_elements},
+    error::OptimizeError,
+    opt::{replace_views, require_extensions},
+    views::count_view_refs,
+};
+
+pub(crate) const QUANTIZATION_EXTENSION: &str = "KHR_mesh_quantization";
+
+/// Float accessor whose view holds nothing else, so its bytes can be swapped out
+fn exclusive_view(n_json: &Root, view_refs: &[usize], index: usize) -> Option<usize> {
+    let acc = n_json.accessors.get(index)?;
+    let view = acc.buffer_view?.value();
+    let float = matches!(
+        acc.component_type,
+        Checked::Valid(GenericComponentType(ComponentType::F32))
+    );
+    (float
+        && acc.sparse.is_none()
+        && acc.byte_offset.is_none_or(|o| o.0 == 0)
+        && view_refs.get(view) == Some(&1))
+    .then_some(view)
+}
+
+/// Point `index` at `bytes` of normalized `component` values
+fn rewrite_accessor(
+    n_json: &mut Root,
+    replacements: &mut HashMap<usize, Vec<u8>>,
+    index: usize,
+    view: usize,
+    (bytes, stride): (Vec<u8>, usize),
+    component: ComponentType,
+    components: usize,
+) {
+    let acc = &mut n_json.accessors[index];
+    acc.component_type = Checked::Valid(GenericComponentType(component));
+    acc.normalized = true;
+    acc.min = None;
+    acc.max = None;
+    n_json.buffer_views[view].byte_stride =
+        (stride != component.size() * components).then_some(Stride(stride));
+    replacements.insert(view, bytes);
+}
+
+/// Store float vertex attributes as normalized integers under `KHR_mesh_quantization`
+///
+/// NORMAL and TANGENT become 8-bit, TEXCOORD_n within `[0, 1]` 16-bit. With `positions`,
+/// POSITION becomes 16-bit in the bounds of its mesh, and every node using the mesh moves it
+/// to a child node whose translation and uniform scale undo the quantization. Skinned and
+/// morphed meshes keep float positions. Only accessors used by a single attribute and alone
+/// in their view are converted. Returns the new binary chunk, `None` when nothing changed.
+pub(crate) fn quantize_attributes(
+    n_json: &mut Root,
+    n_blob: &[u8],
+    positions: bool,
+) -> Result<Option<Vec<u8>>, OptimizeError> {
+    let mut view_refs = vec![0; n_json.buffer_views.len()];
+    count_view_refs(&serde_json::to_value(&*n_json)?, &mut view_refs);
+
+    // Every reference to each accessor, `None` for those that are not a mesh attribute
+    let mut uses: HashMap<usize, Vec<Option<(usize, Semantic)>>> = HashMap::new();
+    for (m, mesh) in n_json.meshes.iter().enumerate() {
+        for p in &mesh.primitives {
+            for (semantic, accessor) in &p.attributes {
+                let semantic = match semantic {
+                    Checked::Valid(s) => Some((m, s.clone())),
+                    Checked::Invalid => None,
+                };
+                uses.entry(accessor.value()).or_default().push(semantic);
+            }
+            let targets = p.targets.iter().flatten();
+            for accessor in p.indices.into_iter().chain(
+                targets.flat_map(|t| [t.positions, t.normals, t.tangents].into_iter().flatten()),
+            ) {
+                uses.entry(accessor.value()).or_default().push(None);
+            }
+        }
+    }
+    let samplers = n_json.animations.iter().flat_map(|a| &a.samplers);
+    for accessor in samplers
+        .flat_map(|s| [s.input, s.output])
+        .chain(n_json.skins.iter().filter_map(|s| s.inverse_bind_matrices))
+    {
+        uses.entry(accessor.value()).or_default().push(None);
+    }
+
+    let mut replacements: HashMap<usize, Vec<u8>> = HashMap::new();
+    let mut single: Vec<(usize, Semantic)> = uses
+        .iter()
+        .filter_map(|(&i, u)| match u.as_slice() {
+            [Some((_, semantic))] => Some((i, semantic.clone())),
+            _ => None,
+        })
+        .collect();
+    single.sort_by_key(|(i, _)| *i);
+    for (index, semantic) in single {
+        let Some(view) = exclusive_view(n_json, &view_refs, index) else {
+            continue;
+        };
+        let idx = Index::<Accessor>::new(index as u32);
+        match semantic {
+            Semantic::Normals => {
+                if let Some(values) = read_accessor::<3>(n_blob, n_json, idx) {
+                    let data = write_elements(&values, ComponentType::I8, true);
+                    rewrite_accessor(
+                        n_json,
+                        &mut replacements,
+                        index,
+                        view,
+                        data,
+                        ComponentType::I8,
+                        3,
+                    );
+                }
+            }
+            Semantic::Tangents => {
+                if let Some(values) = read_accessor::<4>(n_blob, n_json, idx) {
+                    let data = write_elements(&values, ComponentType::I8, true);
+                    rewrite_accessor(
+                        n_json,
+                        &mut replacements,
+                        index,
+                        view,
+                        data,
+                        ComponentType::I8,
+                        4,
+                    );
+                }
+            }
+            Semantic::TexCoords(_) => {
+                if let Some(values) = read_accessor::<2>(n_blob, n_json, idx)
+                    && values.iter().flatten().all(|v| (0.0..=1.0).contains(v))
+                {
+                    let data = write_elements(&values, ComponentType::U16, true);
+                    rewrite_accessor(
+                        n_json,
+                        &mut replacements,
+                        index,
+                        view,
+                        data,
+                        ComponentType::U16,
+                        2,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if positions {
+        quantize_positions(n_json, n_blob, &view_refs, &uses, &mut replacements);
+    }
+
+    if replacements.is_empty() {
+        return Ok(None);
+    }
+    require_extensions(n_json, &[QUANTIZATION_EXTENSION.to_string()]);
+    let blob = replace_views(n_json, n_blob, |view| {
+        replacements.get(&view).map(Vec::as_slice)
+    });
+    Ok(Some(blob))
+}
+
+/// Quantize POSITION per mesh, moving the dequantization into child nodes
+fn quantize_positions(
+    n_json: &mut Root,
+    n_blob: &[u8],
+    view_refs: &[usize],
+    uses: &HashMap<usize, Vec<Option<(usize, Semantic)>>>,
+    replacements: &mut HashMap<usize, Vec<u8>>,
+) {
+    for m in 0..n_json.meshes.len() {
+        let users: Vec<usize> = (0..n_json.nodes.len())
+            .filter(|&n| n_json.nodes[n].mesh.is_some_and(|i| i.value() == m))
+            .collect();
+        let movable = users.iter().all(|&n| {
+            let node = &n_json.nodes[n];
+            node.skin.is_none() && node.extensions.is_none()
+        });
+        let mesh = &n_json.meshes[m];
+        if users.is_empty() || !movable || mesh.primitives.iter().any(|p| p.targets.is_some()) {
+            continue;
+        }
+
+        // Every POSITION accessor of the mesh, used by nothing but this mesh's POSITION
+        let mut accessors: Vec<(usize, usize)> = Vec::new();
+        let mut eligible = true;
+        for p in &mesh.primitives {
+            let Some(accessor) = p.attributes.get(&Checked::Valid(Semantic::Positions)) else {
+                continue;
+            };
+            let index = accessor.value();
+            let own = uses[&index]
+                .iter()
+                .all(|u| matches!(u, Some((um, Semantic::Positions)) if *um == m));
+            match exclusive_view(n_json, view_refs, index) {
+                Some(view) if own => accessors.push((index, view)),
+                _ => eligible = false,
+            }
+        }
+        accessors.sort_unstable();
+        accessors.dedup();
+        if !eligible || accessors.is_empty() {
+            continue;
+        }
+        let Some(values) = accessors
+            .iter()
+            .map(|&(index, _)| read_accessor::<3>(n_blob, n_json, Index::new(index as u32)))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in values.iter().flatten() {
+            for c in 0..3 {
+                min[c] = min[c].min(p[c]);
+                max[c] = max[c].max(p[c]);
+            }
+        }
+        let center: [f32; 3] = std::array::from_fn(|c| (min[c] + max[c]) / 2.0);
+        let extent = (0..3)
+            .map(|c| (max[c] - min[c]) / 2.0)
+            .fold(0.0f32, f32::max);
+        let scale = if extent > 0.0 { extent } else { 1.0 };
+
+        for (&(index, view), values) in accessors.iter().zip(&values) {
+            let local: Vec<[f32; 3]> = values
+                .iter()
+                .map(|p| std::array::from_fn(|c| (p[c] - center[c]) / scale))
+                .collect();
+            let data = write_elements(&local, ComponentType::I16, true);
+            rewrite_accessor(
+                n_json,
+                replacements,
+                index,
+                view,
+                data,
+                ComponentType::I16,
+                3,
+            );
+
+            // POSITION needs bounds, stored as the integer values like the elements
+            let raw = |value: f32| {
+                let mut bytes = Vec::with_capacity(2);
+                encode_component(ComponentType::I16, true, value, &mut bytes);
+                i64::from(i16::from_le_bytes([bytes[0], bytes[1]]))
+            };
+            let bound = |pick: fn(f32, f32) -> f32, start: f32| -> Value {
+                let b: [f32; 3] =
+                    std::array::from_fn(|c| local.iter().map(|p| p[c]).fold(start, pick));
+                Value::from(b.map(raw).to_vec())
+            };
+            let acc = &mut n_json.accessors[index];
+            acc.min = Some(bound(f32::min, f32::MAX));
+            acc.max = Some(bound(f32::max, f32::MIN));
+        }
+
+        for n in users {
+            let name = n_json.nodes[n]
+                .name
+                .as_ref()
+                .map(|name| format!("{name}_quantized"));
+            let mesh = n_json.nodes[n].mesh.take();
+            let child = n_json.push(Node {
+                name,
+                mesh,
+                translation: Some(center),
+                scale: Some([scale; 3]),
+                ..Default::default()
+            });
+            n_json.nodes[n]
+                .children
+                .get_or_insert_with(Vec::new)
+                .push(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn quantized_positions_move_to_child_node() {
+        use gltf::json::mesh::Semantic;
+
+        use crate::glb::read_glb;
+
+        let options = OptimizeOptions {
+            quantize_attributes: true,
+            ..Default::default()
+        };
+        let quantize = |skin| {
+            let glb = SyntheticGlb {
+                skin,
+                ..Default::default()
+            }
+            .build()
+            .unwrap();
+            let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+            let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            (o_json, o_blob, n_json, n_blob)
+        };
+        let position = |json: &Root| {
+            json.meshes[0].primitives[0].attributes[&Checked::Valid(Semantic::Positions)]
+        };
+
+        let (o_json, o_blob, n_json, n_blob) = quantize(false);
+        assert!(
+            n_json
+                .extensions_required
+                .contains(&"KHR_mesh_quantization".to_string())
+        );
+        let acc = &n_json.accessors[position(&n_json).value()];
+        assert!(acc.normalized);
+        assert!(matches!(
+            acc.component_type,
+            Checked::Valid(GenericComponentType(ComponentType::I16))
+        ));
+        assert!(n_json.nodes[0].mesh.is_none());
+        let child = &n_json.nodes[n_json.nodes[0].children.as_ref().unwrap()[0].value()];
+        assert_eq!(child.mesh, Some(Index::new(0)));
+        let (center, scale) = (child.translation.unwrap(), child.scale.unwrap());
+
+        let source = read_accessor::<3>(&o_blob, &o_json, position(&o_json)).unwrap();
+        let quantized = read_accessor::<3>(&n_blob, &n_json, position(&n_json)).unwrap();
+        for (a, b) in source.iter().zip(&quantized) {
+            for c in 0..3 {
+                assert!((a[c] - (b[c] * scale[c] + center[c])).abs() < 1e-3);
+            }
+        }
+
+        // Skinned vertices are placed by their joints, so positions stay float
+        let (_, _, n_json, _) = quantize(true);
+        let acc = &n_json.accessors[position(&n_json).value()];
+        assert!(matches!(
+            acc.component_type,
+            Checked::Valid(GenericComponentType(ComponentType::F32))
+        ));
+        assert!(n_json.nodes.iter().any(|n| n.mesh == Some(Index::new(0))));
+    }
+}
@@ -0,0 +1,362 @@
+use std::{ops::Range, vec};
+
+use gltf::json::{
+    Accessor, Index, Root,
+    accessor::{ComponentType, GenericComponentType, Type},
+    mesh::{Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    convert::{decode_component, read_accessor},
+    error::{AccessorProblem, OptimizeError},
+    glb::accessor_stride,
+};
+
+/// Error for an accessor whose data can't be read
+fn unreadable(accessor: Index<Accessor>, problem: AccessorProblem) -> OptimizeError {
+    OptimizeError::Accessor {
+        accessor: accessor.value(),
+        problem,
+    }
+}
+
+/// Accessor of a primitive attribute, failing when the primitive has none
+fn attribute(primitive: &Primitive, semantic: Semantic) -> Result<Index<Accessor>, OptimizeError> {
+    primitive
+        .attributes
+        .get(&Checked::Valid(semantic.clone()))
+        .copied()
+        .ok_or_else(|| OptimizeError::MissingAttribute {
+            semantic: format!("{semantic:?}"),
+        })
+}
+
+/// Elements of an accessor, decoded from the blob one at a time
+///
+/// Plain strided data is read in place; sparse accessors and ones without a buffer view are
+/// decoded up front by [`read_accessor`].
+#[derive(Debug, Clone)]
+enum Elements<'a, const N: usize> {
+    Strided {
+        data: &'a [u8],
+        stride: usize,
+        component: ComponentType,
+        normalized: bool,
+        next: Range<usize>,
+    },
+    Decoded(vec::IntoIter<[f32; N]>),
+}
+
+impl<'a, const N: usize> Elements<'a, N> {
+    fn new(blob: &'a [u8], json: &Root, index: Index<Accessor>) -> Result<Self, OptimizeError> {
+        let acc = json
+            .accessors
+            .get(index.value())
+            .ok_or_else(|| unreadable(index, AccessorProblem::Missing))?;
+        let component = match (acc.component_type, acc.type_) {
+            (Checked::Valid(GenericComponentType(component)), Checked::Valid(ty))
+                if ty.multiplicity() == N =>
+            {
+                component
+            }
+            _ => {
+                return Err(unreadable(
+                    index,
+                    AccessorProblem::Components { expected: N },
+                ));
+            }
+        };
+        let view = acc
+            .buffer_view
+            .and_then(|v| json.buffer_views.get(v.value()));
+        let (Some(view), None) = (view, &acc.sparse) else {
+            let elements = read_accessor::<N>(blob, json, index)
+                .ok_or_else(|| unreadable(index, AccessorProblem::OutOfBounds))?;
+            return Ok(Elements::Decoded(elements.into_iter()));
+        };
+
+        let count = acc.count.0 as usize;
+        let size = component.size() * N;
+        let stride = accessor_stride(json, index)
+            .ok_or_else(|| unreadable(index, AccessorProblem::ShortStride))?;
+        let offset = view.byte_offset.map_or(0, |o| o.0 as usize)
+            + acc.byte_offset.map_or(0, |o| o.0 as usize);
+        // Checked once here, so elements can be sliced without checks later
+        let end = match count {
+            0 => offset,
+            _ => offset + (count - 1) * stride + size,
+        };
+        let data = blob
+            .get(offset..end)
+            .ok_or_else(|| unreadable(index, AccessorProblem::OutOfBounds))?;
+        Ok(Elements::Strided {
+            data,
+            stride,
+            component,
+            normalized: acc.normalized,
+            next: 0..count,
+        })
+    }
+}
+
+impl<const N: usize> Iterator for Elements<'_, N> {
+    type Item = [f32; N];
+
+    fn next(&mut self) -> Option<[f32; N]> {
+        match self {
+            Elements::Strided {
+                data,
+                stride,
+                component,
+                normalized,
+                next,
+            } => {
+                let bytes = &data[next.next()? * *stride..];
+                let size = component.size();
+                Some(std::array::from_fn(|c| {
+                    decode_component(*component, *normalized, &bytes[c * size..])
+                }))
+            }
+            Elements::Decoded(elements) => elements.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Elements::Strided { next, .. } => next.size_hint(),
+            Elements::Decoded(elements) => elements.size_hint(),
+        }
+    }
+}
+
+/// `POSITION` values of a primitive as floats
+///
+/// Reads the document in place, one vertex at a time, with the same stride and component
+/// handling the optimizer uses. Normalized integers are decoded to their float range; plain
+/// integer positions (`KHR_mesh_quantization`) come out as stored, before node transforms.
+#[derive(Debug, Clone)]
+pub struct PositionsIter<'a>(Elements<'a, 3>);
+
+impl<'a> PositionsIter<'a> {
+    /// Positions of `primitive`, reading `json`'s accessors out of the BIN chunk `blob`
+    ///
+    /// Fails when the primitive has no `POSITION` or its data is not VEC3 or out of bounds.
+    pub fn new(blob: &'a [u8], json: &Root, primitive: &Primitive) -> Result<Self, OptimizeError> {
+        let accessor = attribute(primitive, Semantic::Positions)?;
+        Ok(PositionsIter(Elements::new(blob, json, accessor)?))
+    }
+}
+
+impl Iterator for PositionsIter<'_> {
+    type Item = [f32; 3];
+
+    fn next(&mut self) -> Option<[f32; 3]> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for PositionsIter<'_> {}
+
+/// `TEXCOORD_0` values of a primitive, normalized integers decoded to `[0, 1]`
+#[derive(Debug, Clone)]
+pub struct Uv0Iter<'a>(Elements<'a, 2>);
+
+impl<'a> Uv0Iter<'a> {
+    /// First UV set of `primitive`, reading `json`'s accessors out of the BIN chunk `blob`
+    ///
+    /// Fails when the primitive has no `TEXCOORD_0`, or it is not float or normalized
+    /// 8/16-bit VEC2 data within bounds.
+    pub fn new(blob: &'a [u8], json: &Root, primitive: &Primitive) -> Result<Self, OptimizeError> {
+        let accessor = attribute(primitive, Semantic::TexCoords(0))?;
+        let valid =
+            json.accessors
+                .get(accessor.value())
+                .is_some_and(|acc| match acc.component_type {
+                    Checked::Valid(GenericComponentType(ComponentType::F32)) => true,
+                    Checked::Valid(GenericComponentType(
+                        ComponentType::U16 | ComponentType::U8,
+                    )) => acc.normalized,
+                    _ => false,
+                });
+        if !valid {
+            return Err(unreadable(
+                accessor,
+                AccessorProblem::Unusable {
+                    usage: "TEXCOORD_0",
+                },
+            ));
+        }
+        Ok(Uv0Iter(Elements::new(blob, json, accessor)?))
+    }
+}
+
+impl Iterator for Uv0Iter<'_> {
+    type Item = [f32; 2];
+
+    fn next(&mut self) -> Option<[f32; 2]> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Uv0Iter<'_> {}
+
+/// Vertex indices of a primitive, widened to `u32`
+///
+/// A primitive without indices yields `0..vertex_count`, so triangles can be walked the same
+/// way either way.
+#[derive(Debug, Clone)]
+pub struct IndicesIter<'a>(IndexSource<'a>);
+
+#[derive(Debug, Clone)]
+enum IndexSource<'a> {
+    Stored {
+        data: &'a [u8],
+        size: usize,
+        next: Range<usize>,
+    },
+    Sequential(Range<u32>),
+}
+
+impl<'a> IndicesIter<'a> {
+    /// Indices of `primitive`, reading `json`'s accessors out of the BIN chunk `blob`
+    ///
+    /// Fails when the indices are not unsigned 8, 16 or 32-bit scalars, are sparse or out of
+    /// bounds, or when a non-indexed primitive has no `POSITION` to count vertices by.
+    pub fn new(blob: &'a [u8], json: &Root, primitive: &Primitive) -> Result<Self, OptimizeError> {
+        let Some(index) = primitive.indices else {
+            let accessor = attribute(primitive, Semantic::Positions)?;
+            let count = json
+                .accessors
+                .get(accessor.value())
+                .ok_or_else(|| unreadable(accessor, AccessorProblem::Missing))?
+                .count
+                .0 as u32;
+            return Ok(IndicesIter(IndexSource::Sequential(0..count)));
+        };
+
+        let acc = json
+            .accessors
+            .get(index.value())
+            .ok_or_else(|| unreadable(index, AccessorProblem::Missing))?;
+        let size = match (acc.component_type, acc.type_) {
+            (
+                Checked::Valid(GenericComponentType(
+                    component @ (ComponentType::U8 | ComponentType::U16 | ComponentType::U32),
+                )),
+                Checked::Valid(Type::Scalar),
+            ) => component.size(),
+            _ => {
+                return Err(unreadable(
+                    index,
+                    AccessorProblem::Unusable {
+                        usage: "unsigned scalar index accessor",
+                    },
+                ));
+            }
+        };
+        let view = acc
+            .buffer_view
+            .filter(|_| acc.sparse.is_none())
+            .and_then(|v| json.buffer_views.get(v.value()))
+            .ok_or_else(|| unreadable(index, AccessorProblem::NoBufferView))?;
+        let offset = view.byte_offset.map_or(0, |o| o.0 as usize)
+            + acc.byte_offset.map_or(0, |o| o.0 as usize);
+        let count = acc.count.0 as usize;
+        let data = blob
+            .get(offset..offset + count * size)
+            .ok_or_else(|| unreadable(index, AccessorProblem::OutOfBounds))?;
+        Ok(IndicesIter(IndexSource::Stored {
+            data,
+            size,
+            next: 0..count,
+        }))
+    }
+}
+
+impl Iterator for IndicesIter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        match &mut self.0 {
+            IndexSource::Stored { data, size, next } => {
+                let at = next.next()? * *size;
+                Some(match *size {
+                    1 => data[at] as u32,
+                    2 => u16::from_le_bytes([data[at], data[at + 1]]) as u32,
+                    _ => u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]),
+                })
+            }
+            IndexSource::Sequential(indices) => indices.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IndexSource::Stored { next, .. } => next.size_hint(),
+            IndexSource::Sequential(indices) => indices.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IndicesIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{get_index_data, get_position_data, get_texcoord_data},
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn geometry_iterators_match_the_optimizer_readers() {
+        for interleaved in [false, true] {
+            let glb = SyntheticGlb {
+                interleaved,
+                sparse_morph_targets: true,
+                ..Default::default()
+            }
+            .build()
+            .unwrap();
+            let (json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+            let primitive = &json.meshes[0].primitives[0];
+            let attribute = |semantic| primitive.attributes[&Checked::Valid(semantic)];
+
+            let positions = PositionsIter::new(&blob, &json, primitive).unwrap();
+            assert_eq!(positions.len(), 4);
+            let expected = get_position_data(&blob, &json, attribute(Semantic::Positions));
+            assert_eq!(Some(positions.collect()), expected);
+            let uvs: Vec<_> = Uv0Iter::new(&blob, &json, primitive).unwrap().collect();
+            let expected = get_texcoord_data(&blob, &json, attribute(Semantic::TexCoords(0)));
+            assert_eq!(Some(uvs), expected);
+            let indices: Vec<_> = IndicesIter::new(&blob, &json, primitive).unwrap().collect();
+            assert_eq!(
+                Some(indices),
+                get_index_data(&blob, &json, primitive.indices.unwrap())
+            );
+
+            let mut unindexed = primitive.clone();
+            unindexed.indices = None;
+            let sequential: Vec<_> = IndicesIter::new(&blob, &json, &unindexed)
+                .unwrap()
+                .collect();
+            assert_eq!(sequential, [0, 1, 2, 3]);
+            unindexed
+                .attributes
+                .remove(&Checked::Valid(Semantic::TexCoords(0)));
+            assert!(Uv0Iter::new(&blob, &json, &unindexed).is_err());
+        }
+    }
+}
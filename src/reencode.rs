@@ -0,0 +1,100 @@
+use crate::{decode::is_reoriented, environment::texture_dimensions, stats::image_dimensions};
+
+/// Rules for copying source images as they are instead of resizing and re-encoding them, see
+/// [`OptimizeOptions::skip_reencode`](crate::OptimizeOptions::skip_reencode)
+///
+/// An image is kept when any rule matches. Formats are told apart by their leading bytes, not
+/// by the declared MIME type. Images with an EXIF orientation are always re-encoded, upright.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ReencodeSkip {
+    /// Keep JPEGs smaller than this many bytes
+    pub jpeg_below: Option<u64>,
+    /// Keep PNGs smaller than this many bytes
+    pub png_below: Option<u64>,
+    /// Keep images that already fit their slot's size and store at most this many bits per
+    /// pixel
+    pub fitting_bits_per_pixel: Option<f32>,
+}
+
+impl ReencodeSkip {
+    /// Whether an encoded source image stays as it is in a slot of `size`
+    pub(crate) fn keeps(&self, data: &[u8], size: u32, power_of_two: bool) -> bool {
+        self.matches(data, size, power_of_two) && !is_reoriented(data)
+    }
+
+    /// Whether any rule matches an encoded source image in a slot of `size`
+    fn matches(&self, data: &[u8], size: u32, power_of_two: bool) -> bool {
+        let bytes = data.len() as u64;
+        let below = |limit: Option<u64>| limit.is_some_and(|limit| bytes < limit);
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) && below(self.jpeg_below) {
+            return true;
+        }
+        if data.starts_with(b"\x89PNG") && below(self.png_below) {
+            return true;
+        }
+
+        let (Some(max_bpp), Some((width, height))) =
+            (self.fitting_bits_per_pixel, image_dimensions(data))
+        else {
+            return false;
+        };
+        let pixels = width as u64 * height as u64;
+        texture_dimensions(width, height, size, power_of_two) == (width, height)
+            && pixels > 0
+            && (bytes * 8) as f32 / pixels as f32 <= max_bpp
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn small_sources_skip_reencode() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let source = get_image_data(&o_blob, &o_json, Index::new(0)).unwrap();
+        let output_image = |skip_reencode: ReencodeSkip, texture_size: u32| {
+            let options = OptimizeOptions {
+                skip_reencode,
+                ..OptimizeOptions::default().texture_size(texture_size)
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            get_image_data(&n_blob, &n_json, Index::new(0))
+                .unwrap()
+                .to_vec()
+        };
+
+        let below = |limit: u64| ReencodeSkip {
+            png_below: Some(limit),
+            ..Default::default()
+        };
+        assert_eq!(output_image(below(source.len() as u64 + 1), 16), source);
+        assert_ne!(output_image(below(source.len() as u64), 16), source);
+        // 32x32 fits a 64 pixel slot but not a 16 pixel one
+        let fitting = ReencodeSkip {
+            fitting_bits_per_pixel: Some(64.0),
+            ..Default::default()
+        };
+        assert_eq!(output_image(fitting, 64), source);
+        assert_ne!(output_image(fitting, 16), source);
+        assert_ne!(output_image(ReencodeSkip::default(), 64), source);
+    }
+}
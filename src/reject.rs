@@ -0,0 +1,145 @@
+use std::fmt;
+
+use gltf::json::Root;
+
+use crate::{
+    bounds::{for_each_mesh_instance, scene_roots},
+    error::OptimizeError,
+    stats::primitive_triangles,
+};
+
+/// Size and complexity limits checked before any processing, see
+/// [`OptimizeOptions::reject_if`](crate::OptimizeOptions::reject_if)
+///
+/// Unset limits are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RejectLimits {
+    /// Most triangles the scenes may draw, counting every mesh instance
+    pub max_triangles: Option<u64>,
+    /// Most textures the document may declare
+    pub max_textures: Option<usize>,
+    /// Most bytes of JSON plus binary chunk, about the size of the input GLB
+    pub max_input_bytes: Option<u64>,
+}
+
+/// Limit of [`RejectLimits`] an input exceeded, see [`OptimizeError::Rejected`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RejectLimit {
+    Triangles,
+    Textures,
+    InputBytes,
+}
+
+impl fmt::Display for RejectLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RejectLimit::Triangles => "triangles",
+            RejectLimit::Textures => "textures",
+            RejectLimit::InputBytes => "input bytes",
+        })
+    }
+}
+
+impl RejectLimits {
+    /// Fail with [`OptimizeError::Rejected`] on the first limit the input exceeds
+    ///
+    /// Runs before the source is rewritten, so the counts are those of the input as read.
+    pub(crate) fn check(&self, o_blob: &[u8], o_json: &Root) -> Result<(), OptimizeError> {
+        let reject = |limit, value: u64, max: u64| {
+            if value > max {
+                Err(OptimizeError::Rejected { limit, value, max })
+            } else {
+                Ok(())
+            }
+        };
+        if let Some(max) = self.max_input_bytes {
+            let json = serde_json::to_vec(o_json)?.len();
+            reject(RejectLimit::InputBytes, (json + o_blob.len()) as u64, max)?;
+        }
+        if let Some(max) = self.max_textures {
+            reject(
+                RejectLimit::Textures,
+                o_json.textures.len() as u64,
+                max as u64,
+            )?;
+        }
+        if let Some(max) = self.max_triangles {
+            let mut triangles = 0u64;
+            for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh, _| {
+                for primitive in o_json
+                    .meshes
+                    .get(mesh)
+                    .into_iter()
+                    .flat_map(|m| &m.primitives)
+                {
+                    triangles += primitive_triangles(o_json, primitive);
+                }
+            });
+            reject(RejectLimit::Triangles, triangles, max)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+    #[test]
+    fn inputs_over_reject_limits_fail_before_processing() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run = |reject_if| {
+            let options = OptimizeOptions {
+                reject_if,
+                ..Default::default()
+            };
+            optimize_with(&mut Cursor::new(&glb), &options)
+        };
+        let rejected = |reject_if| match run(reject_if) {
+            Err(OptimizeError::Rejected { limit, value, max }) => {
+                assert!(value > max);
+                Some(limit)
+            }
+            _ => None,
+        };
+
+        assert_eq!(
+            rejected(RejectLimits {
+                max_textures: Some(1),
+                ..Default::default()
+            }),
+            Some(RejectLimit::Textures)
+        );
+        assert_eq!(
+            rejected(RejectLimits {
+                max_triangles: Some(1),
+                ..Default::default()
+            }),
+            Some(RejectLimit::Triangles)
+        );
+        assert_eq!(
+            rejected(RejectLimits {
+                max_input_bytes: Some(1024),
+                ..Default::default()
+            }),
+            Some(RejectLimit::InputBytes)
+        );
+        run(RejectLimits {
+            max_triangles: Some(1_000_000),
+            max_textures: Some(2),
+            max_input_bytes: Some(glb.len() as u64),
+        })
+        .unwrap();
+    }
+}
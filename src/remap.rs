@@ -0,0 +1,199 @@
+/// Output index of each input object, indexed by input index
+///
+/// Objects copied several times map to their first copy; `None` means the object was dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RemapTables {
+    pub nodes: Vec<Option<usize>>,
+    pub meshes: Vec<Option<usize>>,
+    pub materials: Vec<Option<usize>>,
+    pub textures: Vec<Option<usize>>,
+    pub accessors: Vec<Option<usize>>,
+    /// Input names, for lookups by name
+    pub(crate) node_names: Vec<Option<String>>,
+    pub(crate) mesh_names: Vec<Option<String>>,
+    pub(crate) material_names: Vec<Option<String>>,
+}
+
+impl RemapTables {
+    /// Whether every material and texture kept its input index
+    pub fn is_identity(&self) -> bool {
+        is_identity(&self.materials) && is_identity(&self.textures)
+    }
+
+    /// New index of the first input node called `name`
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        find(&self.node_names, &self.nodes, name)
+    }
+
+    /// New index of the first input mesh called `name`
+    pub fn find_mesh(&self, name: &str) -> Option<usize> {
+        find(&self.mesh_names, &self.meshes, name)
+    }
+
+    /// New index of the first input material called `name`
+    pub fn find_material(&self, name: &str) -> Option<usize> {
+        find(&self.material_names, &self.materials, name)
+    }
+
+    /// New index of input node `old`
+    pub fn node(&self, old: usize) -> Option<usize> {
+        self.nodes.get(old).copied().flatten()
+    }
+
+    /// New index of input mesh `old`
+    pub fn mesh(&self, old: usize) -> Option<usize> {
+        self.meshes.get(old).copied().flatten()
+    }
+
+    /// New index of input material `old`
+    pub fn material(&self, old: usize) -> Option<usize> {
+        self.materials.get(old).copied().flatten()
+    }
+
+    /// New index of input texture `old`
+    pub fn texture(&self, old: usize) -> Option<usize> {
+        self.textures.get(old).copied().flatten()
+    }
+
+    /// New index of input accessor `old`
+    pub fn accessor(&self, old: usize) -> Option<usize> {
+        self.accessors.get(old).copied().flatten()
+    }
+}
+
+fn find(names: &[Option<String>], table: &[Option<usize>], name: &str) -> Option<usize> {
+    let old = names.iter().position(|n| n.as_deref() == Some(name))?;
+    table.get(old).copied().flatten()
+}
+
+fn is_identity(table: &[Option<usize>]) -> bool {
+    table.iter().enumerate().all(|(i, new)| *new == Some(i))
+}
+
+/// Record the first copy of input object `old`
+pub(crate) fn note_index(table: &mut [Option<usize>], old: usize, new: usize) {
+    if let Some(slot @ None) = table.get_mut(old) {
+        *slot = Some(new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::get_position_data,
+        optimizer::Optimizer,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[cfg(feature = "png")]
+    /// Three textured meshes, the first and last using each other's material
+    fn crossed_glb() -> Vec<u8> {
+        let glb = SyntheticGlb {
+            meshes: 3,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.meshes[0].primitives[0].material = Some(Index::new(2));
+        json.meshes[2].primitives[0].material = Some(Index::new(0));
+        write_glb(&json, blob).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn stable_indices_keep_input_order() {
+        let options = OptimizeOptions::default();
+        let remap = Optimizer::new(&options)
+            .run(&mut Cursor::new(crossed_glb()))
+            .unwrap()
+            .remap;
+        assert!(!remap.is_identity());
+        assert_eq!(remap.material(2), Some(0));
+        assert_eq!(remap.material(0), Some(2));
+
+        let options = OptimizeOptions {
+            stable_indices: true,
+            ..Default::default()
+        };
+        let optimized = Optimizer::new(&options)
+            .run(&mut Cursor::new(crossed_glb()))
+            .unwrap();
+        assert!(optimized.remap.is_identity());
+        let (json, _) = read_glb(&mut Cursor::new(optimized.glb)).unwrap();
+        let names: Vec<_> = json.materials.iter().map(|m| m.name.as_deref()).collect();
+        assert_eq!(
+            names,
+            [Some("material_0"), Some("material_1"), Some("material_2")]
+        );
+        assert_eq!(json.meshes[0].primitives[0].material, Some(Index::new(2)));
+    }
+
+    #[test]
+    fn dropped_objects_have_no_new_index() {
+        let glb = SyntheticGlb {
+            meshes: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        o_json.scenes[0].nodes.remove(1);
+        let glb = write_glb(&o_json, o_blob.clone()).unwrap();
+
+        let options = OptimizeOptions::default();
+        let optimized = Optimizer::new(&options).run(&mut Cursor::new(glb)).unwrap();
+        let remap = optimized.remap;
+        assert_eq!(remap.nodes, [Some(0), None, Some(1)]);
+        assert_eq!(remap.meshes, [Some(0), None, Some(1)]);
+
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(optimized.glb)).unwrap();
+        let position = gltf::json::validation::Checked::Valid(gltf::Semantic::Positions);
+        for (m, mesh) in o_json.meshes.iter().enumerate() {
+            let old = mesh.primitives[0].attributes[&position];
+            let Some(new) = remap.accessor(old.value()) else {
+                assert_eq!(m, 1);
+                continue;
+            };
+            assert_ne!(m, 1);
+            let source = get_position_data(&o_blob, &o_json, old);
+            assert!(source.is_some());
+            assert_eq!(
+                get_position_data(&n_blob, &n_json, Index::new(new as u32)),
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn lookups_go_by_input_name_and_index() {
+        let remap = RemapTables {
+            nodes: vec![Some(1), None, Some(0)],
+            meshes: vec![None, Some(0)],
+            materials: vec![Some(0)],
+            node_names: vec![Some("a".into()), Some("b".into()), Some("a".into())],
+            mesh_names: vec![Some("m".into()), None],
+            material_names: vec![None],
+            ..Default::default()
+        };
+        assert_eq!(remap.find_node("a"), Some(1));
+        assert_eq!(remap.find_node("b"), None);
+        assert_eq!(remap.find_node("c"), None);
+        assert_eq!(remap.find_mesh("m"), None);
+        assert_eq!(remap.find_material("m"), None);
+        assert_eq!(remap.node(2), Some(0));
+        assert_eq!(remap.node(3), None);
+        assert_eq!(remap.mesh(1), Some(0));
+        assert_eq!(remap.texture(0), None);
+        assert!(remap.is_identity());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use gltf::json::Root;
+
+use crate::{
+    error::OptimizeError,
+    glb::{read_glb, write_glb},
+};
+
+/// Kind of object a name belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NamedObject {
+    Node,
+    Mesh,
+    Material,
+}
+
+/// Source of new names for nodes, meshes and materials
+///
+/// Implemented for a `HashMap` from old to new name, applied to every kind of object, and for
+/// closures taking the object kind and its current name.
+pub trait Rename: Send + Sync {
+    /// New name for an object, or `None` to keep the current one
+    fn rename(&self, object: NamedObject, name: &str) -> Option<String>;
+}
+
+impl Rename for HashMap<String, String> {
+    fn rename(&self, _object: NamedObject, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+impl<F: Fn(NamedObject, &str) -> Option<String> + Send + Sync> Rename for F {
+    fn rename(&self, object: NamedObject, name: &str) -> Option<String> {
+        self(object, name)
+    }
+}
+
+/// Rename the named nodes, meshes and materials of a document
+pub(crate) fn apply_renames(n_json: &mut Root, renamer: &dyn Rename) {
+    let names = n_json
+        .nodes
+        .iter_mut()
+        .map(|n| (NamedObject::Node, &mut n.name))
+        .chain(
+            n_json
+                .meshes
+                .iter_mut()
+                .map(|m| (NamedObject::Mesh, &mut m.name)),
+        )
+        .chain(
+            n_json
+                .materials
+                .iter_mut()
+                .map(|m| (NamedObject::Material, &mut m.name)),
+        );
+
+    for (object, name) in names {
+        if let Some(new) = name.as_deref().and_then(|n| renamer.rename(object, n)) {
+            *name = Some(new);
+        }
+    }
+}
+
+/// Rename nodes, meshes and materials and write the GLB again, leaving the binary chunk as is
+pub fn rename_objects<R: Read + Seek>(
+    reader: &mut R,
+    renamer: &dyn Rename,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (mut o_json, o_blob) = read_glb(reader)?;
+    apply_renames(&mut o_json, renamer);
+    write_glb(&o_json, o_blob)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{optimizer::Optimizer, options::OptimizeOptions, testing::SyntheticGlb};
+
+    type Names = Vec<Option<String>>;
+
+    /// Node, mesh and material names of a GLB
+    fn names(glb: &[u8]) -> (Names, Names, Names) {
+        let (json, _) = read_glb(&mut Cursor::new(glb)).unwrap();
+        (
+            json.nodes.into_iter().map(|n| n.name).collect(),
+            json.meshes.into_iter().map(|m| m.name).collect(),
+            json.materials.into_iter().map(|m| m.name).collect(),
+        )
+    }
+
+    fn some(names: &[&str]) -> Vec<Option<String>> {
+        names.iter().map(|n| Some(n.to_string())).collect()
+    }
+
+    #[test]
+    fn tables_rename_every_kind_of_object() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let table: HashMap<String, String> = [("mesh_0", "body"), ("node_1", "arm")]
+            .into_iter()
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect();
+
+        let out = rename_objects(&mut Cursor::new(&glb), &table).unwrap();
+        let (nodes, meshes, _) = names(&out);
+        assert_eq!(nodes, some(&["node_0", "arm"]));
+        assert_eq!(meshes, some(&["body", "mesh_1"]));
+
+        let options = OptimizeOptions::default();
+        let out = Optimizer::new(&options)
+            .renames(&table)
+            .run(&mut Cursor::new(&glb));
+        let (nodes, meshes, _) = names(&out.unwrap().glb);
+        assert!(nodes.contains(&Some("arm".into())));
+        assert!(meshes.contains(&Some("body".into())));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn closures_see_the_object_kind() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let renamer = |object: NamedObject, name: &str| {
+            (object == NamedObject::Material).then(|| name.to_uppercase())
+        };
+        let out = rename_objects(&mut Cursor::new(&glb), &renamer).unwrap();
+        let (nodes, meshes, materials) = names(&out);
+        assert_eq!(nodes, some(&["node_0"]));
+        assert_eq!(meshes, some(&["mesh_0"]));
+        assert_eq!(materials, some(&["MATERIAL_0"]));
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+
+use gltf::json::{
+    Root,
+    mesh::{Mode, MorphTarget, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{gather_accessor, push_indices},
+    math::bounds,
+    opt::{get_index_data, get_position_data},
+};
+
+/// Vertex order written by [`OptimizeOptions::reorder_vertices`](crate::OptimizeOptions::reorder_vertices)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VertexReorder {
+    /// Keep the source order
+    #[default]
+    Off,
+    /// Reorder triangles for the post-transform vertex cache, then vertices by first use
+    Cache,
+    /// Sort vertices along a Morton curve through the primitive's bounds
+    Spatial,
+}
+
+/// Simulated vertex cache size for [`VertexReorder::Cache`]
+const CACHE_SIZE: usize = 16;
+
+/// Triangle order for the vertex cache (Tipsify, Sander et al. 2007)
+fn tipsify(indices: &[u32], vertex_count: usize) -> Vec<usize> {
+    let triangle_count = indices.len() / 3;
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for &v in tri {
+            adjacency[v as usize].push(t);
+        }
+    }
+    let mut live: Vec<usize> = adjacency.iter().map(Vec::len).collect();
+    let mut cache_time = vec![0usize; vertex_count];
+    let mut emitted = vec![false; triangle_count];
+    let mut dead_end: Vec<u32> = Vec::new();
+    let mut order = Vec::with_capacity(triangle_count);
+    let mut time = CACHE_SIZE + 1;
+    let mut cursor = 0;
+    let mut fan = Some(0);
+
+    while let Some(f) = fan {
+        let mut candidates = Vec::new();
+        for &t in &adjacency[f] {
+            if emitted[t] {
+                continue;
+            }
+            for &v in &indices[t * 3..t * 3 + 3] {
+                let v = v as usize;
+                dead_end.push(v as u32);
+                candidates.push(v);
+                live[v] -= 1;
+                if time - cache_time[v] > CACHE_SIZE {
+                    cache_time[v] = time;
+                    time += 1;
+                }
+            }
+            emitted[t] = true;
+            order.push(t);
+        }
+
+        // Prefer a candidate that stays in cache while its remaining triangles are emitted
+        fan = candidates
+            .iter()
+            .copied()
+            .filter(|&v| live[v] > 0)
+            .max_by_key(|&v| {
+                let age = time - cache_time[v];
+                if age + 2 * live[v] <= CACHE_SIZE {
+                    age
+                } else {
+                    0
+                }
+            })
+            .or_else(|| {
+                while let Some(v) = dead_end.pop() {
+                    if live[v as usize] > 0 {
+                        return Some(v as usize);
+                    }
+                }
+                while cursor < vertex_count {
+                    if live[cursor] > 0 {
+                        return Some(cursor);
+                    }
+                    cursor += 1;
+                }
+                None
+            });
+    }
+    order
+}
+
+/// Spread the low 10 bits of `x` to every third bit
+fn spread_bits(x: u32) -> u32 {
+    let mut x = x & 0x3ff;
+    x = (x | (x << 16)) & 0x0300_00ff;
+    x = (x | (x << 8)) & 0x0300_f00f;
+    x = (x | (x << 4)) & 0x030c_30c3;
+    (x | (x << 2)) & 0x0924_9249
+}
+
+/// 30-bit Morton code of each position within the bounds
+fn morton_codes(positions: &[[f32; 3]]) -> Vec<u32> {
+    let (min, max) = bounds(positions);
+    let extent = (0..3).map(|a| max[a] - min[a]).fold(0.0f32, f32::max);
+    let scale = if extent > 0.0 { 1023.0 / extent } else { 0.0 };
+    positions
+        .iter()
+        .map(|p| {
+            let q = [0, 1, 2].map(|a| ((p[a] - min[a]) * scale) as u32);
+            spread_bits(q[0]) | (spread_bits(q[1]) << 1) | (spread_bits(q[2]) << 2)
+        })
+        .collect()
+}
+
+/// New vertex order (old index per new index) and reordered indices for one primitive
+fn vertex_order(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    mode: VertexReorder,
+) -> (Vec<u32>, Vec<u32>) {
+    let vertex_count = positions.len();
+    let triangles: Vec<usize> = match mode {
+        VertexReorder::Cache => tipsify(indices, vertex_count),
+        _ => (0..indices.len() / 3).collect(),
+    };
+
+    let mut order: Vec<u32> = Vec::with_capacity(vertex_count);
+    if mode == VertexReorder::Spatial {
+        let codes = morton_codes(positions);
+        order.extend(0..vertex_count as u32);
+        order.sort_by_key(|&v| (codes[v as usize], v));
+    } else {
+        // First use in triangle order, unreferenced vertices last
+        let mut seen = vec![false; vertex_count];
+        for &t in &triangles {
+            for &v in &indices[t * 3..t * 3 + 3] {
+                if !std::mem::replace(&mut seen[v as usize], true) {
+                    order.push(v);
+                }
+            }
+        }
+        order.extend((0..vertex_count as u32).filter(|&v| !seen[v as usize]));
+    }
+
+    let mut remap = vec![0u32; vertex_count];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old as usize] = new as u32;
+    }
+    let mut triangles: Vec<[u32; 3]> = triangles
+        .iter()
+        .map(|&t| [0, 1, 2].map(|c| remap[indices[t * 3 + c] as usize]))
+        .collect();
+    if mode == VertexReorder::Spatial {
+        triangles.sort_by_key(|t| t.iter().copied().min());
+    }
+    let mut reordered: Vec<u32> = triangles.into_iter().flatten().collect();
+    // A trailing partial triangle stays at the end
+    reordered.extend(
+        indices[indices.len() / 3 * 3..]
+            .iter()
+            .map(|&v| remap[v as usize]),
+    );
+    (order, reordered)
+}
+
+/// Reorder one primitive's vertices, `None` to leave it as it is
+fn reorder_primitive(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    primitive: &Primitive,
+    mode: VertexReorder,
+) -> Option<Primitive> {
+    if primitive.mode != Checked::Valid(Mode::Triangles) {
+        return None;
+    }
+    let position = *primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    let positions = get_position_data(o_blob, o_json, position)?;
+    let indices = get_index_data(o_blob, o_json, primitive.indices?)?;
+    if indices.len() < 3 || indices.iter().any(|&i| i as usize >= positions.len()) {
+        return None;
+    }
+
+    let (order, reordered) = vertex_order(&indices, &positions, mode);
+    let mut n_primitive = primitive.clone();
+    for (semantic, accessor) in &primitive.attributes {
+        let gathered = gather_accessor(o_blob, o_json, n_blob, n_json, *accessor, &order)?;
+        n_primitive.attributes.insert(semantic.clone(), gathered);
+    }
+    if let Some(targets) = &primitive.targets {
+        let mut n_targets = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mut gather = |accessor: Option<_>| match accessor {
+                Some(a) => gather_accessor(o_blob, o_json, n_blob, n_json, a, &order).map(Some),
+                None => Some(None),
+            };
+            n_targets.push(MorphTarget {
+                positions: gather(target.positions)?,
+                normals: gather(target.normals)?,
+                tangents: gather(target.tangents)?,
+            });
+        }
+        n_primitive.targets = Some(n_targets);
+    }
+    n_primitive.indices = Some(push_indices(n_blob, n_json, &reordered));
+    Some(n_primitive)
+}
+
+/// Indices of every attribute and morph target accessor of a primitive
+pub(crate) fn vertex_accessors(primitive: &Primitive) -> impl Iterator<Item = usize> + '_ {
+    let targets = primitive.targets.iter().flatten();
+    primitive
+        .attributes
+        .values()
+        .copied()
+        .chain(targets.flat_map(|t| [t.positions, t.normals, t.tangents].into_iter().flatten()))
+        .map(|a| a.value())
+}
+
+/// Reorder the vertices of indexed triangle primitives for compression and GPU locality
+///
+/// Every attribute and morph target is permuted together and the indices are rewritten.
+/// Primitives sharing a vertex accessor with another primitive, and unindexed or sparse
+/// ones, keep their order. Returns `None` when nothing changed.
+pub(crate) fn reorder_vertices(
+    o_blob: &[u8],
+    o_json: &Root,
+    mode: VertexReorder,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    if mode == VertexReorder::Off {
+        return Ok(None);
+    }
+
+    let mut uses: HashMap<usize, usize> = HashMap::new();
+    for primitive in o_json.meshes.iter().flat_map(|m| &m.primitives) {
+        for accessor in vertex_accessors(primitive) {
+            *uses.entry(accessor).or_default() += 1;
+        }
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed = false;
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        for (p, primitive) in mesh.primitives.iter().enumerate() {
+            if vertex_accessors(primitive).any(|a| uses[&a] > 1) {
+                continue;
+            }
+            if let Some(reordered) =
+                reorder_primitive(o_blob, o_json, &mut n_blob, &mut n_json, primitive, mode)
+            {
+                n_json.meshes[m].primitives[p] = reordered;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{glb::read_glb, testing::SyntheticGlb};
+
+    /// Triangles by source vertex, each rotated to start at its smallest index, sorted
+    fn normalized(triangles: impl Iterator<Item = [u32; 3]>) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = triangles
+            .map(|t| {
+                let first = (0..3).min_by_key(|&c| t[c]).unwrap();
+                [0, 1, 2].map(|c| t[(first + c) % 3])
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    /// A 3x3 grid of vertices, listed and indexed in scrambled order
+    fn scrambled_grid() -> (Vec<u32>, Vec<[f32; 3]>) {
+        let slots = [4u32, 8, 0, 6, 2, 7, 1, 5, 3];
+        let positions = (0..9)
+            .map(|v| {
+                let slot = slots.iter().position(|&s| s == v).unwrap() as f32;
+                [slot % 3.0, (slot / 3.0).floor(), 0.0]
+            })
+            .collect();
+        let at = |x: usize, y: usize| slots[y * 3 + x];
+        let indices = [(0, 0), (1, 1), (1, 0), (0, 1)]
+            .into_iter()
+            .flat_map(|(x, y)| {
+                let (a, b, c, d) = (at(x, y), at(x + 1, y), at(x + 1, y + 1), at(x, y + 1));
+                [a, b, c, a, c, d]
+            })
+            .collect();
+        (indices, positions)
+    }
+
+    #[test]
+    fn reordering_keeps_every_triangle_and_its_winding() {
+        let (indices, positions) = scrambled_grid();
+        let source = normalized(indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]));
+        for mode in [VertexReorder::Cache, VertexReorder::Spatial] {
+            let (order, reordered) = vertex_order(&indices, &positions, mode);
+            let mut sorted = order.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..9).collect::<Vec<_>>());
+            let triangles = reordered
+                .chunks_exact(3)
+                .map(|t| [0, 1, 2].map(|c| order[t[c] as usize]));
+            assert_eq!(normalized(triangles), source, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn cache_order_numbers_vertices_by_first_use() {
+        let (indices, positions) = scrambled_grid();
+        let (_, reordered) = vertex_order(&indices, &positions, VertexReorder::Cache);
+        let mut next = 0;
+        for &v in &reordered {
+            assert!(v <= next);
+            if v == next {
+                next += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn spatial_order_follows_the_morton_curve() {
+        let (indices, positions) = scrambled_grid();
+        let (order, _) = vertex_order(&indices, &positions, VertexReorder::Spatial);
+        let codes = morton_codes(&positions);
+        assert!(
+            order
+                .windows(2)
+                .all(|w| codes[w[0] as usize] <= codes[w[1] as usize])
+        );
+        assert_eq!(positions[order[0] as usize], [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn attributes_move_with_their_vertices() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        assert!(
+            reorder_vertices(&o_blob, &o_json, VertexReorder::Off)
+                .unwrap()
+                .is_none()
+        );
+        let (n_json, n_blob) = reorder_vertices(&o_blob, &o_json, VertexReorder::Spatial)
+            .unwrap()
+            .unwrap();
+
+        let corners = |blob: &[u8], json: &Root| {
+            let primitive = &json.meshes[0].primitives[0];
+            let position = primitive.attributes[&Checked::Valid(Semantic::Positions)];
+            let positions = get_position_data(blob, json, position).unwrap();
+            let indices = get_index_data(blob, json, primitive.indices.unwrap()).unwrap();
+            let mut corners: Vec<_> = indices
+                .chunks_exact(3)
+                .map(|t| t.iter().map(|&v| positions[v as usize]).collect::<Vec<_>>())
+                .collect();
+            corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            corners
+        };
+        assert_eq!(corners(&n_blob, &n_json), corners(&o_blob, &o_json));
+    }
+}
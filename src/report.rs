@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+
+use gltf::json::{Accessor, Index, Root};
+use serde_json::{Value, json};
+
+use crate::{
+    growth::GrowthFallback,
+    meshopt::chunk_range,
+    mips::mip_chain,
+    stats::{compute_gpu_stats, image_dimensions, stored_level_count},
+};
+
+/// A size or count in the input and in the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BeforeAfter {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl BeforeAfter {
+    /// How much smaller the output is, negative when it grew
+    pub fn saved(&self) -> i64 {
+        self.before as i64 - self.after as i64
+    }
+
+    pub(crate) fn to_json(self) -> Value {
+        json!({ "before": self.before, "after": self.after })
+    }
+}
+
+/// An embedded image as stored in a GLB
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StoredImage {
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: Option<String>,
+    /// Bytes stored in the binary chunk
+    pub bytes: u64,
+    /// Width and height of every stored level, the base first; KTX2 files with a mip chain
+    /// list each level, other images only the base
+    pub levels: Vec<(u32, u32)>,
+}
+
+/// One input texture as read and as written
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextureChange {
+    /// Index of the texture in the input `textures` array
+    pub texture: usize,
+    /// Output texture it became, `None` when it was dropped
+    pub output_texture: Option<usize>,
+    /// `None` when the image is not embedded or its header can't be read
+    pub before: Option<StoredImage>,
+    pub after: Option<StoredImage>,
+}
+
+/// Where the bytes of an optimized GLB went, per category
+///
+/// Buffer views are counted with their stored size, so meshopt-compressed views count their
+/// compressed bytes. A view shared by two categories is counted in both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OptimizeReport {
+    /// The whole file
+    pub file: BeforeAfter,
+    /// The JSON chunk
+    pub json: BeforeAfter,
+    /// Views of mesh attributes, indices and morph targets
+    pub geometry: BeforeAfter,
+    /// Views of animation samplers and skin inverse bind matrices
+    pub animation: BeforeAfter,
+    /// Views of embedded images
+    pub texture_bytes: BeforeAfter,
+    pub textures: Vec<TextureChange>,
+    /// Draw calls of the default scene, one per primitive per mesh instance
+    pub draw_calls: BeforeAfter,
+    /// Unique vertices of the default scene
+    pub vertices: BeforeAfter,
+    /// Triangles drawn in the default scene
+    pub triangles: BeforeAfter,
+    /// What [`OptimizeOptions::never_grow`](crate::OptimizeOptions::never_grow) did about an
+    /// output larger than the input; the same as
+    /// [`Optimized::growth_fallback`](crate::Optimized::growth_fallback), repeated so the
+    /// JSON report carries it
+    pub growth_fallback: Option<GrowthFallback>,
+}
+
+impl StoredImage {
+    fn to_json(&self) -> Value {
+        json!({
+            "width": self.width,
+            "height": self.height,
+            "mimeType": self.mime_type,
+            "bytes": self.bytes,
+            "levels": self.levels,
+        })
+    }
+}
+
+impl OptimizeReport {
+    /// The whole report as pretty-printed JSON, for build dashboards and scripts
+    ///
+    /// Keys are the field names in camelCase, sizes are in bytes and texture levels are
+    /// `[width, height]` pairs. Images that can't be read and dropped textures are `null`.
+    pub fn to_json(&self) -> String {
+        format!("{:#}", self.to_value())
+    }
+
+    pub(crate) fn to_value(&self) -> Value {
+        let textures: Vec<Value> = self
+            .textures
+            .iter()
+            .map(|t| {
+                json!({
+                    "texture": t.texture,
+                    "outputTexture": t.output_texture,
+                    "before": t.before.as_ref().map(StoredImage::to_json),
+                    "after": t.after.as_ref().map(StoredImage::to_json),
+                })
+            })
+            .collect();
+        let growth_fallback = self.growth_fallback.map(|fallback| match fallback {
+            GrowthFallback::TexturesPassedThrough => "texturesPassedThrough",
+            GrowthFallback::InputReturned => "inputReturned",
+        });
+        json!({
+            "file": self.file.to_json(),
+            "json": self.json.to_json(),
+            "geometry": self.geometry.to_json(),
+            "animation": self.animation.to_json(),
+            "textureBytes": self.texture_bytes.to_json(),
+            "textures": textures,
+            "drawCalls": self.draw_calls.to_json(),
+            "vertices": self.vertices.to_json(),
+            "triangles": self.triangles.to_json(),
+            "growthFallback": growth_fallback,
+        })
+    }
+}
+
+/// Sizes and counts of one document
+struct Measure {
+    file: u64,
+    json: u64,
+    geometry: u64,
+    animation: u64,
+    texture_bytes: u64,
+    draw_calls: u64,
+    vertices: u64,
+    triangles: u64,
+}
+
+fn measure(glb: &[u8], json: &Root, blob: &[u8]) -> Measure {
+    // A .gltf input is JSON throughout
+    let json_len = gltf::binary::Glb::from_slice(glb).map_or(glb.len(), |g| g.json.len());
+
+    let mut geometry = Vec::new();
+    for p in json.meshes.iter().flat_map(|m| &m.primitives) {
+        geometry.extend(p.attributes.values().copied());
+        geometry.extend(p.indices);
+        for target in p.targets.iter().flatten() {
+            geometry.extend(
+                [target.positions, target.normals, target.tangents]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+    }
+    let mut animation: Vec<Index<Accessor>> = json
+        .animations
+        .iter()
+        .flat_map(|a| &a.samplers)
+        .flat_map(|s| [s.input, s.output])
+        .collect();
+    animation.extend(json.skins.iter().filter_map(|s| s.inverse_bind_matrices));
+    let images = json
+        .images
+        .iter()
+        .filter_map(|i| i.buffer_view)
+        .map(|v| v.value());
+
+    let stats = compute_gpu_stats(blob, json);
+    Measure {
+        file: glb.len() as u64,
+        json: json_len as u64,
+        geometry: view_bytes(json, accessor_views(json, &geometry)),
+        animation: view_bytes(json, accessor_views(json, &animation)),
+        texture_bytes: view_bytes(json, images.collect()),
+        draw_calls: stats.draw_calls,
+        vertices: stats.vertices,
+        triangles: stats.triangles,
+    }
+}
+
+/// Views holding the data of `accessors`, sparse indices and values included
+fn accessor_views(json: &Root, accessors: &[Index<Accessor>]) -> HashSet<usize> {
+    let mut views = HashSet::new();
+    for accessor in accessors
+        .iter()
+        .filter_map(|a| json.accessors.get(a.value()))
+    {
+        views.extend(accessor.buffer_view.map(|v| v.value()));
+        if let Some(sparse) = &accessor.sparse {
+            views.insert(sparse.indices.buffer_view.value());
+            views.insert(sparse.values.buffer_view.value());
+        }
+    }
+    views
+}
+
+fn view_bytes(json: &Root, views: HashSet<usize>) -> u64 {
+    views
+        .into_iter()
+        .filter_map(|v| json.buffer_views.get(v))
+        .map(|view| chunk_range(view).len() as u64)
+        .sum()
+}
+
+/// The image texture `texture` samples, as stored
+pub(crate) fn stored_image(json: &Root, blob: &[u8], texture: usize) -> Option<StoredImage> {
+    let image = json
+        .images
+        .get(json.textures.get(texture)?.source.value())?;
+    let view = json.buffer_views.get(image.buffer_view?.value())?;
+    let data = blob.get(chunk_range(view))?;
+    let (width, height) = image_dimensions(data)?;
+    Some(StoredImage {
+        width,
+        height,
+        mime_type: image.mime_type.as_ref().map(|m| m.0.clone()),
+        bytes: data.len() as u64,
+        levels: mip_chain(width, height)
+            .into_iter()
+            .take(stored_level_count(data))
+            .collect(),
+    })
+}
+
+/// A parsed GLB with its bytes
+pub(crate) struct Document<'a> {
+    pub glb: &'a [u8],
+    pub json: &'a Root,
+    pub blob: &'a [u8],
+}
+
+/// Sizes, counts and textures of `after` against `before`, input texture `t` having become
+/// `output_texture(t)`
+pub(crate) fn compare(
+    before: &Document,
+    after: &Document,
+    output_texture: impl Fn(usize) -> Option<usize>,
+) -> OptimizeReport {
+    let measured = [before, after].map(|d| measure(d.glb, d.json, d.blob));
+    let change = |field: fn(&Measure) -> u64| BeforeAfter {
+        before: field(&measured[0]),
+        after: field(&measured[1]),
+    };
+    let textures = (0..before.json.textures.len())
+        .map(|texture| {
+            let output_texture = output_texture(texture);
+            TextureChange {
+                texture,
+                output_texture,
+                before: stored_image(before.json, before.blob, texture),
+                after: output_texture.and_then(|t| stored_image(after.json, after.blob, t)),
+            }
+        })
+        .collect();
+
+    OptimizeReport {
+        file: change(|m| m.file),
+        json: change(|m| m.json),
+        geometry: change(|m| m.geometry),
+        animation: change(|m| m.animation),
+        texture_bytes: change(|m| m.texture_bytes),
+        textures,
+        draw_calls: change(|m| m.draw_calls),
+        vertices: change(|m| m.vertices),
+        triangles: change(|m| m.triangles),
+        growth_fallback: None,
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        opt::optimize_with,
+        optimizer::{Optimized, Optimizer},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn report_converts_to_json() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default().texture_size(16);
+        let optimized = Optimizer::new(&options)
+            .report(true)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let (out, report) = (optimized.glb, optimized.report.unwrap());
+
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["file"]["before"], glb.len());
+        assert_eq!(json["file"]["after"], out.len());
+        assert_eq!(json["drawCalls"], json!({ "before": 1, "after": 1 }));
+        assert_eq!(json["growthFallback"], Value::Null);
+        let texture = &json["textures"][0];
+        assert_eq!(texture["outputTexture"], 0);
+        assert_eq!(texture["before"]["width"], 64);
+        assert_eq!(texture["after"]["levels"], json!([[16, 16]]));
+    }
+
+    #[test]
+    fn report_breaks_down_sizes() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions::default().texture_size(16);
+        let Optimized {
+            glb: out, report, ..
+        } = Optimizer::new(&options)
+            .report(true)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let report = report.unwrap();
+        assert_eq!(
+            out,
+            optimize_with(&mut Cursor::new(&glb), &options).unwrap()
+        );
+        assert_eq!(
+            (report.file.before, report.file.after),
+            (glb.len() as u64, out.len() as u64)
+        );
+        assert!(report.json.after > 0 && report.geometry.before > 0);
+        assert_eq!(report.geometry.before, report.geometry.after);
+        assert_eq!(report.draw_calls.before, 1);
+        assert_eq!(report.draw_calls.before, report.draw_calls.after);
+
+        let [texture] = report.textures.as_slice() else {
+            panic!("one texture expected");
+        };
+        let (before, after) = (
+            texture.before.as_ref().unwrap(),
+            texture.after.as_ref().unwrap(),
+        );
+        assert_eq!((before.width, after.width), (64, 16));
+        assert_eq!(after.levels, [(16, 16)]);
+        assert_eq!(report.texture_bytes.after, after.bytes);
+    }
+}
@@ -0,0 +1,148 @@
+use gltf::json::{Node, Root, Scene};
+
+use crate::{
+    bounds::scene_roots, crop::RewrittenDocument, error::OptimizeError, opt::calculate_bounding_box,
+};
+
+/// Uniform scale applied to the default scene, see
+/// [`OptimizeOptions::rescale`](crate::OptimizeOptions::rescale)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Rescale {
+    /// Multiply every size by this factor, e.g. 0.001 for a model authored in millimeters
+    Uniform(f32),
+    /// Scale so the longest side of the scene's bounding box is this long
+    FitToSize(f32),
+}
+
+impl Rescale {
+    /// Factor for a scene with the given bounds, `None` when they are empty or flat
+    fn factor(self, bounds: Option<([f32; 3], [f32; 3])>) -> Option<f32> {
+        match self {
+            Rescale::Uniform(factor) => Some(factor),
+            Rescale::FitToSize(size) => {
+                let (min, max) = bounds?;
+                let longest = (0..3).map(|a| max[a] - min[a]).fold(0.0f32, f32::max);
+                (longest > 0.0).then(|| size / longest)
+            }
+        }
+    }
+}
+
+/// Factor `rescale` scales the default scene by, `None` when it has no size to fit
+pub(crate) fn rescale_factor(o_blob: &[u8], o_json: &Root, rescale: Rescale) -> Option<f32> {
+    rescale.factor(calculate_bounding_box(o_blob, o_json))
+}
+
+/// Put the default scene's root nodes under a new root node scaling them uniformly
+///
+/// Vertex data, animations and skins stay as they are; the new root is appended to `nodes`, so
+/// existing node indices hold. Returns `None` when the factor is 1 or the scene has no nodes
+/// or no size to fit.
+pub(crate) fn rescale_scene(
+    o_blob: &[u8],
+    o_json: &Root,
+    rescale: Rescale,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let value = match rescale {
+        Rescale::Uniform(value) | Rescale::FitToSize(value) => value,
+    };
+    if !value.is_finite() || value <= 0.0 {
+        return Err(OptimizeError::InvalidOption(format!(
+            "{rescale:?} is not a positive scale or size"
+        )));
+    }
+    let Some(factor) = rescale_factor(o_blob, o_json, rescale) else {
+        return Ok(None);
+    };
+    let roots = scene_roots(o_json);
+    if factor == 1.0 || roots.is_empty() {
+        return Ok(None);
+    }
+
+    let mut n_json = o_json.clone();
+    if n_json.scenes.is_empty() {
+        let idx = n_json.push(Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes: Vec::new(),
+        });
+        n_json.scene = Some(idx);
+    }
+    let root = n_json.push(Node {
+        children: Some(roots),
+        scale: Some([factor; 3]),
+        ..Default::default()
+    });
+    let scene_idx = n_json.scene.map(|s| s.value()).unwrap_or(0);
+    if let Some(scene) = n_json.scenes.get_mut(scene_idx) {
+        scene.nodes = vec![root];
+    }
+    Ok(Some((n_json, o_blob.to_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::{mesh::Semantic, validation::Checked};
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{get_position_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn scenes_are_rescaled_under_a_new_root() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let (min, max) = calculate_bounding_box(&o_blob, &o_json).unwrap();
+        let longest = (0..3).map(|a| max[a] - min[a]).fold(0.0f32, f32::max);
+
+        let run = |rescale, center_pivot| {
+            let options = OptimizeOptions {
+                rescale: Some(rescale),
+                center_pivot,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            read_glb(&mut Cursor::new(out)).unwrap()
+        };
+        let (n_json, n_blob) = run(Rescale::FitToSize(2.0), false);
+        let root = &n_json.nodes[n_json.scenes[0].nodes[0].value()];
+        assert_eq!(n_json.scenes[0].nodes.len(), 1);
+        assert_eq!(root.children.as_ref().unwrap().len(), o_json.nodes.len());
+        let (n_min, n_max) = calculate_bounding_box(&n_blob, &n_json).unwrap();
+        let n_longest = (0..3).map(|a| n_max[a] - n_min[a]).fold(0.0f32, f32::max);
+        assert!((n_longest - 2.0).abs() < 1e-5, "{n_longest} vs {longest}");
+        // Vertex data is untouched
+        let positions = |blob: &[u8], json: &Root| {
+            let semantic = Checked::Valid(Semantic::Positions);
+            get_position_data(
+                blob,
+                json,
+                json.meshes[0].primitives[0].attributes[&semantic],
+            )
+        };
+        assert_eq!(positions(&n_blob, &n_json), positions(&o_blob, &o_json));
+
+        // The pivot is centered in scaled units
+        let (n_json, n_blob) = run(Rescale::Uniform(0.5), true);
+        let (n_min, n_max) = calculate_bounding_box(&n_blob, &n_json).unwrap();
+        assert!(n_min[1].abs() < 1e-6);
+        for a in 0..3 {
+            assert!(((n_max[a] - n_min[a]) - (max[a] - min[a]) * 0.5).abs() < 1e-5);
+        }
+
+        let options = OptimizeOptions {
+            rescale: Some(Rescale::Uniform(0.0)),
+            ..Default::default()
+        };
+        let e = optimize_with(&mut Cursor::new(&glb), &options).unwrap_err();
+        assert!(matches!(e, OptimizeError::InvalidOption(_)));
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    fmt,
+    io::{Read, Seek},
+};
+
+use gltf::json::Root;
+use serde_json::Value;
+
+use crate::{
+    error::{Malformation, OptimizeError},
+    extension::retain_extensions,
+    glb::write_glb,
+    legacy::legacy_version,
+    opt::optimize_document,
+    options::OptimizeOptions,
+};
+
+/// Bytes of the GLB header: magic, version and total length
+const HEADER: usize = 12;
+
+/// Bytes of a chunk header: length and type
+const CHUNK_HEADER: usize = 8;
+
+/// How many times the input's size a truncated BIN chunk may be padded up to; a buffer 0
+/// declaring more is taken as damaged rather than cut off
+const MAX_PADDED_GROWTH: usize = 4;
+
+const JSON_CHUNK: u32 = 0x4E4F_534A;
+const BIN_CHUNK: u32 = 0x004E_4942;
+
+/// Damage [`salvage_glb`] repaired in a GLB
+///
+/// The `Display` text says what was wrong and what was done about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GlbRepair {
+    /// The header's total length does not match the size of the file
+    FileLength { declared: u32, actual: usize },
+    /// The JSON chunk's length does not end its JSON; the length was re-derived from the text
+    JsonChunkLength { declared: u32, actual: usize },
+    /// The BIN chunk's length does not match the bytes that follow it; what is there was used
+    BinChunkLength { declared: u32, actual: usize },
+    /// The BIN chunk holds less than buffer 0 declares; the missing tail was filled with zeros,
+    /// so the listed buffer views read zeros where their data was cut off
+    TruncatedBin {
+        missing: usize,
+        damaged_views: Vec<usize>,
+    },
+}
+
+impl fmt::Display for GlbRepair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlbRepair::FileLength { declared, actual } => write!(
+                f,
+                "header declares {declared} bytes but the file has {actual}; used the file size"
+            ),
+            GlbRepair::JsonChunkLength { declared, actual } => write!(
+                f,
+                "JSON chunk declares {declared} bytes but its JSON takes {actual}; used the JSON"
+            ),
+            GlbRepair::BinChunkLength { declared, actual } => write!(
+                f,
+                "BIN chunk declares {declared} bytes but {actual} follow it; used those"
+            ),
+            GlbRepair::TruncatedBin {
+                missing,
+                damaged_views,
+            } if damaged_views.is_empty() => write!(
+                f,
+                "BIN chunk is {missing} bytes short of buffer 0, all past the buffer views; \
+                 padded with zeros"
+            ),
+            GlbRepair::TruncatedBin {
+                missing,
+                damaged_views,
+            } => write!(
+                f,
+                "BIN chunk is {missing} bytes short of buffer 0; padded with zeros, so buffer \
+                 views {damaged_views:?} lost data"
+            ),
+        }
+    }
+}
+
+/// Little-endian `u32` at `at`, `None` past the end of `data`
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Length of the JSON value at the start of `data`, trailing padding spaces left out
+fn json_length(data: &[u8]) -> Option<usize> {
+    let mut values = serde_json::Deserializer::from_slice(data).into_iter::<Value>();
+    values.next()?.ok()?;
+    Some(values.byte_offset())
+}
+
+/// Rebuild a GLB whose header or chunk lengths are off, e.g. from a damaged download
+///
+/// Every length is checked against the bytes actually there. A JSON chunk length that does not
+/// end its JSON is re-derived from the JSON text; BIN chunk and file lengths fall back to what
+/// the file holds. When the BIN chunk is shorter than buffer 0, the tail is filled with zeros
+/// and the buffer views reaching into it are reported. Each repair is listed; a GLB needing
+/// none comes back rewritten with an empty list. Files whose JSON is unreadable, whose buffer 0
+/// declares more than its buffer views or the file's size can account for, or that are not
+/// GLBs, still fail.
+pub fn salvage_glb(data: &[u8]) -> Result<(Vec<u8>, Vec<GlbRepair>), OptimizeError> {
+    let (o_json, o_blob, repairs) = salvage(data)?;
+    Ok((write_glb(&o_json, o_blob)?, repairs))
+}
+
+/// JSON root and BIN chunk of a GLB read as [`salvage_glb`] does, with the repairs made
+fn salvage(data: &[u8]) -> Result<(Root, Vec<u8>, Vec<GlbRepair>), OptimizeError> {
+    if !data.starts_with(b"glTF") {
+        return Err(OptimizeError::NotFormat {
+            expected: "GLB file",
+        });
+    }
+    if let Some(version) = legacy_version(data) {
+        return Err(OptimizeError::UnsupportedVersion { version });
+    }
+    let mut repairs = Vec::new();
+    let declared = read_u32(data, 8).ok_or_else(|| OptimizeError::Truncated {
+        what: "GLB header".to_string(),
+    })?;
+    if declared as usize != data.len() {
+        repairs.push(GlbRepair::FileLength {
+            declared,
+            actual: data.len(),
+        });
+    }
+
+    // JSON chunk
+    let declared = read_u32(data, HEADER).ok_or_else(|| OptimizeError::Truncated {
+        what: "JSON chunk header".to_string(),
+    })?;
+    if read_u32(data, HEADER + 4) != Some(JSON_CHUNK) {
+        return Err(OptimizeError::MissingChunk { chunk: "JSON" });
+    }
+    let start = HEADER + CHUNK_HEADER;
+    let declared_end = start.saturating_add(declared as usize);
+    let declared_json = data
+        .get(start..declared_end)
+        .and_then(|json| serde_json::from_slice::<Value>(json).ok());
+    let json_end = if declared_json.is_some() {
+        declared_end
+    } else {
+        let length =
+            json_length(&data[start..]).ok_or(OptimizeError::Malformed(Malformation::JsonChunk))?;
+        // Padding spaces belong to the chunk
+        let padded = length.next_multiple_of(4);
+        let length = if data
+            .get(start + length..start + padded)
+            .is_some_and(|p| p.iter().all(|&b| b == b' '))
+        {
+            padded
+        } else {
+            length
+        };
+        repairs.push(GlbRepair::JsonChunkLength {
+            declared,
+            actual: length,
+        });
+        start + length
+    };
+    let o_json: Root = gltf::json::deserialize::from_slice(&data[start..json_end])
+        .map_err(|e| OptimizeError::InvalidInput(gltf::Error::Deserialize(e)))?;
+
+    // BIN chunk, when the file still has one
+    let mut o_blob = Vec::new();
+    let bin_start = json_end.next_multiple_of(4);
+    if read_u32(data, bin_start + 4) == Some(BIN_CHUNK) {
+        let declared = read_u32(data, bin_start).unwrap_or_default();
+        let body = &data[bin_start + CHUNK_HEADER..];
+        // A shorter declared length is kept unless it cuts into buffer 0
+        let wanted = o_json
+            .buffers
+            .first()
+            .filter(|b| b.uri.is_none())
+            .map_or(0, |b| b.byte_length.0 as usize);
+        let actual =
+            if (declared as usize) <= body.len() && declared as usize >= wanted.min(body.len()) {
+                declared as usize
+            } else {
+                body.len()
+            };
+        if actual != declared as usize {
+            repairs.push(GlbRepair::BinChunkLength { declared, actual });
+        }
+        o_blob = body[..actual].to_vec();
+    }
+
+    if let Some(buffer) = o_json.buffers.first().filter(|b| b.uri.is_none()) {
+        let wanted = usize::try_from(buffer.byte_length.0).unwrap_or(usize::MAX);
+        if o_blob.len() < wanted {
+            // Only pad up to a length the file could have held: the views' data, within a few
+            // times the input's size. Anything past that is a corrupt declaration, not a cut
+            let views_end = o_json
+                .buffer_views
+                .iter()
+                .filter(|v| v.buffer.value() == 0)
+                .map(|v| {
+                    (v.byte_offset.map_or(0, |o| o.0) as usize)
+                        .saturating_add(v.byte_length.0 as usize)
+                })
+                .max()
+                .unwrap_or(0);
+            let views_end = views_end.checked_next_multiple_of(4).unwrap_or(usize::MAX);
+            if wanted > views_end || wanted > data.len().saturating_mul(MAX_PADDED_GROWTH) {
+                return Err(OptimizeError::Malformed(Malformation::BufferLength {
+                    declared: wanted,
+                    file: data.len(),
+                }));
+            }
+            let present = o_blob.len();
+            let damaged_views = o_json
+                .buffer_views
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.buffer.value() == 0)
+                .filter(|(_, v)| {
+                    (v.byte_offset.map_or(0, |o| o.0) as usize)
+                        .saturating_add(v.byte_length.0 as usize)
+                        > present
+                })
+                .map(|(i, _)| i)
+                .collect();
+            repairs.push(GlbRepair::TruncatedBin {
+                missing: wanted - present,
+                damaged_views,
+            });
+            o_blob.resize(wanted, 0);
+        }
+    }
+
+    Ok((o_json, o_blob, repairs))
+}
+
+/// Optimize a GLB after [`salvage_glb`] repaired it, listing the repairs
+pub fn optimize_salvaged<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+) -> Result<(Vec<u8>, Vec<GlbRepair>), OptimizeError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (o_json, o_blob, repairs) = salvage(&data)?;
+
+    let (mut n_json, n_blob, _) = optimize_document(&o_blob, &o_json, options, None, None)?;
+    retain_extensions(&mut n_json, &[])?;
+    Ok((write_glb(&n_json, n_blob)?, repairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    #[test]
+    fn damaged_glb_lengths_are_salvaged() {
+        use crate::glb::read_glb;
+
+        let glb = SyntheticGlb::default().build().unwrap();
+        assert_eq!(salvage_glb(&glb).unwrap().1, []);
+
+        let mut damaged = glb.clone();
+        let field =
+            |data: &[u8], at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let json_length = field(&damaged, 12);
+        damaged[8..12].copy_from_slice(&(glb.len() as u32 + 100).to_le_bytes());
+        damaged[12..16].copy_from_slice(&(json_length + 8).to_le_bytes());
+        damaged.truncate(glb.len() - 4);
+        assert!(read_glb(&mut Cursor::new(&damaged)).is_err());
+
+        let (salvaged, repairs) = salvage_glb(&damaged).unwrap();
+        let bin_length = field(&glb, 20 + json_length as usize);
+        assert_eq!(
+            repairs[..3],
+            [
+                GlbRepair::FileLength {
+                    declared: glb.len() as u32 + 100,
+                    actual: glb.len() - 4
+                },
+                GlbRepair::JsonChunkLength {
+                    declared: json_length + 8,
+                    actual: json_length as usize
+                },
+                GlbRepair::BinChunkLength {
+                    declared: bin_length,
+                    actual: bin_length as usize - 4
+                },
+            ]
+        );
+        let GlbRepair::TruncatedBin {
+            missing,
+            damaged_views,
+        } = &repairs[3]
+        else {
+            panic!("expected a truncated BIN chunk, got {repairs:?}");
+        };
+        assert_eq!(*missing, 4);
+        assert_eq!(damaged_views.len(), 1);
+        let (json, blob) = read_glb(&mut Cursor::new(salvaged)).unwrap();
+        assert_eq!(blob.len(), bin_length as usize);
+        assert_eq!(json.buffers[0].byte_length.0, bin_length as u64);
+
+        let (_, repairs) =
+            optimize_salvaged(&mut Cursor::new(&damaged), &OptimizeOptions::default()).unwrap();
+        assert_eq!(repairs.len(), 4);
+    }
+
+    #[test]
+    fn implausible_buffer_lengths_are_not_padded() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        let mut json: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        json["buffers"][0]["byteLength"] = 1_000_000_000_000_000u64.into();
+        let json = serde_json::to_vec(&json).unwrap();
+
+        // A GLB with only a JSON chunk, its buffer claiming a petabyte
+        let mut damaged = b"glTF".to_vec();
+        damaged.extend(2u32.to_le_bytes());
+        damaged.extend(((20 + json.len()) as u32).to_le_bytes());
+        damaged.extend((json.len() as u32).to_le_bytes());
+        damaged.extend(JSON_CHUNK.to_le_bytes());
+        damaged.extend(&json);
+
+        assert!(matches!(
+            salvage_glb(&damaged),
+            Err(OptimizeError::Malformed(Malformation::BufferLength {
+                declared: 1_000_000_000_000_000,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            optimize_salvaged(&mut Cursor::new(&damaged), &OptimizeOptions::default()),
+            Err(OptimizeError::Malformed(Malformation::BufferLength { .. }))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use std::mem;
+
+use gltf::json::{Index, Root, Texture};
+use image::DynamicImage;
+
+use crate::decode::load_texture_image;
+use crate::error::OptimizeError;
+
+/// Buffers reused from one texture to the next, so a batch of textures does not allocate
+/// fresh pixel and file buffers for each
+#[derive(Debug, Default)]
+pub(crate) struct TextureScratch {
+    /// Pixels of the last decoded image, handed back with [`TextureScratch::recycle`]
+    decoded: Vec<u8>,
+    /// Pixels written by the resizers
+    pub(crate) resized: Vec<u8>,
+    /// Encoded file, lent out by [`TextureScratch::take_encoded`]
+    encoded: Vec<u8>,
+}
+
+impl TextureScratch {
+    /// Decode the image behind a texture into the reused pixel buffer
+    pub(crate) fn decode(
+        &mut self,
+        o_json: &Root,
+        texture: Index<Texture>,
+        data: &[u8],
+    ) -> Result<DynamicImage, OptimizeError> {
+        load_texture_image(o_json, texture, data, mem::take(&mut self.decoded))
+    }
+
+    /// Keep the pixel buffer of an 8-bit image for the next decode
+    pub(crate) fn recycle(&mut self, img: DynamicImage) {
+        let pixels = match img {
+            DynamicImage::ImageLuma8(img) => img.into_raw(),
+            DynamicImage::ImageLumaA8(img) => img.into_raw(),
+            DynamicImage::ImageRgb8(img) => img.into_raw(),
+            DynamicImage::ImageRgba8(img) => img.into_raw(),
+            _ => return,
+        };
+        if pixels.capacity() > self.decoded.capacity() {
+            self.decoded = pixels;
+        }
+    }
+
+    /// Empty buffer for an encoded file, with the capacity of the ones before it
+    pub(crate) fn take_encoded(&mut self) -> Vec<u8> {
+        let mut buffer = mem::take(&mut self.encoded);
+        buffer.clear();
+        buffer
+    }
+
+    /// Give back a buffer from [`TextureScratch::take_encoded`] once its bytes are copied
+    pub(crate) fn return_encoded(&mut self, buffer: Vec<u8>) {
+        if buffer.capacity() > self.encoded.capacity() {
+            self.encoded = buffer;
+        }
+    }
+}
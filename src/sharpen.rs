@@ -0,0 +1,109 @@
+use image::{RgbaImage, imageops};
+
+/// Unsharp mask applied to downscaled base color and emissive textures, see
+/// [`OptimizeOptions::sharpen`](crate::OptimizeOptions::sharpen)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Sharpen {
+    /// Share of the difference to the blurred image added back; 0.5 by default
+    pub amount: f32,
+    /// Gaussian blur sigma in output pixels; 1.0 by default
+    pub radius: f32,
+    /// Smallest difference in 8-bit levels that gets sharpened, so flat areas and noise are
+    /// left alone; 2 by default
+    pub threshold: u8,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Sharpen {
+            amount: 0.5,
+            radius: 1.0,
+            threshold: 2,
+        }
+    }
+}
+
+/// Sharpen the color channels with an unsharp mask, leaving alpha alone
+pub(crate) fn sharpen_rgba(rgba: &mut RgbaImage, sharpen: &Sharpen) {
+    if sharpen.amount <= 0.0 || sharpen.radius <= 0.0 {
+        return;
+    }
+    let blurred = imageops::blur(rgba, sharpen.radius);
+    let threshold = sharpen.threshold as f32;
+    for (pixel, blur) in rgba.pixels_mut().zip(blurred.pixels()) {
+        for (c, b) in pixel.0.iter_mut().zip(blur.0).take(3) {
+            let difference = *c as f32 - b as f32;
+            if difference.abs() >= threshold {
+                *c = (*c as f32 + sharpen.amount * difference)
+                    .round()
+                    .clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        decode::load_image,
+        glb::read_glb,
+        inject::replace_texture,
+        opt::{ImageFormat, get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn downscaled_color_textures_are_sharpened() {
+        // Diagonal stripes, which a 4x downscale blurs toward gray; straight ones would pass
+        // for a sprite grid and keep their size
+        let stripes = image::RgbaImage::from_fn(64, 64, |x, y| {
+            let v = if ((x + 2 * y) / 6) % 2 == 0 { 40 } else { 220 };
+            image::Rgba([v, v, v, 255])
+        });
+        let mut png = Vec::new();
+        image::DynamicImage::from(stripes)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(glb), 0, &png, None).unwrap();
+
+        let contrast = |options: &OptimizeOptions| {
+            let out = optimize_with(&mut Cursor::new(&glb), options).unwrap();
+            let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&blob, &json, Index::new(0)).unwrap();
+            let img = load_image(data, Vec::new()).unwrap().to_luma8();
+            let values: Vec<u8> = img.pixels().map(|p| p.0[0]).collect();
+            let contrast = values.iter().max().unwrap() - values.iter().min().unwrap();
+            (img.width(), contrast)
+        };
+        let options = OptimizeOptions {
+            image_format: Some(ImageFormat::Png),
+            ..Default::default()
+        }
+        .texture_size(16);
+        let sharpened = OptimizeOptions {
+            sharpen: Some(Sharpen::default()),
+            ..options.clone()
+        };
+        let (width, plain) = contrast(&options);
+        assert_eq!(width, 16);
+        assert!(contrast(&sharpened).1 > plain);
+        // Textures that fit their size are left alone
+        assert_eq!(
+            contrast(&sharpened.texture_size(64)),
+            contrast(&options.texture_size(64))
+        );
+    }
+}
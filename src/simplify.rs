@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+
+use gltf::json::{
+    Root,
+    mesh::{Mode, MorphTarget, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{gather_accessor, push_indices},
+    hints::object_hints,
+    math::{bounds, cross, dot, sub},
+    opt::{get_index_data, get_position_data},
+    reorder::vertex_accessors,
+};
+
+/// How many triangles [`OptimizeOptions::simplify`](crate::OptimizeOptions::simplify)
+/// aims to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SimplifyTarget {
+    /// Fraction of each primitive's triangles, 0..=1
+    Ratio(f32),
+    /// Triangles across the whole model, shared out in proportion to each primitive's count
+    Triangles(usize),
+}
+
+/// Settings of the mesh simplification pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct SimplifyOptions {
+    pub target: SimplifyTarget,
+    /// Largest distance the surface may move, relative to the primitive's bounding box
+    /// diagonal; simplification stops short of the target rather than exceed it
+    pub max_error: f32,
+    /// Primitives of at most this many triangles whose vertices all lie in one plane (decals,
+    /// glass panes, foliage cards) are left as they are, since collapsing their few edges
+    /// makes them vanish; 0 simplifies them like any other
+    pub planar_max_triangles: usize,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        SimplifyOptions {
+            target: SimplifyTarget::Ratio(0.5),
+            max_error: 0.01,
+            planar_max_triangles: 64,
+        }
+    }
+}
+
+/// Largest distance of a vertex from the plane of a planar primitive, relative to its
+/// bounding box diagonal
+const PLANAR_TOLERANCE: f32 = 1e-3;
+
+/// Error quadric of a set of planes: `pᵀAp + 2bᵀp + c` sums their weighted squared distances
+/// to `p`
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    /// Upper triangle of `A`: xx, xy, xz, yy, yz, zz
+    a: [f64; 6],
+    b: [f64; 3],
+    c: f64,
+    weight: f64,
+}
+
+impl Quadric {
+    /// Plane through `p` with unit normal `n`, weighted by `weight`
+    fn plane(n: [f64; 3], p: [f32; 3], weight: f64) -> Self {
+        let d = -(n[0] * p[0] as f64 + n[1] * p[1] as f64 + n[2] * p[2] as f64);
+        Quadric {
+            a: [
+                n[0] * n[0],
+                n[0] * n[1],
+                n[0] * n[2],
+                n[1] * n[1],
+                n[1] * n[2],
+                n[2] * n[2],
+            ]
+            .map(|v| v * weight),
+            b: n.map(|v| v * d * weight),
+            c: d * d * weight,
+            weight,
+        }
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        Quadric {
+            a: std::array::from_fn(|i| self.a[i] + other.a[i]),
+            b: std::array::from_fn(|i| self.b[i] + other.b[i]),
+            c: self.c + other.c,
+            weight: self.weight + other.weight,
+        }
+    }
+
+    /// Mean squared distance from `p` to the planes
+    fn error(&self, p: [f32; 3]) -> f64 {
+        if self.weight == 0.0 {
+            return 0.0;
+        }
+        let [x, y, z] = p.map(f64::from);
+        let [xx, xy, xz, yy, yz, zz] = self.a;
+        let quadratic =
+            xx * x * x + yy * y * y + zz * z * z + 2.0 * (xy * x * y + xz * x * z + yz * y * z);
+        let linear = 2.0 * (self.b[0] * x + self.b[1] * y + self.b[2] * z);
+        ((quadratic + linear + self.c) / self.weight).max(0.0)
+    }
+}
+
+/// Vertices that must stay: those sharing a position with another vertex (UV, normal or
+/// color seams) and the ends of edges not shared by exactly two triangles
+fn locked_vertices(triangles: &[[u32; 3]], positions: &[[f32; 3]]) -> Vec<bool> {
+    let mut locked = vec![false; positions.len()];
+    let mut first_at: HashMap<[u32; 3], usize> = HashMap::new();
+    for (v, p) in positions.iter().enumerate() {
+        if let Some(&other) = first_at.get(&p.map(f32::to_bits)) {
+            locked[v] = true;
+            locked[other] = true;
+        } else {
+            first_at.insert(p.map(f32::to_bits), v);
+        }
+    }
+
+    let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+    for t in triangles {
+        for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    for ((a, b), count) in edges {
+        if count != 2 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+    locked
+}
+
+fn triangle_normal(t: [u32; 3], positions: &[[f32; 3]]) -> [f32; 3] {
+    let [p0, p1, p2] = t.map(|v| positions[v as usize]);
+    cross(sub(p1, p0), sub(p2, p0))
+}
+
+/// Whether moving `u` onto `v` keeps the surface manifold and flips no triangle
+fn can_collapse(
+    u: u32,
+    v: u32,
+    triangles: &[[u32; 3]],
+    adjacent: &[Vec<usize>],
+    positions: &[[f32; 3]],
+) -> bool {
+    let around_u = &adjacent[u as usize];
+    let shared = around_u
+        .iter()
+        .filter(|&&t| triangles[t].contains(&v))
+        .count();
+    let neighbours = |x: u32| -> Vec<u32> {
+        let mut n: Vec<u32> = adjacent[x as usize]
+            .iter()
+            .flat_map(|&t| triangles[t])
+            .filter(|&w| w != x)
+            .collect();
+        n.sort_unstable();
+        n.dedup();
+        n
+    };
+    let of_v = neighbours(v);
+    let common = neighbours(u)
+        .iter()
+        .filter(|w| of_v.binary_search(w).is_ok())
+        .count();
+    if shared != 2 || common != 2 {
+        return false;
+    }
+
+    around_u
+        .iter()
+        .map(|&t| triangles[t])
+        .filter(|t| !t.contains(&v))
+        .all(|t| {
+            let before = triangle_normal(t, positions);
+            let after = triangle_normal(t.map(|w| if w == u { v } else { w }), positions);
+            dot(before, after) > 0.0
+        })
+}
+
+/// Reduce a triangle list to about `target` triangles by collapsing vertices onto neighbours
+///
+/// Half-edge collapses move a vertex onto an existing one, so every remaining vertex keeps its
+/// attributes. Collapses are ranked by quadric error and applied in passes of independent
+/// collapses until the target is reached or the next one would move the surface further
+/// than `max_distance`.
+pub(crate) fn simplify_indices(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    target: usize,
+    max_distance: f32,
+) -> Vec<u32> {
+    let vertex_count = positions.len();
+    let mut triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+        .collect();
+    let locked = locked_vertices(&triangles, positions);
+    let limit = f64::from(max_distance) * f64::from(max_distance);
+
+    // Planes of the triangles around each vertex, weighted by area
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    for &t in &triangles {
+        let normal = triangle_normal(t, positions).map(f64::from);
+        let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        let plane = Quadric::plane(
+            normal.map(|c| c / length),
+            positions[t[0] as usize],
+            length / 2.0,
+        );
+        for v in t {
+            quadrics[v as usize] = quadrics[v as usize].add(&plane);
+        }
+    }
+
+    while triangles.len() > target {
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for (i, t) in triangles.iter().enumerate() {
+            for &v in t {
+                adjacent[v as usize].push(i);
+            }
+        }
+
+        let mut candidates: Vec<(f64, u32, u32)> = Vec::new();
+        for t in &triangles {
+            for (u, v) in
+                [(0, 1), (1, 2), (2, 0), (1, 0), (2, 1), (0, 2)].map(|(a, b)| (t[a], t[b]))
+            {
+                if locked[u as usize] {
+                    continue;
+                }
+                let cost = quadrics[u as usize]
+                    .add(&quadrics[v as usize])
+                    .error(positions[v as usize]);
+                if cost <= limit {
+                    candidates.push((cost, u, v));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Collapses in one pass touch disjoint neighbourhoods, so adjacency stays valid
+        let needed = (triangles.len() - target).div_ceil(2);
+        let mut touched = vec![false; vertex_count];
+        let mut remap: Vec<u32> = (0..vertex_count as u32).collect();
+        let mut collapsed = 0;
+        for (_, u, v) in candidates {
+            if collapsed == needed {
+                break;
+            }
+            if touched[u as usize]
+                || touched[v as usize]
+                || !can_collapse(u, v, &triangles, &adjacent, positions)
+            {
+                continue;
+            }
+            remap[u as usize] = v;
+            quadrics[v as usize] = quadrics[v as usize].add(&quadrics[u as usize]);
+            for &t in &adjacent[u as usize] {
+                for w in triangles[t] {
+                    touched[w as usize] = true;
+                }
+            }
+            collapsed += 1;
+        }
+        if collapsed == 0 {
+            break;
+        }
+
+        for t in &mut triangles {
+            *t = t.map(|v| remap[v as usize]);
+        }
+        triangles.retain(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2]);
+    }
+
+    triangles.into_iter().flatten().collect()
+}
+
+/// Whether an indexed triangle primitive has at most `max_triangles` triangles, all in one plane
+fn is_small_planar(
+    o_blob: &[u8],
+    o_json: &Root,
+    primitive: &Primitive,
+    max_triangles: usize,
+) -> bool {
+    let triangle_count = primitive
+        .indices
+        .and_then(|i| o_json.accessors.get(i.value()))
+        .map_or(usize::MAX, |a| a.count.0 as usize / 3);
+    if primitive.mode != Checked::Valid(Mode::Triangles) || triangle_count > max_triangles {
+        return false;
+    }
+    let Some(positions) = primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))
+        .and_then(|&p| get_position_data(o_blob, o_json, p))
+    else {
+        return false;
+    };
+    let Some(indices) = primitive
+        .indices
+        .and_then(|i| get_index_data(o_blob, o_json, i))
+    else {
+        return false;
+    };
+    if indices.iter().any(|&i| i as usize >= positions.len()) {
+        return false;
+    }
+
+    // Area-weighted normal; opposite-facing halves cancel out and are not treated as planar
+    let normal = indices.chunks_exact(3).fold([0.0; 3], |n, t| {
+        let c = triangle_normal([t[0], t[1], t[2]], &positions);
+        [n[0] + c[0], n[1] + c[1], n[2] + c[2]]
+    });
+    let length = dot(normal, normal).sqrt();
+    if length == 0.0 {
+        return false;
+    }
+    let normal = normal.map(|v| v / length);
+    let (min, max) = bounds(&positions);
+    let diagonal = dot(sub(max, min), sub(max, min)).sqrt();
+    let origin = positions[indices[0] as usize];
+    indices.iter().all(|&i| {
+        dot(sub(positions[i as usize], origin), normal).abs() <= PLANAR_TOLERANCE * diagonal
+    })
+}
+
+/// Simplify one primitive to `ratio` of its triangles, `None` to leave it as it is
+pub(crate) fn simplify_primitive(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    primitive: &Primitive,
+    ratio: f32,
+    max_error: f32,
+) -> Option<Primitive> {
+    if primitive.mode != Checked::Valid(Mode::Triangles) {
+        return None;
+    }
+    let position = *primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    let positions = get_position_data(o_blob, o_json, position)?;
+    let indices = get_index_data(o_blob, o_json, primitive.indices?)?;
+    if indices.len() < 3 || indices.iter().any(|&i| i as usize >= positions.len()) {
+        return None;
+    }
+
+    let (min, max) = bounds(&positions);
+    let diagonal = dot(sub(max, min), sub(max, min)).sqrt();
+    let triangle_count = indices.len() / 3;
+    let target = ((triangle_count as f32 * ratio).ceil() as usize).max(1);
+    let simplified = simplify_indices(&indices, &positions, target, max_error * diagonal);
+    if simplified.len() >= triangle_count * 3 {
+        return None;
+    }
+
+    // Keep only the vertices still referenced, in first-use order
+    let mut remap = vec![u32::MAX; positions.len()];
+    let mut order = Vec::new();
+    let compacted: Vec<u32> = simplified
+        .iter()
+        .map(|&v| {
+            if remap[v as usize] == u32::MAX {
+                remap[v as usize] = order.len() as u32;
+                order.push(v);
+            }
+            remap[v as usize]
+        })
+        .collect();
+
+    let mut n_primitive = primitive.clone();
+    for (semantic, accessor) in &primitive.attributes {
+        let gathered = gather_accessor(o_blob, o_json, n_blob, n_json, *accessor, &order)?;
+        n_primitive.attributes.insert(semantic.clone(), gathered);
+    }
+    if let Some(targets) = &primitive.targets {
+        let mut n_targets = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mut gather = |accessor: Option<_>| match accessor {
+                Some(a) => gather_accessor(o_blob, o_json, n_blob, n_json, a, &order).map(Some),
+                None => Some(None),
+            };
+            n_targets.push(MorphTarget {
+                positions: gather(target.positions)?,
+                normals: gather(target.normals)?,
+                tangents: gather(target.tangents)?,
+            });
+        }
+        n_primitive.targets = Some(n_targets);
+    }
+    n_primitive.indices = Some(push_indices(n_blob, n_json, &compacted));
+    Some(n_primitive)
+}
+
+/// Cut the triangle count of indexed triangle primitives, see [`SimplifyOptions`]
+///
+/// Primitives sharing a vertex accessor with another primitive, unindexed or sparse ones and
+/// small planar ones are left alone. Returns `None` when nothing changed.
+pub(crate) fn simplify_meshes(
+    o_blob: &[u8],
+    o_json: &Root,
+    options: &SimplifyOptions,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    if !(0.0..).contains(&options.max_error) {
+        return Err(OptimizeError::InvalidOption(format!(
+            "simplify max_error {} is negative",
+            options.max_error
+        )));
+    }
+
+    let mut uses: HashMap<usize, usize> = HashMap::new();
+    for primitive in o_json.meshes.iter().flat_map(|m| &m.primitives) {
+        for accessor in vertex_accessors(primitive) {
+            *uses.entry(accessor).or_default() += 1;
+        }
+    }
+
+    let ratio = match options.target {
+        SimplifyTarget::Ratio(ratio) if (0.0..=1.0).contains(&ratio) => ratio,
+        SimplifyTarget::Ratio(ratio) => {
+            return Err(OptimizeError::InvalidOption(format!(
+                "simplify ratio {ratio} is outside 0..=1"
+            )));
+        }
+        SimplifyTarget::Triangles(target) => {
+            let total: usize = o_json
+                .meshes
+                .iter()
+                .flat_map(|m| &m.primitives)
+                .filter(|p| p.mode == Checked::Valid(Mode::Triangles))
+                .filter_map(|p| o_json.accessors.get(p.indices?.value()))
+                .map(|a| a.count.0 as usize / 3)
+                .sum();
+            if total == 0 {
+                return Ok(None);
+            }
+            (target as f32 / total as f32).min(1.0)
+        }
+    };
+    if ratio >= 1.0 {
+        return Ok(None);
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed = false;
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        if object_hints(&mesh.extras).skip {
+            continue;
+        }
+        for (p, primitive) in mesh.primitives.iter().enumerate() {
+            if vertex_accessors(primitive).any(|a| uses[&a] > 1)
+                || is_small_planar(o_blob, o_json, primitive, options.planar_max_triangles)
+            {
+                continue;
+            }
+            if let Some(simplified) = simplify_primitive(
+                o_blob,
+                o_json,
+                &mut n_blob,
+                &mut n_json,
+                primitive,
+                ratio,
+                options.max_error,
+            ) {
+                n_json.meshes[m].primitives[p] = simplified;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::write_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn simplification_keeps_borders_and_orientation() {
+        // 10x10 quads; the bumpy grid lifts the vertices at odd x and y
+        let grid = |bump: f32| -> (Vec<[f32; 3]>, Vec<u32>) {
+            let positions = (0..11u32)
+                .flat_map(|y| (0..11u32).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let z = if x % 2 == 1 && y % 2 == 1 { bump } else { 0.0 };
+                    [x as f32, y as f32, z]
+                })
+                .collect();
+            let indices = (0..10u32)
+                .flat_map(|y| (0..10u32).map(move |x| y * 11 + x))
+                .flat_map(|v| [v, v + 1, v + 12, v, v + 12, v + 11])
+                .collect();
+            (positions, indices)
+        };
+
+        let (flat, indices) = grid(0.0);
+        let simplified = simplify_indices(&indices, &flat, 1, 0.01);
+        // Only the 40 border vertices are left, fanned into 38 triangles
+        let mut used = simplified.clone();
+        used.sort_unstable();
+        used.dedup();
+        assert_eq!(used.len(), 40);
+        assert_eq!(simplified.len(), 38 * 3);
+        for t in simplified.chunks_exact(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|v| flat[v as usize]);
+            assert!(cross(sub(b, a), sub(c, a))[2] > 0.0);
+        }
+
+        // Bumps are taller than the allowed error, so every one of them stays
+        let (bumpy, indices) = grid(0.5);
+        let tight = simplify_indices(&indices, &bumpy, 1, 0.01);
+        assert!((0..bumpy.len() as u32).all(|v| bumpy[v as usize][2] == 0.0 || tight.contains(&v)));
+        assert!(simplify_indices(&indices, &bumpy, 1, 1.0).len() < tight.len());
+    }
+
+    #[test]
+    fn small_planar_primitives_are_not_simplified() {
+        use gltf::json::mesh::Semantic;
+
+        use crate::glb::{push_positions, read_glb};
+
+        // A flat 4x4 quad grid, like a decal, in place of the synthetic quad
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+        let positions: Vec<[f32; 3]> = (0..5u32)
+            .flat_map(|y| (0..5u32).map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let indices: Vec<u32> = (0..4u32)
+            .flat_map(|y| (0..4u32).map(move |x| y * 5 + x))
+            .flat_map(|v| [v, v + 1, v + 6, v, v + 6, v + 5])
+            .collect();
+        let position = push_positions(&mut blob, &mut json, &positions);
+        let indices = push_indices(&mut blob, &mut json, &indices);
+        let primitive = &mut json.meshes[0].primitives[0];
+        primitive.attributes = [(Checked::Valid(Semantic::Positions), position)].into();
+        primitive.indices = Some(indices);
+        let glb = write_glb(&json, blob.into_vec()).unwrap();
+
+        let triangles = |planar_max_triangles| {
+            let options = OptimizeOptions {
+                simplify: Some(SimplifyOptions {
+                    target: SimplifyTarget::Ratio(0.1),
+                    planar_max_triangles,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+            let primitive = &n_json.meshes[0].primitives[0];
+            n_json.accessors[primitive.indices.unwrap().value()].count.0 / 3
+        };
+        assert_eq!(
+            triangles(SimplifyOptions::default().planar_max_triangles),
+            32
+        );
+        assert!(triangles(0) < 32);
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek},
+};
+
+use gltf::json::{
+    Accessor, Index, Root,
+    accessor::{ComponentType, GenericComponentType},
+    buffer::Target,
+    mesh::{Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    convert::{read_accessor, write_elements},
+    crop::RewrittenDocument,
+    error::{AccessorProblem, OptimizeError},
+    glb::read_glb,
+};
+
+/// Largest distance of a vertex's weight sum from 1 that still counts as normalized
+const WEIGHT_SUM_TOLERANCE: f32 = 1e-3;
+
+/// Skinned primitive whose joint indices or weights break the glTF rules
+///
+/// Counts are vertices. Everything reported is fixed by
+/// [`OptimizeOptions::repair_skin_weights`](crate::OptimizeOptions::repair_skin_weights).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SkinningIssue {
+    pub mesh: usize,
+    pub primitive: usize,
+    /// Joints of the skin the primitive is drawn with, the smallest when several skins draw it
+    pub joint_count: usize,
+    /// Vertices giving weight to a joint index at or past `joint_count`
+    pub out_of_range_joints: usize,
+    /// Vertices with a negative or non-finite weight
+    pub invalid_weights: usize,
+    /// Vertices whose weights don't add up to 1
+    pub unnormalized_weights: usize,
+}
+
+/// `JOINTS_n` and `WEIGHTS_n` accessors of each set, in order
+type SkinSets = Vec<(Index<Accessor>, Index<Accessor>)>;
+
+/// Skin sets of a primitive, `None` when a set lacks one of its accessors
+fn skin_sets(primitive: &Primitive) -> Option<SkinSets> {
+    let mut sets = Vec::new();
+    for set in 0.. {
+        let joints = primitive
+            .attributes
+            .get(&Checked::Valid(Semantic::Joints(set)));
+        let weights = primitive
+            .attributes
+            .get(&Checked::Valid(Semantic::Weights(set)));
+        match (joints, weights) {
+            (Some(&joints), Some(&weights)) => sets.push((joints, weights)),
+            (None, None) => break,
+            _ => return None,
+        }
+    }
+    Some(sets)
+}
+
+/// Joint count of the skins drawing each mesh, the smallest when several do
+fn mesh_joint_counts(json: &Root) -> HashMap<usize, usize> {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for node in &json.nodes {
+        let (Some(mesh), Some(skin)) = (node.mesh, node.skin) else {
+            continue;
+        };
+        let Some(skin) = json.skins.get(skin.value()) else {
+            continue;
+        };
+        let count = counts.entry(mesh.value()).or_insert(skin.joints.len());
+        *count = (*count).min(skin.joints.len());
+    }
+    counts
+}
+
+/// Joint indices and weights of every set, all of the same length
+type SkinData = (Vec<Vec<[f32; 4]>>, Vec<Vec<[f32; 4]>>);
+
+fn read_sets(
+    blob: &[u8],
+    json: &Root,
+    sets: &[(Index<Accessor>, Index<Accessor>)],
+) -> Option<SkinData> {
+    let mut joints = Vec::with_capacity(sets.len());
+    let mut weights = Vec::with_capacity(sets.len());
+    for &(j, w) in sets {
+        joints.push(read_accessor::<4>(blob, json, j)?);
+        weights.push(read_accessor::<4>(blob, json, w)?);
+    }
+    let count = joints.first()?.len();
+    joints
+        .iter()
+        .chain(&weights)
+        .all(|set| set.len() == count)
+        .then_some((joints, weights))
+}
+
+/// Check and fix every vertex in place, returning the issue counts
+///
+/// Weights of out-of-range joints and invalid weights become 0, and the rest are scaled to
+/// add up to 1. A vertex left without weight is bound fully to joint 0. `quantum` is the
+/// step of normalized integer weights, whose rounding error goes to the largest weight so
+/// the stored sum stays exact.
+fn repair_vertices(
+    joints: &mut [Vec<[f32; 4]>],
+    weights: &mut [Vec<[f32; 4]>],
+    joint_count: usize,
+    quantum: Option<f32>,
+) -> (usize, usize, usize) {
+    let (mut out_of_range, mut invalid, mut unnormalized) = (0, 0, 0);
+    let count = joints.first().map_or(0, Vec::len);
+    for v in 0..count {
+        let mut slots: Vec<(&mut f32, &mut f32)> = joints
+            .iter_mut()
+            .zip(weights.iter_mut())
+            .flat_map(|(j, w)| j[v].iter_mut().zip(w[v].iter_mut()))
+            .collect();
+
+        let (mut range_fixed, mut invalid_fixed) = (false, false);
+        for (joint, weight) in &mut slots {
+            if !weight.is_finite() || **weight < 0.0 {
+                invalid_fixed = true;
+                **weight = 0.0;
+            }
+            if **joint as usize >= joint_count {
+                range_fixed = true;
+                **weight = 0.0;
+                **joint = 0.0;
+            }
+        }
+        out_of_range += range_fixed as usize;
+        invalid += invalid_fixed as usize;
+
+        let sum: f32 = slots.iter().map(|(_, w)| **w).sum();
+        if (sum - 1.0).abs() <= WEIGHT_SUM_TOLERANCE {
+            continue;
+        }
+        unnormalized += !(range_fixed || invalid_fixed) as usize;
+        if sum <= 0.0 {
+            if let Some((joint, weight)) = slots.first_mut() {
+                **joint = 0.0;
+                **weight = 1.0;
+            }
+            continue;
+        }
+        for (_, weight) in &mut slots {
+            **weight /= sum;
+        }
+        if let Some(quantum) = quantum {
+            for (_, weight) in &mut slots {
+                **weight = (**weight / quantum).round() * quantum;
+            }
+            let rest = 1.0 - slots.iter().map(|(_, w)| **w).sum::<f32>();
+            if let Some((_, largest)) = slots.iter_mut().max_by(|a, b| a.1.total_cmp(b.1)) {
+                **largest += rest;
+            }
+        }
+    }
+    (out_of_range, invalid, unnormalized)
+}
+
+/// Step between representable weights of a normalized integer accessor, `None` for floats
+fn weight_quantum(accessor: &Accessor) -> Option<f32> {
+    match accessor.component_type {
+        Checked::Valid(GenericComponentType(ComponentType::U8)) => Some(1.0 / 255.0),
+        Checked::Valid(GenericComponentType(ComponentType::U16)) => Some(1.0 / 65535.0),
+        _ => None,
+    }
+}
+
+/// Skinned primitives whose joint indices or weights break the glTF rules
+pub(crate) fn skinning_issues(blob: &[u8], json: &Root) -> Vec<SkinningIssue> {
+    let joint_counts = mesh_joint_counts(json);
+    let mut issues = Vec::new();
+    for (m, mesh) in json.meshes.iter().enumerate() {
+        let Some(&joint_count) = joint_counts.get(&m) else {
+            continue;
+        };
+        for (p, primitive) in mesh.primitives.iter().enumerate() {
+            let Some(sets) = skin_sets(primitive).filter(|s| !s.is_empty()) else {
+                continue;
+            };
+            let Some((mut joints, mut weights)) = read_sets(blob, json, &sets) else {
+                continue;
+            };
+            let quantum = json
+                .accessors
+                .get(sets[0].1.value())
+                .and_then(weight_quantum);
+            let (out_of_range_joints, invalid_weights, unnormalized_weights) =
+                repair_vertices(&mut joints, &mut weights, joint_count, quantum);
+            if out_of_range_joints + invalid_weights + unnormalized_weights > 0 {
+                issues.push(SkinningIssue {
+                    mesh: m,
+                    primitive: p,
+                    joint_count,
+                    out_of_range_joints,
+                    invalid_weights,
+                    unnormalized_weights,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Point an accessor at a new dense copy of `values`, in its own component type
+fn rewrite_accessor(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    index: Index<Accessor>,
+    values: &[[f32; 4]],
+) -> Option<()> {
+    let accessor = n_json.accessors.get(index.value())?;
+    let Checked::Valid(GenericComponentType(component)) = accessor.component_type else {
+        return None;
+    };
+    let (bytes, _) = write_elements(values, component, accessor.normalized);
+    let view = n_blob.push_view(n_json, &bytes, Some(Target::ArrayBuffer));
+    let accessor = &mut n_json.accessors[index.value()];
+    accessor.buffer_view = Some(view);
+    accessor.byte_offset = None;
+    accessor.sparse = None;
+    accessor.min = None;
+    accessor.max = None;
+    Some(())
+}
+
+/// Fix the joint indices and weights [`skinning_issues`] reports, see [`repair_vertices`]
+///
+/// Accessors are rewritten in place, once each; a primitive whose accessors another
+/// primitive with a different set layout already rewrote is left alone. Returns `None` when
+/// nothing needed a fix.
+pub(crate) fn repair_skinning(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let joint_counts = mesh_joint_counts(o_json);
+
+    // Each set layout is fixed once for the smallest skin drawing any primitive using it
+    let mut layouts: Vec<(SkinSets, usize)> = Vec::new();
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        let Some(&joint_count) = joint_counts.get(&m) else {
+            continue;
+        };
+        for sets in mesh.primitives.iter().filter_map(skin_sets) {
+            if sets.is_empty() {
+                continue;
+            }
+            match layouts.iter_mut().find(|(s, _)| *s == sets) {
+                Some((_, count)) => *count = (*count).min(joint_count),
+                None => layouts.push((sets, joint_count)),
+            }
+        }
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut rewritten: HashSet<usize> = HashSet::new();
+    for (sets, joint_count) in layouts {
+        let accessors = || sets.iter().flat_map(|&(j, w)| [j.value(), w.value()]);
+        if accessors().any(|a| rewritten.contains(&a)) {
+            continue;
+        }
+        let Some((mut joints, mut weights)) = read_sets(o_blob, o_json, &sets) else {
+            continue;
+        };
+        let quantum = o_json
+            .accessors
+            .get(sets[0].1.value())
+            .and_then(weight_quantum);
+        let fixes = repair_vertices(&mut joints, &mut weights, joint_count, quantum);
+        if fixes == (0, 0, 0) {
+            continue;
+        }
+
+        for (&(j, w), (joints, weights)) in sets.iter().zip(joints.iter().zip(&weights)) {
+            for (index, values) in [(j, joints), (w, weights)] {
+                rewrite_accessor(&mut n_blob, &mut n_json, index, values).ok_or_else(|| {
+                    OptimizeError::Accessor {
+                        accessor: index.value(),
+                        problem: AccessorProblem::Unusable {
+                            usage: "JOINTS_n or WEIGHTS_n",
+                        },
+                    }
+                })?;
+            }
+        }
+        rewritten.extend(accessors());
+    }
+
+    Ok((!rewritten.is_empty()).then_some((n_json, n_blob.into_vec())))
+}
+
+/// Report skinned primitives with out-of-range joint indices or weights that don't add up
+/// to 1, as some mobile renderers crash on them
+pub fn validate_skinning<R: Read + Seek>(
+    reader: &mut R,
+) -> Result<Vec<SkinningIssue>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    Ok(skinning_issues(&o_blob, &o_json))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::write_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn skin_weights_are_validated_and_repaired() {
+        use gltf::json::mesh::Semantic;
+
+        let glb = SyntheticGlb {
+            skin: true,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let mut blob = BlobWriter::from(blob);
+
+        // The skin has two joints: vertex 1 uses joint 5, vertex 2 a negative weight and
+        // vertex 3 weights adding up to 0.75
+        let joints = [
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 5.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0; 4],
+        ];
+        let weights = [
+            [0.5, 0.5, 0.0, 0.0],
+            [0.5, 0.5, 0.0, 0.0],
+            [1.0, -0.5, 0.0, 0.0],
+            [0.25, 0.5, 0.0, 0.0],
+        ];
+        let attributes = json.meshes[0].primitives[0].attributes.clone();
+        for (semantic, values, component) in [
+            (Semantic::Joints(0), &joints, ComponentType::U8),
+            (Semantic::Weights(0), &weights, ComponentType::F32),
+        ] {
+            let (bytes, _) = write_elements(values, component, false);
+            let view = blob.push_view(&mut json, &bytes, None);
+            let accessor = attributes[&Checked::Valid(semantic)].value();
+            json.accessors[accessor].buffer_view = Some(view);
+        }
+        let glb = write_glb(&json, blob.into_vec()).unwrap();
+
+        let issues = validate_skinning(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(
+            issues,
+            [SkinningIssue {
+                mesh: 0,
+                primitive: 0,
+                joint_count: 2,
+                out_of_range_joints: 1,
+                invalid_weights: 1,
+                unnormalized_weights: 1,
+            }]
+        );
+
+        let options = OptimizeOptions {
+            repair_skin_weights: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        assert!(
+            validate_skinning(&mut Cursor::new(&out))
+                .unwrap()
+                .is_empty()
+        );
+
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let attributes = &n_json.meshes[0].primitives[0].attributes;
+        let read = |semantic| {
+            read_accessor::<4>(&n_blob, &n_json, attributes[&Checked::Valid(semantic)]).unwrap()
+        };
+        let (joints, weights) = (read(Semantic::Joints(0)), read(Semantic::Weights(0)));
+        assert_eq!(joints[1], [0.0; 4]);
+        assert_eq!(weights[1], [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(weights[2], [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(weights[0], [0.5, 0.5, 0.0, 0.0]);
+        for (weight, expected) in weights[3].iter().zip([1.0 / 3.0, 2.0 / 3.0, 0.0, 0.0]) {
+            assert!((weight - expected).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use gltf::json::{Index, Root, Texture};
+use image::DynamicImage;
+
+use crate::{
+    decode::load_texture_image,
+    opt::{TextureType, get_image_data},
+};
+
+/// Longest side pixels are sampled at, plenty for a channel distribution
+const SAMPLE_SIZE: u32 = 64;
+
+/// Largest distance of the mean red and green from 0.5 in a normal map
+const FLAT_CHANNEL_TOLERANCE: f32 = 0.1;
+
+/// Smallest mean blue of a normal map, whose texels mostly face out of the surface
+const MIN_MEAN_BLUE: f32 = 0.75;
+
+/// Largest distance from 1 of a decoded normal's length that still counts as unit length
+const UNIT_LENGTH_TOLERANCE: f32 = 0.2;
+
+/// Share of texels that must be blue-dominant unit vectors
+const MIN_NORMAL_SHARE: f32 = 0.9;
+
+/// Name suffixes naming the class of a texture, matched against the last word of a texture
+/// name, image name or image URI stem
+const NAME_CLASSES: [(&[&str], TextureType); 5] = [
+    (
+        &[
+            "bc",
+            "basecolor",
+            "albedo",
+            "diffuse",
+            "diff",
+            "col",
+            "color",
+        ],
+        TextureType::BaseColor,
+    ),
+    (&["n", "nrm", "nor", "nml", "normal"], TextureType::Normal),
+    (
+        &[
+            "orm",
+            "arm",
+            "rma",
+            "mr",
+            "metalrough",
+            "metallicroughness",
+            "metallic",
+            "roughness",
+        ],
+        TextureType::MetallicRoughness,
+    ),
+    (&["ao", "occ", "occlusion"], TextureType::Occlusion),
+    (
+        &["e", "emit", "emissive", "emission"],
+        TextureType::Emissive,
+    ),
+];
+
+/// A texture whose pixels look like they belong in another material slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlotMismatch {
+    pub(crate) material: usize,
+    pub(crate) texture: usize,
+    pub(crate) slot: TextureType,
+    pub(crate) looks_like: TextureType,
+}
+
+/// Whether pixels look like a tangent-space normal map
+///
+/// Red and green average about 0.5, blue is high, and nearly every texel decodes to a
+/// blue-dominant vector of unit length.
+pub(crate) fn looks_like_normal_map(img: &DynamicImage) -> bool {
+    let sample = img.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgb8();
+    let texels = sample.pixels().len();
+    if texels == 0 {
+        return false;
+    }
+
+    let mut sum = [0.0f32; 3];
+    let mut normals = 0;
+    for pixel in sample.pixels() {
+        let [r, g, b] = pixel.0.map(|c| c as f32 / 255.0);
+        sum[0] += r;
+        sum[1] += g;
+        sum[2] += b;
+        let [x, y, z] = [r, g, b].map(|c| c * 2.0 - 1.0);
+        let length = (x * x + y * y + z * z).sqrt();
+        if z > 0.0 && z >= x.abs() && z >= y.abs() && (length - 1.0).abs() <= UNIT_LENGTH_TOLERANCE
+        {
+            normals += 1;
+        }
+    }
+    let [r, g, b] = sum.map(|s| s / texels as f32);
+    (r - 0.5).abs() <= FLAT_CHANNEL_TOLERANCE
+        && (g - 0.5).abs() <= FLAT_CHANNEL_TOLERANCE
+        && b >= MIN_MEAN_BLUE
+        && normals as f32 >= MIN_NORMAL_SHARE * texels as f32
+}
+
+/// Class a texture name ends in, such as `Normal` for `brick_wall_n.png` or `Rock_ORM_2k`
+///
+/// The directory and file extension are ignored, as are trailing resolution words (`2k`, `1024`).
+pub(crate) fn name_class(name: &str) -> Option<TextureType> {
+    let file = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+    let word = stem
+        .split(['_', '-', ' ', '.'])
+        .rev()
+        .find(|w| {
+            !w.is_empty()
+                && !w
+                    .trim_end_matches(['k', 'K'])
+                    .bytes()
+                    .all(|b| b.is_ascii_digit())
+        })?
+        .to_ascii_lowercase();
+    NAME_CLASSES
+        .iter()
+        .find(|(words, _)| words.contains(&word.as_str()))
+        .map(|(_, class)| *class)
+}
+
+/// Whether two slot types are encoded alike: color, normal or linear data
+fn same_family(a: TextureType, b: TextureType) -> bool {
+    let family = |t| match t {
+        TextureType::BaseColor | TextureType::Emissive => 0,
+        TextureType::Normal => 1,
+        TextureType::MetallicRoughness | TextureType::Occlusion => 2,
+    };
+    family(a) == family(b)
+}
+
+/// Textures whose name or pixels say they belong to another class than the slot they are in
+///
+/// The texture name, image name and image URI are looked at first; a texture none of them
+/// classifies is checked for normal map pixels like in [`slot_mismatches`]. Classes that encode
+/// alike, such as occlusion in a metallic/roughness slot, are not reported.
+pub(crate) fn class_mismatches(o_blob: &[u8], o_json: &Root) -> Vec<SlotMismatch> {
+    let mut classes: HashMap<usize, Option<TextureType>> = HashMap::new();
+    let mut class = |texture: Index<Texture>, slot: TextureType| {
+        let named = o_json.textures.get(texture.value()).and_then(|t| {
+            let image = o_json.images.get(t.source.value());
+            [
+                t.name.as_deref(),
+                image.and_then(|i| i.name.as_deref()),
+                image.and_then(|i| i.uri.as_deref()),
+            ]
+            .into_iter()
+            .flatten()
+            .find_map(name_class)
+        });
+        if named.is_some() {
+            return named;
+        }
+        let pixels = *classes.entry(texture.value()).or_insert_with(|| {
+            let data = get_image_data(o_blob, o_json, texture)?;
+            let img = load_texture_image(o_json, texture, data, Vec::new()).ok()?;
+            Some(if looks_like_normal_map(&img) {
+                TextureType::Normal
+            } else {
+                TextureType::BaseColor
+            })
+        });
+        let pixel_class = pixels?;
+        match (slot, pixel_class) {
+            (TextureType::Normal, _) | (_, TextureType::Normal) => Some(pixel_class),
+            _ => None,
+        }
+    };
+
+    let mut mismatches = Vec::new();
+    for (material, mat) in o_json.materials.iter().enumerate() {
+        let pbr = &mat.pbr_metallic_roughness;
+        let slots = [
+            (
+                pbr.base_color_texture.as_ref().map(|i| i.index),
+                TextureType::BaseColor,
+            ),
+            (
+                pbr.metallic_roughness_texture.as_ref().map(|i| i.index),
+                TextureType::MetallicRoughness,
+            ),
+            (
+                mat.normal_texture.as_ref().map(|i| i.index),
+                TextureType::Normal,
+            ),
+            (
+                mat.occlusion_texture.as_ref().map(|i| i.index),
+                TextureType::Occlusion,
+            ),
+            (
+                mat.emissive_texture.as_ref().map(|i| i.index),
+                TextureType::Emissive,
+            ),
+        ];
+        for (texture, slot) in slots {
+            let Some(texture) = texture else {
+                continue;
+            };
+            let Some(looks_like) = class(texture, slot).filter(|&c| !same_family(c, slot)) else {
+                continue;
+            };
+            mismatches.push(SlotMismatch {
+                material,
+                texture: texture.value(),
+                slot,
+                looks_like,
+            });
+        }
+    }
+    mismatches
+}
+
+/// Normal maps wired into base color or emissive slots, and color images in normal slots
+///
+/// Every embedded image is decoded once; images that can't be decoded are left out.
+pub(crate) fn slot_mismatches(o_blob: &[u8], o_json: &Root) -> Vec<SlotMismatch> {
+    let mut normal_like: HashMap<usize, Option<bool>> = HashMap::new();
+    let mut looks_normal = |texture: Index<Texture>| {
+        *normal_like.entry(texture.value()).or_insert_with(|| {
+            let data = get_image_data(o_blob, o_json, texture)?;
+            let img = load_texture_image(o_json, texture, data, Vec::new()).ok()?;
+            Some(looks_like_normal_map(&img))
+        })
+    };
+
+    let mut mismatches = Vec::new();
+    for (material, mat) in o_json.materials.iter().enumerate() {
+        let slots = [
+            (
+                mat.pbr_metallic_roughness
+                    .base_color_texture
+                    .as_ref()
+                    .map(|i| i.index),
+                TextureType::BaseColor,
+            ),
+            (
+                mat.emissive_texture.as_ref().map(|i| i.index),
+                TextureType::Emissive,
+            ),
+            (
+                mat.normal_texture.as_ref().map(|i| i.index),
+                TextureType::Normal,
+            ),
+        ];
+        for (texture, slot) in slots {
+            let Some((texture, normal)) = texture.and_then(|t| Some((t, looks_normal(t)?))) else {
+                continue;
+            };
+            let looks_like = match (slot, normal) {
+                (TextureType::Normal, false) => TextureType::BaseColor,
+                (TextureType::BaseColor | TextureType::Emissive, true) => TextureType::Normal,
+                _ => continue,
+            };
+            mismatches.push(SlotMismatch {
+                material,
+                texture: texture.value(),
+                slot,
+                looks_like,
+            });
+        }
+    }
+    mismatches
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{encode_png, optimize_with},
+        options::OptimizeOptions,
+        replace_texture,
+        testing::SyntheticGlb,
+        warnings::{SceneWarning, find_scene_warnings},
+    };
+
+    #[test]
+    fn normal_map_in_base_color_slot_is_flagged() {
+        // Bumps around +Z, encoded the usual way
+        let normal_map = RgbaImage::from_fn(32, 32, |x, y| {
+            let (nx, ny) = ((x as f32 * 0.4).sin() * 0.3, (y as f32 * 0.4).cos() * 0.3);
+            let nz = (1.0 - nx * nx - ny * ny).sqrt();
+            let encode = |c: f32| ((c * 0.5 + 0.5) * 255.0).round() as u8;
+            image::Rgba([encode(nx), encode(ny), encode(nz), 255])
+        });
+        let gradient = RgbaImage::from_fn(32, 32, |x, y| {
+            image::Rgba([x as u8 * 8, y as u8 * 8, 64, 255])
+        });
+        let normal_map = DynamicImage::from(normal_map);
+        assert!(looks_like_normal_map(&normal_map));
+        assert!(!looks_like_normal_map(&DynamicImage::from(gradient)));
+        assert!(!looks_like_normal_map(&DynamicImage::from(
+            RgbaImage::from_pixel(8, 8, image::Rgba([128, 128, 128, 255]))
+        )));
+
+        let mut png = Vec::new();
+        encode_png(normal_map.as_bytes(), 32, 32, normal_map.color(), &mut png).unwrap();
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let glb = replace_texture(&mut Cursor::new(glb), 0, &png, None).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(
+            find_scene_warnings(&blob, &json),
+            [SceneWarning::MisslottedTexture {
+                material: 0,
+                texture: 0,
+                slot: TextureType::BaseColor,
+                looks_like: TextureType::Normal,
+            }]
+        );
+
+        // Base color goes to JPEG, normal map handling to PNG
+        let mime_type = |fix_misslotted_textures| {
+            let options = OptimizeOptions {
+                fix_misslotted_textures,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+            n_json.images[0].mime_type.clone().unwrap().0
+        };
+        assert_eq!(mime_type(false), "image/jpeg");
+        assert_eq!(mime_type(true), "image/png");
+    }
+}
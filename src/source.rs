@@ -0,0 +1,324 @@
+use std::{
+    error::Error,
+    fs,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+};
+
+use base64::Engine;
+use gltf::json::{Index, Root, image::MimeType};
+
+use crate::{
+    blob::BlobWriter,
+    error::{Malformation, OptimizeError, Stage},
+    glb::{read_glb, write_glb},
+    legacy::parse_error,
+    opt::optimize_with,
+    options::OptimizeOptions,
+};
+
+/// Loader for the files behind relative buffer and image URIs
+///
+/// Base64 `data:` URIs are decoded before a resolver is asked. Implemented for a `PathBuf`,
+/// resolving against that directory, and for closures taking the URI.
+pub trait SourceResolver {
+    /// Bytes of the file `uri` (still percent-encoded) points to
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>;
+}
+
+impl SourceResolver for PathBuf {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        if uri.contains("://") {
+            return Err(format!("unsupported URI scheme: {uri}").into());
+        }
+        let relative = PathBuf::from(percent_decode(uri));
+        if !stays_inside(&relative) {
+            return Err(format!("URI leaves the base directory: {uri}").into());
+        }
+        let path = self.join(relative);
+        fs::read(&path).map_err(|e| format!("failed to read '{}': {e}", path.display()).into())
+    }
+}
+
+impl<F: Fn(&str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>> SourceResolver for F {
+    fn resolve(&self, uri: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        self(uri)
+    }
+}
+
+/// Whether a relative path only names files at or below the directory it is resolved against
+///
+/// Absolute paths, drive prefixes and `..` components climbing above the start are rejected.
+fn stays_inside(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(up) => depth = up,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Decode `%XX` escapes in a URI
+pub(crate) fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Bytes behind a buffer or image URI: a base64 `data:` URI or whatever `resolver` loads
+fn resolve_uri(resolver: &dyn SourceResolver, uri: &str) -> Result<Vec<u8>, OptimizeError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, payload) =
+            data.split_once(";base64,")
+                .ok_or_else(|| OptimizeError::Unsupported {
+                    what: format!("data URI {}", uri.chars().take(40).collect::<String>()),
+                })?;
+        return base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|_| OptimizeError::Malformed(Malformation::DataUriBase64));
+    }
+    resolver
+        .resolve(uri)
+        .map_err(|source| OptimizeError::Callback {
+            stage: Stage::SourceResolver,
+            source,
+        })
+}
+
+/// Image MIME type from a URI's file extension
+fn mime_type_for(uri: &str) -> Option<&'static str> {
+    let extension = uri.rsplit('.').next()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "ktx2" => Some("image/ktx2"),
+        _ => None,
+    }
+}
+
+/// Pack a document and its external resources into GLB bytes
+///
+/// A buffer without URI is the GLB's own `blob`. Every buffer is concatenated into the BIN
+/// chunk and every image with a URI becomes a buffer view.
+pub(crate) fn pack_gltf(
+    mut json: Root,
+    blob: Vec<u8>,
+    resolver: &dyn SourceResolver,
+) -> Result<Vec<u8>, OptimizeError> {
+    let mut embedded = Some(blob);
+    let mut n_blob = BlobWriter::new();
+    let mut offsets = Vec::with_capacity(json.buffers.len());
+    for buffer in &json.buffers {
+        let data = match buffer.uri.as_deref() {
+            Some(uri) => resolve_uri(resolver, uri)?,
+            None => embedded
+                .take()
+                .ok_or(OptimizeError::Malformed(Malformation::BuffersWithoutUri))?,
+        };
+        if data.len() < buffer.byte_length.0 as usize {
+            let name = buffer.uri.as_deref().unwrap_or("BIN chunk");
+            return Err(OptimizeError::Truncated {
+                what: format!("byteLength of buffer '{name}'"),
+            });
+        }
+        offsets.push(n_blob.append(&data));
+    }
+    for view in &mut json.buffer_views {
+        let buffer = view.buffer.value();
+        let offset = offsets.get(buffer).ok_or(OptimizeError::MissingObject {
+            kind: "buffer",
+            index: buffer,
+        })? + view.byte_offset.map_or(0, |o| o.0 as usize);
+        view.buffer = Index::new(0);
+        view.byte_offset = (offset > 0).then(|| offset.into());
+    }
+    json.buffers.truncate(1);
+    if let Some(buffer) = json.buffers.first_mut() {
+        buffer.uri = None;
+    }
+
+    for i in 0..json.images.len() {
+        let Some(uri) = json.images[i].uri.clone() else {
+            continue;
+        };
+        let data = resolve_uri(resolver, &uri)?;
+        let view = n_blob.push_view(&mut json, &data, None);
+        let image = &mut json.images[i];
+        image.buffer_view = Some(view);
+        image.uri = None;
+        if image.mime_type.is_none() {
+            let mime_type = mime_type_for(&uri).or_else(|| {
+                let media = uri.strip_prefix("data:")?.split(';').next()?;
+                mime_type_for(&format!(".{}", media.rsplit('/').next()?))
+            });
+            image.mime_type = mime_type.map(|m| MimeType(m.to_string()));
+        }
+    }
+
+    write_glb(&json, n_blob)
+}
+
+/// Turn a `.gltf`, or a GLB referencing external files, into a self-contained GLB
+///
+/// Buffer and image URIs are loaded through `resolver`; base64 `data:` URIs are decoded.
+pub fn glb_from_gltf<R: Read + Seek>(
+    reader: &mut R,
+    resolver: &dyn SourceResolver,
+) -> Result<Vec<u8>, OptimizeError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    reader.seek(SeekFrom::Current(-4))?;
+
+    let (json, blob) = if &magic == b"glTF" {
+        read_glb(reader)?
+    } else {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let json = gltf::json::deserialize::from_slice(&data)
+            .map_err(|e| parse_error(&data, gltf::Error::Deserialize(e)))?;
+        (json, Vec::new())
+    };
+    pack_gltf(json, blob, resolver)
+}
+
+/// Optimize a `.gltf` or `.glb` file, loading external buffers and images next to it
+pub fn optimize_gltf<P: AsRef<Path>>(
+    path: P,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    let path = path.as_ref();
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let glb = glb_from_gltf(&mut fs::File::open(path)?, &base)?;
+    optimize_with(&mut Cursor::new(glb), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_stay_inside_the_base_directory() {
+        let base = std::env::temp_dir().join("gltf_opt_source_test");
+        fs::create_dir_all(base.join("textures")).unwrap();
+        fs::write(base.join("inside.bin"), [1, 2, 3]).unwrap();
+
+        assert_eq!(base.resolve("textures/../inside.bin").unwrap(), [1, 2, 3]);
+        assert_eq!(base.resolve("./inside%2Ebin").unwrap(), [1, 2, 3]);
+        for uri in [
+            "../inside.bin",
+            "textures/../../x.bin",
+            "%2E%2E/x.bin",
+            "/etc/passwd",
+        ] {
+            let error = base.resolve(uri).unwrap_err().to_string();
+            assert!(
+                error.contains("leaves the base directory"),
+                "{uri}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn non_base64_data_uris_are_unsupported() {
+        let resolver = |uri: &str| -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            Err(format!("unexpected {uri}").into())
+        };
+        // A multi-byte character straddles byte 40 of the URI
+        let uri = format!("data:text/plain;x{}", "é".repeat(30));
+        let error = resolve_uri(&resolver, &uri).unwrap_err();
+        let OptimizeError::Unsupported { what } = error else {
+            panic!("unexpected {error}");
+        };
+        assert_eq!(what.chars().count(), "data URI ".len() + 40);
+    }
+
+    #[test]
+    fn views_of_missing_buffers_are_errors() {
+        let json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 4 }],
+            "bufferViews": [{ "buffer": 3, "byteLength": 4 }],
+        }))
+        .unwrap();
+        let resolver = |uri: &str| -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            Err(format!("unexpected {uri}").into())
+        };
+        let error = pack_gltf(json, vec![0; 4], &resolver).unwrap_err();
+        assert!(
+            matches!(
+                error,
+                OptimizeError::MissingObject {
+                    kind: "buffer",
+                    index: 3
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn gltf_with_external_files_is_packed() {
+        use crate::{
+            opt::get_image_data,
+            testing::{SyntheticGlb, compare_glbs},
+        };
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let png = get_image_data(&blob, &json, Index::new(0))
+            .unwrap()
+            .to_vec();
+        json.buffers[0].uri = Some("model.bin".to_string());
+        json.images[0].buffer_view = None;
+        json.images[0].mime_type = None;
+        json.images[0].uri = Some("base%20color.png".to_string());
+        let gltf = gltf::json::serialize::to_vec(&json).unwrap();
+
+        let resolver = |uri: &str| -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            match uri {
+                "model.bin" => Ok(blob.clone()),
+                "base%20color.png" => Ok(png.clone()),
+                _ => Err(format!("unexpected uri {uri}").into()),
+            }
+        };
+        let packed = glb_from_gltf(&mut Cursor::new(&gltf), &resolver).unwrap();
+        let options = OptimizeOptions::default();
+        let expected = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let actual = optimize_with(&mut Cursor::new(&packed), &options).unwrap();
+        assert_eq!(
+            compare_glbs(&expected, &actual, &Default::default()).unwrap(),
+            []
+        );
+    }
+}
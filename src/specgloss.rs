@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+
+use gltf::json::{
+    Index, Root, Texture,
+    image::MimeType,
+    material::{PbrBaseColorFactor, StrengthFactor},
+    texture::Info,
+};
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage, imageops::FilterType};
+use serde_json::Value;
+
+use crate::{
+    blob::BlobWriter,
+    crop::RewrittenDocument,
+    decode::load_source_image,
+    error::OptimizeError,
+    opt::{encode_png, get_image_data},
+    srgb::{linear_to_srgb, srgb_to_linear},
+};
+
+/// Legacy material model replaced by metallic/roughness on the way in
+pub(crate) const SPEC_GLOSS_EXTENSION: &str = "KHR_materials_pbrSpecularGlossiness";
+
+/// Reflectance of dielectrics in the metallic/roughness model
+const DIELECTRIC_SPECULAR: f32 = 0.04;
+
+/// Keeps the base color solve away from dividing by zero
+const EPSILON: f32 = 1e-6;
+
+/// Parameters of one `KHR_materials_pbrSpecularGlossiness` payload
+struct SpecGloss {
+    diffuse_factor: [f32; 4],
+    specular_factor: [f32; 3],
+    glossiness_factor: f32,
+    diffuse_texture: Option<Value>,
+    specular_glossiness_texture: Option<Value>,
+}
+
+impl SpecGloss {
+    fn parse(payload: &Value) -> SpecGloss {
+        let factors = |key: &str| -> Option<Vec<f32>> {
+            let values = payload.get(key)?.as_array()?;
+            values
+                .iter()
+                .map(|v| v.as_f64().map(|v| v as f32))
+                .collect()
+        };
+        let diffuse = factors("diffuseFactor").filter(|f| f.len() == 4);
+        let specular = factors("specularFactor").filter(|f| f.len() == 3);
+        let texture = |key: &str| {
+            payload
+                .get(key)
+                .filter(|t| t.get("index").is_some())
+                .cloned()
+        };
+        SpecGloss {
+            diffuse_factor: diffuse.map_or([1.0; 4], |f| [f[0], f[1], f[2], f[3]]),
+            specular_factor: specular.map_or([1.0; 3], |f| [f[0], f[1], f[2]]),
+            glossiness_factor: payload
+                .get("glossinessFactor")
+                .and_then(Value::as_f64)
+                .map_or(1.0, |g| g as f32),
+            diffuse_texture: texture("diffuseTexture"),
+            specular_glossiness_texture: texture("specularGlossinessTexture"),
+        }
+    }
+}
+
+/// Metallic/roughness parameters of one linear diffuse and specular color and a glossiness
+///
+/// Returns the linear base color, metallic and roughness. Metallic is solved from how much
+/// brighter the specular color is than a dielectric's; the base color blends the diffuse and
+/// specular colors by it.
+fn metal_rough(diffuse: [f32; 4], specular: [f32; 3], glossiness: f32) -> ([f32; 4], f32, f32) {
+    let one_minus_specular = 1.0 - specular.iter().copied().fold(0.0, f32::max);
+    let metallic = solve_metallic(
+        perceived_brightness(&diffuse[..3]),
+        perceived_brightness(&specular),
+        one_minus_specular,
+    );
+
+    let mut base = [0.0; 4];
+    for i in 0..3 {
+        let from_diffuse = diffuse[i] * one_minus_specular
+            / (1.0 - DIELECTRIC_SPECULAR)
+            / (1.0 - metallic).max(EPSILON);
+        let from_specular =
+            (specular[i] - DIELECTRIC_SPECULAR * (1.0 - metallic)) / metallic.max(EPSILON);
+        let blend = metallic * metallic;
+        base[i] = (from_diffuse + (from_specular - from_diffuse) * blend).clamp(0.0, 1.0);
+    }
+    base[3] = diffuse[3];
+    (base, metallic, (1.0 - glossiness).clamp(0.0, 1.0))
+}
+
+fn perceived_brightness(color: &[f32]) -> f32 {
+    (0.299 * color[0] * color[0] + 0.587 * color[1] * color[1] + 0.114 * color[2] * color[2]).sqrt()
+}
+
+fn solve_metallic(diffuse: f32, specular: f32, one_minus_specular: f32) -> f32 {
+    if specular < DIELECTRIC_SPECULAR {
+        return 0.0;
+    }
+    let a = DIELECTRIC_SPECULAR;
+    let b = diffuse * one_minus_specular / (1.0 - DIELECTRIC_SPECULAR) + specular
+        - 2.0 * DIELECTRIC_SPECULAR;
+    let c = DIELECTRIC_SPECULAR - specular;
+    let discriminant = (b * b - 4.0 * a * c).max(0.0);
+    ((-b + discriminant.sqrt()) / (2.0 * a)).clamp(0.0, 1.0)
+}
+
+/// Decoded pixels of a texture info's texture, `None` when its image is not embedded
+fn decode_info(
+    o_blob: &[u8],
+    o_json: &Root,
+    info: &Value,
+) -> Result<Option<RgbaImage>, OptimizeError> {
+    let Some(texture) = info.get("index").and_then(Value::as_u64) else {
+        return Ok(None);
+    };
+    let texture = Index::<Texture>::new(texture as u32);
+    let Some(image) = o_json
+        .textures
+        .get(texture.value())
+        .map(|t| t.source.value())
+    else {
+        return Ok(None);
+    };
+    let Some(data) = get_image_data(o_blob, o_json, texture) else {
+        return Ok(None);
+    };
+    Ok(Some(
+        load_source_image(o_json, image, data, Vec::new())?.to_rgba8(),
+    ))
+}
+
+/// Whether two texture infos read the same texels at the same coordinates
+fn same_mapping(a: &Value, b: &Value) -> bool {
+    let tex_coord = |info: &Value| info.get("texCoord").and_then(Value::as_u64).unwrap_or(0);
+    tex_coord(a) == tex_coord(b) && a.get("extensions") == b.get("extensions")
+}
+
+/// Base color and metallic/roughness images baked from the textures and factors of `sg`
+///
+/// Both images take the size of the larger source; metallic/roughness has no alpha. The specular/glossiness texture is only
+/// read when it shares the diffuse texture's coordinates, and its factors are used otherwise.
+fn bake_images(
+    sg: &SpecGloss,
+    diffuse: Option<&RgbaImage>,
+    specular_glossiness: Option<&RgbaImage>,
+) -> (DynamicImage, DynamicImage) {
+    let (width, height) = [diffuse, specular_glossiness]
+        .into_iter()
+        .flatten()
+        .map(|img| img.dimensions())
+        .max_by_key(|(w, h)| *w as u64 * *h as u64)
+        .unwrap_or((1, 1));
+    let fit = |img: Option<&RgbaImage>| {
+        img.map(|img| match img.dimensions() == (width, height) {
+            true => img.clone(),
+            false => DynamicImage::from(img.clone())
+                .resize_exact(width, height, FilterType::Triangle)
+                .to_rgba8(),
+        })
+    };
+    let (diffuse, specular_glossiness) = (fit(diffuse), fit(specular_glossiness));
+
+    let mut base_color = RgbaImage::new(width, height);
+    let mut metallic_roughness = RgbImage::new(width, height);
+    for (x, y, base) in base_color.enumerate_pixels_mut() {
+        let mut d = sg.diffuse_factor;
+        if let Some(texel) = diffuse.as_ref().map(|img| img.get_pixel(x, y).0) {
+            for i in 0..3 {
+                d[i] *= srgb_to_linear(texel[i]);
+            }
+            d[3] *= texel[3] as f32 / 255.0;
+        }
+        let mut s = sg.specular_factor;
+        let mut g = sg.glossiness_factor;
+        if let Some(texel) = specular_glossiness
+            .as_ref()
+            .map(|img| img.get_pixel(x, y).0)
+        {
+            for i in 0..3 {
+                s[i] *= srgb_to_linear(texel[i]);
+            }
+            g *= texel[3] as f32 / 255.0;
+        }
+
+        let (color, metallic, roughness) = metal_rough(d, s, g);
+        let unit = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+        *base = Rgba([
+            linear_to_srgb(color[0]),
+            linear_to_srgb(color[1]),
+            linear_to_srgb(color[2]),
+            unit(color[3]),
+        ]);
+        metallic_roughness.put_pixel(x, y, Rgb([0, unit(roughness), unit(metallic)]));
+    }
+    (base_color.into(), metallic_roughness.into())
+}
+
+/// Append a PNG image and a texture sampling it like `like`'s texture
+fn push_texture(
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    img: &DynamicImage,
+    like: &Value,
+) -> Result<Info, OptimizeError> {
+    let mut png = Vec::new();
+    encode_png(
+        img.as_bytes(),
+        img.width(),
+        img.height(),
+        img.color(),
+        &mut png,
+    )?;
+    let view = n_blob.push_view(n_json, &png, None);
+    let image = n_json.push(gltf::json::Image {
+        buffer_view: Some(view),
+        mime_type: Some(MimeType("image/png".to_string())),
+        uri: None,
+        name: None,
+        extensions: None,
+        extras: Default::default(),
+    });
+
+    let sampler = like
+        .get("index")
+        .and_then(Value::as_u64)
+        .and_then(|t| n_json.textures.get(t as usize))
+        .and_then(|t| t.sampler);
+    let texture = n_json.push(Texture {
+        sampler,
+        source: image,
+        name: None,
+        extensions: None,
+        extras: Default::default(),
+    });
+
+    let mut info = like.clone();
+    info["index"] = texture.value().into();
+    Ok(serde_json::from_value(info)?)
+}
+
+/// Source document with every `KHR_materials_pbrSpecularGlossiness` material converted to
+/// metallic/roughness, `None` when no material uses the extension
+///
+/// Factor-only materials get converted factors. Textured ones get a new base color and
+/// metallic/roughness texture baked texel by texel, shared by materials with the same
+/// parameters; textures not embedded in the binary chunk are kept as the base color with
+/// converted factors. The extension is taken out of `extensionsUsed` and `extensionsRequired`.
+pub(crate) fn convert_spec_gloss(
+    o_blob: &[u8],
+    o_json: &Root,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let payload = |material: &gltf::json::Material| {
+        material
+            .extensions
+            .as_ref()
+            .and_then(|e| e.others.get(SPEC_GLOSS_EXTENSION))
+            .cloned()
+    };
+    if !o_json.materials.iter().any(|m| payload(m).is_some()) {
+        return Ok(None);
+    }
+
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut baked: HashMap<String, (Info, Info)> = HashMap::new();
+    for m in 0..n_json.materials.len() {
+        let Some(payload) = payload(&n_json.materials[m]) else {
+            continue;
+        };
+        let sg = SpecGloss::parse(&payload);
+        let diffuse = match &sg.diffuse_texture {
+            Some(info) => decode_info(o_blob, o_json, info)?,
+            None => None,
+        };
+        let specular_glossiness = match (&sg.specular_glossiness_texture, &sg.diffuse_texture) {
+            (Some(info), Some(diffuse)) if !same_mapping(info, diffuse) => None,
+            (Some(info), _) => decode_info(o_blob, o_json, info)?,
+            _ => None,
+        };
+        let like = sg
+            .diffuse_texture
+            .as_ref()
+            .filter(|_| diffuse.is_some())
+            .or(sg
+                .specular_glossiness_texture
+                .as_ref()
+                .filter(|_| specular_glossiness.is_some()));
+
+        let (color, metallic, roughness, textures) = match like {
+            Some(like) => {
+                let key = serde_json::to_string(&payload)?;
+                let infos = match baked.get(&key) {
+                    Some(infos) => infos.clone(),
+                    None => {
+                        let (color_img, mr_img) =
+                            bake_images(&sg, diffuse.as_ref(), specular_glossiness.as_ref());
+                        let infos = (
+                            push_texture(&mut n_blob, &mut n_json, &color_img, like)?,
+                            push_texture(&mut n_blob, &mut n_json, &mr_img, like)?,
+                        );
+                        baked.insert(key, infos.clone());
+                        infos
+                    }
+                };
+                ([1.0; 4], 1.0, 1.0, (Some(infos.0), Some(infos.1)))
+            }
+            // Nothing to bake: convert the factors and keep a diffuse texture as it is
+            None => {
+                let (color, metallic, roughness) =
+                    metal_rough(sg.diffuse_factor, sg.specular_factor, sg.glossiness_factor);
+                let base_color = match &sg.diffuse_texture {
+                    Some(info) => Some(serde_json::from_value(info.clone())?),
+                    None => None,
+                };
+                (color, metallic, roughness, (base_color, None))
+            }
+        };
+
+        let material = &mut n_json.materials[m];
+        if let Some(extensions) = &mut material.extensions {
+            extensions.others.remove(SPEC_GLOSS_EXTENSION);
+        }
+        let pbr = &mut material.pbr_metallic_roughness;
+        pbr.base_color_factor = PbrBaseColorFactor(color);
+        pbr.metallic_factor = StrengthFactor(metallic);
+        pbr.roughness_factor = StrengthFactor(roughness);
+        (pbr.base_color_texture, pbr.metallic_roughness_texture) = textures;
+    }
+
+    n_json.extensions_used.retain(|e| e != SPEC_GLOSS_EXTENSION);
+    n_json
+        .extensions_required
+        .retain(|e| e != SPEC_GLOSS_EXTENSION);
+    Ok(Some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::write_glb, opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn spec_gloss_materials_become_metal_rough() {
+        use crate::{decode::load_image, glb::read_glb};
+
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let payloads = [
+            // Shiny metal over the first texture
+            serde_json::json!({
+                "diffuseTexture": { "index": 0 },
+                "specularFactor": [0.9, 0.9, 0.9],
+                "glossinessFactor": 0.8,
+            }),
+            // Plain dielectric, factors only
+            serde_json::json!({
+                "diffuseFactor": [0.5, 0.2, 0.1, 1.0],
+                "specularFactor": [0.04, 0.04, 0.04],
+                "glossinessFactor": 0.25,
+            }),
+        ];
+        for (material, payload) in json.materials.iter_mut().zip(payloads) {
+            material.pbr_metallic_roughness.base_color_texture = None;
+            material
+                .extensions
+                .get_or_insert_with(Default::default)
+                .others
+                .insert(SPEC_GLOSS_EXTENSION.to_string(), payload);
+        }
+        json.extensions_used.push(SPEC_GLOSS_EXTENSION.to_string());
+        json.extensions_required
+            .push(SPEC_GLOSS_EXTENSION.to_string());
+        let glb = write_glb(&json, blob).unwrap();
+
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        let spec_gloss = SPEC_GLOSS_EXTENSION.to_string();
+        assert!(!n_json.extensions_used.contains(&spec_gloss));
+        assert!(!n_json.extensions_required.contains(&spec_gloss));
+        let material = |name: &str| {
+            n_json
+                .materials
+                .iter()
+                .find(|m| m.name.as_deref() == Some(name))
+                .unwrap()
+        };
+
+        let metal = &material("material_0").pbr_metallic_roughness;
+        assert!(metal.base_color_texture.is_some());
+        let mr = metal.metallic_roughness_texture.as_ref().unwrap().index;
+        let mr = load_image(get_image_data(&n_blob, &n_json, mr).unwrap(), Vec::new())
+            .unwrap()
+            .to_rgb8();
+        let texels = mr.pixels().len() as f32;
+        let mean = |c: usize| mr.pixels().map(|p| p.0[c] as f32).sum::<f32>() / texels;
+        assert!(mean(2) > 200.0, "metallic {}", mean(2));
+        assert!((mean(1) - 0.2 * 255.0).abs() < 2.0, "roughness {}", mean(1));
+
+        let dielectric = &material("material_1").pbr_metallic_roughness;
+        assert!(dielectric.base_color_texture.is_none());
+        assert!(dielectric.metallic_roughness_texture.is_none());
+        assert_eq!(dielectric.metallic_factor.0, 0.0);
+        assert!((dielectric.roughness_factor.0 - 0.75).abs() < 1e-6);
+        for (converted, diffuse) in dielectric
+            .base_color_factor
+            .0
+            .iter()
+            .zip([0.5, 0.2, 0.1, 1.0])
+        {
+            assert!((converted - diffuse).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+
+use gltf::json::{
+    Accessor, Index, Root,
+    mesh::{Mode, MorphTarget, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    blob::BlobWriter,
+    crop::RewrittenDocument,
+    error::OptimizeError,
+    glb::{gather_accessor, merge_extras, push_indices},
+    hints::object_hints,
+    math::bounds,
+    opt::{get_index_data, get_position_data},
+};
+
+/// Splitting of large primitives into a spatial grid, see
+/// [`OptimizeOptions::split_primitives`](crate::OptimizeOptions::split_primitives)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GridSplit {
+    /// Primitives with fewer triangles are left whole
+    pub min_triangles: usize,
+    /// Grid cells along the longest side of the primitive's bounds
+    pub cells: u32,
+}
+
+impl Default for GridSplit {
+    fn default() -> Self {
+        GridSplit {
+            min_triangles: 65_536,
+            cells: 4,
+        }
+    }
+}
+
+/// `extras` key holding a split primitive's grid cell and bounds
+pub(crate) const GRID_CELL_KEY: &str = "gridCell";
+
+/// Triangles of a primitive grouped by the grid cell holding their centroid
+fn grid_cells(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    cells: u32,
+) -> BTreeMap<[u32; 3], Vec<[u32; 3]>> {
+    let (min, max) = bounds(positions);
+    let longest = (0..3).map(|a| max[a] - min[a]).fold(0.0f32, f32::max);
+    let cell_size = (longest / cells.max(1) as f32).max(f32::MIN_POSITIVE);
+
+    let mut grouped: BTreeMap<[u32; 3], Vec<[u32; 3]>> = BTreeMap::new();
+    for t in indices.chunks_exact(3) {
+        let cell = [0, 1, 2].map(|a| {
+            let centroid = t.iter().map(|&i| positions[i as usize][a]).sum::<f32>() / 3.0;
+            (((centroid - min[a]) / cell_size) as u32).min(cells.max(1) - 1)
+        });
+        grouped.entry(cell).or_default().push([t[0], t[1], t[2]]);
+    }
+    grouped
+}
+
+/// Split one primitive into a primitive per occupied grid cell, or `None` to keep it whole
+fn split_primitive(
+    o_blob: &[u8],
+    o_json: &Root,
+    n_blob: &mut BlobWriter,
+    n_json: &mut Root,
+    primitive: &Primitive,
+    split: &GridSplit,
+) -> Option<Vec<Primitive>> {
+    if primitive.mode != Checked::Valid(Mode::Triangles) {
+        return None;
+    }
+    let position = *primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    let positions = get_position_data(o_blob, o_json, position)?;
+    let indices = match primitive.indices {
+        Some(idx) => get_index_data(o_blob, o_json, idx)?,
+        None => (0..positions.len() as u32).collect(),
+    };
+    if indices.len() / 3 < split.min_triangles
+        || indices.iter().any(|&i| i as usize >= positions.len())
+    {
+        return None;
+    }
+    let cells = grid_cells(&positions, &indices, split.cells);
+    if cells.len() < 2 {
+        return None;
+    }
+
+    let mut primitives = Vec::with_capacity(cells.len());
+    for (cell, triangles) in cells {
+        // Vertices used by the cell, in first-use order
+        let mut remap = vec![u32::MAX; positions.len()];
+        let mut vertices = Vec::new();
+        let cell_indices: Vec<u32> = triangles
+            .iter()
+            .flatten()
+            .map(|&i| {
+                if remap[i as usize] == u32::MAX {
+                    remap[i as usize] = vertices.len() as u32;
+                    vertices.push(i);
+                }
+                remap[i as usize]
+            })
+            .collect();
+
+        let mut n_primitive = primitive.clone();
+        for (semantic, accessor) in &primitive.attributes {
+            let gathered = gather_accessor(o_blob, o_json, n_blob, n_json, *accessor, &vertices)?;
+            n_primitive.attributes.insert(semantic.clone(), gathered);
+        }
+        if let Some(targets) = &primitive.targets {
+            let mut n_targets = Vec::with_capacity(targets.len());
+            for target in targets {
+                let mut gather = |accessor: Option<Index<Accessor>>| match accessor {
+                    Some(a) => {
+                        gather_accessor(o_blob, o_json, n_blob, n_json, a, &vertices).map(Some)
+                    }
+                    None => Some(None),
+                };
+                n_targets.push(MorphTarget {
+                    positions: gather(target.positions)?,
+                    normals: gather(target.normals)?,
+                    tangents: gather(target.tangents)?,
+                });
+            }
+            n_primitive.targets = Some(n_targets);
+        }
+        n_primitive.indices = Some(push_indices(n_blob, n_json, &cell_indices));
+
+        let cell_positions: Vec<[f32; 3]> =
+            vertices.iter().map(|&v| positions[v as usize]).collect();
+        let (min, max) = bounds(&cell_positions);
+        n_primitive.extras = merge_extras(
+            &primitive.extras,
+            GRID_CELL_KEY,
+            serde_json::json!({ "cell": cell, "min": min, "max": max }),
+        );
+        primitives.push(n_primitive);
+    }
+    Some(primitives)
+}
+
+/// Split triangle primitives above `split.min_triangles` into a grid of smaller primitives
+///
+/// Each cell becomes its own primitive with the original material, every vertex attribute
+/// and morph target copied for the vertices it uses, and its bounds recorded in `extras` so
+/// engines can cull parts of huge scan meshes. Returns `None` when nothing was split.
+pub(crate) fn split_large_primitives(
+    o_blob: &[u8],
+    o_json: &Root,
+    split: &GridSplit,
+) -> Result<Option<RewrittenDocument>, OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut changed = false;
+
+    for (m, mesh) in o_json.meshes.iter().enumerate() {
+        if object_hints(&mesh.extras).skip {
+            continue;
+        }
+        let mut primitives = Vec::with_capacity(mesh.primitives.len());
+        let mut split_any = false;
+        for primitive in &mesh.primitives {
+            match split_primitive(o_blob, o_json, &mut n_blob, &mut n_json, primitive, split) {
+                Some(cells) => {
+                    primitives.extend(cells);
+                    split_any = true;
+                }
+                None => primitives.push(primitive.clone()),
+            }
+        }
+        if split_any {
+            n_json.meshes[m].primitives = primitives;
+            changed = true;
+        }
+    }
+
+    Ok(changed.then_some((n_json, n_blob.into_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{glb::read_glb, testing::SyntheticGlb};
+
+    fn quad() -> (Root, Vec<u8>) {
+        let glb = SyntheticGlb::default().build().unwrap();
+        read_glb(&mut Cursor::new(glb)).unwrap()
+    }
+
+    #[test]
+    fn triangles_are_grouped_by_grid_cell() {
+        let (o_json, o_blob) = quad();
+        let split = GridSplit {
+            min_triangles: 2,
+            cells: 2,
+        };
+        let (n_json, n_blob) = split_large_primitives(&o_blob, &o_json, &split)
+            .unwrap()
+            .unwrap();
+        let primitives = &n_json.meshes[0].primitives;
+        assert_eq!(primitives.len(), 2);
+
+        let o_positions = get_position_data(&o_blob, &o_json, Index::new(0)).unwrap();
+        let position = Checked::Valid(Semantic::Positions);
+        // Cells come in grid order: the upper-left triangle (cell [0, 1, 0]) first
+        for (primitive, corners) in primitives.iter().zip([[0, 2, 3], [0, 1, 2]]) {
+            let indices = get_index_data(&n_blob, &n_json, primitive.indices.unwrap()).unwrap();
+            let positions =
+                get_position_data(&n_blob, &n_json, primitive.attributes[&position]).unwrap();
+            let triangle: Vec<_> = indices.iter().map(|&i| positions[i as usize]).collect();
+            assert_eq!(triangle, corners.map(|c| o_positions[c]));
+            assert_eq!(primitive.attributes.len(), 3);
+
+            let extras: serde_json::Value =
+                serde_json::from_str(primitive.extras.as_ref().unwrap().get()).unwrap();
+            let (min, max) = bounds(&positions);
+            assert_eq!(extras[GRID_CELL_KEY]["min"], serde_json::json!(min));
+            assert_eq!(extras[GRID_CELL_KEY]["max"], serde_json::json!(max));
+        }
+    }
+
+    #[test]
+    fn small_primitives_stay_whole() {
+        let (o_json, o_blob) = quad();
+        let split = GridSplit {
+            min_triangles: 3,
+            cells: 2,
+        };
+        assert!(
+            split_large_primitives(&o_blob, &o_json, &split)
+                .unwrap()
+                .is_none()
+        );
+        let split = GridSplit {
+            min_triangles: 2,
+            cells: 1,
+        };
+        assert!(
+            split_large_primitives(&o_blob, &o_json, &split)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use gltf::json::{Index, Root, Texture};
+use image::DynamicImage;
+
+use crate::{
+    decode::load_texture_image, glb::from_extras, opt::get_image_data, stats::image_dimensions,
+};
+
+/// Image `extras` entry that keeps an image at its source size when set to `true`
+pub(crate) const NO_RESIZE_KEY: &str = "no_resize";
+
+/// Words in an image name or file name that mark sprite sheets and UI textures
+const SPRITE_NAME_WORDS: [&str; 7] = [
+    "sprite",
+    "sprites",
+    "spritesheet",
+    "ui",
+    "hud",
+    "icons",
+    "font",
+];
+
+/// Alpha at or below which a pixel counts as gutter between sprites
+const GUTTER_ALPHA: u8 = 8;
+
+/// Cells a grid of sprites needs before it counts as a sprite sheet
+const MIN_GRID_CELLS: usize = 4;
+
+/// Source size of every texture kept from being resized, keyed by texture
+///
+/// Images carrying a `no_resize: true` extras entry are always kept. With `detect` set,
+/// images named like sprite sheets or UI textures, and images whose opaque pixels form a
+/// regular grid of cells separated by transparent gutters, are kept too.
+pub(crate) fn source_size_textures(
+    o_blob: &[u8],
+    o_json: &Root,
+    detect: bool,
+) -> HashMap<usize, u32> {
+    let mut kept_images: HashMap<usize, Option<u32>> = HashMap::new();
+    let mut sizes = HashMap::new();
+    for (i, texture) in o_json.textures.iter().enumerate() {
+        let texture_index = Index::<Texture>::new(i as u32);
+        let image = texture.source.value();
+        let size = *kept_images.entry(image).or_insert_with(|| {
+            let json_image = o_json.images.get(image)?;
+            let data = get_image_data(o_blob, o_json, texture_index)?;
+            let kept = marked_no_resize(json_image)
+                || detect
+                    && (sprite_name(json_image)
+                        || load_texture_image(o_json, texture_index, data, Vec::new())
+                            .is_ok_and(|img| is_sprite_grid(&img)));
+            let (width, height) = image_dimensions(data)?;
+            kept.then_some(width.max(height))
+        });
+        if let Some(size) = size {
+            sizes.insert(i, size);
+        }
+    }
+    sizes
+}
+
+fn marked_no_resize(image: &gltf::json::Image) -> bool {
+    from_extras(&image.extras)
+        .and_then(|extras| extras.get(NO_RESIZE_KEY)?.as_bool())
+        .unwrap_or(false)
+}
+
+/// Whether the image name or URI contains one of [`SPRITE_NAME_WORDS`]
+fn sprite_name(image: &gltf::json::Image) -> bool {
+    [image.name.as_deref(), image.uri.as_deref()]
+        .into_iter()
+        .flatten()
+        .flat_map(|name| name.split(|c: char| !c.is_ascii_alphanumeric()))
+        .any(|word| {
+            SPRITE_NAME_WORDS
+                .iter()
+                .any(|w| word.eq_ignore_ascii_case(w))
+        })
+}
+
+/// Whether the opaque pixels form a grid of cells at a regular pitch
+pub(crate) fn is_sprite_grid(img: &DynamicImage) -> bool {
+    if !img.color().has_alpha() {
+        return false;
+    }
+    let rgba = img.to_rgba8();
+    let mut filled_columns = vec![false; rgba.width() as usize];
+    let mut filled_rows = vec![false; rgba.height() as usize];
+    for (x, y, pixel) in rgba.enumerate_pixels() {
+        if pixel[3] > GUTTER_ALPHA {
+            filled_columns[x as usize] = true;
+            filled_rows[y as usize] = true;
+        }
+    }
+    match (regular_runs(&filled_columns), regular_runs(&filled_rows)) {
+        (Some(columns), Some(rows)) => columns * rows >= MIN_GRID_CELLS,
+        _ => false,
+    }
+}
+
+/// Number of filled runs along one axis, `None` unless they start at a regular pitch
+///
+/// Pitches may differ by a quarter, since sprites rarely fill their cells exactly.
+fn regular_runs(filled: &[bool]) -> Option<usize> {
+    let starts: Vec<usize> = (0..filled.len())
+        .filter(|&i| filled[i] && (i == 0 || !filled[i - 1]))
+        .collect();
+    let pitches: Vec<usize> = starts.windows(2).map(|w| w[1] - w[0]).collect();
+    let (min, max) = (pitches.iter().min(), pitches.iter().max());
+    match (min, max) {
+        _ if starts.is_empty() => None,
+        (Some(&min), Some(&max)) if max - min > max / 4 => None,
+        _ => Some(starts.len()),
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::{
+        decode::load_image,
+        glb::{read_glb, to_extras, write_glb},
+        opt::optimize_with,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn sprite_sheets_keep_source_size() {
+        // Four by two cells of 12 pixels, each a 9x9 sprite offset a little differently
+        let mut sheet = RgbaImage::new(48, 24);
+        for cell in 0..8u32 {
+            let (x0, y0) = ((cell % 4) * 12 + cell % 2, (cell / 4) * 12 + 1);
+            for (x, y) in (0..9).flat_map(|x| (0..9).map(move |y| (x, y))) {
+                sheet.put_pixel(x0 + x, y0 + y, Rgba([200, 40, 40, 255]));
+            }
+        }
+        assert!(is_sprite_grid(&DynamicImage::ImageRgba8(sheet.clone())));
+        // A single blob and an opaque image are not sprite sheets
+        let blob = RgbaImage::from_fn(48, 24, |x, _| Rgba([0, 0, 0, if x < 30 { 255 } else { 0 }]));
+        assert!(!is_sprite_grid(&DynamicImage::ImageRgba8(blob)));
+        let opaque = DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(sheet).to_rgb8());
+        assert!(!is_sprite_grid(&opaque));
+
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 3,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        json.images[0].extras = to_extras(serde_json::json!({ "no_resize": true }));
+        json.images[1].name = Some("hud_icons".to_string());
+        let glb = write_glb(&json, blob).unwrap();
+
+        let image_sizes = |options: &OptimizeOptions| {
+            let out = optimize_with(&mut Cursor::new(&glb), options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let mut sizes: Vec<u32> = (0..n_json.textures.len())
+                .map(|i| {
+                    let data = get_image_data(&n_blob, &n_json, Index::new(i as u32)).unwrap();
+                    load_image(data, Vec::new()).unwrap().width()
+                })
+                .collect();
+            sizes.sort_unstable();
+            sizes
+        };
+        let options = OptimizeOptions::default().texture_size(16);
+        assert_eq!(image_sizes(&options), [16, 16, 32]);
+        let options = OptimizeOptions {
+            detect_sprite_sheets: true,
+            ..options
+        };
+        assert_eq!(image_sizes(&options), [16, 32, 32]);
+    }
+}
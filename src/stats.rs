@@ -0,0 +1,769 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{Cursor, Read, Seek},
+};
+
+use gltf::json::{
+    Accessor, Index, Material, Root,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+
+use crate::{
+    alpha::{ALPHA_PREMULTIPLIED_KEY, ALPHA_PRUNED_KEY},
+    bounds::{for_each_mesh_instance, scene_roots},
+    decode::KTX2_MAGIC,
+    error::OptimizeError,
+    glb::{from_extras, read_glb},
+    math::{Mat4, cross, sub, transform_point},
+    mips::mip_chain,
+    opt::{get_index_data, get_position_data, get_texcoord_data},
+    transcode::TranscodeTargets,
+};
+
+/// Estimated GPU bytes of one texture in common runtime formats, full mip chain included
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TranscodedSizes {
+    /// Uncompressed RGBA8, what JPEG/PNG/WebP decode to
+    pub rgba8: u64,
+    /// ETC1 RGB (4 bpp), the usual ETC1S target on older Android
+    pub etc1_rgb: u64,
+    /// ETC2 RGBA / BC3 / BC7 / ASTC 4x4 (8 bpp)
+    pub etc2_rgba: u64,
+    /// BC1 RGB (4 bpp), desktop target for opaque ETC1S
+    pub bc1_rgb: u64,
+    /// BC7 RGBA (8 bpp), desktop target for alpha or UASTC
+    pub bc7_rgba: u64,
+    /// ASTC 4x4 (8 bpp), modern mobile target
+    pub astc_4x4: u64,
+}
+
+/// GPU memory estimate for one image
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TextureMemory {
+    /// Index of the image in the glTF `images` array
+    pub image: usize,
+    pub mime_type: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes stored in the file
+    pub encoded_bytes: u64,
+    pub transcoded: TranscodedSizes,
+    /// Transcode targets recorded in KTX2 metadata
+    pub transcode_targets: Option<TranscodeTargets>,
+    /// Alpha convention recorded when the optimizer converted the texture
+    pub alpha_premultiplied: Option<bool>,
+    /// The optimizer dropped a fully opaque alpha channel from this base color texture
+    pub alpha_pruned: bool,
+}
+
+/// Materials, primitives and surface area that sample one texture
+///
+/// Covers the core material slots (base color, metallic-roughness, normal, occlusion,
+/// emissive). The area tells which textures fill the screen and which could be smaller.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct TextureUsage {
+    /// Index of the texture in the glTF `textures` array
+    pub texture: usize,
+    /// Image the texture samples
+    pub image: usize,
+    pub materials: Vec<usize>,
+    /// `(mesh, primitive)` pairs drawn with one of those materials
+    pub primitives: Vec<(usize, usize)>,
+    pub meshes: Vec<usize>,
+    /// World-space triangle area drawn with the texture, summed over every mesh instance
+    /// of the default scene
+    pub surface_area: f64,
+}
+
+/// Texel density of one material: how many texels of its texture cover a meter of surface
+///
+/// Measured on the texture of the base color slot, or else of the first other core slot, at
+/// the size it is stored at. Both areas are summed over every mesh instance of the default
+/// scene, so the density is an average over everything drawn with the material.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct MaterialDensity {
+    pub material: usize,
+    /// Index of the texture the density is measured on
+    pub texture: usize,
+    pub width: u32,
+    pub height: u32,
+    /// Texture-space area drawn with the material, 1 being the whole texture once, after any
+    /// `KHR_texture_transform` scale
+    pub uv_area: f64,
+    /// World-space triangle area drawn with the material
+    pub surface_area: f64,
+    /// Texels along one meter of surface, taking glTF units as meters
+    pub texels_per_meter: f64,
+}
+
+/// Size of the GLB as stored and as it would be served compressed
+///
+/// Compressed sizes are only measured when the `gzip` / `brotli` features are enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WireSizes {
+    /// Bytes of the GLB file
+    pub raw: u64,
+    /// Bytes after gzip, as written by [`compress_glb`](crate::compress_glb)
+    pub gzip: Option<u64>,
+    /// Bytes after brotli, as written by [`compress_glb`](crate::compress_glb)
+    pub brotli: Option<u64>,
+}
+
+impl WireSizes {
+    /// Measure a GLB with every compressor compiled in
+    pub fn measure(glb: &[u8]) -> Result<WireSizes, OptimizeError> {
+        Ok(WireSizes {
+            raw: glb.len() as u64,
+            gzip: gzip_size(glb)?,
+            brotli: brotli_size(glb)?,
+        })
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_size(data: &[u8]) -> Result<Option<u64>, OptimizeError> {
+    Ok(Some(crate::compress::gzip(data)?.len() as u64))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip_size(_data: &[u8]) -> Result<Option<u64>, OptimizeError> {
+    Ok(None)
+}
+
+#[cfg(feature = "brotli")]
+fn brotli_size(data: &[u8]) -> Result<Option<u64>, OptimizeError> {
+    Ok(Some(crate::compress::brotli(data)?.len() as u64))
+}
+
+#[cfg(not(feature = "brotli"))]
+fn brotli_size(_data: &[u8]) -> Result<Option<u64>, OptimizeError> {
+    Ok(None)
+}
+
+/// Runtime cost of rendering the default scene
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct GpuStats {
+    /// One draw call per primitive per mesh instance
+    pub draw_calls: u64,
+    /// Triangles drawn, counting every mesh instance
+    pub triangles: u64,
+    /// Unique vertices uploaded (instances share buffers)
+    pub vertices: u64,
+    /// Bytes of unique vertex attribute accessors, morph targets included
+    pub vertex_buffer_bytes: u64,
+    /// Bytes of unique index accessors
+    pub index_buffer_bytes: u64,
+    pub textures: Vec<TextureMemory>,
+    /// Where each texture is used, indexed like the glTF `textures` array
+    pub texture_usage: Vec<TextureUsage>,
+    /// Texel density of every textured material the default scene draws, in material order
+    pub material_density: Vec<MaterialDensity>,
+    /// Download size of the file, raw and pre-compressed
+    pub wire: WireSizes,
+}
+
+impl GpuStats {
+    /// Texture bytes if every image is decoded to RGBA8 (JPEG/PNG/WebP path)
+    pub fn texture_bytes_rgba8(&self) -> u64 {
+        self.textures.iter().map(|t| t.transcoded.rgba8).sum()
+    }
+
+    /// Texture bytes when KTX2/Basis images are transcoded to ETC1/ETC2 and the rest stay RGBA8
+    pub fn texture_bytes_mobile(&self) -> u64 {
+        self.textures
+            .iter()
+            .map(|t| match t.mime_type.as_deref() {
+                Some("image/ktx2") => t.transcoded.etc2_rgba,
+                _ => t.transcoded.rgba8,
+            })
+            .sum()
+    }
+
+    /// Texture bytes when KTX2/Basis images are transcoded to BC7 and the rest stay RGBA8
+    pub fn texture_bytes_desktop(&self) -> u64 {
+        self.textures
+            .iter()
+            .map(|t| match t.mime_type.as_deref() {
+                Some("image/ktx2") => t.transcoded.bc7_rgba,
+                _ => t.transcoded.rgba8,
+            })
+            .sum()
+    }
+}
+
+/// Sum a per-level size over the full mip chain
+fn mip_chain_bytes<F: Fn(u64, u64) -> u64>(width: u32, height: u32, level_bytes: F) -> u64 {
+    mip_chain(width, height)
+        .into_iter()
+        .map(|(w, h)| level_bytes(w as u64, h as u64))
+        .sum()
+}
+
+pub(crate) fn transcoded_sizes(width: u32, height: u32) -> TranscodedSizes {
+    let blocks = |w: u64, h: u64| w.div_ceil(4) * h.div_ceil(4);
+    let bpp4 = mip_chain_bytes(width, height, |w, h| blocks(w, h) * 8);
+    let bpp8 = mip_chain_bytes(width, height, |w, h| blocks(w, h) * 16);
+
+    TranscodedSizes {
+        rgba8: mip_chain_bytes(width, height, |w, h| w * h * 4),
+        etc1_rgb: bpp4,
+        etc2_rgba: bpp8,
+        bc1_rgb: bpp4,
+        bc7_rgba: bpp8,
+        astc_4x4: bpp8,
+    }
+}
+
+/// Pixel dimensions of an encoded image, reading only the header where possible
+pub(crate) fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(&KTX2_MAGIC) {
+        // pixelWidth and pixelHeight follow the identifier, vkFormat and typeSize
+        let field = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+        return Some((field(20)?, field(24)?));
+    }
+    imagesize::blob_size(data)
+        .ok()
+        .map(|s| (s.width as u32, s.height as u32))
+}
+
+/// Levels stored in an encoded image, the base included; KTX2 files may hold a mip chain
+pub(crate) fn stored_level_count(data: &[u8]) -> usize {
+    if !data.starts_with(&KTX2_MAGIC) {
+        return 1;
+    }
+    // levelCount follows pixelDepth, layerCount and faceCount; 0 asks runtimes to build mips
+    data.get(40..44)
+        .and_then(|field| field.try_into().ok())
+        .map_or(1, |field| u32::from_le_bytes(field).max(1) as usize)
+}
+
+/// Targets the optimizer wrote into a KTX2 file's key/value data
+#[cfg(feature = "ktx2")]
+fn recorded_transcode_targets(data: &[u8]) -> Option<TranscodeTargets> {
+    let value = ktx2_rw::Ktx2Texture::from_memory(data)
+        .ok()?
+        .get_metadata(crate::transcode::TRANSCODE_TARGETS_KEY)
+        .ok()?;
+    Some(TranscodeTargets::parse(std::str::from_utf8(&value).ok()?))
+}
+
+#[cfg(not(feature = "ktx2"))]
+fn recorded_transcode_targets(_data: &[u8]) -> Option<TranscodeTargets> {
+    None
+}
+
+fn accessor_bytes(acc: &Accessor) -> u64 {
+    let component = match acc.component_type {
+        Checked::Valid(ct) => ct.0.size(),
+        Checked::Invalid => 0,
+    };
+    let multiplicity = match acc.type_ {
+        Checked::Valid(t) => t.multiplicity(),
+        Checked::Invalid => 0,
+    };
+    acc.count.0 * (component * multiplicity) as u64
+}
+
+/// Textures in the core slots of a material
+fn material_textures(material: &Material) -> impl Iterator<Item = usize> {
+    let pbr = &material.pbr_metallic_roughness;
+    [
+        pbr.base_color_texture.as_ref().map(|t| t.index.value()),
+        pbr.metallic_roughness_texture
+            .as_ref()
+            .map(|t| t.index.value()),
+        material.normal_texture.as_ref().map(|t| t.index.value()),
+        material.occlusion_texture.as_ref().map(|t| t.index.value()),
+        material.emissive_texture.as_ref().map(|t| t.index.value()),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Vertices of a primitive, the count of its first attribute
+pub(crate) fn vertex_count(o_json: &Root, primitive: &Primitive) -> u64 {
+    primitive
+        .attributes
+        .values()
+        .next()
+        .and_then(|idx| o_json.accessors.get(idx.value()))
+        .map(|acc| acc.count.0)
+        .unwrap_or(0)
+}
+
+/// Triangles a primitive draws; points and lines draw none
+pub(crate) fn primitive_triangles(o_json: &Root, primitive: &Primitive) -> u64 {
+    let element_count = primitive
+        .indices
+        .and_then(|idx| o_json.accessors.get(idx.value()))
+        .map(|acc| acc.count.0)
+        .unwrap_or_else(|| vertex_count(o_json, primitive));
+    match primitive.mode {
+        Checked::Valid(Mode::Triangles) => element_count / 3,
+        Checked::Valid(Mode::TriangleStrip | Mode::TriangleFan) => element_count.saturating_sub(2),
+        _ => 0,
+    }
+}
+
+/// Positions of a primitive and the corners of each triangle it draws
+type TriangleList = (Vec<[f32; 3]>, Vec<[u32; 3]>);
+
+/// Triangles a primitive draws with its positions, if it draws triangles and its data is in
+/// the GLB's own buffer
+fn primitive_triangle_list(
+    o_blob: &[u8],
+    o_json: &Root,
+    primitive: &Primitive,
+) -> Option<TriangleList> {
+    let in_blob = |acc: Index<Accessor>| {
+        o_json
+            .accessors
+            .get(acc.value())
+            .and_then(|a| a.buffer_view)
+            .and_then(|v| o_json.buffer_views.get(v.value()))
+            .is_some_and(|v| v.buffer.value() == 0)
+    };
+    let &position = primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::Positions))?;
+    if !in_blob(position) || !primitive.indices.is_none_or(in_blob) {
+        return None;
+    }
+    let positions = get_position_data(o_blob, o_json, position)?;
+    let indices = match primitive.indices {
+        Some(idx) => get_index_data(o_blob, o_json, idx).unwrap_or_default(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let triangles: Vec<[u32; 3]> = match primitive.mode {
+        Checked::Valid(Mode::Triangles) => indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+        Checked::Valid(Mode::TriangleStrip) => {
+            indices.windows(3).map(|t| [t[0], t[1], t[2]]).collect()
+        }
+        Checked::Valid(Mode::TriangleFan) if !indices.is_empty() => indices[1..]
+            .windows(2)
+            .map(|t| [indices[0], t[0], t[1]])
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some((positions, triangles))
+}
+
+/// Triangle area of a primitive after applying `world`
+///
+/// Zero for non-triangle modes and for positions it can't read, such as views stored
+/// compressed outside the GLB's own buffer.
+fn primitive_area(o_blob: &[u8], o_json: &Root, primitive: &Primitive, world: &Mat4) -> f64 {
+    let Some((positions, triangles)) = primitive_triangle_list(o_blob, o_json, primitive) else {
+        return 0.0;
+    };
+    let point = |i: u32| {
+        positions
+            .get(i as usize)
+            .map(|&p| transform_point(world, p))
+    };
+    triangles
+        .iter()
+        .filter_map(|&[a, b, c]| {
+            let (a, b, c) = (point(a)?, point(b)?, point(c)?);
+            let n = cross(sub(b, a), sub(c, a));
+            Some(((n[0] * n[0] + n[1] * n[1] + n[2] * n[2]) as f64).sqrt() / 2.0)
+        })
+        .sum()
+}
+
+/// Texture-space triangle area of a primitive in UV set `tex_coord`, zero when unreadable
+fn primitive_uv_area(o_blob: &[u8], o_json: &Root, primitive: &Primitive, tex_coord: u32) -> f64 {
+    let uvs = primitive
+        .attributes
+        .get(&Checked::Valid(Semantic::TexCoords(tex_coord)))
+        .and_then(|&uv| get_texcoord_data(o_blob, o_json, uv));
+    let (Some(uvs), Some((_, triangles))) =
+        (uvs, primitive_triangle_list(o_blob, o_json, primitive))
+    else {
+        return 0.0;
+    };
+    triangles
+        .iter()
+        .filter_map(|&[a, b, c]| {
+            let (a, b, c) = (
+                uvs.get(a as usize)?,
+                uvs.get(b as usize)?,
+                uvs.get(c as usize)?,
+            );
+            let n = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            Some((n as f64).abs() / 2.0)
+        })
+        .sum()
+}
+
+/// Core slots in the order a material's density texture is picked from
+const DENSITY_SLOTS: [&str; 5] = [
+    "/pbrMetallicRoughness/baseColorTexture",
+    "/pbrMetallicRoughness/metallicRoughnessTexture",
+    "/normalTexture",
+    "/occlusionTexture",
+    "/emissiveTexture",
+];
+
+/// Texture a material's density is measured on, with its UV set and the area factor of its
+/// `KHR_texture_transform` scale
+fn density_texture(material: &Material) -> Option<(usize, u32, f64)> {
+    let value = serde_json::to_value(material).ok()?;
+    let info = DENSITY_SLOTS.iter().find_map(|slot| value.pointer(slot))?;
+    let transform = info.pointer("/extensions/KHR_texture_transform");
+    let tex_coord = transform
+        .and_then(|t| t.get("texCoord"))
+        .or_else(|| info.get("texCoord"))
+        .and_then(|t| t.as_u64())
+        .unwrap_or(0);
+    let scale = transform
+        .and_then(|t| t.get("scale")?.as_array())
+        .map_or(1.0, |s| {
+            s.iter().filter_map(|v| v.as_f64()).product::<f64>().abs()
+        });
+    Some((
+        info.get("index")?.as_u64()? as usize,
+        tex_coord as u32,
+        scale,
+    ))
+}
+
+/// Texel density of every textured material the default scene draws
+fn compute_material_density(
+    o_blob: &[u8],
+    o_json: &Root,
+    textures: &[TextureMemory],
+) -> Vec<MaterialDensity> {
+    let references: Vec<Option<(usize, u32, f64)>> =
+        o_json.materials.iter().map(density_texture).collect();
+    let mut uv_areas: HashMap<(usize, usize), f64> = HashMap::new();
+    let mut areas: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, world| {
+        let Some(mesh) = o_json.meshes.get(mesh_idx) else {
+            return;
+        };
+        for (prim_idx, primitive) in mesh.primitives.iter().enumerate() {
+            let Some(material) = primitive.material.map(|m| m.value()) else {
+                continue;
+            };
+            let Some(&Some((_, tex_coord, scale))) = references.get(material) else {
+                continue;
+            };
+            let uv_area = *uv_areas
+                .entry((mesh_idx, prim_idx))
+                .or_insert_with(|| primitive_uv_area(o_blob, o_json, primitive, tex_coord));
+            let entry = areas.entry(material).or_default();
+            entry.0 += uv_area * scale;
+            entry.1 += primitive_area(o_blob, o_json, primitive, world);
+        }
+    });
+
+    areas
+        .into_iter()
+        .filter(|&(_, (uv_area, surface_area))| uv_area > 0.0 && surface_area > 0.0)
+        .filter_map(|(material, (uv_area, surface_area))| {
+            let (texture, _, _) = references[material]?;
+            let image = o_json.textures.get(texture)?.source.value();
+            let memory = textures.iter().find(|t| t.image == image)?;
+            let texels = memory.width as f64 * memory.height as f64 * uv_area;
+            Some(MaterialDensity {
+                material,
+                texture,
+                width: memory.width,
+                height: memory.height,
+                uv_area,
+                surface_area,
+                texels_per_meter: (texels / surface_area).sqrt(),
+            })
+        })
+        .collect()
+}
+
+/// World-space triangle area of every mesh instance in the default scene
+pub(crate) fn scene_surface_area(o_blob: &[u8], o_json: &Root) -> f64 {
+    let mut area = 0.0;
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, world| {
+        for primitive in o_json
+            .meshes
+            .get(mesh_idx)
+            .into_iter()
+            .flat_map(|m| &m.primitives)
+        {
+            area += primitive_area(o_blob, o_json, primitive, world);
+        }
+    });
+    area
+}
+
+/// Materials, primitives, meshes and drawn area for every texture
+fn compute_texture_usage(o_blob: &[u8], o_json: &Root) -> Vec<TextureUsage> {
+    let mut usage: Vec<TextureUsage> = o_json
+        .textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| TextureUsage {
+            texture: i,
+            image: texture.source.value(),
+            ..Default::default()
+        })
+        .collect();
+
+    let material_textures: Vec<Vec<usize>> = o_json
+        .materials
+        .iter()
+        .map(|m| {
+            let mut textures: Vec<usize> = material_textures(m).collect();
+            textures.sort_unstable();
+            textures.dedup();
+            textures
+        })
+        .collect();
+    for (material, textures) in material_textures.iter().enumerate() {
+        for &texture in textures {
+            if let Some(entry) = usage.get_mut(texture) {
+                entry.materials.push(material);
+            }
+        }
+    }
+
+    let textures_of = |primitive: &Primitive| {
+        primitive
+            .material
+            .and_then(|m| material_textures.get(m.value()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+    for (mesh_idx, mesh) in o_json.meshes.iter().enumerate() {
+        for (prim_idx, primitive) in mesh.primitives.iter().enumerate() {
+            for &texture in textures_of(primitive) {
+                if let Some(entry) = usage.get_mut(texture) {
+                    entry.primitives.push((mesh_idx, prim_idx));
+                    if entry.meshes.last() != Some(&mesh_idx) {
+                        entry.meshes.push(mesh_idx);
+                    }
+                }
+            }
+        }
+    }
+
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, world| {
+        let Some(mesh) = o_json.meshes.get(mesh_idx) else {
+            return;
+        };
+        for primitive in &mesh.primitives {
+            let textures = textures_of(primitive);
+            if textures.is_empty() {
+                continue;
+            }
+            let area = primitive_area(o_blob, o_json, primitive, world);
+            for &texture in textures {
+                if let Some(entry) = usage.get_mut(texture) {
+                    entry.surface_area += area;
+                }
+            }
+        }
+    });
+
+    usage
+}
+
+pub(crate) fn compute_gpu_stats(o_blob: &[u8], o_json: &Root) -> GpuStats {
+    let mut stats = GpuStats::default();
+
+    let mut vertex_accessors: HashSet<usize> = HashSet::new();
+    let mut index_accessors: HashSet<usize> = HashSet::new();
+    let mut counted_primitives: HashSet<(usize, usize)> = HashSet::new();
+
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh_idx, _| {
+        let Some(mesh) = o_json.meshes.get(mesh_idx) else {
+            return;
+        };
+
+        for (prim_idx, primitive) in mesh.primitives.iter().enumerate() {
+            stats.draw_calls += 1;
+
+            stats.triangles += primitive_triangles(o_json, primitive);
+
+            if counted_primitives.insert((mesh_idx, prim_idx)) {
+                stats.vertices += vertex_count(o_json, primitive);
+            }
+
+            let targets = primitive.targets.iter().flatten().flat_map(|t| {
+                [t.positions, t.normals, t.tangents]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<Index<Accessor>>>()
+            });
+            for idx in primitive.attributes.values().copied().chain(targets) {
+                vertex_accessors.insert(idx.value());
+            }
+            if let Some(idx) = primitive.indices {
+                index_accessors.insert(idx.value());
+            }
+        }
+    });
+
+    stats.vertex_buffer_bytes = vertex_accessors
+        .iter()
+        .filter_map(|&i| o_json.accessors.get(i))
+        .map(accessor_bytes)
+        .sum();
+    stats.index_buffer_bytes = index_accessors
+        .iter()
+        .filter_map(|&i| o_json.accessors.get(i))
+        .map(accessor_bytes)
+        .sum();
+
+    for (i, image) in o_json.images.iter().enumerate() {
+        let Some(view) = image
+            .buffer_view
+            .and_then(|v| o_json.buffer_views.get(v.value()))
+        else {
+            continue;
+        };
+        let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+        let length = view.byte_length.0 as usize;
+        let Some(data) = o_blob.get(offset..offset.saturating_add(length)) else {
+            continue;
+        };
+        let Some((width, height)) = image_dimensions(data) else {
+            continue;
+        };
+
+        let extras = from_extras(&image.extras);
+        let tag = |key: &str| extras.as_ref()?.get(key)?.as_bool();
+
+        stats.textures.push(TextureMemory {
+            image: i,
+            mime_type: image.mime_type.as_ref().map(|m| m.0.clone()),
+            width,
+            height,
+            encoded_bytes: length as u64,
+            transcoded: transcoded_sizes(width, height),
+            transcode_targets: recorded_transcode_targets(data),
+            alpha_premultiplied: tag(ALPHA_PREMULTIPLIED_KEY),
+            alpha_pruned: tag(ALPHA_PRUNED_KEY).unwrap_or(false),
+        });
+    }
+
+    stats.texture_usage = compute_texture_usage(o_blob, o_json);
+    stats.material_density = compute_material_density(o_blob, o_json, &stats.textures);
+
+    stats
+}
+
+/// Estimate GPU memory, draw cost and download size of a GLB's default scene
+pub fn gpu_stats<R: Read + Seek>(reader: &mut R) -> Result<GpuStats, OptimizeError> {
+    let mut glb = Vec::new();
+    reader.read_to_end(&mut glb)?;
+    let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb))?;
+    Ok(GpuStats {
+        wire: WireSizes::measure(&glb)?,
+        ..compute_gpu_stats(&o_blob, &o_json)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "png")]
+    use crate::glb::write_glb;
+    use crate::testing::SyntheticGlb;
+
+    #[test]
+    fn transcoded_sizes_cover_the_mip_chain() {
+        let sizes = transcoded_sizes(4, 4);
+        // 4x4, 2x2 and 1x1 levels, each at least one 4x4 block
+        assert_eq!(sizes.rgba8, (16 + 4 + 1) * 4);
+        assert_eq!(sizes.etc1_rgb, 3 * 8);
+        assert_eq!(sizes.bc7_rgba, 3 * 16);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn instances_add_draw_calls_but_not_buffers() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            primitives_per_mesh: 2,
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        // A second instance of mesh 0
+        let node = o_json.push(gltf::json::Node {
+            mesh: Some(Index::new(0)),
+            ..Default::default()
+        });
+        o_json.scenes[0].nodes.push(node);
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let stats = gpu_stats(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(stats.draw_calls, 6);
+        assert_eq!(stats.triangles, 12);
+        assert_eq!(stats.vertices, 16);
+        // Position, normal and UV of four vertices, and six u16 indices, per primitive
+        assert_eq!(stats.vertex_buffer_bytes, 4 * 4 * (12 + 12 + 8));
+        assert_eq!(stats.index_buffer_bytes, 4 * 12);
+
+        let texture = &stats.textures[0];
+        assert_eq!((texture.width, texture.height), (32, 32));
+        assert_eq!(texture.mime_type.as_deref(), Some("image/png"));
+        assert_eq!(stats.texture_bytes_rgba8(), transcoded_sizes(32, 32).rgba8);
+        assert_eq!(stats.texture_bytes_mobile(), stats.texture_bytes_rgba8());
+
+        assert_eq!(stats.wire.raw, glb.len() as u64);
+        assert_eq!(stats.wire.gzip.is_some(), cfg!(feature = "gzip"));
+    }
+
+    #[test]
+    fn wire_sizes_measure_every_compiled_compressor() {
+        let glb = SyntheticGlb {
+            meshes: 8,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let stats = gpu_stats(&mut Cursor::new(&glb)).unwrap();
+        assert_eq!(stats.wire.raw, glb.len() as u64);
+        assert_eq!(stats.wire.gzip.is_some(), cfg!(feature = "gzip"));
+        assert_eq!(stats.wire.brotli.is_some(), cfg!(feature = "brotli"));
+        // Eight copies of the same quad compress well
+        for size in [stats.wire.gzip, stats.wire.brotli].into_iter().flatten() {
+            assert!(size > 0 && size < stats.wire.raw);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "jpeg"))]
+    fn texture_usage_sums_area_over_meshes() {
+        use crate::{opt::optimize_with, options::OptimizeOptions};
+
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let stats = gpu_stats(&mut Cursor::new(out)).unwrap();
+        let usage = &stats.texture_usage;
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].materials, [0]);
+        assert_eq!(usage[0].primitives, [(0, 0), (1, 0)]);
+        assert_eq!(usage[0].meshes, [0, 1]);
+        assert!((usage[0].surface_area - 2.0).abs() < 1e-4);
+    }
+}
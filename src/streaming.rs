@@ -0,0 +1,276 @@
+use gltf::json::{Accessor, Index, Root};
+use serde_json::Value;
+
+use crate::{
+    blob::BlobWriter, error::OptimizeError, meshlet::MESHLET_EXTENSION, meshopt::chunk_range,
+    views::renumber_view_refs,
+};
+
+/// Root extension listing the groups of a streaming layout
+pub(crate) const STREAMING_EXTENSION: &str = "GLTFOPT_streaming";
+
+/// Part of the binary chunk a renderer can use on its own, in the order they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Group {
+    /// Mesh accessors, geometry codec payloads and meshlet data
+    Geometry,
+    /// Skin and animation accessors
+    Animation,
+    /// Anything not claimed by another group
+    Other,
+    /// Image data
+    Textures,
+}
+
+impl Group {
+    fn name(self) -> &'static str {
+        match self {
+            Group::Geometry => "geometry",
+            Group::Animation => "animation",
+            Group::Other => "other",
+            Group::Textures => "textures",
+        }
+    }
+}
+
+/// Collect every `"bufferView"` number in an extension payload
+fn payload_views(value: &Value, views: &mut Vec<usize>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                if key == "bufferView"
+                    && let Some(view) = value.as_u64()
+                {
+                    views.push(view as usize);
+                }
+                payload_views(value, views);
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|v| payload_views(v, views)),
+        _ => {}
+    }
+}
+
+/// Group of every buffer view; a view used by several groups goes to the earliest
+pub(crate) fn view_groups(n_json: &Root) -> Vec<Group> {
+    let mut groups: Vec<Option<Group>> = vec![None; n_json.buffer_views.len()];
+    let mut claim = |view: usize, group: Group| {
+        if let Some(slot) = groups.get_mut(view) {
+            *slot = Some(slot.map_or(group, |g| g.min(group)));
+        }
+    };
+    let accessor_views = |idx: Index<Accessor>| {
+        let accessor = n_json.accessors.get(idx.value());
+        let sparse = accessor.and_then(|acc| acc.sparse.as_ref());
+        accessor
+            .and_then(|acc| acc.buffer_view)
+            .into_iter()
+            .chain(sparse.map(|s| s.indices.buffer_view))
+            .chain(sparse.map(|s| s.values.buffer_view))
+            .map(|view| view.value())
+            .collect::<Vec<_>>()
+    };
+
+    for primitive in n_json.meshes.iter().flat_map(|m| &m.primitives) {
+        let mut accessors: Vec<Index<Accessor>> = primitive
+            .indices
+            .into_iter()
+            .chain(primitive.attributes.values().copied())
+            .collect();
+        for target in primitive.targets.iter().flatten() {
+            accessors.extend(
+                [target.positions, target.normals, target.tangents]
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        let mut views = Vec::new();
+        if let Some(extensions) = &primitive.extensions {
+            for (name, payload) in &extensions.others {
+                payload_views(payload, &mut views);
+                if name == MESHLET_EXTENSION {
+                    let meshlet_accessors = ["ranges", "bounds"]
+                        .into_iter()
+                        .filter_map(|key| payload.get(key)?.as_u64());
+                    accessors.extend(meshlet_accessors.map(|acc| Index::new(acc as u32)));
+                }
+            }
+        }
+        views.extend(accessors.into_iter().flat_map(accessor_views));
+        for view in views {
+            claim(view, Group::Geometry);
+        }
+    }
+
+    let skin_accessors = n_json.skins.iter().filter_map(|s| s.inverse_bind_matrices);
+    let sampler_accessors = n_json
+        .animations
+        .iter()
+        .flat_map(|a| &a.samplers)
+        .flat_map(|s| [s.input, s.output]);
+    for idx in skin_accessors.chain(sampler_accessors) {
+        for view in accessor_views(idx) {
+            claim(view, Group::Animation);
+        }
+    }
+
+    for image in &n_json.images {
+        if let Some(view) = image.buffer_view {
+            claim(view.value(), Group::Textures);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|g| g.unwrap_or(Group::Other))
+        .collect()
+}
+
+/// Write the manifest of a streaming layout from the current view order
+///
+/// Each group is a run of consecutive views; `byteEnd` is where its last byte sits in the
+/// binary chunk, so a renderer holding that much of the chunk can use the group.
+fn write_manifest(n_json: &mut Root) {
+    let groups = view_groups(n_json);
+    let mut entries: Vec<Value> = Vec::new();
+    let mut start = 0;
+    let mut byte_end = 0;
+    for (i, view) in n_json.buffer_views.iter().enumerate() {
+        byte_end = byte_end.max(chunk_range(view).end);
+        if groups.get(i + 1) != Some(&groups[i]) {
+            entries.push(serde_json::json!({
+                "name": groups[i].name(),
+                "firstView": start,
+                "viewCount": i + 1 - start,
+                "byteEnd": byte_end,
+            }));
+            start = i + 1;
+        }
+    }
+
+    n_json
+        .extensions
+        .get_or_insert_with(Default::default)
+        .others
+        .insert(
+            STREAMING_EXTENSION.to_string(),
+            serde_json::json!({ "groups": entries }),
+        );
+    if !n_json
+        .extensions_used
+        .iter()
+        .any(|e| e == STREAMING_EXTENSION)
+    {
+        n_json.extensions_used.push(STREAMING_EXTENSION.to_string());
+    }
+}
+
+/// Rewrite the manifest after a pass laid the binary chunk out again, if there is one
+pub(crate) fn refresh_manifest(n_json: &mut Root) {
+    let has_manifest = n_json
+        .extensions
+        .as_ref()
+        .is_some_and(|e| e.others.contains_key(STREAMING_EXTENSION));
+    if has_manifest {
+        write_manifest(n_json);
+    }
+}
+
+/// Order the binary chunk geometry first and textures last, recording the groups in a
+/// [`STREAMING_EXTENSION`] manifest
+///
+/// Buffer views are renumbered so each group is a run of consecutive views written in view
+/// order. Returns the new binary chunk.
+pub(crate) fn layout_for_streaming(
+    n_json: &mut Root,
+    n_blob: &[u8],
+) -> Result<Vec<u8>, OptimizeError> {
+    let groups = view_groups(n_json);
+    let mut order: Vec<usize> = (0..n_json.buffer_views.len()).collect();
+    order.sort_by_key(|&i| groups[i]);
+
+    let mut blob = BlobWriter::new();
+    let mut moved = vec![0; order.len()];
+    let mut views = Vec::with_capacity(order.len());
+    for &i in &order {
+        let mut view = n_json.buffer_views[i].clone();
+        let offset = view.byte_offset.map_or(0, |o| o.0 as usize);
+        let data = n_blob
+            .get(offset..offset.saturating_add(view.byte_length.0 as usize))
+            .unwrap_or_default();
+        let n_offset = blob.append(data);
+        view.byte_offset = (n_offset > 0).then(|| n_offset.into());
+        moved[i] = views.len();
+        views.push(view);
+    }
+    n_json.buffer_views = views;
+
+    let mut doc = serde_json::to_value(&*n_json)?;
+    renumber_view_refs(&mut doc, &moved);
+    *n_json = serde_json::from_value(doc)?;
+
+    write_manifest(n_json);
+    Ok(blob.into_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::view_bytes;
+
+    #[test]
+    fn streaming_layout_puts_geometry_first() {
+        // An image, then animation times, then positions: the reverse of the streaming order
+        let mut blob = b"image bytes!".to_vec();
+        blob.extend([0.0f32, 1.0].iter().flat_map(|v| v.to_le_bytes()));
+        blob.extend([0.0f32; 9].iter().flat_map(|v| v.to_le_bytes()));
+        let json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": blob.len() }],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 12, "byteLength": 8 },
+                { "buffer": 0, "byteOffset": 20, "byteLength": 36 },
+            ],
+            "accessors": [
+                { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5126, "count": 2, "type": "SCALAR" },
+            ],
+            "images": [{ "bufferView": 0, "mimeType": "image/png" }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "animations": [{
+                "channels": [{ "sampler": 0, "target": { "node": 0, "path": "scale" } }],
+                "samplers": [{ "input": 1, "output": 0 }],
+            }],
+            "nodes": [{ "mesh": 0 }],
+        }))
+        .unwrap();
+
+        let mut n_json = json.clone();
+        let n_blob = layout_for_streaming(&mut n_json, &blob).unwrap();
+        let position_view = n_json.accessors[0].buffer_view.unwrap().value();
+        let time_view = n_json.accessors[1].buffer_view.unwrap().value();
+        let image_view = n_json.images[0].buffer_view.unwrap().value();
+        assert_eq!((position_view, time_view, image_view), (0, 1, 2));
+        assert_eq!(view_bytes(&n_json, &n_blob, position_view), &blob[20..]);
+        assert_eq!(view_bytes(&n_json, &n_blob, time_view), &blob[12..20]);
+        assert_eq!(view_bytes(&n_json, &n_blob, image_view), &blob[..12]);
+
+        let manifest = &n_json.extensions.as_ref().unwrap().others["GLTFOPT_streaming"];
+        let groups: Vec<(&str, u64)> = manifest["groups"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|g| (g["name"].as_str().unwrap(), g["byteEnd"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(
+            groups,
+            [("geometry", 36), ("animation", 44), ("textures", 56)]
+        );
+        assert!(
+            n_json
+                .extensions_used
+                .iter()
+                .any(|e| e == "GLTFOPT_streaming")
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Index, Node, Root, Scene};
+
+use crate::{
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    math::{IDENTITY, mul, node_matrix},
+    opt::optimize_document,
+    options::OptimizeOptions,
+};
+
+/// Node picked by [`extract_subtree`], by index or by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NodeSelector<'a> {
+    Index(usize),
+    /// The first node with this name
+    Name(&'a str),
+}
+
+impl From<usize> for NodeSelector<'_> {
+    fn from(index: usize) -> Self {
+        NodeSelector::Index(index)
+    }
+}
+
+impl<'a> From<&'a str> for NodeSelector<'a> {
+    fn from(name: &'a str) -> Self {
+        NodeSelector::Name(name)
+    }
+}
+
+impl NodeSelector<'_> {
+    fn resolve(self, o_json: &Root) -> Result<usize, OptimizeError> {
+        match self {
+            NodeSelector::Index(index) if index < o_json.nodes.len() => Ok(index),
+            NodeSelector::Index(index) => Err(OptimizeError::MissingObject {
+                kind: "node",
+                index,
+            }),
+            NodeSelector::Name(name) => o_json
+                .nodes
+                .iter()
+                .position(|n| n.name.as_deref() == Some(name))
+                .ok_or_else(|| OptimizeError::MissingName {
+                    kind: "node",
+                    names: vec![name.to_string()],
+                }),
+        }
+    }
+}
+
+/// World transform of the parent of `node`, following the first parent of each ancestor
+fn parent_world(o_json: &Root, node: usize) -> [f32; 16] {
+    let parent_of = |child: usize| {
+        o_json
+            .nodes
+            .iter()
+            .position(|n| n.children.iter().flatten().any(|c| c.value() == child))
+    };
+    let mut world = IDENTITY;
+    let mut current = node;
+    // Bounded against cyclic hierarchies in malformed files
+    for _ in 0..o_json.nodes.len() {
+        let Some(parent) = parent_of(current) else {
+            break;
+        };
+        world = mul(&node_matrix(&o_json.nodes[parent]), &world);
+        current = parent;
+    }
+    world
+}
+
+/// Source document whose only scene holds `node`, placed where its ancestors put it
+///
+/// A node with transformed ancestors is wrapped in a new root carrying their combined
+/// transform, so the node keeps its own transform and animations.
+fn select_subtree(o_json: &Root, node: usize) -> Root {
+    let mut n_json = o_json.clone();
+    let world = parent_world(o_json, node);
+    let root = if world == IDENTITY {
+        Index::new(node as u32)
+    } else {
+        n_json.push(Node {
+            children: Some(vec![Index::new(node as u32)]),
+            matrix: Some(world),
+            ..Default::default()
+        })
+    };
+    let name = o_json
+        .scenes
+        .get(o_json.scene.map(|s| s.value()).unwrap_or(0))
+        .and_then(|s| s.name.clone());
+    n_json.scenes = vec![Scene {
+        extensions: None,
+        extras: Default::default(),
+        name,
+        nodes: vec![root],
+    }];
+    n_json.scene = Some(Index::new(0));
+    n_json
+}
+
+/// Optimize only the subtree under one node into a GLB of its own
+///
+/// The node becomes the only root of the only scene, at the world position its ancestors gave
+/// it. Everything the subtree does not reach is left out: other nodes, meshes, materials,
+/// textures, skins, cameras and animation channels, as with
+/// [`prune_unused`](crate::OptimizeOptions::prune_unused), which is forced on here
+/// along with `stable_indices` off. Skin joints outside the subtree are kept for the skins
+/// using them.
+pub fn extract_subtree<'a, R: Read + Seek>(
+    reader: &mut R,
+    node: impl Into<NodeSelector<'a>>,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let node = node.into().resolve(&o_json)?;
+    let o_json = select_subtree(&o_json, node);
+    let options = OptimizeOptions {
+        prune_unused: true,
+        stable_indices: false,
+        ..options.clone()
+    };
+
+    let (mut n_json, n_blob, _) = optimize_document(&o_blob, &o_json, &options, None, None)?;
+    retain_extensions(&mut n_json, &[])?;
+    write_glb(&n_json, n_blob)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{opt::calculate_bounding_box, testing::SyntheticGlb};
+
+    #[test]
+    fn subtrees_are_extracted_with_their_resources() {
+        let glb = SyntheticGlb {
+            meshes: 3,
+            textures: 3,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        // node_1 hangs under a raised node_0
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["nodes"][0]["translation"] = serde_json::json!([0.0, 5.0, 0.0]);
+        doc["nodes"][0]["children"] = serde_json::json!([1]);
+        doc["scenes"][0]["nodes"] = serde_json::json!([0, 2]);
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let options = OptimizeOptions::default();
+        let out = extract_subtree(&mut Cursor::new(&glb), "node_1", &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(n_json.meshes.len(), 1);
+        assert_eq!(n_json.meshes[0].name.as_deref(), Some("mesh_1"));
+        assert_eq!(n_json.materials.len(), 1);
+        assert_eq!(n_json.textures.len(), 1);
+        assert_eq!(n_json.images.len(), 1);
+        // Kept where its parent placed it, under a new root
+        assert_eq!(n_json.nodes.len(), 2);
+        let (min, _) = calculate_bounding_box(&n_blob, &n_json).unwrap();
+        assert_eq!(min, [2.0, 5.0, 0.0]);
+
+        let out = extract_subtree(&mut Cursor::new(&glb), 2, &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert_eq!(n_json.nodes.len(), 1);
+        assert_eq!(n_json.meshes[0].name.as_deref(), Some("mesh_2"));
+
+        assert!(extract_subtree(&mut Cursor::new(&glb), "missing", &options).is_err());
+        assert!(extract_subtree(&mut Cursor::new(&glb), 9, &options).is_err());
+    }
+}
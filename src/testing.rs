@@ -0,0 +1,664 @@
+use std::{fmt, io::Cursor};
+
+use gltf::json::{Accessor, Index, Root, validation::Checked};
+use serde_json::{Value, json};
+
+use crate::{
+    blob::BlobWriter,
+    convert::read_accessor,
+    decode::load_image,
+    error::OptimizeError,
+    glb::{read_glb, write_glb},
+    opt::{encode_png, get_index_data},
+};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Small GLB assembled in memory, for tests that should not depend on binary fixtures
+///
+/// Every primitive is a unit quad in the XY plane with positions, normals, UVs and u16
+/// indices; mesh `m` sits at `x = 2m` and has a node of its own in the default scene.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct SyntheticGlb {
+    pub meshes: usize,
+    pub primitives_per_mesh: usize,
+    /// Store position, normal and UV of a primitive in one strided buffer view
+    pub interleaved: bool,
+    /// Give every primitive a morph target whose POSITION deltas are a sparse accessor
+    pub sparse_morph_targets: bool,
+    /// Number of distinct PNG base color textures, each with a material of its own
+    ///
+    /// Primitives take the materials in turn, so with fewer textures than primitives the
+    /// textures are shared. `0` leaves the primitives without material.
+    pub textures: usize,
+    /// Width and height of each texture
+    pub texture_size: u32,
+    /// Skin every mesh to a two-joint skeleton with identity inverse bind matrices
+    pub skin: bool,
+    /// Add an animation that moves every mesh node linearly and turns it in steps over one
+    /// second, all samplers sharing one accessor of key times
+    pub animated: bool,
+}
+
+impl Default for SyntheticGlb {
+    fn default() -> Self {
+        SyntheticGlb {
+            meshes: 1,
+            primitives_per_mesh: 1,
+            interleaved: false,
+            sparse_morph_targets: false,
+            textures: 0,
+            texture_size: 32,
+            skin: false,
+            animated: false,
+        }
+    }
+}
+
+/// Buffer views and accessors written so far
+struct Builder {
+    blob: BlobWriter,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Builder {
+    fn view(&mut self, data: &[u8], stride: Option<usize>, target: Option<u32>) -> usize {
+        let offset = self.blob.append(data);
+        let mut view = json!({ "buffer": 0, "byteOffset": offset, "byteLength": data.len() });
+        if let Some(stride) = stride {
+            view["byteStride"] = stride.into();
+        }
+        if let Some(target) = target {
+            view["target"] = target.into();
+        }
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+fn float_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Pixels of texture `i`: a gradient tinted differently for every texture
+fn texture_pixels(i: usize, size: u32) -> Vec<u8> {
+    let tint = (i as u32 * 67 % 256) as u8;
+    let scale = |v: u32| (v * 255 / size.saturating_sub(1).max(1)) as u8;
+    (0..size)
+        .flat_map(|y| (0..size).flat_map(move |x| [scale(x), scale(y), tint, 255]))
+        .collect()
+}
+
+impl SyntheticGlb {
+    /// Write the GLB; fails only when textures are requested without the `png` feature
+    pub fn build(&self) -> Result<Vec<u8>, OptimizeError> {
+        let mut b = Builder {
+            blob: BlobWriter::new(),
+            views: Vec::new(),
+            accessors: Vec::new(),
+        };
+
+        let mut images = Vec::new();
+        let mut textures = Vec::new();
+        let mut materials = Vec::new();
+        for i in 0..self.textures {
+            let mut png = Vec::new();
+            let size = self.texture_size;
+            encode_png(
+                &texture_pixels(i, size),
+                size,
+                size,
+                image::ColorType::Rgba8,
+                &mut png,
+            )?;
+            let view = b.view(&png, None, None);
+            images.push(json!({ "bufferView": view, "mimeType": "image/png" }));
+            textures.push(json!({ "source": i }));
+            materials.push(json!({
+                "name": format!("material_{i}"),
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": i } },
+            }));
+        }
+
+        let corners = [[0.0f32, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let mut meshes = Vec::new();
+        let mut nodes = Vec::new();
+        let mut skin_joints = None;
+        if self.skin {
+            let matrices: Vec<f32> = (0..2)
+                .flat_map(|_| (0..16).map(|i| if i % 5 == 0 { 1.0 } else { 0.0 }))
+                .collect();
+            let view = b.view(&float_bytes(&matrices), None, None);
+            let ibm = b.accessor(json!({
+                "bufferView": view, "componentType": 5126, "count": 2, "type": "MAT4",
+            }));
+            nodes.push(json!({ "name": "joint_root", "children": [1] }));
+            nodes.push(json!({ "name": "joint_tip", "translation": [0.0, 1.0, 0.0] }));
+            skin_joints = Some(ibm);
+        }
+
+        for m in 0..self.meshes {
+            let x = 2.0 * m as f32;
+            let mut primitives = Vec::new();
+            for p in 0..self.primitives_per_mesh {
+                let z = p as f32;
+                let positions: Vec<[f32; 3]> =
+                    corners.iter().map(|[u, v]| [x + u, *v, z]).collect();
+                let vec = |view: usize, offset: usize, components: &str, minmax: bool| {
+                    let mut acc = json!({
+                        "bufferView": view, "byteOffset": offset, "componentType": 5126,
+                        "count": 4, "type": components,
+                    });
+                    if minmax {
+                        acc["min"] = json!([x, 0.0, z]);
+                        acc["max"] = json!([x + 1.0, 1.0, z]);
+                    }
+                    acc
+                };
+
+                let (position, normal, uv) = if self.interleaved {
+                    let vertices: Vec<f32> = positions
+                        .iter()
+                        .zip(&corners)
+                        .flat_map(|(pos, uv)| [pos[0], pos[1], pos[2], 0.0, 0.0, 1.0, uv[0], uv[1]])
+                        .collect();
+                    let view = b.view(&float_bytes(&vertices), Some(32), Some(ARRAY_BUFFER));
+                    (
+                        b.accessor(vec(view, 0, "VEC3", true)),
+                        b.accessor(vec(view, 12, "VEC3", false)),
+                        b.accessor(vec(view, 24, "VEC2", false)),
+                    )
+                } else {
+                    let views = [
+                        float_bytes(positions.as_flattened()),
+                        float_bytes(&[0.0, 0.0, 1.0].repeat(4)),
+                        float_bytes(corners.as_flattened()),
+                    ]
+                    .map(|data| b.view(&data, None, Some(ARRAY_BUFFER)));
+                    (
+                        b.accessor(vec(views[0], 0, "VEC3", true)),
+                        b.accessor(vec(views[1], 0, "VEC3", false)),
+                        b.accessor(vec(views[2], 0, "VEC2", false)),
+                    )
+                };
+
+                let index_bytes: Vec<u8> = [0u16, 1, 2, 0, 2, 3]
+                    .iter()
+                    .flat_map(|i| i.to_le_bytes())
+                    .collect();
+                let view = b.view(&index_bytes, None, Some(ELEMENT_ARRAY_BUFFER));
+                let indices = b.accessor(json!({
+                    "bufferView": view, "componentType": 5123, "count": 6, "type": "SCALAR",
+                }));
+
+                let mut primitive = json!({
+                    "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv },
+                    "indices": indices,
+                });
+                if self.textures > 0 {
+                    primitive["material"] =
+                        ((m * self.primitives_per_mesh + p) % self.textures).into();
+                }
+                if self.skin {
+                    let joints = b.view(&[0u8; 16], None, Some(ARRAY_BUFFER));
+                    let weights = b.view(
+                        &float_bytes(&[1.0, 0.0, 0.0, 0.0].repeat(4)),
+                        None,
+                        Some(ARRAY_BUFFER),
+                    );
+                    primitive["attributes"]["JOINTS_0"] = b
+                        .accessor(json!({
+                            "bufferView": joints, "componentType": 5121, "count": 4,
+                            "type": "VEC4",
+                        }))
+                        .into();
+                    primitive["attributes"]["WEIGHTS_0"] = b
+                        .accessor(json!({
+                            "bufferView": weights, "componentType": 5126, "count": 4,
+                            "type": "VEC4",
+                        }))
+                        .into();
+                }
+                if self.sparse_morph_targets {
+                    // Only the top right corner moves
+                    let sparse_indices = b.view(&2u16.to_le_bytes(), None, None);
+                    let sparse_values = b.view(&float_bytes(&[0.0, 0.5, 0.0]), None, None);
+                    let target = b.accessor(json!({
+                        "componentType": 5126, "count": 4, "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0], "max": [0.0, 0.5, 0.0],
+                        "sparse": {
+                            "count": 1,
+                            "indices": { "bufferView": sparse_indices, "componentType": 5123 },
+                            "values": { "bufferView": sparse_values },
+                        },
+                    }));
+                    primitive["targets"] = json!([{ "POSITION": target }]);
+                }
+                primitives.push(primitive);
+            }
+
+            let mut mesh = json!({ "name": format!("mesh_{m}"), "primitives": primitives });
+            if self.sparse_morph_targets {
+                mesh["weights"] = json!([0.0]);
+            }
+            meshes.push(mesh);
+            let mut node = json!({ "name": format!("node_{m}"), "mesh": m });
+            if skin_joints.is_some() {
+                node["skin"] = 0.into();
+            }
+            nodes.push(node);
+        }
+
+        let skin_nodes = if self.skin { 2 } else { 0 };
+        let mut animation = None;
+        if self.animated {
+            let times = [0.0f32, 0.5, 1.0];
+            let view = b.view(&float_bytes(&times), None, None);
+            let input = b.accessor(json!({
+                "bufferView": view, "componentType": 5126, "count": 3, "type": "SCALAR",
+                "min": [0.0], "max": [1.0],
+            }));
+            let (mut samplers, mut channels) = (Vec::new(), Vec::new());
+            for m in 0..self.meshes {
+                let half = std::f32::consts::FRAC_1_SQRT_2;
+                let keys = [
+                    (
+                        "translation",
+                        "LINEAR",
+                        "VEC3",
+                        vec![0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 1.0, 0.0],
+                    ),
+                    (
+                        "rotation",
+                        "STEP",
+                        "VEC4",
+                        vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, half, half, 0.0, 0.0, 1.0, 0.0],
+                    ),
+                ];
+                for (path, interpolation, ty, values) in keys {
+                    let view = b.view(&float_bytes(&values), None, None);
+                    let output = b.accessor(json!({
+                        "bufferView": view, "componentType": 5126, "count": 3, "type": ty,
+                    }));
+                    channels.push(json!({
+                        "sampler": samplers.len(),
+                        "target": { "node": skin_nodes + m, "path": path },
+                    }));
+                    samplers.push(json!({
+                        "input": input, "output": output, "interpolation": interpolation,
+                    }));
+                }
+            }
+            animation = Some(json!({ "name": "move", "samplers": samplers, "channels": channels }));
+        }
+
+        let mut roots: Vec<usize> = (skin_nodes..nodes.len()).collect();
+        if self.skin {
+            roots.insert(0, 0);
+        }
+        let mut doc = json!({
+            "asset": { "version": "2.0", "generator": "gltf_opt::testing" },
+            "buffers": [{ "byteLength": b.blob.len() }],
+            "bufferViews": b.views,
+            "accessors": b.accessors,
+            "meshes": meshes,
+            "nodes": nodes,
+            "scenes": [{ "nodes": roots }],
+            "scene": 0,
+        });
+        if let Some(animation) = animation {
+            doc["animations"] = json!([animation]);
+        }
+        if let Some(ibm) = skin_joints {
+            doc["skins"] = json!([{ "inverseBindMatrices": ibm, "joints": [0, 1] }]);
+        }
+        if !images.is_empty() {
+            doc["images"] = images.into();
+            doc["textures"] = textures.into();
+            doc["materials"] = materials.into();
+        }
+
+        let json: Root = serde_json::from_value(doc)?;
+        write_glb(&json, b.blob)
+    }
+}
+
+/// How far an output may drift from a snapshot before [`compare_glbs`] reports it
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct GlbTolerances {
+    /// Largest difference allowed in any component of any accessor element
+    pub geometry: f32,
+    /// Largest mean absolute difference allowed per RGBA channel value (0-255) of an image
+    pub image: f32,
+}
+
+impl Default for GlbTolerances {
+    fn default() -> Self {
+        GlbTolerances {
+            geometry: 1e-5,
+            image: 2.0,
+        }
+    }
+}
+
+/// Way in which an output differs from a snapshot beyond the tolerances
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum GlbDifference {
+    /// Different number of objects of one kind, e.g. `"materials"`
+    Count {
+        kind: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// Different `extensionsUsed`
+    Extensions {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// Attributes, indices or attribute values of a primitive differ
+    Geometry {
+        mesh: usize,
+        primitive: usize,
+        detail: String,
+    },
+    /// Format, size or pixels of an image differ
+    Image { image: usize, detail: String },
+}
+
+impl fmt::Display for GlbDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlbDifference::Count {
+                kind,
+                expected,
+                actual,
+            } => write!(f, "{kind}: expected {expected}, got {actual}"),
+            GlbDifference::Extensions { expected, actual } => {
+                write!(f, "extensionsUsed: expected {expected:?}, got {actual:?}")
+            }
+            GlbDifference::Geometry {
+                mesh,
+                primitive,
+                detail,
+            } => write!(f, "mesh {mesh} primitive {primitive}: {detail}"),
+            GlbDifference::Image { image, detail } => write!(f, "image {image}: {detail}"),
+        }
+    }
+}
+
+/// Every element of an accessor as floats, whatever its type
+fn accessor_values(blob: &[u8], json: &Root, idx: Index<Accessor>) -> Option<Vec<f32>> {
+    fn flat<const N: usize>(blob: &[u8], json: &Root, idx: Index<Accessor>) -> Option<Vec<f32>> {
+        read_accessor::<N>(blob, json, idx).map(|v| v.concat())
+    }
+    let multiplicity = match json.accessors.get(idx.value())?.type_ {
+        Checked::Valid(ty) => ty.multiplicity(),
+        Checked::Invalid => return None,
+    };
+    match multiplicity {
+        1 => flat::<1>(blob, json, idx),
+        2 => flat::<2>(blob, json, idx),
+        3 => flat::<3>(blob, json, idx),
+        4 => flat::<4>(blob, json, idx),
+        9 => flat::<9>(blob, json, idx),
+        16 => flat::<16>(blob, json, idx),
+        _ => None,
+    }
+}
+
+/// Compare two GLBs by structure, geometry and decoded pixels instead of bytes
+///
+/// Object counts and `extensionsUsed` must match exactly, as must each primitive's attribute
+/// names and index data. Attribute values may differ by `tolerances.geometry` and images, once
+/// decoded, by `tolerances.image`, so snapshots survive encoder updates that change bytes but
+/// not results. Returns every difference found; an empty list means the GLBs match.
+pub fn compare_glbs(
+    expected: &[u8],
+    actual: &[u8],
+    tolerances: &GlbTolerances,
+) -> Result<Vec<GlbDifference>, OptimizeError> {
+    let (e_json, e_blob) = read_glb(&mut Cursor::new(expected))?;
+    let (a_json, a_blob) = read_glb(&mut Cursor::new(actual))?;
+    let mut differences = Vec::new();
+
+    let counts = |json: &Root| {
+        [
+            ("meshes", json.meshes.len()),
+            ("nodes", json.nodes.len()),
+            ("materials", json.materials.len()),
+            ("textures", json.textures.len()),
+            ("images", json.images.len()),
+            ("skins", json.skins.len()),
+            ("animations", json.animations.len()),
+        ]
+    };
+    for ((kind, expected), (_, actual)) in counts(&e_json).into_iter().zip(counts(&a_json)) {
+        if expected != actual {
+            differences.push(GlbDifference::Count {
+                kind,
+                expected,
+                actual,
+            });
+        }
+    }
+    let mut e_extensions = e_json.extensions_used.clone();
+    let mut a_extensions = a_json.extensions_used.clone();
+    e_extensions.sort();
+    a_extensions.sort();
+    if e_extensions != a_extensions {
+        differences.push(GlbDifference::Extensions {
+            expected: e_extensions,
+            actual: a_extensions,
+        });
+    }
+
+    for (m, (e_mesh, a_mesh)) in e_json.meshes.iter().zip(&a_json.meshes).enumerate() {
+        if e_mesh.primitives.len() != a_mesh.primitives.len() {
+            differences.push(GlbDifference::Geometry {
+                mesh: m,
+                primitive: 0,
+                detail: format!(
+                    "expected {} primitives, got {}",
+                    e_mesh.primitives.len(),
+                    a_mesh.primitives.len()
+                ),
+            });
+            continue;
+        }
+        for (p, (e_p, a_p)) in e_mesh.primitives.iter().zip(&a_mesh.primitives).enumerate() {
+            let mut report = |detail: String| {
+                differences.push(GlbDifference::Geometry {
+                    mesh: m,
+                    primitive: p,
+                    detail,
+                })
+            };
+            let e_indices = e_p
+                .indices
+                .and_then(|i| get_index_data(&e_blob, &e_json, i));
+            let a_indices = a_p
+                .indices
+                .and_then(|i| get_index_data(&a_blob, &a_json, i));
+            if e_indices != a_indices {
+                report("index data differs".to_string());
+            }
+            if !e_p.attributes.keys().eq(a_p.attributes.keys()) {
+                report("attribute names differ".to_string());
+                continue;
+            }
+            for (semantic, &e_idx) in &e_p.attributes {
+                let a_idx = a_p.attributes[semantic];
+                let values = (
+                    accessor_values(&e_blob, &e_json, e_idx),
+                    accessor_values(&a_blob, &a_json, a_idx),
+                );
+                let error = match values {
+                    (Some(e), Some(a)) if e.len() == a.len() => e
+                        .iter()
+                        .zip(&a)
+                        .map(|(e, a)| (e - a).abs())
+                        .fold(0.0, f32::max),
+                    (None, None) => 0.0,
+                    _ => f32::INFINITY,
+                };
+                if error > tolerances.geometry {
+                    let name = match semantic {
+                        Checked::Valid(semantic) => semantic.to_string(),
+                        Checked::Invalid => "invalid attribute".to_string(),
+                    };
+                    report(format!("{name} differs by up to {error}"));
+                }
+            }
+        }
+    }
+
+    for (i, (e_img, a_img)) in e_json.images.iter().zip(&a_json.images).enumerate() {
+        let bytes = |json: &Root, blob: &'_ [u8], img: &gltf::json::Image| {
+            let view = json.buffer_views.get(img.buffer_view?.value())?;
+            let offset = view.byte_offset.map_or(0, |o| o.0 as usize);
+            blob.get(offset..offset + view.byte_length.0 as usize)
+                .map(<[u8]>::to_vec)
+        };
+        let mut report =
+            |detail: String| differences.push(GlbDifference::Image { image: i, detail });
+        let mime_types = [e_img, a_img].map(|img| img.mime_type.as_ref().map(|m| m.0.clone()));
+        if mime_types[0] != mime_types[1] {
+            report(format!(
+                "expected {:?}, got {:?}",
+                mime_types[0], mime_types[1]
+            ));
+            continue;
+        }
+        let (Some(e_bytes), Some(a_bytes)) = (
+            bytes(&e_json, &e_blob, e_img),
+            bytes(&a_json, &a_blob, a_img),
+        ) else {
+            if e_img.uri != a_img.uri {
+                report(format!("expected URI {:?}, got {:?}", e_img.uri, a_img.uri));
+            }
+            continue;
+        };
+        if e_bytes == a_bytes {
+            continue;
+        }
+        // Formats this build cannot decode are only equal when their bytes are
+        let (Ok(e_pixels), Ok(a_pixels)) = (
+            load_image(&e_bytes, Vec::new()),
+            load_image(&a_bytes, Vec::new()),
+        ) else {
+            report("bytes differ and cannot be decoded".to_string());
+            continue;
+        };
+        let (e_pixels, a_pixels) = (e_pixels.to_rgba8(), a_pixels.to_rgba8());
+        if e_pixels.dimensions() != a_pixels.dimensions() {
+            report(format!(
+                "expected {:?}, got {:?}",
+                e_pixels.dimensions(),
+                a_pixels.dimensions()
+            ));
+            continue;
+        }
+        let total: u64 = e_pixels
+            .as_raw()
+            .iter()
+            .zip(a_pixels.as_raw())
+            .map(|(&e, &a)| e.abs_diff(a) as u64)
+            .sum();
+        let mean = total as f32 / e_pixels.as_raw().len().max(1) as f32;
+        if mean > tolerances.image {
+            report(format!("pixels differ by {mean:.2} on average"));
+        }
+    }
+
+    Ok(differences)
+}
+
+/// Panic with every difference when `actual` does not match the `expected` snapshot
+///
+/// See [`compare_glbs`] for what is compared.
+pub fn assert_glbs_match(expected: &[u8], actual: &[u8], tolerances: &GlbTolerances) {
+    let differences = compare_glbs(expected, actual, tolerances).expect("GLBs cannot be read");
+    if !differences.is_empty() {
+        let lines: Vec<String> = differences.iter().map(ToString::to_string).collect();
+        panic!("GLBs differ:\n{}", lines.join("\n"));
+    }
+}
+
+/// Bytes of buffer view `view` in a binary chunk
+#[cfg(test)]
+pub(crate) fn view_bytes<'a>(json: &Root, blob: &'a [u8], view: usize) -> &'a [u8] {
+    let view = &json.buffer_views[view];
+    let start = view.byte_offset.map_or(0, |o| o.0 as usize);
+    &blob[start..start + view.byte_length.0 as usize]
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use super::*;
+    use crate::{opt::optimize_with, options::OptimizeOptions};
+
+    #[test]
+    fn snapshots_match_within_tolerances() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let run =
+            |options: OptimizeOptions| optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        let snapshot = run(OptimizeOptions::default());
+        let tolerances = GlbTolerances::default();
+        assert_eq!(compare_glbs(&snapshot, &snapshot, &tolerances).unwrap(), []);
+
+        // A lower JPEG quality changes the bytes but barely the pixels
+        let lower_quality = run(OptimizeOptions {
+            auto_quality: Some(-5),
+            ..Default::default()
+        });
+        assert_ne!(lower_quality, snapshot);
+        assert_eq!(
+            compare_glbs(&snapshot, &lower_quality, &tolerances).unwrap(),
+            []
+        );
+        let exact = GlbTolerances {
+            image: 0.0,
+            ..tolerances
+        };
+        assert!(matches!(
+            compare_glbs(&snapshot, &lower_quality, &exact).unwrap()[..],
+            [GlbDifference::Image { image: 0, .. }]
+        ));
+
+        let moved = run(OptimizeOptions {
+            center_pivot: true,
+            ..Default::default()
+        });
+        assert!(matches!(
+            compare_glbs(&snapshot, &moved, &tolerances).unwrap()[..],
+            [GlbDifference::Geometry {
+                mesh: 0,
+                primitive: 0,
+                ..
+            }]
+        ));
+        let smaller = run(OptimizeOptions {
+            texture_size: 16,
+            ..Default::default()
+        });
+        assert!(matches!(
+            compare_glbs(&snapshot, &smaller, &tolerances).unwrap()[..],
+            [GlbDifference::Image { image: 0, .. }]
+        ));
+    }
+}
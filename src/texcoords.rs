@@ -0,0 +1,49 @@
+use std::collections::BTreeSet;
+
+use gltf::json::Material;
+use serde_json::{Map, Value};
+
+/// `KHR_texture_transform`, whose `texCoord` overrides the one of the texture info it is on
+const TEXTURE_TRANSFORM: &str = "KHR_texture_transform";
+
+/// UVs this far outside `[0, 1]` still count as non-tiling
+pub(crate) const UV_EPSILON: f32 = 1e-4;
+
+/// UV sets a material samples: the `texCoord` of every core and extension texture info, or
+/// the `KHR_texture_transform` override when one is set
+pub(crate) fn material_tex_coords(material: &Material) -> BTreeSet<u32> {
+    let mut sets = BTreeSet::new();
+    if let Ok(value) = serde_json::to_value(material) {
+        collect_tex_coords(&value, &mut sets);
+    }
+    sets
+}
+
+fn collect_tex_coords(value: &Value, sets: &mut BTreeSet<u32>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                if key.ends_with("Texture") && value.get("index").is_some() {
+                    let tex_coord = value
+                        .pointer(&format!("/extensions/{TEXTURE_TRANSFORM}/texCoord"))
+                        .or_else(|| value.get("texCoord"))
+                        .and_then(Value::as_u64)
+                        .unwrap_or(0);
+                    sets.insert(tex_coord as u32);
+                } else {
+                    collect_tex_coords(value, sets);
+                }
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|v| collect_tex_coords(v, sets)),
+        _ => {}
+    }
+}
+
+/// Drop a `KHR_texture_transform` `texCoord` override from a texture info's extensions, so
+/// the info's own `texCoord` applies
+pub(crate) fn drop_tex_coord_override(extensions: Option<&mut Map<String, Value>>) {
+    if let Some(Value::Object(transform)) = extensions.and_then(|e| e.get_mut(TEXTURE_TRANSFORM)) {
+        transform.remove("texCoord");
+    }
+}
@@ -0,0 +1,323 @@
+use std::io::{Read, Seek};
+
+use gltf::json::{Index, Root};
+
+use crate::{
+    blob::BlobWriter,
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    meshopt::chunk_range,
+    opt::optimize_document,
+    options::OptimizeOptions,
+    streaming::{Group, view_groups},
+    texture_table::refresh_texture_table,
+    views::renumber_view_refs,
+};
+
+/// Extension name prefixes of texture and material extensions, which the rebuild decides on
+const TEXTURE_PREFIXES: [&str; 3] = ["KHR_texture_", "EXT_texture_", "KHR_materials_"];
+
+/// Name of the first setting that rewrites geometry, UVs or the node tree
+fn geometry_setting(options: &OptimizeOptions) -> Option<&'static str> {
+    let defaults = OptimizeOptions::default();
+    // No `..`, so a new option doesn't build until it is sorted into one of the two groups
+    let OptimizeOptions {
+        // Texture, material and output settings the texture rebuild honours
+        texture_size: _,
+        slot_texture_sizes: _,
+        power_of_two_textures: _,
+        remove_normal_texture: _,
+        convert_to_ktx2: _,
+        ktx2_mipmaps: _,
+        ktx2_mip_rounding: _,
+        share_variant_mips: _,
+        compression_profiles: _,
+        texture_fallbacks: _,
+        gpu_texture_budget: _,
+        target_profile: _,
+        stable_indices: _,
+        prune_unused: _,
+        transcode_targets: _,
+        ktx2_metadata: _,
+        asset_license: _,
+        viewer_hints: _,
+        image_format: _,
+        environment_texture_size: _,
+        auto_quality: _,
+        upscale_textures: _,
+        skip_reencode: _,
+        detect_sprite_sheets: _,
+        fix_misslotted_textures: _,
+        infer_texture_classes: _,
+        dither: _,
+        sharpen: _,
+        linear_light_resize: _,
+        alpha_conversion: _,
+        watermark: _,
+        anonymize_textures: _,
+        process_base_color: _,
+        process_normals: _,
+        process_metallic_roughness: _,
+        process_occlusion: _,
+        process_emissive: _,
+        content_hash_uris: _,
+        reject_if: _,
+        best_effort: _,
+        never_grow: _,
+        encoder_threads: _,
+        low_priority: _,
+        texture_table: _,
+        // Settings that rewrite geometry, UVs or the node tree
+        center_pivot,
+        rescale,
+        triangle_texture_scaling,
+        preview_subset,
+        crop_textures,
+        atlas_textures,
+        gutter_padding,
+        normalize_uvs,
+        half_precision_attributes,
+        quantize_attributes,
+        meshopt_compression,
+        sparse_morph_targets,
+        max_morph_targets,
+        bake_morph_targets,
+        quantize_animations,
+        repair_animation_timing,
+        repair_skin_weights,
+        resample_cubic_animations,
+        bake_texture_transforms,
+        simplify,
+        lods,
+        merge_primitives,
+        dedupe_meshes,
+        collapse_materials,
+        split_primitives,
+        meshlets,
+        reorder_vertices,
+        accessor_views,
+        streaming_layout,
+    } = options;
+    let set = [
+        ("center_pivot", *center_pivot),
+        ("rescale", rescale.is_some()),
+        (
+            "triangle_texture_scaling",
+            triangle_texture_scaling.is_some(),
+        ),
+        ("preview_subset", preview_subset.is_some()),
+        ("crop_textures", *crop_textures),
+        ("atlas_textures", atlas_textures.is_some()),
+        ("gutter_padding", gutter_padding.is_some()),
+        ("normalize_uvs", *normalize_uvs),
+        ("half_precision_attributes", *half_precision_attributes),
+        ("quantize_attributes", *quantize_attributes),
+        ("meshopt_compression", *meshopt_compression),
+        ("sparse_morph_targets", sparse_morph_targets.is_some()),
+        ("max_morph_targets", max_morph_targets.is_some()),
+        ("bake_morph_targets", *bake_morph_targets),
+        ("quantize_animations", quantize_animations.is_some()),
+        ("repair_animation_timing", *repair_animation_timing),
+        ("repair_skin_weights", *repair_skin_weights),
+        (
+            "resample_cubic_animations",
+            resample_cubic_animations.is_some(),
+        ),
+        ("bake_texture_transforms", *bake_texture_transforms),
+        ("simplify", simplify.is_some()),
+        ("lods", lods.is_some()),
+        ("merge_primitives", *merge_primitives),
+        ("dedupe_meshes", *dedupe_meshes),
+        ("collapse_materials", *collapse_materials),
+        ("split_primitives", split_primitives.is_some()),
+        ("meshlets", meshlets.is_some()),
+        (
+            "reorder_vertices",
+            *reorder_vertices != defaults.reorder_vertices,
+        ),
+        ("accessor_views", *accessor_views != defaults.accessor_views),
+        ("streaming_layout", *streaming_layout),
+    ];
+    set.into_iter().find(|(_, set)| *set).map(|(name, _)| name)
+}
+
+/// The document with its materials and mesh structure but no vertex, index or animation data,
+/// which is all the texture work of a run needs
+fn without_geometry(o_json: &Root) -> Root {
+    let mut t_json = Root {
+        accessors: Vec::new(),
+        animations: Vec::new(),
+        skins: Vec::new(),
+        ..o_json.clone()
+    };
+    for primitive in t_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+        primitive.attributes.clear();
+        primitive.indices = None;
+        primitive.targets = None;
+    }
+    for node in &mut t_json.nodes {
+        node.skin = None;
+    }
+    t_json
+}
+
+/// Optimize only the materials and textures of a GLB, keeping its geometry byte for byte
+///
+/// Accessors, vertex and index data, skins and animations are not read or rewritten: the
+/// BIN chunk is kept as it is up to the end of the last view an image doesn't own, and the
+/// rebuilt images are appended after it. Buffer views only images read are dropped and the
+/// other views renumbered, so image bytes sitting between geometry views stay behind unused.
+/// Much faster than [`optimize_with`](crate::optimize_with) on geometry-heavy files,
+/// and geometry is guaranteed untouched.
+///
+/// Settings that rewrite geometry, UVs or the node tree (`center_pivot`, `simplify`,
+/// `crop_textures`, `atlas_textures`, `meshopt_compression`, ...) are refused with
+/// [`OptimizeError::InvalidOption`]. `prune_unused` is turned off and `stable_indices` on, so
+/// materials keep the indices primitives refer to.
+pub fn optimize_textures_only<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    if let Some(name) = geometry_setting(options) {
+        return Err(OptimizeError::InvalidOption(format!(
+            "{name} rewrites geometry, which optimize_textures_only keeps as it is"
+        )));
+    }
+    let options = OptimizeOptions {
+        prune_unused: false,
+        stable_indices: true,
+        ..options.clone()
+    };
+    let (o_json, o_blob) = read_glb(reader)?;
+    let (n_json, n_blob, remap) =
+        optimize_document(&o_blob, &without_geometry(&o_json), &options, None, None)?;
+
+    // Drop the views only images read, keeping every other view's bytes where they are
+    let groups = view_groups(&o_json);
+    let mut moved = vec![usize::MAX; o_json.buffer_views.len()];
+    let mut views = Vec::new();
+    let mut kept_end = 0;
+    for (i, view) in o_json.buffer_views.iter().enumerate() {
+        if groups[i] == Group::Textures {
+            continue;
+        }
+        if view.buffer.value() == 0 {
+            kept_end = kept_end.max(chunk_range(view).end);
+        }
+        moved[i] = views.len();
+        views.push(view.clone());
+    }
+    let mut out = Root {
+        buffer_views: views,
+        images: Vec::new(),
+        ..o_json.clone()
+    };
+    let mut doc = serde_json::to_value(&out)?;
+    renumber_view_refs(&mut doc, &moved);
+    out = serde_json::from_value(doc)?;
+
+    let mut blob = BlobWriter::from(o_blob.get(..kept_end).unwrap_or(&o_blob[..]).to_vec());
+    for image in &n_json.images {
+        let mut image = image.clone();
+        if let Some(view) = image.buffer_view {
+            let data = n_json
+                .buffer_views
+                .get(view.value())
+                .and_then(|v| n_blob.get(chunk_range(v)))
+                .unwrap_or_default();
+            image.buffer_view = Some(blob.push_view(&mut out, data, None));
+        }
+        out.images.push(image);
+    }
+    out.materials = n_json.materials;
+    out.textures = n_json.textures;
+    out.samplers = n_json.samplers;
+    out.extensions = n_json.extensions;
+    // Texture and material extensions follow the rebuild, geometry ones the source
+    let keep = |names: &[String], rebuilt: Vec<String>| {
+        let mut names: Vec<String> = names
+            .iter()
+            .filter(|e| !TEXTURE_PREFIXES.iter().any(|p| e.starts_with(p)))
+            .cloned()
+            .collect();
+        for name in rebuilt {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    };
+    out.extensions_used = keep(&o_json.extensions_used, n_json.extensions_used);
+    out.extensions_required = keep(&o_json.extensions_required, n_json.extensions_required);
+    for primitive in out.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+        primitive.material = primitive
+            .material
+            .and_then(|m| remap.material(m.value()))
+            .map(|m| Index::new(m as u32));
+    }
+
+    retain_extensions(&mut out, &[])?;
+    refresh_texture_table(&mut out, &blob);
+    write_glb(&out, blob.into_vec())
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        opt::get_image_data,
+        testing::{SyntheticGlb, view_bytes},
+    };
+
+    #[test]
+    fn textures_only_keeps_geometry_bytes() {
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 2,
+            texture_size: 64,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let options = OptimizeOptions {
+            texture_size: 16,
+            ..Default::default()
+        };
+        let out = optimize_textures_only(&mut Cursor::new(&glb), &options).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+
+        assert_eq!(json.accessors.len(), o_json.accessors.len());
+        for (accessor, o_accessor) in json.accessors.iter().zip(&o_json.accessors) {
+            let (view, o_view) = (
+                accessor.buffer_view.unwrap(),
+                o_accessor.buffer_view.unwrap(),
+            );
+            assert_eq!(
+                view_bytes(&json, &blob, view.value()),
+                view_bytes(&o_json, &o_blob, o_view.value())
+            );
+        }
+        assert_eq!(
+            serde_json::to_value(&json.meshes).unwrap(),
+            serde_json::to_value(&o_json.meshes).unwrap()
+        );
+        for texture in 0..json.textures.len() {
+            let data = get_image_data(&blob, &json, Index::new(texture as u32)).unwrap();
+            let image = image::load_from_memory(data).unwrap();
+            assert!(image.width() <= 16 && image.height() <= 16);
+        }
+
+        let moving = OptimizeOptions {
+            center_pivot: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            optimize_textures_only(&mut Cursor::new(&glb), &moving),
+            Err(OptimizeError::InvalidOption(_))
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+
+use gltf::json::Root;
+use serde_json::{Value, json};
+
+use crate::{
+    atlas::slots, crop::extension_textures, material_extensions::extension_texture_type,
+    meshopt::chunk_range, opt::TextureType, report::stored_image,
+};
+
+/// Root extension holding a flat table of every texture, for bindless descriptor arrays
+pub(crate) const TEXTURE_TABLE_EXTENSION: &str = "GLTFOPT_texture_table";
+
+/// Textures sampled as color by a core slot or a material extension, which decode as sRGB
+fn srgb_textures(json: &Root) -> HashSet<usize> {
+    let mut srgb = HashSet::new();
+    for mat in &json.materials {
+        let [base_color, _, _, _, emissive] = slots(mat);
+        srgb.extend(
+            [base_color, emissive]
+                .into_iter()
+                .flatten()
+                .map(|s| s.0.value()),
+        );
+        for (name, payload) in mat.extensions.iter().flat_map(|e| &e.others) {
+            let Some(payload) = payload.as_object() else {
+                continue;
+            };
+            for (key, value) in payload {
+                if extension_texture_type(name, key) == Some(TextureType::Emissive) {
+                    let mut found = Vec::new();
+                    extension_textures(value, &mut found);
+                    srgb.extend(found);
+                }
+            }
+        }
+    }
+    srgb
+}
+
+/// Table entry of texture `texture`, reusing the stored image fields of `previous` when the
+/// image can't be read from `blob`
+fn entry(json: &Root, blob: &[u8], texture: usize, srgb: bool, previous: Option<&Value>) -> Value {
+    let t = &json.textures[texture];
+    let image = json.images.get(t.source.value());
+    let mut entry = json!({
+        "id": texture,
+        "image": t.source.value(),
+        "sampler": t.sampler.map(|s| s.value()),
+        "colorSpace": if srgb { "srgb" } else { "linear" },
+        "mimeType": image.and_then(|img| img.mime_type.as_ref()).map(|m| &m.0),
+    });
+
+    let view = image
+        .and_then(|img| img.buffer_view)
+        .and_then(|v| Some((v.value(), json.buffer_views.get(v.value())?)));
+    if let Some((index, view)) = view {
+        let range = chunk_range(view);
+        entry["buffer"] = view.buffer.value().into();
+        entry["bufferView"] = index.into();
+        entry["byteOffset"] = range.start.into();
+        entry["byteLength"] = range.len().into();
+    } else if let Some(uri) = image.and_then(|img| img.uri.as_ref()) {
+        entry["uri"] = uri.as_str().into();
+    }
+
+    // Only the first buffer is the binary chunk at hand
+    let in_blob = view.is_some_and(|(_, v)| v.buffer.value() == 0);
+    if let Some(stored) = stored_image(json, blob, texture).filter(|_| in_blob) {
+        entry["width"] = stored.width.into();
+        entry["height"] = stored.height.into();
+        entry["levels"] = stored.levels.len().into();
+    } else if let Some(previous) = previous {
+        for key in ["width", "height", "levels"] {
+            if let Some(value) = previous.get(key) {
+                entry[key] = value.clone();
+            }
+        }
+    }
+    entry
+}
+
+/// Write the [`TEXTURE_TABLE_EXTENSION`] table, one entry per texture in texture order
+///
+/// An entry's `id` is its texture index, so a renderer can put texture `id` in descriptor
+/// slot `id` and index it from materials directly. Byte ranges are relative to the start of
+/// the buffer's data; for buffer 0 that is the GLB binary chunk.
+pub(crate) fn write_texture_table(n_json: &mut Root, n_blob: &[u8]) {
+    let previous: Vec<Value> = n_json
+        .extensions
+        .as_ref()
+        .and_then(|e| e.others.get(TEXTURE_TABLE_EXTENSION))
+        .and_then(|t| t.get("textures"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let srgb = srgb_textures(n_json);
+    let entries: Vec<Value> = (0..n_json.textures.len())
+        .map(|i| entry(n_json, n_blob, i, srgb.contains(&i), previous.get(i)))
+        .collect();
+
+    n_json
+        .extensions
+        .get_or_insert_with(Default::default)
+        .others
+        .insert(
+            TEXTURE_TABLE_EXTENSION.to_string(),
+            json!({ "textures": entries }),
+        );
+    if !n_json
+        .extensions_used
+        .iter()
+        .any(|e| e == TEXTURE_TABLE_EXTENSION)
+    {
+        n_json
+            .extensions_used
+            .push(TEXTURE_TABLE_EXTENSION.to_string());
+    }
+}
+
+/// Rewrite the table after a pass moved or replaced image data, if there is one
+pub(crate) fn refresh_texture_table(n_json: &mut Root, n_blob: &[u8]) {
+    let has_table = n_json
+        .extensions
+        .as_ref()
+        .is_some_and(|e| e.others.contains_key(TEXTURE_TABLE_EXTENSION));
+    if has_table {
+        write_texture_table(n_json, n_blob);
+    }
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::{Index, material::NormalTexture};
+
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::optimize_with,
+        options::OptimizeOptions,
+        stats::image_dimensions,
+        testing::{SyntheticGlb, view_bytes},
+    };
+
+    #[test]
+    fn texture_table_lists_every_texture() {
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        // The second texture becomes the first material's normal map
+        json.materials[0].normal_texture = Some(NormalTexture {
+            index: Index::new(1),
+            scale: 1.0,
+            tex_coord: 0,
+            extensions: None,
+            extras: Default::default(),
+        });
+        let glb = write_glb(&json, blob).unwrap();
+
+        let options = OptimizeOptions {
+            texture_table: true,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(
+            n_json
+                .extensions_used
+                .iter()
+                .any(|e| e == "GLTFOPT_texture_table")
+        );
+        let table = &n_json.extensions.as_ref().unwrap().others["GLTFOPT_texture_table"];
+        let entries = table["textures"].as_array().unwrap();
+        assert_eq!(entries.len(), n_json.textures.len());
+        for (i, entry) in entries.iter().enumerate() {
+            assert_eq!(entry["id"], i);
+            let image = &n_json.images[n_json.textures[i].source.value()];
+            let view = image.buffer_view.unwrap().value();
+            assert_eq!(entry["bufferView"], view);
+            let start = entry["byteOffset"].as_u64().unwrap() as usize;
+            let end = start + entry["byteLength"].as_u64().unwrap() as usize;
+            assert_eq!(&n_blob[start..end], view_bytes(&n_json, &n_blob, view));
+            let (width, height) = image_dimensions(&n_blob[start..end]).unwrap();
+            assert_eq!(
+                (entry["width"].as_u64(), entry["height"].as_u64()),
+                (Some(width as u64), Some(height as u64))
+            );
+            assert_eq!(entry["mimeType"], image.mime_type.as_ref().unwrap().0);
+        }
+
+        let material = &n_json.materials[0];
+        let base_color = material.pbr_metallic_roughness.base_color_texture.as_ref();
+        let normal = material.normal_texture.as_ref();
+        assert_eq!(
+            entries[base_color.unwrap().index.value()]["colorSpace"],
+            "srgb"
+        );
+        assert_eq!(
+            entries[normal.unwrap().index.value()]["colorSpace"],
+            "linear"
+        );
+    }
+}
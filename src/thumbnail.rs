@@ -0,0 +1,372 @@
+use std::error::Error;
+
+use crate::error::{OptimizeError, Stage};
+
+/// Integration point for producing a preview image of a GLB
+///
+/// Implementations receive the finished GLB bytes and return an encoded PNG.
+pub trait ThumbnailRenderer {
+    fn render(
+        &self,
+        glb: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>;
+}
+
+/// Render a PNG preview with any [`ThumbnailRenderer`]
+pub fn render_thumbnail<T: ThumbnailRenderer + ?Sized>(
+    renderer: &T,
+    glb: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, OptimizeError> {
+    if width == 0 || height == 0 {
+        return Err(OptimizeError::InvalidOption(
+            "thumbnail dimensions must be non-zero".to_string(),
+        ));
+    }
+    renderer
+        .render(glb, width, height)
+        .map_err(|source| OptimizeError::Callback {
+            stage: Stage::ThumbnailRenderer,
+            source,
+        })
+}
+
+#[cfg(feature = "software-thumbnail")]
+pub use software::SoftwareRenderer;
+
+#[cfg(feature = "software-thumbnail")]
+mod software {
+    use std::{collections::HashMap, error::Error, io::Cursor};
+
+    use gltf::json::{Root, mesh::Semantic, validation::Checked};
+    use image::{ImageEncoder, RgbaImage, codecs::png::PngEncoder};
+
+    use super::ThumbnailRenderer;
+    use crate::{
+        bounds::{compute_bounding_volumes, for_each_mesh_instance, scene_roots},
+        camera::CameraFraming,
+        decode::load_image,
+        glb::read_glb,
+        math::{cross, dot, sub, transform_point},
+        opt::{get_image_data, get_index_data, get_position_data, get_texcoord_data},
+    };
+
+    /// Flat-shaded CPU rasterizer sampling base color factor and texture
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SoftwareRenderer {
+        /// Camera placement; the aspect ratio is taken from the output size
+        pub framing: CameraFraming,
+        /// RGBA clear color, transparent black by default
+        pub background: [u8; 4],
+    }
+
+    struct Vertex {
+        screen: [f32; 3],
+        inv_w: f32,
+        uv: [f32; 2],
+    }
+
+    fn sample(texture: &RgbaImage, uv: [f32; 2]) -> [f32; 4] {
+        let x = (uv[0].rem_euclid(1.0) * texture.width() as f32) as u32;
+        let y = (uv[1].rem_euclid(1.0) * texture.height() as f32) as u32;
+        let p = texture.get_pixel(x.min(texture.width() - 1), y.min(texture.height() - 1));
+        [0, 1, 2, 3].map(|i| p.0[i] as f32 / 255.0)
+    }
+
+    fn base_color_texture(
+        o_blob: &[u8],
+        o_json: &Root,
+        material: usize,
+        cache: &mut HashMap<usize, Option<RgbaImage>>,
+    ) -> Option<usize> {
+        let info = o_json
+            .materials
+            .get(material)?
+            .pbr_metallic_roughness
+            .base_color_texture
+            .as_ref()?;
+        let texture = info.index.value();
+        cache.entry(texture).or_insert_with(|| {
+            get_image_data(o_blob, o_json, info.index)
+                .and_then(|data| load_image(data, Vec::new()).ok())
+                .map(|img| img.to_rgba8())
+        });
+        Some(texture)
+    }
+
+    impl ThumbnailRenderer for SoftwareRenderer {
+        fn render(
+            &self,
+            glb: &[u8],
+            width: u32,
+            height: u32,
+        ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            let (o_json, o_blob) = read_glb(&mut Cursor::new(glb))?;
+
+            let aspect = width as f32 / height as f32;
+            let framing = CameraFraming {
+                aspect_ratio: Some(aspect),
+                ..self.framing
+            };
+
+            let scene_idx = o_json.scene.map(|s| s.value()).unwrap_or(0);
+            let volume = compute_bounding_volumes(&o_blob, &o_json)
+                .scenes
+                .get(scene_idx)
+                .copied()
+                .flatten();
+
+            let mut color: Vec<[u8; 4]> = vec![self.background; (width * height) as usize];
+            let mut depth: Vec<f32> = vec![f32::MAX; (width * height) as usize];
+
+            if let Some(volume) = volume {
+                let view = framing.fit(&volume.sphere);
+
+                let (sy, cy) = framing.yaw.sin_cos();
+                let (sp, cp) = framing.pitch.sin_cos();
+                let forward = [-sy * cp, -sp, -cy * cp];
+                let right = [cy, 0.0, -sy];
+                let up = cross(right, forward);
+                let tan_half = (framing.yfov / 2.0).tan();
+
+                let project = |p: [f32; 3], uv: [f32; 2]| -> Option<Vertex> {
+                    let d = sub(p, view.eye);
+                    let z = dot(d, forward);
+                    if z <= view.distance * 1e-3 {
+                        return None;
+                    }
+                    let x = dot(d, right) / (z * tan_half * aspect);
+                    let y = dot(d, up) / (z * tan_half);
+                    Some(Vertex {
+                        screen: [
+                            (x * 0.5 + 0.5) * width as f32,
+                            (0.5 - y * 0.5) * height as f32,
+                            z,
+                        ],
+                        inv_w: 1.0 / z,
+                        uv,
+                    })
+                };
+
+                let mut textures: HashMap<usize, Option<RgbaImage>> = HashMap::new();
+
+                for_each_mesh_instance(&o_json, &scene_roots(&o_json), |_, mesh_idx, world| {
+                    let Some(mesh) = o_json.meshes.get(mesh_idx) else {
+                        return;
+                    };
+
+                    for primitive in &mesh.primitives {
+                        if !matches!(
+                            primitive.mode,
+                            Checked::Valid(gltf::json::mesh::Mode::Triangles)
+                        ) {
+                            continue;
+                        }
+                        let Some(positions) = primitive
+                            .attributes
+                            .get(&Checked::Valid(Semantic::Positions))
+                            .and_then(|idx| get_position_data(&o_blob, &o_json, *idx))
+                        else {
+                            continue;
+                        };
+                        let uvs = primitive
+                            .attributes
+                            .get(&Checked::Valid(Semantic::TexCoords(0)))
+                            .and_then(|idx| get_texcoord_data(&o_blob, &o_json, *idx))
+                            .filter(|uvs| uvs.len() == positions.len());
+                        let indices = match primitive.indices {
+                            Some(idx) => match get_index_data(&o_blob, &o_json, idx) {
+                                Some(i) => i,
+                                None => continue,
+                            },
+                            None => (0..positions.len() as u32).collect(),
+                        };
+
+                        let factor = primitive
+                            .material
+                            .and_then(|m| o_json.materials.get(m.value()))
+                            .map(|m| m.pbr_metallic_roughness.base_color_factor.0)
+                            .unwrap_or([1.0; 4]);
+                        let texture = primitive
+                            .material
+                            .zip(uvs.as_ref())
+                            .and_then(|(m, _)| {
+                                base_color_texture(&o_blob, &o_json, m.value(), &mut textures)
+                            })
+                            .and_then(|t| textures.get(&t))
+                            .and_then(|t| t.as_ref());
+
+                        let world_positions: Vec<[f32; 3]> = positions
+                            .iter()
+                            .map(|p| transform_point(world, *p))
+                            .collect();
+
+                        for tri in indices.chunks_exact(3) {
+                            let Some(corners) = tri
+                                .iter()
+                                .map(|&i| world_positions.get(i as usize).copied())
+                                .collect::<Option<Vec<_>>>()
+                            else {
+                                continue;
+                            };
+
+                            let normal =
+                                cross(sub(corners[1], corners[0]), sub(corners[2], corners[0]));
+                            let len = dot(normal, normal).sqrt();
+                            if len == 0.0 {
+                                continue;
+                            }
+                            let shade = 0.35 + 0.65 * (dot(normal, forward) / len).abs();
+
+                            let Some(verts) = tri
+                                .iter()
+                                .zip(&corners)
+                                .map(|(&i, p)| {
+                                    let uv =
+                                        uvs.as_ref().map(|uvs| uvs[i as usize]).unwrap_or([0.0; 2]);
+                                    project(*p, uv)
+                                })
+                                .collect::<Option<Vec<_>>>()
+                            else {
+                                continue;
+                            };
+
+                            rasterize(&verts, width, height, &mut depth, &mut color, |uv| {
+                                let texel = texture.map(|t| sample(t, uv)).unwrap_or([1.0; 4]);
+                                let rgb = [0, 1, 2].map(|i| factor[i] * texel[i] * shade);
+                                [
+                                    (rgb[0].clamp(0.0, 1.0) * 255.0) as u8,
+                                    (rgb[1].clamp(0.0, 1.0) * 255.0) as u8,
+                                    (rgb[2].clamp(0.0, 1.0) * 255.0) as u8,
+                                    255,
+                                ]
+                            });
+                        }
+                    }
+                });
+            }
+
+            let pixels: Vec<u8> = color.into_iter().flatten().collect();
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png).write_image(
+                &pixels,
+                width,
+                height,
+                image::ExtendedColorType::Rgba8,
+            )?;
+            Ok(png)
+        }
+    }
+
+    /// Depth-tested triangle fill with perspective-correct UV interpolation
+    fn rasterize<F: Fn([f32; 2]) -> [u8; 4]>(
+        verts: &[Vertex],
+        width: u32,
+        height: u32,
+        depth: &mut [f32],
+        color: &mut [[u8; 4]],
+        shade: F,
+    ) {
+        let [a, b, c] = [&verts[0], &verts[1], &verts[2]];
+        let edge = |p: &[f32; 3], q: &[f32; 3], x: f32, y: f32| {
+            (q[0] - p[0]) * (y - p[1]) - (q[1] - p[1]) * (x - p[0])
+        };
+        let area = edge(&a.screen, &b.screen, c.screen[0], c.screen[1]);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let min_x = a.screen[0].min(b.screen[0]).min(c.screen[0]).max(0.0) as u32;
+        let max_x = (a.screen[0].max(b.screen[0]).max(c.screen[0]).ceil() as u32).min(width);
+        let min_y = a.screen[1].min(b.screen[1]).min(c.screen[1]).max(0.0) as u32;
+        let max_y = (a.screen[1].max(b.screen[1]).max(c.screen[1]).ceil() as u32).min(height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(&b.screen, &c.screen, px, py) / area;
+                let w1 = edge(&c.screen, &a.screen, px, py) / area;
+                let w2 = edge(&a.screen, &b.screen, px, py) / area;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let z = w0 * a.screen[2] + w1 * b.screen[2] + w2 * c.screen[2];
+                let idx = (y * width + x) as usize;
+                if z >= depth[idx] {
+                    continue;
+                }
+
+                let inv_w = w0 * a.inv_w + w1 * b.inv_w + w2 * c.inv_w;
+                let uv = [0, 1].map(|k| {
+                    (w0 * a.uv[k] * a.inv_w + w1 * b.uv[k] * b.inv_w + w2 * c.uv[k] * c.inv_w)
+                        / inv_w
+                });
+
+                depth[idx] = z;
+                color[idx] = shade(uv);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    impl ThumbnailRenderer for Failing {
+        fn render(
+            &self,
+            _: &[u8],
+            _: u32,
+            _: u32,
+        ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+            Err("no GPU".into())
+        }
+    }
+
+    #[test]
+    fn renderer_errors_keep_their_stage() {
+        let error = render_thumbnail(&Failing, &[], 8, 8).unwrap_err();
+        assert!(matches!(
+            error,
+            OptimizeError::Callback {
+                stage: Stage::ThumbnailRenderer,
+                ..
+            }
+        ));
+        let error = render_thumbnail(&Failing, &[], 0, 8).unwrap_err();
+        assert!(matches!(error, OptimizeError::InvalidOption(_)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "software-thumbnail", feature = "png"))]
+    fn software_renderer_draws_the_model_over_the_background() {
+        use crate::{camera::CameraFraming, testing::SyntheticGlb};
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let renderer = SoftwareRenderer {
+            framing: CameraFraming {
+                yaw: 0.0,
+                pitch: 0.0,
+                ..Default::default()
+            },
+            background: [0, 0, 255, 255],
+        };
+        let png = render_thumbnail(&renderer, &glb, 32, 24).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (32, 24));
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert_ne!(image.get_pixel(16, 12).0, [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,582 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{Read, Seek},
+};
+
+use gltf::json::{
+    Index, Mesh, Node, Root, Scene,
+    accessor::Type,
+    mesh::{Mode, Primitive, Semantic},
+    validation::Checked,
+};
+use serde_json::json;
+
+use crate::{
+    blob::BlobWriter,
+    bounds::{for_each_mesh_instance, scene_roots},
+    convert::read_accessor,
+    crop::extension_textures,
+    error::OptimizeError,
+    extension::retain_extensions,
+    glb::{push_attribute, push_indices, push_positions, read_glb, write_glb},
+    math::{Mat4, bounds, cross, dot, transform_point},
+    opt::{
+        get_image_data, get_index_data, get_position_data, get_texcoord_data, optimize_document,
+    },
+    options::OptimizeOptions,
+    texcoords::material_tex_coords,
+};
+
+/// How a tile is divided into children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Subdivision {
+    /// Four children split on the horizontal plane (glTF X/Z), for terrain and building scans
+    Quadtree,
+    /// Eight children split on every axis
+    Octree,
+}
+
+/// Settings for [`optimize_tiled`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TilingOptions {
+    pub subdivision: Subdivision,
+    /// Tiles with more triangles than this are split further
+    pub max_triangles: usize,
+    /// Deepest level of the tree, the root being 0
+    pub max_depth: u32,
+}
+
+impl Default for TilingOptions {
+    fn default() -> Self {
+        TilingOptions {
+            subdivision: Subdivision::Quadtree,
+            max_triangles: 100_000,
+            max_depth: 8,
+        }
+    }
+}
+
+/// GLB content of one tile, written next to `tileset.json` under its URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TileContent {
+    /// Relative URI stored in the tileset, e.g. `tiles/0_2.glb`
+    pub uri: String,
+    pub data: Vec<u8>,
+}
+
+/// A 3D Tiles 1.1 tileset and the tile files it references
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Tileset {
+    /// Contents of `tileset.json`
+    pub json: Vec<u8>,
+    pub tiles: Vec<TileContent>,
+}
+
+/// Triangles of one primitive instance, baked to world space
+struct Part {
+    material: Option<usize>,
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    /// UV sets by `TEXCOORD_n` index
+    uvs: BTreeMap<u32, Vec<[f32; 2]>>,
+    indices: Vec<u32>,
+}
+
+/// Triangle `tri` of part `part`, with its centroid
+#[derive(Clone, Copy)]
+struct TriangleRef {
+    part: usize,
+    tri: usize,
+    centroid: [f32; 3],
+}
+
+/// Normal transform of a world matrix (cofactor of its upper 3x3, with mirroring flag)
+fn normal_matrix(m: &Mat4) -> ([[f32; 3]; 3], bool) {
+    let c = [[m[0], m[1], m[2]], [m[4], m[5], m[6]], [m[8], m[9], m[10]]];
+    let mirrored = dot(c[0], cross(c[1], c[2])) < 0.0;
+    let sign = if mirrored { -1.0 } else { 1.0 };
+    let cofactor = [cross(c[1], c[2]), cross(c[2], c[0]), cross(c[0], c[1])];
+    (cofactor.map(|col| col.map(|v| v * sign)), mirrored)
+}
+
+/// Flatten every triangle-list primitive of the default scene into world space
+///
+/// Only positions, normals, the first UV set and any other set the primitive's material samples
+/// are kept, so textures reading `TEXCOORD_1` or above still find their coordinates.
+fn collect_parts(o_blob: &[u8], o_json: &Root) -> Vec<Part> {
+    let mut parts = Vec::new();
+    for_each_mesh_instance(o_json, &scene_roots(o_json), |_, mesh, world| {
+        let Some(mesh) = o_json.meshes.get(mesh) else {
+            return;
+        };
+        let (normal_m, mirrored) = normal_matrix(world);
+        for primitive in &mesh.primitives {
+            if primitive.mode != Checked::Valid(Mode::Triangles) {
+                continue;
+            }
+            let attribute = |semantic| primitive.attributes.get(&Checked::Valid(semantic)).copied();
+            let Some(positions) = attribute(Semantic::Positions)
+                .and_then(|idx| get_position_data(o_blob, o_json, idx))
+            else {
+                continue;
+            };
+            let count = positions.len();
+            let mut indices = match primitive.indices {
+                Some(idx) => match get_index_data(o_blob, o_json, idx) {
+                    Some(indices) => indices,
+                    None => continue,
+                },
+                None => (0..count as u32).collect(),
+            };
+            indices.truncate(indices.len() / 3 * 3);
+            if indices.iter().any(|&i| i as usize >= count) {
+                continue;
+            }
+            if mirrored {
+                indices.chunks_exact_mut(3).for_each(|t| t.swap(1, 2));
+            }
+
+            let normals = attribute(Semantic::Normals)
+                .and_then(|idx| read_accessor::<3>(o_blob, o_json, idx))
+                .filter(|n| n.len() == count)
+                .map(|normals| {
+                    normals
+                        .into_iter()
+                        .map(|n| {
+                            let v = [0, 1, 2].map(|r| (0..3).map(|c| normal_m[c][r] * n[c]).sum());
+                            let len = dot(v, v).sqrt();
+                            if len > 0.0 {
+                                v.map(|x: f32| x / len)
+                            } else {
+                                n
+                            }
+                        })
+                        .collect()
+                });
+            let mut sets = primitive
+                .material
+                .and_then(|m| o_json.materials.get(m.value()))
+                .map(material_tex_coords)
+                .unwrap_or_default();
+            sets.insert(0);
+            let uvs = sets
+                .into_iter()
+                .filter_map(|set| {
+                    let uv = attribute(Semantic::TexCoords(set))
+                        .and_then(|idx| get_texcoord_data(o_blob, o_json, idx))
+                        .filter(|uv| uv.len() == count)?;
+                    Some((set, uv))
+                })
+                .collect();
+
+            parts.push(Part {
+                material: primitive.material.map(|m| m.value()),
+                positions: positions
+                    .into_iter()
+                    .map(|p| transform_point(world, p))
+                    .collect(),
+                normals,
+                uvs,
+                indices,
+            });
+        }
+    });
+    parts
+}
+
+/// Bounds of the vertices a set of triangles uses
+fn triangle_bounds(parts: &[Part], triangles: &[TriangleRef]) -> ([f32; 3], [f32; 3]) {
+    let corners: Vec<[f32; 3]> = triangles
+        .iter()
+        .flat_map(|t| {
+            let part = &parts[t.part];
+            part.indices[t.tri * 3..t.tri * 3 + 3]
+                .iter()
+                .map(|&i| part.positions[i as usize])
+        })
+        .collect();
+    bounds(&corners)
+}
+
+/// One node of the tile tree; only leaves carry content
+struct TileNode {
+    id: String,
+    min: [f32; 3],
+    max: [f32; 3],
+    triangles: Vec<TriangleRef>,
+    children: Vec<TileNode>,
+}
+
+fn build_tree(
+    parts: &[Part],
+    id: String,
+    triangles: Vec<TriangleRef>,
+    depth: u32,
+    tiling: &TilingOptions,
+) -> TileNode {
+    let (min, max) = triangle_bounds(parts, &triangles);
+    let mut node = TileNode {
+        id,
+        min,
+        max,
+        triangles,
+        children: Vec::new(),
+    };
+    if node.triangles.len() <= tiling.max_triangles || depth >= tiling.max_depth {
+        return node;
+    }
+
+    let center = [0, 1, 2].map(|a| (min[a] + max[a]) / 2.0);
+    let axes: &[usize] = match tiling.subdivision {
+        Subdivision::Quadtree => &[0, 2],
+        Subdivision::Octree => &[0, 1, 2],
+    };
+    let mut cells: BTreeMap<usize, Vec<TriangleRef>> = BTreeMap::new();
+    for t in &node.triangles {
+        let cell = axes
+            .iter()
+            .enumerate()
+            .map(|(bit, &a)| ((t.centroid[a] >= center[a]) as usize) << bit)
+            .sum();
+        cells.entry(cell).or_default().push(*t);
+    }
+    // Identical centroids cannot be separated any further
+    if cells.len() < 2 {
+        return node;
+    }
+
+    node.children = cells
+        .into_iter()
+        .map(|(cell, triangles)| {
+            let id = format!("{}_{cell}", node.id);
+            build_tree(parts, id, triangles, depth + 1, tiling)
+        })
+        .collect();
+    node.triangles.clear();
+    node
+}
+
+/// Source document for one tile: its geometry plus the materials and images it uses
+fn tile_document(
+    o_blob: &[u8],
+    o_json: &Root,
+    parts: &[Part],
+    triangles: &[TriangleRef],
+) -> (Root, Vec<u8>) {
+    let mut json = Root {
+        asset: o_json.asset.clone(),
+        materials: o_json.materials.clone(),
+        textures: o_json.textures.clone(),
+        samplers: o_json.samplers.clone(),
+        images: o_json.images.clone(),
+        ..Default::default()
+    };
+    let mut blob = BlobWriter::new();
+
+    // Primitives merge triangles sharing a material and vertex layout
+    let mut groups: BTreeMap<(Option<usize>, bool, Vec<u32>), Vec<TriangleRef>> = BTreeMap::new();
+    for t in triangles {
+        let part = &parts[t.part];
+        let sets = part.uvs.keys().copied().collect();
+        let key = (part.material, part.normals.is_some(), sets);
+        groups.entry(key).or_default().push(*t);
+    }
+
+    let mut primitives = Vec::new();
+    for ((material, has_normals, sets), triangles) in groups {
+        let mut remap: HashMap<(usize, u32), u32> = HashMap::new();
+        let (mut positions, mut normals, mut indices) = (vec![], vec![], vec![]);
+        let mut uvs: Vec<Vec<f32>> = vec![Vec::new(); sets.len()];
+        for t in &triangles {
+            let part = &parts[t.part];
+            for &i in &part.indices[t.tri * 3..t.tri * 3 + 3] {
+                let index = *remap.entry((t.part, i)).or_insert_with(|| {
+                    let v = i as usize;
+                    positions.push(part.positions[v]);
+                    if let Some(n) = &part.normals {
+                        normals.extend_from_slice(&n[v]);
+                    }
+                    for (set, uv) in uvs.iter_mut().zip(part.uvs.values()) {
+                        set.extend_from_slice(&uv[v]);
+                    }
+                    positions.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        let mut primitive = Primitive {
+            attributes: Default::default(),
+            extensions: None,
+            extras: Default::default(),
+            indices: Some(push_indices(&mut blob, &mut json, &indices)),
+            material: material.map(|m| Index::new(m as u32)),
+            mode: Checked::Valid(Mode::Triangles),
+            targets: None,
+        };
+        let position = push_positions(&mut blob, &mut json, &positions);
+        primitive
+            .attributes
+            .insert(Checked::Valid(Semantic::Positions), position);
+        if has_normals {
+            let normal = push_attribute(&mut blob, &mut json, &normals, Type::Vec3);
+            primitive
+                .attributes
+                .insert(Checked::Valid(Semantic::Normals), normal);
+        }
+        for (set, uvs) in sets.into_iter().zip(uvs) {
+            let uv = push_attribute(&mut blob, &mut json, &uvs, Type::Vec2);
+            primitive
+                .attributes
+                .insert(Checked::Valid(Semantic::TexCoords(set)), uv);
+        }
+        primitives.push(primitive);
+    }
+
+    // Copy only the images of materials this tile draws
+    let used: HashSet<usize> = primitives
+        .iter()
+        .filter_map(|p| p.material)
+        .filter_map(|m| o_json.materials.get(m.value()))
+        .filter_map(|mat| serde_json::to_value(mat).ok())
+        .flat_map(|mat| {
+            let mut found = Vec::new();
+            extension_textures(&mat, &mut found);
+            found
+        })
+        .collect();
+    let used_images: HashSet<usize> = used
+        .iter()
+        .filter_map(|&t| o_json.textures.get(t))
+        .map(|t| t.source.value())
+        .collect();
+    for i in 0..json.images.len() {
+        json.images[i].buffer_view = None;
+        let texture = o_json.textures.iter().position(|t| t.source.value() == i);
+        let data = texture
+            .filter(|_| used_images.contains(&i))
+            .and_then(|t| get_image_data(o_blob, o_json, Index::new(t as u32)));
+        if let Some(data) = data {
+            let view = blob.push_view(&mut json, data, None);
+            json.images[i].buffer_view = Some(view);
+        }
+    }
+
+    let mesh = json.push(Mesh {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        primitives,
+        weights: None,
+    });
+    let node = json.push(Node {
+        mesh: Some(mesh),
+        ..Default::default()
+    });
+    let scene = json.push(Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: vec![node],
+    });
+    json.scene = Some(scene);
+    (json, blob.into_vec())
+}
+
+/// 3D Tiles box around glTF bounds, converting from glTF Y-up to the tileset's Z-up frame
+fn bounding_box(min: [f32; 3], max: [f32; 3]) -> serde_json::Value {
+    let c = [0, 1, 2].map(|a| (min[a] + max[a]) / 2.0);
+    let h = [0, 1, 2].map(|a| (max[a] - min[a]) / 2.0);
+    json!({ "box": [c[0], -c[2], c[1], h[0], 0, 0, 0, h[2], 0, 0, 0, h[1]] })
+}
+
+fn diagonal(min: [f32; 3], max: [f32; 3]) -> f32 {
+    let d = [0, 1, 2].map(|a| max[a] - min[a]);
+    dot(d, d).sqrt()
+}
+
+/// Tileset JSON for a node, optimizing and collecting the content of its leaves
+fn write_tile(
+    o_blob: &[u8],
+    o_json: &Root,
+    parts: &[Part],
+    node: &TileNode,
+    options: &OptimizeOptions,
+    tiles: &mut Vec<TileContent>,
+) -> Result<serde_json::Value, OptimizeError> {
+    let mut tile = json!({
+        "boundingVolume": bounding_box(node.min, node.max),
+        "geometricError": diagonal(node.min, node.max),
+    });
+
+    if node.children.is_empty() {
+        let (t_json, t_blob) = tile_document(o_blob, o_json, parts, &node.triangles);
+        let (mut n_json, n_blob, _) = optimize_document(&t_blob, &t_json, options, None, None)?;
+        retain_extensions(&mut n_json, &[])?;
+
+        let uri = format!("tiles/{}.glb", node.id);
+        tile["geometricError"] = json!(0.0);
+        tile["content"] = json!({ "uri": uri });
+        tiles.push(TileContent {
+            uri,
+            data: write_glb(&n_json, n_blob)?,
+        });
+    } else {
+        let children: Result<Vec<_>, _> = node
+            .children
+            .iter()
+            .map(|child| write_tile(o_blob, o_json, parts, child, options, tiles))
+            .collect();
+        tile["children"] = children?.into();
+    }
+    Ok(tile)
+}
+
+/// Split a large scene spatially into GLB tiles plus a 3D Tiles `tileset.json`
+///
+/// The default scene is flattened to world space and its triangles are divided by centroid
+/// into a quadtree or octree until each tile holds at most `tiling.max_triangles`. Each leaf
+/// becomes one GLB run through the usual optimization (`center_pivot` is ignored so tiles
+/// stay aligned); inner tiles have no content and refine with `ADD`. Only positions,
+/// normals, the first UV set and materials are kept: animations, skins and morph targets
+/// are dropped. Bounding volumes are in the glTF frame converted to Z-up; set a root
+/// `transform` in the tileset to place it on the globe.
+pub fn optimize_tiled<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+    tiling: &TilingOptions,
+) -> Result<Tileset, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let options = OptimizeOptions {
+        center_pivot: false,
+        ..options.clone()
+    };
+
+    let parts = collect_parts(&o_blob, &o_json);
+    let triangles: Vec<TriangleRef> = parts
+        .iter()
+        .enumerate()
+        .flat_map(|(p, part)| {
+            part.indices
+                .chunks_exact(3)
+                .enumerate()
+                .map(move |(tri, t)| {
+                    let v = [0, 1, 2].map(|k| part.positions[t[k] as usize]);
+                    TriangleRef {
+                        part: p,
+                        tri,
+                        centroid: [0, 1, 2].map(|a| (v[0][a] + v[1][a] + v[2][a]) / 3.0),
+                    }
+                })
+        })
+        .collect();
+    if triangles.is_empty() {
+        return Err(OptimizeError::NoGeometry { task: "tile" });
+    }
+
+    let tree = build_tree(&parts, "0".to_string(), triangles, 0, tiling);
+    let mut tiles = Vec::new();
+    let mut root = write_tile(&o_blob, &o_json, &parts, &tree, &options, &mut tiles)?;
+    root["refine"] = json!("ADD");
+
+    let tileset = json!({
+        "asset": { "version": "1.1", "generator": "gltf_opt" },
+        "geometricError": diagonal(tree.min, tree.max),
+        "root": root,
+    });
+    Ok(Tileset {
+        json: serde_json::to_vec_pretty(&tileset)?,
+        tiles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::testing::SyntheticGlb;
+
+    /// Sixteen triangles spread along X (meshes) and Z (primitives)
+    fn tiled(tiling: &TilingOptions) -> (Value, Vec<usize>) {
+        let glb = SyntheticGlb {
+            meshes: 4,
+            primitives_per_mesh: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let tileset =
+            optimize_tiled(&mut Cursor::new(glb), &OptimizeOptions::default(), tiling).unwrap();
+        let triangles = tileset
+            .tiles
+            .iter()
+            .map(|tile| {
+                let (json, blob) = read_glb(&mut Cursor::new(&tile.data)).unwrap();
+                let primitives = json.meshes.iter().flat_map(|m| &m.primitives);
+                primitives
+                    .map(|p| {
+                        get_index_data(&blob, &json, p.indices.unwrap())
+                            .unwrap()
+                            .len()
+                            / 3
+                    })
+                    .sum()
+            })
+            .collect();
+        let json = serde_json::from_slice(&tileset.json).unwrap();
+        (json, triangles)
+    }
+
+    fn content_uris(tile: &Value, uris: &mut Vec<String>) {
+        if let Some(uri) = tile["content"]["uri"].as_str() {
+            uris.push(uri.to_string());
+        }
+        for child in tile["children"].as_array().into_iter().flatten() {
+            content_uris(child, uris);
+        }
+    }
+
+    #[test]
+    fn large_scenes_split_into_leaf_tiles() {
+        let tiling = TilingOptions {
+            max_triangles: 4,
+            ..Default::default()
+        };
+        let (tileset, triangles) = tiled(&tiling);
+        assert_eq!(triangles.iter().sum::<usize>(), 16);
+        assert!(triangles.len() > 1);
+        assert!(triangles.iter().all(|&n| n <= 4));
+
+        let root = &tileset["root"];
+        assert_eq!(root["refine"], "ADD");
+        assert!(root.get("content").is_none());
+        let mut uris = Vec::new();
+        content_uris(root, &mut uris);
+        assert_eq!(uris.len(), triangles.len());
+        assert!(uris.iter().all(|uri| uri.starts_with("tiles/0_")));
+    }
+
+    #[test]
+    fn depth_limit_keeps_a_single_tile() {
+        let tiling = TilingOptions {
+            max_triangles: 4,
+            max_depth: 0,
+            subdivision: Subdivision::Octree,
+        };
+        let (tileset, triangles) = tiled(&tiling);
+        assert_eq!(triangles, [16]);
+        assert_eq!(tileset["root"]["content"]["uri"], "tiles/0.glb");
+        // X spans the four meshes, glTF Z becomes the tileset's -Y
+        let region = &tileset["root"]["boundingVolume"]["box"];
+        assert_eq!(region[0], 3.5);
+        assert_eq!(region[1], -0.5);
+        assert_eq!(region[3], 3.5);
+    }
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+
+#[cfg(feature = "ktx2")]
+use crate::error::OptimizeError;
+use crate::opt::TextureType;
+
+/// KTX2 key/value entry listing the intended transcode targets
+#[cfg(feature = "ktx2")]
+pub(crate) const TRANSCODE_TARGETS_KEY: &str = "TranscodeTargets";
+
+/// KTX2 keys the optimizer writes itself, which caller metadata may not use
+#[cfg(feature = "ktx2")]
+const OWN_KEYS: [&str; 4] = [
+    "Tool",
+    "Dimensions",
+    "CompressionMode",
+    TRANSCODE_TARGETS_KEY,
+];
+
+/// Error for a caller KTX2 metadata key that clashes with the optimizer's or the format's
+#[cfg(feature = "ktx2")]
+pub(crate) fn reserved_key_error(key: &str) -> Option<OptimizeError> {
+    let reserved =
+        OWN_KEYS.contains(&key) || key.get(..3).is_some_and(|p| p.eq_ignore_ascii_case("ktx"));
+    reserved.then(|| OptimizeError::InvalidOption(format!("KTX2 metadata key `{key}` is reserved")))
+}
+
+/// GPU formats runtime loaders are expected to transcode Basis textures to
+///
+/// Written to KTX2 metadata as a comma separated list (`ETC2,ASTC,BC7`), so a loader can pick
+/// its transcode path without probing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct TranscodeTargets {
+    /// ETC2 RGB(A), the mobile baseline
+    pub etc2: bool,
+    /// ASTC 4x4, modern mobile and Apple GPUs
+    pub astc: bool,
+    /// BC7, desktop GPUs
+    pub bc7: bool,
+}
+
+impl TranscodeTargets {
+    pub fn is_empty(&self) -> bool {
+        !(self.etc2 || self.astc || self.bc7)
+    }
+
+    /// Parse a metadata value written by the optimizer, ignoring unknown names
+    #[cfg(feature = "ktx2")]
+    pub(crate) fn parse(value: &str) -> Self {
+        let mut targets = TranscodeTargets::default();
+        for name in value.trim_end_matches('\0').split(',') {
+            match name.trim() {
+                "ETC2" => targets.etc2 = true,
+                "ASTC" => targets.astc = true,
+                "BC7" => targets.bc7 = true,
+                _ => {}
+            }
+        }
+        targets
+    }
+}
+
+impl fmt::Display for TranscodeTargets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = [(self.etc2, "ETC2"), (self.astc, "ASTC"), (self.bc7, "BC7")]
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Basis Universal supercompression mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BasisMode {
+    /// Small files; transcodes cleanly to ETC2 but carries only ETC1-level quality
+    Etc1s,
+    /// Roughly 4x larger; keeps BC7/ASTC quality after transcoding
+    Uastc,
+}
+
+impl BasisMode {
+    /// Mode to use for a texture slot given the targets it will be transcoded to
+    ///
+    /// Normal maps headed for ASTC or BC7 get UASTC, since ETC1S block artifacts show up as
+    /// lighting errors; everything else stays on the smaller ETC1S.
+    pub fn recommended(targets: TranscodeTargets, texture_type: TextureType) -> Self {
+        if texture_type == TextureType::Normal && (targets.astc || targets.bc7) {
+            BasisMode::Uastc
+        } else {
+            BasisMode::Etc1s
+        }
+    }
+
+    /// Whether this mode is a reasonable fit for the targets
+    ///
+    /// ETC1S is wrong only where [`recommended`](Self::recommended) asks for UASTC; UASTC is
+    /// wasted when no high-quality target (ASTC, BC7) is requested.
+    pub fn suits(&self, targets: TranscodeTargets, texture_type: TextureType) -> bool {
+        match self {
+            BasisMode::Etc1s => Self::recommended(targets, texture_type) == BasisMode::Etc1s,
+            BasisMode::Uastc => targets.astc || targets.bc7,
+        }
+    }
+}
+
+/// Basis Universal settings for the textures of one material slot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureCompressionProfile {
+    pub mode: BasisMode,
+    /// ETC1S quality, 1-255; `auto_quality` moves it per texture
+    pub quality_level: u32,
+    /// ETC1S endpoint RDO threshold; higher gives smaller, blurrier files
+    pub endpoint_rdo_threshold: f32,
+    /// ETC1S selector RDO threshold; higher gives smaller, blurrier files
+    pub selector_rdo_threshold: f32,
+    /// UASTC pack level, 0 (fastest) to 4 (slowest, best)
+    pub uastc_level: u32,
+    /// UASTC rate-distortion quality scalar, trading detail for better compressing data;
+    /// `None` turns UASTC RDO off
+    pub uastc_rdo_quality: Option<f32>,
+    /// Zstandard level for UASTC data (`zstd` feature); ETC1S data is always BasisLZ
+    pub zstd_level: Option<i32>,
+}
+
+impl TextureCompressionProfile {
+    /// ETC1S at a quality level and one RDO threshold for endpoints and selectors
+    pub fn etc1s(quality_level: u32, rdo_threshold: f32) -> Self {
+        TextureCompressionProfile {
+            mode: BasisMode::Etc1s,
+            quality_level,
+            endpoint_rdo_threshold: rdo_threshold,
+            selector_rdo_threshold: rdo_threshold,
+            uastc_level: 2,
+            uastc_rdo_quality: None,
+            zstd_level: None,
+        }
+    }
+
+    /// UASTC at the default pack level without RDO or supercompression
+    pub fn uastc() -> Self {
+        TextureCompressionProfile {
+            mode: BasisMode::Uastc,
+            ..Self::etc1s(150, 1.25)
+        }
+    }
+}
+
+/// KTX2 compression per material slot
+///
+/// The defaults are ETC1S everywhere, at a higher quality for normal maps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct TextureCompressionProfiles {
+    pub base_color: TextureCompressionProfile,
+    pub normal: TextureCompressionProfile,
+    pub metallic_roughness: TextureCompressionProfile,
+    pub occlusion: TextureCompressionProfile,
+    pub emissive: TextureCompressionProfile,
+}
+
+impl Default for TextureCompressionProfiles {
+    fn default() -> Self {
+        let standard = TextureCompressionProfile::etc1s(150, 1.25);
+        TextureCompressionProfiles {
+            base_color: standard,
+            normal: TextureCompressionProfile::etc1s(180, 1.0),
+            metallic_roughness: standard,
+            occlusion: standard,
+            emissive: standard,
+        }
+    }
+}
+
+impl TextureCompressionProfiles {
+    /// Profile for a texture slot
+    pub fn for_type(&self, texture_type: TextureType) -> TextureCompressionProfile {
+        match texture_type {
+            TextureType::BaseColor => self.base_color,
+            TextureType::Normal => self.normal,
+            TextureType::MetallicRoughness => self.metallic_roughness,
+            TextureType::Occlusion => self.occlusion,
+            TextureType::Emissive => self.emissive,
+        }
+    }
+
+    /// Profile for a texture slot, to change
+    pub(crate) fn for_type_mut(
+        &mut self,
+        texture_type: TextureType,
+    ) -> &mut TextureCompressionProfile {
+        match texture_type {
+            TextureType::BaseColor => &mut self.base_color,
+            TextureType::Normal => &mut self.normal,
+            TextureType::MetallicRoughness => &mut self.metallic_roughness,
+            TextureType::Occlusion => &mut self.occlusion,
+            TextureType::Emissive => &mut self.emissive,
+        }
+    }
+}
+
+/// KTX2 `supercompressionScheme` value for Zstandard
+#[cfg(all(feature = "ktx2", feature = "zstd"))]
+const KTX2_SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+/// Zstandard-supercompress every level of a KTX2 file that has no supercompression
+///
+/// Levels keep their storage order. The header, level index and the `bytesPlane` fields of
+/// the data format descriptor are updated as the KTX2 spec asks for supercompressed data.
+#[cfg(all(feature = "ktx2", feature = "zstd"))]
+pub(crate) fn zstd_supercompress(ktx2: &[u8], level: i32) -> Result<Vec<u8>, OptimizeError> {
+    let field = |at: usize, len: usize| -> Result<u64, OptimizeError> {
+        let bytes = ktx2
+            .get(at..at + len)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 header".to_string(),
+            })?;
+        Ok(bytes.iter().rev().fold(0, |v, &b| v << 8 | b as u64))
+    };
+    if field(44, 4)? != 0 {
+        return Err(OptimizeError::Unsupported {
+            what: "supercompressing already supercompressed KTX2 data".to_string(),
+        });
+    }
+    let level_count = field(40, 4)?.max(1) as usize;
+    let levels = (0..level_count)
+        .map(|i| {
+            Ok((
+                field(80 + i * 24, 8)? as usize,
+                field(88 + i * 24, 8)? as usize,
+            ))
+        })
+        .collect::<Result<Vec<_>, OptimizeError>>()?;
+
+    let start = levels.iter().map(|&(offset, _)| offset).min().unwrap_or(0);
+    let mut out = ktx2
+        .get(..start)
+        .ok_or_else(|| OptimizeError::Truncated {
+            what: "KTX2 level".to_string(),
+        })?
+        .to_vec();
+    out[44..48].copy_from_slice(&KTX2_SUPERCOMPRESSION_ZSTD.to_le_bytes());
+    // bytesPlane0-7 of the basic descriptor block
+    let dfd = field(48, 4)? as usize;
+    if let Some(planes) = out.get_mut(dfd + 20..dfd + 28) {
+        planes.fill(0);
+    }
+
+    let mut order: Vec<usize> = (0..level_count).collect();
+    order.sort_by_key(|&i| levels[i].0);
+    for i in order {
+        let (offset, length) = levels[i];
+        let data = ktx2
+            .get(offset..offset + length)
+            .ok_or_else(|| OptimizeError::Truncated {
+                what: "KTX2 level".to_string(),
+            })?;
+        let compressed = zstd::encode_all(data, level)?;
+        // uncompressedByteLength keeps the original size
+        let entry = 80 + i * 24;
+        let n_offset = out.len() as u64;
+        out[entry..entry + 8].copy_from_slice(&n_offset.to_le_bytes());
+        out[entry + 8..entry + 16].copy_from_slice(&(compressed.len() as u64).to_le_bytes());
+        out.extend_from_slice(&compressed);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_looked_up_by_slot() {
+        let profiles = TextureCompressionProfiles {
+            normal: TextureCompressionProfile::uastc(),
+            ..Default::default()
+        };
+        assert_eq!(
+            profiles.for_type(TextureType::Normal).mode,
+            BasisMode::Uastc
+        );
+        assert_eq!(
+            profiles.for_type(TextureType::BaseColor),
+            profiles.base_color
+        );
+    }
+
+    #[test]
+    fn transcode_targets_are_listed_in_order() {
+        let targets = TranscodeTargets {
+            etc2: true,
+            astc: false,
+            bc7: true,
+        };
+        assert_eq!(targets.to_string(), "ETC2,BC7");
+        assert_eq!(TranscodeTargets::default().to_string(), "");
+        assert!(TranscodeTargets::default().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "ktx2")]
+    fn transcode_targets_parse_metadata_values() {
+        let targets = TranscodeTargets {
+            etc2: true,
+            astc: true,
+            bc7: true,
+        };
+        assert_eq!(TranscodeTargets::parse(&targets.to_string()), targets);
+        assert_eq!(
+            TranscodeTargets::parse("ASTC, PVRTC\0"),
+            TranscodeTargets {
+                astc: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn uastc_is_recommended_for_normal_maps_on_high_quality_targets() {
+        let etc2 = TranscodeTargets {
+            etc2: true,
+            ..Default::default()
+        };
+        let astc = TranscodeTargets { astc: true, ..etc2 };
+        assert_eq!(
+            BasisMode::recommended(astc, TextureType::Normal),
+            BasisMode::Uastc
+        );
+        assert_eq!(
+            BasisMode::recommended(etc2, TextureType::Normal),
+            BasisMode::Etc1s
+        );
+        assert_eq!(
+            BasisMode::recommended(astc, TextureType::BaseColor),
+            BasisMode::Etc1s
+        );
+        assert!(!BasisMode::Etc1s.suits(astc, TextureType::Normal));
+        assert!(BasisMode::Uastc.suits(astc, TextureType::BaseColor));
+        assert!(!BasisMode::Uastc.suits(etc2, TextureType::Normal));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ktx2", feature = "zstd"))]
+    fn zstd_supercompression_rewrites_levels() {
+        // Header, two-level index, a 44-byte descriptor, then level 1 stored before level 0
+        let (level0, level1) = (vec![7u8; 64], vec![3u8; 16]);
+        let mut ktx2 = vec![0u8; 80 + 2 * 24];
+        let put = |ktx2: &mut Vec<u8>, at: usize, value: u64, len: usize| {
+            ktx2[at..at + len].copy_from_slice(&value.to_le_bytes()[..len]);
+        };
+        put(&mut ktx2, 40, 2, 4);
+        put(&mut ktx2, 48, 128, 4);
+        put(&mut ktx2, 52, 44, 4);
+        ktx2.extend_from_slice(&[16u8; 44]);
+        for (i, offset, data) in [(1, 172, &level1), (0, 188, &level0)] {
+            put(&mut ktx2, 80 + i * 24, offset, 8);
+            put(&mut ktx2, 88 + i * 24, data.len() as u64, 8);
+            put(&mut ktx2, 96 + i * 24, data.len() as u64, 8);
+        }
+        ktx2.extend_from_slice(&level1);
+        ktx2.extend_from_slice(&level0);
+
+        let out = zstd_supercompress(&ktx2, 3).unwrap();
+        let field = |at: usize| u64::from_le_bytes(out[at..at + 8].try_into().unwrap()) as usize;
+        assert_eq!(out[44..48], 2u32.to_le_bytes());
+        assert_eq!(out[148..156], [0; 8]);
+        for (i, data) in [(0, &level0), (1, &level1)] {
+            let (offset, length) = (field(80 + i * 24), field(88 + i * 24));
+            assert_eq!(field(96 + i * 24), data.len());
+            let decoded = zstd::decode_all(&out[offset..offset + length]).unwrap();
+            assert_eq!(&decoded, data);
+        }
+        assert!(field(80 + 24) < field(80));
+    }
+}
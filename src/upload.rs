@@ -0,0 +1,231 @@
+use std::{
+    io::{Cursor, Read, Seek},
+    ops::Range,
+};
+
+use gltf::json::{Index, Root, buffer::View};
+
+use crate::{error::OptimizeError, glb::read_glb, meshopt::MESHOPT_EXTENSION};
+
+/// GLB header plus the JSON chunk header, before the JSON bytes
+const JSON_START: usize = 20;
+/// BIN chunk header: length and type
+const BIN_HEADER_LEN: usize = 8;
+
+/// What a buffer view of vertex or index data feeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ViewUsage {
+    /// Attributes or morph targets, for a vertex buffer
+    Vertices,
+    /// Primitive indices, for an index buffer
+    Indices,
+}
+
+/// An embedded image as laid out in a GLB file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextureBytes {
+    pub texture: usize,
+    pub image: usize,
+    pub mime_type: Option<String>,
+    /// Byte range within the whole file
+    pub range: Range<u64>,
+}
+
+/// A buffer view of geometry as laid out in a GLB file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ViewBytes {
+    pub view: usize,
+    pub usage: ViewUsage,
+    pub byte_stride: Option<usize>,
+    /// Byte range within the whole file
+    pub range: Range<u64>,
+}
+
+/// File byte ranges of the data a loader uploads to the GPU as is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UploadRanges {
+    /// Every texture whose image is embedded in the BIN chunk, in texture order
+    pub textures: Vec<TextureBytes>,
+    /// Every vertex and index view stored in the BIN chunk, in view order
+    pub views: Vec<ViewBytes>,
+}
+
+/// Where the BIN chunk data starts in a GLB file, checking the chunk headers
+fn bin_start(glb: &[u8]) -> Result<usize, OptimizeError> {
+    let u32_at = |at: usize| {
+        glb.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    if glb.get(..4) != Some(b"glTF") {
+        return Err(OptimizeError::NotFormat {
+            expected: "GLB file",
+        });
+    }
+    let json_len = u32_at(12).ok_or_else(|| OptimizeError::Truncated {
+        what: "GLB header".to_string(),
+    })?;
+    let bin_header = JSON_START + json_len;
+    if glb.get(bin_header + 4..bin_header + BIN_HEADER_LEN) != Some(b"BIN\0") {
+        return Err(OptimizeError::MissingChunk { chunk: "BIN" });
+    }
+    Ok(bin_header + BIN_HEADER_LEN)
+}
+
+/// Usage of every view holding primitive indices or vertex data, by view index
+fn view_usages(json: &Root) -> Vec<Option<ViewUsage>> {
+    let mut usages = vec![None; json.buffer_views.len()];
+    let mut claim = |accessor: Index<gltf::json::Accessor>, usage| {
+        let view = json
+            .accessors
+            .get(accessor.value())
+            .and_then(|a| a.buffer_view);
+        if let Some(slot) = view.and_then(|v| usages.get_mut(v.value())) {
+            *slot = Some(usage);
+        }
+    };
+    for primitive in json.meshes.iter().flat_map(|m| &m.primitives) {
+        for &accessor in primitive.attributes.values() {
+            claim(accessor, ViewUsage::Vertices);
+        }
+        for target in primitive.targets.iter().flatten() {
+            for accessor in [target.positions, target.normals, target.tangents]
+                .into_iter()
+                .flatten()
+            {
+                claim(accessor, ViewUsage::Vertices);
+            }
+        }
+        if let Some(indices) = primitive.indices {
+            claim(indices, ViewUsage::Indices);
+        }
+    }
+    usages
+}
+
+/// File range of a view stored plainly in the BIN chunk
+fn file_range(view: &View, bin_start: usize, bin_len: usize) -> Option<Range<u64>> {
+    let compressed = view
+        .extensions
+        .as_ref()
+        .is_some_and(|e| e.others.contains_key(MESHOPT_EXTENSION));
+    if view.buffer.value() != 0 || compressed {
+        return None;
+    }
+    let start = view.byte_offset.map_or(0, |o| o.0 as usize);
+    let end = start.checked_add(view.byte_length.0 as usize)?;
+    (end <= bin_len).then(|| (bin_start + start) as u64..(bin_start + end) as u64)
+}
+
+/// Byte ranges of the embedded textures and the vertex and index views of a GLB, relative to
+/// the start of the file
+///
+/// A loader can `mmap` the file and hand these regions to the GPU without parsing the JSON at
+/// runtime, for instance by storing the result next to the file. Views in external buffers
+/// or compressed with `EXT_meshopt_compression` are left out, since their bytes can't be
+/// uploaded as they are.
+pub fn upload_ranges<R: Read + Seek>(reader: &mut R) -> Result<UploadRanges, OptimizeError> {
+    let mut glb = Vec::new();
+    reader.read_to_end(&mut glb)?;
+    let bin_start = bin_start(&glb)?;
+    let (json, blob) = read_glb(&mut Cursor::new(&glb))?;
+    let bin_len = blob.len();
+
+    let view_range = |view: usize| {
+        json.buffer_views
+            .get(view)
+            .and_then(|v| file_range(v, bin_start, bin_len))
+    };
+    let textures = json
+        .textures
+        .iter()
+        .enumerate()
+        .filter_map(|(texture, t)| {
+            let image = json.images.get(t.source.value())?;
+            Some(TextureBytes {
+                texture,
+                image: t.source.value(),
+                mime_type: image.mime_type.as_ref().map(|m| m.0.clone()),
+                range: view_range(image.buffer_view?.value())?,
+            })
+        })
+        .collect();
+    let views = view_usages(&json)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(view, usage)| {
+            Some(ViewBytes {
+                view,
+                usage: usage?,
+                byte_stride: json.buffer_views[view].byte_stride.map(|s| s.0),
+                range: view_range(view)?,
+            })
+        })
+        .collect();
+    Ok(UploadRanges { textures, views })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        opt::{get_image_data, optimize_with},
+        options::OptimizeOptions,
+        testing::{SyntheticGlb, view_bytes},
+    };
+
+    #[test]
+    fn upload_ranges_point_into_the_file() {
+        let glb = SyntheticGlb {
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let out = optimize_with(&mut Cursor::new(glb), &OptimizeOptions::default()).unwrap();
+        let (json, blob) = read_glb(&mut Cursor::new(&out)).unwrap();
+        let ranges = upload_ranges(&mut Cursor::new(&out)).unwrap();
+        let file = |range: &std::ops::Range<u64>| &out[range.start as usize..range.end as usize];
+
+        assert_eq!(ranges.textures.len(), json.textures.len());
+        for texture in &ranges.textures {
+            let data = get_image_data(&blob, &json, Index::new(texture.texture as u32)).unwrap();
+            assert_eq!(file(&texture.range), data);
+        }
+        let index_views: Vec<usize> = json
+            .meshes
+            .iter()
+            .flat_map(|m| &m.primitives)
+            .filter_map(|p| json.accessors[p.indices?.value()].buffer_view)
+            .map(|v| v.value())
+            .collect();
+        assert!(!index_views.is_empty());
+        for view in &ranges.views {
+            assert_eq!(file(&view.range), view_bytes(&json, &blob, view.view));
+            assert_eq!(
+                view.usage == ViewUsage::Indices,
+                index_views.contains(&view.view)
+            );
+        }
+        assert!(ranges.views.iter().any(|v| v.usage == ViewUsage::Vertices));
+
+        let error = |bytes: &[u8]| upload_ranges(&mut Cursor::new(bytes.to_vec())).unwrap_err();
+        assert!(matches!(
+            error(b"{}"),
+            OptimizeError::NotFormat {
+                expected: "GLB file"
+            }
+        ));
+        assert!(matches!(
+            error(b"glTF\x02\0\0\0"),
+            OptimizeError::Truncated { what } if what == "GLB header"
+        ));
+        assert!(matches!(
+            error(b"glTF\x02\0\0\0\x18\0\0\0\x04\0\0\0JSON{}  "),
+            OptimizeError::MissingChunk { chunk: "BIN" }
+        ));
+    }
+}
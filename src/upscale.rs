@@ -0,0 +1,116 @@
+use image::{DynamicImage, imageops::FilterType};
+
+/// Filter used by [`TextureUpscale`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpscaleFilter {
+    /// Blocky, keeps pixel art crisp
+    Nearest,
+    Bilinear,
+    /// Sharper than bilinear with little ringing
+    #[default]
+    CatmullRom,
+    /// Sharpest, may ring around hard edges
+    Lanczos3,
+}
+
+impl From<UpscaleFilter> for FilterType {
+    fn from(filter: UpscaleFilter) -> Self {
+        match filter {
+            UpscaleFilter::Nearest => FilterType::Nearest,
+            UpscaleFilter::Bilinear => FilterType::Triangle,
+            UpscaleFilter::CatmullRom => FilterType::CatmullRom,
+            UpscaleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Enlarge textures whose longer side is below `min_size`, see
+/// [`OptimizeOptions::upscale_textures`](crate::OptimizeOptions::upscale_textures)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TextureUpscale {
+    pub min_size: u32,
+    pub filter: UpscaleFilter,
+}
+
+impl Default for TextureUpscale {
+    fn default() -> Self {
+        TextureUpscale {
+            min_size: 256,
+            filter: UpscaleFilter::default(),
+        }
+    }
+}
+
+/// `img` enlarged so its longer side reaches `min_size` (at most the slot's `size`), keeping the
+/// aspect ratio; `None` when it is large enough already
+pub(crate) fn upscale_image(
+    img: &DynamicImage,
+    upscale: &TextureUpscale,
+    size: u32,
+) -> Option<DynamicImage> {
+    let longer = img.width().max(img.height());
+    let target = upscale.min_size.min(size);
+    if longer == 0 || longer >= target {
+        return None;
+    }
+    let scale =
+        |side: u32| ((side as u64 * target as u64 + longer as u64 / 2) / longer as u64).max(1);
+    Some(img.resize_exact(
+        scale(img.width()) as u32,
+        scale(img.height()) as u32,
+        upscale.filter.into(),
+    ))
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        decode::load_image,
+        glb::read_glb,
+        opt::{get_image_data, optimize_variants, optimize_with},
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn small_textures_are_upscaled() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            texture_size: 16,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let image_size = |out: &[u8]| {
+            let (json, blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&blob, &json, Index::new(0)).unwrap();
+            let img = load_image(data, Vec::new()).unwrap();
+            (img.width(), img.height())
+        };
+        let upscale = TextureUpscale {
+            min_size: 64,
+            filter: UpscaleFilter::Bilinear,
+        };
+
+        let options = OptimizeOptions {
+            upscale_textures: Some(upscale),
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+        assert_eq!(image_size(&out), (64, 64));
+        // Never beyond the slot size
+        let out = optimize_with(&mut Cursor::new(&glb), &options.clone().texture_size(32)).unwrap();
+        assert_eq!(image_size(&out), (32, 32));
+        let variants = optimize_variants(&mut Cursor::new(&glb), &options, &[128]).unwrap();
+        assert_eq!(image_size(&variants[0].glb), (64, 64));
+        let out = optimize_with(&mut Cursor::new(&glb), &OptimizeOptions::default()).unwrap();
+        assert_eq!(image_size(&out), (16, 16));
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use gltf::json::{
+    Image, Index, Material, Root, Texture,
+    image::MimeType,
+    material::{NormalTexture, OcclusionTexture},
+    mesh::Primitive,
+    texture::Info,
+};
+use serde_json::{Value, json};
+
+use crate::{
+    blob::BlobWriter,
+    decode::{sniff_format, sniffed_mime_type},
+    error::{Malformation, OptimizeError},
+    extension::retain_extensions,
+    glb::{read_glb, write_glb},
+    material::{MaterialEdit, apply_material_edit},
+    opt::{TextureType, optimize_document},
+    options::OptimizeOptions,
+};
+
+/// Extension switching primitive materials between named variants
+pub(crate) const VARIANTS_EXTENSION: &str = "KHR_materials_variants";
+
+/// Source materials a primitive's `KHR_materials_variants` mappings point at
+pub(crate) fn variant_materials(primitive: &Primitive) -> Vec<usize> {
+    let mappings = primitive
+        .extensions
+        .as_ref()
+        .and_then(|e| e.others.get(VARIANTS_EXTENSION))
+        .and_then(|v| v.get("mappings"))
+        .and_then(Value::as_array);
+    mappings
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get("material")?.as_u64())
+        .map(|m| m as usize)
+        .collect()
+}
+
+/// Point a primitive's variant mappings at new material indices, dropping mappings `f` has
+/// none for
+pub(crate) fn remap_variant_materials<F: Fn(usize) -> Option<usize>>(
+    primitive: &mut Primitive,
+    f: F,
+) {
+    let mappings = primitive
+        .extensions
+        .as_mut()
+        .and_then(|e| e.others.get_mut(VARIANTS_EXTENSION))
+        .and_then(|v| v.get_mut("mappings"))
+        .and_then(Value::as_array_mut);
+    if let Some(mappings) = mappings {
+        mappings.retain_mut(|mapping| {
+            let material = mapping.get("material").and_then(Value::as_u64);
+            match material.and_then(|m| f(m as usize)) {
+                Some(n) => {
+                    mapping["material"] = n.into();
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
+
+/// New factors and maps for the materials of one name in a [`MaterialVariant`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialOverride {
+    /// Factors to set; `edit.name` picks the source materials
+    pub edit: MaterialEdit,
+    /// Encoded images (PNG, JPEG, ...) replacing the material's maps, by slot; a slot the
+    /// material lacks is added reading `TEXCOORD_0`
+    pub textures: Vec<(TextureType, Vec<u8>)>,
+}
+
+/// Named set of material overrides, e.g. one color option of a product
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialVariant {
+    pub name: String,
+    pub overrides: Vec<MaterialOverride>,
+}
+
+/// Point `slot` of `mat` at `texture`, keeping the UV set and strength of an existing slot
+pub(crate) fn set_slot(mat: &mut Material, slot: TextureType, texture: Index<Texture>) {
+    let info = |old: Option<&Info>| Info {
+        index: texture,
+        tex_coord: old.map_or(0, |i| i.tex_coord),
+        extensions: None,
+        extras: Default::default(),
+    };
+    let pbr = &mut mat.pbr_metallic_roughness;
+    match slot {
+        TextureType::BaseColor => {
+            pbr.base_color_texture = Some(info(pbr.base_color_texture.as_ref()));
+        }
+        TextureType::MetallicRoughness => {
+            pbr.metallic_roughness_texture = Some(info(pbr.metallic_roughness_texture.as_ref()));
+        }
+        TextureType::Emissive => mat.emissive_texture = Some(info(mat.emissive_texture.as_ref())),
+        TextureType::Normal => {
+            let old = mat.normal_texture.take();
+            mat.normal_texture = Some(NormalTexture {
+                index: texture,
+                scale: old.as_ref().map_or(1.0, |n| n.scale),
+                tex_coord: old.as_ref().map_or(0, |n| n.tex_coord),
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        TextureType::Occlusion => {
+            let old = mat.occlusion_texture.take();
+            mat.occlusion_texture = Some(OcclusionTexture {
+                index: texture,
+                strength: old.as_ref().map_or(Default::default(), |o| o.strength),
+                tex_coord: old.as_ref().map_or(0, |o| o.tex_coord),
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+    }
+}
+
+/// Texture a material reads in `slot`
+fn slot_texture(mat: &Material, slot: TextureType) -> Option<Index<Texture>> {
+    let pbr = &mat.pbr_metallic_roughness;
+    match slot {
+        TextureType::BaseColor => pbr.base_color_texture.as_ref().map(|i| i.index),
+        TextureType::MetallicRoughness => pbr.metallic_roughness_texture.as_ref().map(|i| i.index),
+        TextureType::Emissive => mat.emissive_texture.as_ref().map(|i| i.index),
+        TextureType::Normal => mat.normal_texture.as_ref().map(|n| n.index),
+        TextureType::Occlusion => mat.occlusion_texture.as_ref().map(|o| o.index),
+    }
+}
+
+/// Per variant, the index of each overridden source material's copy
+type VariantCopies = Vec<HashMap<usize, usize>>;
+
+/// Source document with a material appended per variant and overridden source material
+///
+/// Returns the copies made for each variant. New images get buffer views of their own; new
+/// textures take the sampler of the slot they replace. Fails when an override names no
+/// material, sets a factor outside `0..=1` or brings an image of unknown format.
+fn add_variant_materials(
+    o_blob: &[u8],
+    o_json: &Root,
+    variants: &[MaterialVariant],
+) -> Result<(Root, Vec<u8>, VariantCopies), OptimizeError> {
+    let mut n_json = o_json.clone();
+    let mut n_blob = BlobWriter::from(o_blob.to_vec());
+    let mut copies = Vec::with_capacity(variants.len());
+
+    for variant in variants {
+        let mut copy_of: HashMap<usize, usize> = HashMap::new();
+        for material_override in &variant.overrides {
+            let name = material_override.edit.name.as_str();
+            let matched: Vec<usize> = (0..o_json.materials.len())
+                .filter(|&m| o_json.materials[m].name.as_deref() == Some(name))
+                .collect();
+            if matched.is_empty() {
+                return Err(OptimizeError::InvalidOption(format!(
+                    "variant '{}': no material is named '{name}'",
+                    variant.name
+                )));
+            }
+
+            // One texture per image, shared by every material of the name
+            let mut textures = Vec::new();
+            for (slot, data) in &material_override.textures {
+                let mime_type = sniffed_mime_type(data).ok_or_else(|| {
+                    OptimizeError::InvalidOption(format!(
+                        "variant '{}': {} map of '{name}' is {}",
+                        variant.name,
+                        slot.label(),
+                        sniff_format(data)
+                    ))
+                })?;
+                let view = n_blob.push_view(&mut n_json, data, None);
+                let image = n_json.push(Image {
+                    buffer_view: Some(view),
+                    mime_type: Some(MimeType(mime_type.to_string())),
+                    name: None,
+                    uri: None,
+                    extensions: None,
+                    extras: Default::default(),
+                });
+                let sampler = slot_texture(&o_json.materials[matched[0]], *slot)
+                    .and_then(|t| o_json.textures.get(t.value()))
+                    .and_then(|t| t.sampler);
+                let texture = n_json.push(Texture {
+                    name: None,
+                    sampler,
+                    source: image,
+                    extensions: None,
+                    extras: Default::default(),
+                });
+                textures.push((*slot, texture));
+            }
+
+            for m in matched {
+                // Later overrides of the same material build on earlier ones
+                let index = *copy_of.entry(m).or_insert_with(|| {
+                    let mut copy = o_json.materials[m].clone();
+                    copy.name = copy.name.map(|n| format!("{n} ({})", variant.name));
+                    n_json.push(copy).value()
+                });
+                let copy = &mut n_json.materials[index];
+                apply_material_edit(copy, &material_override.edit)?;
+                for &(slot, texture) in &textures {
+                    set_slot(copy, slot, texture);
+                }
+            }
+        }
+        copies.push(copy_of);
+    }
+    Ok((n_json, n_blob.into_vec(), copies))
+}
+
+/// Add material variants to a document as `KHR_materials_variants`, ahead of the rebuild
+///
+/// Each variant gets a copy of every material it overrides, with the override's factors and
+/// maps; the maps go through the same resizing and encoding as the source textures. Every
+/// primitive drawn with an overridden material maps it to its copies, and primitives a variant
+/// leaves alone keep their material under it. Variants already in the file are kept, the new
+/// ones listed after them.
+pub(crate) fn add_material_variants(
+    o_blob: &[u8],
+    o_json: &Root,
+    variants: &[MaterialVariant],
+) -> Result<(Root, Vec<u8>), OptimizeError> {
+    let (mut o_json, o_blob, copies) = add_variant_materials(o_blob, o_json, variants)?;
+
+    let root = o_json.extensions.get_or_insert_with(Default::default);
+    let payload = root
+        .others
+        .entry(VARIANTS_EXTENSION.to_string())
+        .or_insert_with(|| json!({ "variants": [] }));
+    let Some(listed) = payload.get_mut("variants").and_then(Value::as_array_mut) else {
+        return Err(OptimizeError::Malformed(Malformation::VariantsList));
+    };
+    let first = listed.len();
+    listed.extend(variants.iter().map(|v| json!({ "name": v.name })));
+    if !o_json
+        .extensions_used
+        .iter()
+        .any(|e| e == VARIANTS_EXTENSION)
+    {
+        o_json.extensions_used.push(VARIANTS_EXTENSION.to_string());
+    }
+
+    for primitive in o_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+        let Some(material) = primitive.material.map(|m| m.value()) else {
+            continue;
+        };
+        let mappings: Vec<Value> = copies
+            .iter()
+            .enumerate()
+            .filter_map(|(v, copy_of)| {
+                let copy = copy_of.get(&material)?;
+                Some(json!({ "material": copy, "variants": [first + v] }))
+            })
+            .collect();
+        if mappings.is_empty() {
+            continue;
+        }
+        let extensions = primitive.extensions.get_or_insert_with(Default::default);
+        let payload = extensions
+            .others
+            .entry(VARIANTS_EXTENSION.to_string())
+            .or_insert_with(|| json!({ "mappings": [] }));
+        if let Some(existing) = payload.get_mut("mappings").and_then(Value::as_array_mut) {
+            existing.extend(mappings);
+        }
+    }
+
+    Ok((o_json, o_blob))
+}
+
+/// Optimize one GLB per material variant, named after it
+///
+/// Each output draws every overridden material with the variant's copy (see
+/// [`Optimizer::material_variants`](crate::Optimizer::material_variants)); geometry,
+/// animations and untouched materials are the same in all of them.
+pub fn material_variant_glbs<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+    variants: &[MaterialVariant],
+) -> Result<Vec<(String, Vec<u8>)>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let (o_json, o_blob, copies) = add_variant_materials(&o_blob, &o_json, variants)?;
+
+    let mut outputs = Vec::with_capacity(variants.len());
+    for (variant, copy_of) in variants.iter().zip(copies) {
+        let mut v_json = o_json.clone();
+        for primitive in v_json.meshes.iter_mut().flat_map(|m| &mut m.primitives) {
+            if let Some(copy) = primitive.material.and_then(|m| copy_of.get(&m.value())) {
+                primitive.material = Some(Index::new(*copy as u32));
+            }
+        }
+        let (mut n_json, n_blob, _) = optimize_document(&o_blob, &v_json, options, None, None)?;
+        retain_extensions(&mut n_json, &[])?;
+        outputs.push((variant.name.clone(), write_glb(&n_json, n_blob)?));
+    }
+    Ok(outputs)
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        opt::{encode_png, get_image_data},
+        optimizer::Optimizer,
+        stats::image_dimensions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn material_variants_are_encoded_and_mapped() {
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let mut blue = Vec::new();
+        let pixels = [0, 0, 255, 255].repeat(64 * 64);
+        encode_png(&pixels, 64, 64, image::ColorType::Rgba8, &mut blue).unwrap();
+        let variants = [
+            MaterialVariant {
+                name: "red".to_string(),
+                overrides: vec![MaterialOverride {
+                    edit: MaterialEdit {
+                        name: "material_0".to_string(),
+                        base_color_factor: Some([1.0, 0.0, 0.0, 1.0]),
+                        ..Default::default()
+                    },
+                    textures: Vec::new(),
+                }],
+            },
+            MaterialVariant {
+                name: "blue".to_string(),
+                overrides: vec![MaterialOverride {
+                    edit: MaterialEdit {
+                        name: "material_0".to_string(),
+                        ..Default::default()
+                    },
+                    textures: vec![(TextureType::BaseColor, blue)],
+                }],
+            },
+        ];
+        let options = OptimizeOptions {
+            texture_size: 16,
+            ..Default::default()
+        };
+
+        let out = Optimizer::new(&options)
+            .material_variants(&variants)
+            .run(&mut Cursor::new(&glb))
+            .unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(out.glb)).unwrap();
+        assert!(
+            n_json
+                .extensions_used
+                .iter()
+                .any(|e| e == VARIANTS_EXTENSION)
+        );
+        let names = &n_json.extensions.as_ref().unwrap().others[VARIANTS_EXTENSION]["variants"];
+        assert_eq!(
+            names,
+            &serde_json::json!([{ "name": "red" }, { "name": "blue" }])
+        );
+        let primitive = &n_json.meshes[0].primitives[0];
+        let mappings = &primitive.extensions.as_ref().unwrap().others[VARIANTS_EXTENSION];
+        let mapped = |v: usize| {
+            let m = mappings["mappings"][v]["material"].as_u64().unwrap() as usize;
+            assert_eq!(mappings["mappings"][v]["variants"], serde_json::json!([v]));
+            &n_json.materials[m]
+        };
+        let red = mapped(0);
+        assert_eq!(
+            red.pbr_metallic_roughness.base_color_factor.0,
+            [1.0, 0.0, 0.0, 1.0]
+        );
+        // The variant's map went through the same resizing as the source textures
+        let texture = mapped(1).pbr_metallic_roughness.base_color_texture.as_ref();
+        let data = get_image_data(&n_blob, &n_json, texture.unwrap().index).unwrap();
+        assert_eq!(image_dimensions(data), Some((16, 16)));
+        assert_eq!(n_json.materials.len(), 3);
+
+        let outputs = material_variant_glbs(&mut Cursor::new(&glb), &options, &variants).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].0, "red");
+        let (n_json, _) = read_glb(&mut Cursor::new(&outputs[0].1)).unwrap();
+        assert_eq!(n_json.materials.len(), 1);
+        let factor = n_json.materials[0]
+            .pbr_metallic_roughness
+            .base_color_factor
+            .0;
+        assert_eq!(factor, [1.0, 0.0, 0.0, 1.0]);
+        assert!(
+            n_json
+                .extensions_used
+                .iter()
+                .all(|e| e != VARIANTS_EXTENSION)
+        );
+
+        let unknown = [MaterialVariant {
+            name: "green".to_string(),
+            overrides: vec![MaterialOverride {
+                edit: MaterialEdit {
+                    name: "missing".to_string(),
+                    ..Default::default()
+                },
+                textures: Vec::new(),
+            }],
+        }];
+        let e = Optimizer::new(&options)
+            .material_variants(&unknown)
+            .run(&mut Cursor::new(&glb));
+        assert!(matches!(e, Err(OptimizeError::InvalidOption(_))));
+    }
+}
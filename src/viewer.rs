@@ -0,0 +1,156 @@
+use gltf::json::{Node, Scene};
+use serde_json::{Map, Value};
+
+use crate::{
+    error::OptimizeError,
+    glb::{from_extras, merge_extras},
+};
+
+/// `scene.extras` key holding [`ViewerHints`]
+pub const VIEWER_HINTS_KEY: &str = "viewer";
+
+/// Viewer settings written to every scene's `extras.viewer`, see
+/// [`OptimizeOptions::viewer_hints`](crate::OptimizeOptions::viewer_hints)
+///
+/// Set fields replace the same fields of a `viewer` object the source already has; the
+/// others are kept.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ViewerHints {
+    /// Name of the node whose camera the viewer starts from, written as `camera`
+    pub camera: Option<String>,
+    /// Exposure in EV stops, 0 for neutral, written as `exposure`
+    pub exposure: Option<f32>,
+    /// Name of the environment map the viewer lights the scene with, written as
+    /// `environment`
+    pub environment: Option<String>,
+    /// Linear RGBA clear color, written as `backgroundColor`
+    pub background_color: Option<[f32; 4]>,
+}
+
+impl ViewerHints {
+    /// Write the set fields to the `extras` of every scene
+    ///
+    /// Fails when `camera` names no node holding a camera, or a number is not finite.
+    pub(crate) fn apply(&self, scenes: &mut [Scene], nodes: &[Node]) -> Result<(), OptimizeError> {
+        if let Some(camera) = &self.camera
+            && !nodes
+                .iter()
+                .any(|n| n.camera.is_some() && n.name.as_ref() == Some(camera))
+        {
+            return Err(OptimizeError::InvalidOption(format!(
+                "viewer_hints.camera names no camera node: {camera}"
+            )));
+        }
+        let numbers = self
+            .exposure
+            .iter()
+            .chain(self.background_color.iter().flatten());
+        if numbers.clone().any(|n| !n.is_finite()) {
+            return Err(OptimizeError::InvalidOption(format!(
+                "viewer_hints has a number that is not finite: {self:?}"
+            )));
+        }
+
+        let mut hints = Map::new();
+        if let Some(camera) = &self.camera {
+            hints.insert("camera".to_string(), camera.as_str().into());
+        }
+        if let Some(exposure) = self.exposure {
+            hints.insert("exposure".to_string(), exposure.into());
+        }
+        if let Some(environment) = &self.environment {
+            hints.insert("environment".to_string(), environment.as_str().into());
+        }
+        if let Some(color) = self.background_color {
+            hints.insert("backgroundColor".to_string(), color.to_vec().into());
+        }
+        if hints.is_empty() {
+            return Ok(());
+        }
+        for scene in scenes {
+            let mut merged = match from_extras(&scene.extras)
+                .and_then(|extras| extras.get(VIEWER_HINTS_KEY).cloned())
+            {
+                Some(Value::Object(existing)) => existing,
+                _ => Map::new(),
+            };
+            merged.extend(hints.clone());
+            scene.extras = merge_extras(&scene.extras, VIEWER_HINTS_KEY, Value::Object(merged));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Root;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        opt::optimize_with,
+        options::OptimizeOptions,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn viewer_hints_are_merged_into_scene_extras() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (o_json, o_blob) = read_glb(&mut Cursor::new(&glb)).unwrap();
+        let mut doc = serde_json::to_value(&o_json).unwrap();
+        doc["cameras"] = serde_json::json!([
+            { "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } }
+        ]);
+        doc["nodes"][0]["name"] = "Hero".into();
+        doc["nodes"][0]["camera"] = 0.into();
+        doc["scenes"][0]["extras"] =
+            serde_json::json!({ "viewer": { "exposure": 1.0, "autoRotate": true } });
+        let o_json: Root = serde_json::from_value(doc).unwrap();
+        let glb = write_glb(&o_json, o_blob).unwrap();
+
+        let run = |viewer_hints| {
+            let options = OptimizeOptions {
+                viewer_hints,
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options)?;
+            Ok::<_, OptimizeError>(read_glb(&mut Cursor::new(out)).unwrap().0.scenes)
+        };
+        let scenes = run(ViewerHints::default()).unwrap();
+        let viewer = &from_extras(&scenes[0].extras).unwrap()["viewer"];
+        assert_eq!(viewer["exposure"], 1.0);
+
+        let scenes = run(ViewerHints {
+            camera: Some("Hero".to_string()),
+            exposure: Some(-0.5),
+            environment: Some("studio_small".to_string()),
+            background_color: Some([0.0, 0.0, 0.0, 1.0]),
+        })
+        .unwrap();
+        let viewer = &from_extras(&scenes[0].extras).unwrap()["viewer"];
+        assert_eq!(viewer["camera"], "Hero");
+        assert_eq!(viewer["exposure"], -0.5);
+        assert_eq!(viewer["environment"], "studio_small");
+        assert_eq!(
+            viewer["backgroundColor"],
+            serde_json::json!([0.0, 0.0, 0.0, 1.0])
+        );
+        assert_eq!(viewer["autoRotate"], true);
+
+        for hints in [
+            ViewerHints {
+                camera: Some("Missing".to_string()),
+                ..Default::default()
+            },
+            ViewerHints {
+                exposure: Some(f32::NAN),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(run(hints), Err(OptimizeError::InvalidOption(_))));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    mem,
+};
+
+use gltf::json::{
+    Index, Root,
+    buffer::{Stride, Target, View},
+    validation::Checked,
+};
+use serde_json::Value;
+
+use crate::{blob::BlobWriter, error::OptimizeError, glb::accessor_stride};
+
+/// How copied mesh accessors are spread over buffer views, see
+/// [`OptimizeOptions::accessor_views`](crate::OptimizeOptions::accessor_views)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AccessorViews {
+    /// One view per copied accessor, as in the source
+    #[default]
+    PerAccessor,
+    /// The accessors of a primitive share views
+    PerPrimitive,
+    /// The accessors of every primitive of a mesh share views
+    PerMesh,
+}
+
+/// Index data or vertex data; the two never share a view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Role {
+    Indices,
+    /// Vertex attribute read `stride` bytes apart
+    Vertices(usize),
+}
+
+/// Views merged into one: the group they belong to and how they are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ShareKey {
+    mesh: usize,
+    primitive: Option<usize>,
+    role: Role,
+    target: Option<Target>,
+}
+
+impl Hash for ShareKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mesh.hash(state);
+        self.primitive.hash(state);
+        self.role.hash(state);
+        // `Target` itself does not implement `Hash`
+        self.target.as_ref().map(mem::discriminant).hash(state);
+    }
+}
+
+/// Count `"bufferView"` references anywhere in a document
+pub(crate) fn count_view_refs(value: &Value, counts: &mut [usize]) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                if key == "bufferView"
+                    && let Some(slot) = value.as_u64().and_then(|v| counts.get_mut(v as usize))
+                {
+                    *slot += 1;
+                }
+                count_view_refs(value, counts);
+            }
+        }
+        Value::Array(values) => values.iter().for_each(|v| count_view_refs(v, counts)),
+        _ => {}
+    }
+}
+
+/// Point every `"bufferView"` reference at the view's new index
+pub(crate) fn renumber_view_refs(value: &mut Value, moved: &[usize]) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if key == "bufferView"
+                    && let Some(&new) = value.as_u64().and_then(|v| moved.get(v as usize))
+                {
+                    *value = new.into();
+                }
+                renumber_view_refs(value, moved);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| renumber_view_refs(v, moved)),
+        _ => {}
+    }
+}
+
+/// Share key of every accessor used by exactly one mesh group, `None` for conflicting uses
+fn accessor_groups(n_json: &Root, grouping: AccessorViews) -> HashMap<usize, Option<ShareKey>> {
+    let mut groups: HashMap<usize, Option<ShareKey>> = HashMap::new();
+    let mut assign = |acc: usize, mesh: usize, primitive: Option<usize>, indices: bool| {
+        let Some(accessor) = n_json.accessors.get(acc) else {
+            return;
+        };
+        let Some(view) = accessor
+            .buffer_view
+            .and_then(|v| n_json.buffer_views.get(v.value()))
+        else {
+            return;
+        };
+        let role = match indices {
+            true => Role::Indices,
+            false => match accessor_stride(n_json, Index::new(acc as u32)) {
+                Some(stride) => Role::Vertices(stride),
+                None => {
+                    groups.insert(acc, None);
+                    return;
+                }
+            },
+        };
+        let key = ShareKey {
+            mesh,
+            primitive,
+            role,
+            target: view.target.and_then(|t| match t {
+                Checked::Valid(t) => Some(t),
+                Checked::Invalid => None,
+            }),
+        };
+        groups
+            .entry(acc)
+            .and_modify(|k| {
+                if *k != Some(key) {
+                    *k = None;
+                }
+            })
+            .or_insert(Some(key));
+    };
+
+    for (m, mesh) in n_json.meshes.iter().enumerate() {
+        for (p, primitive) in mesh.primitives.iter().enumerate() {
+            let group = (grouping == AccessorViews::PerPrimitive).then_some(p);
+            if let Some(indices) = primitive.indices {
+                assign(indices.value(), m, group, true);
+            }
+            for acc in primitive.attributes.values() {
+                assign(acc.value(), m, group, false);
+            }
+            for target in primitive.targets.iter().flatten() {
+                for acc in [target.positions, target.normals, target.tangents]
+                    .into_iter()
+                    .flatten()
+                {
+                    assign(acc.value(), m, group, false);
+                }
+            }
+        }
+    }
+    groups
+}
+
+/// Merge the buffer views of mesh accessors per primitive or per mesh
+///
+/// Only views read by nothing but accessors of a single group are merged, and index data,
+/// each vertex stride and each `target` get views of their own, so every merged vertex view
+/// can carry the `byteStride` glTF requires of shared views. Returns the re-laid-out binary
+/// chunk, or `None` when nothing could be merged.
+pub(crate) fn share_accessor_views(
+    n_json: &mut Root,
+    n_blob: &[u8],
+    grouping: AccessorViews,
+) -> Result<Option<Vec<u8>>, OptimizeError> {
+    if grouping == AccessorViews::PerAccessor {
+        return Ok(None);
+    }
+
+    let view_count = n_json.buffer_views.len();
+    let mut refs = vec![0; view_count];
+    count_view_refs(&serde_json::to_value(&*n_json)?, &mut refs);
+
+    // Share key of each view, when every reference to it comes from accessors of one group
+    let groups = accessor_groups(n_json, grouping);
+    let mut direct = vec![0; view_count];
+    let mut view_keys: Vec<Option<ShareKey>> = vec![None; view_count];
+    let mut conflicted = vec![false; view_count];
+    for (i, acc) in n_json.accessors.iter().enumerate() {
+        let Some(view) = acc.buffer_view.map(|v| v.value()) else {
+            continue;
+        };
+        let Some(slot) = direct.get_mut(view) else {
+            continue;
+        };
+        *slot += 1;
+        match groups.get(&i).copied().flatten() {
+            Some(key) if view_keys[view].is_none_or(|k| k == key) => view_keys[view] = Some(key),
+            _ => conflicted[view] = true,
+        }
+    }
+
+    let mut members: HashMap<ShareKey, Vec<usize>> = HashMap::new();
+    for (i, view) in n_json.buffer_views.iter().enumerate() {
+        let Some(key) = view_keys[i] else {
+            continue;
+        };
+        let layout_ok = match key.role {
+            Role::Indices => view.byte_stride.is_none(),
+            Role::Vertices(stride) => stride.is_multiple_of(4) && (4..=252).contains(&stride),
+        };
+        if !conflicted[i] && refs[i] == direct[i] && view.buffer.value() == 0 && layout_ok {
+            members.entry(key).or_default().push(i);
+        }
+    }
+    members.retain(|_, views| views.len() > 1);
+    if members.is_empty() {
+        return Ok(None);
+    }
+    let mut merged_into: HashMap<usize, ShareKey> = HashMap::new();
+    for (key, views) in &members {
+        for &view in views {
+            merged_into.insert(view, *key);
+        }
+    }
+
+    let view_bytes = |view: &View| {
+        let offset = view.byte_offset.map(|o| o.0 as usize).unwrap_or(0);
+        n_blob
+            .get(offset..offset.saturating_add(view.byte_length.0 as usize))
+            .unwrap_or_default()
+    };
+
+    // Lay the binary chunk out again, a merged view taking the place of its first member
+    let mut blob = BlobWriter::new();
+    let mut views = Vec::new();
+    let mut moved = vec![0; view_count];
+    let mut shifts = vec![0; view_count];
+    for (i, view) in n_json.buffer_views.iter().enumerate() {
+        let Some(key) = merged_into.get(&i) else {
+            let mut view = view.clone();
+            let data = view_bytes(&view);
+            let offset = blob.append(data);
+            view.byte_offset = (offset > 0).then(|| offset.into());
+            moved[i] = views.len();
+            views.push(view);
+            continue;
+        };
+        let group = &members[key];
+        if group[0] != i {
+            continue;
+        }
+
+        // Members with the same bytes keep sharing them, as they did in the blob
+        let mut data = Vec::new();
+        let mut written: HashMap<&[u8], usize> = HashMap::new();
+        for &member in group {
+            let bytes = view_bytes(&n_json.buffer_views[member]);
+            moved[member] = views.len();
+            shifts[member] = *written.entry(bytes).or_insert_with(|| {
+                data.resize(data.len().next_multiple_of(4), 0);
+                let shift = data.len();
+                data.extend_from_slice(bytes);
+                shift
+            });
+        }
+        let offset = blob.append(&data);
+        views.push(View {
+            buffer: Index::new(0),
+            byte_length: data.len().into(),
+            byte_offset: (offset > 0).then(|| offset.into()),
+            byte_stride: match key.role {
+                Role::Vertices(stride) => Some(Stride(stride)),
+                Role::Indices => None,
+            },
+            name: None,
+            target: key.target.map(Checked::Valid),
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+
+    for acc in &mut n_json.accessors {
+        if let Some(view) = acc.buffer_view
+            && let Some(&shift) = shifts.get(view.value())
+            && shift > 0
+        {
+            let offset = acc.byte_offset.map_or(0, |o| o.0 as usize) + shift;
+            acc.byte_offset = Some(offset.into());
+        }
+    }
+    n_json.buffer_views = views;
+
+    // Accessors, sparse data, images and extension payloads all follow the new numbering
+    let mut doc = serde_json::to_value(&*n_json)?;
+    renumber_view_refs(&mut doc, &moved);
+    *n_json = serde_json::from_value(doc)?;
+
+    Ok(Some(blob.into_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{convert::read_accessor, testing::view_bytes};
+
+    #[test]
+    fn accessor_views_are_shared_per_group() {
+        // Two primitives, each with positions, normals, UVs and u16 indices, plus an image
+        let mut blob = Vec::new();
+        let mut views = Vec::new();
+        let mut push = |bytes: Vec<u8>| {
+            views.push(serde_json::json!({
+                "buffer": 0, "byteOffset": blob.len(), "byteLength": bytes.len(),
+            }));
+            blob.extend_from_slice(&bytes);
+            blob.resize(blob.len().next_multiple_of(4), 0);
+        };
+        let floats = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        for p in 0..2 {
+            let p = p as f32;
+            push(floats(&[p, 0.0, 0.0, 1.0, p, 0.0, 0.0, 1.0, p]));
+            push(floats(&[0.0, 0.0, 1.0].repeat(3)));
+            push(floats(&[0.0, 0.0, 1.0, 0.0, p, 1.0]));
+            push([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()).collect());
+        }
+        push(b"not decoded".to_vec());
+
+        let vec3 = |view: usize| {
+            serde_json::json!({ "bufferView": view, "componentType": 5126, "count": 3,
+                "type": "VEC3" })
+        };
+        let mut accessors = Vec::new();
+        let mut primitives = Vec::new();
+        for p in 0..2 {
+            let base = accessors.len();
+            accessors.push(vec3(p * 4));
+            accessors.push(vec3(p * 4 + 1));
+            accessors.push(serde_json::json!({ "bufferView": p * 4 + 2,
+                "componentType": 5126, "count": 3, "type": "VEC2" }));
+            accessors.push(serde_json::json!({ "bufferView": p * 4 + 3,
+                "componentType": 5123, "count": 3, "type": "SCALAR" }));
+            primitives.push(serde_json::json!({
+                "attributes": { "POSITION": base, "NORMAL": base + 1, "TEXCOORD_0": base + 2 },
+                "indices": base + 3,
+            }));
+        }
+        let json: Root = serde_json::from_value(serde_json::json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": blob.len() }],
+            "bufferViews": views,
+            "accessors": accessors,
+            "images": [{ "bufferView": 8, "mimeType": "image/png" }],
+            "meshes": [{ "primitives": primitives }],
+        }))
+        .unwrap();
+
+        for (grouping, view_count) in [
+            (AccessorViews::PerPrimitive, 7),
+            (AccessorViews::PerMesh, 4),
+        ] {
+            let mut n_json = json.clone();
+            let n_blob = share_accessor_views(&mut n_json, &blob, grouping)
+                .unwrap()
+                .unwrap();
+            assert_eq!(n_json.buffer_views.len(), view_count);
+
+            // Every accessor reads what it did before; shared vertex views carry a stride
+            for i in 0..json.accessors.len() {
+                let idx = Index::new(i as u32);
+                let read = |blob: &[u8], json: &Root| {
+                    let scalars = read_accessor::<1>(blob, json, idx).map(|v| v.concat());
+                    let vec2 = read_accessor::<2>(blob, json, idx).map(|v| v.concat());
+                    let vec3 = read_accessor::<3>(blob, json, idx).map(|v| v.concat());
+                    scalars.or(vec2).or(vec3).unwrap()
+                };
+                assert_eq!(read(&blob, &json), read(&n_blob, &n_json));
+            }
+            let image_view = n_json.images[0].buffer_view.unwrap().value();
+            assert_eq!(
+                view_bytes(&n_json, &n_blob, image_view),
+                b"not decoded".as_slice()
+            );
+            let position_view = n_json.accessors[0].buffer_view.unwrap().value();
+            assert_eq!(
+                n_json.buffer_views[position_view].byte_stride,
+                Some(Stride(12))
+            );
+        }
+        let mut n_json = json.clone();
+        assert!(
+            share_accessor_views(&mut n_json, &blob, AccessorViews::PerAccessor)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    error::Error,
+    io::{Read, Seek},
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    error::OptimizeError,
+    extension::{ExtensionContext, ExtensionHandler, ExtensionOwner, ExtensionRegistry},
+    optimizer::Optimizer,
+    options::OptimizeOptions,
+};
+
+/// VRM extensions carried through by [`vrm_extensions`]
+///
+/// Node and mesh references need no fixing up, since the rebuild keeps those indices.
+const VRM_EXTENSIONS: [&str; 6] = [
+    "VRM",
+    "VRMC_vrm",
+    "VRMC_materials_mtoon",
+    "VRMC_springBone",
+    "VRMC_springBone_extended_collider",
+    "VRMC_node_constraint",
+];
+
+/// Handler for one VRM 0.x or 1.0 extension
+struct VrmExtension(&'static str);
+
+impl ExtensionHandler for VrmExtension {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn rebuild(
+        &self,
+        owner: ExtensionOwner,
+        payload: &mut Value,
+        ctx: &mut ExtensionContext,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match (self.0, owner) {
+            ("VRM", ExtensionOwner::Root) => rebuild_vrm0(payload, ctx)?,
+            ("VRMC_vrm", ExtensionOwner::Root) => rebuild_vrm1(payload, ctx)?,
+            ("VRMC_materials_mtoon", _) => ctx.copy_texture_infos(payload)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Point a texture index at its copy; VRM 0.x uses `-1` for no texture
+fn copy_texture_index(
+    value: Option<&mut Value>,
+    ctx: &mut ExtensionContext,
+) -> Result<(), OptimizeError> {
+    if let Some(value) = value
+        && let Some(index) = value.as_u64()
+    {
+        *value = ctx.copy_texture(index as usize)?.into();
+    }
+    Ok(())
+}
+
+/// Texture in the slot of output `material` where source material `source` had `texture`
+///
+/// MToon properties mostly name the material's own textures, which the rebuild has already
+/// optimized; reusing those avoids storing the source image a second time.
+fn slot_texture(
+    ctx: &ExtensionContext,
+    source: usize,
+    material: usize,
+    texture: usize,
+) -> Option<usize> {
+    let slots = |m: &gltf::json::Material| {
+        let pbr = &m.pbr_metallic_roughness;
+        [
+            pbr.base_color_texture.as_ref().map(|t| t.index.value()),
+            pbr.metallic_roughness_texture
+                .as_ref()
+                .map(|t| t.index.value()),
+            m.normal_texture.as_ref().map(|t| t.index.value()),
+            m.occlusion_texture.as_ref().map(|t| t.index.value()),
+            m.emissive_texture.as_ref().map(|t| t.index.value()),
+        ]
+    };
+    let o_slots = slots(ctx.source().materials.get(source)?);
+    let n_slots = slots(ctx.output().materials.get(material)?);
+    let slot = o_slots.iter().position(|&t| t == Some(texture))?;
+    n_slots[slot]
+}
+
+/// VRM 0.x: the thumbnail, and `materialProperties`, which must line up with the materials
+fn rebuild_vrm0(payload: &mut Value, ctx: &mut ExtensionContext) -> Result<(), OptimizeError> {
+    copy_texture_index(payload.pointer_mut("/meta/texture"), ctx)?;
+
+    let Some(properties) = payload
+        .get_mut("materialProperties")
+        .and_then(Value::as_array_mut)
+    else {
+        return Ok(());
+    };
+    let mut rebuilt: Vec<Option<Value>> = vec![None; ctx.output().materials.len()];
+    for (source, property) in properties.drain(..).enumerate() {
+        for material in ctx.materials(source).to_vec() {
+            let mut property = property.clone();
+            if let Some(textures) = property
+                .get_mut("textureProperties")
+                .and_then(Value::as_object_mut)
+            {
+                for texture in textures.values_mut() {
+                    match texture
+                        .as_u64()
+                        .and_then(|t| slot_texture(ctx, source, material, t as usize))
+                    {
+                        Some(output) => *texture = output.into(),
+                        None => copy_texture_index(Some(texture), ctx)?,
+                    }
+                }
+            }
+            if let Some(slot) = rebuilt.get_mut(material) {
+                *slot = Some(property);
+            }
+        }
+    }
+
+    // Materials without properties render with their glTF definition
+    *properties = rebuilt
+        .into_iter()
+        .enumerate()
+        .map(|(i, property)| {
+            property.unwrap_or_else(|| {
+                let name = ctx.output().materials[i].name.clone();
+                json!({ "name": name, "shader": "VRM_USE_GLTFSHADER" })
+            })
+        })
+        .collect();
+    Ok(())
+}
+
+/// VRM 1.0: the thumbnail image, and expression binds, one per output copy of their material
+fn rebuild_vrm1(payload: &mut Value, ctx: &mut ExtensionContext) -> Result<(), OptimizeError> {
+    if let Some(thumbnail) = payload.pointer_mut("/meta/thumbnailImage")
+        && let Some(image) = thumbnail.as_u64()
+    {
+        *thumbnail = ctx.copy_image(image as usize)?.into();
+    }
+
+    for group in ["preset", "custom"] {
+        let Some(expressions) = payload
+            .pointer_mut(&format!("/expressions/{group}"))
+            .and_then(Value::as_object_mut)
+        else {
+            continue;
+        };
+        for expression in expressions.values_mut() {
+            for key in ["materialColorBinds", "textureTransformBinds"] {
+                let Some(binds) = expression.get_mut(key).and_then(Value::as_array_mut) else {
+                    continue;
+                };
+                *binds = binds
+                    .drain(..)
+                    .flat_map(|bind| {
+                        let source = bind["material"].as_u64().map_or(usize::MAX, |m| m as usize);
+                        ctx.materials(source)
+                            .iter()
+                            .map(move |&material| {
+                                let mut bind = bind.clone();
+                                bind["material"] = material.into();
+                                bind
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Registry carrying the VRM 0.x `VRM` and VRM 1.0 `VRMC_*` extensions through the rebuild
+///
+/// Register further handlers on it for other vendor extensions the avatar uses.
+pub fn vrm_extensions() -> ExtensionRegistry {
+    let mut registry = ExtensionRegistry::new();
+    for name in VRM_EXTENSIONS {
+        registry.register(VrmExtension(name));
+    }
+    registry
+}
+
+/// Optimize a VRM avatar, keeping its humanoid, expression, spring bone and MToon data
+///
+/// Settings that would renumber morph targets, move meshes to other nodes or shift the
+/// model (`max_morph_targets`, `bake_morph_targets`, `quantize_attributes`, `center_pivot`)
+/// are turned off, as VRM data refers to those.
+pub fn optimize_vrm<R: Read + Seek>(
+    reader: &mut R,
+    options: &OptimizeOptions,
+) -> Result<Vec<u8>, OptimizeError> {
+    let options = OptimizeOptions {
+        max_morph_targets: None,
+        bake_morph_targets: false,
+        quantize_attributes: false,
+        center_pivot: false,
+        ..options.clone()
+    };
+    let registry = vrm_extensions();
+    Ok(Optimizer::new(&options)
+        .extensions(&registry)
+        .run(reader)?
+        .glb)
+}
+
+#[cfg(all(test, feature = "png", feature = "jpeg"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        glb::{read_glb, write_glb},
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn vrm_material_properties_follow_materials() {
+        let glb = SyntheticGlb {
+            primitives_per_mesh: 2,
+            textures: 2,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        let property = |i: usize| {
+            serde_json::json!({
+                "name": format!("material_{i}"),
+                "shader": "VRM/MToon",
+                "textureProperties": { "_MainTex": i, "_ShadeTexture": i },
+            })
+        };
+        json.extensions
+            .get_or_insert_with(Default::default)
+            .others
+            .insert(
+                "VRM".to_string(),
+                serde_json::json!({
+                    "meta": { "texture": -1 },
+                    "humanoid": { "humanBones": [{ "bone": "hips", "node": 0 }] },
+                    "materialProperties": [property(0), property(1)],
+                }),
+            );
+        json.extensions_used.push("VRM".to_string());
+        let vrm = write_glb(&json, blob).unwrap();
+
+        let options = OptimizeOptions {
+            center_pivot: true,
+            ..Default::default()
+        };
+        let out = optimize_vrm(&mut Cursor::new(vrm), &options).unwrap();
+        let (n_json, _) = read_glb(&mut Cursor::new(out)).unwrap();
+        assert!(n_json.extensions_used.contains(&"VRM".to_string()));
+        let vrm = &n_json.extensions.as_ref().unwrap().others["VRM"];
+        assert_eq!(vrm["humanoid"]["humanBones"][0]["node"], 0);
+        assert_eq!(vrm["meta"]["texture"], -1);
+
+        // Each property names its material's optimized texture; no source image is copied
+        let properties = vrm["materialProperties"].as_array().unwrap();
+        assert_eq!(properties.len(), n_json.materials.len());
+        for (property, material) in properties.iter().zip(&n_json.materials) {
+            let base_color = material
+                .pbr_metallic_roughness
+                .base_color_texture
+                .as_ref()
+                .unwrap()
+                .index
+                .value();
+            assert_eq!(property["name"].as_str(), material.name.as_deref());
+            assert_eq!(property["textureProperties"]["_MainTex"], base_color);
+            assert_eq!(property["textureProperties"]["_ShadeTexture"], base_color);
+        }
+        assert_eq!(n_json.images.len(), 2);
+    }
+}
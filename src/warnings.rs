@@ -0,0 +1,384 @@
+use std::{
+    fmt,
+    io::{Read, Seek},
+};
+
+use gltf::json::{Root, mesh::Semantic, validation::Checked};
+
+use crate::{
+    error::OptimizeError,
+    glb::read_glb,
+    opt::TextureType,
+    slot_check::slot_mismatches,
+    stats::{compute_gpu_stats, scene_surface_area},
+    texcoords::material_tex_coords,
+};
+
+/// Materials from which a file is reported as having many
+const MANY_MATERIALS: usize = 100;
+
+/// Vertices from which an unindexed primitive is reported, past what 16-bit indices address
+const GIANT_UNINDEXED_VERTICES: u64 = 65_536;
+
+/// Width or height from which a texture is reported when it covers little of the scene
+const LARGE_TEXTURE_SIZE: u32 = 8192;
+
+/// Share of the scene's surface below which a large texture is reported
+const SMALL_SURFACE_SHARE: f64 = 0.01;
+
+/// Unusual content found in a file, with a hint at the settings that handle it better
+///
+/// These are not errors: such files optimize fine, but usually not as well as they could.
+/// The `Display` text names what was found and what to try.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SceneWarning {
+    /// 100 or more materials, each costing at least one draw call
+    ManyMaterials { count: usize },
+    /// A primitive of 65,536 or more vertices without an index buffer
+    UnindexedMesh {
+        mesh: usize,
+        primitive: usize,
+        vertices: u64,
+    },
+    /// An 8K or larger texture sampled by less than 1% of the default scene's surface
+    OversizedTexture {
+        texture: usize,
+        image: usize,
+        width: u32,
+        height: u32,
+        /// Part of the default scene's surface area the texture covers, from 0 to 1
+        surface_share: f64,
+    },
+    /// No default scene is set, so viewers pick one on their own or show nothing
+    NoDefaultScene { scenes: usize },
+    /// Pixels that look like another slot's: a normal map in a base color or emissive slot, or
+    /// a color image in a normal slot, which then gets decoded in the wrong color space
+    MisslottedTexture {
+        material: usize,
+        texture: usize,
+        slot: TextureType,
+        looks_like: TextureType,
+    },
+    /// A primitive drawn with a material sampling a `TEXCOORD_n` set the primitive lacks,
+    /// which viewers render with missing or garbage texturing
+    MissingTexCoord {
+        mesh: usize,
+        primitive: usize,
+        material: usize,
+        tex_coord: u32,
+    },
+    /// A material whose texture covers fewer texels per meter of surface than asked for, see
+    /// [`scene_warnings_with_density`]
+    LowTexelDensity {
+        material: usize,
+        texture: usize,
+        texels_per_meter: f64,
+        min_texels_per_meter: f64,
+    },
+}
+
+impl SceneWarning {
+    /// Every name [`kind`](SceneWarning::kind) returns
+    pub const KINDS: [&'static str; 7] = [
+        "many-materials",
+        "unindexed-mesh",
+        "oversized-texture",
+        "no-default-scene",
+        "misslotted-texture",
+        "missing-tex-coord",
+        "low-texel-density",
+    ];
+
+    /// Stable kebab-case name of the variant, e.g. `many-materials`, for filtering warnings
+    /// by kind in scripts and CI rules
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SceneWarning::ManyMaterials { .. } => "many-materials",
+            SceneWarning::UnindexedMesh { .. } => "unindexed-mesh",
+            SceneWarning::OversizedTexture { .. } => "oversized-texture",
+            SceneWarning::NoDefaultScene { .. } => "no-default-scene",
+            SceneWarning::MisslottedTexture { .. } => "misslotted-texture",
+            SceneWarning::MissingTexCoord { .. } => "missing-tex-coord",
+            SceneWarning::LowTexelDensity { .. } => "low-texel-density",
+        }
+    }
+}
+
+impl fmt::Display for SceneWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneWarning::ManyMaterials { count } => write!(
+                f,
+                "{count} materials, each at least one draw call; merge the ones that differ \
+                 only in factors and atlas their textures"
+            ),
+            SceneWarning::UnindexedMesh {
+                mesh,
+                primitive,
+                vertices,
+            } => write!(
+                f,
+                "mesh {mesh} primitive {primitive} has {vertices} vertices and no indices; \
+                 weld it in the authoring tool, since `simplify` and `reorder_vertices` skip it"
+            ),
+            SceneWarning::OversizedTexture {
+                texture,
+                width,
+                height,
+                surface_share,
+                ..
+            } => write!(
+                f,
+                "texture {texture} is {width}x{height} but covers {:.2}% of the scene; cap it \
+                 with `slot_texture_sizes` or `gpu_texture_budget`",
+                surface_share * 100.0
+            ),
+            SceneWarning::NoDefaultScene { scenes: 0 } => write!(
+                f,
+                "no scenes; viewers fall back to every parentless node, if at all"
+            ),
+            SceneWarning::NoDefaultScene { scenes } => write!(
+                f,
+                "{scenes} scenes but no default; set `scene` so viewers and `gpu_stats` agree \
+                 on what is shown"
+            ),
+            SceneWarning::MisslottedTexture {
+                material,
+                texture,
+                slot,
+                looks_like,
+            } => write!(
+                f,
+                "material {material} uses texture {texture} as {} but it looks like a {} map; \
+                 fix the slot in the source, or set `fix_misslotted_textures` to encode it as \
+                 what it looks like",
+                slot.label(),
+                looks_like.label()
+            ),
+            SceneWarning::MissingTexCoord {
+                mesh,
+                primitive,
+                material,
+                tex_coord,
+            } => write!(
+                f,
+                "mesh {mesh} primitive {primitive} has no TEXCOORD_{tex_coord} but material \
+                 {material} samples it; export the UV set or point the texture at one the mesh has"
+            ),
+            SceneWarning::LowTexelDensity {
+                material,
+                texture,
+                texels_per_meter,
+                min_texels_per_meter,
+            } => write!(
+                f,
+                "material {material} draws texture {texture} at {texels_per_meter:.0} texels per \
+                 meter, under {min_texels_per_meter:.0}; raise `texture_size` or its \
+                 `slot_texture_sizes` entry"
+            ),
+        }
+    }
+}
+
+/// Look for unusual content in a GLB
+pub fn scene_warnings<R: Read + Seek>(reader: &mut R) -> Result<Vec<SceneWarning>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    Ok(find_scene_warnings(&o_blob, &o_json))
+}
+
+/// Look for unusual content in a GLB like [`scene_warnings`], and for materials drawn at
+/// fewer than `min_texels_per_meter` texels per meter of surface
+///
+/// Run it on optimized output to catch textures downscaled too far before they ship. Density
+/// is measured as in [`GpuStats::material_density`](crate::GpuStats::material_density),
+/// on textures at their stored size and taking glTF units as meters.
+pub fn scene_warnings_with_density<R: Read + Seek>(
+    reader: &mut R,
+    min_texels_per_meter: f64,
+) -> Result<Vec<SceneWarning>, OptimizeError> {
+    let (o_json, o_blob) = read_glb(reader)?;
+    let mut warnings = find_scene_warnings(&o_blob, &o_json);
+    let stats = compute_gpu_stats(&o_blob, &o_json);
+    warnings.extend(
+        stats
+            .material_density
+            .iter()
+            .filter(|d| d.texels_per_meter < min_texels_per_meter)
+            .map(|d| SceneWarning::LowTexelDensity {
+                material: d.material,
+                texture: d.texture,
+                texels_per_meter: d.texels_per_meter,
+                min_texels_per_meter,
+            }),
+    );
+    Ok(warnings)
+}
+
+pub(crate) fn find_scene_warnings(o_blob: &[u8], o_json: &Root) -> Vec<SceneWarning> {
+    let mut warnings = Vec::new();
+
+    if o_json.materials.len() >= MANY_MATERIALS {
+        warnings.push(SceneWarning::ManyMaterials {
+            count: o_json.materials.len(),
+        });
+    }
+
+    for (mesh, m) in o_json.meshes.iter().enumerate() {
+        for (primitive, p) in m.primitives.iter().enumerate() {
+            let vertices = p
+                .attributes
+                .values()
+                .next()
+                .and_then(|idx| o_json.accessors.get(idx.value()))
+                .map_or(0, |acc| acc.count.0);
+            if p.indices.is_none() && vertices >= GIANT_UNINDEXED_VERTICES {
+                warnings.push(SceneWarning::UnindexedMesh {
+                    mesh,
+                    primitive,
+                    vertices,
+                });
+            }
+            let Some(material) = p.material.map(|m| m.value()) else {
+                continue;
+            };
+            let sets = o_json
+                .materials
+                .get(material)
+                .map(material_tex_coords)
+                .unwrap_or_default();
+            for tex_coord in sets {
+                let semantic = Checked::Valid(Semantic::TexCoords(tex_coord));
+                if !p.attributes.contains_key(&semantic) {
+                    warnings.push(SceneWarning::MissingTexCoord {
+                        mesh,
+                        primitive,
+                        material,
+                        tex_coord,
+                    });
+                }
+            }
+        }
+    }
+
+    let stats = compute_gpu_stats(o_blob, o_json);
+    let total_area = scene_surface_area(o_blob, o_json);
+    if total_area > 0.0 {
+        for usage in &stats.texture_usage {
+            let Some(memory) = stats.textures.iter().find(|t| t.image == usage.image) else {
+                continue;
+            };
+            let surface_share = usage.surface_area / total_area;
+            if memory.width.max(memory.height) >= LARGE_TEXTURE_SIZE
+                && surface_share < SMALL_SURFACE_SHARE
+            {
+                warnings.push(SceneWarning::OversizedTexture {
+                    texture: usage.texture,
+                    image: usage.image,
+                    width: memory.width,
+                    height: memory.height,
+                    surface_share,
+                });
+            }
+        }
+    }
+
+    if o_json.scene.is_none() {
+        warnings.push(SceneWarning::NoDefaultScene {
+            scenes: o_json.scenes.len(),
+        });
+    }
+
+    warnings.extend(slot_mismatches(o_blob, o_json).into_iter().map(|m| {
+        SceneWarning::MisslottedTexture {
+            material: m.material,
+            texture: m.texture,
+            slot: m.slot,
+            looks_like: m.looks_like,
+        }
+    }));
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{glb::read_glb, testing::SyntheticGlb};
+
+    #[test]
+    fn kinds_are_listed() {
+        let warnings = [
+            SceneWarning::ManyMaterials { count: 120 },
+            SceneWarning::NoDefaultScene { scenes: 2 },
+        ];
+        for warning in &warnings {
+            assert!(SceneWarning::KINDS.contains(&warning.kind()));
+        }
+        assert_eq!(warnings[0].kind(), "many-materials");
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn low_texel_density_is_reported() {
+        use std::io::Cursor;
+
+        use crate::{opt::optimize_with, options::OptimizeOptions, testing::SyntheticGlb};
+
+        let glb = SyntheticGlb {
+            meshes: 2,
+            textures: 1,
+            texture_size: 128,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let options = OptimizeOptions {
+            texture_size: 32,
+            ..Default::default()
+        };
+        let out = optimize_with(&mut Cursor::new(glb), &options).unwrap();
+        let stats = crate::stats::gpu_stats(&mut Cursor::new(&out)).unwrap();
+        let density = &stats.material_density;
+        assert_eq!(density.len(), 1);
+        assert_eq!((density[0].material, density[0].texture), (0, 0));
+        assert!((density[0].surface_area - 2.0).abs() < 1e-4);
+        // Each unit quad maps the whole 32x32 texture
+        assert!((density[0].texels_per_meter - 32.0).abs() < 1e-3);
+
+        let low = |warnings: Vec<SceneWarning>| {
+            warnings
+                .iter()
+                .any(|w| matches!(w, SceneWarning::LowTexelDensity { material: 0, .. }))
+        };
+        assert!(low(scene_warnings_with_density(
+            &mut Cursor::new(&out),
+            64.0
+        )
+        .unwrap()));
+        assert!(!low(scene_warnings_with_density(
+            &mut Cursor::new(&out),
+            16.0
+        )
+        .unwrap()));
+    }
+
+    #[test]
+    fn unusual_scenes_are_warned_about() {
+        let glb = SyntheticGlb::default().build().unwrap();
+        let (mut json, blob) = read_glb(&mut Cursor::new(glb)).unwrap();
+        assert_eq!(find_scene_warnings(&blob, &json), []);
+
+        json.scene = None;
+        json.materials = vec![Default::default(); 120];
+        assert_eq!(
+            find_scene_warnings(&blob, &json),
+            [
+                SceneWarning::ManyMaterials { count: 120 },
+                SceneWarning::NoDefaultScene { scenes: 1 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use image::{RgbaImage, imageops::FilterType};
+
+use crate::error::OptimizeError;
+
+/// Where a [`Watermark`] goes on a texture
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WatermarkPlacement {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+    /// Repeated edge to edge from the top left corner
+    Tiled,
+}
+
+/// Image alpha-composited onto base color textures as they are re-encoded, see
+/// [`OptimizeOptions::watermark`](crate::OptimizeOptions::watermark)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    /// Encoded image (PNG, JPEG, ...) whose alpha channel marks what gets stamped
+    pub image: Vec<u8>,
+    pub placement: WatermarkPlacement,
+    /// Width of the stamp as a share of the texture width, keeping its aspect ratio
+    pub scale: f32,
+    /// Factor on the stamp's alpha, in `[0, 1]`
+    pub opacity: f32,
+}
+
+impl Watermark {
+    /// Watermark `image` in the bottom right corner, a quarter of the texture wide at half
+    /// opacity
+    pub fn new(image: Vec<u8>) -> Self {
+        Watermark {
+            image,
+            placement: WatermarkPlacement::default(),
+            scale: 0.25,
+            opacity: 0.5,
+        }
+    }
+}
+
+/// Composite `watermark` over the color channels of `rgba`, keeping the texture's own alpha
+///
+/// The stamp is resized for `rgba` each time, so textures of every size get the same share
+/// of it. Blending happens on the stored sRGB values, like image editors do.
+pub(crate) fn stamp_watermark(
+    rgba: &mut RgbaImage,
+    watermark: &Watermark,
+) -> Result<(), OptimizeError> {
+    let valid = watermark.scale.is_finite()
+        && watermark.scale > 0.0
+        && (0.0..=1.0).contains(&watermark.opacity);
+    if !valid {
+        return Err(OptimizeError::InvalidOption(format!(
+            "watermark scale {} or opacity {} is out of range",
+            watermark.scale, watermark.opacity
+        )));
+    }
+    let stamp = image::load_from_memory(&watermark.image)
+        .map_err(|e| OptimizeError::InvalidOption(format!("watermark image is unreadable: {e}")))?
+        .to_rgba8();
+    if stamp.width() == 0 || stamp.height() == 0 || watermark.opacity == 0.0 {
+        return Ok(());
+    }
+
+    let (width, height) = rgba.dimensions();
+    let stamp_width = ((width as f32 * watermark.scale).round() as u32).max(1);
+    let stamp_height = ((stamp_width as u64 * stamp.height() as u64 + stamp.width() as u64 / 2)
+        / stamp.width() as u64)
+        .max(1) as u32;
+    let stamp = image::imageops::resize(&stamp, stamp_width, stamp_height, FilterType::Triangle);
+
+    let (x, y) = match watermark.placement {
+        WatermarkPlacement::TopLeft | WatermarkPlacement::Tiled => (0, 0),
+        WatermarkPlacement::TopRight => (width as i64 - stamp_width as i64, 0),
+        WatermarkPlacement::BottomLeft => (0, height as i64 - stamp_height as i64),
+        WatermarkPlacement::BottomRight => (
+            width as i64 - stamp_width as i64,
+            height as i64 - stamp_height as i64,
+        ),
+        WatermarkPlacement::Center => (
+            (width as i64 - stamp_width as i64) / 2,
+            (height as i64 - stamp_height as i64) / 2,
+        ),
+    };
+    let tiled = watermark.placement == WatermarkPlacement::Tiled;
+    for (px, py, pixel) in rgba.enumerate_pixels_mut() {
+        let (sx, sy) = (px as i64 - x, py as i64 - y);
+        let (sx, sy) = if tiled {
+            (
+                sx.rem_euclid(stamp_width as i64),
+                sy.rem_euclid(stamp_height as i64),
+            )
+        } else if (0..stamp_width as i64).contains(&sx) && (0..stamp_height as i64).contains(&sy) {
+            (sx, sy)
+        } else {
+            continue;
+        };
+        let mark = stamp.get_pixel(sx as u32, sy as u32).0;
+        let alpha = mark[3] as f32 / 255.0 * watermark.opacity;
+        for (c, m) in pixel.0.iter_mut().zip(mark).take(3) {
+            *c = (m as f32 * alpha + *c as f32 * (1.0 - alpha)).round() as u8;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use std::io::Cursor;
+
+    use gltf::json::Index;
+
+    use super::*;
+    use crate::{
+        glb::read_glb,
+        opt::{ImageFormat, encode_png, get_image_data, optimize_with},
+        options::OptimizeOptions,
+        reencode::ReencodeSkip,
+        testing::SyntheticGlb,
+    };
+
+    #[test]
+    fn watermarks_are_stamped_onto_base_color() {
+        let mut mark = Vec::new();
+        let black = [0, 0, 0, 255].repeat(16);
+        encode_png(&black, 4, 4, image::ColorType::Rgba8, &mut mark).unwrap();
+
+        let glb = SyntheticGlb {
+            textures: 1,
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let base_color = |placement: Option<WatermarkPlacement>, opacity| {
+            let options = OptimizeOptions {
+                image_format: Some(ImageFormat::Png),
+                // Would copy the small source PNG unless watermarking forces a re-encode
+                skip_reencode: ReencodeSkip {
+                    png_below: Some(u64::MAX),
+                    ..Default::default()
+                },
+                watermark: placement.map(|placement| Watermark {
+                    placement,
+                    opacity,
+                    ..Watermark::new(mark.clone())
+                }),
+                ..Default::default()
+            };
+            let out = optimize_with(&mut Cursor::new(&glb), &options).unwrap();
+            let (n_json, n_blob) = read_glb(&mut Cursor::new(out)).unwrap();
+            let data = get_image_data(&n_blob, &n_json, Index::new(0)).unwrap();
+            image::load_from_memory(data).unwrap().to_rgba8()
+        };
+
+        let plain = base_color(None, 1.0);
+        let stamped = base_color(Some(WatermarkPlacement::BottomRight), 1.0);
+        assert_eq!(stamped.dimensions(), (32, 32));
+        for (x, y, pixel) in stamped.enumerate_pixels() {
+            match x >= 24 && y >= 24 {
+                true => assert_eq!(pixel.0, [0, 0, 0, 255]),
+                false => assert_eq!(pixel, plain.get_pixel(x, y)),
+            }
+        }
+
+        let faint = base_color(Some(WatermarkPlacement::Tiled), 0.5);
+        for (pixel, plain) in faint.pixels().zip(plain.pixels()) {
+            let half = plain.0.map(|c| (c as f32 * 0.5).round() as u8);
+            assert_eq!(pixel.0[..3], half[..3]);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::{io::Write, ops::Range};
+
+use gltf::json::Root;
+
+use crate::error::OptimizeError;
+
+/// GLB file header: magic, version and total length
+const HEADER_LEN: usize = 12;
+/// Chunk header: length and type
+const CHUNK_HEADER_LEN: usize = 8;
+
+/// Assembles a JSON root and named binary segments into a GLB file
+///
+/// Segments are laid out in the order they are added, each starting at a multiple of its
+/// alignment within the BIN chunk, so buffer views can be pointed at the ranges
+/// [`segment`](GlbWriter::segment) returns before the file is written. Writing pads the JSON
+/// chunk with spaces and the BIN chunk with zeros to 4 bytes, sets `buffers[0].byteLength`
+/// (adding the buffer when there is none) and fails when a chunk or the file would not fit
+/// the format's 32-bit lengths.
+#[derive(Debug, Clone, Default)]
+pub struct GlbWriter {
+    bin: Vec<u8>,
+    segments: Vec<(String, Range<usize>)>,
+}
+
+impl GlbWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer whose BIN chunk starts out as `bin`, with no named segments
+    pub(crate) fn from_bin(bin: Vec<u8>) -> Self {
+        GlbWriter {
+            bin,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Append `data` at the next multiple of `alignment` bytes, returning its byte range
+    ///
+    /// Fails when `alignment` is 0 or `name` is already taken.
+    pub fn segment(
+        &mut self,
+        name: impl Into<String>,
+        data: &[u8],
+        alignment: usize,
+    ) -> Result<Range<usize>, OptimizeError> {
+        let name = name.into();
+        if alignment == 0 {
+            return Err(OptimizeError::InvalidOption(format!(
+                "segment {name} has an alignment of 0"
+            )));
+        }
+        if self.range(&name).is_some() {
+            return Err(OptimizeError::InvalidOption(format!(
+                "segment {name} was already added"
+            )));
+        }
+        let start = self.bin.len().next_multiple_of(alignment);
+        self.bin.resize(start, 0);
+        self.bin.extend_from_slice(data);
+        let range = start..self.bin.len();
+        self.segments.push((name, range.clone()));
+        Ok(range)
+    }
+
+    /// Byte range of a named segment in the BIN chunk
+    pub fn range(&self, name: &str) -> Option<Range<usize>> {
+        self.segments
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, range)| range.clone())
+    }
+
+    /// Names and byte ranges of every segment, in layout order
+    pub fn segments(&self) -> impl Iterator<Item = (&str, Range<usize>)> {
+        self.segments.iter().map(|(n, r)| (n.as_str(), r.clone()))
+    }
+
+    /// Bytes of the BIN chunk so far, unpadded
+    pub fn len(&self) -> usize {
+        self.bin.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bin.is_empty()
+    }
+
+    /// Serialize `json` and the segments into GLB bytes
+    pub fn write(self, json: &Root) -> Result<Vec<u8>, OptimizeError> {
+        let mut glb = Vec::new();
+        self.write_to(json, &mut glb)?;
+        Ok(glb)
+    }
+
+    /// Serialize `json` and the segments as a GLB into `writer`
+    ///
+    /// A document without buffers and with no segment data gets no BIN chunk.
+    pub fn write_to<W: Write>(self, json: &Root, mut writer: W) -> Result<(), OptimizeError> {
+        let mut bin = self.bin;
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let has_bin = !bin.is_empty() || !json.buffers.is_empty();
+
+        let mut json = json.clone();
+        if has_bin {
+            if let Some(buffer) = json.buffers.get_mut(0) {
+                buffer.byte_length = bin.len().into();
+            } else {
+                json.push(gltf::json::Buffer {
+                    byte_length: bin.len().into(),
+                    name: None,
+                    uri: None,
+                    extensions: None,
+                    extras: Default::default(),
+                });
+            }
+        }
+        let mut json_bytes = gltf::json::serialize::to_vec(&json)?;
+        json_bytes.resize(json_bytes.len().next_multiple_of(4), b' ');
+
+        let mut length = HEADER_LEN + CHUNK_HEADER_LEN + json_bytes.len();
+        if has_bin {
+            length += CHUNK_HEADER_LEN + bin.len();
+        }
+        let u32_len = |what: &'static str, len: usize| {
+            u32::try_from(len).map_err(|_| OptimizeError::TooLarge { what, len })
+        };
+        let length = u32_len("file", length)?;
+        let json_len = u32_len("JSON chunk", json_bytes.len())?;
+        let bin_len = u32_len("BIN chunk", bin.len())?;
+
+        writer.write_all(b"glTF")?;
+        writer.write_all(&2u32.to_le_bytes())?;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&json_len.to_le_bytes())?;
+        writer.write_all(b"JSON")?;
+        writer.write_all(&json_bytes)?;
+        if has_bin {
+            writer.write_all(&bin_len.to_le_bytes())?;
+            writer.write_all(b"BIN\0")?;
+            writer.write_all(&bin)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::glb::read_glb;
+
+    #[test]
+    fn glb_writer_aligns_named_segments() {
+        let mut glb = GlbWriter::new();
+        assert_eq!(
+            glb.segment("indices", &[0, 0, 1, 0, 2, 0], 4).unwrap(),
+            0..6
+        );
+        assert_eq!(glb.segment("pixels", &[7; 5], 16).unwrap(), 16..21);
+        assert!(matches!(
+            glb.segment("pixels", &[], 4),
+            Err(OptimizeError::InvalidOption(_))
+        ));
+        assert!(glb.segment("empty", &[], 0).is_err());
+        assert_eq!(glb.range("pixels"), Some(16..21));
+        let names: Vec<&str> = glb.segments().map(|(name, _)| name).collect();
+        assert_eq!(names, ["indices", "pixels"]);
+
+        let mut json = Root::default();
+        json.asset.generator = Some("segments".to_string());
+        let bytes = glb.write(&json).unwrap();
+        assert_eq!(bytes.len() % 4, 0);
+        assert_eq!(
+            u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize,
+            bytes.len()
+        );
+        gltf::Gltf::from_slice(&bytes).unwrap();
+        let (n_json, n_blob) = read_glb(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(n_json.buffers[0].byte_length.0, 24);
+        assert_eq!(&n_blob[16..21], &[7; 5]);
+        assert_eq!(n_blob[21..], [0; 3]);
+
+        // Nothing to store leaves the BIN chunk out
+        let bytes = GlbWriter::new().write(&json).unwrap();
+        assert!(
+            read_glb(&mut Cursor::new(&bytes))
+                .unwrap()
+                .0
+                .buffers
+                .is_empty()
+        );
+    }
+}